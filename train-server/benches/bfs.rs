@@ -0,0 +1,130 @@
+//! Benchmark of the BFS fallback's early exit.
+//!
+//! Searches a grid of stations, with trains along every row and column
+//! every quarter hour, from the top-left corner to the bottom-right. The
+//! destination's arrivals board is cut short, as Darwin's are, so the BFS
+//! fallback has work to do. Each configuration is searched repeatedly,
+//! reporting the mean time, the boards fetched, and the earliest arrival
+//! found, so the cost of exploring every level can be set against what
+//! stopping early gives up.
+//!
+//! Run with `cargo bench --bench bfs`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use train_server::domain::CallIndex;
+use train_server::planner::{Planner, SearchConfig, SearchRequest};
+use train_server::testing::{Network, NetworkBuilder, ServiceBuilder, crs};
+
+/// Stations along each side of the grid.
+const SIDE: u8 = 8;
+
+/// Searches timed for each configuration.
+const RUNS: u32 = 50;
+
+/// Code of the station at `row` and `col`, e.g. "GAB".
+fn station(row: u8, col: u8) -> String {
+    format!("G{}{}", (b'A' + row) as char, (b'A' + col) as char)
+}
+
+/// "HH:MM" for minutes past midnight.
+fn clock(mins: u32) -> String {
+    format!("{:02}:{:02}", mins / 60, mins % 60)
+}
+
+/// A train calling at `stops` from `start` (minutes past midnight), ten
+/// minutes apart with a minute at each.
+fn train(id: String, stops: &[String], start: u32) -> ServiceBuilder {
+    let last = stops.len() - 1;
+    stops
+        .iter()
+        .enumerate()
+        .fold(ServiceBuilder::new(id), |train, (i, stop)| {
+            let at = start + i as u32 * 11;
+            let train = train.call(stop);
+            match i {
+                0 => train.dep(&clock(at)),
+                i if i == last => train.arr(&clock(at)),
+                _ => train.arr(&clock(at)).dep(&clock(at + 1)),
+            }
+        })
+}
+
+fn grid() -> Network {
+    let mut network = NetworkBuilder::new().max_arrivals(6);
+    for line in 0..SIDE {
+        let row: Vec<String> = (0..SIDE).map(|col| station(line, col)).collect();
+        let column: Vec<String> = (0..SIDE).map(|row| station(row, line)).collect();
+        for start in (6 * 60..12 * 60).step_by(15) {
+            network = network
+                .service(train(format!("R{line}-{start}"), &row, start))
+                .service(train(format!("C{line}-{start}"), &column, start + 5));
+        }
+    }
+    network.build()
+}
+
+#[tokio::main]
+async fn main() {
+    let network = grid();
+    let row: Vec<String> = (0..SIDE).map(|col| station(0, col)).collect();
+    let current = train("ON".to_string(), &row, 8 * 60).build();
+    let request = SearchRequest::new(current, CallIndex(0), crs(&station(SIDE - 1, SIDE - 1)));
+
+    let exhaustive = SearchConfig {
+        max_changes: 3,
+        ..SearchConfig::default()
+    };
+    let configs = [
+        ("every level", exhaustive.clone()),
+        (
+            "3 within 30 min",
+            SearchConfig {
+                bfs_target_journeys: 3,
+                bfs_arrival_margin_mins: 30,
+                ..exhaustive.clone()
+            },
+        ),
+        (
+            "1 within 0 min",
+            SearchConfig {
+                bfs_target_journeys: 1,
+                bfs_arrival_margin_mins: 0,
+                ..exhaustive
+            },
+        ),
+    ];
+
+    println!(
+        "{:<16} {:>10} {:>8} {:>9} {:>9}",
+        "bfs early exit", "mean", "boards", "journeys", "earliest"
+    );
+    for (name, config) in &configs {
+        let planner = Planner::new(&network, network.walkable(), config);
+        let before = network.requests();
+        let mut elapsed = Duration::ZERO;
+        let mut last = None;
+        for _ in 0..RUNS {
+            let started = Instant::now();
+            let result = black_box(planner.search(&request).await.expect("search succeeds"));
+            elapsed += started.elapsed();
+            last = Some(result);
+        }
+        let result = last.expect("at least one run");
+        let earliest = result
+            .journeys
+            .iter()
+            .map(|j| j.arrival_time().time().format("%H:%M").to_string())
+            .min()
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<16} {:>10.2?} {:>8} {:>9} {:>9}",
+            name,
+            elapsed / RUNS,
+            (network.requests() - before) / RUNS as usize,
+            result.journeys.len(),
+            earliest
+        );
+    }
+}
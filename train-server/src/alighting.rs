@@ -0,0 +1,275 @@
+//! Where to alight when changing trains.
+//!
+//! Some stations have their exit or interchange at one end of the platform,
+//! so standing in the right part of the train saves minutes on a tight
+//! connection. This module keeps a table of per-station (and optionally
+//! per-platform) metadata, and turns it into hints like "alight near the
+//! front for the quickest interchange".
+//!
+//! Positions and door sides are relative to the direction of travel of a
+//! train arriving at the station. That is unambiguous at termini, where
+//! every train arrives heading for the buffers; at through stations, record
+//! metadata per platform so it applies to one direction only.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::domain::{Crs, Leg};
+
+/// Part of the train, relative to its direction of travel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrainPosition {
+    Front,
+    Middle,
+    Rear,
+}
+
+impl TrainPosition {
+    /// Lowercase name, as used in hints and the JSON API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrainPosition::Front => "front",
+            TrainPosition::Middle => "middle",
+            TrainPosition::Rear => "rear",
+        }
+    }
+}
+
+impl fmt::Display for TrainPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Side of the train the doors open on, facing the direction of travel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DoorSide {
+    Left,
+    Right,
+}
+
+impl DoorSide {
+    /// Lowercase name, as used in hints and the JSON API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DoorSide::Left => "left",
+            DoorSide::Right => "right",
+        }
+    }
+}
+
+impl fmt::Display for DoorSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What is known about alighting at a station or platform.
+///
+/// Every field is optional so partial knowledge can be recorded, and
+/// platform entries only need to override what differs from the station.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StationMetadata {
+    /// Where the exit and interchange are along the platform
+    pub exit: Option<TrainPosition>,
+
+    /// Which side the doors open on
+    pub door_side: Option<DoorSide>,
+}
+
+impl StationMetadata {
+    /// Metadata with nothing known.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set where the exit is along the platform.
+    pub fn with_exit(mut self, exit: TrainPosition) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    /// Set which side the doors open on.
+    pub fn with_door_side(mut self, door_side: DoorSide) -> Self {
+        self.door_side = Some(door_side);
+        self
+    }
+
+    /// Returns true if nothing is known.
+    pub fn is_empty(&self) -> bool {
+        self.exit.is_none() && self.door_side.is_none()
+    }
+
+    /// Fill in anything unknown here from `fallback`.
+    fn or(self, fallback: StationMetadata) -> Self {
+        Self {
+            exit: self.exit.or(fallback.exit),
+            door_side: self.door_side.or(fallback.door_side),
+        }
+    }
+
+    /// A short, human-readable hint for someone about to alight.
+    ///
+    /// Returns `None` if nothing is known.
+    pub fn hint(&self) -> Option<String> {
+        let exit = self
+            .exit
+            .map(|p| format!("Alight near the {p} for the quickest interchange."));
+        let doors = self.door_side.map(|s| format!("Doors open on the {s}."));
+        match (exit, doors) {
+            (Some(exit), Some(doors)) => Some(format!("{exit} {doors}")),
+            (exit, doors) => exit.or(doors),
+        }
+    }
+}
+
+/// Alighting metadata for stations, with optional per-platform overrides.
+#[derive(Debug, Clone, Default)]
+pub struct StationMetadataTable {
+    stations: HashMap<Crs, StationMetadata>,
+    platforms: HashMap<(Crs, String), StationMetadata>,
+}
+
+impl StationMetadataTable {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the metadata for a whole station, replacing any existing entry.
+    pub fn insert(&mut self, station: Crs, metadata: StationMetadata) {
+        self.stations.insert(station, metadata);
+    }
+
+    /// Set the metadata for one platform, replacing any existing entry.
+    ///
+    /// Fields left unset fall back to the station-wide entry.
+    pub fn insert_platform(&mut self, station: Crs, platform: &str, metadata: StationMetadata) {
+        self.platforms
+            .insert((station, platform.to_string()), metadata);
+    }
+
+    /// Look up what is known about alighting at a station and platform.
+    ///
+    /// Returns `None` if nothing is known.
+    pub fn get(&self, station: &Crs, platform: Option<&str>) -> Option<StationMetadata> {
+        let station_wide = self.stations.get(station).copied().unwrap_or_default();
+        let metadata = platform
+            .and_then(|p| self.platforms.get(&(*station, p.to_string())))
+            .map_or(station_wide, |m| m.or(station_wide));
+        (!metadata.is_empty()).then_some(metadata)
+    }
+
+    /// Look up what is known about alighting from a leg.
+    pub fn for_alighting(&self, leg: &Leg) -> Option<StationMetadata> {
+        self.get(leg.alight_station(), leg.alight_platform())
+    }
+
+    /// Returns the number of stations and platforms with an entry.
+    pub fn len(&self) -> usize {
+        self.stations.len() + self.platforms.len()
+    }
+
+    /// Returns true if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty() && self.platforms.is_empty()
+    }
+}
+
+/// Create the default metadata for London termini.
+///
+/// Trains arrive at a terminus heading for the buffers, and the concourse
+/// (with the Underground and other onward connections) is at that end, so
+/// the front of the train is always closest to the exit.
+pub fn london_metadata() -> StationMetadataTable {
+    let mut table = StationMetadataTable::new();
+    for crs in [
+        "CHX", "CST", "EUS", "FST", "KGX", "LST", "MYB", "PAD", "STP", "VIC", "WAT",
+    ] {
+        if let Ok(crs) = Crs::parse(crs) {
+            table.insert(crs, StationMetadata::new().with_exit(TrainPosition::Front));
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[test]
+    fn platform_overrides_station() {
+        let mut table = StationMetadataTable::new();
+        table.insert(
+            crs("RDG"),
+            StationMetadata::new()
+                .with_exit(TrainPosition::Middle)
+                .with_door_side(DoorSide::Left),
+        );
+        table.insert_platform(
+            crs("RDG"),
+            "7",
+            StationMetadata::new().with_exit(TrainPosition::Rear),
+        );
+
+        let platform_7 = table.get(&crs("RDG"), Some("7")).unwrap();
+        assert_eq!(platform_7.exit, Some(TrainPosition::Rear));
+        // Unset platform fields fall back to the station
+        assert_eq!(platform_7.door_side, Some(DoorSide::Left));
+
+        let platform_1 = table.get(&crs("RDG"), Some("1")).unwrap();
+        assert_eq!(platform_1.exit, Some(TrainPosition::Middle));
+
+        assert!(table.get(&crs("SWI"), None).is_none());
+    }
+
+    #[test]
+    fn platform_only_entry() {
+        let mut table = StationMetadataTable::new();
+        table.insert_platform(
+            crs("RDG"),
+            "7",
+            StationMetadata::new().with_door_side(DoorSide::Right),
+        );
+
+        assert!(table.get(&crs("RDG"), None).is_none());
+        assert_eq!(
+            table.get(&crs("RDG"), Some("7")).unwrap().door_side,
+            Some(DoorSide::Right)
+        );
+    }
+
+    #[test]
+    fn hint_text() {
+        assert_eq!(StationMetadata::new().hint(), None);
+        assert_eq!(
+            StationMetadata::new()
+                .with_exit(TrainPosition::Front)
+                .hint()
+                .unwrap(),
+            "Alight near the front for the quickest interchange."
+        );
+        assert_eq!(
+            StationMetadata::new()
+                .with_exit(TrainPosition::Rear)
+                .with_door_side(DoorSide::Left)
+                .hint()
+                .unwrap(),
+            "Alight near the rear for the quickest interchange. Doors open on the left."
+        );
+    }
+
+    #[test]
+    fn london_termini_exit_at_front() {
+        let table = london_metadata();
+        assert_eq!(
+            table.get(&crs("KGX"), Some("4")).unwrap().exit,
+            Some(TrainPosition::Front)
+        );
+        assert!(table.get(&crs("RDG"), None).is_none());
+    }
+}
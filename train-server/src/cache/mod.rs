@@ -6,6 +6,20 @@
 //!
 //! Time bucketing (5-minute buckets) bounds cache cardinality while ensuring
 //! reasonable freshness.
+//!
+//! Arrivals boards for popular destinations can be kept warm by a
+//! background task (see [`spawn_warmup`]).
+//!
+//! With the `redis` feature, a [`RedisCache`] can be added as a second level
+//! shared between server instances.
+
+#[cfg(feature = "redis")]
+mod redis;
+mod warmup;
+
+#[cfg(feature = "redis")]
+pub use redis::{RedisCache, SharedCacheError};
+pub use warmup::{WARMUP_PHASE, WarmupConfig, spawn as spawn_warmup, warm};
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,7 +27,10 @@ use std::time::Duration;
 use chrono::NaiveDate;
 use moka::future::Cache as MokaCache;
 
-use crate::darwin::{ConvertedService, DarwinClientImpl, DarwinError, ServiceDetails};
+use crate::darwin::{
+    BoardWindow, ConvertedService, DarwinClientImpl, DarwinError, DarwinMetrics,
+    InstrumentedDarwin, ServiceDetails,
+};
 use crate::domain::Crs;
 
 /// Board type: departures or arrivals.
@@ -23,7 +40,7 @@ enum BoardType {
     Arrivals,
 }
 
-/// Cache key for station boards: (station CRS, date, time bucket, time window, board type).
+/// Cache key for station boards: (station CRS, date the board starts, time bucket, time window, board type).
 /// Time bucket is minutes from midnight divided by bucket_mins.
 /// Time window is included because the API returns different data for different windows.
 /// Board type distinguishes arrivals from departures.
@@ -109,20 +126,173 @@ impl DarwinCache {
 /// Darwin client with caching.
 ///
 /// Wraps a `DarwinClientImpl` (real or mock) and caches departure board responses.
+/// Calls that miss the cache are logged and counted (see [`InstrumentedDarwin`]).
 pub struct CachedDarwinClient {
-    client: DarwinClientImpl,
+    client: InstrumentedDarwin,
     cache: DarwinCache,
+    /// TTL for boards written to the shared cache.
+    #[cfg(feature = "redis")]
+    ttl: Duration,
+    /// Second-level cache shared with other instances.
+    #[cfg(feature = "redis")]
+    shared: Option<RedisCache>,
 }
 
 impl CachedDarwinClient {
     /// Create a new cached client.
     pub fn new(client: DarwinClientImpl, cache_config: &CacheConfig) -> Self {
         Self {
-            client,
+            client: InstrumentedDarwin::new(client),
             cache: DarwinCache::new(cache_config),
+            #[cfg(feature = "redis")]
+            ttl: cache_config.ttl,
+            #[cfg(feature = "redis")]
+            shared: None,
         }
     }
 
+    /// Log one Darwin call in `every` (0 logs none); failures are always
+    /// logged, and every call is counted regardless.
+    pub fn with_call_log_sampling(mut self, every: u64) -> Self {
+        self.client = self.client.with_log_sampling(every);
+        self
+    }
+
+    /// Share cached boards with other instances through Redis.
+    #[cfg(feature = "redis")]
+    pub fn with_shared_cache(mut self, shared: RedisCache) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    /// Look up a board in the local cache, then the shared cache, then
+    /// fetch it and cache it in both.
+    async fn cached_board<F>(&self, key: BoardKey, fetch: F) -> Result<BoardEntry, DarwinError>
+    where
+        F: Future<Output = Result<Vec<ConvertedService>, DarwinError>>,
+    {
+        // Try cache first
+        if let Some(cached) = self.cache.get_board(&key).await {
+            return Ok(cached);
+        }
+
+        #[cfg(feature = "redis")]
+        let shared_key = {
+            let (crs, date, bucket, time_window, board_type) = key;
+            redis::board_key(
+                &crs,
+                date,
+                bucket,
+                time_window,
+                board_type == BoardType::Arrivals,
+            )
+        };
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared
+            && let Some(services) = shared.get_board(&shared_key).await
+        {
+            let entry = Arc::new(services);
+            self.cache.insert_board(key, entry.clone()).await;
+            return Ok(entry);
+        }
+
+        // Fetch from API
+        let services = fetch.await?;
+
+        // Wrap in Arc for sharing
+        let services: Vec<Arc<ConvertedService>> = services.into_iter().map(Arc::new).collect();
+        let entry = Arc::new(services);
+
+        // Cache and return
+        self.cache.insert_board(key, entry.clone()).await;
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            shared.put_board(&shared_key, &entry, self.ttl).await;
+        }
+
+        Ok(entry)
+    }
+
+    /// The key a board is cached under.
+    fn board_key(
+        &self,
+        crs: &Crs,
+        window: &BoardWindow,
+        current_mins: u16,
+        time_offset: i16,
+        time_window: u16,
+        board_type: BoardType,
+    ) -> BoardKey {
+        let bucket = self.cache.time_bucket(time_offset, current_mins);
+        (*crs, window.start().date(), bucket, time_window, board_type)
+    }
+
+    /// Whether `get_departures_with_details` would be answered from the
+    /// local cache, without a Darwin call.
+    pub fn has_departures(
+        &self,
+        crs: &Crs,
+        date: NaiveDate,
+        current_mins: u16,
+        time_offset: i16,
+        time_window: u16,
+    ) -> bool {
+        let window = BoardWindow::at(date, current_mins, time_offset, time_window);
+        let key = self.board_key(
+            crs,
+            &window,
+            current_mins,
+            time_offset,
+            time_window,
+            BoardType::Departures,
+        );
+        self.cache.boards.contains_key(&key)
+    }
+
+    /// Whether `get_arrivals_with_details` would be answered from the local
+    /// cache, without a Darwin call.
+    pub fn has_arrivals(
+        &self,
+        crs: &Crs,
+        date: NaiveDate,
+        current_mins: u16,
+        time_offset: i16,
+        time_window: u16,
+    ) -> bool {
+        let window = BoardWindow::at(date, current_mins, time_offset, time_window);
+        let key = self.board_key(
+            crs,
+            &window,
+            current_mins,
+            time_offset,
+            time_window,
+            BoardType::Arrivals,
+        );
+        self.cache.boards.contains_key(&key)
+    }
+
+    /// The departures board at `crs` if it's in the local cache; never
+    /// calls Darwin.
+    pub async fn cached_departures(
+        &self,
+        crs: &Crs,
+        date: NaiveDate,
+        current_mins: u16,
+        time_offset: i16,
+        time_window: u16,
+    ) -> Option<Arc<Vec<Arc<ConvertedService>>>> {
+        let window = BoardWindow::at(date, current_mins, time_offset, time_window);
+        let key = self.board_key(
+            crs,
+            &window,
+            current_mins,
+            time_offset,
+            time_window,
+            BoardType::Departures,
+        );
+        self.cache.get_board(&key).await
+    }
+
     /// Get departures with details, using cache if available.
     ///
     /// # Arguments
@@ -139,28 +309,21 @@ impl CachedDarwinClient {
         time_offset: i16,
         time_window: u16,
     ) -> Result<Arc<Vec<Arc<ConvertedService>>>, DarwinError> {
-        let bucket = self.cache.time_bucket(time_offset, current_mins);
-        let key = (*crs, date, bucket, time_window, BoardType::Departures);
-
-        // Try cache first
-        if let Some(cached) = self.cache.get_board(&key).await {
-            return Ok(cached);
-        }
-
-        // Fetch from API
-        let services = self
-            .client
-            .get_departures_with_details(crs, 150, time_offset, time_window, date)
-            .await?;
-
-        // Wrap in Arc for sharing
-        let services: Vec<Arc<ConvertedService>> = services.into_iter().map(Arc::new).collect();
-        let entry = Arc::new(services);
-
-        // Cache and return
-        self.cache.insert_board(key, entry.clone()).await;
+        let window = BoardWindow::at(date, current_mins, time_offset, time_window);
+        let key = self.board_key(
+            crs,
+            &window,
+            current_mins,
+            time_offset,
+            time_window,
+            BoardType::Departures,
+        );
 
-        Ok(entry)
+        self.cached_board(
+            key,
+            self.client.get_departures_with_details(crs, 150, window),
+        )
+        .await
     }
 
     /// Get arrivals with details, using cache if available.
@@ -174,28 +337,18 @@ impl CachedDarwinClient {
         time_offset: i16,
         time_window: u16,
     ) -> Result<Arc<Vec<Arc<ConvertedService>>>, DarwinError> {
-        let bucket = self.cache.time_bucket(time_offset, current_mins);
-        let key = (*crs, date, bucket, time_window, BoardType::Arrivals);
-
-        // Try cache first
-        if let Some(cached) = self.cache.get_board(&key).await {
-            return Ok(cached);
-        }
-
-        // Fetch from API
-        let services = self
-            .client
-            .get_arrivals_with_details(crs, 150, time_offset, time_window, date)
-            .await?;
-
-        // Wrap in Arc for sharing
-        let services: Vec<Arc<ConvertedService>> = services.into_iter().map(Arc::new).collect();
-        let entry = Arc::new(services);
-
-        // Cache and return
-        self.cache.insert_board(key, entry.clone()).await;
+        let window = BoardWindow::at(date, current_mins, time_offset, time_window);
+        let key = self.board_key(
+            crs,
+            &window,
+            current_mins,
+            time_offset,
+            time_window,
+            BoardType::Arrivals,
+        );
 
-        Ok(entry)
+        self.cached_board(key, self.client.get_arrivals_with_details(crs, 150, window))
+            .await
     }
 
     /// Get departures filtered to a specific destination.
@@ -223,9 +376,64 @@ impl CachedDarwinClient {
         Ok(filtered)
     }
 
+    /// Get departures from several stations (e.g. a station group),
+    /// interleaved by departure time.
+    ///
+    /// Boards are fetched concurrently. Each service's
+    /// `service_ref.board_crs` says which station it departs from. Stations
+    /// whose board can't be fetched are left out, unless all of them fail.
+    pub async fn get_merged_departures(
+        &self,
+        stations: &[Crs],
+        date: NaiveDate,
+        current_mins: u16,
+        time_offset: i16,
+        time_window: u16,
+    ) -> Result<Vec<Arc<ConvertedService>>, DarwinError> {
+        let boards = futures::future::join_all(stations.iter().map(|crs| {
+            self.get_departures_with_details(crs, date, current_mins, time_offset, time_window)
+        }))
+        .await;
+
+        let mut merged = Vec::new();
+        let mut fetched = 0;
+        let mut error = None;
+        for board in boards {
+            match board {
+                Ok(board) => {
+                    fetched += 1;
+                    merged.extend(board.iter().cloned());
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        if fetched == 0
+            && let Some(e) = error
+        {
+            return Err(e);
+        }
+
+        // Stable, so services at the same time keep station order
+        merged.sort_by_key(|s| s.candidate.departure_time());
+        Ok(merged)
+    }
+
     /// Access the underlying client for operations that bypass cache.
     pub fn client(&self) -> &DarwinClientImpl {
-        &self.client
+        self.client.inner()
+    }
+
+    /// The messages on `station`'s most recent board, as plain text.
+    ///
+    /// Only boards this process fetched from Darwin count; boards served
+    /// from the shared cache carry no messages.
+    pub fn station_messages(&self, station: &Crs) -> Vec<String> {
+        self.client().station_messages().get(station)
+    }
+
+    /// Totals of the calls that reached Darwin.
+    pub fn call_metrics(&self) -> &DarwinMetrics {
+        self.client.metrics()
     }
 
     /// Get full service details by service ID.
@@ -290,6 +498,42 @@ mod tests {
         let cache = DarwinCache::new(&config);
         assert_eq!(cache.entry_count(), 0);
     }
+
+    #[tokio::test]
+    async fn merged_departures_interleave_by_time() {
+        use crate::darwin::MockDarwinClient;
+
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let darwin = CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default());
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let pad = Crs::parse("PAD").unwrap();
+        let rdg = Crs::parse("RDG").unwrap();
+
+        let merged = darwin
+            .get_merged_departures(&[pad, rdg], date, 14 * 60, 0, 120)
+            .await
+            .unwrap();
+        let pad_only = darwin
+            .get_departures_with_details(&pad, date, 14 * 60, 0, 120)
+            .await
+            .unwrap();
+        let rdg_only = darwin
+            .get_departures_with_details(&rdg, date, 14 * 60, 0, 120)
+            .await
+            .unwrap();
+
+        assert_eq!(merged.len(), pad_only.len() + rdg_only.len());
+        assert!(
+            merged
+                .windows(2)
+                .all(|w| w[0].candidate.departure_time() <= w[1].candidate.departure_time())
+        );
+        let stations: std::collections::HashSet<_> = merged
+            .iter()
+            .map(|s| s.service.service_ref.board_crs)
+            .collect();
+        assert_eq!(stations.len(), 2, "services are tagged with their station");
+    }
 }
 
 /// Tests for fixed cache behavior.
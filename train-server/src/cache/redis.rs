@@ -0,0 +1,594 @@
+//! Redis-backed cache shared between server instances.
+//!
+//! Behind a load balancer each instance has its own in-memory cache, so
+//! without sharing every instance fetches the same boards from Darwin. This
+//! second-level cache sits behind the in-memory one: a local miss checks
+//! Redis before calling Darwin, and fresh boards are written to both.
+//! Planned journeys are shared too, so a follow-up request by journey ID can
+//! land on any instance.
+//!
+//! The shared cache is best-effort. Redis errors are logged and treated as
+//! misses; they never fail a request.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::darwin::ConvertedService;
+use crate::domain::{
+    AtocCode, Call, CallIndex, Coach, Crs, Formation, Headcode, Journey, Leg, Portion, RailTime,
+    Segment, Service, ServiceCandidate, ServiceRef, ServiceUid, TrainEnd, Walk,
+};
+use crate::error::{Classify, ErrorKind};
+
+/// Errors setting up the shared cache.
+#[derive(Debug, thiserror::Error)]
+pub enum SharedCacheError {
+    /// Could not connect to Redis
+    #[error("failed to connect to Redis: {message}")]
+    Connect { message: String },
+}
+
+impl Classify for SharedCacheError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SharedCacheError::Connect { .. } => ErrorKind::Transient,
+        }
+    }
+}
+
+/// Key prefix, so the cache can share a Redis database with other data.
+const DEFAULT_PREFIX: &str = "train-planner:";
+
+/// Redis cache for boards and journeys, shared between instances.
+#[derive(Clone)]
+pub struct RedisCache {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisCache {
+    /// Connect to Redis at the given URL (e.g. `redis://127.0.0.1/`).
+    pub async fn connect(url: &str) -> Result<Self, SharedCacheError> {
+        let connect_err = |e: redis::RedisError| SharedCacheError::Connect {
+            message: e.to_string(),
+        };
+        let client = redis::Client::open(url).map_err(connect_err)?;
+        let conn = ConnectionManager::new(client).await.map_err(connect_err)?;
+        Ok(Self {
+            conn,
+            prefix: DEFAULT_PREFIX.to_string(),
+        })
+    }
+
+    /// Set the prefix for all keys.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Look up a cached board.
+    pub async fn get_board(&self, key: &str) -> Option<Vec<Arc<ConvertedService>>> {
+        let stored: Vec<StoredBoardService> = self.get(&format!("board:{key}")).await?;
+        let services: Option<Vec<_>> = stored
+            .into_iter()
+            .map(|s| s.restore().map(Arc::new))
+            .collect();
+        if services.is_none() {
+            warn!(key, "Discarding unreadable board from shared cache");
+        }
+        services
+    }
+
+    /// Cache a board for `ttl`.
+    pub async fn put_board(&self, key: &str, board: &[Arc<ConvertedService>], ttl: Duration) {
+        let stored: Vec<_> = board.iter().map(|s| StoredBoardService::new(s)).collect();
+        self.put(&format!("board:{key}"), &stored, ttl).await;
+    }
+
+    /// Look up a stored journey by ID.
+    pub async fn get_journey(&self, id: &str) -> Option<Journey> {
+        let stored: StoredJourney = self.get(&format!("journey:{id}")).await?;
+        let journey = stored.restore();
+        if journey.is_none() {
+            warn!(id, "Discarding unreadable journey from shared cache");
+        }
+        journey
+    }
+
+    /// Store a journey under its ID for `ttl`.
+    pub async fn put_journey(&self, id: &str, journey: &Journey, ttl: Duration) {
+        self.put(&format!("journey:{id}"), &StoredJourney::new(journey), ttl)
+            .await;
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
+        let key = format!("{}{}", self.prefix, key);
+        let mut conn = self.conn.clone();
+        let json: Option<String> = match conn.get(&key).await {
+            Ok(json) => json,
+            Err(e) => {
+                warn!(%key, error = %e, "Shared cache read failed");
+                return None;
+            }
+        };
+        serde_json::from_str(&json?)
+            .inspect_err(|e| warn!(%key, error = %e, "Shared cache entry is not valid JSON"))
+            .ok()
+    }
+
+    async fn put<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) {
+        let key = format!("{}{}", self.prefix, key);
+        let Ok(json) = serde_json::to_string(value) else {
+            return;
+        };
+        let mut conn = self.conn.clone();
+        let result: Result<(), _> = conn.set_ex(&key, json, ttl.as_secs().max(1)).await;
+        if let Err(e) = result {
+            warn!(%key, error = %e, "Shared cache write failed");
+        }
+    }
+}
+
+// Serialized forms. Most domain types don't implement serde, so entries
+// are stored as plain strings (times as RFC 3339, via `RailTime`'s own
+// serde support) and re-validated when read back; an entry that no longer
+// parses (e.g. written by a different version) is a miss.
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCall {
+    station: String,
+    station_name: String,
+    platform: Option<String>,
+    booked_arrival: Option<RailTime>,
+    booked_departure: Option<RailTime>,
+    realtime_arrival: Option<RailTime>,
+    realtime_departure: Option<RailTime>,
+    is_cancelled: bool,
+    #[serde(default)]
+    is_request_stop: bool,
+    #[serde(default)]
+    is_set_down_only: bool,
+    #[serde(default)]
+    is_pick_up_only: bool,
+    #[serde(default)]
+    delay_reason: Option<String>,
+    #[serde(default)]
+    cancel_reason: Option<String>,
+}
+
+impl StoredCall {
+    fn new(call: &Call) -> Self {
+        Self {
+            station: call.station.as_str().to_string(),
+            station_name: call.station_name.clone(),
+            platform: call.platform.clone(),
+            booked_arrival: call.booked_arrival,
+            booked_departure: call.booked_departure,
+            realtime_arrival: call.realtime_arrival,
+            realtime_departure: call.realtime_departure,
+            is_cancelled: call.is_cancelled,
+            is_request_stop: call.is_request_stop,
+            is_set_down_only: call.is_set_down_only,
+            is_pick_up_only: call.is_pick_up_only,
+            delay_reason: call.delay_reason.clone(),
+            cancel_reason: call.cancel_reason.clone(),
+        }
+    }
+
+    fn restore(&self) -> Option<Call> {
+        let mut call = Call::new(Crs::parse(&self.station).ok()?, self.station_name.clone());
+        call.platform = self.platform.clone();
+        call.booked_arrival = self.booked_arrival;
+        call.booked_departure = self.booked_departure;
+        call.realtime_arrival = self.realtime_arrival;
+        call.realtime_departure = self.realtime_departure;
+        call.is_cancelled = self.is_cancelled;
+        call.is_request_stop = self.is_request_stop;
+        call.is_set_down_only = self.is_set_down_only;
+        call.is_pick_up_only = self.is_pick_up_only;
+        call.delay_reason = self.delay_reason.clone();
+        call.cancel_reason = self.cancel_reason.clone();
+        Some(call)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredService {
+    darwin_id: String,
+    board_crs: String,
+    headcode: Option<String>,
+    operator: String,
+    operator_code: Option<String>,
+    calls: Vec<StoredCall>,
+    board_station_idx: usize,
+    #[serde(default)]
+    formation: Option<StoredFormation>,
+}
+
+impl StoredService {
+    fn new(service: &Service) -> Self {
+        Self {
+            darwin_id: service.service_ref.darwin_id.clone(),
+            board_crs: service.service_ref.board_crs.as_str().to_string(),
+            headcode: service.headcode.map(|h| h.as_str().to_string()),
+            operator: service.operator.clone(),
+            operator_code: service.operator_code.map(|c| c.as_str().to_string()),
+            calls: service.calls.iter().map(StoredCall::new).collect(),
+            board_station_idx: service.board_station_idx.0,
+            formation: service.formation.as_ref().map(StoredFormation::new),
+        }
+    }
+
+    fn restore(&self) -> Option<Service> {
+        Some(Service {
+            service_ref: ServiceRef::new(self.darwin_id.clone(), Crs::parse(&self.board_crs).ok()?),
+            headcode: restore_headcode(&self.headcode)?,
+            operator: self.operator.clone(),
+            operator_code: restore_operator_code(&self.operator_code)?,
+            calls: self
+                .calls
+                .iter()
+                .map(StoredCall::restore)
+                .collect::<Option<_>>()?,
+            board_station_idx: CallIndex(self.board_station_idx),
+            formation: match &self.formation {
+                Some(f) => Some(f.restore()?),
+                None => None,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredFormation {
+    /// (number, first class) from the front
+    coaches: Vec<(Option<String>, bool)>,
+    length: Option<usize>,
+    portions: Vec<StoredPortion>,
+    /// Whether the service's calls are for the rear of the train
+    #[serde(default)]
+    rear: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredPortion {
+    divides_at: String,
+    destination: String,
+    destination_name: String,
+    remaining_coaches: Option<usize>,
+    #[serde(default)]
+    calls: Vec<StoredCall>,
+}
+
+impl StoredFormation {
+    fn new(formation: &Formation) -> Self {
+        Self {
+            coaches: formation
+                .coaches
+                .iter()
+                .map(|c| (c.number.clone(), c.first_class))
+                .collect(),
+            length: formation.length,
+            portions: formation
+                .portions
+                .iter()
+                .map(|p| StoredPortion {
+                    divides_at: p.divides_at.as_str().to_string(),
+                    destination: p.destination.as_str().to_string(),
+                    destination_name: p.destination_name.clone(),
+                    remaining_coaches: p.remaining_coaches,
+                    calls: p.calls.iter().map(StoredCall::new).collect(),
+                })
+                .collect(),
+            rear: formation.end == TrainEnd::Rear,
+        }
+    }
+
+    fn restore(&self) -> Option<Formation> {
+        Some(Formation {
+            coaches: self
+                .coaches
+                .iter()
+                .map(|(number, first_class)| Coach {
+                    number: number.clone(),
+                    first_class: *first_class,
+                })
+                .collect(),
+            length: self.length,
+            portions: self
+                .portions
+                .iter()
+                .map(|p| {
+                    Some(Portion {
+                        divides_at: Crs::parse(&p.divides_at).ok()?,
+                        destination: Crs::parse(&p.destination).ok()?,
+                        destination_name: p.destination_name.clone(),
+                        remaining_coaches: p.remaining_coaches,
+                        calls: p
+                            .calls
+                            .iter()
+                            .map(StoredCall::restore)
+                            .collect::<Option<_>>()?,
+                    })
+                })
+                .collect::<Option<_>>()?,
+            end: if self.rear {
+                TrainEnd::Rear
+            } else {
+                TrainEnd::Front
+            },
+        })
+    }
+}
+
+fn restore_headcode(s: &Option<String>) -> Option<Option<Headcode>> {
+    match s {
+        Some(s) => Headcode::parse(s).map(Some),
+        None => Some(None),
+    }
+}
+
+fn restore_operator_code(s: &Option<String>) -> Option<Option<AtocCode>> {
+    match s {
+        Some(s) => AtocCode::parse(s).ok().map(Some),
+        None => Some(None),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCandidate {
+    darwin_id: String,
+    board_crs: String,
+    headcode: Option<String>,
+    scheduled_departure: RailTime,
+    expected_departure: Option<RailTime>,
+    destination: String,
+    destination_crs: Option<String>,
+    operator: String,
+    operator_code: Option<String>,
+    platform: Option<String>,
+    is_cancelled: bool,
+}
+
+impl StoredCandidate {
+    fn new(candidate: &ServiceCandidate) -> Self {
+        Self {
+            darwin_id: candidate.service_ref.darwin_id.clone(),
+            board_crs: candidate.service_ref.board_crs.as_str().to_string(),
+            headcode: candidate.headcode.map(|h| h.as_str().to_string()),
+            scheduled_departure: candidate.scheduled_departure,
+            expected_departure: candidate.expected_departure,
+            destination: candidate.destination.clone(),
+            destination_crs: candidate.destination_crs.map(|c| c.as_str().to_string()),
+            operator: candidate.operator.clone(),
+            operator_code: candidate.operator_code.map(|c| c.as_str().to_string()),
+            platform: candidate.platform.clone(),
+            is_cancelled: candidate.is_cancelled,
+        }
+    }
+
+    fn restore(&self) -> Option<ServiceCandidate> {
+        let destination_crs = match &self.destination_crs {
+            Some(c) => Some(Crs::parse(c).ok()?),
+            None => None,
+        };
+        Some(ServiceCandidate {
+            service_ref: ServiceRef::new(self.darwin_id.clone(), Crs::parse(&self.board_crs).ok()?),
+            headcode: restore_headcode(&self.headcode)?,
+            scheduled_departure: self.scheduled_departure,
+            expected_departure: self.expected_departure,
+            destination: self.destination.clone(),
+            destination_crs,
+            operator: self.operator.clone(),
+            operator_code: restore_operator_code(&self.operator_code)?,
+            platform: self.platform.clone(),
+            is_cancelled: self.is_cancelled,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredBoardService {
+    candidate: StoredCandidate,
+    service: StoredService,
+    uid: Option<String>,
+    coaches: Option<usize>,
+}
+
+impl StoredBoardService {
+    fn new(converted: &ConvertedService) -> Self {
+        Self {
+            candidate: StoredCandidate::new(&converted.candidate),
+            service: StoredService::new(&converted.service),
+            uid: converted.uid.as_ref().map(|u| u.as_str().to_string()),
+            coaches: converted.coaches,
+        }
+    }
+
+    fn restore(self) -> Option<ConvertedService> {
+        let uid = match self.uid {
+            Some(u) => Some(ServiceUid::new(u).ok()?),
+            None => None,
+        };
+        Some(ConvertedService {
+            candidate: self.candidate.restore()?,
+            service: self.service.restore()?,
+            uid,
+            coaches: self.coaches,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum StoredSegment {
+    Train {
+        service: StoredService,
+        board_idx: usize,
+        alight_idx: usize,
+    },
+    Walk {
+        from: String,
+        to: String,
+        minutes: i64,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredJourney {
+    segments: Vec<StoredSegment>,
+}
+
+impl StoredJourney {
+    fn new(journey: &Journey) -> Self {
+        let segments = journey
+            .segments()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Train(leg) => StoredSegment::Train {
+                    service: StoredService::new(leg.service()),
+                    board_idx: leg.board_idx().0,
+                    alight_idx: leg.alight_idx().0,
+                },
+                Segment::Walk(walk) => StoredSegment::Walk {
+                    from: walk.from.as_str().to_string(),
+                    to: walk.to.as_str().to_string(),
+                    minutes: walk.duration.num_minutes(),
+                },
+            })
+            .collect();
+        Self { segments }
+    }
+
+    fn restore(&self) -> Option<Journey> {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                StoredSegment::Train {
+                    service,
+                    board_idx,
+                    alight_idx,
+                } => Leg::new(
+                    Arc::new(service.restore()?),
+                    CallIndex(*board_idx),
+                    CallIndex(*alight_idx),
+                )
+                .ok()
+                .map(Segment::Train),
+                StoredSegment::Walk { from, to, minutes } => Some(Segment::Walk(Walk::new(
+                    Crs::parse(from).ok()?,
+                    Crs::parse(to).ok()?,
+                    chrono::Duration::minutes(*minutes),
+                ))),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // Watched journeys are stored as refreshed, missed connections and all
+        Journey::new_allowing_missed(segments).ok()
+    }
+}
+
+/// Build the shared cache key for a board.
+pub(super) fn board_key(
+    crs: &Crs,
+    date: NaiveDate,
+    bucket: u16,
+    time_window: u16,
+    arrivals: bool,
+) -> String {
+    let kind = if arrivals { "arr" } else { "dep" };
+    format!("{}:{date}:{bucket}:{time_window}:{kind}", crs.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::darwin::{BoardWindow, MockDarwinClient};
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    async fn mock_board() -> Vec<ConvertedService> {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let crs = mock.available_stations().await[0];
+        mock.get_departures_with_details(&crs, 150, BoardWindow::at(date(), 10 * 60, 0, 120))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn board_roundtrips_through_json() {
+        let board = mock_board().await;
+        assert!(!board.is_empty());
+
+        let stored: Vec<_> = board.iter().map(StoredBoardService::new).collect();
+        let json = serde_json::to_string(&stored).unwrap();
+        let restored: Vec<StoredBoardService> = serde_json::from_str(&json).unwrap();
+
+        for (original, restored) in board.iter().zip(restored) {
+            let restored = restored.restore().unwrap();
+            assert_eq!(restored.service.calls, original.service.calls);
+            assert_eq!(restored.service.headcode, original.service.headcode);
+            assert_eq!(restored.service.formation, original.service.formation);
+            assert_eq!(
+                restored.candidate.scheduled_departure,
+                original.candidate.scheduled_departure
+            );
+            assert_eq!(restored.uid, original.uid);
+        }
+    }
+
+    #[tokio::test]
+    async fn journey_roundtrips_through_json() {
+        let board = mock_board().await;
+        let leg = board
+            .iter()
+            .find_map(|s| {
+                let service = Arc::new(s.service.clone());
+                let last = CallIndex(service.calls.len() - 1);
+                Leg::new(service, s.service.board_station_idx, last).ok()
+            })
+            .unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+
+        let json = serde_json::to_string(&StoredJourney::new(&journey)).unwrap();
+        let stored: StoredJourney = serde_json::from_str(&json).unwrap();
+        let restored = stored.restore().unwrap();
+
+        assert_eq!(restored.fingerprint(), journey.fingerprint());
+        assert_eq!(restored.arrival_time(), journey.arrival_time());
+    }
+
+    #[test]
+    fn invalid_entries_are_misses() {
+        let call = StoredCall {
+            station: "??".to_string(),
+            station_name: "Nowhere".to_string(),
+            platform: None,
+            booked_arrival: None,
+            booked_departure: None,
+            realtime_arrival: None,
+            realtime_departure: None,
+            is_cancelled: false,
+            is_request_stop: false,
+            is_set_down_only: false,
+            is_pick_up_only: false,
+            delay_reason: None,
+            cancel_reason: None,
+        };
+        assert!(call.restore().is_none());
+
+        // Times are checked as the entry is read
+        let json = r#"{"station":"PAD","station_name":"London Paddington","platform":null,
+            "booked_arrival":null,"booked_departure":"not a time","realtime_arrival":null,
+            "realtime_departure":null,"is_cancelled":false}"#;
+        assert!(serde_json::from_str::<StoredCall>(json).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Keeping popular destinations' arrivals boards warm.
+//!
+//! Every journey search starts from the destination's arrivals board, so
+//! the first search to a busy station after the cache expires pays for a
+//! Darwin round trip. [`spawn`] fetches the boards of a list of popular
+//! destinations as the server starts, then again every interval, so that
+//! searches to them find their board already cached. Boards are fetched
+//! with the same window searches ask for from "now", and the calls are
+//! attributed to the `warmup` phase on `/metrics`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Timelike};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::clock::Clock;
+use crate::domain::{Crs, InvalidCrs};
+use crate::planner::phase::in_phase;
+
+use super::{CacheConfig, CachedDarwinClient};
+
+/// Phase the warm-up's Darwin calls are attributed to.
+pub const WARMUP_PHASE: &str = "warmup";
+
+/// Destinations warmed by default: the London termini and the busiest
+/// stations in other major cities.
+const DEFAULT_STATIONS: [&str; 18] = [
+    "CHX", "CST", "EUS", "FST", "KGX", "LBG", "LST", "MYB", "PAD", "STP", "VIC", "WAT", "BHM",
+    "BRI", "EDB", "GLC", "LDS", "MAN",
+];
+
+/// Which arrivals boards to keep warm, and how often to fetch them.
+#[derive(Debug, Clone)]
+pub struct WarmupConfig {
+    /// Stations whose arrivals boards are fetched
+    pub stations: Vec<Crs>,
+
+    /// Time between rounds of fetches
+    pub interval: Duration,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            stations: DEFAULT_STATIONS
+                .iter()
+                .map(|s| Crs::parse(s).expect("default warm-up stations are valid"))
+                .collect(),
+            // Boards are refetched as they expire from the cache
+            interval: CacheConfig::default().ttl,
+        }
+    }
+}
+
+impl WarmupConfig {
+    /// Replace the stations with a comma-separated list of CRS codes, such
+    /// as `PAD,KGX,MAN`. An empty list disables the warm-up.
+    pub fn parse_stations(mut self, spec: &str) -> Result<Self, InvalidCrs> {
+        self.stations = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Crs::parse_normalized)
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Set the time between rounds of fetches.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns true if there are no stations to warm.
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+}
+
+/// Fetch the arrivals board of each station as of `now`, caching them.
+///
+/// Returns how many boards were fetched (or were already cached). Failures
+/// are logged and don't stop the other stations being fetched.
+pub async fn warm(darwin: &CachedDarwinClient, stations: &[Crs], now: NaiveDateTime) -> usize {
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+
+    let fetches = stations.iter().map(|station| async move {
+        let result = darwin
+            .get_arrivals_with_details(station, date, current_mins, 0, 120)
+            .await;
+        if let Err(e) = &result {
+            warn!(station = %station, error = %e, "Failed to warm arrivals board");
+        }
+        result.is_ok()
+    });
+    let warmed = in_phase(WARMUP_PHASE, futures::future::join_all(fetches))
+        .await
+        .into_iter()
+        .filter(|ok| *ok)
+        .count();
+
+    debug!(warmed, stations = stations.len(), "Warmed arrivals boards");
+    warmed
+}
+
+/// Warm the configured boards now, then again every interval.
+pub fn spawn(
+    darwin: Arc<CachedDarwinClient>,
+    clock: Clock,
+    config: WarmupConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            warm(&darwin, &config.stations, clock.now()).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient, Operation};
+    use chrono::NaiveDate;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, 3)
+            .unwrap()
+            .and_hms_opt(14, 0, 0)
+            .unwrap()
+    }
+
+    fn darwin() -> CachedDarwinClient {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default())
+    }
+
+    #[test]
+    fn default_config_warms_london_termini() {
+        let config = WarmupConfig::default();
+        assert!(config.stations.contains(&crs("PAD")));
+        assert!(config.stations.contains(&crs("KGX")));
+        assert_eq!(config.interval, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn parse_stations_replaces_defaults() {
+        let config = WarmupConfig::default()
+            .parse_stations(" pad, MAN ,,")
+            .unwrap();
+        assert_eq!(config.stations, vec![crs("PAD"), crs("MAN")]);
+
+        assert!(
+            WarmupConfig::default()
+                .parse_stations("")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(WarmupConfig::default().parse_stations("PAD,XX").is_err());
+    }
+
+    #[tokio::test]
+    async fn warmed_board_serves_later_search() {
+        let darwin = darwin();
+        let warmed = warm(&darwin, &[crs("PAD"), crs("RDG")], now()).await;
+        assert_eq!(warmed, 2);
+
+        let metrics = darwin.call_metrics();
+        assert_eq!(metrics.totals(Operation::Arrivals, WARMUP_PHASE).calls, 2);
+
+        // A search a few minutes later finds the board in the cache
+        darwin
+            .get_arrivals_with_details(&crs("PAD"), now().date(), 14 * 60 + 3, 0, 120)
+            .await
+            .unwrap();
+        let calls = [WARMUP_PHASE, crate::planner::phase::UNATTRIBUTED_PHASE]
+            .map(|phase| metrics.totals(Operation::Arrivals, phase).calls);
+        assert_eq!(calls, [2, 0]);
+    }
+}
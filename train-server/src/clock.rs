@@ -0,0 +1,89 @@
+//! Where the web layer gets the time from.
+//!
+//! Boards, searches and session tracking all work relative to "now". In
+//! production that is the wall clock; in the offline demo it is a moment on
+//! the fixture day, so the bundled timetable always has trains to show.
+
+use std::time::Instant;
+
+use chrono::{Duration, Local, NaiveDateTime};
+
+/// A source of the current UK local time.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Clock {
+    /// The system clock.
+    #[default]
+    System,
+
+    /// A fixed start time, advancing in step with real time from when the
+    /// clock was created.
+    Pinned {
+        /// The time when the clock was created
+        start: NaiveDateTime,
+        /// When the clock was created
+        created: Instant,
+    },
+
+    /// A time that never moves.
+    Frozen(NaiveDateTime),
+}
+
+impl Clock {
+    /// The system clock.
+    pub fn system() -> Self {
+        Self::System
+    }
+
+    /// A clock reading `start` now and running on from there.
+    pub fn pinned(start: NaiveDateTime) -> Self {
+        Self::Pinned {
+            start,
+            created: Instant::now(),
+        }
+    }
+
+    /// A clock that always reads `at`.
+    pub fn frozen(at: NaiveDateTime) -> Self {
+        Self::Frozen(at)
+    }
+
+    /// The current time.
+    pub fn now(&self) -> NaiveDateTime {
+        match self {
+            Self::System => Local::now().naive_local(),
+            Self::Pinned { start, created } => {
+                let elapsed = Duration::from_std(created.elapsed()).unwrap_or_default();
+                *start + elapsed
+            }
+            Self::Frozen(at) => *at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 15)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn frozen_clock_never_moves() {
+        let clock = Clock::frozen(at(8, 30));
+        assert_eq!(clock.now(), at(8, 30));
+        assert_eq!(clock.now(), at(8, 30));
+    }
+
+    #[test]
+    fn pinned_clock_runs_from_its_start() {
+        let clock = Clock::pinned(at(8, 30));
+        let now = clock.now();
+        assert!(now >= at(8, 30));
+        assert!(now < at(8, 31));
+    }
+}
@@ -4,17 +4,21 @@
 //! Handles authentication, rate limiting, and conversion to domain types.
 
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use chrono::NaiveDate;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::header::HeaderValue;
 use tokio::sync::Semaphore;
 use tracing::{debug, info, instrument, trace, warn};
 
 use crate::domain::Crs;
 
-use super::convert::{ConvertedService, convert_station_board};
+use super::convert::{ConversionMode, ConvertedService, convert_station_board_report};
 use super::error::DarwinError;
+use super::instrument::record_response_bytes;
+use super::messages::StationMessages;
+use super::request::{BoardParams, BoardWindow};
 use super::types::{ServiceDetails, StationBoardWithDetails};
 
 /// Default base URL for Darwin LDB departures API.
@@ -25,9 +29,51 @@ const DEFAULT_DEPARTURES_URL: &str =
 /// This is a separate product on Rail Data Marketplace.
 const DEFAULT_ARRIVALS_URL: &str = "https://api1.raildata.org.uk/1010-live-arrival-board-arr/LDBWS";
 
+/// Default base URL for the Darwin LDB staff API (LDBSVWS).
+/// Another separate product; richer data (UIDs, formations, arrival and
+/// departure times at every calling point) for approved consumers.
+const DEFAULT_STAFF_URL: &str =
+    "https://api1.raildata.org.uk/1010-live-departure-board---staff-version1_0/LDBSVWS";
+
+/// Default wait before trying staff credentials again after a rejection.
+const DEFAULT_STAFF_RETRY: Duration = Duration::from_secs(60);
+
+/// Longest wait between attempts with rejected staff credentials; the
+/// wait doubles after each consecutive rejection up to this.
+const MAX_STAFF_RETRY: Duration = Duration::from_secs(30 * 60);
+
 /// Default maximum concurrent requests.
 const DEFAULT_MAX_CONCURRENT: usize = 5;
 
+/// Header carrying the Rail Data Marketplace API key.
+pub(crate) const API_KEY_HEADER: &str = "x-apikey";
+
+/// Which HTTP version to speak to the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/2 where the server offers it, else HTTP/1.1
+    #[default]
+    Auto,
+
+    /// HTTP/1.1 only
+    Http1,
+
+    /// HTTP/2 only, without negotiating it first
+    Http2,
+}
+
+impl HttpVersion {
+    /// Parse `auto`, `1` or `2`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "auto" => Some(Self::Auto),
+            "1" | "1.1" => Some(Self::Http1),
+            "2" => Some(Self::Http2),
+            _ => None,
+        }
+    }
+}
+
 /// Configuration for the Darwin client.
 #[derive(Debug, Clone)]
 pub struct DarwinConfig {
@@ -37,12 +83,30 @@ pub struct DarwinConfig {
     pub arrivals_api_key: Option<String>,
     /// Base URL for departures API
     pub departures_url: String,
+    /// API key for the staff API (None = use the public API only)
+    pub staff_api_key: Option<String>,
+    /// Base URL for the staff API
+    pub staff_url: String,
+    /// How long to use the public API after the staff API rejects our
+    /// credentials before trying them again
+    pub staff_retry: Duration,
     /// Maximum concurrent requests
     pub max_concurrent: usize,
-    /// Request timeout in seconds
+    /// Request timeout in seconds, from sending to the whole response
     pub timeout_secs: u64,
+    /// Timeout for establishing a connection, in seconds
+    pub connect_timeout_secs: u64,
+    /// Timeout for each read of a response, in seconds
+    pub read_timeout_secs: u64,
+    /// Idle connections kept open per host for reuse
+    pub pool_size: usize,
+    /// HTTP version to use
+    pub http_version: HttpVersion,
     /// Directory for capturing API responses (None = no capture)
     pub capture_dir: Option<PathBuf>,
+    /// Whether a service that can't be converted skips the service or
+    /// fails the board
+    pub conversion_mode: ConversionMode,
 }
 
 impl DarwinConfig {
@@ -52,9 +116,18 @@ impl DarwinConfig {
             api_key: api_key.into(),
             arrivals_api_key: None,
             departures_url: DEFAULT_DEPARTURES_URL.to_string(),
+            staff_api_key: None,
+            staff_url: DEFAULT_STAFF_URL.to_string(),
+            staff_retry: DEFAULT_STAFF_RETRY,
             max_concurrent: DEFAULT_MAX_CONCURRENT,
             timeout_secs: 30,
+            connect_timeout_secs: 5,
+            read_timeout_secs: 15,
+            // Searches fan out to many boards at once
+            pool_size: 32,
+            http_version: HttpVersion::Auto,
             capture_dir: None,
+            conversion_mode: ConversionMode::Lenient,
         }
     }
 
@@ -72,6 +145,25 @@ impl DarwinConfig {
         self
     }
 
+    /// Set the API key for the staff (LDBSVWS) product.
+    /// When set, board requests go to the staff API instead of the public one.
+    pub fn with_staff_api_key(mut self, key: impl Into<String>) -> Self {
+        self.staff_api_key = Some(key.into());
+        self
+    }
+
+    /// Set a custom base URL for the staff API (for testing).
+    pub fn with_staff_url(mut self, url: impl Into<String>) -> Self {
+        self.staff_url = url.into();
+        self
+    }
+
+    /// Set how long to wait before retrying rejected staff credentials.
+    pub fn with_staff_retry(mut self, retry: Duration) -> Self {
+        self.staff_retry = retry;
+        self
+    }
+
     /// Set maximum concurrent requests.
     pub fn with_max_concurrent(mut self, n: usize) -> Self {
         self.max_concurrent = n;
@@ -84,6 +176,56 @@ impl DarwinConfig {
         self
     }
 
+    /// Set the timeout for establishing a connection.
+    pub fn with_connect_timeout(mut self, secs: u64) -> Self {
+        self.connect_timeout_secs = secs;
+        self
+    }
+
+    /// Set the timeout for each read of a response.
+    pub fn with_read_timeout(mut self, secs: u64) -> Self {
+        self.read_timeout_secs = secs;
+        self
+    }
+
+    /// Set how many idle connections are kept open per host.
+    pub fn with_pool_size(mut self, n: usize) -> Self {
+        self.pool_size = n;
+        self
+    }
+
+    /// Set the HTTP version to use.
+    pub fn with_http_version(mut self, version: HttpVersion) -> Self {
+        self.http_version = version;
+        self
+    }
+
+    /// Set what happens to services on a board that can't be converted.
+    pub fn with_conversion_mode(mut self, mode: ConversionMode) -> Self {
+        self.conversion_mode = mode;
+        self
+    }
+
+    /// Build an HTTP client with this config's timeouts, pool and HTTP
+    /// version.
+    ///
+    /// The client carries no API key, so it can be shared with other Rail
+    /// Data Marketplace clients (e.g. `StationClient`), which send their
+    /// own keys.
+    pub fn http_client(&self) -> Result<reqwest::Client, DarwinError> {
+        let builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(self.read_timeout_secs))
+            .pool_max_idle_per_host(self.pool_size);
+        let builder = match self.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        };
+        Ok(builder.build()?)
+    }
+
     /// Set capture directory for saving API responses.
     /// When set, all successful API responses will be saved as JSON files.
     pub fn with_capture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
@@ -99,29 +241,101 @@ impl DarwinConfig {
 #[derive(Debug, Clone)]
 pub struct DarwinClient {
     http: reqwest::Client,
+    api_key: HeaderValue,
     departures_url: String,
     arrivals_api_key: Option<String>,
+    staff: Option<StaffApi>,
     semaphore: Arc<Semaphore>,
     capture_dir: Option<PathBuf>,
+    conversion_mode: ConversionMode,
+    messages: StationMessages,
+}
+
+/// Connection details for the staff API.
+#[derive(Debug, Clone)]
+struct StaffApi {
+    url: String,
+    api_key: String,
+    /// Number of calls that fell back to the public API (shared across clones).
+    fallbacks: Arc<AtomicU64>,
+    /// Wait before the first retry after our credentials are rejected.
+    retry: Duration,
+    /// Set while our credentials stand rejected (shared across clones).
+    backoff: Arc<Mutex<Option<StaffBackoff>>>,
+}
+
+/// When to try rejected staff credentials again.
+#[derive(Debug, Clone, Copy)]
+struct StaffBackoff {
+    retry_at: Instant,
+    /// Wait that led to `retry_at`, doubled on the next rejection.
+    delay: Duration,
+}
+
+impl StaffApi {
+    /// Whether to skip the staff API for now and go straight to the
+    /// public one.
+    fn backing_off(&self) -> bool {
+        self.backoff
+            .lock()
+            .unwrap()
+            .is_some_and(|b| Instant::now() < b.retry_at)
+    }
+
+    /// Record a credentials rejection, backing off for longer each time.
+    fn rejected(&self) -> Duration {
+        let mut backoff = self.backoff.lock().unwrap();
+        let delay = backoff.map_or(self.retry, |b| (b.delay * 2).min(MAX_STAFF_RETRY));
+        *backoff = Some(StaffBackoff {
+            retry_at: Instant::now() + delay,
+            delay,
+        });
+        delay
+    }
+
+    /// Record a staff call that worked.
+    fn recovered(&self) {
+        if self.backoff.lock().unwrap().take().is_some() {
+            info!("Staff API accepted credentials again");
+        }
+    }
+}
+
+/// Which staff board to request.
+#[derive(Debug, Clone, Copy)]
+enum StaffBoard {
+    Departures,
+    Arrivals,
+}
+
+/// Parameters for a staff board request.
+struct StaffBoardRequest<'a> {
+    board: StaffBoard,
+    crs: &'a Crs,
+    params: BoardParams<'a>,
+    window: BoardWindow,
 }
 
 impl DarwinClient {
     /// Create a new Darwin client with the given configuration.
     pub fn new(config: DarwinConfig) -> Result<Self, DarwinError> {
-        let mut headers = HeaderMap::new();
+        let http = config.http_client()?;
+        Self::with_http_client(config, http)
+    }
 
-        // Use x-apikey header for Rail Data Marketplace authentication
-        let api_key_header =
+    /// Create a Darwin client that sends its requests through `http`,
+    /// which may be shared with other clients (see
+    /// [`DarwinConfig::http_client`]).
+    pub fn with_http_client(
+        config: DarwinConfig,
+        http: reqwest::Client,
+    ) -> Result<Self, DarwinError> {
+        // Rail Data Marketplace authenticates with the x-apikey header
+        let api_key =
             HeaderValue::from_str(&config.api_key).map_err(|_| DarwinError::ApiError {
                 status: 0,
                 message: "Invalid API key format".to_string(),
             })?;
-        headers.insert(HeaderName::from_static("x-apikey"), api_key_header);
-
-        let http = reqwest::Client::builder()
-            .default_headers(headers)
-            .timeout(std::time::Duration::from_secs(config.timeout_secs))
-            .build()?;
 
         // Create capture directory if specified
         if let Some(ref dir) = config.capture_dir {
@@ -134,13 +348,113 @@ impl DarwinClient {
 
         Ok(Self {
             http,
+            api_key,
             departures_url: config.departures_url,
             arrivals_api_key: config.arrivals_api_key,
+            staff: config.staff_api_key.map(|api_key| StaffApi {
+                url: config.staff_url,
+                api_key,
+                fallbacks: Arc::new(AtomicU64::new(0)),
+                retry: config.staff_retry,
+                backoff: Arc::new(Mutex::new(None)),
+            }),
             semaphore: Arc::new(Semaphore::new(config.max_concurrent)),
             capture_dir: config.capture_dir,
+            conversion_mode: config.conversion_mode,
+            messages: StationMessages::new(),
         })
     }
 
+    /// Convert a fetched board in this client's conversion mode.
+    fn convert_board(
+        &self,
+        board: &StationBoardWithDetails,
+        window: &BoardWindow,
+    ) -> Result<Vec<ConvertedService>, DarwinError> {
+        let report =
+            convert_station_board_report(board, window, self.conversion_mode).map_err(|e| {
+                DarwinError::Json {
+                    message: e.to_string(),
+                    body: None,
+                }
+            })?;
+        if !report.is_complete() {
+            debug!(
+                skipped = report.skipped.len(),
+                converted = report.services.len(),
+                "Board converted with services skipped"
+            );
+        }
+        Ok(report.services)
+    }
+
+    /// The messages on the boards this client (or any clone) has fetched.
+    pub fn station_messages(&self) -> &StationMessages {
+        &self.messages
+    }
+
+    /// Whether board requests are served by the staff API.
+    pub fn uses_staff_api(&self) -> bool {
+        self.staff.is_some()
+    }
+
+    /// Whether arrivals boards can be fetched, from either the arrivals
+    /// product or the staff API.
+    pub fn serves_arrivals(&self) -> bool {
+        self.arrivals_api_key.is_some() || self.staff.is_some()
+    }
+
+    /// Number of staff API calls that fell back to the public API because
+    /// the staff credentials were rejected.
+    pub fn staff_fallbacks(&self) -> u64 {
+        self.staff
+            .as_ref()
+            .map_or(0, |s| s.fallbacks.load(Ordering::Relaxed))
+    }
+
+    /// Whether board requests are falling back to the public API because
+    /// the staff API rejected our credentials.
+    ///
+    /// Cleared as soon as a staff call succeeds again.
+    pub fn is_staff_degraded(&self) -> bool {
+        self.staff
+            .as_ref()
+            .is_some_and(|s| s.backoff.lock().unwrap().is_some())
+    }
+
+    /// Try a board request against the staff API, if configured.
+    ///
+    /// Returns `None` when the caller should use the public API instead:
+    /// either no staff key is configured, or the staff API rejected our
+    /// credentials. Auth failures are downgraded rather than surfaced, so a
+    /// misconfigured staff key degrades data quality but never takes the
+    /// service down. After a rejection the staff API is left alone for a
+    /// while, then tried again, so a key that starts working (or a staff
+    /// outage that ends) is picked up without a restart.
+    async fn try_staff_board(
+        &self,
+        req: StaffBoardRequest<'_>,
+    ) -> Option<Result<Vec<ConvertedService>, DarwinError>> {
+        let staff = self.staff.as_ref()?;
+        if staff.backing_off() {
+            return None;
+        }
+        match self.get_staff_board(staff, req).await {
+            Err(e) if e.is_auth_error() => {
+                let total = staff.fallbacks.fetch_add(1, Ordering::Relaxed) + 1;
+                let retry = staff.rejected();
+                warn!(error = %e, fallbacks = total, retry_secs = retry.as_secs(), "Staff API rejected credentials, falling back to public API");
+                None
+            }
+            result => {
+                if result.is_ok() {
+                    staff.recovered();
+                }
+                Some(result)
+            }
+        }
+    }
+
     /// Capture a response to disk if capture is enabled.
     fn capture_response(&self, board_type: &str, crs: &str, body: &str) {
         if let Some(ref dir) = self.capture_dir {
@@ -167,19 +481,29 @@ impl DarwinClient {
     ///
     /// * `crs` - Station CRS code
     /// * `num_rows` - Number of services to return (max 150)
-    /// * `time_offset` - Minutes offset from now (-120 to 120)
-    /// * `time_window` - Minutes window for results (0 to 120)
-    /// * `board_date` - Date to use for parsing times
+    /// * `window` - When the board starts and how long it covers; each
+    ///   service is dated by where it falls in it
     #[instrument(skip(self), fields(crs = %crs.as_str()))]
     pub async fn get_departures_with_details(
         &self,
         crs: &Crs,
         num_rows: u8,
-        time_offset: i16,
-        time_window: u16,
-        board_date: NaiveDate,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
-        debug!(num_rows, time_offset, time_window, %board_date, "Fetching departures");
+        debug!(num_rows, start = %window.start(), time_window = window.time_window(), "Fetching departures");
+        let params = BoardParams::new(num_rows, window.time_offset(), window.time_window())?;
+
+        if let Some(result) = self
+            .try_staff_board(StaffBoardRequest {
+                board: StaffBoard::Departures,
+                crs,
+                params,
+                window,
+            })
+            .await
+        {
+            return result;
+        }
 
         let _permit = self
             .semaphore
@@ -201,11 +525,8 @@ impl DarwinClient {
         let response = self
             .http
             .get(&url)
-            .query(&[
-                ("numRows", num_rows.to_string()),
-                ("timeOffset", time_offset.to_string()),
-                ("timeWindow", time_window.to_string()),
-            ])
+            .header(API_KEY_HEADER, &self.api_key)
+            .query(&params.query())
             .send()
             .await?;
 
@@ -232,6 +553,7 @@ impl DarwinClient {
         }
 
         let body = response.text().await?;
+        record_response_bytes(body.len());
 
         // Capture response if enabled
         self.capture_response("departures", crs.as_str(), &body);
@@ -241,12 +563,9 @@ impl DarwinClient {
                 message: e.to_string(),
                 body: Some(body.chars().take(500).collect()),
             })?;
+        self.messages.record(*crs, &board);
 
-        let services =
-            convert_station_board(&board, board_date).map_err(|e| DarwinError::Json {
-                message: e.to_string(),
-                body: None,
-            })?;
+        let services = self.convert_board(&board, &window)?;
 
         debug!(service_count = services.len(), "Departures parsed");
         for svc in &services {
@@ -269,20 +588,30 @@ impl DarwinClient {
     /// * `crs` - Origin station CRS code
     /// * `filter_crs` - Destination station CRS code to filter by
     /// * `num_rows` - Number of services to return
-    /// * `time_offset` - Minutes offset from now
-    /// * `time_window` - Minutes window for results
-    /// * `board_date` - Date to use for parsing times
+    /// * `window` - When the board starts and how long it covers
     #[instrument(skip(self), fields(crs = %crs.as_str(), filter = %filter_crs.as_str()))]
     pub async fn get_departures_to(
         &self,
         crs: &Crs,
         filter_crs: &Crs,
         num_rows: u8,
-        time_offset: i16,
-        time_window: u16,
-        board_date: NaiveDate,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
-        debug!(num_rows, time_offset, time_window, %board_date, "Fetching filtered departures");
+        debug!(num_rows, start = %window.start(), time_window = window.time_window(), "Fetching filtered departures");
+        let params = BoardParams::new(num_rows, window.time_offset(), window.time_window())?
+            .filter_to(filter_crs);
+
+        if let Some(result) = self
+            .try_staff_board(StaffBoardRequest {
+                board: StaffBoard::Departures,
+                crs,
+                params,
+                window,
+            })
+            .await
+        {
+            return result;
+        }
 
         let _permit = self
             .semaphore
@@ -304,13 +633,8 @@ impl DarwinClient {
         let response = self
             .http
             .get(&url)
-            .query(&[
-                ("numRows", num_rows.to_string()),
-                ("timeOffset", time_offset.to_string()),
-                ("timeWindow", time_window.to_string()),
-                ("filterCrs", filter_crs.as_str().to_string()),
-                ("filterType", "to".to_string()),
-            ])
+            .header(API_KEY_HEADER, &self.api_key)
+            .query(&params.query())
             .send()
             .await?;
 
@@ -337,6 +661,7 @@ impl DarwinClient {
         }
 
         let body = response.text().await?;
+        record_response_bytes(body.len());
 
         // Capture response if enabled
         let capture_name = format!("departures_{}_to_{}", crs.as_str(), filter_crs.as_str());
@@ -347,12 +672,9 @@ impl DarwinClient {
                 message: e.to_string(),
                 body: Some(body.chars().take(500).collect()),
             })?;
+        self.messages.record(*crs, &board);
 
-        let services =
-            convert_station_board(&board, board_date).map_err(|e| DarwinError::Json {
-                message: e.to_string(),
-                body: None,
-            })?;
+        let services = self.convert_board(&board, &window)?;
 
         debug!(service_count = services.len(), "Filtered departures parsed");
 
@@ -391,7 +713,12 @@ impl DarwinClient {
 
         trace!(%url, "Sending Darwin request");
 
-        let response = self.http.get(&url).send().await?;
+        let response = self
+            .http
+            .get(&url)
+            .header(API_KEY_HEADER, &self.api_key)
+            .send()
+            .await?;
 
         let status = response.status();
         debug!(%status, "Darwin response received");
@@ -421,6 +748,7 @@ impl DarwinClient {
         }
 
         let body = response.text().await?;
+        record_response_bytes(body.len());
 
         // Capture response if enabled
         self.capture_response("service", service_id, &body);
@@ -450,19 +778,29 @@ impl DarwinClient {
     ///
     /// * `crs` - Station CRS code
     /// * `num_rows` - Number of services to return (max 150)
-    /// * `time_offset` - Minutes offset from now (-120 to 120)
-    /// * `time_window` - Minutes window for results (0 to 120)
-    /// * `board_date` - Date to use for parsing times
+    /// * `window` - When the board starts and how long it covers; each
+    ///   service is dated by where it falls in it
     #[instrument(skip(self), fields(crs = %crs.as_str()))]
     pub async fn get_arrivals_with_details(
         &self,
         crs: &Crs,
         num_rows: u8,
-        time_offset: i16,
-        time_window: u16,
-        board_date: NaiveDate,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
-        debug!(num_rows, time_offset, time_window, %board_date, "Fetching arrivals");
+        debug!(num_rows, start = %window.start(), time_window = window.time_window(), "Fetching arrivals");
+        let params = BoardParams::new(num_rows, window.time_offset(), window.time_window())?;
+
+        if let Some(result) = self
+            .try_staff_board(StaffBoardRequest {
+                board: StaffBoard::Arrivals,
+                crs,
+                params,
+                window,
+            })
+            .await
+        {
+            return result;
+        }
 
         let arrivals_api_key = self.arrivals_api_key.as_ref().ok_or_else(|| DarwinError::ApiError {
             status: 0,
@@ -490,12 +828,8 @@ impl DarwinClient {
         let response = self
             .http
             .get(&url)
-            .header("x-apikey", arrivals_api_key)
-            .query(&[
-                ("numRows", num_rows.to_string()),
-                ("timeOffset", time_offset.to_string()),
-                ("timeWindow", time_window.to_string()),
-            ])
+            .header(API_KEY_HEADER, arrivals_api_key)
+            .query(&params.query())
             .send()
             .await?;
 
@@ -522,6 +856,7 @@ impl DarwinClient {
         }
 
         let body = response.text().await?;
+        record_response_bytes(body.len());
 
         // Capture response if enabled
         self.capture_response("arrivals", crs.as_str(), &body);
@@ -531,14 +866,96 @@ impl DarwinClient {
                 message: e.to_string(),
                 body: Some(body.chars().take(500).collect()),
             })?;
+        self.messages.record(*crs, &board);
+
+        let services = self.convert_board(&board, &window)?;
+
+        debug!(service_count = services.len(), "Arrivals parsed");
+
+        Ok(services)
+    }
+
+    /// Get a board with details from the staff API.
+    ///
+    /// The staff API takes an explicit board time rather than an offset from
+    /// now, and returns the richer staff fields (UIDs, formations, arrival and
+    /// departure at every calling point), which the converter prefers.
+    #[instrument(skip(self, staff, req), fields(crs = %req.crs.as_str(), board = ?req.board))]
+    async fn get_staff_board(
+        &self,
+        staff: &StaffApi,
+        req: StaffBoardRequest<'_>,
+    ) -> Result<Vec<ConvertedService>, DarwinError> {
+        let _permit = self
+            .semaphore
+            .acquire()
+            .await
+            .map_err(|_| DarwinError::ApiError {
+                status: 0,
+                message: "Semaphore closed".to_string(),
+            })?;
+
+        let (operation, board_type) = match req.board {
+            StaffBoard::Departures => ("GetDepBoardWithDetails", "staff_departures"),
+            StaffBoard::Arrivals => ("GetArrBoardWithDetails", "staff_arrivals"),
+        };
+        let time = req.window.start().to_datetime();
+        let url = format!(
+            "{}/api/20220120/{}/{}/{}",
+            staff.url,
+            operation,
+            req.crs.as_str(),
+            time.format("%Y%m%dT%H%M%S")
+        );
+
+        trace!(%url, "Sending Darwin staff request");
+
+        let response = self
+            .http
+            .get(&url)
+            .header(API_KEY_HEADER, &staff.api_key)
+            .query(&req.params.staff_query())
+            .send()
+            .await?;
+
+        let status = response.status();
+        debug!(%status, "Darwin staff response received");
+
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            warn!("Darwin staff API unauthorized");
+            return Err(DarwinError::Unauthorized);
+        }
 
-        let services =
-            convert_station_board(&board, board_date).map_err(|e| DarwinError::Json {
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            warn!("Darwin staff API rate limited");
+            return Err(DarwinError::RateLimited);
+        }
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            warn!(%status, %url, "Darwin staff API error");
+            return Err(DarwinError::ApiError {
+                status: status.as_u16(),
+                message: body,
+            });
+        }
+
+        let body = response.text().await?;
+        record_response_bytes(body.len());
+
+        // Capture response if enabled
+        self.capture_response(board_type, req.crs.as_str(), &body);
+
+        let board: StationBoardWithDetails =
+            serde_json::from_str(&body).map_err(|e| DarwinError::Json {
                 message: e.to_string(),
-                body: None,
+                body: Some(body.chars().take(500).collect()),
             })?;
+        self.messages.record(*req.crs, &board);
 
-        debug!(service_count = services.len(), "Arrivals parsed");
+        let services = self.convert_board(&board, &req.window)?;
+
+        debug!(service_count = services.len(), "Staff board parsed");
 
         Ok(services)
     }
@@ -589,6 +1006,7 @@ impl DarwinClient {
         }
 
         let body = response.text().await?;
+        record_response_bytes(body.len());
 
         // Capture response if enabled
         self.capture_response("raw_departures", crs.as_str(), &body);
@@ -603,6 +1021,7 @@ impl DarwinClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::NaiveDate;
 
     #[test]
     fn config_builder() {
@@ -629,6 +1048,69 @@ mod tests {
         assert_eq!(config.max_concurrent, DEFAULT_MAX_CONCURRENT);
         assert_eq!(config.timeout_secs, 30);
         assert_eq!(config.capture_dir, None);
+        assert_eq!(config.staff_api_key, None);
+        assert_eq!(config.staff_url, DEFAULT_STAFF_URL);
+        assert_eq!(config.http_version, HttpVersion::Auto);
+        assert_eq!(config.conversion_mode, ConversionMode::Lenient);
+    }
+
+    #[test]
+    fn config_http_tuning() {
+        let config = DarwinConfig::new("test-api-key")
+            .with_connect_timeout(2)
+            .with_read_timeout(8)
+            .with_pool_size(4)
+            .with_http_version(HttpVersion::Http1);
+
+        assert_eq!(config.connect_timeout_secs, 2);
+        assert_eq!(config.read_timeout_secs, 8);
+        assert_eq!(config.pool_size, 4);
+        assert!(config.http_client().is_ok());
+
+        assert_eq!(HttpVersion::parse("2"), Some(HttpVersion::Http2));
+        assert_eq!(HttpVersion::parse("3"), None);
+    }
+
+    #[tokio::test]
+    async fn shared_http_client_sends_each_clients_key() {
+        use axum::http::HeaderMap;
+        use axum::routing::get;
+
+        let fixture = include_str!("../../tests/fixtures/elizabeth_line_zlw_departures.json");
+        let public = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs",
+            get(move |headers: HeaderMap| async move {
+                assert_eq!(headers[API_KEY_HEADER], "public-key");
+                fixture
+            }),
+        ))
+        .await;
+
+        let config = DarwinConfig::new("public-key").with_base_url(public);
+        let http = config.http_client().unwrap();
+        let client = DarwinClient::with_http_client(config, http).unwrap();
+        let crs = Crs::parse("ZLW").unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let window = BoardWindow::at(date, 12 * 60, 0, 120);
+
+        let services = client
+            .get_departures_with_details(&crs, 10, window)
+            .await
+            .unwrap();
+        assert!(!services.is_empty());
+    }
+
+    #[test]
+    fn config_with_staff_api() {
+        let config = DarwinConfig::new("test-api-key")
+            .with_staff_api_key("staff-key")
+            .with_staff_url("http://localhost:8081");
+
+        assert_eq!(config.staff_api_key, Some("staff-key".to_string()));
+        assert_eq!(config.staff_url, "http://localhost:8081");
+
+        let client = DarwinClient::new(config).unwrap();
+        assert!(client.uses_staff_api());
     }
 
     #[test]
@@ -643,6 +1125,142 @@ mod tests {
         let config = DarwinConfig::new("test-api-key");
         let client = DarwinClient::new(config);
         assert!(client.is_ok());
+        assert!(!client.unwrap().uses_staff_api());
+    }
+
+    /// Serve `router` on an ephemeral local port, returning its base URL.
+    async fn serve(router: axum::Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn staff_auth_failure_falls_back_to_public_api() {
+        use axum::http::StatusCode;
+        use axum::routing::get;
+
+        let fixture = include_str!("../../tests/fixtures/elizabeth_line_zlw_departures.json");
+        let public = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs",
+            get(move || async move { fixture }),
+        ))
+        .await;
+        let staff = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs/:time",
+            get(|| async { StatusCode::FORBIDDEN }),
+        ))
+        .await;
+
+        let config = DarwinConfig::new("public-key")
+            .with_base_url(public)
+            .with_staff_api_key("staff-key")
+            .with_staff_url(staff);
+        let client = DarwinClient::new(config).unwrap();
+        let crs = Crs::parse("ZLW").unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let window = BoardWindow::at(date, 12 * 60, 0, 120);
+
+        assert!(!client.is_staff_degraded());
+        let services = client
+            .get_departures_with_details(&crs, 10, window)
+            .await
+            .unwrap();
+
+        assert!(!services.is_empty());
+        assert_eq!(client.staff_fallbacks(), 1);
+        assert!(client.is_staff_degraded());
+    }
+
+    #[tokio::test]
+    async fn rejected_staff_credentials_are_retried_after_a_backoff() {
+        use axum::http::StatusCode;
+        use axum::response::IntoResponse;
+        use axum::routing::get;
+        use std::sync::atomic::AtomicBool;
+
+        let fixture = include_str!("../../tests/fixtures/elizabeth_line_zlw_departures.json");
+        let public = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs",
+            get(move || async move { fixture }),
+        ))
+        .await;
+        let accepting = Arc::new(AtomicBool::new(false));
+        let staff_calls = Arc::new(AtomicU64::new(0));
+        let staff = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs/:time",
+            get({
+                let accepting = accepting.clone();
+                let staff_calls = staff_calls.clone();
+                move || async move {
+                    staff_calls.fetch_add(1, Ordering::Relaxed);
+                    if accepting.load(Ordering::Relaxed) {
+                        fixture.into_response()
+                    } else {
+                        StatusCode::FORBIDDEN.into_response()
+                    }
+                }
+            }),
+        ))
+        .await;
+
+        let config = DarwinConfig::new("public-key")
+            .with_base_url(public)
+            .with_staff_api_key("staff-key")
+            .with_staff_url(staff)
+            .with_staff_retry(Duration::from_millis(200));
+        let client = DarwinClient::new(config).unwrap();
+        let crs = Crs::parse("ZLW").unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let window = BoardWindow::at(date, 12 * 60, 0, 120);
+        let board = || client.get_departures_with_details(&crs, 10, window);
+
+        board().await.unwrap();
+        assert_eq!(staff_calls.load(Ordering::Relaxed), 1);
+        assert!(client.is_staff_degraded());
+
+        // Backing off: straight to the public API
+        board().await.unwrap();
+        assert_eq!(staff_calls.load(Ordering::Relaxed), 1);
+        assert_eq!(client.staff_fallbacks(), 1);
+
+        // The key starts working; after the backoff the staff API gets it
+        accepting.store(true, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        board().await.unwrap();
+        assert_eq!(staff_calls.load(Ordering::Relaxed), 2);
+        assert!(!client.is_staff_degraded());
+    }
+
+    #[tokio::test]
+    async fn staff_server_error_is_not_masked() {
+        use axum::http::StatusCode;
+        use axum::routing::get;
+
+        let staff = serve(axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs/:time",
+            get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        ))
+        .await;
+
+        let config = DarwinConfig::new("public-key")
+            .with_base_url("http://127.0.0.1:1")
+            .with_staff_api_key("staff-key")
+            .with_staff_url(staff);
+        let client = DarwinClient::new(config).unwrap();
+        let crs = Crs::parse("ZLW").unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let window = BoardWindow::at(date, 12 * 60, 0, 120);
+
+        let result = client.get_departures_with_details(&crs, 10, window).await;
+
+        assert!(matches!(
+            result,
+            Err(DarwinError::ApiError { status: 500, .. })
+        ));
+        assert_eq!(client.staff_fallbacks(), 0);
+        assert!(!client.is_staff_degraded());
     }
 
     // Integration tests would go here, but require a real API key
@@ -2,14 +2,28 @@
 //!
 //! This module handles the transformation of raw Darwin API responses into
 //! our validated domain types, including time parsing with rollover detection.
+//! Converted services are then normalized for their stations' known quirks
+//! (see [`super::quirks`]).
+//!
+//! A board may contain services that can't be converted (a missing time, a
+//! malformed CRS). By default ([`ConversionMode::Lenient`]) these are
+//! skipped with a warning, and listed in the [`ConversionReport`], so the
+//! rest of the board is still usable; [`ConversionMode::Strict`] fails the
+//! whole board instead.
 
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate};
+use tracing::warn;
 
 use crate::domain::{
-    AtocCode, Call, CallIndex, Crs, Headcode, RailTime, Service, ServiceCandidate, ServiceRef,
-    parse_time_sequence, parse_time_sequence_reverse,
+    AtocCode, Call, CallIndex, Coach, Crs, Formation, Headcode, Portion, RailTime, Service,
+    ServiceCandidate, ServiceRef, ServiceUid, TimeError, parse_time_sequence,
+    parse_time_sequence_reverse,
 };
 
+use crate::stations;
+
+use super::quirks::{hide_board_platforms, normalize};
+use super::request::BoardWindow;
 use super::types::{
     CallingPoint, ServiceDetails, ServiceItemWithCallingPoints, StationBoardWithDetails,
 };
@@ -32,6 +46,49 @@ pub enum ConversionError {
     /// Invalid service structure
     #[error("invalid service: {0}")]
     InvalidService(&'static str),
+
+    /// A service on a board couldn't be converted, in strict mode
+    #[error("service {service_id}: {reason}")]
+    SkippedService {
+        service_id: String,
+        #[source]
+        reason: Box<ConversionError>,
+    },
+}
+
+/// What to do with services on a board that can't be converted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// Skip them, converting the rest of the board
+    #[default]
+    Lenient,
+    /// Fail the whole board
+    Strict,
+}
+
+/// A service left out of a converted board, and why.
+#[derive(Debug, Clone)]
+pub struct SkippedService {
+    /// Darwin's ID for the service
+    pub service_id: String,
+    /// Why it couldn't be converted
+    pub reason: ConversionError,
+}
+
+/// The services converted from a board, and those skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionReport {
+    /// Services converted, in board order
+    pub services: Vec<ConvertedService>,
+    /// Services that couldn't be converted (always empty in strict mode)
+    pub skipped: Vec<SkippedService>,
+}
+
+impl ConversionReport {
+    /// Whether every service on the board was converted.
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
 }
 
 /// Result of converting a Darwin service item.
@@ -41,37 +98,106 @@ pub struct ConvertedService {
     pub candidate: ServiceCandidate,
     /// Full service with calling points
     pub service: Service,
+    /// Schedule UID (staff API only)
+    pub uid: Option<ServiceUid>,
+    /// Number of coaches, from the formation when known, else the reported length
+    pub coaches: Option<usize>,
 }
 
 /// Convert a departure board response to domain types.
 ///
-/// Returns converted services paired with candidates for display.
+/// Returns converted services paired with candidates for display, skipping
+/// any that can't be converted. Every service's board time is taken to be
+/// on `board_date`; for a board that may cross midnight use
+/// [`convert_station_board_in`].
 pub fn convert_station_board(
     board: &StationBoardWithDetails,
     board_date: NaiveDate,
 ) -> Result<Vec<ConvertedService>, ConversionError> {
+    convert_board(board, |_| board_date, ConversionMode::Lenient).map(|r| r.services)
+}
+
+/// Convert a departure board covering `window` to domain types, skipping
+/// services that can't be converted.
+///
+/// Each service is dated by where its board time falls in the window, so
+/// on a board fetched at 23:50 the 00:10 departure is tomorrow's. Calling
+/// points then roll over from the service's own date as usual.
+pub fn convert_station_board_in(
+    board: &StationBoardWithDetails,
+    window: &BoardWindow,
+) -> Result<Vec<ConvertedService>, ConversionError> {
+    convert_station_board_report(board, window, ConversionMode::Lenient).map(|r| r.services)
+}
+
+/// Convert a departure board covering `window` to domain types, reporting
+/// the services skipped.
+///
+/// In strict mode the first service that can't be converted fails the
+/// board with [`ConversionError::SkippedService`].
+pub fn convert_station_board_report(
+    board: &StationBoardWithDetails,
+    window: &BoardWindow,
+    mode: ConversionMode,
+) -> Result<ConversionReport, ConversionError> {
+    convert_board(
+        board,
+        |item| {
+            item.std
+                .as_deref()
+                .or(item.sta.as_deref())
+                .and_then(|t| parse_clock(t, window.start().date()).ok())
+                .map_or(window.start().date(), |t| window.date_of(t.time()))
+        },
+        mode,
+    )
+}
+
+/// Convert every train service on a board, dating each with `date_of`.
+fn convert_board(
+    board: &StationBoardWithDetails,
+    date_of: impl Fn(&ServiceItemWithCallingPoints) -> NaiveDate,
+    mode: ConversionMode,
+) -> Result<ConversionReport, ConversionError> {
     let board_crs =
         Crs::parse(&board.crs).map_err(|_| ConversionError::InvalidCrs(board.crs.clone()))?;
 
     let train_services = board.train_services.as_deref().unwrap_or(&[]);
 
-    let mut results = Vec::with_capacity(train_services.len());
+    let mut report = ConversionReport {
+        services: Vec::with_capacity(train_services.len()),
+        skipped: Vec::new(),
+    };
 
     for service_item in train_services {
+        let board_date = date_of(service_item);
         match convert_service_item(service_item, &board_crs, &board.location_name, board_date) {
-            Ok(converted) => results.push(converted),
-            Err(e) => {
-                // Log and skip invalid services rather than failing the whole board
-                // In production, we'd use proper logging here
-                eprintln!(
-                    "Warning: skipping service {}: {}",
-                    service_item.service_id, e
+            Ok(mut converted) => {
+                if board.platform_available == Some(false) {
+                    hide_board_platforms(&mut converted);
+                }
+                report.services.push(converted);
+            }
+            Err(reason) => {
+                let service_id = service_item.service_id.clone();
+                if mode == ConversionMode::Strict {
+                    return Err(ConversionError::SkippedService {
+                        service_id,
+                        reason: Box::new(reason),
+                    });
+                }
+                warn!(
+                    crs = %board_crs.as_str(),
+                    service_id = %service_id,
+                    error = %reason,
+                    "Skipping service that can't be converted"
                 );
+                report.skipped.push(SkippedService { service_id, reason });
             }
         }
     }
 
-    Ok(results)
+    Ok(report)
 }
 
 /// Convert a single service item to domain types.
@@ -84,15 +210,22 @@ pub fn convert_service_item(
     // Parse the service reference
     let service_ref = ServiceRef::new(item.service_id.clone(), *board_crs);
 
-    // Parse headcode from RSID if available (format: "GW123400" -> "1234")
-    let headcode = item.rsid.as_ref().and_then(|rsid| {
-        // RSID format is typically "XX1234YY" where XX is operator, 1234 is headcode
-        if rsid.len() >= 6 {
-            Headcode::parse(&rsid[2..6])
-        } else {
-            None
-        }
-    });
+    // Prefer the staff API's train ID; otherwise parse the headcode from the
+    // RSID if available (format: "GW123400" -> "1234")
+    let headcode = item
+        .trainid
+        .as_deref()
+        .and_then(Headcode::parse)
+        .or_else(|| {
+            item.rsid.as_ref().and_then(|rsid| {
+                // RSID format is typically "XX1234YY" where XX is operator, 1234 is headcode
+                if rsid.len() >= 6 {
+                    Headcode::parse(&rsid[2..6])
+                } else {
+                    None
+                }
+            })
+        });
 
     // Parse operator code
     let operator_code = item
@@ -108,7 +241,7 @@ pub fn convert_service_item(
         .as_ref()
         .or(item.sta.as_ref())
         .ok_or(ConversionError::MissingField("std or sta (scheduled time)"))?;
-    let scheduled_departure = RailTime::parse_hhmm(scheduled_time_str, board_date)
+    let scheduled_departure = parse_clock(scheduled_time_str, board_date)
         .map_err(|_| ConversionError::InvalidTime(scheduled_time_str.clone()))?;
 
     // Parse expected time (may be "On time", "Delayed", "Cancelled", or a time).
@@ -143,9 +276,25 @@ pub fn convert_service_item(
         operator_code,
         calls,
         board_station_idx,
+        formation: build_formation(item, board_date),
     };
 
-    Ok(ConvertedService { candidate, service })
+    let uid = item
+        .uid
+        .as_ref()
+        .and_then(|u| ServiceUid::new(u.clone()).ok());
+
+    // Prefer the staff API's coach-by-coach formation over the bare length
+    let coaches = service.formation.as_ref().and_then(Formation::length);
+
+    let mut converted = ConvertedService {
+        candidate,
+        service,
+        uid,
+        coaches,
+    };
+    normalize(&mut converted);
+    Ok(converted)
 }
 
 /// Convert a ServiceDetails response (from GetServiceDetails) to domain types.
@@ -189,7 +338,7 @@ pub fn convert_service_details(
         .as_ref()
         .or(details.sta.as_ref())
         .ok_or(ConversionError::MissingField("std or sta (scheduled time)"))?;
-    let scheduled_time = RailTime::parse_hhmm(scheduled_time_str, board_date)
+    let scheduled_time = parse_clock(scheduled_time_str, board_date)
         .map_err(|_| ConversionError::InvalidTime(scheduled_time_str.clone()))?;
 
     // Parse expected time (prefer departure, fall back to arrival)
@@ -222,9 +371,17 @@ pub fn convert_service_details(
         operator_code,
         calls,
         board_station_idx,
+        formation: None,
     };
 
-    Ok(ConvertedService { candidate, service })
+    let mut converted = ConvertedService {
+        candidate,
+        service,
+        uid: None,
+        coaches: details.length.and_then(|l| usize::try_from(l).ok()),
+    };
+    normalize(&mut converted);
+    Ok(converted)
 }
 
 /// Build calls list from ServiceDetails.
@@ -245,7 +402,7 @@ fn build_calls_from_details(
 
     if !previous.is_empty() {
         let reversed: Vec<&CallingPoint> = previous.iter().rev().collect();
-        let times: Vec<Option<&str>> = reversed.iter().map(|cp| cp.st.as_deref()).collect();
+        let times: Vec<Option<&str>> = reversed.iter().map(|cp| scheduled_time_of(cp)).collect();
         let parsed_times = parse_time_sequence_reverse(&times, board_date)
             .map_err(|e| ConversionError::InvalidTime(e.to_string()))?;
 
@@ -273,10 +430,10 @@ fn build_calls_from_details(
         .unwrap_or(&[]);
 
     if !subsequent.is_empty() {
-        let anchor_time = details.std.as_deref().or(details.sta.as_deref());
+        let anchor_time = details.std.as_deref().or(details.sta.as_deref()).map(clock);
         let mut times: Vec<Option<&str>> = Vec::with_capacity(subsequent.len() + 1);
         times.push(anchor_time);
-        times.extend(subsequent.iter().map(|cp| cp.st.as_deref()));
+        times.extend(subsequent.iter().map(scheduled_time_of));
 
         let parsed_times = parse_time_sequence(&times, board_date)
             .map_err(|e| ConversionError::InvalidTime(e.to_string()))?;
@@ -305,10 +462,11 @@ fn create_board_station_call_from_details(
     board_date: NaiveDate,
 ) -> Result<Call, ConversionError> {
     let mut call = Call::new(*board_crs, details.location_name.clone());
+    apply_activities(&mut call, details.activities.as_deref());
 
     // Parse arrival time
     if let Some(sta) = &details.sta
-        && let Ok(t) = RailTime::parse_hhmm(sta, board_date)
+        && let Ok(t) = parse_clock(sta, board_date)
     {
         call.booked_arrival = Some(t);
         if let Some(rt) = parse_expected_time(details.eta.as_deref(), &t) {
@@ -318,7 +476,7 @@ fn create_board_station_call_from_details(
 
     // Parse departure time
     if let Some(std) = &details.std
-        && let Ok(t) = RailTime::parse_hhmm(std, board_date)
+        && let Ok(t) = parse_clock(std, board_date)
     {
         call.booked_departure = Some(t);
         if let Some(rt) = parse_expected_time(details.etd.as_deref(), &t) {
@@ -328,6 +486,8 @@ fn create_board_station_call_from_details(
 
     call.platform = details.platform.clone();
     call.is_cancelled = details.is_cancelled.unwrap_or(false);
+    call.delay_reason = details.delay_reason.clone();
+    call.cancel_reason = details.cancel_reason.clone();
 
     Ok(call)
 }
@@ -348,10 +508,9 @@ fn parse_expected_time(etd: Option<&str>, scheduled: &RailTime) -> Option<RailTi
     match etd {
         "On time" => Some(*scheduled),
         "Cancelled" | "Delayed" | "" => None,
-        time_str => {
-            // Try to parse as time
-            RailTime::parse_hhmm(time_str, scheduled.date()).ok()
-        }
+        // A delay can carry the train past midnight, so take whichever day
+        // is closest to the scheduled time
+        time_str => parse_clock_near(time_str, *scheduled),
     }
 }
 
@@ -400,7 +559,7 @@ fn build_calls(
     // 3. Parse subsequent calling points (if any)
     // Pass the board station's scheduled departure for midnight rollover detection
     // Fall back to sta if std is not available (e.g., at a terminus)
-    let anchor_time = item.std.as_deref().or(item.sta.as_deref());
+    let anchor_time = item.std.as_deref().or(item.sta.as_deref()).map(clock);
     let subsequent_calls = parse_subsequent_calling_points(item, anchor_time, board_date)?;
 
     // 4. Merge: previous + board + subsequent
@@ -412,6 +571,129 @@ fn build_calls(
     Ok((calls, board_station_idx))
 }
 
+/// The formation of a service as it leaves the board station, if Darwin
+/// says anything about it.
+fn build_formation(
+    item: &ServiceItemWithCallingPoints,
+    board_date: NaiveDate,
+) -> Option<Formation> {
+    let coaches: Vec<Coach> = item
+        .formation
+        .as_ref()
+        .and_then(|f| f.coaches.as_ref())
+        .map(|coaches| {
+            coaches
+                .iter()
+                .map(|c| Coach {
+                    number: c.number.clone(),
+                    first_class: matches!(c.coach_class.as_deref(), Some("First" | "Mixed")),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let length = item
+        .length
+        .and_then(|l| usize::try_from(l).ok())
+        .filter(|&n| n > 0);
+
+    // Further lists of calling points are portions dividing from the train
+    let portions: Vec<Portion> = match item.subsequent_calling_points.as_deref() {
+        Some([main, others @ ..]) => {
+            let length = if coaches.is_empty() {
+                length
+            } else {
+                Some(coaches.len())
+            };
+            let anchor_time = item.std.as_deref().or(item.sta.as_deref()).map(clock);
+            others
+                .iter()
+                .filter_map(|portion| {
+                    let mut built =
+                        build_portion(&main.calling_point, &portion.calling_point, length)?;
+                    built.calls = portion_calls(&portion.calling_point, anchor_time, board_date)
+                        .unwrap_or_default();
+                    Some(built)
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+
+    if coaches.is_empty() && length.is_none() && portions.is_empty() {
+        return None;
+    }
+    Some(Formation {
+        coaches,
+        length,
+        portions,
+        ..Formation::default()
+    })
+}
+
+/// A portion dividing from a train whose own calling points are `main`.
+///
+/// Darwin starts a portion's calling points at the division, which is also
+/// one of the train's own stops; a portion that doesn't is left out, as
+/// there's no telling where it divides.
+fn build_portion(
+    main: &[CallingPoint],
+    portion: &[CallingPoint],
+    length: Option<usize>,
+) -> Option<Portion> {
+    let (first, last) = (portion.first()?, portion.last()?);
+    let at = main.iter().position(|cp| cp.crs == first.crs)?;
+    let divides_at = &main[at];
+
+    // The length of the train's own part once it has divided, or failing
+    // that, what's left when the portion's length is taken off
+    let coaches = |cp: &CallingPoint| cp.length.and_then(|l| usize::try_from(l).ok());
+    let remaining_coaches = main[at + 1..]
+        .iter()
+        .find_map(coaches)
+        .or_else(|| {
+            let detached = portion
+                .iter()
+                .filter(|cp| cp.crs != divides_at.crs)
+                .find_map(coaches)?;
+            length?.checked_sub(detached)
+        })
+        .filter(|&n| n > 0);
+
+    Some(Portion {
+        divides_at: Crs::parse(&divides_at.crs).ok()?,
+        destination: Crs::parse(&last.crs).ok()?,
+        destination_name: last.location_name.clone(),
+        remaining_coaches,
+        calls: Vec::new(),
+    })
+}
+
+/// A portion's calling points after the division, which starts them.
+///
+/// Times are read in sequence from the board station's, as for the train's
+/// own subsequent calling points. Returns `None` if any can't be converted.
+fn portion_calls(
+    portion: &[CallingPoint],
+    board_std: Option<&str>,
+    board_date: NaiveDate,
+) -> Option<Vec<Call>> {
+    let after_division = portion.get(1..)?;
+
+    let mut times: Vec<Option<&str>> = Vec::with_capacity(portion.len() + 1);
+    times.push(board_std);
+    times.extend(portion.iter().map(scheduled_time_of));
+    let parsed_times = parse_time_sequence(&times, board_date).ok()?;
+
+    // Skip the board station and the division
+    let count = after_division.len();
+    after_division
+        .iter()
+        .zip(parsed_times.iter().skip(2))
+        .enumerate()
+        .map(|(idx, (cp, time))| calling_point_to_call(cp, *time, idx == count - 1).ok())
+        .collect()
+}
+
 /// Parse previous calling points into domain Calls.
 fn parse_previous_calling_points(
     item: &ServiceItemWithCallingPoints,
@@ -435,7 +717,7 @@ fn parse_previous_calling_points(
     let reversed: Vec<&CallingPoint> = previous.iter().rev().collect();
 
     // Extract times for parsing
-    let times: Vec<Option<&str>> = reversed.iter().map(|cp| cp.st.as_deref()).collect();
+    let times: Vec<Option<&str>> = reversed.iter().map(|cp| scheduled_time_of(cp)).collect();
 
     let parsed_times = parse_time_sequence_reverse(&times, board_date)
         .map_err(|e| ConversionError::InvalidTime(e.to_string()))?;
@@ -476,7 +758,7 @@ fn parse_subsequent_calling_points(
     // For example: board at 23:30, first subsequent at 00:15 -> should be next day.
     let mut times: Vec<Option<&str>> = Vec::with_capacity(subsequent.len() + 1);
     times.push(board_std);
-    times.extend(subsequent.iter().map(|cp| cp.st.as_deref()));
+    times.extend(subsequent.iter().map(scheduled_time_of));
 
     let parsed_times = parse_time_sequence(&times, board_date)
         .map_err(|e| ConversionError::InvalidTime(e.to_string()))?;
@@ -506,6 +788,18 @@ fn calling_point_to_call(
     let station = Crs::parse(&cp.crs).map_err(|_| ConversionError::InvalidCrs(cp.crs.clone()))?;
 
     let mut call = Call::new(station, cp.location_name.clone());
+    call.is_cancelled = cp.is_cancelled.unwrap_or(false);
+    apply_activities(&mut call, cp.activities.as_deref());
+    call.delay_reason = cp.delay_reason.clone();
+    call.cancel_reason = cp.cancel_reason.clone();
+
+    // The staff API gives explicit arrival/departure pairs; prefer those
+    if cp.sta.is_some() || cp.std.is_some() {
+        if let Some(anchor) = scheduled_time {
+            apply_staff_times(&mut call, cp, anchor);
+        }
+        return Ok(call);
+    }
 
     // Set times based on whether this is arrival or departure
     // For calling points, `st` is the scheduled time (departure for intermediate,
@@ -518,7 +812,7 @@ fn calling_point_to_call(
             // Parse realtime (et or at)
             let realtime = cp.at.as_deref().or(cp.et.as_deref());
             if let Some(rt_str) = realtime
-                && let Ok(rt) = RailTime::parse_hhmm(rt_str, st.date())
+                && let Ok(rt) = parse_clock(rt_str, st.date())
             {
                 call.realtime_arrival = Some(rt);
             }
@@ -529,18 +823,109 @@ fn calling_point_to_call(
             // Parse realtime (et or at)
             let realtime = cp.at.as_deref().or(cp.et.as_deref());
             if let Some(rt_str) = realtime
-                && let Ok(rt) = RailTime::parse_hhmm(rt_str, st.date())
+                && let Ok(rt) = parse_clock(rt_str, st.date())
             {
                 call.realtime_departure = Some(rt);
             }
         }
     }
 
-    call.is_cancelled = cp.is_cancelled.unwrap_or(false);
-
     Ok(call)
 }
 
+/// Whether a call is a request stop.
+///
+/// Darwin's activity codes ("R " among two-character codes) say for sure
+/// when the staff API gives them; otherwise fall back on the bundled list.
+fn is_request_stop(activities: Option<&str>, station: &Crs) -> bool {
+    match activities {
+        Some(codes) => has_activity(codes, b"R"),
+        None => stations::is_request_stop(station),
+    }
+}
+
+/// Whether Darwin's two-character activity codes include `code`.
+fn has_activity(codes: &str, code: &[u8]) -> bool {
+    codes
+        .as_bytes()
+        .chunks(2)
+        .any(|activity| activity.trim_ascii() == code)
+}
+
+/// Record what passengers may do at a call from its activity codes: as
+/// well as request stops, "D" marks a call that only sets down and "U" one
+/// that only picks up. Without codes, both boarding and alighting are
+/// assumed.
+fn apply_activities(call: &mut Call, activities: Option<&str>) {
+    call.is_request_stop = is_request_stop(activities, &call.station);
+    if let Some(codes) = activities {
+        call.is_set_down_only = has_activity(codes, b"D");
+        call.is_pick_up_only = has_activity(codes, b"U");
+    }
+}
+
+/// Fill in a call's times from the staff API's arrival/departure pairs.
+///
+/// `anchor` is the rollover-corrected scheduled time for this calling point
+/// (see [`scheduled_time_of`]); each time is placed on whichever day puts it
+/// closest to the anchor.
+fn apply_staff_times(call: &mut Call, cp: &CallingPoint, anchor: RailTime) {
+    call.booked_arrival = cp.sta.as_deref().and_then(|t| parse_clock_near(t, anchor));
+    call.booked_departure = cp.std.as_deref().and_then(|t| parse_clock_near(t, anchor));
+
+    if let Some(sta) = call.booked_arrival {
+        let realtime = cp.ata.as_deref().or(cp.eta.as_deref());
+        call.realtime_arrival = parse_expected_time(realtime, &sta);
+    }
+    if let Some(std) = call.booked_departure {
+        let realtime = cp.atd.as_deref().or(cp.etd.as_deref());
+        call.realtime_departure = parse_expected_time(realtime, &std);
+    }
+}
+
+/// The time used to order a calling point for rollover detection.
+///
+/// Staff responses carry explicit departure/arrival times (departure
+/// preferred, as with `st` for intermediate stops); public responses only
+/// have `st`.
+fn scheduled_time_of(cp: &CallingPoint) -> Option<&str> {
+    cp.std
+        .as_deref()
+        .or(cp.sta.as_deref())
+        .or(cp.st.as_deref())
+        .map(clock)
+}
+
+/// Reduce a Darwin time to "HH:MM".
+///
+/// The public API sends "HH:MM"; the staff API sends ISO datetimes such as
+/// "2024-03-15T10:45:00". Date handling is left to rollover detection, so
+/// only the clock part is kept.
+fn clock(s: &str) -> &str {
+    match s.split_once('T') {
+        Some((_, time)) if time.len() >= 5 => &time[..5],
+        _ => s,
+    }
+}
+
+/// Parse a Darwin time (either format) on the given date.
+fn parse_clock(s: &str, date: NaiveDate) -> Result<RailTime, TimeError> {
+    RailTime::parse_hhmm(clock(s), date)
+}
+
+/// Parse a Darwin time on whichever day puts it within 12 hours of `anchor`.
+fn parse_clock_near(s: &str, anchor: RailTime) -> Option<RailTime> {
+    let t = parse_clock(s, anchor.date()).ok()?;
+    let diff = t.signed_duration_since(anchor);
+    if diff > Duration::hours(12) {
+        t.checked_sub(Duration::days(1))
+    } else if diff < -Duration::hours(12) {
+        t.checked_add(Duration::days(1))
+    } else {
+        Some(t)
+    }
+}
+
 /// Create the Call for the board station itself.
 fn create_board_station_call(
     item: &ServiceItemWithCallingPoints,
@@ -549,10 +934,21 @@ fn create_board_station_call(
     board_date: NaiveDate,
 ) -> Result<Call, ConversionError> {
     let mut call = Call::new(*board_crs, board_station_name.to_string());
+    apply_activities(&mut call, item.activities.as_deref());
+
+    // The service is dated by its departure, so an arrival either side of
+    // midnight (in at 23:58, out at 00:02) goes on whichever day is closer
+    let departure = item
+        .std
+        .as_deref()
+        .and_then(|std| parse_clock(std, board_date).ok());
 
     // Parse arrival time (sta/eta) if present
     if let Some(sta) = &item.sta
-        && let Ok(t) = RailTime::parse_hhmm(sta, board_date)
+        && let Some(t) = match departure {
+            Some(departure) => parse_clock_near(sta, departure),
+            None => parse_clock(sta, board_date).ok(),
+        }
     {
         call.booked_arrival = Some(t);
 
@@ -563,9 +959,7 @@ fn create_board_station_call(
     }
 
     // Parse departure time (std/etd)
-    if let Some(std) = &item.std
-        && let Ok(t) = RailTime::parse_hhmm(std, board_date)
-    {
+    if let Some(t) = departure {
         call.booked_departure = Some(t);
 
         // Parse expected departure
@@ -576,6 +970,8 @@ fn create_board_station_call(
 
     call.platform = item.platform.clone();
     call.is_cancelled = item.is_cancelled.unwrap_or(false);
+    call.delay_reason = item.delay_reason.clone();
+    call.cancel_reason = item.cancel_reason.clone();
 
     Ok(call)
 }
@@ -583,7 +979,7 @@ fn create_board_station_call(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::darwin::types::{ArrayOfCallingPoints, ServiceLocation};
+    use crate::darwin::types::{ArrayOfCallingPoints, CoachData, Formation, ServiceLocation};
 
     fn date() -> NaiveDate {
         NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
@@ -596,10 +992,17 @@ mod tests {
             st: Some(st.to_string()),
             et: None,
             at: None,
+            sta: None,
+            eta: None,
+            ata: None,
+            std: None,
+            etd: None,
+            atd: None,
             is_cancelled: None,
             length: None,
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         }
     }
 
@@ -612,6 +1015,8 @@ mod tests {
         ServiceItemWithCallingPoints {
             service_id: service_id.to_string(),
             rsid: None,
+            uid: None,
+            trainid: None,
             sta: None,
             eta: None,
             std: Some(std.to_string()),
@@ -622,6 +1027,7 @@ mod tests {
             is_cancelled: Some(false),
             service_type: None,
             length: None,
+            formation: None,
             origin: None,
             destination: Some(vec![ServiceLocation {
                 location_name: destination_name.to_string(),
@@ -633,6 +1039,7 @@ mod tests {
             subsequent_calling_points: None,
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         }
     }
 
@@ -686,6 +1093,32 @@ mod tests {
         assert_eq!(result.service.calls[3].station, Crs::parse("BRI").unwrap());
     }
 
+    #[test]
+    fn reasons_are_kept_on_their_calls() {
+        let mut item = make_service_item("ABC123", "10:00", "BRI", "Bristol Temple Meads");
+        item.delay_reason = Some("106".to_string());
+        let mut cancelled = make_calling_point("Bristol Temple Meads", "BRI", "11:30");
+        cancelled.is_cancelled = Some(true);
+        cancelled.cancel_reason = Some("A fault with the signalling system".to_string());
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: vec![make_calling_point("Reading", "RDG", "10:25"), cancelled],
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        let calls = &result.service.calls;
+        assert_eq!(calls[0].delay_reason.as_deref(), Some("106"));
+        assert_eq!(calls[1].delay_reason, None);
+        assert_eq!(
+            calls[2].cancel_reason.as_deref(),
+            Some("A fault with the signalling system")
+        );
+    }
+
     #[test]
     fn convert_service_with_previous_calls() {
         let mut item = make_service_item("ABC123", "10:27", "BRI", "Bristol Temple Meads");
@@ -776,6 +1209,464 @@ mod tests {
         assert!(result.candidate.is_delayed());
     }
 
+    fn make_staff_calling_point(name: &str, crs: &str, sta: &str, std: &str) -> CallingPoint {
+        let mut cp = make_calling_point(name, crs, "00:00");
+        cp.st = None;
+        cp.sta = (!sta.is_empty()).then(|| format!("2024-03-15T{sta}:00"));
+        cp.std = (!std.is_empty()).then(|| format!("2024-03-15T{std}:00"));
+        cp
+    }
+
+    #[test]
+    fn staff_calling_points_keep_arrival_and_departure() {
+        let mut item = make_service_item("ABC123", "2024-03-15T10:00:00", "BRI", "Bristol");
+        item.etd = Some("2024-03-15T10:02:00".to_string());
+        let mut reading = make_staff_calling_point("Reading", "RDG", "10:23", "10:25");
+        reading.etd = Some("2024-03-15T10:28:00".to_string());
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: vec![
+                reading,
+                make_staff_calling_point("Bristol Temple Meads", "BRI", "11:30", ""),
+            ],
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        let board = &result.service.calls[0];
+        assert_eq!(board.booked_departure.unwrap().to_string(), "10:00");
+        assert_eq!(board.realtime_departure.unwrap().to_string(), "10:02");
+
+        let rdg = &result.service.calls[1];
+        assert_eq!(rdg.booked_arrival.unwrap().to_string(), "10:23");
+        assert_eq!(rdg.booked_departure.unwrap().to_string(), "10:25");
+        assert_eq!(rdg.realtime_departure.unwrap().to_string(), "10:28");
+        assert!(rdg.realtime_arrival.is_none());
+
+        let bri = &result.service.calls[2];
+        assert_eq!(bri.booked_arrival.unwrap().to_string(), "11:30");
+        assert!(bri.booked_departure.is_none());
+    }
+
+    #[test]
+    fn marks_request_stops() {
+        let mut item = make_service_item("ABC123", "10:00", "SWA", "Swansea");
+        let mut reading = make_staff_calling_point("Reading", "RDG", "10:23", "10:25");
+        reading.activities = Some("T R ".to_string());
+        let mut sugar_loaf = make_staff_calling_point("Sugar Loaf", "SUG", "10:40", "10:41");
+        sugar_loaf.activities = Some("T ".to_string());
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: vec![
+                reading,
+                sugar_loaf,
+                make_calling_point("Llangynllo", "LGO", "11:00"),
+                make_calling_point("Swansea", "SWA", "12:00"),
+            ],
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        let request_stops: Vec<_> = result
+            .service
+            .calls
+            .iter()
+            .map(|call| call.is_request_stop)
+            .collect();
+        // Darwin's activity codes win over the bundled list, which covers
+        // calls without them
+        assert_eq!(request_stops, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn marks_set_down_and_pick_up_only_calls() {
+        let mut item = make_service_item("ABC123", "10:00", "SWA", "Swansea");
+        item.activities = Some("TBU ".to_string());
+        let mut reading = make_staff_calling_point("Reading", "RDG", "10:23", "10:25");
+        reading.activities = Some("U ".to_string());
+        let mut bristol = make_staff_calling_point("Bristol Parkway", "BPW", "11:00", "11:02");
+        bristol.activities = Some("D T ".to_string());
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: vec![
+                reading,
+                bristol,
+                make_calling_point("Swansea", "SWA", "12:00"),
+            ],
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        let access: Vec<_> = result
+            .service
+            .calls
+            .iter()
+            .map(|call| (call.can_board(), call.can_alight()))
+            .collect();
+        assert_eq!(
+            access,
+            vec![(true, false), (true, false), (false, true), (true, true),]
+        );
+    }
+
+    #[test]
+    fn staff_calling_point_arrival_before_midnight() {
+        let mut item = make_service_item("ABC123", "23:40", "EDB", "Edinburgh");
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: vec![make_staff_calling_point("York", "YRK", "23:58", "00:02")],
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+
+        let board_crs = Crs::parse("KGX").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Kings Cross", date()).unwrap();
+
+        let yrk = &result.service.calls[1];
+        assert_eq!(yrk.booked_arrival.unwrap().date(), date());
+        assert_eq!(
+            yrk.booked_departure.unwrap().date(),
+            date().succ_opt().unwrap()
+        );
+    }
+
+    #[test]
+    fn staff_fields_preferred_for_headcode_uid_and_coaches() {
+        let mut item = make_service_item("ABC123", "10:00", "BRI", "Bristol Temple Meads");
+        item.rsid = Some("GW1A2300".to_string());
+        item.trainid = Some("2B45".to_string());
+        item.uid = Some("P12345".to_string());
+        item.length = Some(5);
+        item.formation = Some(Formation {
+            coaches: Some(
+                ["A", "B", "C", "D", "E", "F", "G", "H", "J"]
+                    .iter()
+                    .map(|n| CoachData {
+                        number: Some(n.to_string()),
+                        coach_class: None,
+                    })
+                    .collect(),
+            ),
+        });
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        assert_eq!(result.service.headcode.unwrap().as_str(), "2B45");
+        assert_eq!(result.uid.unwrap().as_str(), "P12345");
+        assert_eq!(result.coaches, Some(9));
+    }
+
+    /// Waterloo to Portsmouth Harbour, with a Southampton portion dividing
+    /// at Woking.
+    fn dividing_service() -> ServiceItemWithCallingPoints {
+        let mut item = make_service_item("ABC123", "10:00", "PMH", "Portsmouth Harbour");
+        item.length = Some(10);
+        let main = vec![
+            make_calling_point("Woking", "WOK", "10:25"),
+            make_calling_point("Guildford", "GLD", "10:40"),
+            make_calling_point("Portsmouth Harbour", "PMH", "11:35"),
+        ];
+        let mut portion = vec![
+            make_calling_point("Woking", "WOK", "10:25"),
+            make_calling_point("Basingstoke", "BSK", "10:50"),
+            make_calling_point("Southampton Central", "SOU", "11:30"),
+        ];
+        portion[1].length = Some(4);
+        item.subsequent_calling_points = Some(
+            [main, portion]
+                .into_iter()
+                .map(|calling_point| ArrayOfCallingPoints {
+                    calling_point,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        item
+    }
+
+    #[test]
+    fn portions_become_divisions() {
+        let item = dividing_service();
+        let board_crs = Crs::parse("WAT").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Waterloo", date()).unwrap();
+
+        // The service's own calls are the front portion's
+        assert_eq!(result.service.calls.len(), 4);
+        let formation = result.service.formation.unwrap();
+        assert_eq!(formation.length(), Some(10));
+        assert_eq!(formation.portions.len(), 1);
+        let portion = &formation.portions[0];
+        assert_eq!(portion.divides_at.as_str(), "WOK");
+        assert_eq!(portion.destination.as_str(), "SOU");
+        assert_eq!(portion.destination_name, "Southampton Central");
+        assert_eq!(portion.remaining_coaches, Some(6));
+
+        // Calling points after the division, with Southampton as terminus
+        let stations: Vec<_> = portion.calls.iter().map(|c| c.station.as_str()).collect();
+        assert_eq!(stations, vec!["BSK", "SOU"]);
+        assert_eq!(
+            portion.calls[1].booked_arrival.map(|t| t.to_string()),
+            Some("11:30".to_string())
+        );
+    }
+
+    #[test]
+    fn portion_without_known_division_left_out() {
+        let mut item = dividing_service();
+        item.subsequent_calling_points.as_mut().unwrap()[1]
+            .calling_point
+            .remove(0);
+
+        let board_crs = Crs::parse("WAT").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Waterloo", date()).unwrap();
+
+        assert!(!result.service.formation.unwrap().divides());
+    }
+
+    #[test]
+    fn remaining_coaches_from_main_length_after_division() {
+        let mut item = dividing_service();
+        item.subsequent_calling_points.as_mut().unwrap()[0].calling_point[1].length = Some(5);
+
+        let board_crs = Crs::parse("WAT").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Waterloo", date()).unwrap();
+
+        let formation = result.service.formation.unwrap();
+        assert_eq!(formation.portions[0].remaining_coaches, Some(5));
+    }
+
+    #[test]
+    fn no_formation_without_data() {
+        let item = make_service_item("ABC123", "10:00", "BRI", "Bristol Temple Meads");
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        assert!(result.service.formation.is_none());
+        assert_eq!(result.coaches, None);
+    }
+
+    #[test]
+    fn public_fields_used_without_staff_data() {
+        let mut item = make_service_item("ABC123", "10:00", "BRI", "Bristol Temple Meads");
+        item.rsid = Some("GW1A2300".to_string());
+        item.length = Some(5);
+
+        let board_crs = Crs::parse("PAD").unwrap();
+        let result = convert_service_item(&item, &board_crs, "London Paddington", date()).unwrap();
+
+        assert_eq!(result.service.headcode.unwrap().as_str(), "1A23");
+        assert!(result.uid.is_none());
+        assert_eq!(result.coaches, Some(5));
+    }
+
+    fn board(services: Vec<ServiceItemWithCallingPoints>) -> StationBoardWithDetails {
+        StationBoardWithDetails {
+            generated_at: "2024-03-15T23:50:00".to_string(),
+            location_name: "London Kings Cross".to_string(),
+            crs: "KGX".to_string(),
+            train_services: Some(services),
+            bus_services: None,
+            ferry_services: None,
+            platform_available: Some(true),
+            are_services_available: Some(true),
+            nrcc_messages: None,
+        }
+    }
+
+    fn with_calls(
+        mut item: ServiceItemWithCallingPoints,
+        calls: &[(&str, &str, &str)],
+    ) -> ServiceItemWithCallingPoints {
+        item.subsequent_calling_points = Some(vec![ArrayOfCallingPoints {
+            calling_point: calls
+                .iter()
+                .map(|(name, crs, st)| make_calling_point(name, crs, st))
+                .collect(),
+            service_type: None,
+            service_change_required: None,
+            assoc_is_cancelled: None,
+        }]);
+        item
+    }
+
+    #[test]
+    fn board_spanning_midnight_dates_each_service() {
+        let next_day = date().succ_opt().unwrap();
+        let board = board(vec![
+            with_calls(
+                make_service_item("TONIGHT", "23:55", "PBO", "Peterborough"),
+                &[("Peterborough", "PBO", "00:40")],
+            ),
+            with_calls(
+                make_service_item("TOMORROW", "00:10", "CBG", "Cambridge"),
+                &[("Cambridge", "CBG", "01:05")],
+            ),
+        ]);
+        let window = BoardWindow::at(date(), 23 * 60 + 50, 0, 120);
+
+        let services = convert_station_board_in(&board, &window).unwrap();
+
+        let tonight = &services[0];
+        assert_eq!(tonight.candidate.scheduled_departure.date(), date());
+        assert_eq!(
+            tonight.service.calls[1].booked_arrival.unwrap().date(),
+            next_day
+        );
+
+        let tomorrow = &services[1];
+        assert_eq!(tomorrow.candidate.scheduled_departure.date(), next_day);
+        assert_eq!(
+            tomorrow.service.calls[0].booked_departure.unwrap().date(),
+            next_day
+        );
+        assert_eq!(
+            tomorrow.service.calls[1].booked_arrival.unwrap().date(),
+            next_day
+        );
+        assert!(tonight.candidate.scheduled_departure < tomorrow.candidate.scheduled_departure);
+    }
+
+    #[test]
+    fn board_quirks_are_normalized() {
+        let mut board = board(vec![with_calls(
+            make_service_item("DIVIDES", "10:00", "BTN", "Brighton"),
+            &[
+                ("Haywards Heath", "HHE", "10:40"),
+                ("Haywards Heath", "HHE", "10:45"),
+                ("Brighton", "BTN", "11:05"),
+            ],
+        )]);
+        board.platform_available = Some(false);
+        let window = BoardWindow::at(date(), 9 * 60 + 50, 0, 120);
+
+        let services = convert_station_board_in(&board, &window).unwrap();
+
+        let service = &services[0];
+        assert_eq!(service.candidate.platform, None);
+        assert_eq!(service.service.calls[0].platform, None);
+        let stations: Vec<&str> = service
+            .service
+            .calls
+            .iter()
+            .map(|c| c.station.as_str())
+            .collect();
+        assert_eq!(stations, ["KGX", "HHE", "BTN"]);
+    }
+
+    #[test]
+    fn board_just_after_midnight_keeps_late_train_on_previous_day() {
+        let next_day = date().succ_opt().unwrap();
+        let mut late = make_service_item("LATE", "23:58", "PBO", "Peterborough");
+        late.etd = Some("00:07".to_string());
+        let board = board(vec![late]);
+        let window = BoardWindow::at(next_day, 5, 0, 120);
+
+        let services = convert_station_board_in(&board, &window).unwrap();
+
+        let candidate = &services[0].candidate;
+        assert_eq!(candidate.scheduled_departure.date(), date());
+        assert_eq!(candidate.expected_departure.unwrap().date(), next_day);
+    }
+
+    #[test]
+    fn board_call_arriving_before_midnight_and_leaving_after() {
+        let mut item = make_service_item("SLEEPER", "00:02", "INV", "Inverness");
+        item.sta = Some("23:58".to_string());
+        let window = BoardWindow::at(date(), 23 * 60 + 50, 0, 120);
+
+        let services = convert_station_board_in(&board(vec![item]), &window).unwrap();
+
+        let call = &services[0].service.calls[0];
+        assert_eq!(call.booked_arrival.unwrap().date(), date());
+        assert_eq!(
+            call.booked_departure.unwrap().date(),
+            date().succ_opt().unwrap()
+        );
+    }
+
+    #[test]
+    fn lenient_boards_report_skipped_services() {
+        let mut broken = make_service_item("BROKEN", "10:05", "CBG", "Cambridge");
+        broken.std = None;
+        let board = board(vec![
+            make_service_item("GOOD", "10:00", "PBO", "Peterborough"),
+            broken,
+        ]);
+        let window = BoardWindow::at(date(), 9 * 60 + 50, 0, 120);
+
+        let report =
+            convert_station_board_report(&board, &window, ConversionMode::Lenient).unwrap();
+
+        assert!(!report.is_complete());
+        assert_eq!(report.services.len(), 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].service_id, "BROKEN");
+        assert!(matches!(
+            report.skipped[0].reason,
+            ConversionError::MissingField(_)
+        ));
+        assert_eq!(convert_station_board_in(&board, &window).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn strict_boards_fail_on_a_skipped_service() {
+        let mut broken = make_service_item("BROKEN", "10:05", "CBG", "Cambridge");
+        broken.std = Some("soon".to_string());
+        let window = BoardWindow::at(date(), 9 * 60 + 50, 0, 120);
+
+        let good = board(vec![make_service_item(
+            "GOOD",
+            "10:00",
+            "PBO",
+            "Peterborough",
+        )]);
+        let report = convert_station_board_report(&good, &window, ConversionMode::Strict).unwrap();
+        assert!(report.is_complete());
+
+        let bad = board(vec![
+            make_service_item("GOOD", "10:00", "PBO", "Peterborough"),
+            broken,
+        ]);
+        match convert_station_board_report(&bad, &window, ConversionMode::Strict) {
+            Err(ConversionError::SkippedService { service_id, reason }) => {
+                assert_eq!(service_id, "BROKEN");
+                assert!(matches!(*reason, ConversionError::InvalidTime(_)));
+            }
+            other => panic!("expected a skipped service error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn board_date_alone_puts_every_service_on_that_date() {
+        let board = board(vec![make_service_item(
+            "TOMORROW",
+            "00:10",
+            "CBG",
+            "Cambridge",
+        )]);
+
+        let services = convert_station_board(&board, date()).unwrap();
+
+        assert_eq!(services[0].candidate.scheduled_departure.date(), date());
+    }
+
+    #[test]
+    fn clock_strips_iso_date() {
+        assert_eq!(clock("2024-03-15T10:45:00"), "10:45");
+        assert_eq!(clock("10:45"), "10:45");
+        assert_eq!(clock("On time"), "On time");
+    }
+
     #[test]
     fn parse_expected_time_on_time() {
         let scheduled = RailTime::parse_hhmm("10:00", date()).unwrap();
@@ -949,10 +1840,17 @@ mod fixed_behavior_tests {
             st: Some(st.to_string()),
             et: None,
             at: None,
+            sta: None,
+            eta: None,
+            ata: None,
+            std: None,
+            etd: None,
+            atd: None,
             is_cancelled: None,
             length: None,
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         }
     }
 
@@ -969,6 +1867,8 @@ mod fixed_behavior_tests {
         let item = ServiceItemWithCallingPoints {
             service_id: "NIGHT".to_string(),
             rsid: None,
+            uid: None,
+            trainid: None,
             sta: Some("23:45".to_string()),
             eta: Some("On time".to_string()),
             std: Some("23:50".to_string()), // Departure at 23:50
@@ -979,6 +1879,7 @@ mod fixed_behavior_tests {
             is_cancelled: Some(false),
             service_type: None,
             length: None,
+            formation: None,
             origin: None,
             destination: Some(vec![ServiceLocation {
                 location_name: "Edinburgh".to_string(),
@@ -998,6 +1899,7 @@ mod fixed_behavior_tests {
             }]),
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         };
 
         // Board at York at 23:50
@@ -1033,6 +1935,8 @@ mod fixed_behavior_tests {
         let item = ServiceItemWithCallingPoints {
             service_id: "ABC".to_string(),
             rsid: None,
+            uid: None,
+            trainid: None,
             sta: None,
             eta: None,
             std: Some("10:00".to_string()),
@@ -1043,6 +1947,7 @@ mod fixed_behavior_tests {
             is_cancelled: Some(false),
             service_type: None,
             length: None,
+            formation: None,
             origin: None,
             destination: Some(vec![ServiceLocation {
                 location_name: "Bristol".to_string(),
@@ -1062,6 +1967,7 @@ mod fixed_behavior_tests {
             }]),
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         };
 
         let board_crs = Crs::parse("PAD").unwrap();
@@ -1103,6 +2009,8 @@ mod fixed_behavior_tests {
         let item = ServiceItemWithCallingPoints {
             service_id: "ARR123".to_string(),
             rsid: Some("LE1P2300".to_string()),
+            uid: None,
+            trainid: None,
             sta: Some("09:15".to_string()), // Scheduled arrival
             eta: Some("09:20".to_string()), // Expected arrival (delayed)
             std: None,                      // No departure - arrivals board
@@ -1113,6 +2021,7 @@ mod fixed_behavior_tests {
             is_cancelled: Some(false),
             service_type: None,
             length: None,
+            formation: None,
             origin: Some(vec![ServiceLocation {
                 location_name: "Norwich".to_string(),
                 crs: "NRW".to_string(),
@@ -1146,6 +2055,7 @@ mod fixed_behavior_tests {
             }]),
             cancel_reason: None,
             delay_reason: None,
+            activities: None,
         };
 
         let board_crs = Crs::parse("SRA").unwrap();
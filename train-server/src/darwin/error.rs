@@ -2,6 +2,8 @@
 
 use std::fmt;
 
+use crate::error::{Classify, ErrorKind};
+
 /// Errors from the Darwin HTTP client.
 #[derive(Debug)]
 pub enum DarwinError {
@@ -28,6 +30,38 @@ pub enum DarwinError {
 
     /// Feature not configured or not available
     NotConfigured(String),
+
+    /// Request parameters outside Darwin's limits (caught before sending)
+    InvalidRequest(String),
+}
+
+impl DarwinError {
+    /// Whether this error means the credentials were rejected (401 or 403).
+    pub fn is_auth_error(&self) -> bool {
+        matches!(
+            self,
+            DarwinError::Unauthorized
+                | DarwinError::ApiError {
+                    status: 401 | 403,
+                    ..
+                }
+        )
+    }
+}
+
+impl Classify for DarwinError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DarwinError::Http(_) => ErrorKind::Transient,
+            DarwinError::Json { .. } => ErrorKind::Permanent,
+            DarwinError::ApiError { status, .. } => ErrorKind::from_status(*status),
+            DarwinError::ServiceNotFound => ErrorKind::NotFound,
+            DarwinError::RateLimited => ErrorKind::RateLimited,
+            DarwinError::Unauthorized
+            | DarwinError::NotConfigured(_)
+            | DarwinError::InvalidRequest(_) => ErrorKind::Config,
+        }
+    }
 }
 
 impl fmt::Display for DarwinError {
@@ -50,6 +84,7 @@ impl fmt::Display for DarwinError {
             DarwinError::RateLimited => write!(f, "rate limited by Darwin API"),
             DarwinError::Unauthorized => write!(f, "unauthorized (invalid API key)"),
             DarwinError::NotConfigured(msg) => write!(f, "not configured: {msg}"),
+            DarwinError::InvalidRequest(msg) => write!(f, "invalid request: {msg}"),
         }
     }
 }
@@ -91,4 +126,45 @@ mod tests {
         assert!(err.to_string().contains("JSON parse error"));
         assert!(err.to_string().contains("expected string"));
     }
+
+    #[test]
+    fn auth_errors() {
+        assert!(DarwinError::Unauthorized.is_auth_error());
+        assert!(
+            DarwinError::ApiError {
+                status: 403,
+                message: "Forbidden".into(),
+            }
+            .is_auth_error()
+        );
+        assert!(
+            !DarwinError::ApiError {
+                status: 500,
+                message: "Internal Server Error".into(),
+            }
+            .is_auth_error()
+        );
+        assert!(!DarwinError::RateLimited.is_auth_error());
+    }
+
+    #[test]
+    fn error_kinds() {
+        assert_eq!(DarwinError::ServiceNotFound.kind(), ErrorKind::NotFound);
+        assert_eq!(DarwinError::RateLimited.kind(), ErrorKind::RateLimited);
+        assert_eq!(DarwinError::Unauthorized.kind(), ErrorKind::Config);
+        assert_eq!(
+            DarwinError::Json {
+                message: "bad".into(),
+                body: None,
+            }
+            .kind(),
+            ErrorKind::Permanent
+        );
+        let err = DarwinError::ApiError {
+            status: 503,
+            message: "Service Unavailable".into(),
+        };
+        assert_eq!(err.kind(), ErrorKind::Transient);
+        assert!(err.is_retryable());
+    }
 }
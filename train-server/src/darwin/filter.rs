@@ -0,0 +1,203 @@
+//! Filters over converted departure boards.
+//!
+//! A terminus board can list dozens of trains across twenty-odd platforms,
+//! which is a lot to scroll through on a phone. [`BoardFilter`] narrows a
+//! board to the platforms, operator or destination the user cares about,
+//! after conversion so it works the same for every board source.
+
+use std::sync::Arc;
+
+use super::convert::ConvertedService;
+
+/// Criteria a departure must meet to stay on the board.
+///
+/// Every criterion is optional; an empty filter keeps everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoardFilter {
+    platforms: Vec<String>,
+    operator: Option<String>,
+    destination: Option<String>,
+}
+
+impl BoardFilter {
+    /// A filter that keeps every departure.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep departures from any of these platforms (e.g. "1,2,3").
+    ///
+    /// Platforms are compared exactly, ignoring case, so "1" doesn't match
+    /// "10" or "1A". Services with no platform yet are dropped.
+    pub fn with_platforms(mut self, platforms: &str) -> Self {
+        self.platforms = platforms
+            .split(',')
+            .map(|p| p.trim().to_uppercase())
+            .filter(|p| !p.is_empty())
+            .collect();
+        self
+    }
+
+    /// Keep departures run by an operator, by ATOC code ("GW") or part of
+    /// its name ("great western"), ignoring case.
+    pub fn with_operator(mut self, operator: &str) -> Self {
+        self.operator = non_empty_lowercase(operator);
+        self
+    }
+
+    /// Keep departures whose destination name contains `destination`,
+    /// ignoring case.
+    pub fn with_destination(mut self, destination: &str) -> Self {
+        self.destination = non_empty_lowercase(destination);
+        self
+    }
+
+    /// Whether the filter keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.platforms.is_empty() && self.operator.is_none() && self.destination.is_none()
+    }
+
+    /// Whether a departure meets every criterion.
+    pub fn matches(&self, service: &ConvertedService) -> bool {
+        let candidate = &service.candidate;
+
+        let platform_ok = self.platforms.is_empty()
+            || candidate
+                .platform
+                .as_deref()
+                .is_some_and(|p| self.platforms.contains(&p.trim().to_uppercase()));
+
+        let operator_ok = self.operator.as_deref().is_none_or(|wanted| {
+            candidate.operator.to_lowercase().contains(wanted)
+                || candidate
+                    .operator_code
+                    .is_some_and(|code| code.as_str().eq_ignore_ascii_case(wanted))
+        });
+
+        let destination_ok = self
+            .destination
+            .as_deref()
+            .is_none_or(|wanted| candidate.destination.to_lowercase().contains(wanted));
+
+        platform_ok && operator_ok && destination_ok
+    }
+
+    /// The departures that meet every criterion, in board order.
+    pub fn apply(&self, services: Vec<Arc<ConvertedService>>) -> Vec<Arc<ConvertedService>> {
+        if self.is_empty() {
+            return services;
+        }
+        services.into_iter().filter(|s| self.matches(s)).collect()
+    }
+}
+
+fn non_empty_lowercase(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        AtocCode, Call, CallIndex, Crs, RailTime, Service, ServiceCandidate, ServiceRef,
+    };
+    use chrono::NaiveDate;
+
+    fn departure(
+        id: &str,
+        platform: Option<&str>,
+        operator: &str,
+        code: &str,
+        destination: &str,
+    ) -> Arc<ConvertedService> {
+        let kgx = Crs::parse("KGX").unwrap();
+        let time =
+            RailTime::parse_hhmm("10:00", NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap();
+        let service_ref = ServiceRef::new(id.to_string(), kgx);
+        let operator_code = AtocCode::parse(code).ok();
+        let mut call = Call::new(kgx, "London Kings Cross".to_string());
+        call.booked_departure = Some(time);
+
+        Arc::new(ConvertedService {
+            candidate: ServiceCandidate {
+                service_ref: service_ref.clone(),
+                headcode: None,
+                scheduled_departure: time,
+                expected_departure: None,
+                destination: destination.to_string(),
+                destination_crs: None,
+                operator: operator.to_string(),
+                operator_code,
+                platform: platform.map(String::from),
+                is_cancelled: false,
+            },
+            service: Service {
+                service_ref,
+                headcode: None,
+                operator: operator.to_string(),
+                operator_code,
+                calls: vec![call],
+                board_station_idx: CallIndex(0),
+                formation: None,
+            },
+            uid: None,
+            coaches: None,
+        })
+    }
+
+    fn board() -> Vec<Arc<ConvertedService>> {
+        vec![
+            departure("a", Some("1"), "LNER", "GR", "Edinburgh"),
+            departure("b", Some("10"), "Great Northern", "GN", "Cambridge"),
+            departure("c", Some("1A"), "Thameslink", "TL", "Cambridge North"),
+            departure("d", None, "Grand Central", "GC", "Sunderland"),
+        ]
+    }
+
+    fn ids(services: &[Arc<ConvertedService>]) -> Vec<&str> {
+        services
+            .iter()
+            .map(|s| s.service.service_ref.darwin_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn empty_filter_keeps_everything() {
+        let filter = BoardFilter::new()
+            .with_platforms(" , ")
+            .with_operator("  ")
+            .with_destination("");
+        assert!(filter.is_empty());
+        assert_eq!(filter.apply(board()).len(), 4);
+    }
+
+    #[test]
+    fn platforms_match_exactly() {
+        let filter = BoardFilter::new().with_platforms("1, 1a");
+        assert_eq!(ids(&filter.apply(board())), ["a", "c"]);
+    }
+
+    #[test]
+    fn operator_by_code_or_name() {
+        let by_code = BoardFilter::new().with_operator("gn");
+        assert_eq!(ids(&by_code.apply(board())), ["b"]);
+
+        let by_name = BoardFilter::new().with_operator("grand");
+        assert_eq!(ids(&by_name.apply(board())), ["d"]);
+    }
+
+    #[test]
+    fn destination_substring() {
+        let filter = BoardFilter::new().with_destination("cambridge");
+        assert_eq!(ids(&filter.apply(board())), ["b", "c"]);
+    }
+
+    #[test]
+    fn criteria_combine() {
+        let filter = BoardFilter::new()
+            .with_destination("Cambridge")
+            .with_platforms("1A");
+        assert_eq!(ids(&filter.apply(board())), ["c"]);
+    }
+}
@@ -0,0 +1,438 @@
+//! Per-call instrumentation of Darwin requests.
+//!
+//! Every request that reaches Darwin (i.e. misses the cache) counts against
+//! the API quota. [`InstrumentedDarwin`] wraps [`DarwinClientImpl`] and
+//! records, for each call, the operation, station, latency, response size
+//! and number of services, both as a structured log line and as totals in
+//! [`DarwinMetrics`].
+//!
+//! Calls are attributed to the search phase that made them (identifying a
+//! train, each phase of planning, a board search...) through
+//! [`in_phase`](crate::planner::phase::in_phase), so quota use can be
+//! traced back to the part of a search that spent it.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::domain::Crs;
+use crate::planner::phase::current_phase;
+
+use super::DarwinClientImpl;
+use super::convert::ConvertedService;
+use super::error::DarwinError;
+use super::request::BoardWindow;
+use super::types::ServiceDetails;
+
+tokio::task_local! {
+    static RESPONSE_BYTES: Cell<Option<usize>>;
+}
+
+/// Note the size of a response body, for the call being instrumented.
+///
+/// Clients call this once they've read the body; it does nothing outside
+/// an instrumented call.
+pub(super) fn record_response_bytes(bytes: usize) {
+    let _ = RESPONSE_BYTES.try_with(|b| b.set(Some(b.get().unwrap_or(0) + bytes)));
+}
+
+/// Which Darwin operation a call made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operation {
+    Departures,
+    Arrivals,
+    ServiceDetails,
+}
+
+impl Operation {
+    /// Label for logs and metrics.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Departures => "departures",
+            Self::Arrivals => "arrivals",
+            Self::ServiceDetails => "service_details",
+        }
+    }
+}
+
+/// One instrumented call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRecord {
+    pub operation: Operation,
+    pub phase: &'static str,
+    pub crs: Option<Crs>,
+    pub latency: Duration,
+    /// Size of the response body, when the client reports it (the mock
+    /// client doesn't)
+    pub response_bytes: Option<usize>,
+    pub services: usize,
+    pub ok: bool,
+}
+
+/// Running totals for one operation in one phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallTotals {
+    pub calls: u64,
+    pub errors: u64,
+    pub latency_ms: u64,
+    pub response_bytes: u64,
+    pub services: u64,
+}
+
+/// A metric: name, help text and which total it reports.
+type Series = (&'static str, &'static str, fn(&CallTotals) -> u64);
+
+/// The metrics rendered for each operation and phase.
+const SERIES: [Series; 5] = [
+    ("darwin_calls_total", "Darwin API calls.", |t| t.calls),
+    (
+        "darwin_call_errors_total",
+        "Darwin API calls that failed.",
+        |t| t.errors,
+    ),
+    (
+        "darwin_call_latency_ms_total",
+        "Time spent waiting for Darwin, in milliseconds.",
+        |t| t.latency_ms,
+    ),
+    (
+        "darwin_response_bytes_total",
+        "Bytes received from Darwin.",
+        |t| t.response_bytes,
+    ),
+    (
+        "darwin_services_total",
+        "Services returned by Darwin.",
+        |t| t.services,
+    ),
+];
+
+/// Totals of every Darwin call, by operation and phase.
+#[derive(Debug, Default)]
+pub struct DarwinMetrics {
+    totals: Mutex<BTreeMap<(Operation, &'static str), CallTotals>>,
+}
+
+impl DarwinMetrics {
+    /// Add a call to the totals.
+    pub fn record(&self, call: &CallRecord) {
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = totals.entry((call.operation, call.phase)).or_default();
+        entry.calls += 1;
+        entry.errors += u64::from(!call.ok);
+        entry.latency_ms += u64::try_from(call.latency.as_millis()).unwrap_or(u64::MAX);
+        entry.response_bytes += call.response_bytes.unwrap_or(0) as u64;
+        entry.services += call.services as u64;
+    }
+
+    /// Totals for one operation in one phase.
+    pub fn totals(&self, operation: Operation, phase: &str) -> CallTotals {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        totals
+            .iter()
+            .find(|((op, p), _)| *op == operation && *p == phase)
+            .map(|(_, t)| *t)
+            .unwrap_or_default()
+    }
+
+    /// The totals in Prometheus text format.
+    pub fn render(&self) -> String {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut out = String::new();
+        for (name, help, value) in SERIES {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            for ((operation, phase), t) in totals.iter() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{operation=\"{}\",phase=\"{phase}\"}} {}",
+                    operation.as_str(),
+                    value(t)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// A Darwin client that logs and counts every call.
+///
+/// Every call is counted; successful calls are logged one in
+/// `log_every` (failures always are), since a busy instance makes
+/// thousands.
+#[derive(Clone)]
+pub struct InstrumentedDarwin {
+    inner: DarwinClientImpl,
+    metrics: Arc<DarwinMetrics>,
+    log_every: u64,
+    seen: Arc<AtomicU64>,
+}
+
+impl InstrumentedDarwin {
+    /// Instrument a client, logging every call.
+    pub fn new(inner: DarwinClientImpl) -> Self {
+        Self {
+            inner,
+            metrics: Default::default(),
+            log_every: 1,
+            seen: Default::default(),
+        }
+    }
+
+    /// Log one successful call in `every` (0 logs none).
+    pub fn with_log_sampling(mut self, every: u64) -> Self {
+        self.log_every = every;
+        self
+    }
+
+    /// The wrapped client.
+    pub fn inner(&self) -> &DarwinClientImpl {
+        &self.inner
+    }
+
+    /// Totals of the calls made so far.
+    pub fn metrics(&self) -> &DarwinMetrics {
+        &self.metrics
+    }
+
+    /// Get departure board with details for a station.
+    pub async fn get_departures_with_details(
+        &self,
+        crs: &Crs,
+        num_rows: u8,
+        window: BoardWindow,
+    ) -> Result<Vec<ConvertedService>, DarwinError> {
+        self.observe(
+            Operation::Departures,
+            Some(*crs),
+            self.inner
+                .get_departures_with_details(crs, num_rows, window),
+            |services| services.len(),
+        )
+        .await
+    }
+
+    /// Get arrival board with details for a station.
+    pub async fn get_arrivals_with_details(
+        &self,
+        crs: &Crs,
+        num_rows: u8,
+        window: BoardWindow,
+    ) -> Result<Vec<ConvertedService>, DarwinError> {
+        self.observe(
+            Operation::Arrivals,
+            Some(*crs),
+            self.inner.get_arrivals_with_details(crs, num_rows, window),
+            |services| services.len(),
+        )
+        .await
+    }
+
+    /// Get full service details by service ID.
+    pub async fn get_service_details(
+        &self,
+        service_id: &str,
+    ) -> Result<ServiceDetails, DarwinError> {
+        self.observe(
+            Operation::ServiceDetails,
+            None,
+            self.inner.get_service_details(service_id),
+            |_| 1,
+        )
+        .await
+    }
+
+    async fn observe<T>(
+        &self,
+        operation: Operation,
+        crs: Option<Crs>,
+        call: impl Future<Output = Result<T, DarwinError>>,
+        services: impl Fn(&T) -> usize,
+    ) -> Result<T, DarwinError> {
+        let started = Instant::now();
+        let (result, response_bytes) = RESPONSE_BYTES
+            .scope(Cell::new(None), async {
+                let result = call.await;
+                (result, RESPONSE_BYTES.with(Cell::get))
+            })
+            .await;
+
+        let record = CallRecord {
+            operation,
+            phase: current_phase(),
+            crs,
+            latency: started.elapsed(),
+            response_bytes,
+            services: result.as_ref().map_or(0, services),
+            ok: result.is_ok(),
+        };
+        self.metrics.record(&record);
+        self.log(&record, result.as_ref().err());
+
+        result
+    }
+
+    fn log(&self, call: &CallRecord, error: Option<&DarwinError>) {
+        let crs = call.crs.as_ref().map_or("-", Crs::as_str);
+        let latency_ms = call.latency.as_millis() as u64;
+        if let Some(error) = error {
+            warn!(
+                operation = call.operation.as_str(),
+                phase = call.phase,
+                crs,
+                latency_ms,
+                %error,
+                "Darwin call failed"
+            );
+            return;
+        }
+
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        if self.log_every != 0 && n.is_multiple_of(self.log_every) {
+            info!(
+                operation = call.operation.as_str(),
+                phase = call.phase,
+                crs,
+                latency_ms,
+                response_bytes = call.response_bytes,
+                services = call.services,
+                "Darwin call"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::darwin::MockDarwinClient;
+    use crate::planner::phase::{UNATTRIBUTED_PHASE, in_phase};
+    use chrono::NaiveDate;
+
+    fn client() -> InstrumentedDarwin {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        InstrumentedDarwin::new(DarwinClientImpl::Mock(mock))
+    }
+
+    fn window() -> BoardWindow {
+        BoardWindow::at(NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(), 600, 0, 120)
+    }
+
+    #[tokio::test]
+    async fn calls_are_counted_by_phase() {
+        let client = client();
+        let pad = Crs::parse("PAD").unwrap();
+
+        let services = in_phase(
+            "identify",
+            client.get_departures_with_details(&pad, 10, window()),
+        )
+        .await
+        .unwrap();
+        client
+            .get_departures_with_details(&pad, 10, window())
+            .await
+            .unwrap();
+
+        let identify = client.metrics().totals(Operation::Departures, "identify");
+        assert_eq!(identify.calls, 1);
+        assert_eq!(identify.errors, 0);
+        assert_eq!(identify.services, services.len() as u64);
+
+        let other = client
+            .metrics()
+            .totals(Operation::Departures, UNATTRIBUTED_PHASE);
+        assert_eq!(other.calls, 1);
+    }
+
+    #[tokio::test]
+    async fn failures_are_counted() {
+        let client = client();
+        let unknown = Crs::parse("XYZ").unwrap();
+
+        let result = in_phase(
+            "plan",
+            client.get_arrivals_with_details(&unknown, 10, window()),
+        )
+        .await;
+
+        assert!(result.is_err());
+        let totals = client.metrics().totals(Operation::Arrivals, "plan");
+        assert_eq!(totals.calls, 1);
+        assert_eq!(totals.errors, 1);
+        assert_eq!(totals.services, 0);
+    }
+
+    #[tokio::test]
+    async fn response_bytes_are_attributed_to_the_call_and_its_phase() {
+        use crate::darwin::{DarwinClient, DarwinConfig};
+        use crate::planner::phase::{BOARD_PHASE, in_phase};
+
+        let fixture = include_str!("../../tests/fixtures/elizabeth_line_zlw_departures.json");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let router = axum::Router::new().route(
+            "/api/20220120/GetDepBoardWithDetails/:crs",
+            axum::routing::get(move || async move { fixture }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let real = DarwinClient::new(DarwinConfig::new("key").with_base_url(url)).unwrap();
+        let client = InstrumentedDarwin::new(DarwinClientImpl::Real(real));
+        let zlw = Crs::parse("ZLW").unwrap();
+        let window = BoardWindow::at(NaiveDate::from_ymd_opt(2026, 1, 3).unwrap(), 720, 0, 120);
+
+        let services = in_phase(
+            BOARD_PHASE,
+            client.get_departures_with_details(&zlw, 10, window),
+        )
+        .await
+        .unwrap();
+
+        let totals = client.metrics().totals(Operation::Departures, BOARD_PHASE);
+        assert_eq!(totals.calls, 1);
+        assert_eq!(totals.response_bytes, fixture.len() as u64);
+        assert_eq!(totals.services, services.len() as u64);
+
+        // Outside a call there's nothing to attribute it to
+        record_response_bytes(99);
+        assert_eq!(
+            client
+                .metrics()
+                .totals(Operation::Departures, BOARD_PHASE)
+                .response_bytes,
+            fixture.len() as u64
+        );
+    }
+
+    #[test]
+    fn render_prometheus() {
+        let metrics = DarwinMetrics::default();
+        metrics.record(&CallRecord {
+            operation: Operation::Arrivals,
+            phase: "identify",
+            crs: Crs::parse("RDG").ok(),
+            latency: Duration::from_millis(120),
+            response_bytes: Some(2048),
+            services: 12,
+            ok: true,
+        });
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE darwin_calls_total counter"));
+        assert!(text.contains("darwin_calls_total{operation=\"arrivals\",phase=\"identify\"} 1"));
+        assert!(text.contains(
+            "darwin_response_bytes_total{operation=\"arrivals\",phase=\"identify\"} 2048"
+        ));
+        assert!(
+            text.contains("darwin_services_total{operation=\"arrivals\",phase=\"identify\"} 12")
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Station messages from Darwin boards.
+//!
+//! Boards carry NRCC messages: free text from National Rail about the
+//! station or the lines through it ("The escalators at London Bridge are out
+//! of service"). They come as HTML fragments, so they are reduced to plain
+//! text before being shown anywhere: tags are dropped (along with the
+//! contents of `script` and `style` elements), the common entities are
+//! decoded, and whitespace is collapsed.
+//!
+//! Messages are per station rather than per service, so they don't fit the
+//! converted services a board is turned into. Instead each client records a
+//! station's messages in a [`StationMessages`] whenever it parses its board,
+//! and they are read from there.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::Crs;
+
+use super::types::StationBoardWithDetails;
+
+/// How long a board's messages are shown after it was fetched.
+pub const MESSAGE_FRESHNESS: Duration = Duration::from_secs(15 * 60);
+
+/// A station's messages, as last fetched.
+#[derive(Debug)]
+struct Recorded {
+    messages: Vec<String>,
+    fetched: Instant,
+}
+
+/// The messages on each station's most recently fetched board.
+///
+/// Clones share the same messages.
+#[derive(Debug, Clone, Default)]
+pub struct StationMessages {
+    stations: Arc<Mutex<HashMap<Crs, Recorded>>>,
+}
+
+impl StationMessages {
+    /// Create an empty set of messages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the messages on a board just fetched for `station`,
+    /// replacing any from earlier boards.
+    pub fn record(&self, station: Crs, board: &StationBoardWithDetails) {
+        let recorded = Recorded {
+            messages: board_messages(board),
+            fetched: Instant::now(),
+        };
+        self.stations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(station, recorded);
+    }
+
+    /// The messages for `station`, if its board was fetched within
+    /// [`MESSAGE_FRESHNESS`]; empty otherwise.
+    pub fn get(&self, station: &Crs) -> Vec<String> {
+        let stations = self.stations.lock().unwrap_or_else(|e| e.into_inner());
+        stations
+            .get(station)
+            .filter(|recorded| recorded.fetched.elapsed() <= MESSAGE_FRESHNESS)
+            .map(|recorded| recorded.messages.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// A board's messages as plain text, without blanks or repeats.
+pub fn board_messages(board: &StationBoardWithDetails) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for message in board.nrcc_messages.iter().flatten() {
+        let text = strip_html(message.value.as_deref().unwrap_or_default());
+        if !text.is_empty() && !messages.contains(&text) {
+            messages.push(text);
+        }
+    }
+    messages
+}
+
+/// Reduce an HTML fragment to plain text.
+///
+/// Tags are removed, and `script` and `style` elements entirely. Entities
+/// for the usual escaped characters are decoded; others are kept as
+/// written. Runs of whitespace, including line breaks from `<br>` and
+/// `<p>`, become single spaces.
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    // Set while inside a script or style element, to its closing tag
+    let mut skip_until: Option<&str> = None;
+
+    while let Some(start) = rest.find('<') {
+        if skip_until.is_none() {
+            text.push_str(&decode_entities(&rest[..start]));
+        }
+        let Some(len) = rest[start..].find('>') else {
+            // An unclosed tag: drop it, as a browser would
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + len].trim().to_ascii_lowercase();
+        rest = &rest[start + len + 1..];
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .find(|s| !s.is_empty())
+            .unwrap_or_default();
+        match skip_until {
+            Some(closing) if tag.starts_with('/') && name == closing => skip_until = None,
+            Some(_) => {}
+            None if !tag.starts_with('/') && !tag.ends_with('/') && name == "script" => {
+                skip_until = Some("script");
+            }
+            None if !tag.starts_with('/') && !tag.ends_with('/') && name == "style" => {
+                skip_until = Some("style");
+            }
+            // Tags separate words, even if not written with spaces
+            None => text.push(' '),
+        }
+    }
+    if skip_until.is_none() {
+        text.push_str(&decode_entities(rest));
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decode the entities Darwin uses for escaped characters.
+fn decode_entities(s: &str) -> String {
+    const ENTITIES: [(&str, &str); 7] = [
+        ("&lt;", "<"),
+        ("&gt;", ">"),
+        ("&quot;", "\""),
+        ("&#39;", "'"),
+        ("&apos;", "'"),
+        ("&nbsp;", " "),
+        // Last, so "&amp;lt;" becomes "&lt;" rather than "<"
+        ("&amp;", "&"),
+    ];
+    ENTITIES
+        .iter()
+        .fold(s.to_string(), |s, (entity, c)| s.replace(entity, c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::darwin::types::NrccMessage;
+
+    fn board(messages: &[&str]) -> StationBoardWithDetails {
+        StationBoardWithDetails {
+            nrcc_messages: Some(
+                messages
+                    .iter()
+                    .map(|m| NrccMessage {
+                        value: Some((*m).to_string()),
+                    })
+                    .collect(),
+            ),
+            ..StationBoardWithDetails::default()
+        }
+    }
+
+    #[test]
+    fn strips_tags_and_decodes_entities() {
+        assert_eq!(
+            strip_html(
+                "<p>Escalators out of service at London&nbsp;Bridge.<br/>\
+                 More at <a href=\"https://www.nationalrail.co.uk\">National Rail</a> &amp; TfL</p>"
+            ),
+            "Escalators out of service at London Bridge. More at National Rail & TfL"
+        );
+        assert_eq!(strip_html("a &lt;b&gt; &amp;lt;"), "a <b> &lt;");
+    }
+
+    #[test]
+    fn drops_scripts_styles_and_broken_tags() {
+        assert_eq!(
+            strip_html("<style>p { color: red }</style>Lifts <SCRIPT>alert(1)</SCRIPT>working"),
+            "Lifts working"
+        );
+        assert_eq!(
+            strip_html("Platform 4 closed <a href=\"x"),
+            "Platform 4 closed"
+        );
+        assert_eq!(strip_html("<script>never closed"), "");
+    }
+
+    #[test]
+    fn board_messages_skip_blanks_and_repeats() {
+        let board = board(&["<p>Lifts out of use</p>", "  ", "Lifts out of use"]);
+        assert_eq!(board_messages(&board), ["Lifts out of use"]);
+        assert!(board_messages(&StationBoardWithDetails::default()).is_empty());
+    }
+
+    #[test]
+    fn records_latest_board_per_station() {
+        let messages = StationMessages::new();
+        let lbg = Crs::parse("LBG").unwrap();
+        assert!(messages.get(&lbg).is_empty());
+
+        messages.record(lbg, &board(&["Escalators out of service"]));
+        let shared = messages.clone();
+        assert_eq!(shared.get(&lbg), ["Escalators out of service"]);
+
+        messages.record(lbg, &board(&[]));
+        assert!(shared.get(&lbg).is_empty());
+    }
+}
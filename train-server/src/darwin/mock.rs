@@ -1,21 +1,93 @@
 //! Mock Darwin client for testing without API access.
 //!
 //! Loads sample departure boards from JSON files and serves them
-//! as if they were live API responses.
+//! as if they were live API responses. Boards can also be given directly,
+//! e.g. a whole day's timetable per station, in which case only the
+//! services inside each request's window are served.
+//!
+//! The client can also misbehave on purpose (see [`FailureModes`]): fail
+//! some calls, answer slowly, time out at chosen stations or cut boards
+//! short, so retries and partial failures can be exercised repeatably.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
-use chrono::NaiveDate;
 use tokio::sync::RwLock;
 
 use crate::domain::Crs;
+use crate::planner::splitmix64;
 
-use super::convert::{ConvertedService, convert_station_board};
+use super::convert::{ConvertedService, convert_station_board, convert_station_board_in};
 use super::error::DarwinError;
+use super::messages::StationMessages;
+use super::request::{BoardParams, BoardWindow};
 use super::types::StationBoardWithDetails;
 
+/// Failures a mock client injects into the boards it serves.
+///
+/// Which calls fail and how slowly each answers follow from `seed` and the
+/// order of calls, so a scenario plays out the same way every run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailureModes {
+    /// Share of calls failing as a transient upstream error, from 0 to 1
+    pub error_rate: f64,
+
+    /// Least time a call takes to answer
+    pub min_latency: Duration,
+
+    /// Most time a call takes to answer; times are spread evenly from
+    /// `min_latency` up to this
+    pub max_latency: Duration,
+
+    /// Stations whose boards never answer in time
+    pub timeout_stations: HashSet<Crs>,
+
+    /// How long a call to one of `timeout_stations` waits before failing
+    pub timeout: Duration,
+
+    /// Most services on any board, as if Darwin cut it short (None = all)
+    pub truncate_boards: Option<usize>,
+
+    /// Seed for which calls fail and how slowly each answers
+    pub seed: u64,
+}
+
+impl Default for FailureModes {
+    fn default() -> Self {
+        Self {
+            error_rate: 0.0,
+            min_latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+            timeout_stations: HashSet::new(),
+            // The real client's request timeout
+            timeout: Duration::from_secs(30),
+            truncate_boards: None,
+            seed: 0,
+        }
+    }
+}
+
+impl FailureModes {
+    /// How long the call with this `sample` takes to answer.
+    fn latency(&self, sample: u64) -> Duration {
+        let spread = self.max_latency.saturating_sub(self.min_latency);
+        self.min_latency + spread.mul_f64(fraction(sample as u32))
+    }
+
+    /// Whether the call with this `sample` fails.
+    fn fails(&self, sample: u64) -> bool {
+        fraction((sample >> 32) as u32) < self.error_rate
+    }
+}
+
+/// `bits` as a fraction from 0 (inclusive) to 1 (exclusive).
+fn fraction(bits: u32) -> f64 {
+    f64::from(bits) / (f64::from(u32::MAX) + 1.0)
+}
+
 /// Mock Darwin client that serves data from JSON files.
 ///
 /// This is useful for development and testing without needing real Darwin API credentials.
@@ -23,6 +95,18 @@ use super::types::StationBoardWithDetails;
 pub struct MockDarwinClient {
     /// Pre-loaded station boards, keyed by CRS.
     boards: Arc<RwLock<HashMap<Crs, StationBoardWithDetails>>>,
+
+    /// Whether to serve only the services inside each request's window.
+    filter_to_window: bool,
+
+    /// Messages on the boards served so far.
+    messages: StationMessages,
+
+    /// Failures to inject.
+    failures: FailureModes,
+
+    /// Board calls made so far by this client or any clone.
+    calls: Arc<AtomicU64>,
 }
 
 impl MockDarwinClient {
@@ -86,39 +170,55 @@ impl MockDarwinClient {
             });
         }
 
-        Ok(Self {
+        Ok(Self::from_boards(boards))
+    }
+
+    /// Create a mock client serving the given boards.
+    pub fn from_boards(boards: HashMap<Crs, StationBoardWithDetails>) -> Self {
+        Self {
             boards: Arc::new(RwLock::new(boards)),
-        })
+            filter_to_window: false,
+            messages: StationMessages::new(),
+            failures: FailureModes::default(),
+            calls: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Serve only the services inside each request's window, at most
+    /// `num_rows` of them, as Darwin does; trains terminating at a station
+    /// are left off its departures, and trains starting there off its
+    /// arrivals.
+    ///
+    /// For boards holding a whole day's timetable rather than a snapshot.
+    pub fn with_window_filtering(mut self) -> Self {
+        self.filter_to_window = true;
+        self
+    }
+
+    /// Inject `failures` into the boards served.
+    pub fn with_failures(mut self, failures: FailureModes) -> Self {
+        self.failures = failures;
+        self
+    }
+
+    /// The messages on the boards this client (or any clone) has served.
+    pub fn station_messages(&self) -> &StationMessages {
+        &self.messages
     }
 
     /// Get departure board with details for a station.
     ///
     /// Mimics the real `DarwinClient::get_departures_with_details` interface.
-    /// Time parameters are ignored - mock data is static.
+    /// Parameters are validated like the real client's, but otherwise
+    /// ignored unless window filtering is on - mock data is static, and
+    /// dated on the day the window was requested.
     pub async fn get_departures_with_details(
         &self,
         crs: &Crs,
-        _num_rows: u8,
-        _time_offset: i16,
-        _time_window: u16,
-        board_date: NaiveDate,
+        num_rows: u8,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
-        let boards = self.boards.read().await;
-
-        let board = boards.get(crs).ok_or_else(|| DarwinError::ApiError {
-            status: 404,
-            message: format!(
-                "No mock data for station {}. Available: {:?}",
-                crs.as_str(),
-                boards.keys().map(|c| c.as_str()).collect::<Vec<_>>()
-            ),
-        })?;
-
-        // Convert the station board to domain types
-        convert_station_board(board, board_date).map_err(|e| DarwinError::ApiError {
-            status: 500,
-            message: format!("Failed to convert mock board data: {}", e),
-        })
+        self.board(crs, num_rows, window, true).await
     }
 
     /// Get arrival board with details for a station.
@@ -128,13 +228,23 @@ impl MockDarwinClient {
     pub async fn get_arrivals_with_details(
         &self,
         crs: &Crs,
-        _num_rows: u8,
-        _time_offset: i16,
-        _time_window: u16,
-        board_date: NaiveDate,
+        num_rows: u8,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
         // Arrivals use the same JSON structure as departures, just with sta/eta instead of std/etd.
         // For mock purposes, we reuse the same data.
+        self.board(crs, num_rows, window, false).await
+    }
+
+    async fn board(
+        &self,
+        crs: &Crs,
+        num_rows: u8,
+        window: BoardWindow,
+        departures: bool,
+    ) -> Result<Vec<ConvertedService>, DarwinError> {
+        BoardParams::new(num_rows, window.time_offset(), window.time_window())?;
+        self.inject_failures(crs).await?;
         let boards = self.boards.read().await;
 
         let board = boards.get(crs).ok_or_else(|| DarwinError::ApiError {
@@ -145,11 +255,127 @@ impl MockDarwinClient {
                 boards.keys().map(|c| c.as_str()).collect::<Vec<_>>()
             ),
         })?;
+        self.messages.record(*crs, board);
 
-        convert_station_board(board, board_date).map_err(|e| DarwinError::ApiError {
+        // Convert the station board to domain types
+        let converted = if self.filter_to_window {
+            convert_station_board_in(board, &window)
+        } else {
+            convert_station_board(board, window.now().date())
+        };
+        let mut services = converted.map_err(|e| DarwinError::ApiError {
             status: 500,
             message: format!("Failed to convert mock board data: {}", e),
-        })
+        })?;
+
+        if self.filter_to_window {
+            // Trains terminating here aren't departures, nor are trains
+            // starting here arrivals
+            services.retain(|s| {
+                let call = &s.service.calls[s.service.board_station_idx.0];
+                if departures {
+                    call.booked_departure.is_some()
+                } else {
+                    call.booked_arrival.is_some()
+                }
+            });
+
+            // Late trains stay on the board until they have actually gone
+            services.retain(|s| {
+                let candidate = &s.candidate;
+                let time = candidate
+                    .expected_departure
+                    .unwrap_or(candidate.scheduled_departure)
+                    .max(candidate.scheduled_departure);
+                time >= window.start() && candidate.scheduled_departure <= window.end()
+            });
+            services.truncate(usize::from(num_rows));
+        }
+        if let Some(limit) = self.failures.truncate_boards {
+            services.truncate(limit);
+        }
+        Ok(services)
+    }
+
+    /// Wait as long as this call should take, then fail it if it should.
+    async fn inject_failures(&self, crs: &Crs) -> Result<(), DarwinError> {
+        let failures = &self.failures;
+        let call = self.calls.fetch_add(1, Ordering::Relaxed);
+        let sample = splitmix64(failures.seed.wrapping_add(call));
+
+        if failures.timeout_stations.contains(crs) {
+            tokio::time::sleep(failures.timeout).await;
+            return Err(DarwinError::ApiError {
+                status: 504,
+                message: format!("Mock board for {} timed out", crs.as_str()),
+            });
+        }
+
+        let latency = failures.latency(sample);
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        if failures.fails(sample) {
+            return Err(DarwinError::ApiError {
+                status: 503,
+                message: format!("Injected failure fetching {}", crs.as_str()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove a service from every board, as Darwin does once a service ID
+    /// expires.
+    ///
+    /// Lets tests script a service disappearing partway through a scenario.
+    /// Returns the number of boards it was removed from.
+    pub async fn expire_service(&self, service_id: &str) -> usize {
+        let mut boards = self.boards.write().await;
+        let mut removed = 0;
+        for board in boards.values_mut() {
+            if let Some(services) = board.train_services.as_mut() {
+                let before = services.len();
+                services.retain(|s| s.service_id != service_id);
+                removed += before - services.len();
+            }
+        }
+        removed
+    }
+
+    /// Delay a service on every board, as Darwin does when it revises a
+    /// train's estimates.
+    ///
+    /// Every estimated time on the service becomes its booked time plus
+    /// `minutes`. Returns the number of boards it was delayed on.
+    pub async fn delay_service(&self, service_id: &str, minutes: i64) -> usize {
+        let late = |booked: &Option<String>| {
+            let booked = chrono::NaiveTime::parse_from_str(booked.as_deref()?, "%H:%M").ok()?;
+            Some(
+                (booked + chrono::Duration::minutes(minutes))
+                    .format("%H:%M")
+                    .to_string(),
+            )
+        };
+
+        let mut boards = self.boards.write().await;
+        let mut delayed = 0;
+        for board in boards.values_mut() {
+            let services = board.train_services.iter_mut().flatten();
+            for service in services.filter(|s| s.service_id == service_id) {
+                service.eta = late(&service.sta).or(service.eta.take());
+                service.etd = late(&service.std).or(service.etd.take());
+                let points = service
+                    .subsequent_calling_points
+                    .iter_mut()
+                    .flatten()
+                    .flat_map(|portion| portion.calling_point.iter_mut());
+                for point in points {
+                    point.et = late(&point.st).or(point.et.take());
+                }
+                delayed += 1;
+            }
+        }
+        delayed
     }
 
     /// List available stations in the mock data.
@@ -171,6 +397,7 @@ impl MockDarwinClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::Classify;
 
     #[tokio::test]
     async fn load_mock_data() {
@@ -188,7 +415,7 @@ mod tests {
         let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
 
         let services = client
-            .get_departures_with_details(&crs, 10, 0, 120, date)
+            .get_departures_with_details(&crs, 10, BoardWindow::at(date, 10 * 60, 0, 120))
             .await
             .unwrap();
 
@@ -196,6 +423,83 @@ mod tests {
         assert!(services[0].service.calls.len() > 1);
     }
 
+    #[tokio::test]
+    async fn expired_service_leaves_boards() {
+        let client = MockDarwinClient::new("data/mock_boards").unwrap();
+        let crs = Crs::parse("PAD").unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+
+        assert_eq!(client.expire_service("pad_service_1").await, 1);
+        assert_eq!(client.expire_service("pad_service_1").await, 0);
+
+        let services = client
+            .get_departures_with_details(&crs, 10, BoardWindow::at(date, 10 * 60, 0, 120))
+            .await
+            .unwrap();
+        assert!(
+            services
+                .iter()
+                .all(|s| s.service.service_ref.darwin_id != "pad_service_1")
+        );
+    }
+
+    #[tokio::test]
+    async fn delay_service_shifts_estimates() {
+        let client = MockDarwinClient::new("data/mock_boards").unwrap();
+        let crs = Crs::parse("PAD").unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+
+        assert_eq!(client.delay_service("pad_service_1", 10).await, 1);
+
+        let services = client
+            .get_departures_with_details(&crs, 10, BoardWindow::at(date, 14 * 60, 0, 120))
+            .await
+            .unwrap();
+        let delayed = services
+            .iter()
+            .find(|s| s.service.service_ref.darwin_id == "pad_service_1")
+            .unwrap();
+        let times: Vec<String> = delayed
+            .service
+            .calls
+            .iter()
+            .filter_map(|c| c.expected_departure().or(c.expected_arrival()))
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(times, ["14:25", "14:50", "15:15", "15:45", "15:55"]);
+    }
+
+    #[tokio::test]
+    async fn window_filtering_serves_only_the_window() {
+        let boards = MockDarwinClient::new("data/mock_boards")
+            .unwrap()
+            .boards
+            .read()
+            .await
+            .clone();
+        let client = MockDarwinClient::from_boards(boards).with_window_filtering();
+        let crs = Crs::parse("PAD").unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let window = BoardWindow::at(date, 14 * 60 + 20, 0, 30);
+
+        let services = client
+            .get_departures_with_details(&crs, 10, window)
+            .await
+            .unwrap();
+
+        assert!(!services.is_empty());
+        assert!(services.iter().all(|s| {
+            let departs = s.candidate.scheduled_departure;
+            departs >= window.start() && departs <= window.end()
+        }));
+
+        let one = client
+            .get_departures_with_details(&crs, 1, window)
+            .await
+            .unwrap();
+        assert_eq!(one.len(), 1);
+    }
+
     #[tokio::test]
     async fn unknown_station_returns_error() {
         let client = MockDarwinClient::new("data/mock_boards").unwrap();
@@ -203,9 +507,119 @@ mod tests {
         let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
 
         let result = client
-            .get_departures_with_details(&crs, 10, 0, 120, date)
+            .get_departures_with_details(&crs, 10, BoardWindow::at(date, 10 * 60, 0, 120))
             .await;
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn out_of_range_parameters_rejected() {
+        let client = MockDarwinClient::new("data/mock_boards").unwrap();
+        let crs = Crs::parse("PAD").unwrap();
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+
+        let result = client
+            .get_departures_with_details(&crs, 10, BoardWindow::at(date, 10 * 60, 0, 180))
+            .await;
+
+        assert!(matches!(result, Err(DarwinError::InvalidRequest(_))));
+    }
+
+    fn pad_window() -> (Crs, BoardWindow) {
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        (
+            Crs::parse("PAD").unwrap(),
+            BoardWindow::at(date, 10 * 60, 0, 120),
+        )
+    }
+
+    #[tokio::test]
+    async fn injected_errors_repeat_for_a_seed() {
+        let (crs, window) = pad_window();
+        let outcomes = |seed| async move {
+            let client = MockDarwinClient::new("data/mock_boards")
+                .unwrap()
+                .with_failures(FailureModes {
+                    error_rate: 0.5,
+                    seed,
+                    ..FailureModes::default()
+                });
+            let mut outcomes = Vec::new();
+            for _ in 0..32 {
+                let result = client.get_departures_with_details(&crs, 10, window).await;
+                if let Err(e) = &result {
+                    assert!(e.is_retryable());
+                }
+                outcomes.push(result.is_ok());
+            }
+            outcomes
+        };
+
+        let first = outcomes(7).await;
+        assert_eq!(first, outcomes(7).await);
+        assert_ne!(first, outcomes(8).await);
+        assert!(first.contains(&true) && first.contains(&false));
+    }
+
+    #[tokio::test]
+    async fn chosen_stations_time_out() {
+        let (crs, window) = pad_window();
+        let client = MockDarwinClient::new("data/mock_boards")
+            .unwrap()
+            .with_failures(FailureModes {
+                timeout_stations: HashSet::from([crs]),
+                timeout: Duration::from_millis(20),
+                ..FailureModes::default()
+            });
+
+        let started = std::time::Instant::now();
+        let result = client.get_departures_with_details(&crs, 10, window).await;
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert!(matches!(
+            result,
+            Err(DarwinError::ApiError { status: 504, .. })
+        ));
+
+        let rdg = Crs::parse("RDG").unwrap();
+        assert!(
+            client
+                .get_arrivals_with_details(&rdg, 10, window)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_and_truncated_boards() {
+        let (crs, window) = pad_window();
+        let client = MockDarwinClient::new("data/mock_boards")
+            .unwrap()
+            .with_failures(FailureModes {
+                min_latency: Duration::from_millis(10),
+                max_latency: Duration::from_millis(30),
+                truncate_boards: Some(1),
+                ..FailureModes::default()
+            });
+
+        let started = std::time::Instant::now();
+        let services = client
+            .get_departures_with_details(&crs, 10, window)
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(10));
+        assert_eq!(services.len(), 1);
+    }
+
+    #[test]
+    fn latency_spreads_between_bounds() {
+        let failures = FailureModes {
+            min_latency: Duration::from_millis(100),
+            max_latency: Duration::from_millis(300),
+            ..FailureModes::default()
+        };
+        assert_eq!(failures.latency(0), Duration::from_millis(100));
+        let slowest = failures.latency(u64::from(u32::MAX));
+        assert!(slowest <= Duration::from_millis(300) && slowest > Duration::from_millis(299));
+    }
 }
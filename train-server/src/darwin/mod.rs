@@ -10,20 +10,36 @@
 //! - `GetDepBoardWithDetails` returns calling points inline, avoiding
 //!   the need for separate service detail requests
 
-use chrono::NaiveDate;
-
 use crate::domain::Crs;
 
 mod client;
 mod convert;
 mod error;
+mod filter;
+mod instrument;
+mod messages;
 mod mock;
+mod quirks;
+mod reference;
+mod request;
 mod types;
 
-pub use client::{DarwinClient, DarwinConfig};
-pub use convert::{ConversionError, ConvertedService, convert_service_details};
+pub use client::{DarwinClient, DarwinConfig, HttpVersion};
+pub use convert::{
+    ConversionError, ConversionMode, ConversionReport, ConvertedService, SkippedService,
+    convert_service_details, convert_station_board_in, convert_station_board_report,
+};
 pub use error::DarwinError;
-pub use mock::MockDarwinClient;
+pub use filter::BoardFilter;
+pub use instrument::{CallRecord, CallTotals, DarwinMetrics, InstrumentedDarwin, Operation};
+pub use messages::{MESSAGE_FRESHNESS, StationMessages, board_messages, strip_html};
+pub use mock::{FailureModes, MockDarwinClient};
+pub use quirks::{BoardQuirk, StationQuirks};
+pub use reference::{
+    ReasonCodeDto, ReasonTranslation, ReasonTranslations, ReferenceCache, ReferenceClient,
+    ReferenceClientConfig, ReferenceData, ReferenceLists, ReferenceTables, TocDto,
+};
+pub use request::{BoardParams, BoardWindow, NUM_ROWS_RANGE, TIME_OFFSET_RANGE, TIME_WINDOW_RANGE};
 pub use types::{
     ArrayOfCallingPoints, CallingPoint, ServiceDetails, ServiceItemWithCallingPoints,
     ServiceLocation, StationBoardWithDetails,
@@ -45,31 +61,17 @@ impl DarwinClientImpl {
         &self,
         crs: &Crs,
         num_rows: u8,
-        time_offset: i16,
-        time_window: u16,
-        board_date: NaiveDate,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
         match self {
             Self::Real(client) => {
                 client
-                    .get_departures_with_details(
-                        crs,
-                        num_rows,
-                        time_offset,
-                        time_window,
-                        board_date,
-                    )
+                    .get_departures_with_details(crs, num_rows, window)
                     .await
             }
             Self::Mock(client) => {
                 client
-                    .get_departures_with_details(
-                        crs,
-                        num_rows,
-                        time_offset,
-                        time_window,
-                        board_date,
-                    )
+                    .get_departures_with_details(crs, num_rows, window)
                     .await
             }
         }
@@ -80,24 +82,46 @@ impl DarwinClientImpl {
         &self,
         crs: &Crs,
         num_rows: u8,
-        time_offset: i16,
-        time_window: u16,
-        board_date: NaiveDate,
+        window: BoardWindow,
     ) -> Result<Vec<ConvertedService>, DarwinError> {
         match self {
             Self::Real(client) => {
                 client
-                    .get_arrivals_with_details(crs, num_rows, time_offset, time_window, board_date)
+                    .get_arrivals_with_details(crs, num_rows, window)
                     .await
             }
             Self::Mock(client) => {
                 client
-                    .get_arrivals_with_details(crs, num_rows, time_offset, time_window, board_date)
+                    .get_arrivals_with_details(crs, num_rows, window)
                     .await
             }
         }
     }
 
+    /// The messages on the boards this client has fetched.
+    pub fn station_messages(&self) -> &StationMessages {
+        match self {
+            Self::Real(client) => client.station_messages(),
+            Self::Mock(client) => client.station_messages(),
+        }
+    }
+
+    /// Number of staff API calls that fell back to the public API.
+    pub fn staff_fallbacks(&self) -> u64 {
+        match self {
+            Self::Real(client) => client.staff_fallbacks(),
+            Self::Mock(_) => 0,
+        }
+    }
+
+    /// Whether the most recent staff API call fell back to the public API.
+    pub fn is_staff_degraded(&self) -> bool {
+        match self {
+            Self::Real(client) => client.is_staff_degraded(),
+            Self::Mock(_) => false,
+        }
+    }
+
     /// Get full service details by service ID.
     ///
     /// Returns the complete calling points for a service, including both
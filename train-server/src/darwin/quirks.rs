@@ -0,0 +1,253 @@
+//! Known misbehaviour in some stations' boards.
+//!
+//! Darwin's data isn't uniform: some stations list a calling point twice in
+//! a row (typically where trains divide or join), and some report platforms
+//! that aren't meaningful. Rather than have the planner and views guard
+//! against these everywhere, converted services are normalized here, using
+//! a table of each station's quirks ([`StationQuirks`]). A board that says
+//! its platforms aren't available has its own platforms dropped too.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::domain::{Call, CallIndex, Crs};
+
+use super::convert::ConvertedService;
+
+/// A way a station's calls are known to misbehave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardQuirk {
+    /// The station can be listed twice in a row in a service's calls; the
+    /// two are merged into one call
+    DuplicateCalls,
+
+    /// Platforms reported for the station aren't meaningful, so are dropped
+    PhantomPlatforms,
+}
+
+/// Stations whose boards are known to misbehave, and how.
+const DEFAULT_QUIRKS: &[(&str, &[BoardQuirk])] = &[
+    // Trains divide and join here, and the call can be listed per portion
+    ("AFK", &[BoardQuirk::DuplicateCalls]),
+    ("HHE", &[BoardQuirk::DuplicateCalls]),
+];
+
+/// The quirks used when converting boards.
+static QUIRKS: LazyLock<StationQuirks> = LazyLock::new(StationQuirks::default);
+
+/// A table of stations' quirks.
+#[derive(Debug, Clone)]
+pub struct StationQuirks {
+    by_station: HashMap<Crs, Vec<BoardQuirk>>,
+}
+
+impl Default for StationQuirks {
+    fn default() -> Self {
+        DEFAULT_QUIRKS
+            .iter()
+            .flat_map(|(crs, quirks)| quirks.iter().map(move |q| (*crs, *q)))
+            .fold(Self::empty(), |table, (crs, quirk)| {
+                table.with_quirk(
+                    Crs::parse(crs).expect("default quirk stations are valid"),
+                    quirk,
+                )
+            })
+    }
+}
+
+impl StationQuirks {
+    /// A table with no quirks.
+    pub fn empty() -> Self {
+        Self {
+            by_station: HashMap::new(),
+        }
+    }
+
+    /// Record a quirk of a station.
+    pub fn with_quirk(mut self, station: Crs, quirk: BoardQuirk) -> Self {
+        let quirks = self.by_station.entry(station).or_default();
+        if !quirks.contains(&quirk) {
+            quirks.push(quirk);
+        }
+        self
+    }
+
+    /// Returns true if the station has the quirk.
+    pub fn has(&self, station: &Crs, quirk: BoardQuirk) -> bool {
+        self.by_station
+            .get(station)
+            .is_some_and(|quirks| quirks.contains(&quirk))
+    }
+
+    /// Normalize a converted service for its stations' quirks.
+    pub fn normalize(&self, converted: &mut ConvertedService) {
+        let service = &mut converted.service;
+        for call in &mut service.calls {
+            self.drop_phantom_platform(call);
+        }
+        if self.has(&service.service_ref.board_crs, BoardQuirk::PhantomPlatforms) {
+            converted.candidate.platform = None;
+        }
+
+        let board_idx = self.dedupe(&mut service.calls, service.board_station_idx);
+        service.board_station_idx = board_idx;
+
+        if let Some(formation) = &mut service.formation {
+            for portion in &mut formation.portions {
+                for call in &mut portion.calls {
+                    self.drop_phantom_platform(call);
+                }
+                self.dedupe(&mut portion.calls, CallIndex(0));
+            }
+        }
+    }
+
+    fn drop_phantom_platform(&self, call: &mut Call) {
+        if self.has(&call.station, BoardQuirk::PhantomPlatforms) {
+            call.platform = None;
+        }
+    }
+
+    /// Merge consecutive calls at a station listed twice in a row, returning
+    /// where the call at `idx` is afterwards.
+    fn dedupe(&self, calls: &mut Vec<Call>, idx: CallIndex) -> CallIndex {
+        let mut idx = idx.0;
+        let mut i = 1;
+        while i < calls.len() {
+            let station = calls[i].station;
+            if station == calls[i - 1].station && self.has(&station, BoardQuirk::DuplicateCalls) {
+                let later = calls.remove(i);
+                merge_into(&mut calls[i - 1], later);
+                if idx >= i {
+                    idx -= 1;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        CallIndex(idx)
+    }
+}
+
+/// Merge a repeated call into the one before it: the train arrives as the
+/// first says and leaves as the second says.
+fn merge_into(first: &mut Call, later: Call) {
+    if later.booked_departure.is_some() {
+        first.booked_departure = later.booked_departure;
+        first.realtime_departure = later.realtime_departure;
+    }
+    if first.platform.is_none() {
+        first.platform = later.platform;
+    }
+    first.is_cancelled = first.is_cancelled && later.is_cancelled;
+}
+
+/// Normalize a converted service with the default quirks table.
+pub(super) fn normalize(converted: &mut ConvertedService) {
+    QUIRKS.normalize(converted);
+}
+
+/// Drop the platforms at a board's own station, for a board reporting that
+/// its platforms aren't available.
+pub(super) fn hide_board_platforms(converted: &mut ConvertedService) {
+    converted.candidate.platform = None;
+    let idx = converted.service.board_station_idx.0;
+    if let Some(call) = converted.service.calls.get_mut(idx) {
+        call.platform = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{RailTime, Service, ServiceCandidate, ServiceRef};
+    use chrono::NaiveDate;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    fn call(station: &str, arrives: Option<&str>, departs: Option<&str>, platform: &str) -> Call {
+        let mut call = Call::new(crs(station), station.to_string());
+        call.booked_arrival = arrives.map(time);
+        call.booked_departure = departs.map(time);
+        call.platform = Some(platform.to_string());
+        call
+    }
+
+    /// Victoria to Brighton, with Haywards Heath listed twice, looked up on
+    /// Brighton's board.
+    fn converted() -> ConvertedService {
+        let calls = vec![
+            call("VIC", None, Some("10:00"), "15"),
+            call("HHE", Some("10:40"), None, "3"),
+            call("HHE", None, Some("10:45"), "3"),
+            call("BTN", Some("11:05"), None, "4"),
+        ];
+        let service_ref = ServiceRef::new("S1".into(), crs("BTN"));
+        ConvertedService {
+            candidate: ServiceCandidate {
+                service_ref: service_ref.clone(),
+                headcode: None,
+                scheduled_departure: time("11:05"),
+                expected_departure: None,
+                destination: "Brighton".into(),
+                destination_crs: Some(crs("BTN")),
+                operator: "Test".into(),
+                operator_code: None,
+                platform: Some("4".into()),
+                is_cancelled: false,
+            },
+            service: Service {
+                service_ref,
+                headcode: None,
+                operator: "Test".into(),
+                operator_code: None,
+                calls,
+                board_station_idx: CallIndex(3),
+                formation: None,
+            },
+            uid: None,
+            coaches: None,
+        }
+    }
+
+    #[test]
+    fn duplicate_calls_are_merged() {
+        let mut converted = converted();
+        StationQuirks::default().normalize(&mut converted);
+
+        let calls = &converted.service.calls;
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[1].booked_arrival, Some(time("10:40")));
+        assert_eq!(calls[1].booked_departure, Some(time("10:45")));
+        assert_eq!(converted.service.board_station_idx, CallIndex(2));
+        assert_eq!(calls[2].station, crs("BTN"));
+    }
+
+    #[test]
+    fn only_quirky_stations_are_normalized() {
+        let mut converted = converted();
+        StationQuirks::empty().normalize(&mut converted);
+        assert_eq!(converted.service.calls.len(), 4);
+
+        let quirks = StationQuirks::empty().with_quirk(crs("BTN"), BoardQuirk::PhantomPlatforms);
+        quirks.normalize(&mut converted);
+        assert_eq!(converted.candidate.platform, None);
+        assert_eq!(converted.service.calls[3].platform, None);
+        assert_eq!(converted.service.calls[0].platform.as_deref(), Some("15"));
+    }
+
+    #[test]
+    fn hidden_board_platforms_are_dropped() {
+        let mut converted = converted();
+        hide_board_platforms(&mut converted);
+        assert_eq!(converted.candidate.platform, None);
+        assert_eq!(converted.service.calls[3].platform, None);
+        assert_eq!(converted.service.calls[1].platform.as_deref(), Some("3"));
+    }
+}
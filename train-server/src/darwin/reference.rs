@@ -0,0 +1,609 @@
+//! Darwin reference data: reason codes and train operators.
+//!
+//! The staff API gives delay and cancellation reasons as numeric codes
+//! ("106") where the public API gives text, so turning them into something
+//! a passenger can read needs Darwin's reason code table. Alongside it comes
+//! the TOC list, naming operators the boards only give a code for.
+//!
+//! Like the station list, the tables change rarely: they are fetched at
+//! startup, kept in a disk cache between restarts and refreshed daily (see
+//! [`ReferenceData`]). Nothing is bundled, so without an API key codes go
+//! unexplained, though reasons Darwin already gives as text still show.
+//!
+//! Darwin's reason texts are in en-GB, and that's what passengers get by
+//! default. A [`ReasonTranslations`] table plugged into [`ReferenceData`]
+//! gives them in other languages, chosen from the request's
+//! `Accept-Language`; reasons it has no text for stay in English.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::client::API_KEY_HEADER;
+use super::error::DarwinError;
+use crate::domain::AtocCode;
+
+/// Default base URL for the reference data API (Rail Data Marketplace).
+const DEFAULT_BASE_URL: &str =
+    "https://api1.raildata.org.uk/1010-reference-data1_0/LDBSVWS/api/ref/20211101";
+
+/// Default cache TTL: 24 hours.
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// One entry in Darwin's reason code table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReasonCodeDto {
+    pub code: u16,
+    /// Text for a train delayed for this reason
+    pub late_reason: String,
+    /// Text for a train cancelled for this reason
+    pub canc_reason: String,
+}
+
+/// One train operator in Darwin's TOC list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TocDto {
+    pub toc: String,
+    pub name: String,
+}
+
+/// Both reference lists, as fetched or cached.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReferenceLists {
+    pub reason_codes: Vec<ReasonCodeDto>,
+    pub operators: Vec<TocDto>,
+}
+
+/// Lookups built from [`ReferenceLists`].
+#[derive(Debug, Default)]
+pub struct ReferenceTables {
+    late: HashMap<u16, String>,
+    cancelled: HashMap<u16, String>,
+    operators: HashMap<String, String>,
+    /// Codes by their late text, for translating reasons the public API
+    /// gives as text
+    late_codes: HashMap<String, u16>,
+    /// Codes by their cancellation text
+    cancelled_codes: HashMap<String, u16>,
+}
+
+impl ReferenceTables {
+    /// Build the lookups from fetched or cached lists.
+    pub fn from_lists(lists: ReferenceLists) -> Self {
+        let mut tables = Self::default();
+        for reason in lists.reason_codes {
+            tables
+                .late_codes
+                .insert(reason.late_reason.clone(), reason.code);
+            tables
+                .cancelled_codes
+                .insert(reason.canc_reason.clone(), reason.code);
+            tables.late.insert(reason.code, reason.late_reason);
+            tables.cancelled.insert(reason.code, reason.canc_reason);
+        }
+        tables.operators = lists
+            .operators
+            .into_iter()
+            .map(|toc| (toc.toc, toc.name))
+            .collect();
+        tables
+    }
+
+    /// Passenger-friendly text for a delay reason as Darwin gave it.
+    ///
+    /// Text is passed through; a code is looked up, giving `None` if it's
+    /// unknown.
+    pub fn delay_text(&self, reason: &str) -> Option<String> {
+        self.delay_text_in(reason, None)
+    }
+
+    /// Passenger-friendly text for a cancellation reason as Darwin gave it.
+    pub fn cancel_text(&self, reason: &str) -> Option<String> {
+        self.cancel_text_in(reason, None)
+    }
+
+    /// Text for a delay reason in `translation`'s language, or in English
+    /// without one or if it doesn't cover the reason.
+    ///
+    /// Reasons given as Darwin's own text for a code are translated as that
+    /// code; other text is passed through.
+    pub fn delay_text_in(
+        &self,
+        reason: &str,
+        translation: Option<&ReasonTranslation>,
+    ) -> Option<String> {
+        let translated = |code| translation?.late.get(&code).cloned();
+        explain(&self.late, &self.late_codes, translated, reason)
+    }
+
+    /// Text for a cancellation reason in `translation`'s language, as for
+    /// [`delay_text_in`](Self::delay_text_in).
+    pub fn cancel_text_in(
+        &self,
+        reason: &str,
+        translation: Option<&ReasonTranslation>,
+    ) -> Option<String> {
+        let translated = |code| translation?.cancelled.get(&code).cloned();
+        explain(&self.cancelled, &self.cancelled_codes, translated, reason)
+    }
+
+    /// An operator's name, if it's in the TOC list.
+    pub fn operator_name(&self, code: &AtocCode) -> Option<&str> {
+        self.operators.get(code.as_str()).map(String::as_str)
+    }
+
+    /// Number of reason codes known.
+    pub fn reason_count(&self) -> usize {
+        self.late.len()
+    }
+}
+
+/// Explain `reason`: translated if it's a code (or a code's text) that
+/// `translated` covers, else looked up if it's a code, else passed through.
+fn explain(
+    table: &HashMap<u16, String>,
+    codes: &HashMap<String, u16>,
+    translated: impl Fn(u16) -> Option<String>,
+    reason: &str,
+) -> Option<String> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return None;
+    }
+    let code = reason.parse::<u16>().ok();
+    if let Some(text) = code
+        .or_else(|| codes.get(reason).copied())
+        .and_then(translated)
+    {
+        return Some(text);
+    }
+    match code {
+        Some(code) => table.get(&code).cloned(),
+        None => Some(reason.to_string()),
+    }
+}
+
+/// Reason texts in one language, by reason code.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ReasonTranslation {
+    /// Text for a train delayed for each reason
+    #[serde(default)]
+    pub late: HashMap<u16, String>,
+    /// Text for a train cancelled for each reason
+    #[serde(default)]
+    pub cancelled: HashMap<u16, String>,
+}
+
+/// Reason texts in languages other than Darwin's en-GB, by language tag.
+#[derive(Debug, Clone, Default)]
+pub struct ReasonTranslations {
+    /// Keyed by lowercase language tag, e.g. "cy" or "pt-br"
+    languages: HashMap<String, ReasonTranslation>,
+}
+
+impl ReasonTranslations {
+    /// Create an empty table: every reason in English.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the texts for a language.
+    pub fn insert(&mut self, language: &str, translation: ReasonTranslation) {
+        self.languages
+            .insert(language.trim().to_ascii_lowercase(), translation);
+    }
+
+    /// Parse a table from JSON mapping language tags to texts by code, e.g.
+    /// `{"cy": {"late": {"106": "..."}, "cancelled": {"106": "..."}}}`.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let languages: HashMap<String, ReasonTranslation> = serde_json::from_str(json)?;
+        let mut translations = Self::new();
+        for (language, translation) in languages {
+            translations.insert(&language, translation);
+        }
+        Ok(translations)
+    }
+
+    /// The translation for an `Accept-Language` header's languages: the
+    /// first one listed that has a translation, by its full tag or its
+    /// primary language (`cy-GB` finds `cy`). `None` means English, also
+    /// when English is listed before any translated language.
+    pub fn negotiate(&self, accept_language: &str) -> Option<&ReasonTranslation> {
+        for tag in accept_language.split(',') {
+            let tag = tag
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase();
+            let primary = tag.split('-').next().unwrap_or("");
+            if primary == "en" {
+                return None;
+            }
+            if let Some(translation) = self
+                .languages
+                .get(&tag)
+                .or_else(|| self.languages.get(primary))
+            {
+                return Some(translation);
+            }
+        }
+        None
+    }
+
+    /// Number of languages translated into.
+    pub fn len(&self) -> usize {
+        self.languages.len()
+    }
+
+    /// Returns true if there are no translations.
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+}
+
+/// Configuration for the reference data client.
+#[derive(Debug, Clone)]
+pub struct ReferenceClientConfig {
+    /// API key for x-apikey header authentication
+    pub api_key: String,
+    /// Base URL for the API
+    pub base_url: String,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+}
+
+impl ReferenceClientConfig {
+    /// Create a new config with the given API key.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            timeout_secs: 30,
+        }
+    }
+
+    /// Set a custom base URL (for testing).
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+}
+
+/// Client for Darwin's reference data API.
+#[derive(Debug, Clone)]
+pub struct ReferenceClient {
+    http: reqwest::Client,
+    api_key: HeaderValue,
+    base_url: String,
+    timeout: Duration,
+}
+
+impl ReferenceClient {
+    /// Create a reference data client that sends its requests through
+    /// `http`, e.g. the Darwin client's.
+    pub fn with_http_client(
+        config: ReferenceClientConfig,
+        http: reqwest::Client,
+    ) -> Result<Self, DarwinError> {
+        let api_key = HeaderValue::from_str(&config.api_key)
+            .map_err(|_| DarwinError::NotConfigured("invalid reference API key".to_string()))?;
+        Ok(Self {
+            http,
+            api_key,
+            base_url: config.base_url,
+            timeout: Duration::from_secs(config.timeout_secs),
+        })
+    }
+
+    /// Fetch the reason code table and the TOC list.
+    pub async fn fetch(&self) -> Result<ReferenceLists, DarwinError> {
+        Ok(ReferenceLists {
+            reason_codes: self.get("GetReasonCodeList").await?,
+            operators: self.get("GetTOCList").await?,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, operation: &str) -> Result<T, DarwinError> {
+        let url = format!("{}/{}", self.base_url, operation);
+        let response = self
+            .http
+            .get(&url)
+            .header(API_KEY_HEADER, &self.api_key)
+            .timeout(self.timeout)
+            .send()
+            .await?;
+        let status = response.status();
+
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(DarwinError::Unauthorized);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(DarwinError::ApiError {
+                status: status.as_u16(),
+                message: body,
+            });
+        }
+
+        let body = response.text().await?;
+        serde_json::from_str(&body).map_err(|e| DarwinError::Json {
+            message: e.to_string(),
+            body: None,
+        })
+    }
+}
+
+/// Cached reference lists with metadata.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedLists {
+    /// Unix timestamp when the cache was written.
+    cached_at_secs: u64,
+    /// The cached lists.
+    lists: ReferenceLists,
+}
+
+/// Disk cache for reference data, so restarts don't refetch it.
+#[derive(Debug, Clone)]
+pub struct ReferenceCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl ReferenceCache {
+    /// Create a cache at `path` with the default TTL (24 hours).
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Set a custom TTL.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Try to load the lists from the cache.
+    ///
+    /// Returns `None` if the cache doesn't exist, is invalid, or has expired.
+    pub fn load(&self) -> Option<ReferenceLists> {
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        let cached: CachedLists = serde_json::from_str(&contents).ok()?;
+        let age_secs = unix_now()?.saturating_sub(cached.cached_at_secs);
+        (age_secs < self.ttl.as_secs()).then_some(cached.lists)
+    }
+
+    /// Save the lists to the cache, creating parent directories if needed.
+    pub fn save(&self, lists: &ReferenceLists) -> std::io::Result<()> {
+        let cached = CachedLists {
+            cached_at_secs: unix_now().unwrap_or_default(),
+            lists: lists.clone(),
+        };
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&cached)?)
+    }
+}
+
+fn unix_now() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Thread-safe reference data, refreshed in the background.
+#[derive(Clone, Default)]
+pub struct ReferenceData {
+    tables: Arc<RwLock<Arc<ReferenceTables>>>,
+    client: Option<ReferenceClient>,
+    cache: Option<ReferenceCache>,
+    translations: Arc<ReasonTranslations>,
+}
+
+impl ReferenceData {
+    /// Reference data with no tables (codes go unexplained), e.g. without
+    /// an API key or in mock mode.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Reference data from fixed lists, e.g. for tests.
+    pub fn from_lists(lists: ReferenceLists) -> Self {
+        Self {
+            tables: Arc::new(RwLock::new(Arc::new(ReferenceTables::from_lists(lists)))),
+            ..Self::default()
+        }
+    }
+
+    /// Load reference data from the disk cache if valid, otherwise fetch it
+    /// and save it to the cache.
+    ///
+    /// Returns the data and whether it was loaded from the cache.
+    pub async fn fetch_with_cache(
+        client: ReferenceClient,
+        cache: ReferenceCache,
+    ) -> Result<(Self, bool), DarwinError> {
+        let (lists, from_cache) = match cache.load() {
+            Some(lists) => (lists, true),
+            None => {
+                let lists = client.fetch().await?;
+                if let Err(e) = cache.save(&lists) {
+                    warn!(error = %e, "Failed to save reference data cache");
+                }
+                (lists, false)
+            }
+        };
+        let mut data = Self::from_lists(lists);
+        data.client = Some(client);
+        data.cache = Some(cache);
+        Ok((data, from_cache))
+    }
+
+    /// Give reasons in other languages from `translations`, kept across
+    /// refreshes.
+    pub fn with_translations(mut self, translations: ReasonTranslations) -> Self {
+        self.translations = Arc::new(translations);
+        self
+    }
+
+    /// Reason texts in languages other than English.
+    pub fn translations(&self) -> &ReasonTranslations {
+        &self.translations
+    }
+
+    /// The current tables.
+    pub async fn tables(&self) -> Arc<ReferenceTables> {
+        self.tables.read().await.clone()
+    }
+
+    /// Refetch the tables, returning the number of reason codes.
+    ///
+    /// On failure the existing tables are kept. Reference data without a
+    /// client has nothing to refresh from, and keeps its tables.
+    pub async fn refresh(&self) -> Result<usize, DarwinError> {
+        let Some(client) = &self.client else {
+            return Ok(self.tables().await.reason_count());
+        };
+        let lists = client.fetch().await?;
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.save(&lists)
+        {
+            warn!(error = %e, "Failed to save reference data cache");
+        }
+        let tables = ReferenceTables::from_lists(lists);
+        let count = tables.reason_count();
+        *self.tables.write().await = Arc::new(tables);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn lists() -> ReferenceLists {
+        ReferenceLists {
+            reason_codes: vec![ReasonCodeDto {
+                code: 106,
+                late_reason: "This train has been delayed by a fault with the signalling system"
+                    .to_string(),
+                canc_reason:
+                    "This train has been cancelled because of a fault with the signalling system"
+                        .to_string(),
+            }],
+            operators: vec![TocDto {
+                toc: "GW".to_string(),
+                name: "Great Western Railway".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn codes_are_looked_up_and_text_passed_through() {
+        let tables = ReferenceTables::from_lists(lists());
+
+        assert!(tables.delay_text("106").unwrap().contains("delayed"));
+        assert!(tables.cancel_text(" 106 ").unwrap().contains("cancelled"));
+        assert_eq!(
+            tables.delay_text("Congestion").as_deref(),
+            Some("Congestion")
+        );
+        assert_eq!(tables.delay_text("999"), None);
+        assert_eq!(tables.cancel_text(""), None);
+        assert_eq!(
+            tables.operator_name(&AtocCode::parse("GW").unwrap()),
+            Some("Great Western Railway")
+        );
+    }
+
+    #[test]
+    fn reasons_are_translated_where_a_translation_covers_them() {
+        let tables = ReferenceTables::from_lists(lists());
+        let translations = ReasonTranslations::from_json(
+            r#"{"CY": {"late": {"106": "Oedi oherwydd nam ar y signalau"}}}"#,
+        )
+        .unwrap();
+        let welsh = translations.negotiate("cy-GB, en;q=0.8");
+        assert!(welsh.is_some());
+
+        assert_eq!(
+            tables.delay_text_in("106", welsh).as_deref(),
+            Some("Oedi oherwydd nam ar y signalau")
+        );
+        // Darwin's own text for a code is translated as the code
+        let english = tables.delay_text("106").unwrap();
+        assert_eq!(
+            tables.delay_text_in(&english, welsh).as_deref(),
+            Some("Oedi oherwydd nam ar y signalau")
+        );
+        // Untranslated reasons, and other text, stay as they are
+        assert!(
+            tables
+                .cancel_text_in("106", welsh)
+                .unwrap()
+                .contains("cancelled")
+        );
+        assert_eq!(
+            tables.delay_text_in("Congestion", welsh).as_deref(),
+            Some("Congestion")
+        );
+
+        assert!(translations.negotiate("en-GB, cy").is_none());
+        assert!(translations.negotiate("fr").is_none());
+        assert!(ReasonTranslations::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn reference_lists_parse_from_darwin_json() {
+        let reasons: Vec<ReasonCodeDto> = serde_json::from_str(
+            r#"[{"code": 106, "lateReason": "Delayed", "cancReason": "Cancelled"}]"#,
+        )
+        .unwrap();
+        assert_eq!(reasons[0].code, 106);
+        assert_eq!(reasons[0].canc_reason, "Cancelled");
+
+        let tocs: Vec<TocDto> =
+            serde_json::from_str(r#"[{"toc": "GW", "name": "Great Western Railway"}]"#).unwrap();
+        assert_eq!(tocs[0].name, "Great Western Railway");
+    }
+
+    #[test]
+    fn cache_round_trips_and_expires() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("nested").join("reference.json");
+
+        let cache = ReferenceCache::new(&path);
+        cache.save(&lists()).unwrap();
+        assert_eq!(cache.load().unwrap().reason_codes[0].code, 106);
+
+        let expired = ReferenceCache::new(&path).with_ttl(Duration::from_secs(0));
+        assert!(expired.load().is_none());
+        assert!(
+            ReferenceCache::new("/nonexistent/reference.json")
+                .load()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn data_without_a_client_keeps_its_tables_on_refresh() {
+        let data = ReferenceData::from_lists(lists());
+        assert_eq!(data.refresh().await.unwrap(), 1);
+        assert!(data.tables().await.delay_text("106").is_some());
+        assert_eq!(ReferenceData::empty().tables().await.reason_count(), 0);
+    }
+}
@@ -0,0 +1,360 @@
+//! Typed board request parameters.
+//!
+//! Darwin answers out-of-range parameters with an opaque fault (or, worse,
+//! silently clamps them), so [`BoardParams`] checks them before anything is
+//! sent and turns them into query pairs for either API.
+//!
+//! Darwin times carry no date, so a board fetched just before midnight mixes
+//! tonight's departures with tomorrow's. [`BoardWindow`] pins the board to
+//! the moment it was requested, so each service can be dated by where it
+//! falls in the window rather than by the day the request was made.
+
+use std::ops::RangeInclusive;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+
+use crate::domain::{Crs, RailTime};
+
+use super::error::DarwinError;
+
+/// Rows Darwin will return on one board.
+pub const NUM_ROWS_RANGE: RangeInclusive<u8> = 1..=150;
+
+/// Offset from now, in minutes, Darwin accepts.
+pub const TIME_OFFSET_RANGE: RangeInclusive<i16> = -120..=120;
+
+/// Window, in minutes, Darwin accepts.
+pub const TIME_WINDOW_RANGE: RangeInclusive<u16> = 0..=120;
+
+/// Validated parameters for a board request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardParams<'a> {
+    num_rows: u8,
+    time_offset: i16,
+    time_window: u16,
+    filter_to: Option<&'a Crs>,
+}
+
+impl<'a> BoardParams<'a> {
+    /// Check board parameters against Darwin's limits.
+    pub fn new(num_rows: u8, time_offset: i16, time_window: u16) -> Result<Self, DarwinError> {
+        check("numRows", num_rows, &NUM_ROWS_RANGE)?;
+        check("timeOffset", time_offset, &TIME_OFFSET_RANGE)?;
+        check("timeWindow", time_window, &TIME_WINDOW_RANGE)?;
+        Ok(Self {
+            num_rows,
+            time_offset,
+            time_window,
+            filter_to: None,
+        })
+    }
+
+    /// Only services calling at `crs` after the board station.
+    pub fn filter_to(mut self, crs: &'a Crs) -> Self {
+        self.filter_to = Some(crs);
+        self
+    }
+
+    /// Number of services requested.
+    pub fn num_rows(&self) -> u8 {
+        self.num_rows
+    }
+
+    /// Minutes from now the board starts.
+    pub fn time_offset(&self) -> i16 {
+        self.time_offset
+    }
+
+    /// Minutes the board covers.
+    pub fn time_window(&self) -> u16 {
+        self.time_window
+    }
+
+    /// Query parameters for the public API.
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("numRows", self.num_rows.to_string()),
+            ("timeOffset", self.time_offset.to_string()),
+            ("timeWindow", self.time_window.to_string()),
+        ];
+        if let Some(crs) = self.filter_to {
+            query.push(("filterCrs", crs.as_str().to_string()));
+            query.push(("filterType", "to".to_string()));
+        }
+        query
+    }
+
+    /// Query parameters for the staff API, which takes the board time in
+    /// the path rather than an offset.
+    pub fn staff_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![
+            ("numRows", self.num_rows.to_string()),
+            ("timeWindow", self.time_window.to_string()),
+        ];
+        if let Some(crs) = self.filter_to {
+            query.push(("filterCRS", crs.as_str().to_string()));
+            query.push(("filterType", "to".to_string()));
+        }
+        query
+    }
+}
+
+/// The stretch of time a board covers, anchored to when it was requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardWindow {
+    now: RailTime,
+    time_offset: i16,
+    time_window: u16,
+}
+
+impl BoardWindow {
+    /// A board starting `time_offset` minutes from `now` and covering
+    /// `time_window` minutes.
+    ///
+    /// Limits are checked when the board is requested (see [`BoardParams`]).
+    pub fn new(now: RailTime, time_offset: i16, time_window: u16) -> Self {
+        Self {
+            now,
+            time_offset,
+            time_window,
+        }
+    }
+
+    /// A board requested at `current_mins` past midnight on `date`.
+    pub fn at(date: NaiveDate, current_mins: u16, time_offset: i16, time_window: u16) -> Self {
+        let mins = u32::from(current_mins) % (24 * 60);
+        let time = NaiveTime::from_hms_opt(mins / 60, mins % 60, 0).unwrap_or_default();
+        Self::new(RailTime::new(date, time), time_offset, time_window)
+    }
+
+    /// A board requested at `now` starting at `from`, covering up to
+    /// `time_window` minutes.
+    ///
+    /// The offset is worked out from the clock rather than assumed to be
+    /// zero, so a user whose next stop is 40 minutes away sees the trains
+    /// there then. Darwin won't look more than two hours either side of
+    /// now, nor past two hours ahead, so the offset is clamped and the
+    /// window cut short to fit. Returns `None` if `from` is too far ahead
+    /// for any of it to fit.
+    pub fn starting(now: RailTime, from: RailTime, time_window: u16) -> Option<Self> {
+        let (min_offset, max_offset) = (*TIME_OFFSET_RANGE.start(), *TIME_OFFSET_RANGE.end());
+        let offset = from
+            .signed_duration_since(now)
+            .num_minutes()
+            .clamp(i64::from(min_offset), i64::from(max_offset)) as i16;
+        let room = TIME_WINDOW_RANGE.end().saturating_sub(offset.max(0) as u16);
+        let time_window = time_window.min(room);
+        (time_window > 0).then(|| Self::new(now, offset, time_window))
+    }
+
+    /// When the board was requested.
+    pub fn now(&self) -> RailTime {
+        self.now
+    }
+
+    /// Minutes from now the board starts.
+    pub fn time_offset(&self) -> i16 {
+        self.time_offset
+    }
+
+    /// Minutes the board covers.
+    pub fn time_window(&self) -> u16 {
+        self.time_window
+    }
+
+    /// Earliest time on the board.
+    pub fn start(&self) -> RailTime {
+        self.now + Duration::minutes(i64::from(self.time_offset))
+    }
+
+    /// Latest time on the board.
+    pub fn end(&self) -> RailTime {
+        self.start() + Duration::minutes(i64::from(self.time_window))
+    }
+
+    /// Whether the board covers times on two dates.
+    pub fn spans_midnight(&self) -> bool {
+        self.start().date() != self.end().date()
+    }
+
+    /// Date a board time falls on.
+    ///
+    /// A board only reaches two hours either side of now, so a time is put
+    /// on whichever day brings it within 12 hours of the window's start:
+    /// "00:10" on a board starting at 23:50 is tomorrow, and "23:58" (a
+    /// late-running train still shown) on a board starting at 00:05 is
+    /// yesterday.
+    pub fn date_of(&self, time: NaiveTime) -> NaiveDate {
+        let start = self.start();
+        let t = RailTime::new(start.date(), time);
+        let diff = t.signed_duration_since(start);
+        if diff < -Duration::hours(12) {
+            start.date().succ_opt().unwrap_or(start.date())
+        } else if diff > Duration::hours(12) {
+            start.date().pred_opt().unwrap_or(start.date())
+        } else {
+            start.date()
+        }
+    }
+}
+
+fn check<T: PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: T,
+    range: &RangeInclusive<T>,
+) -> Result<(), DarwinError> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(DarwinError::InvalidRequest(format!(
+            "{name} must be between {} and {}, got {value}",
+            range.start(),
+            range.end()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Classify, ErrorKind};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    fn hm(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn public_query() {
+        let params = BoardParams::new(150, -30, 120).unwrap();
+        assert_eq!(
+            params.query(),
+            vec![
+                ("numRows", "150".to_string()),
+                ("timeOffset", "-30".to_string()),
+                ("timeWindow", "120".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filtered_queries() {
+        let bri = Crs::parse("BRI").unwrap();
+        let params = BoardParams::new(10, 0, 60).unwrap().filter_to(&bri);
+
+        assert_eq!(
+            &params.query()[3..],
+            &[
+                ("filterCrs", "BRI".to_string()),
+                ("filterType", "to".to_string())
+            ]
+        );
+        assert_eq!(
+            params.staff_query(),
+            vec![
+                ("numRows", "10".to_string()),
+                ("timeWindow", "60".to_string()),
+                ("filterCRS", "BRI".to_string()),
+                ("filterType", "to".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bounds_are_inclusive() {
+        assert!(BoardParams::new(1, -120, 0).is_ok());
+        assert!(BoardParams::new(150, 120, 120).is_ok());
+    }
+
+    #[test]
+    fn out_of_range_is_config_error() {
+        let err = BoardParams::new(151, 0, 60).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid request: numRows must be between 1 and 150, got 151"
+        );
+        assert_eq!(err.kind(), ErrorKind::Config);
+
+        assert!(BoardParams::new(0, 0, 60).is_err());
+        assert!(BoardParams::new(10, -121, 60).is_err());
+        assert!(BoardParams::new(10, 121, 60).is_err());
+        assert!(BoardParams::new(10, 0, 121).is_err());
+    }
+
+    #[test]
+    fn window_before_midnight_spans_it() {
+        // 23:50 on the 15th, looking two hours ahead
+        let window = BoardWindow::at(date(15), 23 * 60 + 50, 0, 120);
+
+        assert!(window.spans_midnight());
+        assert_eq!(window.start().date(), date(15));
+        assert_eq!(window.end().date(), date(16));
+        assert_eq!(window.end().to_string(), "01:50");
+        assert_eq!(window.date_of(hm("23:55")), date(15));
+        assert_eq!(window.date_of(hm("00:10")), date(16));
+        assert_eq!(window.date_of(hm("01:45")), date(16));
+    }
+
+    #[test]
+    fn offset_moves_window_past_midnight() {
+        // Requested at 23:30 but starting an hour later
+        let window = BoardWindow::at(date(15), 23 * 60 + 30, 60, 60);
+
+        assert!(!window.spans_midnight());
+        assert_eq!(window.start().date(), date(16));
+        assert_eq!(window.date_of(hm("00:45")), date(16));
+    }
+
+    #[test]
+    fn late_train_before_midnight_is_yesterday() {
+        let window = BoardWindow::at(date(16), 5, 0, 120);
+
+        assert!(!window.spans_midnight());
+        assert_eq!(window.date_of(hm("23:58")), date(15));
+        assert_eq!(window.date_of(hm("00:30")), date(16));
+    }
+
+    #[test]
+    fn starting_later_offsets_from_now() {
+        let now = RailTime::new(date(15), hm("14:00"));
+        let from = RailTime::new(date(15), hm("14:40"));
+
+        let window = BoardWindow::starting(now, from, 120).unwrap();
+        assert_eq!(window.time_offset(), 40);
+        assert_eq!(window.time_window(), 80);
+        assert_eq!(window.end().to_string(), "16:00");
+
+        let window = BoardWindow::starting(now, from, 30).unwrap();
+        assert_eq!(window.time_window(), 30);
+    }
+
+    #[test]
+    fn starting_out_of_reach_is_clamped() {
+        let now = RailTime::new(date(15), hm("23:30"));
+
+        let earlier = RailTime::new(date(15), hm("20:00"));
+        let window = BoardWindow::starting(now, earlier, 60).unwrap();
+        assert_eq!(window.time_offset(), -120);
+
+        let tomorrow = RailTime::new(date(16), hm("01:00"));
+        let window = BoardWindow::starting(now, tomorrow, 120).unwrap();
+        assert_eq!(window.start().date(), date(16));
+        assert_eq!(window.time_window(), 30);
+
+        let too_late = RailTime::new(date(16), hm("02:00"));
+        assert_eq!(BoardWindow::starting(now, too_late, 120), None);
+    }
+
+    #[test]
+    fn daytime_window_stays_on_its_date() {
+        let window = BoardWindow::at(date(15), 14 * 60, -30, 120);
+
+        assert!(!window.spans_midnight());
+        assert_eq!(window.start().to_string(), "13:30");
+        assert_eq!(window.date_of(hm("10:00")), date(15));
+        assert_eq!(window.date_of(hm("15:29")), date(15));
+    }
+}
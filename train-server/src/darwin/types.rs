@@ -3,11 +3,16 @@
 //! These types map directly to the Darwin LDB JSON API responses.
 //! They use `Option` liberally because Darwin omits fields rather than
 //! sending null values in many cases.
+//!
+//! The staff API (LDBSVWS) returns a superset of the public (LDBWS) shape, so
+//! its extra fields live on the same structs and are simply absent for
+//! public responses. Staff responses also give times as ISO datetimes
+//! ("2024-03-15T10:45:00") rather than "HH:MM".
 
 use serde::Deserialize;
 
 /// Response from `GetDepBoardWithDetails` or `GetArrDepBoardWithDetails`.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StationBoardWithDetails {
     /// When this response was generated (ISO 8601 datetime).
@@ -39,7 +44,7 @@ pub struct StationBoardWithDetails {
 }
 
 /// A service on the departure board, including calling points.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ServiceItemWithCallingPoints {
     /// Ephemeral Darwin service ID. Only valid while on departure board.
@@ -49,6 +54,12 @@ pub struct ServiceItemWithCallingPoints {
     /// Retail Service ID (headcode-like, e.g., "GW123400").
     pub rsid: Option<String>,
 
+    /// Schedule UID (staff API only, e.g., "P12345").
+    pub uid: Option<String>,
+
+    /// Train ID / headcode (staff API only, e.g., "1A23").
+    pub trainid: Option<String>,
+
     /// Scheduled time of arrival at this station.
     pub sta: Option<String>,
 
@@ -80,6 +91,9 @@ pub struct ServiceItemWithCallingPoints {
     /// Train length in coaches.
     pub length: Option<i32>,
 
+    /// Coach-by-coach formation (staff API only).
+    pub formation: Option<Formation>,
+
     /// Origin station(s).
     pub origin: Option<Vec<ServiceLocation>>,
 
@@ -97,6 +111,9 @@ pub struct ServiceItemWithCallingPoints {
 
     /// Reason for delay (if delayed).
     pub delay_reason: Option<String>,
+
+    /// Activity codes at this station (staff API only).
+    pub activities: Option<String>,
 }
 
 /// Response from `GetServiceDetails`.
@@ -165,13 +182,16 @@ pub struct ServiceDetails {
 
     /// Subsequent calling points.
     pub subsequent_calling_points: Option<Vec<ArrayOfCallingPoints>>,
+
+    /// Activity codes at board station (staff API only).
+    pub activities: Option<String>,
 }
 
 /// Wrapper for a list of calling points.
 ///
 /// Darwin wraps calling points in this structure to support split/join services,
 /// where multiple arrays represent different portions of a train.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ArrayOfCallingPoints {
     /// The calling points in this portion.
@@ -188,7 +208,7 @@ pub struct ArrayOfCallingPoints {
 }
 
 /// A single calling point (station stop).
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallingPoint {
     /// Human-readable station name.
@@ -206,6 +226,24 @@ pub struct CallingPoint {
     /// Actual time (only present after the train has called).
     pub at: Option<String>,
 
+    /// Scheduled arrival (staff API only).
+    pub sta: Option<String>,
+
+    /// Estimated arrival (staff API only).
+    pub eta: Option<String>,
+
+    /// Actual arrival (staff API only).
+    pub ata: Option<String>,
+
+    /// Scheduled departure (staff API only).
+    pub std: Option<String>,
+
+    /// Estimated departure (staff API only).
+    pub etd: Option<String>,
+
+    /// Actual departure (staff API only).
+    pub atd: Option<String>,
+
     /// Whether this call is cancelled.
     pub is_cancelled: Option<bool>,
 
@@ -217,11 +255,34 @@ pub struct CallingPoint {
 
     /// Delay reason at this stop.
     pub delay_reason: Option<String>,
+
+    /// Activity codes at this stop, two characters each (staff API only).
+    /// "R " marks a request stop.
+    pub activities: Option<String>,
 }
 
-/// Origin or destination location.
+/// Train formation (staff API only).
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct Formation {
+    /// Coaches in order from the front of the train.
+    pub coaches: Option<Vec<CoachData>>,
+}
+
+/// A single coach in a [`Formation`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoachData {
+    /// Coach identifier as shown on the train (e.g., "A").
+    pub number: Option<String>,
+
+    /// Accommodation class (e.g., "First", "Standard").
+    pub coach_class: Option<String>,
+}
+
+/// Origin or destination location.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ServiceLocation {
     /// Human-readable station name.
     pub location_name: String,
@@ -427,6 +488,49 @@ mod tests {
         assert_eq!(subseq[0].calling_point.len(), 2);
     }
 
+    #[test]
+    fn deserialize_staff_service_fields() {
+        let json = r#"{
+            "serviceID": "abc123",
+            "uid": "P12345",
+            "trainid": "1A23",
+            "std": "2024-03-15T10:45:00",
+            "formation": {
+                "coaches": [
+                    {"number": "A", "coachClass": "First"},
+                    {"number": "B", "coachClass": "Standard"}
+                ]
+            },
+            "subsequentCallingPoints": [
+                {
+                    "callingPoint": [
+                        {
+                            "locationName": "Reading",
+                            "crs": "RDG",
+                            "sta": "2024-03-15T11:08:00",
+                            "std": "2024-03-15T11:10:00",
+                            "etd": "2024-03-15T11:12:00"
+                        }
+                    ]
+                }
+            ]
+        }"#;
+
+        let service: ServiceItemWithCallingPoints = serde_json::from_str(json).unwrap();
+
+        assert_eq!(service.uid.as_deref(), Some("P12345"));
+        assert_eq!(service.trainid.as_deref(), Some("1A23"));
+        let coaches = service.formation.unwrap().coaches.unwrap();
+        assert_eq!(coaches.len(), 2);
+        assert_eq!(coaches[0].coach_class.as_deref(), Some("First"));
+
+        let cp = &service.subsequent_calling_points.unwrap()[0].calling_point[0];
+        assert_eq!(cp.st, None);
+        assert_eq!(cp.sta.as_deref(), Some("2024-03-15T11:08:00"));
+        assert_eq!(cp.std.as_deref(), Some("2024-03-15T11:10:00"));
+        assert_eq!(cp.etd.as_deref(), Some("2024-03-15T11:12:00"));
+    }
+
     /// Golden test: parse real Darwin response from Elizabeth Line at Whitechapel.
     /// This captures the case where intermediate calling points only have departure times.
     #[test]
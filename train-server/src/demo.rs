@@ -0,0 +1,594 @@
+//! Offline demo network.
+//!
+//! A miniature GB network - 36 stations on nine routes, 200 trains across
+//! the day - served by [`MockDarwinClient`], so the planner can be tried
+//! without National Rail credentials. Each station's board holds the whole
+//! day's timetable and the mock serves just the slice a request's window
+//! covers. The timetable repeats daily; [`clock`] starts the server at a
+//! fixed time on [`fixture_day`] so every run of the demo sees the same
+//! trains, delays and cancellations.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveTime};
+
+use crate::clock::Clock;
+use crate::darwin::{
+    ArrayOfCallingPoints, CallingPoint, MockDarwinClient, ServiceItemWithCallingPoints,
+    ServiceLocation, StationBoardWithDetails,
+};
+use crate::domain::Crs;
+use crate::stations::{StationClient, StationLocation, StationNames};
+
+/// Stations on the network: CRS, name, latitude and longitude.
+const STATIONS: &[(&str, &str, f64, f64)] = &[
+    ("BAN", "Banbury", 52.0603, -1.3279),
+    ("BDM", "Bedford", 52.1362, -0.4792),
+    ("BHI", "Birmingham International", 52.4507, -1.7258),
+    ("BHM", "Birmingham New Street", 52.4778, -1.8990),
+    ("BPW", "Bristol Parkway", 51.5138, -2.5424),
+    ("BRI", "Bristol Temple Meads", 51.4491, -2.5813),
+    ("BTH", "Bath Spa", 51.3776, -2.3571),
+    ("CBG", "Cambridge", 52.1943, 0.1374),
+    ("CNM", "Cheltenham Spa", 51.8973, -2.0997),
+    ("COV", "Coventry", 52.4009, -1.5134),
+    ("CPM", "Chippenham", 51.4625, -2.1153),
+    ("DBY", "Derby", 52.9166, -1.4633),
+    ("DID", "Didcot Parkway", 51.6110, -1.2427),
+    ("DON", "Doncaster", 53.5221, -1.1397),
+    ("EUS", "London Euston", 51.5282, -0.1337),
+    ("FPK", "Finsbury Park", 51.5643, -0.1065),
+    ("GRA", "Grantham", 52.9063, -0.6422),
+    ("HIT", "Hitchin", 51.9532, -0.2636),
+    ("KGX", "London Kings Cross", 51.5320, -0.1233),
+    ("LEI", "Leicester", 52.6315, -1.1254),
+    ("LMS", "Leamington Spa", 52.2845, -1.5362),
+    ("LUT", "Luton", 51.8824, -0.4140),
+    ("MAN", "Manchester Piccadilly", 53.4774, -2.2309),
+    ("MKC", "Milton Keynes Central", 52.0343, -0.7740),
+    ("NNG", "Newark North Gate", 53.0818, -0.7997),
+    ("OXF", "Oxford", 51.7535, -1.2700),
+    ("PAD", "London Paddington", 51.5154, -0.1755),
+    ("PBO", "Peterborough", 52.5747, -0.2502),
+    ("RDG", "Reading", 51.4589, -0.9719),
+    ("RUG", "Rugby", 52.3791, -1.2503),
+    ("SHF", "Sheffield", 53.3782, -1.4620),
+    ("SOT", "Stoke-on-Trent", 53.0079, -2.1809),
+    ("STP", "London St Pancras International", 51.5319, -0.1263),
+    ("SVG", "Stevenage", 51.9016, -0.2070),
+    ("SWI", "Swindon", 51.5655, -1.7855),
+    ("YRK", "York", 53.9580, -1.0930),
+];
+
+/// A route the demo timetable runs trains along, in both directions.
+struct Route {
+    operator: &'static str,
+    operator_code: &'static str,
+    /// Letter used in the route's headcodes
+    headcode_letter: char,
+    /// Stations called at, with minutes from the first
+    stops: &'static [(&'static str, u16)],
+    /// First departure from either end, minutes past midnight
+    first: u16,
+    /// Last departure from either end, minutes past midnight
+    last: u16,
+    /// Minutes between departures
+    every: u16,
+    coaches: i32,
+}
+
+const ROUTES: &[Route] = &[
+    Route {
+        operator: "Great Western Railway",
+        operator_code: "GW",
+        headcode_letter: 'C',
+        stops: &[
+            ("PAD", 0),
+            ("RDG", 25),
+            ("DID", 41),
+            ("SWI", 57),
+            ("CPM", 72),
+            ("BTH", 85),
+            ("BRI", 98),
+        ],
+        first: 6 * 60,
+        last: 21 * 60,
+        every: 60,
+        coaches: 9,
+    },
+    Route {
+        operator: "Great Western Railway",
+        operator_code: "GW",
+        headcode_letter: 'P',
+        stops: &[("RDG", 0), ("DID", 16), ("OXF", 30)],
+        first: 6 * 60 + 15,
+        last: 21 * 60 + 15,
+        every: 60,
+        coaches: 5,
+    },
+    Route {
+        operator: "CrossCountry",
+        operator_code: "XC",
+        headcode_letter: 'V',
+        stops: &[
+            ("BRI", 0),
+            ("BPW", 10),
+            ("CNM", 42),
+            ("BHM", 88),
+            ("DBY", 125),
+            ("SHF", 160),
+            ("YRK", 212),
+        ],
+        first: 7 * 60,
+        last: 19 * 60,
+        every: 120,
+        coaches: 4,
+    },
+    Route {
+        operator: "Avanti West Coast",
+        operator_code: "VT",
+        headcode_letter: 'G',
+        stops: &[
+            ("EUS", 0),
+            ("MKC", 31),
+            ("RUG", 50),
+            ("COV", 61),
+            ("BHI", 71),
+            ("BHM", 82),
+        ],
+        first: 6 * 60 + 20,
+        last: 21 * 60 + 20,
+        every: 60,
+        coaches: 11,
+    },
+    Route {
+        operator: "Avanti West Coast",
+        operator_code: "VT",
+        headcode_letter: 'H',
+        stops: &[("EUS", 0), ("MKC", 32), ("SOT", 84), ("MAN", 128)],
+        first: 7 * 60 + 40,
+        last: 19 * 60 + 40,
+        every: 120,
+        coaches: 11,
+    },
+    Route {
+        operator: "LNER",
+        operator_code: "GR",
+        headcode_letter: 'N',
+        stops: &[
+            ("KGX", 0),
+            ("SVG", 20),
+            ("PBO", 46),
+            ("GRA", 66),
+            ("NNG", 78),
+            ("DON", 98),
+            ("YRK", 120),
+        ],
+        first: 6 * 60,
+        last: 21 * 60,
+        every: 60,
+        coaches: 9,
+    },
+    Route {
+        operator: "Great Northern",
+        operator_code: "GN",
+        headcode_letter: 'T',
+        stops: &[
+            ("KGX", 0),
+            ("FPK", 7),
+            ("SVG", 24),
+            ("HIT", 30),
+            ("CBG", 52),
+        ],
+        first: 6 * 60 + 45,
+        last: 20 * 60 + 45,
+        every: 120,
+        coaches: 8,
+    },
+    Route {
+        operator: "CrossCountry",
+        operator_code: "XC",
+        headcode_letter: 'O',
+        stops: &[
+            ("BHM", 0),
+            ("LMS", 22),
+            ("BAN", 40),
+            ("OXF", 57),
+            ("RDG", 82),
+        ],
+        first: 8 * 60 + 10,
+        last: 18 * 60 + 10,
+        every: 120,
+        coaches: 4,
+    },
+    Route {
+        operator: "East Midlands Railway",
+        operator_code: "EM",
+        headcode_letter: 'F',
+        stops: &[
+            ("STP", 0),
+            ("LUT", 22),
+            ("BDM", 36),
+            ("LEI", 68),
+            ("DBY", 95),
+            ("SHF", 128),
+        ],
+        first: 6 * 60 + 30,
+        last: 20 * 60 + 30,
+        every: 120,
+        coaches: 7,
+    },
+];
+
+/// The day the fixture timetable is dated on.
+pub fn fixture_day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 3, 17).expect("valid fixture day")
+}
+
+/// A clock reading `start` on the fixture day when the server boots, and
+/// running on in real time from there.
+pub fn clock(start: NaiveTime) -> Clock {
+    Clock::pinned(fixture_day().and_time(start))
+}
+
+/// A mock Darwin client serving the demo timetable.
+pub fn client() -> MockDarwinClient {
+    MockDarwinClient::from_boards(boards()).with_window_filtering()
+}
+
+/// Names and locations of the demo network's stations.
+pub fn station_names(client: StationClient) -> StationNames {
+    let names = STATIONS
+        .iter()
+        .map(|(crs, name, _, _)| (crs_of(crs), name.to_string()))
+        .collect();
+    let locations = STATIONS
+        .iter()
+        .filter_map(|(crs, _, lat, lon)| Some((crs_of(crs), StationLocation::new(*lat, *lon)?)))
+        .collect();
+    StationNames::from_known(client, names, locations)
+}
+
+/// Each station's board for the whole fixture day.
+pub fn boards() -> HashMap<Crs, StationBoardWithDetails> {
+    let mut items: HashMap<&str, Vec<(u16, ServiceItemWithCallingPoints)>> = HashMap::new();
+    for trip in trips() {
+        for (i, call) in trip.calls.iter().enumerate() {
+            let at = call.departs.or(call.arrives).unwrap_or_default();
+            items
+                .entry(call.crs)
+                .or_default()
+                .push((at, trip.board_item(i)));
+        }
+    }
+
+    STATIONS
+        .iter()
+        .map(|(crs, name, _, _)| {
+            let mut services = items.remove(crs).unwrap_or_default();
+            services.sort_by_key(|(at, _)| *at);
+            let board = StationBoardWithDetails {
+                generated_at: format!("{}T00:00:00Z", fixture_day()),
+                location_name: name.to_string(),
+                crs: crs.to_string(),
+                train_services: Some(services.into_iter().map(|(_, s)| s).collect()),
+                platform_available: Some(true),
+                are_services_available: Some(true),
+                ..Default::default()
+            };
+            (crs_of(crs), board)
+        })
+        .collect()
+}
+
+/// One timetabled train.
+struct Trip {
+    id: usize,
+    route: usize,
+    headcode: String,
+    calls: Vec<TripCall>,
+}
+
+/// A train's call at a station, in minutes past midnight.
+struct TripCall {
+    crs: &'static str,
+    arrives: Option<u16>,
+    departs: Option<u16>,
+}
+
+/// Every train in the day's timetable, numbered in route order.
+fn trips() -> Vec<Trip> {
+    let mut trips = Vec::new();
+    for (route_idx, route) in ROUTES.iter().enumerate() {
+        let total = route.stops.last().map_or(0, |(_, mins)| *mins);
+        for reverse in [false, true] {
+            let stops: Vec<(&'static str, u16)> = if reverse {
+                route
+                    .stops
+                    .iter()
+                    .rev()
+                    .map(|(crs, mins)| (*crs, total - mins))
+                    .collect()
+            } else {
+                route.stops.to_vec()
+            };
+
+            let departures = (route.first..=route.last).step_by(usize::from(route.every));
+            for (n, departs) in departures.enumerate() {
+                let last = stops.len() - 1;
+                let calls = stops
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (crs, mins))| {
+                        let at = departs + mins;
+                        TripCall {
+                            crs,
+                            // A minute's dwell at intermediate stops
+                            arrives: (i > 0).then(|| if i == last { at } else { at - 1 }),
+                            departs: (i < last).then_some(at),
+                        }
+                    })
+                    .collect();
+                trips.push(Trip {
+                    id: trips.len(),
+                    route: route_idx,
+                    headcode: format!(
+                        "1{}{:02}",
+                        route.headcode_letter,
+                        n * 2 + usize::from(reverse)
+                    ),
+                    calls,
+                });
+            }
+        }
+    }
+    trips
+}
+
+impl Trip {
+    fn route(&self) -> &'static Route {
+        &ROUTES[self.route]
+    }
+
+    /// Minutes late, the same all the way along.
+    fn delay(&self) -> u16 {
+        match self.id % 9 {
+            4 => 7,
+            7 => 2,
+            _ => 0,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.id % 53 == 26
+    }
+
+    /// Darwin's estimate for a scheduled time.
+    fn expected(&self, scheduled: u16) -> String {
+        if self.is_cancelled() {
+            "Cancelled".to_string()
+        } else if self.delay() > 0 {
+            hhmm(scheduled + self.delay())
+        } else {
+            "On time".to_string()
+        }
+    }
+
+    /// Platform at a call: termini spread trains over their platforms,
+    /// through stations use one per direction.
+    fn platform(&self, i: usize) -> String {
+        let through = i > 0 && i < self.calls.len() - 1;
+        let platform = if through {
+            let up = self.calls[0].crs != self.route().stops[0].0;
+            self.route % 3 * 2 + 1 + usize::from(up)
+        } else {
+            self.id % 6 + 1
+        };
+        platform.to_string()
+    }
+
+    fn location(&self, i: usize) -> ServiceLocation {
+        let crs = self.calls[i].crs;
+        ServiceLocation {
+            location_name: station_name(crs).to_string(),
+            crs: crs.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn calling_point(&self, i: usize) -> CallingPoint {
+        let call = &self.calls[i];
+        CallingPoint {
+            location_name: station_name(call.crs).to_string(),
+            crs: call.crs.to_string(),
+            sta: call.arrives.map(hhmm),
+            eta: call.arrives.map(|t| self.expected(t)),
+            std: call.departs.map(hhmm),
+            etd: call.departs.map(|t| self.expected(t)),
+            is_cancelled: Some(self.is_cancelled()),
+            length: Some(self.route().coaches),
+            ..Default::default()
+        }
+    }
+
+    fn calling_points(&self, range: std::ops::Range<usize>) -> Option<Vec<ArrayOfCallingPoints>> {
+        (!range.is_empty()).then(|| {
+            vec![ArrayOfCallingPoints {
+                calling_point: range.map(|i| self.calling_point(i)).collect(),
+                ..Default::default()
+            }]
+        })
+    }
+
+    /// This train as it appears on the board of its `i`th call.
+    fn board_item(&self, i: usize) -> ServiceItemWithCallingPoints {
+        let route = self.route();
+        let call = &self.calls[i];
+        ServiceItemWithCallingPoints {
+            service_id: format!("demo_{:03}", self.id),
+            rsid: Some(format!("{}{:04}00", route.operator_code, self.id)),
+            uid: Some(format!("D{:05}", self.id)),
+            trainid: Some(self.headcode.clone()),
+            sta: call.arrives.map(hhmm),
+            eta: call.arrives.map(|t| self.expected(t)),
+            std: call.departs.map(hhmm),
+            etd: call.departs.map(|t| self.expected(t)),
+            platform: Some(self.platform(i)),
+            operator: Some(route.operator.to_string()),
+            operator_code: Some(route.operator_code.to_string()),
+            is_cancelled: Some(self.is_cancelled()),
+            length: Some(route.coaches),
+            origin: Some(vec![self.location(0)]),
+            destination: Some(vec![self.location(self.calls.len() - 1)]),
+            previous_calling_points: self.calling_points(0..i),
+            subsequent_calling_points: self.calling_points(i + 1..self.calls.len()),
+            ..Default::default()
+        }
+    }
+}
+
+fn station_name(crs: &str) -> &'static str {
+    STATIONS
+        .iter()
+        .find(|(c, ..)| *c == crs)
+        .map_or("", |(_, name, ..)| name)
+}
+
+fn crs_of(crs: &str) -> Crs {
+    Crs::parse(crs).expect("demo stations have valid CRS codes")
+}
+
+fn hhmm(mins: u16) -> String {
+    format!("{:02}:{:02}", mins / 60 % 24, mins % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::darwin::BoardWindow;
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn network_is_the_advertised_size() {
+        assert_eq!(STATIONS.len(), 36);
+        assert_eq!(trips().len(), 200);
+        assert_eq!(boards().len(), STATIONS.len());
+    }
+
+    #[test]
+    fn every_route_stop_is_a_known_station() {
+        for route in ROUTES {
+            for (crs, _) in route.stops {
+                assert!(!station_name(crs).is_empty(), "{crs} missing from STATIONS");
+            }
+        }
+        assert!(boards().values().all(|b| b.train_services.is_some()));
+    }
+
+    #[test]
+    fn headcodes_are_unique() {
+        let trips = trips();
+        let mut headcodes: Vec<&str> = trips.iter().map(|t| t.headcode.as_str()).collect();
+        headcodes.sort();
+        headcodes.dedup();
+        assert_eq!(headcodes.len(), trips.len());
+    }
+
+    #[tokio::test]
+    async fn board_serves_the_requested_window() {
+        let client = client();
+        let rdg = crs_of("RDG");
+        let window = BoardWindow::new(
+            crate::domain::RailTime::new(fixture_day(), hm(8, 30)),
+            0,
+            60,
+        );
+
+        let services = client
+            .get_departures_with_details(&rdg, 50, window)
+            .await
+            .unwrap();
+
+        assert!(!services.is_empty());
+        for s in &services {
+            assert!(s.candidate.scheduled_departure <= window.end());
+        }
+        let destinations: Vec<&str> = services
+            .iter()
+            .map(|s| s.candidate.destination.as_str())
+            .collect();
+        assert!(destinations.contains(&"Bristol Temple Meads"));
+        assert!(destinations.contains(&"Oxford"));
+    }
+
+    #[tokio::test]
+    async fn terminating_trains_are_only_arrivals() {
+        let client = client();
+        let pad = crs_of("PAD");
+        let window = BoardWindow::new(
+            crate::domain::RailTime::new(fixture_day(), hm(8, 30)),
+            0,
+            120,
+        );
+
+        let departures = client
+            .get_departures_with_details(&pad, 50, window)
+            .await
+            .unwrap();
+        let arrivals = client
+            .get_arrivals_with_details(&pad, 50, window)
+            .await
+            .unwrap();
+
+        assert!(!departures.is_empty() && !arrivals.is_empty());
+        assert!(
+            departures
+                .iter()
+                .all(|s| s.candidate.destination != "London Paddington")
+        );
+        assert!(
+            arrivals
+                .iter()
+                .all(|s| s.candidate.destination == "London Paddington")
+        );
+    }
+
+    #[tokio::test]
+    async fn a_train_has_the_same_id_and_times_on_every_board() {
+        let client = client();
+        let window = BoardWindow::new(
+            crate::domain::RailTime::new(fixture_day(), hm(6, 0)),
+            0,
+            120,
+        );
+        let pad = client
+            .get_departures_with_details(&crs_of("PAD"), 10, window)
+            .await
+            .unwrap();
+        let first = &pad[0].service;
+        let swi = client
+            .get_departures_with_details(&crs_of("SWI"), 50, window)
+            .await
+            .unwrap();
+        let same = swi
+            .iter()
+            .find(|s| s.service.service_ref.darwin_id == first.service_ref.darwin_id)
+            .expect("Paddington's first train calls at Swindon");
+
+        assert_eq!(same.service.calls.len(), first.calls.len());
+        for (a, b) in same.service.calls.iter().zip(&first.calls) {
+            assert_eq!(a.station, b.station);
+            assert_eq!(a.booked_departure, b.booked_departure);
+        }
+    }
+
+    #[test]
+    fn clock_starts_on_the_fixture_day() {
+        let now = clock(hm(8, 30)).now();
+        assert_eq!(now.date(), fixture_day());
+        assert!(now.time() >= hm(8, 30));
+    }
+}
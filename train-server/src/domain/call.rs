@@ -87,6 +87,17 @@ pub struct Call {
     pub realtime_departure: Option<RailTime>,
     /// Whether this call is cancelled
     pub is_cancelled: bool,
+    /// Whether the train only stops here on request
+    pub is_request_stop: bool,
+    /// Whether passengers may only alight here, not board
+    pub is_set_down_only: bool,
+    /// Whether passengers may only board here, not alight
+    pub is_pick_up_only: bool,
+    /// Why the train is late here, as Darwin gives it: either text or a
+    /// numeric reason code (see `darwin::ReferenceTables`)
+    pub delay_reason: Option<String>,
+    /// Why the train is cancelled here, as Darwin gives it
+    pub cancel_reason: Option<String>,
 }
 
 impl Call {
@@ -101,6 +112,11 @@ impl Call {
             realtime_arrival: None,
             realtime_departure: None,
             is_cancelled: false,
+            is_request_stop: false,
+            is_set_down_only: false,
+            is_pick_up_only: false,
+            delay_reason: None,
+            cancel_reason: None,
         }
     }
 
@@ -134,6 +150,18 @@ impl Call {
         self.realtime_departure.or(self.booked_departure)
     }
 
+    /// Returns true if passengers may board here, i.e. the train doesn't
+    /// only set down.
+    pub fn can_board(&self) -> bool {
+        !self.is_set_down_only
+    }
+
+    /// Returns true if passengers may alight here, i.e. the train doesn't
+    /// only pick up.
+    pub fn can_alight(&self) -> bool {
+        !self.is_pick_up_only
+    }
+
     /// Returns the booked arrival time.
     pub fn booked_arrival(&self) -> Option<RailTime> {
         self.booked_arrival
@@ -180,6 +208,7 @@ impl Call {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::ServiceBuilder;
     use chrono::NaiveDate;
 
     fn date() -> NaiveDate {
@@ -250,6 +279,28 @@ mod tests {
         assert!(call.realtime_arrival.is_none());
         assert!(call.realtime_departure.is_none());
         assert!(!call.is_cancelled);
+        assert!(call.can_board());
+        assert!(call.can_alight());
+    }
+
+    #[test]
+    fn set_down_and_pick_up_only() {
+        let service = ServiceBuilder::new("WATWOK")
+            .call("WAT")
+            .dep("10:00")
+            .pick_up_only()
+            .call("WOK")
+            .arr("10:25")
+            .set_down_only()
+            .build();
+        let [waterloo, woking] = &service.calls[..] else {
+            panic!("expected two calls");
+        };
+
+        assert!(waterloo.can_board());
+        assert!(!waterloo.can_alight());
+        assert!(!woking.can_board());
+        assert!(woking.can_alight());
     }
 
     #[test]
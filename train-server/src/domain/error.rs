@@ -3,7 +3,7 @@
 //! These errors represent validation failures and data inconsistencies
 //! in the domain layer. They are distinct from API/IO errors.
 
-use super::Crs;
+use super::{Crs, RailTime};
 
 /// Domain-level errors for validation and data consistency.
 #[derive(Debug, Clone, thiserror::Error)]
@@ -27,6 +27,25 @@ pub enum DomainError {
     /// Journey has no segments
     #[error("journey must have at least one segment")]
     EmptyJourney,
+
+    /// A train departs before the journey reaches its boarding station
+    #[error("train from {station} departs at {departure}, before arriving there at {arrival}")]
+    MissedConnection {
+        /// Where the train is boarded
+        station: Crs,
+        /// When the journey gets there, after any walk
+        arrival: RailTime,
+        /// When the train departs
+        departure: RailTime,
+    },
+
+    /// A train is boarded at a call where it only sets down
+    #[error("train only sets down at {0}, so can't be boarded there")]
+    SetDownOnly(Crs),
+
+    /// A train is left at a call where it only picks up
+    #[error("train only picks up at {0}, so can't be left there")]
+    PickUpOnly(Crs),
 }
 
 #[cfg(test)]
@@ -54,5 +73,22 @@ mod tests {
 
         let err = DomainError::EmptyJourney;
         assert_eq!(err.to_string(), "journey must have at least one segment");
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let err = DomainError::MissedConnection {
+            station: Crs::parse("RDG").unwrap(),
+            arrival: RailTime::parse_hhmm("10:30", date).unwrap(),
+            departure: RailTime::parse_hhmm("10:25", date).unwrap(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "train from RDG departs at 10:25, before arriving there at 10:30"
+        );
+
+        let err = DomainError::SetDownOnly(Crs::parse("WAT").unwrap());
+        assert_eq!(
+            err.to_string(),
+            "train only sets down at WAT, so can't be boarded there"
+        );
     }
 }
@@ -0,0 +1,294 @@
+//! Train formations: the coaches a train is made of, and where it divides.
+//!
+//! Darwin's staff API lists a train's coaches from the front, and shows a
+//! train that divides as extra lists of calling points, one per portion
+//! leaving it. Passengers for stations beyond a division need to be in the
+//! right part of the train, which [`Formation::boarding_advice`] tells them.
+//!
+//! Darwin doesn't say which end of the train each portion is. A service's
+//! own calling points are taken to be those of the front portion, and
+//! portions dividing from it to be at the rear. A passenger staying on a
+//! rear portion rides a service built by [`Service::portions_after`], whose
+//! formation is marked as being for the rear of the train.
+//!
+//! [`Service::portions_after`]: super::Service::portions_after
+
+use super::{Call, CallIndex, Crs};
+
+/// One coach of a train.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coach {
+    /// Identifier shown on the coach (e.g. "A"), if any
+    pub number: Option<String>,
+
+    /// Whether the coach has first class seating
+    pub first_class: bool,
+}
+
+/// An end of a train.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrainEnd {
+    /// The front, which carries on along the service's own calling points
+    #[default]
+    Front,
+    /// The rear, which divides off
+    Rear,
+}
+
+impl TrainEnd {
+    /// Lowercase name, as used in hints and the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrainEnd::Front => "front",
+            TrainEnd::Rear => "rear",
+        }
+    }
+
+    /// The other end of the train.
+    pub fn opposite(self) -> Self {
+        match self {
+            TrainEnd::Front => TrainEnd::Rear,
+            TrainEnd::Rear => TrainEnd::Front,
+        }
+    }
+}
+
+/// Part of a train that divides from it and runs to another destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Portion {
+    /// Station where this portion divides from the train
+    pub divides_at: Crs,
+
+    /// Where this portion terminates
+    pub destination: Crs,
+
+    /// Display name of where this portion terminates
+    pub destination_name: String,
+
+    /// Coaches carrying on with the rest of the train after the division,
+    /// if known
+    pub remaining_coaches: Option<usize>,
+
+    /// This portion's calling points after the division, to its
+    /// destination; empty if not known
+    pub calls: Vec<Call>,
+}
+
+/// The make-up of a train as it leaves the station it was looked up at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Formation {
+    /// Coaches from the front of the train; empty if not listed
+    pub coaches: Vec<Coach>,
+
+    /// Length in coaches, as reported when the coaches aren't listed
+    pub length: Option<usize>,
+
+    /// Portions dividing from the train further on, in calling order
+    pub portions: Vec<Portion>,
+
+    /// Which end of the train the service's calling points are for
+    pub end: TrainEnd,
+}
+
+/// Where to sit on a train that divides before the passenger gets off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardingAdvice {
+    /// The end of the train to travel in
+    pub end: TrainEnd,
+
+    /// How many coaches at that end go all the way, if known
+    pub coaches: Option<usize>,
+
+    /// Display name of the station where the train divides
+    pub divides_at_name: String,
+
+    /// Display name of where the other end of the train goes instead
+    pub other_destination_name: String,
+}
+
+impl BoardingAdvice {
+    /// A short, human-readable hint for someone boarding or already on the
+    /// train.
+    pub fn hint(&self) -> String {
+        let end = self.end.as_str();
+        let part = match self.coaches {
+            Some(1) => format!("{end} coach"),
+            Some(n) => format!("{end} {n} coaches"),
+            None => format!("{end} of the train"),
+        };
+        format!(
+            "Travel in the {part}: the train divides at {} and the {} goes to {}.",
+            self.divides_at_name,
+            self.end.opposite().as_str(),
+            self.other_destination_name
+        )
+    }
+}
+
+impl Formation {
+    /// Length of the train in coaches, if known.
+    pub fn length(&self) -> Option<usize> {
+        if self.coaches.is_empty() {
+            self.length
+        } else {
+            Some(self.coaches.len())
+        }
+    }
+
+    /// Returns true if the train divides after the station it was looked up
+    /// at.
+    pub fn divides(&self) -> bool {
+        !self.portions.is_empty()
+    }
+
+    /// Advice for riding this train from `board` to `alight`, where `calls`
+    /// are the calling points of the service it belongs to.
+    ///
+    /// Returns `None` unless the train divides strictly between the two.
+    /// If it divides more than once on the way, the advice is for the last
+    /// division, which leaves the fewest coaches.
+    pub fn boarding_advice(
+        &self,
+        calls: &[Call],
+        board: CallIndex,
+        alight: CallIndex,
+    ) -> Option<BoardingAdvice> {
+        let between = calls.get(board.0 + 1..alight.0)?;
+        let (divides_at, portion) = between.iter().rev().find_map(|call| {
+            let portion = self
+                .portions
+                .iter()
+                .find(|p| p.divides_at == call.station)?;
+            Some((call, portion))
+        })?;
+        Some(BoardingAdvice {
+            end: self.end,
+            coaches: portion.remaining_coaches,
+            divides_at_name: divides_at.station_name.clone(),
+            other_destination_name: portion.destination_name.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    /// Waterloo to Portsmouth Harbour, with a Southampton portion dividing
+    /// at Woking.
+    fn calls() -> Vec<Call> {
+        [
+            ("WAT", "London Waterloo"),
+            ("WOK", "Woking"),
+            ("GLD", "Guildford"),
+            ("PMH", "Portsmouth Harbour"),
+        ]
+        .into_iter()
+        .map(|(station, name)| Call::new(crs(station), name.into()))
+        .collect()
+    }
+
+    fn formation(remaining: Option<usize>) -> Formation {
+        Formation {
+            coaches: Vec::new(),
+            length: Some(10),
+            portions: vec![Portion {
+                divides_at: crs("WOK"),
+                destination: crs("SOU"),
+                destination_name: "Southampton Central".into(),
+                remaining_coaches: remaining,
+                calls: Vec::new(),
+            }],
+            end: TrainEnd::Front,
+        }
+    }
+
+    #[test]
+    fn advice_when_dividing_during_leg() {
+        let advice = formation(Some(5))
+            .boarding_advice(&calls(), CallIndex(0), CallIndex(3))
+            .unwrap();
+
+        assert_eq!(advice.coaches, Some(5));
+        assert_eq!(
+            advice.hint(),
+            "Travel in the front 5 coaches: the train divides at Woking and the rear goes to Southampton Central."
+        );
+    }
+
+    #[test]
+    fn advice_without_coach_count() {
+        let advice = formation(None)
+            .boarding_advice(&calls(), CallIndex(0), CallIndex(2))
+            .unwrap();
+
+        assert_eq!(
+            advice.hint(),
+            "Travel in the front of the train: the train divides at Woking and the rear goes to Southampton Central."
+        );
+    }
+
+    #[test]
+    fn no_advice_unless_dividing_strictly_between() {
+        let formation = formation(Some(5));
+
+        // Alighting at the division
+        assert!(
+            formation
+                .boarding_advice(&calls(), CallIndex(0), CallIndex(1))
+                .is_none()
+        );
+        // Boarding at the division
+        assert!(
+            formation
+                .boarding_advice(&calls(), CallIndex(1), CallIndex(3))
+                .is_none()
+        );
+        // A train that doesn't divide
+        assert!(
+            Formation::default()
+                .boarding_advice(&calls(), CallIndex(0), CallIndex(3))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn advice_for_rear_portion() {
+        let mut formation = formation(Some(4));
+        formation.end = TrainEnd::Rear;
+        formation.portions[0].destination_name = "Portsmouth Harbour".into();
+
+        let advice = formation
+            .boarding_advice(&calls(), CallIndex(0), CallIndex(2))
+            .unwrap();
+
+        assert_eq!(advice.end, TrainEnd::Rear);
+        assert_eq!(
+            advice.hint(),
+            "Travel in the rear 4 coaches: the train divides at Woking and the front goes to Portsmouth Harbour."
+        );
+    }
+
+    #[test]
+    fn length_prefers_listed_coaches() {
+        let mut formation = formation(None);
+        assert_eq!(formation.length(), Some(10));
+        assert!(formation.divides());
+
+        formation.coaches = vec![
+            Coach {
+                number: Some("A".into()),
+                first_class: true,
+            },
+            Coach {
+                number: Some("B".into()),
+                first_class: false,
+            },
+        ];
+        assert_eq!(formation.length(), Some(2));
+    }
+}
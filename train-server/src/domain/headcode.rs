@@ -24,6 +24,13 @@ use std::fmt;
 /// // Non-standard formats return None
 /// assert!(Headcode::parse("ABCD").is_none());
 /// assert!(Headcode::parse("1234").is_none());
+///
+/// // Free-text input is tolerated via parse_normalized
+/// let hc = Headcode::parse_normalized(" 1a 23 ").unwrap();
+/// assert_eq!(hc.as_str(), "1A23");
+///
+/// // The alternate Display form decodes the class and route
+/// assert_eq!(format!("{:#}", hc), "1A23 (express passenger, route A)");
 /// ```
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Headcode([u8; 4]);
@@ -58,6 +65,19 @@ impl Headcode {
         Some(Headcode([bytes[0], bytes[1], bytes[2], bytes[3]]))
     }
 
+    /// Parse a headcode from user free-text input.
+    ///
+    /// Like [`Headcode::parse`], but ignores surrounding and embedded
+    /// whitespace and accepts lowercase letters (e.g. `" 1a 23"` → `1A23`).
+    pub fn parse_normalized(s: &str) -> Option<Self> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        Self::parse(&normalized)
+    }
+
     /// Returns the headcode as a string slice.
     pub fn as_str(&self) -> &str {
         // SAFETY: We only store valid ASCII characters
@@ -66,32 +86,109 @@ impl Headcode {
 
     /// Returns the train class digit (first character).
     ///
-    /// This indicates the type of service:
-    /// - 0: Light locomotive
-    /// - 1: Express passenger
-    /// - 2: Ordinary passenger
-    /// - 3: Parcels/mail
-    /// - 4-6: Freight
-    /// - 9: Eurostar (historically)
+    /// This indicates the type of service; see [`TrainClass`] for the decoding.
     pub fn class_digit(&self) -> char {
         self.0[0] as char
     }
 
+    /// Returns the decoded train class.
+    pub fn class(&self) -> TrainClass {
+        TrainClass::from_digit(self.0[0] - b'0')
+    }
+
     /// Returns the route letter (second character).
+    ///
+    /// This identifies the destination area, but the meaning of each letter
+    /// varies by region, so it isn't decoded further.
     pub fn route_letter(&self) -> char {
         self.0[1] as char
     }
 }
 
+/// Train class, as encoded by the first digit of a headcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrainClass {
+    /// 0: Light locomotive
+    LightLocomotive,
+    /// 1: Express passenger
+    ExpressPassenger,
+    /// 2: Ordinary (stopping) passenger
+    OrdinaryPassenger,
+    /// 3: Parcels, mail and priority empty stock
+    Parcels,
+    /// 4, 6, 7, 8: Freight (the digit encodes the speed band)
+    Freight,
+    /// 5: Empty coaching stock
+    EmptyCoachingStock,
+    /// 9: International and special passenger services
+    Special,
+}
+
+impl TrainClass {
+    /// Decode a class digit (0-9).
+    fn from_digit(d: u8) -> Self {
+        match d {
+            0 => TrainClass::LightLocomotive,
+            1 => TrainClass::ExpressPassenger,
+            2 => TrainClass::OrdinaryPassenger,
+            3 => TrainClass::Parcels,
+            5 => TrainClass::EmptyCoachingStock,
+            9 => TrainClass::Special,
+            _ => TrainClass::Freight,
+        }
+    }
+
+    /// Whether trains of this class carry passengers.
+    pub fn is_passenger(&self) -> bool {
+        matches!(
+            self,
+            TrainClass::ExpressPassenger | TrainClass::OrdinaryPassenger | TrainClass::Special
+        )
+    }
+
+    /// Short human-readable description.
+    pub fn description(&self) -> &'static str {
+        match self {
+            TrainClass::LightLocomotive => "light locomotive",
+            TrainClass::ExpressPassenger => "express passenger",
+            TrainClass::OrdinaryPassenger => "ordinary passenger",
+            TrainClass::Parcels => "parcels",
+            TrainClass::Freight => "freight",
+            TrainClass::EmptyCoachingStock => "empty coaching stock",
+            TrainClass::Special => "special passenger",
+        }
+    }
+}
+
+impl fmt::Display for TrainClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
 impl fmt::Debug for Headcode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Headcode({})", self.as_str())
     }
 }
 
+/// Renders the bare headcode (e.g. `1A23`).
+///
+/// The alternate form (`{:#}`) adds the decoded class and route letter,
+/// e.g. `1A23 (express passenger, route A)`.
 impl fmt::Display for Headcode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(self.as_str())
+        if f.alternate() {
+            write!(
+                f,
+                "{} ({}, route {})",
+                self.as_str(),
+                self.class(),
+                self.route_letter()
+            )
+        } else {
+            f.write_str(self.as_str())
+        }
     }
 }
 
@@ -169,6 +266,53 @@ mod tests {
         assert_eq!(format!("{}", hc), "2B45");
     }
 
+    #[test]
+    fn display_alternate_decodes() {
+        let hc = Headcode::parse("2B45").unwrap();
+        assert_eq!(format!("{:#}", hc), "2B45 (ordinary passenger, route B)");
+    }
+
+    #[test]
+    fn class_decoding() {
+        let class = |s| Headcode::parse(s).unwrap().class();
+        assert_eq!(class("0Z01"), TrainClass::LightLocomotive);
+        assert_eq!(class("1A23"), TrainClass::ExpressPassenger);
+        assert_eq!(class("2C10"), TrainClass::OrdinaryPassenger);
+        assert_eq!(class("3S50"), TrainClass::Parcels);
+        assert_eq!(class("4L90"), TrainClass::Freight);
+        assert_eq!(class("5A01"), TrainClass::EmptyCoachingStock);
+        assert_eq!(class("6M12"), TrainClass::Freight);
+        assert_eq!(class("7V04"), TrainClass::Freight);
+        assert_eq!(class("8F30"), TrainClass::Freight);
+        assert_eq!(class("9O10"), TrainClass::Special);
+    }
+
+    #[test]
+    fn passenger_classes() {
+        assert!(TrainClass::ExpressPassenger.is_passenger());
+        assert!(TrainClass::OrdinaryPassenger.is_passenger());
+        assert!(TrainClass::Special.is_passenger());
+        assert!(!TrainClass::EmptyCoachingStock.is_passenger());
+        assert!(!TrainClass::Freight.is_passenger());
+    }
+
+    #[test]
+    fn parse_normalized_tolerates_case_and_whitespace() {
+        let expected = Headcode::parse("1A23");
+        assert_eq!(Headcode::parse_normalized("1a23"), expected);
+        assert_eq!(Headcode::parse_normalized("  1A23\n"), expected);
+        assert_eq!(Headcode::parse_normalized("1a 23"), expected);
+        assert_eq!(Headcode::parse_normalized("1 A 2 3"), expected);
+    }
+
+    #[test]
+    fn parse_normalized_rejects_invalid() {
+        assert!(Headcode::parse_normalized("").is_none());
+        assert!(Headcode::parse_normalized("   ").is_none());
+        assert!(Headcode::parse_normalized("1a2").is_none());
+        assert!(Headcode::parse_normalized("abcd").is_none());
+    }
+
     #[test]
     fn debug() {
         let hc = Headcode::parse("3C67").unwrap();
@@ -233,6 +377,13 @@ mod proptests {
             prop_assert!(Headcode::parse(&s).is_none());
         }
 
+        /// parse_normalized agrees with parse on lowercase/spaced variants
+        #[test]
+        fn normalized_accepts_lowercase(s in valid_headcode_string()) {
+            let spaced = format!(" {} ", s.to_lowercase());
+            prop_assert_eq!(Headcode::parse_normalized(&spaced), Headcode::parse(&s));
+        }
+
         /// Lowercase in second position is rejected
         #[test]
         fn lowercase_letter_rejected(
@@ -3,7 +3,7 @@
 //! Types for identifying the user's current train based on observable
 //! information like the next station and terminus.
 
-use super::Crs;
+use super::{Crs, Headcode, RailTime};
 
 /// User's criteria for identifying their current train.
 ///
@@ -27,6 +27,18 @@ pub struct IdentifyTrainRequest {
     /// matches this station. Combined with next_station, this often
     /// uniquely identifies the train.
     pub terminus: Option<Crs>,
+
+    /// Headcode, if the user can see it (e.g. on the cab front).
+    pub headcode: Option<Headcode>,
+
+    /// When the user expects the train at the next station.
+    pub time: Option<RailTime>,
+
+    /// Platform at the next station, if announced.
+    pub platform: Option<String>,
+
+    /// Other stations the user knows the train calls at.
+    pub calling_at: Vec<Crs>,
 }
 
 impl IdentifyTrainRequest {
@@ -35,23 +47,45 @@ impl IdentifyTrainRequest {
         Self {
             next_station,
             terminus,
+            headcode: None,
+            time: None,
+            platform: None,
+            calling_at: Vec::new(),
         }
     }
 
     /// Create a request with just the next station.
     pub fn next_station_only(next_station: Crs) -> Self {
-        Self {
-            next_station,
-            terminus: None,
-        }
+        Self::new(next_station, None)
     }
 
     /// Create a request with both next station and terminus.
     pub fn with_terminus(next_station: Crs, terminus: Crs) -> Self {
-        Self {
-            next_station,
-            terminus: Some(terminus),
-        }
+        Self::new(next_station, Some(terminus))
+    }
+
+    /// Add the train's headcode.
+    pub fn with_headcode(mut self, headcode: Headcode) -> Self {
+        self.headcode = Some(headcode);
+        self
+    }
+
+    /// Add when the train is expected at the next station.
+    pub fn with_time(mut self, time: RailTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Add the platform at the next station.
+    pub fn with_platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = Some(platform.into());
+        self
+    }
+
+    /// Add stations the train calls at.
+    pub fn with_calling_at(mut self, stations: Vec<Crs>) -> Self {
+        self.calling_at = stations;
+        self
     }
 }
 
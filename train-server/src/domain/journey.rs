@@ -4,8 +4,9 @@
 //! potentially including multiple train legs and walks between stations.
 
 use chrono::Duration;
+use sha2::{Digest, Sha256};
 
-use super::{Crs, DomainError, Leg, RailTime};
+use super::{Crs, DomainError, Leg, RailTime, ServiceIdentity};
 
 /// A walk between nearby stations.
 ///
@@ -98,20 +99,88 @@ impl Segment {
     }
 }
 
+/// How a journey finishes: getting off its last train, then perhaps
+/// walking to the destination.
+#[derive(Debug, Clone)]
+pub struct Ending {
+    /// The last train, from where it's boarded to where it's left
+    pub leg: Leg,
+    /// The walk from there to the destination, if any
+    pub walk: Option<Walk>,
+}
+
+impl Ending {
+    /// Returns the station the ending arrives at.
+    pub fn destination(&self) -> &Crs {
+        match &self.walk {
+            Some(walk) => &walk.to,
+            None => self.leg.alight_station(),
+        }
+    }
+
+    /// Returns the arrival time at the destination, including any walk.
+    pub fn arrival_time(&self) -> RailTime {
+        let arrival = self.leg.arrival_time();
+        match &self.walk {
+            Some(walk) => arrival + walk.duration,
+            None => arrival,
+        }
+    }
+}
+
+/// A change between two trains within a journey.
+#[derive(Debug, Clone, Copy)]
+pub struct Change<'a> {
+    /// The leg being left
+    pub from: &'a Leg,
+    /// Walk to another station, if the change isn't within one station
+    pub walk: Option<&'a Walk>,
+    /// The leg being joined
+    pub to: &'a Leg,
+}
+
+impl Change<'_> {
+    /// Time between arriving on the first train and the second departing,
+    /// including any walk.
+    pub fn connection_time(&self) -> Duration {
+        self.to
+            .departure_time()
+            .signed_duration_since(self.from.arrival_time())
+    }
+
+    /// Time left over after any walk.
+    pub fn slack(&self) -> Duration {
+        self.connection_time() - self.walk.map_or(Duration::zero(), |w| w.duration)
+    }
+}
+
 /// A complete journey from origin to destination.
 ///
 /// A journey consists of one or more segments (trains and walks).
-/// Segments alternate: Train, Walk, Train, Walk, ... with walks only
-/// between consecutive trains.
+/// Segments alternate: Train, Walk, Train, Walk, ... with walks between
+/// consecutive trains, and possibly one at the end from the last train to
+/// the destination.
 ///
 /// # Invariants
 ///
 /// - At least one segment
-/// - First and last segments are trains (walks only connect trains)
+/// - First segment is a train; the last is a train or a walk from one
 /// - Consecutive segments connect (destination of one = origin of next)
+/// - Each train departs no earlier than the journey reaches its boarding
+///   station, unless built with [`Journey::new_allowing_missed`]
+/// - Each train is left where it doesn't only pick up, and each after the
+///   first is boarded where it doesn't only set down (the first may be the
+///   one the user is already on), unless built with
+///   [`Journey::new_allowing_missed`]
 #[derive(Debug, Clone)]
 pub struct Journey {
     segments: Vec<Segment>,
+    /// Typical gap between departures, if this journey stands in for a
+    /// group of equivalent journeys on a frequent service.
+    headway: Option<Duration>,
+    /// Other ways to finish the journey on the same trains, to stations
+    /// equivalent to its destination.
+    alternative_endings: Vec<Ending>,
 }
 
 impl Journey {
@@ -122,6 +191,9 @@ impl Journey {
     /// Returns `Err` if:
     /// - Segments list is empty
     /// - Segments don't connect (destination != next origin)
+    /// - A train departs before the previous train arrives, plus any walk
+    /// - A train is boarded where it only sets down, or left where it only
+    ///   picks up
     ///
     /// # Examples
     ///
@@ -147,6 +219,7 @@ impl Journey {
     ///     operator_code: None,
     ///     calls: vec![call1, call2],
     ///     board_station_idx: CallIndex(0),
+    ///     formation: None,
     /// });
     ///
     /// let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
@@ -155,6 +228,23 @@ impl Journey {
     /// assert_eq!(journey.segment_count(), 1);
     /// ```
     pub fn new(segments: Vec<Segment>) -> Result<Self, DomainError> {
+        let journey = Self::new_allowing_missed(segments)?;
+        check_chronology(&journey.segments)?;
+        check_access(&journey.segments)?;
+        Ok(journey)
+    }
+
+    /// Constructs a journey whose connections may no longer be possible.
+    ///
+    /// Only checks that the segments connect. Used to show a planned
+    /// journey after delays have broken it, so the missed connection can be
+    /// pointed out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the segments list is empty or the segments don't
+    /// connect.
+    pub fn new_allowing_missed(segments: Vec<Segment>) -> Result<Self, DomainError> {
         if segments.is_empty() {
             return Err(DomainError::EmptyJourney);
         }
@@ -168,7 +258,11 @@ impl Journey {
             }
         }
 
-        Ok(Journey { segments })
+        Ok(Journey {
+            segments,
+            headway: None,
+            alternative_endings: Vec::new(),
+        })
     }
 
     /// Constructs a journey from legs, inserting walks where needed.
@@ -185,7 +279,9 @@ impl Journey {
     ///
     /// # Errors
     ///
-    /// Returns `Err` if consecutive legs don't connect and aren't walkable.
+    /// Returns `Err` if consecutive legs don't connect and aren't walkable,
+    /// if a leg departs before the previous one arrives, plus any walk, or
+    /// if a leg is boarded or left where passengers can't do so.
     pub fn from_legs<F>(legs: Vec<Leg>, walk_duration: F) -> Result<Self, DomainError>
     where
         F: Fn(&Crs, &Crs) -> Option<Duration>,
@@ -217,7 +313,13 @@ impl Journey {
             segments.push(Segment::Train(leg));
         }
 
-        Ok(Journey { segments })
+        check_chronology(&segments)?;
+        check_access(&segments)?;
+        Ok(Journey {
+            segments,
+            headway: None,
+            alternative_endings: Vec::new(),
+        })
     }
 
     /// Returns all segments in order.
@@ -240,11 +342,40 @@ impl Journey {
         self.leg_count().saturating_sub(1)
     }
 
+    /// Returns the number of stations the trains stop at between boarding
+    /// and alighting, over all legs.
+    pub fn stops_count(&self) -> usize {
+        self.legs().map(Leg::stops_count).sum()
+    }
+
     /// Returns all train legs in order.
     pub fn legs(&self) -> impl Iterator<Item = &Leg> {
         self.segments.iter().filter_map(|s| s.as_leg())
     }
 
+    /// Returns the changes between trains, in order.
+    pub fn changes(&self) -> impl Iterator<Item = Change<'_>> {
+        let mut changes = Vec::with_capacity(self.change_count());
+        let mut from = None;
+        let mut walk = None;
+        for segment in &self.segments {
+            match segment {
+                Segment::Train(to) => {
+                    if let Some(from) = from {
+                        changes.push(Change {
+                            from,
+                            walk: walk.take(),
+                            to,
+                        });
+                    }
+                    from = Some(to);
+                }
+                Segment::Walk(w) => walk = Some(w),
+            }
+        }
+        changes.into_iter()
+    }
+
     /// Returns all walks in order.
     pub fn walks(&self) -> impl Iterator<Item = &Walk> {
         self.segments.iter().filter_map(|s| s.as_walk())
@@ -268,10 +399,40 @@ impl Journey {
         self.legs().next().unwrap().departure_time()
     }
 
-    /// Returns the arrival time (from last leg).
+    /// Returns the arrival time: when the last leg arrives, plus any walk
+    /// from it to the destination.
     pub fn arrival_time(&self) -> RailTime {
-        // Safe: last segment must be a train
-        self.legs().last().unwrap().arrival_time()
+        // Safe: there is always a train, and at most a walk after the last
+        let arrival = self.legs().last().unwrap().arrival_time();
+        match self.segments.last() {
+            Some(Segment::Walk(walk)) => arrival + walk.duration,
+            _ => arrival,
+        }
+    }
+
+    /// Returns the walk from the last train to the destination, if the
+    /// journey doesn't end on a train.
+    pub fn final_walk(&self) -> Option<&Walk> {
+        self.segments.last().and_then(|s| s.as_walk())
+    }
+
+    /// Returns the rest of the journey from its `n`th train leg (counting
+    /// from 0), or `None` if it has no such leg.
+    ///
+    /// Walks before that leg are dropped with the legs they connect.
+    pub fn from_leg(&self, n: usize) -> Option<Journey> {
+        let start = self
+            .segments
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_train())
+            .nth(n)?
+            .0;
+        Some(Journey {
+            segments: self.segments[start..].to_vec(),
+            headway: None,
+            alternative_endings: Vec::new(),
+        })
     }
 
     /// Returns the total journey duration.
@@ -280,6 +441,23 @@ impl Journey {
             .signed_duration_since(self.departure_time())
     }
 
+    /// Returns the time from `now` until arrival.
+    ///
+    /// Unlike [`total_duration`](Self::total_duration), this includes the
+    /// wait for the first train, which is what a passenger deciding at
+    /// `now` cares about.
+    pub fn duration_from(&self, now: RailTime) -> Duration {
+        self.arrival_time().signed_duration_since(now)
+    }
+
+    /// Returns the wait from `now` until the first train departs, or zero
+    /// if it already has.
+    pub fn initial_wait(&self, now: RailTime) -> Duration {
+        self.departure_time()
+            .signed_duration_since(now)
+            .max(Duration::zero())
+    }
+
     /// Returns the total walking time.
     pub fn total_walk_duration(&self) -> Duration {
         self.walks().map(|w| w.duration).sum()
@@ -289,12 +467,298 @@ impl Journey {
     pub fn is_direct(&self) -> bool {
         self.leg_count() == 1
     }
+
+    /// Returns how often equivalent journeys run, if this journey represents
+    /// a collapsed group of them.
+    pub fn headway(&self) -> Option<Duration> {
+        self.headway
+    }
+
+    /// Marks this journey as representing equivalent journeys that run
+    /// every `headway`.
+    pub fn with_headway(mut self, headway: Duration) -> Self {
+        self.headway = Some(headway);
+        self
+    }
+
+    /// Returns how the journey finishes: its last train and any walk after.
+    pub fn ending(&self) -> Ending {
+        Ending {
+            // Safe: there is always a train
+            leg: self.legs().last().unwrap().clone(),
+            walk: self.final_walk().cloned(),
+        }
+    }
+
+    /// Returns other ways to finish the journey on the same trains, if it
+    /// stands in for journeys ending at equivalent stations.
+    pub fn alternative_endings(&self) -> &[Ending] {
+        &self.alternative_endings
+    }
+
+    /// Marks this journey as standing in for ones on the same trains that
+    /// finish in each of `endings` instead.
+    pub fn with_alternative_endings(mut self, endings: Vec<Ending>) -> Self {
+        self.alternative_endings = endings;
+        self
+    }
+
+    /// Returns a stable identifier for the route this journey takes.
+    ///
+    /// Journeys on the same trains between the same stations, with the same
+    /// walks, share a fingerprint even if their real-time estimates differ,
+    /// so re-planning the same route yields the same value. Trains are known
+    /// by their [`ServiceIdentity`] rather than their Darwin IDs, which
+    /// change from one board to the next. Returned as 32 lowercase hex
+    /// digits.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Train(leg) => {
+                    let booked = leg
+                        .board_call()
+                        .booked_departure
+                        .map(|t| t.to_string())
+                        .unwrap_or_default();
+                    let train = match ServiceIdentity::of(leg.service()) {
+                        Some(identity) => format!(
+                            "{}@{}>{}",
+                            identity.origin.as_str(),
+                            identity.origin_departure,
+                            identity.terminus.as_str()
+                        ),
+                        None => leg.service().service_ref.darwin_id.clone(),
+                    };
+                    hasher.update(format!(
+                        "T|{}|{}|{}|{}\n",
+                        train,
+                        leg.board_station().as_str(),
+                        leg.alight_station().as_str(),
+                        booked,
+                    ));
+                }
+                Segment::Walk(walk) => {
+                    hasher.update(format!(
+                        "W|{}|{}|{}\n",
+                        walk.from.as_str(),
+                        walk.to.as_str(),
+                        walk.duration.num_minutes(),
+                    ));
+                }
+            }
+        }
+        hasher.finalize()[..16]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+/// Checks that each train departs no earlier than the journey reaches its
+/// boarding station: the previous train's arrival plus any walks since.
+fn check_chronology(segments: &[Segment]) -> Result<(), DomainError> {
+    let mut reached: Option<RailTime> = None;
+    for segment in segments {
+        match segment {
+            Segment::Train(leg) => {
+                if let Some(arrival) = reached
+                    && leg.departure_time() < arrival
+                {
+                    return Err(DomainError::MissedConnection {
+                        station: *leg.board_station(),
+                        arrival,
+                        departure: leg.departure_time(),
+                    });
+                }
+                reached = Some(leg.arrival_time());
+            }
+            Segment::Walk(walk) => reached = reached.map(|t| t + walk.duration),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that each train is left where passengers may alight, and each
+/// after the first boarded where they may board. The first train's
+/// boarding isn't checked: the journey may start on it.
+fn check_access(segments: &[Segment]) -> Result<(), DomainError> {
+    for (i, leg) in segments.iter().filter_map(Segment::as_leg).enumerate() {
+        if i > 0 && !leg.board_call().can_board() {
+            return Err(DomainError::SetDownOnly(*leg.board_station()));
+        }
+        if !leg.alight_call().can_alight() {
+            return Err(DomainError::PickUpOnly(*leg.alight_station()));
+        }
+    }
+    Ok(())
+}
+
+/// Characters used to draw a journey timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimelineGlyphs {
+    /// Box-drawing characters, for terminals
+    #[default]
+    Unicode,
+    /// Plain ASCII, for logs that may not be UTF-8 clean
+    Ascii,
+}
+
+impl TimelineGlyphs {
+    /// (board, ride, alight, change, walk)
+    fn chars(self) -> (char, char, char, char, char) {
+        match self {
+            TimelineGlyphs::Unicode => ('┬', '│', '┴', '╎', '┆'),
+            TimelineGlyphs::Ascii => ('+', '|', '+', ':', '~'),
+        }
+    }
+}
+
+/// Render a journey as a compact text timeline, one line per event.
+///
+/// Each train shows its boarding and alighting stations with expected times
+/// (and how late, where running late), the train and platform, and a ride
+/// line with its stops and time aboard. Changes show the time to spare,
+/// and walks their length:
+///
+/// ```text
+/// PAD → BRI  10:00–11:20, 1h20m, 1 change
+/// 10:00 ┬ Paddington (PAD)  1A23 GWR, plat 3
+///       │ 2 stops, 25m
+/// 10:25 ┴ Reading (RDG)
+///       ╎ change, 10m
+/// 10:35 ┬ Reading (RDG)  GWR
+///       │ 1 stop, 45m
+/// 11:20 ┴ Bristol Temple Meads (BRI)
+/// ```
+pub fn render_timeline(journey: &Journey, glyphs: TimelineGlyphs) -> String {
+    let (board, ride, alight, change, walk_glyph) = glyphs.chars();
+    let arrow = match glyphs {
+        TimelineGlyphs::Unicode => "→",
+        TimelineGlyphs::Ascii => "->",
+    };
+    let dash = match glyphs {
+        TimelineGlyphs::Unicode => "–",
+        TimelineGlyphs::Ascii => "-",
+    };
+    let changes = match journey.change_count() {
+        0 => "direct".to_string(),
+        1 => "1 change".to_string(),
+        n => format!("{n} changes"),
+    };
+    let mut lines = vec![format!(
+        "{} {arrow} {}  {}{dash}{}, {}, {changes}",
+        journey.origin(),
+        journey.destination(),
+        journey.departure_time(),
+        journey.arrival_time(),
+        format_duration(journey.total_duration()),
+    )];
+    let pad = " ".repeat(5);
+
+    let mut walk: Option<&Walk> = None;
+    let mut previous: Option<&Leg> = None;
+    for segment in journey.segments() {
+        let leg = match segment {
+            Segment::Walk(w) => {
+                walk = Some(w);
+                continue;
+            }
+            Segment::Train(leg) => leg,
+        };
+
+        if let Some(from) = previous {
+            let spare = format_duration(
+                Change {
+                    from,
+                    walk,
+                    to: leg,
+                }
+                .slack(),
+            );
+            lines.push(match walk.take() {
+                Some(w) => format!(
+                    "{pad} {walk_glyph} walk {} to {}, {spare} to spare",
+                    format_duration(w.duration),
+                    w.to
+                ),
+                None => format!("{pad} {change} change, {spare}"),
+            });
+        }
+
+        let service = leg.service();
+        let train = match &service.headcode {
+            Some(headcode) => format!("{headcode} {}", service.operator),
+            None => service.operator.clone(),
+        };
+        let platform = leg
+            .board_platform()
+            .map(|p| format!(", plat {p}"))
+            .unwrap_or_default();
+        let cancelled = if leg.is_cancelled() {
+            ", CANCELLED"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "{} {board} {} ({}){}  {train}{platform}{cancelled}",
+            leg.departure_time(),
+            leg.board_station_name(),
+            leg.board_station(),
+            late(leg.board_call().departure_delay()),
+        ));
+
+        let stops = match leg.intermediate_stop_count() {
+            0 => "non-stop".to_string(),
+            1 => "1 stop".to_string(),
+            n => format!("{n} stops"),
+        };
+        lines.push(format!(
+            "{pad} {ride} {stops}, {}",
+            format_duration(leg.duration())
+        ));
+        lines.push(format!(
+            "{} {alight} {} ({}){}",
+            leg.arrival_time(),
+            leg.alight_station_name(),
+            leg.alight_station(),
+            late(leg.alight_call().arrival_delay()),
+        ));
+        previous = Some(leg);
+    }
+    if let Some(w) = walk {
+        lines.push(format!(
+            "{pad} {walk_glyph} walk {} to {}",
+            format_duration(w.duration),
+            w.to
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// A duration as e.g. "25m" or "1h20m".
+fn format_duration(duration: Duration) -> String {
+    let mins = duration.num_minutes();
+    match (mins / 60, mins % 60) {
+        (0, m) => format!("{m}m"),
+        (h, 0) => format!("{h}h"),
+        (h, m) => format!("{h}h{m:02}m"),
+    }
+}
+
+/// A lateness note like ", 6m late", or nothing if on time.
+fn late(delay: Option<Duration>) -> String {
+    delay
+        .map(|d| format!(", {} late", format_duration(d)))
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::{Call, CallIndex, Service, ServiceRef};
+    use crate::domain::{Call, CallIndex, Headcode, Service, ServiceRef};
+    use crate::testing::ServiceBuilder;
     use chrono::NaiveDate;
     use std::sync::Arc;
 
@@ -334,6 +798,7 @@ mod tests {
             operator_code: None,
             calls: vec![call1, call2],
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -398,6 +863,17 @@ mod tests {
         assert_eq!(journey.total_duration(), Duration::minutes(25));
     }
 
+    #[test]
+    fn duration_from_includes_wait() {
+        let service = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+
+        assert_eq!(journey.duration_from(time("09:50")), Duration::minutes(35));
+        assert_eq!(journey.initial_wait(time("09:50")), Duration::minutes(10));
+        assert_eq!(journey.initial_wait(time("10:05")), Duration::zero());
+    }
+
     #[test]
     fn journey_with_change_same_station() {
         // PAD -> RDG, then RDG -> SWI
@@ -441,6 +917,81 @@ mod tests {
         assert_eq!(journey.total_walk_duration(), Duration::minutes(5));
     }
 
+    #[test]
+    fn journey_ending_with_walk() {
+        // KGX -> STP, then walk to EUS
+        let service = make_service("KGX", "King's Cross", "STP", "St Pancras", "10:00", "10:50");
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        let walk = Walk::new(crs("STP"), crs("EUS"), Duration::minutes(8));
+
+        let journey = Journey::new(vec![Segment::Train(leg), Segment::Walk(walk)]).unwrap();
+
+        assert_eq!(journey.destination(), &crs("EUS"));
+        assert_eq!(journey.arrival_time(), time("10:58"));
+        assert_eq!(journey.total_duration(), Duration::minutes(58));
+        assert_eq!(journey.final_walk().map(|w| w.to), Some(crs("EUS")));
+        assert_eq!(journey.change_count(), 0);
+        assert!(
+            render_timeline(&journey, TimelineGlyphs::Ascii).ends_with("\n      ~ walk 8m to EUS")
+        );
+    }
+
+    #[test]
+    fn timeline_shows_trains_changes_and_walks() {
+        let service1 = make_service("KGX", "King's Cross", "CAM", "Cambridge", "10:00", "11:00");
+        let mut service1 = (*service1).clone();
+        service1.headcode = Some(Headcode::parse("1A23").unwrap());
+        service1.calls[0].platform = Some("4".into());
+        service1.calls[1].realtime_arrival = Some(time("11:06"));
+        let service2 = make_service("STP", "St Pancras", "EUS", "Euston", "11:15", "12:20");
+
+        let journey = Journey::new(vec![
+            Segment::Train(Leg::new(Arc::new(service1), CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(Walk::new(crs("CAM"), crs("STP"), Duration::minutes(5))),
+            Segment::Train(Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            render_timeline(&journey, TimelineGlyphs::Unicode),
+            "KGX → EUS  10:00–12:20, 2h20m, 1 change\n\
+             10:00 ┬ King's Cross (KGX)  1A23 Test, plat 4\n\
+             \x20     │ non-stop, 1h06m\n\
+             11:06 ┴ Cambridge (CAM), 6m late\n\
+             \x20     ┆ walk 5m to STP, 4m to spare\n\
+             11:15 ┬ St Pancras (STP)  Test\n\
+             \x20     │ non-stop, 1h05m\n\
+             12:20 ┴ Euston (EUS)"
+        );
+        assert!(
+            render_timeline(&journey, TimelineGlyphs::Ascii)
+                .lines()
+                .all(|line| line.is_ascii())
+        );
+    }
+
+    #[test]
+    fn rest_of_journey_starts_at_the_leg() {
+        let service1 = make_service("KGX", "King's Cross", "CAM", "Cambridge", "10:00", "11:00");
+        let service2 = make_service("STP", "St Pancras", "EUS", "Euston", "11:15", "12:20");
+        let journey = Journey::new(vec![
+            Segment::Train(Leg::new(service1, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(Walk::new(crs("CAM"), crs("STP"), Duration::minutes(5))),
+            Segment::Train(Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap()),
+        ])
+        .unwrap()
+        .with_headway(Duration::minutes(30));
+
+        let rest = journey.from_leg(1).unwrap();
+        assert_eq!(rest.segment_count(), 1);
+        assert_eq!(*rest.origin(), crs("STP"));
+        assert_eq!(rest.departure_time(), time("11:15"));
+        assert_eq!(rest.headway(), None);
+
+        assert_eq!(journey.from_leg(0).unwrap().segment_count(), 3);
+        assert!(journey.from_leg(2).is_none());
+    }
+
     #[test]
     fn journey_from_legs_direct() {
         let service = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
@@ -531,6 +1082,98 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn journey_rejects_train_departing_before_arrival() {
+        let service1 = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
+        let service2 = make_service("RDG", "Reading", "SWI", "Swindon", "10:20", "10:45");
+
+        let leg1 = Leg::new(service1, CallIndex(0), CallIndex(1)).unwrap();
+        let leg2 = Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap();
+        let segments = vec![Segment::Train(leg1), Segment::Train(leg2)];
+
+        let err = Journey::new(segments.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            DomainError::MissedConnection { station, arrival, departure }
+                if station == crs("RDG") && arrival == time("10:25") && departure == time("10:20")
+        ));
+
+        // Still buildable on purpose, e.g. to show the missed connection
+        let journey = Journey::new_allowing_missed(segments).unwrap();
+        assert_eq!(journey.leg_count(), 2);
+    }
+
+    #[test]
+    fn journey_counts_walk_towards_connection() {
+        let service1 = make_service("KGX", "King's Cross", "CAM", "Cambridge", "10:00", "11:00");
+        let service2 = make_service("STP", "St Pancras", "EUS", "Euston", "11:03", "11:20");
+
+        let leg1 = Leg::new(service1, CallIndex(0), CallIndex(1)).unwrap();
+        let leg2 = Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap();
+        let walk = Walk::new(crs("CAM"), crs("STP"), Duration::minutes(5));
+
+        let result = Journey::new(vec![
+            Segment::Train(leg1.clone()),
+            Segment::Walk(walk),
+            Segment::Train(leg2.clone()),
+        ]);
+        assert!(matches!(
+            result,
+            Err(DomainError::MissedConnection { arrival, .. }) if arrival == time("11:05")
+        ));
+
+        let result = Journey::from_legs(vec![leg1, leg2], |_, _| Some(Duration::minutes(5)));
+        assert!(matches!(result, Err(DomainError::MissedConnection { .. })));
+    }
+
+    #[test]
+    fn journey_allows_zero_minute_connection() {
+        let service1 = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
+        let service2 = make_service("RDG", "Reading", "SWI", "Swindon", "10:25", "10:50");
+
+        let leg1 = Leg::new(service1, CallIndex(0), CallIndex(1)).unwrap();
+        let leg2 = Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap();
+
+        assert!(Journey::new(vec![Segment::Train(leg1), Segment::Train(leg2)]).is_ok());
+    }
+
+    #[test]
+    fn journey_rejects_boarding_or_alighting_where_not_allowed() {
+        let leg = |service| Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        let journey = |legs: Vec<Leg>| Journey::new(legs.into_iter().map(Segment::Train).collect());
+        let into_reading = || {
+            ServiceBuilder::new("PADRDG")
+                .call("PAD")
+                .dep("10:00")
+                .call("RDG")
+                .arr("10:25")
+        };
+        let from_reading = || ServiceBuilder::new("RDGSWI").call("RDG").dep("10:30");
+
+        let result = journey(vec![leg(into_reading().pick_up_only().build())]);
+        assert!(matches!(result, Err(DomainError::PickUpOnly(c)) if c == crs("RDG")));
+
+        let result = journey(vec![
+            leg(into_reading().build()),
+            leg(from_reading()
+                .set_down_only()
+                .call("SWI")
+                .arr("10:50")
+                .build()),
+        ]);
+        assert!(matches!(result, Err(DomainError::SetDownOnly(c)) if c == crs("RDG")));
+
+        // The first train may be the one the user is already on
+        let service = ServiceBuilder::new("PADRDG")
+            .call("PAD")
+            .dep("10:00")
+            .set_down_only()
+            .call("RDG")
+            .arr("10:25")
+            .build();
+        assert!(journey(vec![leg(service)]).is_ok());
+    }
+
     #[test]
     fn journey_legs_iterator() {
         let service1 = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
@@ -546,6 +1189,95 @@ mod tests {
         assert_eq!(legs[0].board_station(), &crs("PAD"));
         assert_eq!(legs[1].board_station(), &crs("RDG"));
     }
+
+    #[test]
+    fn journey_changes_iterator() {
+        // KGX -> CAM, walk to STP, STP -> EUS, then EUS -> WFJ
+        let service1 = make_service("KGX", "King's Cross", "CAM", "Cambridge", "10:00", "11:00");
+        let service2 = make_service("STP", "St Pancras", "EUS", "Euston", "11:15", "11:20");
+        let service3 = make_service("EUS", "Euston", "WFJ", "Watford Junction", "11:30", "11:50");
+
+        let journey = Journey::new(vec![
+            Segment::Train(Leg::new(service1, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(Walk::new(crs("CAM"), crs("STP"), Duration::minutes(5))),
+            Segment::Train(Leg::new(service2, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Train(Leg::new(service3, CallIndex(0), CallIndex(1)).unwrap()),
+        ])
+        .unwrap();
+
+        let changes: Vec<_> = journey.changes().collect();
+        assert_eq!(changes.len(), journey.change_count());
+
+        assert_eq!(changes[0].from.alight_station(), &crs("CAM"));
+        assert_eq!(changes[0].walk.unwrap().to, crs("STP"));
+        assert_eq!(changes[0].to.board_station(), &crs("STP"));
+        assert_eq!(changes[0].connection_time(), Duration::minutes(15));
+        assert_eq!(changes[0].slack(), Duration::minutes(10));
+
+        assert!(changes[1].walk.is_none());
+        assert_eq!(changes[1].slack(), Duration::minutes(10));
+    }
+
+    #[test]
+    fn fingerprint_ignores_realtime_estimates() {
+        let service = make_service("PAD", "Paddington", "RDG", "Reading", "10:00", "10:25");
+        let journey = Journey::new(vec![Segment::Train(
+            Leg::new(service.clone(), CallIndex(0), CallIndex(1)).unwrap(),
+        )])
+        .unwrap();
+
+        let mut delayed = (*service).clone();
+        delayed.calls[0].realtime_departure = Some(time("10:07"));
+        delayed.calls[1].realtime_arrival = Some(time("10:32"));
+        let delayed_journey = Journey::new(vec![Segment::Train(
+            Leg::new(Arc::new(delayed), CallIndex(0), CallIndex(1)).unwrap(),
+        )])
+        .unwrap();
+
+        let fingerprint = journey.fingerprint();
+        assert_eq!(fingerprint.len(), 32);
+        assert_eq!(fingerprint, delayed_journey.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_is_the_same_whichever_board_the_train_came_from() {
+        let train = |id: &str| {
+            ServiceBuilder::new(id)
+                .call("PAD")
+                .dep("10:00")
+                .call("SWI")
+                .arr("11:00")
+                .build()
+        };
+        let journey = |service| {
+            Journey::new(vec![Segment::Train(
+                Leg::new(service, CallIndex(0), CallIndex(1)).unwrap(),
+            )])
+            .unwrap()
+        };
+
+        assert_eq!(
+            journey(train("board1")).fingerprint(),
+            journey(train("board2")).fingerprint()
+        );
+    }
+
+    #[test]
+    fn fingerprint_distinguishes_routes() {
+        let direct = make_service("PAD", "Paddington", "SWI", "Swindon", "10:00", "11:00");
+        let later = make_service("PAD", "Paddington", "SWI", "Swindon", "10:30", "11:30");
+
+        let a = Journey::new(vec![Segment::Train(
+            Leg::new(direct, CallIndex(0), CallIndex(1)).unwrap(),
+        )])
+        .unwrap();
+        let b = Journey::new(vec![Segment::Train(
+            Leg::new(later, CallIndex(0), CallIndex(1)).unwrap(),
+        )])
+        .unwrap();
+
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
 }
 
 #[cfg(test)]
@@ -597,6 +1329,7 @@ mod proptests {
             operator_code: None,
             calls: vec![call1, call2],
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -5,7 +5,7 @@
 
 use std::sync::Arc;
 
-use super::{Call, CallIndex, Crs, DomainError, RailTime, Service};
+use super::{BoardingAdvice, Call, CallIndex, Crs, DomainError, RailTime, Service};
 
 /// A leg of a journey (one train).
 ///
@@ -64,6 +64,7 @@ impl Leg {
     ///     operator_code: None,
     ///     calls: vec![call1, call2],
     ///     board_station_idx: CallIndex(0),
+    ///     formation: None,
     /// });
     ///
     /// let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
@@ -188,13 +189,39 @@ impl Leg {
 
     /// Returns all calls for this leg (from board to alight, inclusive).
     pub fn calls(&self) -> &[Call] {
-        &self.service.calls[self.board_idx.0..=self.alight_idx.0]
+        self.service
+            .segment_between(self.board_idx, self.alight_idx)
+            .expect("leg indices are validated on construction")
+    }
+
+    /// Returns the calls between boarding and alighting, in order,
+    /// excluding both.
+    pub fn intermediate_calls(&self) -> impl Iterator<Item = &Call> {
+        let calls = self.calls();
+        calls[1..calls.len() - 1].iter()
+    }
+
+    /// Returns the number of stations the train stops at between boarding
+    /// and alighting, leaving out cancelled calls.
+    pub fn stops_count(&self) -> usize {
+        self.intermediate_calls()
+            .filter(|call| !call.is_cancelled)
+            .count()
     }
 
     /// Returns true if this leg has been cancelled.
     pub fn is_cancelled(&self) -> bool {
         self.board_call().is_cancelled || self.alight_call().is_cancelled
     }
+
+    /// Where to sit, if the train divides before this leg ends.
+    pub fn boarding_advice(&self) -> Option<BoardingAdvice> {
+        self.service.formation.as_ref()?.boarding_advice(
+            &self.service.calls,
+            self.board_idx,
+            self.alight_idx,
+        )
+    }
 }
 
 impl PartialEq for Leg {
@@ -252,6 +279,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -331,6 +359,31 @@ mod tests {
         assert_eq!(calls[2].station, crs("BRI"));
     }
 
+    #[test]
+    fn leg_intermediate_calls() {
+        let service = make_service();
+
+        let leg = Leg::new(service.clone(), CallIndex(0), CallIndex(3)).unwrap();
+        let stations: Vec<_> = leg.intermediate_calls().map(|c| c.station).collect();
+        assert_eq!(stations, [crs("RDG"), crs("SWI")]);
+
+        let leg = Leg::new(service, CallIndex(1), CallIndex(2)).unwrap();
+        assert_eq!(leg.intermediate_calls().count(), 0);
+    }
+
+    #[test]
+    fn leg_stops_count_skips_cancelled_calls() {
+        let service = make_service();
+        let leg = Leg::new(service.clone(), CallIndex(0), CallIndex(3)).unwrap();
+        assert_eq!(leg.stops_count(), 2);
+
+        let mut cancelled = (*service).clone();
+        cancelled.calls[1].is_cancelled = true;
+        let leg = Leg::new(Arc::new(cancelled), CallIndex(0), CallIndex(3)).unwrap();
+        assert_eq!(leg.stops_count(), 1);
+        assert_eq!(leg.intermediate_stop_count(), 2);
+    }
+
     #[test]
     fn leg_invalid_alight_before_board() {
         let service = make_service();
@@ -379,6 +432,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         let result = Leg::new(service, CallIndex(0), CallIndex(1));
@@ -401,6 +455,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         let result = Leg::new(service, CallIndex(0), CallIndex(1));
@@ -434,6 +489,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
@@ -458,6 +514,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
@@ -526,6 +583,7 @@ mod proptests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -6,6 +6,7 @@
 
 mod call;
 mod error;
+mod formation;
 mod headcode;
 mod identify;
 mod journey;
@@ -18,12 +19,13 @@ mod time;
 
 pub use call::{Call, CallIndex};
 pub use error::DomainError;
-pub use headcode::Headcode;
+pub use formation::{BoardingAdvice, Coach, Formation, Portion, TrainEnd};
+pub use headcode::{Headcode, TrainClass};
 pub use identify::{IdentifyTrainRequest, MatchConfidence};
-pub use journey::{Journey, Segment, Walk};
+pub use journey::{Change, Ending, Journey, Segment, TimelineGlyphs, Walk, render_timeline};
 pub use leg::Leg;
 pub use operator::{AtocCode, InvalidAtocCode};
-pub use service::{Service, ServiceCandidate, ServiceRef};
+pub use service::{Service, ServiceCandidate, ServiceIdentity, ServiceRef};
 pub use service_uid::{InvalidServiceUid, ServiceUid};
 pub use station::{Crs, InvalidCrs};
 pub use time::{RailTime, TimeError, parse_time_sequence, parse_time_sequence_reverse};
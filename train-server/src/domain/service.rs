@@ -3,8 +3,9 @@
 //! A `Service` represents a complete train journey with all its calling points.
 //! `ServiceRef` provides an ephemeral reference to a service on Darwin,
 //! and `ServiceCandidate` holds summary info from departure board searches.
+//! `ServiceIdentity` recognises a service across boards once its ID expires.
 
-use super::{AtocCode, Call, CallIndex, Crs, Headcode, RailTime};
+use super::{AtocCode, Call, CallIndex, Crs, Formation, Headcode, Portion, RailTime, TrainEnd};
 
 /// Ephemeral Darwin service reference.
 ///
@@ -105,6 +106,8 @@ pub struct Service {
     pub calls: Vec<Call>,
     /// Index of the board station in the calls list
     pub board_station_idx: CallIndex,
+    /// Coaches and divisions, where Darwin gives them (staff API only)
+    pub formation: Option<Formation>,
 }
 
 impl Service {
@@ -135,6 +138,107 @@ impl Service {
             .map(|(i, call)| (CallIndex(i), call))
     }
 
+    /// Iterate over the calls strictly after the given index, with their
+    /// indices.
+    ///
+    /// These are the calls a passenger at `idx` could alight at.
+    pub fn calls_after(&self, idx: CallIndex) -> impl Iterator<Item = (CallIndex, &Call)> {
+        self.calls
+            .iter()
+            .enumerate()
+            .skip(idx.0 + 1)
+            .map(|(i, call)| (CallIndex(i), call))
+    }
+
+    /// Find the first call at a station strictly after the given index.
+    ///
+    /// Unlike [`find_call`](Self::find_call), the call at `after` itself is
+    /// never returned, so the result can always alight a leg boarding at
+    /// `after`.
+    pub fn call_at(&self, station: &Crs, after: CallIndex) -> Option<(CallIndex, &Call)> {
+        self.calls_after(after)
+            .find(|(_, call)| &call.station == station)
+    }
+
+    /// Where this service calls at a station, as seen on that station's
+    /// board.
+    ///
+    /// Prefers the board station call, so a service that calls at the
+    /// station more than once is boarded where the board showed it;
+    /// otherwise the first call at the station.
+    pub fn board_call_at(&self, station: &Crs) -> Option<(CallIndex, &Call)> {
+        let board = self.board_station_idx;
+        match self.calls.get(board.0) {
+            Some(call) if &call.station == station => Some((board, call)),
+            _ => self.find_call(station, CallIndex(0)),
+        }
+    }
+
+    /// The calls from `board` to `alight`, both inclusive.
+    ///
+    /// Returns `None` unless `board` is before `alight` and both are in
+    /// bounds, i.e. unless the two could make a leg.
+    pub fn segment_between(&self, board: CallIndex, alight: CallIndex) -> Option<&[Call]> {
+        if board >= alight {
+            return None;
+        }
+        self.calls.get(board.0..=alight.0)
+    }
+
+    /// Services for staying on each portion that divides from this train
+    /// strictly after the given index, each with the index of its division.
+    ///
+    /// Each runs along this service's calls as far as the division, then
+    /// along the portion's own, so calls up to the division keep their
+    /// indices. Its formation is marked as being for the rear of the train,
+    /// with the rest of the train as the portion dividing from it. Portions
+    /// whose calling points aren't known are left out.
+    pub fn portions_after(&self, idx: CallIndex) -> Vec<(CallIndex, Service)> {
+        let (Some(formation), Some((_, terminus))) = (&self.formation, self.destination_call())
+        else {
+            return Vec::new();
+        };
+        formation
+            .portions
+            .iter()
+            .filter(|portion| !portion.calls.is_empty())
+            .filter_map(|portion| {
+                let (divides, _) = self.call_at(&portion.divides_at, idx)?;
+                let mut calls = self.calls_up_to_index(divides).to_vec();
+                calls.extend(portion.calls.iter().cloned());
+
+                let detached = formation
+                    .length()
+                    .zip(portion.remaining_coaches)
+                    .and_then(|(length, remaining)| length.checked_sub(remaining))
+                    .filter(|&n| n > 0);
+                let rest = Portion {
+                    divides_at: portion.divides_at,
+                    destination: terminus.station,
+                    destination_name: terminus.station_name.clone(),
+                    remaining_coaches: detached,
+                    calls: Vec::new(),
+                };
+
+                let service = Service {
+                    service_ref: self.service_ref.clone(),
+                    headcode: self.headcode,
+                    operator: self.operator.clone(),
+                    operator_code: self.operator_code,
+                    calls,
+                    board_station_idx: self.board_station_idx,
+                    formation: Some(Formation {
+                        coaches: Vec::new(),
+                        length: formation.length(),
+                        portions: vec![rest],
+                        end: TrainEnd::Rear,
+                    }),
+                };
+                Some((divides, service))
+            })
+            .collect()
+    }
+
     /// Find all calls at a station.
     ///
     /// For services that call at the same station multiple times (loops,
@@ -203,6 +307,40 @@ impl Service {
     }
 }
 
+/// What identifies a service across boards, independent of Darwin IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServiceIdentity {
+    /// First station of the service
+    pub origin: Crs,
+
+    /// Booked departure from the origin
+    pub origin_departure: RailTime,
+
+    /// Last station of the service
+    pub terminus: Crs,
+}
+
+impl ServiceIdentity {
+    /// The identity of a service.
+    ///
+    /// Returns `None` if the service has no calls or its origin has no
+    /// booked departure.
+    pub fn of(service: &Service) -> Option<Self> {
+        let origin = service.calls.first()?;
+        let terminus = service.calls.last()?;
+        Some(Self {
+            origin: origin.station,
+            origin_departure: origin.booked_departure?,
+            terminus: terminus.station,
+        })
+    }
+
+    /// Whether a service (from any board) is the one with this identity.
+    pub fn matches(&self, service: &Service) -> bool {
+        Self::of(service).as_ref() == Some(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -247,6 +385,7 @@ mod tests {
             operator_code: AtocCode::parse("GW").ok(),
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         }
     }
 
@@ -411,6 +550,146 @@ mod tests {
         assert!(result.is_none());
     }
 
+    /// A service that calls at Reading twice, as a loop or turnback might.
+    fn make_looping_service() -> Service {
+        let mut service = make_service();
+        service.calls.push(make_call("RDG", "Reading"));
+        service
+    }
+
+    #[test]
+    fn service_calls_after() {
+        let service = make_service();
+
+        let after: Vec<_> = service
+            .calls_after(CallIndex(1))
+            .map(|(idx, call)| (idx, call.station))
+            .collect();
+        assert_eq!(
+            after,
+            vec![(CallIndex(2), crs("SWI")), (CallIndex(3), crs("BRI"))]
+        );
+
+        // Nothing after the last call, or out of bounds
+        assert_eq!(service.calls_after(CallIndex(3)).count(), 0);
+        assert_eq!(service.calls_after(CallIndex(10)).count(), 0);
+    }
+
+    #[test]
+    fn service_call_at_is_strictly_after() {
+        let service = make_looping_service();
+
+        // Never the call at the index itself
+        let (idx, _) = service.call_at(&crs("RDG"), CallIndex(1)).unwrap();
+        assert_eq!(idx, CallIndex(4));
+        assert_eq!(
+            service.find_call(&crs("RDG"), CallIndex(1)).unwrap().0,
+            CallIndex(1)
+        );
+
+        let (idx, _) = service.call_at(&crs("RDG"), CallIndex(0)).unwrap();
+        assert_eq!(idx, CallIndex(1));
+        assert!(service.call_at(&crs("PAD"), CallIndex(0)).is_none());
+        assert!(service.call_at(&crs("RDG"), CallIndex(4)).is_none());
+    }
+
+    #[test]
+    fn service_board_call_at_prefers_board_station() {
+        let mut service = make_looping_service();
+
+        // Seen on Reading's board at its second call
+        service.board_station_idx = CallIndex(4);
+        assert_eq!(service.board_call_at(&crs("RDG")).unwrap().0, CallIndex(4));
+
+        // Another station falls back to its first call
+        assert_eq!(service.board_call_at(&crs("SWI")).unwrap().0, CallIndex(2));
+        assert!(service.board_call_at(&crs("XXX")).is_none());
+    }
+
+    #[test]
+    fn service_segment_between() {
+        let service = make_service();
+
+        let segment = service.segment_between(CallIndex(1), CallIndex(3)).unwrap();
+        let stations: Vec<_> = segment.iter().map(|c| c.station).collect();
+        assert_eq!(stations, vec![crs("RDG"), crs("SWI"), crs("BRI")]);
+
+        // Must run forwards and stay in bounds
+        assert!(
+            service
+                .segment_between(CallIndex(2), CallIndex(2))
+                .is_none()
+        );
+        assert!(
+            service
+                .segment_between(CallIndex(3), CallIndex(1))
+                .is_none()
+        );
+        assert!(
+            service
+                .segment_between(CallIndex(0), CallIndex(4))
+                .is_none()
+        );
+    }
+
+    /// Paddington to Bristol, with a Cheltenham portion dividing at Swindon.
+    fn make_dividing_service() -> Service {
+        let mut service = make_service();
+        let mut kemble = make_call("KEM", "Kemble");
+        kemble.booked_departure = Some(time("11:15"));
+        let mut cheltenham = make_call("CNM", "Cheltenham Spa");
+        cheltenham.booked_arrival = Some(time("11:50"));
+        service.formation = Some(Formation {
+            length: Some(10),
+            portions: vec![Portion {
+                divides_at: crs("SWI"),
+                destination: crs("CNM"),
+                destination_name: "Cheltenham Spa".into(),
+                remaining_coaches: Some(5),
+                calls: vec![kemble, cheltenham],
+            }],
+            ..Formation::default()
+        });
+        service
+    }
+
+    #[test]
+    fn service_portions_after() {
+        let service = make_dividing_service();
+
+        let portions = service.portions_after(CallIndex(0));
+        assert_eq!(portions.len(), 1);
+        let (divides, portion) = &portions[0];
+        assert_eq!(*divides, CallIndex(2));
+        let stations: Vec<_> = portion.calls.iter().map(|c| c.station).collect();
+        assert_eq!(
+            stations,
+            vec![crs("PAD"), crs("RDG"), crs("SWI"), crs("KEM"), crs("CNM")]
+        );
+        assert_eq!(portion.service_ref, service.service_ref);
+
+        let formation = portion.formation.as_ref().unwrap();
+        assert_eq!(formation.end, TrainEnd::Rear);
+        assert_eq!(formation.portions[0].destination, crs("BRI"));
+        assert_eq!(formation.portions[0].remaining_coaches, Some(5));
+    }
+
+    #[test]
+    fn service_portions_after_division_are_gone() {
+        let service = make_dividing_service();
+
+        // At or beyond Swindon the train has already divided
+        assert!(service.portions_after(CallIndex(2)).is_empty());
+        assert!(make_service().portions_after(CallIndex(0)).is_empty());
+
+        // Nothing to ride without the portion's calling points
+        let mut unknown = make_dividing_service();
+        unknown.formation.as_mut().unwrap().portions[0]
+            .calls
+            .clear();
+        assert!(unknown.portions_after(CallIndex(0)).is_empty());
+    }
+
     #[test]
     fn service_all_calls_at() {
         let service = make_service();
@@ -458,6 +737,7 @@ mod tests {
             operator_code: None,
             calls: vec![],
             board_station_idx: CallIndex(0),
+            formation: None,
         };
 
         assert!(empty.is_empty());
@@ -541,6 +821,7 @@ mod proptests {
                     operator_code: None,
                     calls,
                     board_station_idx: CallIndex(0),
+                    formation: None,
                 };
 
                 let target_crs = crs_from_index(target_idx);
@@ -571,6 +852,7 @@ mod proptests {
                 operator_code: None,
                 calls,
                 board_station_idx: CallIndex(0),
+                formation: None,
             };
 
             let result = service.calls_from_index(CallIndex(start_idx));
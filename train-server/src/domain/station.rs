@@ -29,7 +29,7 @@ pub struct InvalidCrs {
 /// assert!(Crs::parse("KG").is_err());
 /// assert!(Crs::parse("KGXX").is_err());
 /// ```
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Crs([u8; 3]);
 
 impl Crs {
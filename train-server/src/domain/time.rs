@@ -3,8 +3,16 @@
 //! Darwin provides times as "HH:MM" strings. This module provides types for
 //! working with these times in a date-aware manner, handling overnight
 //! services that cross midnight.
-
-use chrono::{Duration, NaiveDate, NaiveTime, Timelike};
+//!
+//! Outside Darwin, times are written as RFC 3339 timestamps in UK time
+//! (e.g. `2024-07-01T14:30:00+01:00`), which is also how [`RailTime`]
+//! serializes.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat,
+    TimeZone, Timelike,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::fmt;
 use std::ops::Add;
@@ -170,6 +178,114 @@ impl RailTime {
         self.to_datetime()
             .signed_duration_since(other.to_datetime())
     }
+
+    /// The UK's offset from UTC at this time: an hour ahead during British
+    /// Summer Time, otherwise none.
+    ///
+    /// In the hour repeated when the clocks go back, BST is assumed; the
+    /// hour skipped when they go forward is read as GMT.
+    pub fn uk_offset(&self) -> FixedOffset {
+        let (starts, ends) = bst_bounds(self.date.year());
+        // The bounds are in UTC, and local time is an hour ahead of UTC
+        // once BST has started
+        let local = self.to_datetime();
+        if local >= starts + Duration::hours(1) && local < ends + Duration::hours(1) {
+            bst()
+        } else {
+            gmt()
+        }
+    }
+
+    /// Format as an RFC 3339 timestamp, taking this time as the local time
+    /// in `tz` (e.g. `2024-03-15T14:30:00+00:00`).
+    ///
+    /// The canonical form, used when serializing, is in UK time (see
+    /// [`uk_offset`](Self::uk_offset)).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use train_server::domain::RailTime;
+    /// use chrono::{FixedOffset, NaiveDate};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+    /// let time = RailTime::parse_hhmm("14:30", date).unwrap();
+    /// assert_eq!(time.to_rfc3339(&time.uk_offset()), "2024-07-01T14:30:00+01:00");
+    ///
+    /// let utc = FixedOffset::east_opt(0).unwrap();
+    /// assert_eq!(time.to_rfc3339(&utc), "2024-07-01T14:30:00+00:00");
+    /// ```
+    pub fn to_rfc3339<Tz: TimeZone>(&self, tz: &Tz) -> String
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let local = self.to_datetime();
+        // A time skipped when the clocks go forward has no local reading
+        let dt = tz
+            .from_local_datetime(&local)
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&local));
+        dt.to_rfc3339_opts(SecondsFormat::Secs, false)
+    }
+
+    /// Parse an RFC 3339 timestamp, converting it to UK time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use train_server::domain::RailTime;
+    ///
+    /// let time = RailTime::from_rfc3339("2024-07-01T13:30:00Z").unwrap();
+    /// assert_eq!(time.to_string(), "14:30");
+    /// assert!(RailTime::from_rfc3339("14:30").is_err());
+    /// ```
+    pub fn from_rfc3339(s: &str) -> Result<Self, TimeError> {
+        let utc = DateTime::parse_from_rfc3339(s)
+            .map_err(|_| TimeError::new("expected an RFC 3339 timestamp"))?
+            .naive_utc();
+        let (starts, ends) = bst_bounds(utc.year());
+        let offset = if utc >= starts && utc < ends {
+            bst()
+        } else {
+            gmt()
+        };
+        let local = utc + Duration::seconds(i64::from(offset.local_minus_utc()));
+        Ok(Self::new(local.date(), local.time()))
+    }
+}
+
+fn gmt() -> FixedOffset {
+    FixedOffset::east_opt(0).expect("zero offset is valid")
+}
+
+fn bst() -> FixedOffset {
+    FixedOffset::east_opt(3600).expect("one hour offset is valid")
+}
+
+/// When British Summer Time starts and ends in a year, in UTC: 01:00 on the
+/// last Sundays of March and October.
+fn bst_bounds(year: i32) -> (NaiveDateTime, NaiveDateTime) {
+    let last_sunday = |month: u32| {
+        let last = NaiveDate::from_ymd_opt(year, month + 1, 1)
+            .and_then(|d| d.pred_opt())
+            .expect("March and October have last days");
+        last - Duration::days(i64::from(last.weekday().num_days_from_sunday()))
+    };
+    let at_one = |date: NaiveDate| date.and_hms_opt(1, 0, 0).expect("01:00 is valid");
+    (at_one(last_sunday(3)), at_one(last_sunday(10)))
+}
+
+impl Serialize for RailTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_rfc3339(&self.uk_offset()))
+    }
+}
+
+impl<'de> Deserialize<'de> for RailTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::from_rfc3339(&s).map_err(serde::de::Error::custom)
+    }
 }
 
 impl Add<Duration> for RailTime {
@@ -750,6 +866,48 @@ mod tests {
         assert_eq!(parsed[0].unwrap().date(), d);
         assert_eq!(parsed[1].unwrap().date(), prev_day);
     }
+
+    #[test]
+    fn uk_offset_follows_summer_time() {
+        // BST 2024 ran from 31 March to 27 October
+        let at = |d: NaiveDate, hhmm: &str| RailTime::parse_hhmm(hhmm, d).unwrap();
+        let hours = |t: RailTime| t.uk_offset().local_minus_utc() / 3600;
+
+        assert_eq!(hours(at(date(2024, 3, 31), "00:59")), 0);
+        assert_eq!(hours(at(date(2024, 3, 31), "02:00")), 1);
+        assert_eq!(hours(at(date(2024, 7, 1), "12:00")), 1);
+        assert_eq!(hours(at(date(2024, 10, 27), "01:30")), 1);
+        assert_eq!(hours(at(date(2024, 10, 27), "02:00")), 0);
+        assert_eq!(hours(at(date(2024, 12, 25), "12:00")), 0);
+    }
+
+    #[test]
+    fn rfc3339_roundtrip() {
+        let summer = RailTime::parse_hhmm("23:45", date(2024, 7, 1)).unwrap();
+        let json = serde_json::to_string(&summer).unwrap();
+        assert_eq!(json, "\"2024-07-01T23:45:00+01:00\"");
+        assert_eq!(serde_json::from_str::<RailTime>(&json).unwrap(), summer);
+
+        let winter = RailTime::parse_hhmm("09:05", date(2024, 1, 15)).unwrap();
+        assert_eq!(
+            winter.to_rfc3339(&winter.uk_offset()),
+            "2024-01-15T09:05:00+00:00"
+        );
+    }
+
+    #[test]
+    fn from_rfc3339_converts_to_uk_time() {
+        // Just after midnight UK time is still the previous day in UTC
+        let t = RailTime::from_rfc3339("2024-07-01T23:30:00Z").unwrap();
+        assert_eq!(t.date(), date(2024, 7, 2));
+        assert_eq!(t.to_string(), "00:30");
+
+        let t = RailTime::from_rfc3339("2024-01-15T10:00:00+01:00").unwrap();
+        assert_eq!(t.to_string(), "09:00");
+
+        assert!(RailTime::from_rfc3339("2024-01-15T10:00").is_err());
+        assert!(serde_json::from_str::<RailTime>("\"10:00\"").is_err());
+    }
 }
 
 #[cfg(test)]
@@ -787,6 +945,16 @@ mod proptests {
             prop_assert_eq!(parsed.to_string(), time_str);
         }
 
+        /// RFC 3339 roundtrips, except in the hour skipped when the clocks
+        /// go forward
+        #[test]
+        fn rfc3339_roundtrip(time_str in valid_time(), date in valid_date()) {
+            let t = RailTime::parse_hhmm(&time_str, date).unwrap();
+            let (starts, _) = bst_bounds(date.year());
+            prop_assume!(!(starts..starts + Duration::hours(1)).contains(&t.to_datetime()));
+            prop_assert_eq!(RailTime::from_rfc3339(&t.to_rfc3339(&t.uk_offset())), Ok(t));
+        }
+
         /// Ordering is transitive
         #[test]
         fn ordering_transitive(
@@ -0,0 +1,117 @@
+//! Shared error classification.
+//!
+//! Each module keeps its own error enum, but they all map onto a common
+//! [`ErrorKind`] so callers can decide whether to retry and the web layer
+//! can pick an HTTP status without knowing every variant.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Broad category of a failure, independent of which module raised it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Temporary failure (network error, timeout, upstream 5xx); worth retrying.
+    Transient,
+    /// Will fail the same way every time (bad input, unparseable response).
+    Permanent,
+    /// The server is misconfigured (missing or rejected credentials).
+    Config,
+    /// The requested thing does not exist (or no longer exists).
+    NotFound,
+    /// An upstream quota was hit; retry only after backing off.
+    RateLimited,
+    /// The caller gave up (e.g. the client disconnected); not a failure of
+    /// anything upstream, and nobody is waiting for a retry.
+    Cancelled,
+}
+
+impl ErrorKind {
+    /// Classify an upstream HTTP status code.
+    ///
+    /// Status 0 is used by the API clients for local failures that never
+    /// reached the network, which are configuration problems.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            0 | 401 | 403 => ErrorKind::Config,
+            404 => ErrorKind::NotFound,
+            429 => ErrorKind::RateLimited,
+            500..=599 => ErrorKind::Transient,
+            _ => ErrorKind::Permanent,
+        }
+    }
+
+    /// How long to wait before retrying, or `None` if retrying is pointless.
+    pub fn retry_delay(self) -> Option<Duration> {
+        match self {
+            ErrorKind::Transient => Some(Duration::from_millis(200)),
+            ErrorKind::RateLimited => Some(Duration::from_secs(2)),
+            ErrorKind::Permanent
+            | ErrorKind::Config
+            | ErrorKind::NotFound
+            | ErrorKind::Cancelled => None,
+        }
+    }
+
+    /// Whether the operation may succeed if retried.
+    pub fn is_retryable(self) -> bool {
+        self.retry_delay().is_some()
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::Transient => "transient",
+            ErrorKind::Permanent => "permanent",
+            ErrorKind::Config => "configuration",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::RateLimited => "rate limited",
+            ErrorKind::Cancelled => "cancelled",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Errors that can be classified into an [`ErrorKind`].
+pub trait Classify {
+    /// The broad category of this error.
+    fn kind(&self) -> ErrorKind;
+
+    /// Whether the failed operation may succeed if retried.
+    fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_classification() {
+        assert_eq!(ErrorKind::from_status(0), ErrorKind::Config);
+        assert_eq!(ErrorKind::from_status(401), ErrorKind::Config);
+        assert_eq!(ErrorKind::from_status(403), ErrorKind::Config);
+        assert_eq!(ErrorKind::from_status(404), ErrorKind::NotFound);
+        assert_eq!(ErrorKind::from_status(429), ErrorKind::RateLimited);
+        assert_eq!(ErrorKind::from_status(400), ErrorKind::Permanent);
+        assert_eq!(ErrorKind::from_status(502), ErrorKind::Transient);
+        assert_eq!(ErrorKind::from_status(503), ErrorKind::Transient);
+    }
+
+    #[test]
+    fn retryability() {
+        assert!(ErrorKind::Transient.is_retryable());
+        assert!(ErrorKind::RateLimited.is_retryable());
+        assert!(!ErrorKind::Permanent.is_retryable());
+        assert!(!ErrorKind::Config.is_retryable());
+        assert!(!ErrorKind::NotFound.is_retryable());
+        assert!(!ErrorKind::Cancelled.is_retryable());
+
+        // Rate limits deserve a longer pause than blips
+        assert!(
+            ErrorKind::RateLimited.retry_delay().unwrap()
+                > ErrorKind::Transient.retry_delay().unwrap()
+        );
+    }
+}
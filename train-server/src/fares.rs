@@ -0,0 +1,175 @@
+//! Rough fare estimates, for price-aware ranking.
+//!
+//! National Rail fares are notoriously hard to pin down (route restrictions,
+//! peak times, advance quotas), and there's no free live source for them.
+//! The planner only needs a coarse idea of which journeys are cheaper, so a
+//! [`FareProvider`] estimates a [`PriceBand`] per journey. [`EstimatedFares`]
+//! is a stand-in working from the journey alone; a real fares source can be
+//! plugged in behind the same trait.
+
+use chrono::{Datelike, NaiveTime, Weekday};
+
+use crate::domain::Journey;
+
+/// Longest time on trains (minutes) for a journey to be priced low.
+const LOW_MAX_RAIL_MINS: i64 = 45;
+
+/// Longest time on trains (minutes) for a journey to be priced medium.
+const MEDIUM_MAX_RAIL_MINS: i64 = 120;
+
+/// How expensive a journey is likely to be, cheapest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PriceBand {
+    /// A short or off-peak hop
+    Low,
+
+    /// A typical fare
+    Medium,
+
+    /// A long-distance or peak-time fare
+    High,
+}
+
+impl PriceBand {
+    /// Short identifier, as used in the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// How many bands above the cheapest this is.
+    pub fn steps_above_low(self) -> i32 {
+        match self {
+            Self::Low => 0,
+            Self::Medium => 1,
+            Self::High => 2,
+        }
+    }
+
+    /// The next band up, or this band if it's already the highest.
+    fn higher(self) -> Self {
+        match self {
+            Self::Low => Self::Medium,
+            Self::Medium | Self::High => Self::High,
+        }
+    }
+}
+
+/// A source of fare estimates.
+///
+/// Estimates are made while ranking search results, so must be cheap; a
+/// provider backed by a remote fares service should answer from data it
+/// already holds.
+pub trait FareProvider: Send + Sync + std::fmt::Debug {
+    /// The likely price band of a journey, or `None` if it can't be priced.
+    fn price_band(&self, journey: &Journey) -> Option<PriceBand>;
+}
+
+/// Fare estimates from the journey itself, until a real fares source is
+/// available.
+///
+/// Journeys are priced by time spent on trains, a rough proxy for distance,
+/// and moved up a band if they start in the weekday morning peak.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EstimatedFares;
+
+impl EstimatedFares {
+    /// Whether a journey starts in the weekday morning peak.
+    fn in_peak(journey: &Journey) -> bool {
+        let departs = journey.departure_time();
+        let weekday = !matches!(departs.date().weekday(), Weekday::Sat | Weekday::Sun);
+        let peak_start = NaiveTime::from_hms_opt(6, 30, 0).expect("valid time");
+        let peak_end = NaiveTime::from_hms_opt(9, 30, 0).expect("valid time");
+        weekday && (peak_start..peak_end).contains(&departs.time())
+    }
+}
+
+impl FareProvider for EstimatedFares {
+    fn price_band(&self, journey: &Journey) -> Option<PriceBand> {
+        let rail_mins: i64 = journey.legs().map(|leg| leg.duration().num_minutes()).sum();
+        let band = if rail_mins <= LOW_MAX_RAIL_MINS {
+            PriceBand::Low
+        } else if rail_mins <= MEDIUM_MAX_RAIL_MINS {
+            PriceBand::Medium
+        } else {
+            PriceBand::High
+        };
+        Some(if Self::in_peak(journey) {
+            band.higher()
+        } else {
+            band
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, Crs, Leg, RailTime, Service, ServiceRef};
+    use chrono::NaiveDate;
+    use std::sync::Arc;
+
+    /// A direct journey PAD → RDG on the given day and times.
+    fn journey(date: NaiveDate, dep: &str, arr: &str) -> Journey {
+        let mut origin = Call::new(Crs::parse("PAD").unwrap(), "Paddington".into());
+        origin.booked_departure = Some(RailTime::parse_hhmm(dep, date).unwrap());
+        let mut dest = Call::new(Crs::parse("RDG").unwrap(), "Reading".into());
+        dest.booked_arrival = Some(RailTime::parse_hhmm(arr, date).unwrap());
+        let service = Arc::new(Service {
+            service_ref: ServiceRef::new("S1".into(), Crs::parse("PAD").unwrap()),
+            headcode: None,
+            operator: "GWR".into(),
+            operator_code: None,
+            calls: vec![origin, dest],
+            board_station_idx: CallIndex(0),
+            formation: None,
+        });
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        Journey::from_legs(vec![leg], |_, _| None).unwrap()
+    }
+
+    #[test]
+    fn bands_by_time_on_trains() {
+        // A Saturday, so never peak
+        let saturday = NaiveDate::from_ymd_opt(2024, 3, 16).unwrap();
+        let fares = EstimatedFares;
+
+        let short = journey(saturday, "08:00", "08:25");
+        let medium = journey(saturday, "08:00", "09:30");
+        let long = journey(saturday, "08:00", "11:00");
+
+        assert_eq!(fares.price_band(&short), Some(PriceBand::Low));
+        assert_eq!(fares.price_band(&medium), Some(PriceBand::Medium));
+        assert_eq!(fares.price_band(&long), Some(PriceBand::High));
+    }
+
+    #[test]
+    fn weekday_peak_costs_more() {
+        let friday = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let fares = EstimatedFares;
+
+        assert_eq!(
+            fares.price_band(&journey(friday, "08:00", "08:25")),
+            Some(PriceBand::Medium)
+        );
+        assert_eq!(
+            fares.price_band(&journey(friday, "09:30", "09:55")),
+            Some(PriceBand::Low)
+        );
+        assert_eq!(
+            fares.price_band(&journey(friday, "08:00", "11:00")),
+            Some(PriceBand::High)
+        );
+    }
+
+    #[test]
+    fn bands_order_cheapest_first() {
+        assert!(PriceBand::Low < PriceBand::Medium);
+        assert!(PriceBand::Medium < PriceBand::High);
+        assert_eq!(PriceBand::High.steps_above_low(), 2);
+        assert_eq!(PriceBand::Medium.as_str(), "medium");
+    }
+}
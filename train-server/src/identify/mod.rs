@@ -1,12 +1,22 @@
 //! Train identification logic.
 //!
 //! This module contains the core logic for identifying a user's current train
-//! based on observable information (next station, terminus).
+//! based on observable information (next station, terminus), and scoring
+//! candidates against anything else the user can see (`score`).
 
+pub mod score;
+
+use std::cmp::Reverse;
 use std::sync::Arc;
 
 use crate::darwin::ConvertedService;
-use crate::domain::{Crs, MatchConfidence};
+use crate::domain::{CallIndex, Crs, IdentifyTrainRequest, MatchConfidence};
+use crate::stations::uk_station_usage;
+
+pub use score::{MatchScore, ScoreReason, ScoringWeights, Signal};
+
+/// How many calling points after the board station [`BoardContext`] lists.
+pub const NEXT_CALLS: usize = 3;
 
 /// A matched train with its confidence level.
 #[derive(Debug, Clone)]
@@ -15,6 +25,43 @@ pub struct TrainMatch {
     pub service: Arc<ConvertedService>,
     /// How confidently we matched the train.
     pub confidence: MatchConfidence,
+    /// Where the train goes next, to tell it apart from others on the board.
+    pub context: BoardContext,
+    /// How well the train fits the user's other observations.
+    pub score: MatchScore,
+}
+
+/// Where a train on a board goes after the board station.
+///
+/// Several trains on a board often share a next station; the stops after
+/// it and the final destination are what a user recognises their train by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardContext {
+    /// Up to [`NEXT_CALLS`] calling points after the board station.
+    ///
+    /// Empty for services from an arrivals board, which stop at the board
+    /// station.
+    pub next_calls: Vec<CallIndex>,
+    /// Final destination as shown on the board.
+    pub destination: String,
+    /// Final destination CRS, if known.
+    pub destination_crs: Option<Crs>,
+}
+
+impl BoardContext {
+    /// The context of a service from a board.
+    pub fn of(svc: &ConvertedService) -> Self {
+        let board = svc.service.board_station_idx.0;
+        let next_calls = (board + 1..svc.service.calls.len())
+            .take(NEXT_CALLS)
+            .map(CallIndex)
+            .collect();
+        Self {
+            next_calls,
+            destination: svc.candidate.destination.clone(),
+            destination_crs: svc.candidate.destination_crs,
+        }
+    }
 }
 
 /// Filter and rank services based on identification criteria.
@@ -31,7 +78,7 @@ pub struct TrainMatch {
 /// # Returns
 ///
 /// Services that match the criteria, sorted by confidence (exact matches first),
-/// then by departure time.
+/// then by departure time, then busiest destination first.
 pub fn filter_and_rank_matches(
     services: &[Arc<ConvertedService>],
     terminus: Option<&Crs>,
@@ -59,30 +106,62 @@ pub fn filter_and_rank_matches(
             Some(TrainMatch {
                 service: Arc::clone(svc),
                 confidence,
+                context: BoardContext::of(svc),
+                score: MatchScore::default(),
             })
         })
         .collect();
 
-    // Sort: exact matches first, then by departure time
+    // Sort: exact matches first, then by departure time, then busier
+    // destinations (the likelier train) first
+    let usage = uk_station_usage();
+    let busyness = |m: &TrainMatch| {
+        m.service
+            .candidate
+            .destination_crs
+            .and_then(|crs| usage.entries_exits(&crs))
+            .unwrap_or(0)
+    };
     matches.sort_by(|a, b| {
-        a.confidence.cmp(&b.confidence).then_with(|| {
-            let a_dep = a
-                .service
-                .candidate
-                .expected_departure
-                .or(Some(a.service.candidate.scheduled_departure));
-            let b_dep = b
-                .service
-                .candidate
-                .expected_departure
-                .or(Some(b.service.candidate.scheduled_departure));
-            a_dep.cmp(&b_dep)
-        })
+        a.confidence
+            .cmp(&b.confidence)
+            .then_with(|| {
+                let a_dep = a
+                    .service
+                    .candidate
+                    .expected_departure
+                    .or(Some(a.service.candidate.scheduled_departure));
+                let b_dep = b
+                    .service
+                    .candidate
+                    .expected_departure
+                    .or(Some(b.service.candidate.scheduled_departure));
+                a_dep.cmp(&b_dep)
+            })
+            .then_with(|| busyness(b).cmp(&busyness(a)))
     });
 
     matches
 }
 
+/// Filter and rank services against everything the user observed.
+///
+/// As [`filter_and_rank_matches`], but each match is also scored (see
+/// [`score`](score::score)) and, within a confidence level, higher scores
+/// rank first. Equal scores keep departure-time order.
+pub fn score_and_rank_matches(
+    services: &[Arc<ConvertedService>],
+    request: &IdentifyTrainRequest,
+    weights: &ScoringWeights,
+) -> Vec<TrainMatch> {
+    let mut matches = filter_and_rank_matches(services, request.terminus.as_ref());
+    for m in &mut matches {
+        m.score = score::score(&m.service, request, weights);
+    }
+    matches.sort_by_key(|m| (m.confidence, Reverse(m.score.score)));
+    matches
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,6 +220,7 @@ mod tests {
             operator_code: AtocCode::parse("TO").ok(),
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         };
 
         let destination_name = stations
@@ -162,7 +242,12 @@ mod tests {
             is_cancelled: false,
         };
 
-        Arc::new(ConvertedService { service, candidate })
+        Arc::new(ConvertedService {
+            service,
+            candidate,
+            uid: None,
+            coaches: None,
+        })
     }
 
     #[test]
@@ -255,6 +340,35 @@ mod tests {
         assert!(matches.is_empty());
     }
 
+    #[test]
+    fn simultaneous_departures_rank_busier_destination_first() {
+        let services = vec![
+            mock_service(
+                "svc1",
+                "1P01",
+                &[("WDB", "Woodbridge"), ("IPS", "Ipswich")],
+                time(10, 0),
+            ),
+            mock_service(
+                "svc2",
+                "1P02",
+                &[("WDB", "Woodbridge"), ("LST", "London Liverpool Street")],
+                time(10, 0),
+            ),
+        ];
+
+        let matches = filter_and_rank_matches(&services, None);
+
+        assert_eq!(
+            matches[0].service.candidate.destination_crs,
+            Some(crs("LST"))
+        );
+        assert_eq!(
+            matches[1].service.candidate.destination_crs,
+            Some(crs("IPS"))
+        );
+    }
+
     #[test]
     fn sorted_by_departure_time() {
         let services = vec![
@@ -451,6 +565,96 @@ mod tests {
         assert_eq!(matched.service.candidate.destination, "Ipswich");
         assert_eq!(matched.service.candidate.scheduled_departure, time(10, 23));
     }
+
+    #[test]
+    fn score_outranks_departure_time() {
+        let services = vec![
+            mock_service(
+                "earlier",
+                "1P01",
+                &[("WDB", "Woodbridge"), ("IPS", "Ipswich")],
+                time(10, 0),
+            ),
+            mock_service(
+                "later",
+                "1P02",
+                &[("WDB", "Woodbridge"), ("IPS", "Ipswich")],
+                time(10, 15),
+            ),
+        ];
+        let request = IdentifyTrainRequest::next_station_only(crs("WDB"))
+            .with_headcode(Headcode::parse("1P02").unwrap());
+
+        let matches = score_and_rank_matches(&services, &request, &ScoringWeights::default());
+
+        assert_eq!(matches[0].service.service.service_ref.darwin_id, "later");
+        assert_eq!(matches[0].score.score, 100);
+        assert_eq!(matches[1].score.score, 0);
+    }
+
+    #[test]
+    fn equal_scores_keep_departure_order() {
+        let services = vec![
+            mock_service(
+                "later",
+                "1P02",
+                &[("WDB", "Woodbridge"), ("IPS", "Ipswich")],
+                time(10, 15),
+            ),
+            mock_service(
+                "earlier",
+                "1P01",
+                &[("WDB", "Woodbridge"), ("IPS", "Ipswich")],
+                time(10, 0),
+            ),
+        ];
+        let request = IdentifyTrainRequest::next_station_only(crs("WDB")).with_platform("1");
+
+        let matches = score_and_rank_matches(&services, &request, &ScoringWeights::default());
+
+        assert_eq!(matches[0].service.service.service_ref.darwin_id, "earlier");
+        assert_eq!(matches[0].score.score, matches[1].score.score);
+    }
+
+    #[test]
+    fn board_context_lists_next_three_calls() {
+        let services = vec![mock_service(
+            "ecml_express",
+            "1E01",
+            &[
+                ("PBO", "Peterborough"),
+                ("GRA", "Grantham"),
+                ("NEW", "Newark North Gate"),
+                ("DON", "Doncaster"),
+                ("YRK", "York"),
+            ],
+            time(10, 0),
+        )];
+
+        let matches = filter_and_rank_matches(&services, None);
+
+        let context = &matches[0].context;
+        assert_eq!(
+            context.next_calls,
+            vec![CallIndex(1), CallIndex(2), CallIndex(3)]
+        );
+        assert_eq!(context.destination, "York");
+        assert_eq!(context.destination_crs, Some(crs("YRK")));
+    }
+
+    #[test]
+    fn board_context_near_terminus() {
+        let services = vec![mock_service(
+            "local",
+            "2E05",
+            &[("PBO", "Peterborough"), ("GRA", "Grantham")],
+            time(10, 15),
+        )];
+
+        let matches = filter_and_rank_matches(&services, None);
+
+        assert_eq!(matches[0].context.next_calls, vec![CallIndex(1)]);
+    }
 }
 
 #[cfg(test)]
@@ -509,6 +713,7 @@ mod property_tests {
                     operator_code: None,
                     calls,
                     board_station_idx: CallIndex(0),
+                    formation: None,
                 };
 
                 let candidate = ServiceCandidate {
@@ -524,7 +729,12 @@ mod property_tests {
                     is_cancelled: false,
                 };
 
-                Arc::new(ConvertedService { service, candidate })
+                Arc::new(ConvertedService {
+                    service,
+                    candidate,
+                    uid: None,
+                    coaches: None,
+                })
             })
     }
 
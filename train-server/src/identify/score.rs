@@ -0,0 +1,342 @@
+//! Confidence scoring for train identification.
+//!
+//! The next station narrows a board down to a handful of trains; anything
+//! else the user can see (headcode, platform, the time the train is due,
+//! stations it calls at) tells those trains apart. Each observation the user
+//! gives is a signal, weighted by how reliably it identifies a train. A
+//! service's score is the weighted share of signals it matches, with a
+//! reason for each.
+//!
+//! Signals the service can't be checked against (e.g. no platform on the
+//! board yet) count neither for nor against it.
+
+use std::collections::HashSet;
+
+use crate::darwin::ConvertedService;
+use crate::domain::{Crs, IdentifyTrainRequest};
+
+/// Time difference at which the time signal stops counting, in minutes.
+const TIME_TOLERANCE_MINS: i64 = 15;
+
+/// Something the user observed that a service can match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// The train's headcode
+    Headcode,
+    /// Where the train terminates
+    Terminus,
+    /// When the train is due at the next station
+    Time,
+    /// The platform at the next station
+    Platform,
+    /// Stations the train calls at
+    CallingPattern,
+}
+
+/// How much each signal counts towards a score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoringWeights {
+    /// Weight of a headcode match (near-unique on a board)
+    pub headcode: f64,
+    /// Weight of a terminus match
+    pub terminus: f64,
+    /// Weight of the due time, scaled by how close it is
+    pub time: f64,
+    /// Weight of a platform match
+    pub platform: f64,
+    /// Weight of the calling pattern, scaled by the share of stations matched
+    pub calling_pattern: f64,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        Self {
+            headcode: 5.0,
+            terminus: 3.0,
+            time: 2.0,
+            platform: 1.0,
+            calling_pattern: 2.0,
+        }
+    }
+}
+
+impl ScoringWeights {
+    fn weight(&self, signal: Signal) -> f64 {
+        match signal {
+            Signal::Headcode => self.headcode,
+            Signal::Terminus => self.terminus,
+            Signal::Time => self.time,
+            Signal::Platform => self.platform,
+            Signal::CallingPattern => self.calling_pattern,
+        }
+    }
+}
+
+/// How one signal contributed to a score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreReason {
+    /// The signal
+    pub signal: Signal,
+    /// How well the service matches it, from 0 (not at all) to 1 (fully)
+    pub fit: f64,
+    /// Human-readable explanation
+    pub message: String,
+}
+
+/// A service's score against the user's observations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchScore {
+    /// Percentage of the weighted signals matched; 0 if there were none to
+    /// check
+    pub score: u8,
+    /// Each signal checked, in [`Signal`] order
+    pub reasons: Vec<ScoreReason>,
+}
+
+/// Score a service from the next station's board against a request.
+pub fn score(
+    svc: &ConvertedService,
+    request: &IdentifyTrainRequest,
+    weights: &ScoringWeights,
+) -> MatchScore {
+    let reasons: Vec<ScoreReason> = [
+        headcode(svc, request),
+        terminus(svc, request),
+        time(svc, request),
+        platform(svc, request),
+        calling_pattern(svc, request),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let total: f64 = reasons.iter().map(|r| weights.weight(r.signal)).sum();
+    let matched: f64 = reasons
+        .iter()
+        .map(|r| weights.weight(r.signal) * r.fit)
+        .sum();
+    let score = if total > 0.0 {
+        (matched / total * 100.0).round() as u8
+    } else {
+        0
+    };
+
+    MatchScore { score, reasons }
+}
+
+fn reason(signal: Signal, fit: f64, message: String) -> Option<ScoreReason> {
+    Some(ScoreReason {
+        signal,
+        fit,
+        message,
+    })
+}
+
+fn headcode(svc: &ConvertedService, request: &IdentifyTrainRequest) -> Option<ScoreReason> {
+    let wanted = request.headcode?;
+    let actual = svc.service.headcode?;
+    if actual == wanted {
+        reason(Signal::Headcode, 1.0, format!("Headcode {actual} matches"))
+    } else {
+        reason(
+            Signal::Headcode,
+            0.0,
+            format!("Headcode is {actual}, not {wanted}"),
+        )
+    }
+}
+
+fn terminus(svc: &ConvertedService, request: &IdentifyTrainRequest) -> Option<ScoreReason> {
+    let wanted = request.terminus?;
+    let actual = svc.candidate.destination_crs?;
+    if actual == wanted {
+        reason(
+            Signal::Terminus,
+            1.0,
+            format!("Terminates at {}", svc.candidate.destination),
+        )
+    } else {
+        reason(
+            Signal::Terminus,
+            0.0,
+            format!("Terminates at {}, not {wanted}", svc.candidate.destination),
+        )
+    }
+}
+
+fn time(svc: &ConvertedService, request: &IdentifyTrainRequest) -> Option<ScoreReason> {
+    let wanted = request.time?;
+    let call = svc.service.calls.get(svc.service.board_station_idx.0)?;
+    let due = call.expected_arrival().or(call.expected_departure())?;
+    let off_by = due.signed_duration_since(wanted).num_minutes().abs();
+    let fit = (1.0 - off_by as f64 / TIME_TOLERANCE_MINS as f64).max(0.0);
+    let message = if off_by == 0 {
+        format!("Due at {due}")
+    } else {
+        format!("Due at {due}, {off_by} min from {wanted}")
+    };
+    reason(Signal::Time, fit, message)
+}
+
+fn platform(svc: &ConvertedService, request: &IdentifyTrainRequest) -> Option<ScoreReason> {
+    let wanted = request.platform.as_deref()?;
+    let actual = svc.candidate.platform.as_deref()?;
+    if actual.eq_ignore_ascii_case(wanted) {
+        reason(Signal::Platform, 1.0, format!("Platform {actual} matches"))
+    } else {
+        reason(
+            Signal::Platform,
+            0.0,
+            format!("Platform is {actual}, not {wanted}"),
+        )
+    }
+}
+
+fn calling_pattern(svc: &ConvertedService, request: &IdentifyTrainRequest) -> Option<ScoreReason> {
+    if request.calling_at.is_empty() {
+        return None;
+    }
+    let stations: HashSet<&Crs> = svc.service.calls.iter().map(|c| &c.station).collect();
+    let wanted: HashSet<&Crs> = request.calling_at.iter().collect();
+    let matched = wanted.iter().filter(|s| stations.contains(*s)).count();
+    reason(
+        Signal::CallingPattern,
+        matched as f64 / wanted.len() as f64,
+        format!("Calls at {matched} of {} stations given", wanted.len()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        Call, CallIndex, Headcode, RailTime, Service, ServiceCandidate, ServiceRef,
+    };
+    use chrono::NaiveDate;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2026, 1, 3).unwrap()).unwrap()
+    }
+
+    /// A 1A23 due at Reading at 10:25 on platform 4, then Swindon and Bristol.
+    fn service() -> ConvertedService {
+        let stations = [("RDG", "10:25"), ("SWI", "10:50"), ("BRI", "11:20")];
+        let calls = stations
+            .iter()
+            .map(|(station, t)| {
+                let mut call = Call::new(crs(station), station.to_string());
+                call.booked_arrival = Some(time(t));
+                call
+            })
+            .collect();
+        let service = Service {
+            service_ref: ServiceRef::new("S".into(), crs("RDG")),
+            headcode: Headcode::parse("1A23"),
+            operator: "GWR".into(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        };
+        let candidate = ServiceCandidate {
+            service_ref: service.service_ref.clone(),
+            headcode: service.headcode,
+            scheduled_departure: time("10:27"),
+            expected_departure: None,
+            destination: "Bristol Temple Meads".into(),
+            destination_crs: Some(crs("BRI")),
+            operator: "GWR".into(),
+            operator_code: None,
+            platform: Some("4".into()),
+            is_cancelled: false,
+        };
+        ConvertedService {
+            candidate,
+            service,
+            uid: None,
+            coaches: None,
+        }
+    }
+
+    fn request() -> IdentifyTrainRequest {
+        IdentifyTrainRequest::next_station_only(crs("RDG"))
+    }
+
+    #[test]
+    fn no_observations_scores_zero() {
+        let score = score(&service(), &request(), &ScoringWeights::default());
+        assert_eq!(score.score, 0);
+        assert!(score.reasons.is_empty());
+    }
+
+    #[test]
+    fn all_signals_match() {
+        let request = request()
+            .with_headcode(Headcode::parse("1A23").unwrap())
+            .with_time(time("10:25"))
+            .with_platform("4")
+            .with_calling_at(vec![crs("SWI"), crs("BRI")]);
+
+        let score = score(&service(), &request, &ScoringWeights::default());
+
+        assert_eq!(score.score, 100);
+        let signals: Vec<Signal> = score.reasons.iter().map(|r| r.signal).collect();
+        assert_eq!(
+            signals,
+            vec![
+                Signal::Headcode,
+                Signal::Time,
+                Signal::Platform,
+                Signal::CallingPattern
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_headcode_outweighs_platform() {
+        let request = request()
+            .with_headcode(Headcode::parse("2B45").unwrap())
+            .with_platform("4");
+
+        let score = score(&service(), &request, &ScoringWeights::default());
+
+        // 1 of 6 weighted points
+        assert_eq!(score.score, 17);
+        assert_eq!(score.reasons[0].message, "Headcode is 1A23, not 2B45");
+    }
+
+    #[test]
+    fn time_fit_falls_off_with_distance() {
+        let weights = ScoringWeights::default();
+        let near = score(&service(), &request().with_time(time("10:22")), &weights);
+        let far = score(&service(), &request().with_time(time("10:50")), &weights);
+
+        assert_eq!(near.score, 80);
+        assert_eq!(near.reasons[0].message, "Due at 10:25, 3 min from 10:22");
+        assert_eq!(far.score, 0);
+    }
+
+    #[test]
+    fn partial_calling_pattern() {
+        let request = request().with_calling_at(vec![crs("SWI"), crs("OXF")]);
+        let score = score(&service(), &request, &ScoringWeights::default());
+        assert_eq!(score.score, 50);
+        assert_eq!(score.reasons[0].message, "Calls at 1 of 2 stations given");
+    }
+
+    #[test]
+    fn unknown_platform_is_not_counted() {
+        let mut svc = service();
+        svc.candidate.platform = None;
+        let request = request().with_platform("4").with_time(time("10:25"));
+
+        let score = score(&svc, &request, &ScoringWeights::default());
+
+        assert_eq!(score.score, 100);
+        assert_eq!(score.reasons.len(), 1);
+    }
+}
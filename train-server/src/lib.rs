@@ -2,12 +2,39 @@
 //!
 //! A web application that answers: "I'm on this specific train,
 //! where can I change to reach my destination?"
+//!
+//! The domain types are always built. The rest is behind cargo features, so
+//! other projects can depend on just the planner with their own providers:
+//! `planner-core` adds the planner, `darwin` the Darwin and Station API
+//! clients and caching, and `web` (the default) the HTTP server.
+//!
+//! [`testing`] has builders for the services and networks planner tests
+//! run against.
 
+pub mod alighting;
+#[cfg(feature = "darwin")]
 pub mod cache;
+pub mod clock;
+#[cfg(feature = "darwin")]
 pub mod darwin;
+#[cfg(feature = "demo")]
+pub mod demo;
 pub mod domain;
+pub mod error;
+pub mod fares;
+#[cfg(feature = "darwin")]
 pub mod identify;
+#[cfg(feature = "web")]
+pub mod notify;
+#[cfg(feature = "planner-core")]
 pub mod planner;
+#[cfg(feature = "web")]
+pub mod refresh;
+#[cfg(feature = "darwin")]
+pub mod resolver;
 pub mod stations;
+pub mod testing;
+pub mod tracking;
 pub mod walkable;
+#[cfg(feature = "web")]
 pub mod web;
@@ -1,9 +1,13 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::time::Duration;
 
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
-use train_server::cache::{CacheConfig, CachedDarwinClient};
+use train_server::alighting::london_metadata;
+#[cfg(feature = "redis")]
+use train_server::cache::RedisCache;
+use train_server::cache::{CacheConfig, CachedDarwinClient, WarmupConfig, spawn_warmup};
 
 /// Read a secret from environment, preferring `{name}_FILE` over `{name}`.
 ///
@@ -19,17 +23,192 @@ fn read_secret(name: &str) -> Option<String> {
     }
     std::env::var(name).ok()
 }
-use train_server::darwin::{DarwinClient, DarwinClientImpl, DarwinConfig, MockDarwinClient};
-use train_server::planner::SearchConfig;
+use train_server::darwin::{
+    ConversionMode, DarwinClient, DarwinClientImpl, DarwinConfig, FailureModes, HttpVersion,
+    MockDarwinClient, ReasonTranslations, ReferenceCache, ReferenceClient, ReferenceClientConfig,
+    ReferenceData,
+};
+use train_server::domain::Crs;
+use train_server::notify::{Notifier, NotifierConfig};
+use train_server::planner::{PerformanceStore, SearchConfig};
 use train_server::stations::{
-    StationCache, StationCacheConfig, StationClient, StationClientConfig, StationNames,
+    PlatformTopology, StationCache, StationCacheConfig, StationClient, StationClientConfig,
+    StationNames, uk_station_groups,
+};
+use train_server::walkable::{WalkableStore, london_connections};
+use train_server::web::{
+    AnalyticsStore, ApiKeys, AppState, ArchiveStore, DarwinProviders, Experiments, SearchLimits,
+    SessionKey, archive_arrived_watches, create_router, remove_implausible_walks,
 };
-use train_server::walkable::london_connections;
-use train_server::web::{AppState, create_router};
+
+/// Connect to the shared Redis cache, if `REDIS_URL` is set.
+///
+/// Falls back to local caching only if Redis is unreachable.
+#[cfg(feature = "redis")]
+async fn connect_shared_cache() -> Option<RedisCache> {
+    let url = std::env::var("REDIS_URL").ok()?;
+    match RedisCache::connect(&url).await {
+        Ok(cache) => {
+            println!("Sharing cache via Redis");
+            Some(cache)
+        }
+        Err(e) => {
+            eprintln!("{}; continuing with local cache only", e);
+            None
+        }
+    }
+}
+
+/// Station names for the demo network.
+#[cfg(feature = "demo")]
+fn demo_station_names(client: StationClient) -> StationNames {
+    train_server::demo::station_names(client)
+}
+
+#[cfg(not(feature = "demo"))]
+fn demo_station_names(client: StationClient) -> StationNames {
+    StationNames::empty(client)
+}
+
+/// Parse an environment variable, ignoring it if unset or invalid.
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+/// Read settings from a file of `NAME=value` lines, such as the one named by
+/// `SEARCH_CONFIG_PATH`. Blank lines and `#` comments are skipped.
+fn read_settings(path: &str) -> HashMap<String, String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to read settings from {}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Failures for the mock and demo Darwin clients to inject, from the
+/// `MOCK_*` environment variables; none unless set.
+fn mock_failures() -> FailureModes {
+    let mut failures = FailureModes::default();
+    if let Some(rate) = env_parse("MOCK_ERROR_RATE") {
+        failures.error_rate = rate;
+    }
+    if let Some(ms) = env_parse("MOCK_LATENCY_MIN_MS") {
+        failures.min_latency = Duration::from_millis(ms);
+    }
+    if let Some(ms) = env_parse("MOCK_LATENCY_MAX_MS") {
+        failures.max_latency = Duration::from_millis(ms);
+    }
+    if let Ok(stations) = std::env::var("MOCK_TIMEOUT_STATIONS") {
+        failures.timeout_stations = stations
+            .split(',')
+            .filter_map(|s| Crs::parse(s.trim()).ok())
+            .collect();
+    }
+    if let Some(ms) = env_parse("MOCK_TIMEOUT_MS") {
+        failures.timeout = Duration::from_millis(ms);
+    }
+    if let Some(n) = env_parse("MOCK_TRUNCATE_BOARDS") {
+        failures.truncate_boards = Some(n);
+    }
+    if let Some(seed) = env_parse("MOCK_FAILURE_SEED") {
+        failures.seed = seed;
+    }
+    failures
+}
+
+/// Parse a setting from the settings file, or else the environment.
+fn setting<T: std::str::FromStr>(file: &HashMap<String, String>, name: &str) -> Option<T> {
+    match file.get(name) {
+        Some(value) => value.parse().ok(),
+        None => env_parse(name),
+    }
+}
+
+/// Create the search config, optionally pacing departure fetches to stay
+/// within Darwin's burst limits.
+///
+/// Read at startup and again on each reload, so settings in the
+/// `SEARCH_CONFIG_PATH` file can be changed without a restart.
+fn search_config() -> SearchConfig {
+    let file = std::env::var("SEARCH_CONFIG_PATH")
+        .map(|path| read_settings(&path))
+        .unwrap_or_default();
+    let mut config = SearchConfig::default();
+    if let Some(n) = setting(&file, "SEARCH_FETCH_CONCURRENCY") {
+        config.fetch_concurrency = n;
+    }
+    if let Some(n) = setting(&file, "SEARCH_BOARD_CONCURRENCY") {
+        config.board_concurrency = n;
+    }
+    if let Some(ms) = setting(&file, "SEARCH_BATCH_DELAY_MS") {
+        config.batch_delay_ms = ms;
+    }
+    if let Some(seed) = setting(&file, "SEARCH_JITTER_SEED") {
+        config.jitter_seed = seed;
+    }
+    if let Some(secs) = setting(&file, "SEARCH_ARRIVALS_REUSE_SECS") {
+        config.arrivals_reuse_secs = secs;
+    }
+    if let Some(mins) = setting(&file, "SEARCH_FOOTBRIDGE_CONNECTION_MINS") {
+        config.footbridge_connection_mins = mins;
+    }
+    if let Some(n) = setting(&file, "SEARCH_MAX_ARRIVALS_PAGES") {
+        config.max_arrivals_pages = n;
+    }
+    if let Some(mins) = setting(&file, "SEARCH_PRICE_WEIGHT_MINS") {
+        config.price_weight_mins = mins;
+    }
+    if let Some(n) = setting(&file, "SEARCH_BFS_TARGET_JOURNEYS") {
+        config.bfs_target_journeys = n;
+    }
+    if let Some(mins) = setting(&file, "SEARCH_BFS_ARRIVAL_MARGIN_MINS") {
+        config.bfs_arrival_margin_mins = mins;
+    }
+    if let Some(mins) = setting(&file, "SEARCH_TRAVEL_TIME_MARGIN_MINS") {
+        config.travel_time_margin_mins = mins;
+    }
+    if let Some(n) = setting(&file, "SEARCH_MAX_BOARD_SERVICES") {
+        config.max_board_services = n;
+    }
+    if let Some(n) = setting(&file, "SEARCH_MAX_HELD_CALLS") {
+        config.max_held_calls = n;
+    }
+    if let Some(avoid) = setting(&file, "SEARCH_AVOID_LIFT_OUTAGES") {
+        config.avoid_lift_outages = avoid;
+    }
+    if let Some(combine) = setting(&file, "SEARCH_COMBINE_GROUP_ENDINGS") {
+        config.combine_group_endings = combine;
+    }
+    if let Some(dominance) = setting(&file, "SEARCH_DOMINANCE") {
+        config.dominance = dominance;
+    }
+    config
+}
 
 /// How often to refresh station names (24 hours).
 const STATION_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
 
+/// How often to refresh Darwin reference data (reason codes, operators).
+const REFERENCE_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often to save the punctuality history, when it is kept on disk.
+const PERFORMANCE_SAVE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to save the usage analytics, when they are kept on disk.
+const ANALYTICS_SAVE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to archive watched journeys that have arrived, and save the
+/// archive when it is kept on disk.
+const ARCHIVE_SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 #[tokio::main]
 async fn main() {
     // Set up tracing subscriber
@@ -48,11 +227,34 @@ async fn main() {
         .and_then(|v| v.parse::<bool>().ok())
         .unwrap_or(false);
 
+    // The demo feature can boot against a bundled fixture network instead
+    #[cfg(feature = "demo")]
+    let demo = std::env::var("DEMO")
+        .ok()
+        .and_then(|v| v.parse::<bool>().ok())
+        .unwrap_or(false);
+    #[cfg(not(feature = "demo"))]
+    let demo = false;
+
     // Create Darwin client (real or mock)
-    let darwin_client = if use_mock {
+    #[cfg(feature = "demo")]
+    let demo_client = demo.then(|| {
+        println!("Using DEMO network (bundled fixture timetable, no Darwin access)");
+        DarwinClientImpl::Mock(train_server::demo::client().with_failures(mock_failures()))
+    });
+    #[cfg(not(feature = "demo"))]
+    let demo_client: Option<DarwinClientImpl> = None;
+
+    // The real Darwin client's HTTP client, shared with the Station API client
+    let mut shared_http: Option<reqwest::Client> = None;
+
+    let darwin_client = if let Some(client) = demo_client {
+        client
+    } else if use_mock {
         println!("Using MOCK Darwin client (loading from data/mock_boards/)");
-        let mock =
-            MockDarwinClient::new("data/mock_boards").expect("Failed to load mock Darwin data");
+        let mock = MockDarwinClient::new("data/mock_boards")
+            .expect("Failed to load mock Darwin data")
+            .with_failures(mock_failures());
         println!(
             "Available mock stations: {:?}",
             mock.available_stations()
@@ -84,29 +286,100 @@ async fn main() {
             );
         }
 
+        // Check for optional staff API key (LDBSVWS, richer data than the public API)
+        if let Some(staff_key) = read_secret("DARWIN_STAFF_API_KEY") {
+            println!("Staff API configured");
+            darwin_config = darwin_config.with_staff_api_key(staff_key);
+        }
+
         // Check for optional capture directory (for debugging/testing)
         if let Ok(capture_dir) = std::env::var("DARWIN_CAPTURE_DIR") {
             println!("Darwin capture enabled: {}", capture_dir);
             darwin_config = darwin_config.with_capture_dir(&capture_dir);
         }
 
-        let client = DarwinClient::new(darwin_config).expect("Failed to create Darwin client");
+        // Fail boards with services that can't be converted, rather than
+        // skipping those services
+        if env_parse::<bool>("DARWIN_STRICT_CONVERSION") == Some(true) {
+            darwin_config = darwin_config.with_conversion_mode(ConversionMode::Strict);
+        }
+
+        // Optional HTTP tuning: searches fan out many concurrent board fetches
+        if let Some(secs) = env_parse("DARWIN_CONNECT_TIMEOUT_SECS") {
+            darwin_config = darwin_config.with_connect_timeout(secs);
+        }
+        if let Some(secs) = env_parse("DARWIN_READ_TIMEOUT_SECS") {
+            darwin_config = darwin_config.with_read_timeout(secs);
+        }
+        if let Some(n) = env_parse("DARWIN_POOL_SIZE") {
+            darwin_config = darwin_config.with_pool_size(n);
+        }
+        if let Ok(version) = std::env::var("DARWIN_HTTP_VERSION") {
+            match HttpVersion::parse(&version) {
+                Some(version) => darwin_config = darwin_config.with_http_version(version),
+                None => eprintln!("Ignoring DARWIN_HTTP_VERSION={version}: expected auto, 1 or 2"),
+            }
+        }
+
+        let http = darwin_config
+            .http_client()
+            .expect("Failed to create HTTP client");
+        shared_http = Some(http.clone());
+        let client = DarwinClient::with_http_client(darwin_config, http)
+            .expect("Failed to create Darwin client");
         DarwinClientImpl::Real(client)
     };
 
     // Create cached client
     let cache_config = CacheConfig::default();
-    let cached_darwin = CachedDarwinClient::new(darwin_client, &cache_config);
+    let mut cached_darwin = CachedDarwinClient::new(darwin_client, &cache_config);
 
-    // Create walkable connections (using London termini defaults)
-    let walkable = london_connections();
+    // Darwin call logging: one call in DARWIN_LOG_SAMPLE is logged (default
+    // every call, 0 for none); all are counted on /metrics
+    if let Some(every) = std::env::var("DARWIN_LOG_SAMPLE")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        cached_darwin = cached_darwin.with_call_log_sampling(every);
+    }
 
-    // Create search config
-    let search_config = SearchConfig::default();
+    // Optional Redis cache shared between instances
+    #[cfg(feature = "redis")]
+    let shared_cache = connect_shared_cache().await;
+    #[cfg(feature = "redis")]
+    let cached_darwin = match &shared_cache {
+        Some(cache) => cached_darwin.with_shared_cache(cache.clone()),
+        None => cached_darwin,
+    };
+
+    // Create walkable connections: admin edits saved to WALKABLE_PATH take
+    // precedence over the built-in London termini defaults
+    let walkable_path =
+        std::env::var("WALKABLE_PATH").unwrap_or_else(|_| "walkable.json".to_string());
+    let walkable_store = WalkableStore::new(&walkable_path);
+    let walkable = match walkable_store.load() {
+        Ok(Some(connections)) => {
+            println!(
+                "Loaded {} walkable connections from {}",
+                connections.len(),
+                walkable_path
+            );
+            connections
+        }
+        Ok(None) => london_connections(),
+        Err(e) => {
+            eprintln!("Failed to load walkable connections, using defaults: {}", e);
+            london_connections()
+        }
+    };
 
     // Fetch station names (requires separate Rail Data Marketplace subscription)
     // Uses disk cache to avoid hitting the expensive API on every restart
-    let station_names = if use_mock {
+    let station_names = if demo {
+        let station_client = StationClient::new(StationClientConfig::new(""))
+            .expect("Failed to create Station client");
+        demo_station_names(station_client)
+    } else if use_mock {
         println!("Using mock mode: skipping station names API fetch");
         let station_config = StationClientConfig::new("");
         let station_client =
@@ -114,8 +387,11 @@ async fn main() {
         StationNames::empty(station_client)
     } else if let Some(api_key) = read_secret("STATION_API_KEY") {
         let station_config = StationClientConfig::new(&api_key);
-        let station_client =
-            StationClient::new(station_config).expect("Failed to create Station client");
+        let station_client = match shared_http.clone() {
+            Some(http) => StationClient::with_http_client(station_config, http),
+            None => StationClient::new(station_config),
+        }
+        .expect("Failed to create Station client");
 
         // Configure disk cache (default: stations_cache.json, 24h TTL)
         let cache_path = std::env::var("STATION_CACHE_PATH")
@@ -146,6 +422,13 @@ async fn main() {
         StationNames::empty(station_client)
     };
 
+    // Drop walks too quick for the distance between their stations, so a
+    // bad edit to the file can't let the planner make impossible changes
+    let mut walkable = walkable;
+    for rejected in remove_implausible_walks(&mut walkable, &station_names).await {
+        eprintln!("Ignoring walkable connection: {}", rejected);
+    }
+
     // Spawn background task to refresh station names daily
     let station_names_refresh = station_names.clone();
     tokio::spawn(async move {
@@ -160,8 +443,331 @@ async fn main() {
         }
     });
 
+    // Darwin reason codes and operator names, for explaining disruption.
+    // Reference data is a staff API product, so its key defaults to the
+    // staff key; cached on disk and refreshed daily like station names
+    let reference_key =
+        read_secret("DARWIN_REFERENCE_API_KEY").or_else(|| read_secret("DARWIN_STAFF_API_KEY"));
+    let reference = match (shared_http, reference_key) {
+        (Some(http), Some(api_key)) => {
+            let client =
+                ReferenceClient::with_http_client(ReferenceClientConfig::new(api_key), http)
+                    .expect("Failed to create reference data client");
+            let cache_path = std::env::var("REFERENCE_CACHE_PATH")
+                .unwrap_or_else(|_| "reference_cache.json".to_string());
+            match ReferenceData::fetch_with_cache(client, ReferenceCache::new(&cache_path)).await {
+                Ok((reference, from_cache)) => {
+                    let count = reference.tables().await.reason_count();
+                    let source = if from_cache { "cache" } else { "API" };
+                    println!("Loaded {} reason codes from {}", count, source);
+                    reference
+                }
+                Err(e) => {
+                    eprintln!("Failed to fetch reference data, reasons will go unexplained: {e}");
+                    ReferenceData::empty()
+                }
+            }
+        }
+        _ => ReferenceData::empty(),
+    };
+
+    // Reasons in languages other than Darwin's English, for clients that
+    // ask for them in Accept-Language. Optional: without it reasons are
+    // given in English
+    let reference = match std::env::var("REASON_TRANSLATIONS_PATH") {
+        Ok(path) => match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| ReasonTranslations::from_json(&json).map_err(|e| e.to_string()))
+        {
+            Ok(translations) => {
+                println!(
+                    "Loaded reason translations for {} languages from {}",
+                    translations.len(),
+                    path
+                );
+                reference.with_translations(translations)
+            }
+            Err(e) => {
+                eprintln!("Failed to load reason translations from {}: {}", path, e);
+                reference
+            }
+        },
+        Err(_) => reference,
+    };
+    let reference_refresh = reference.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFERENCE_REFRESH_INTERVAL);
+        interval.tick().await; // First tick is immediate, skip it
+        loop {
+            interval.tick().await;
+            if let Err(e) = reference_refresh.refresh().await {
+                eprintln!("Failed to refresh reference data: {}", e);
+            }
+        }
+    });
+
     // Build app state
-    let state = AppState::new(cached_darwin, walkable, search_config, station_names);
+    let mut state = AppState::new(cached_darwin, walkable, search_config(), station_names)
+        .with_config_source(search_config)
+        .with_station_metadata(london_metadata())
+        .with_station_groups(uk_station_groups())
+        .with_reference_data(reference);
+
+    #[cfg(feature = "redis")]
+    if let Some(cache) = shared_cache {
+        state = state.with_shared_cache(cache);
+    }
+
+    // Boards reaching past midnight are fetched as today's and tomorrow's
+    // when searching this close to it
+    if let Some(mins) = env_parse::<u16>("DARWIN_MIDNIGHT_SPLIT_MINS") {
+        let providers = DarwinProviders::new(state.darwin.clone()).with_midnight_split(mins);
+        state = state.with_providers(std::sync::Arc::new(providers));
+    }
+
+    // The demo timetable is for one day, so the clock starts on it
+    #[cfg(feature = "demo")]
+    if demo {
+        let start = std::env::var("DEMO_START")
+            .ok()
+            .map(|v| {
+                chrono::NaiveTime::parse_from_str(&v, "%H:%M")
+                    .expect("DEMO_START must be a time like 08:30")
+            })
+            .unwrap_or_else(|| chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        println!(
+            "Demo clock starts at {} on {}",
+            start.format("%H:%M"),
+            train_server::demo::fixture_day()
+        );
+        state = state.with_clock(train_server::demo::clock(start));
+    }
+
+    // Platform layouts, for footbridge margins and same-platform hints.
+    // Optional: without them changes are judged as before
+    if let Ok(path) = std::env::var("PLATFORM_TOPOLOGY_PATH") {
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| PlatformTopology::from_json(&json).map_err(|e| e.to_string()))
+        {
+            Ok(topology) => {
+                println!(
+                    "Loaded platform layouts for {} stations from {}",
+                    topology.len(),
+                    path
+                );
+                state = state.with_platform_topology(topology);
+            }
+            Err(e) => eprintln!("Failed to load platform layouts from {}: {}", path, e),
+        }
+    }
+
+    // Punctuality history, learned from the boards searches see. It needs
+    // days of observations to be useful, so PERFORMANCE_PATH keeps it across
+    // restarts; otherwise it starts afresh each time
+    if let Ok(path) = std::env::var("PERFORMANCE_PATH") {
+        let store = PerformanceStore::new(&path);
+        let history = match store.load() {
+            Ok(history) => {
+                println!(
+                    "Loaded punctuality history for {} calls from {}",
+                    history.len(),
+                    path
+                );
+                history
+            }
+            Err(e) => {
+                eprintln!("Failed to load punctuality history, starting afresh: {}", e);
+                Default::default()
+            }
+        };
+        state = state.with_performance(std::sync::Arc::new(history));
+        let history = state.performance.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PERFORMANCE_SAVE_INTERVAL);
+            interval.tick().await; // First tick is immediate, skip it
+            loop {
+                interval.tick().await;
+                if let Err(e) = store.save(&history) {
+                    eprintln!("Failed to save punctuality history: {}", e);
+                }
+            }
+        });
+    }
+
+    // Anonymous usage analytics, read through the admin API. On unless
+    // ANALYTICS_ENABLED=false; ANALYTICS_PATH keeps them across restarts
+    if env_parse::<bool>("ANALYTICS_ENABLED") == Some(false) {
+        println!("Usage analytics disabled");
+        state = state.with_analytics(None);
+    } else if let Ok(path) = std::env::var("ANALYTICS_PATH") {
+        let store = AnalyticsStore::new(&path);
+        let analytics = match store.load() {
+            Ok(analytics) => {
+                println!(
+                    "Loaded usage analytics for {} days from {}",
+                    analytics.len(),
+                    path
+                );
+                analytics
+            }
+            Err(e) => {
+                eprintln!("Failed to load usage analytics, starting afresh: {}", e);
+                Default::default()
+            }
+        };
+        let analytics = std::sync::Arc::new(analytics);
+        state = state.with_analytics(Some(analytics.clone()));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ANALYTICS_SAVE_INTERVAL);
+            interval.tick().await; // First tick is immediate, skip it
+            loop {
+                interval.tick().await;
+                if let Err(e) = store.save(&analytics) {
+                    eprintln!("Failed to save usage analytics: {}", e);
+                }
+            }
+        });
+    }
+
+    // Finished journeys from watches, archived as they arrive; ARCHIVE_PATH
+    // keeps them across restarts
+    let archive_store = std::env::var("ARCHIVE_PATH").ok().map(ArchiveStore::new);
+    if let Some(store) = &archive_store {
+        match store.load().await {
+            Ok(archive) => {
+                println!("Loaded journey archive from {}", store.path().display());
+                state = state.with_archive(archive);
+            }
+            Err(e) => eprintln!("Failed to load journey archive, starting afresh: {}", e),
+        }
+    }
+
+    // Printed itineraries link back to the server at its public address
+    if let Ok(url) = std::env::var("PUBLIC_BASE_URL") {
+        if url.starts_with("https://") || url.starts_with("http://") {
+            state = state.with_public_url(&url);
+        } else {
+            eprintln!("Ignoring PUBLIC_BASE_URL {url:?}: not an http(s) URL");
+        }
+    }
+
+    // Session signing key (random if unset, so sessions don't survive restarts)
+    if let Some(secret) = read_secret("SESSION_SECRET") {
+        state = state.with_session_key(SessionKey::from_secret(&secret));
+    } else {
+        println!("SESSION_SECRET not set, sessions will reset on restart");
+    }
+
+    // Admin API for runtime edits (disabled unless a token is configured)
+    state = state.with_walkable_store(walkable_store);
+    if let Some(token) = read_secret("ADMIN_TOKEN") {
+        println!("Admin API enabled");
+        state = state.with_admin_token(token);
+    }
+
+    // API keys issued to clients, which alone may give alert channels, get
+    // their own search queue slots and have experiment overrides honoured
+    if let Some(keys) = read_secret("API_KEYS") {
+        let keys = ApiKeys::parse(&keys);
+        println!("Accepting {} API keys", keys.len());
+        state = state.with_api_keys(keys);
+    }
+
+    // Urgent alerts (platform changes) to watchers who gave channels. Each
+    // kind of channel is off unless configured
+    let mut notifier_config = NotifierConfig::default();
+    if let Some(token) = read_secret("TELEGRAM_BOT_TOKEN") {
+        notifier_config = notifier_config.with_telegram_bot_token(token);
+    }
+    if let Ok(url) = std::env::var("SMS_GATEWAY_URL") {
+        notifier_config = notifier_config.with_sms_gateway(url, read_secret("SMS_GATEWAY_API_KEY"));
+    }
+    if env_parse::<bool>("WEBHOOK_ALERTS") == Some(true) {
+        let hosts = std::env::var("WEBHOOK_ALLOWED_HOSTS").unwrap_or_default();
+        let hosts = hosts
+            .split(',')
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .map(String::from)
+            .collect();
+        notifier_config = notifier_config.with_webhooks(hosts);
+    }
+    if notifier_config.is_enabled() {
+        match Notifier::new(notifier_config) {
+            Ok(notifier) => state = state.with_notifier(notifier),
+            Err(e) => eprintln!("Failed to create notifier, alerts won't be sent: {}", e),
+        }
+    }
+
+    // Bound the searches running at once, overall and per API key
+    let mut limits = SearchLimits::default();
+    if let Some(n) = env_parse("SEARCH_MAX_CONCURRENT") {
+        limits.global = n;
+    }
+    if let Some(n) = env_parse("SEARCH_MAX_CONCURRENT_PER_KEY") {
+        limits.per_key = n;
+    }
+    if let Some(ms) = env_parse("SEARCH_QUEUE_WAIT_MS") {
+        limits.queue_wait = Duration::from_millis(ms);
+    }
+    state = state.with_search_limits(limits);
+
+    // Experiments on planner behaviours (none unless configured)
+    let experiments = Experiments::new()
+        .parse_rollouts(&std::env::var("EXPERIMENTS").unwrap_or_default())
+        .expect("EXPERIMENTS must be comma-separated name=percent entries")
+        .parse_overrides(&read_secret("EXPERIMENT_OVERRIDES").unwrap_or_default())
+        .expect("EXPERIMENT_OVERRIDES must be comma-separated key:name=on|off entries");
+    if !experiments.is_empty() {
+        println!("Experiments enabled");
+        state = state.with_experiments(experiments);
+    }
+
+    // Keep popular destinations' arrivals boards cached (needs an arrivals
+    // or staff key against the real API)
+    let serves_arrivals = match state.darwin.client() {
+        DarwinClientImpl::Real(client) => client.serves_arrivals(),
+        DarwinClientImpl::Mock(_) => true,
+    };
+    let mut warmup = WarmupConfig::default();
+    if let Ok(stations) = std::env::var("WARMUP_STATIONS") {
+        warmup = warmup
+            .parse_stations(&stations)
+            .expect("WARMUP_STATIONS must be comma-separated CRS codes");
+    }
+    if let Some(secs) = std::env::var("WARMUP_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+    {
+        warmup = warmup.with_interval(Duration::from_secs(secs));
+    }
+    if serves_arrivals && !warmup.is_empty() {
+        println!(
+            "Warming arrivals boards for {} stations every {}s",
+            warmup.stations.len(),
+            warmup.interval.as_secs()
+        );
+        spawn_warmup(state.darwin.clone(), state.clock, warmup);
+    }
+
+    // Archive watched journeys once they arrive, whether or not anyone is
+    // listing their watches
+    let sweeping = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ARCHIVE_SWEEP_INTERVAL);
+        interval.tick().await; // First tick is immediate, skip it
+        loop {
+            interval.tick().await;
+            archive_arrived_watches(&sweeping).await;
+            if let Some(store) = &archive_store
+                && let Err(e) = store.save(&sweeping.archive).await
+            {
+                eprintln!("Failed to save journey archive: {}", e);
+            }
+        }
+    });
 
     // Get static directory path (defaults to development path)
     let static_dir =
@@ -183,10 +789,26 @@ async fn main() {
     println!();
     println!("API Endpoints:");
     println!("  GET  /health          - Health check");
+    println!("  GET  /metrics         - Operational metrics");
     println!("  GET  /about           - About page");
-    println!("  GET  /search/service  - Search for services");
-    println!("  POST /journey/plan    - Plan a journey");
-
+    println!("  GET  /api/v1/stations/search  - Search stations by name or CRS");
+    println!("  GET  /api/v1/search/service   - Search for services");
+    println!("  POST /api/v1/journey/plan     - Plan a journey");
+    println!("  GET  /api/v1/journey/current  - Re-plan from the session's train");
+    println!(
+        "  GET  /api/v1/departures/reachable - Trains leaving a station that reach a destination"
+    );
+    println!("  GET  /api/v1/journeys/:id/geojson - Journey as GeoJSON");
+    println!("  GET  /api/v1/journeys/:id/text - Journey as plain-text narration");
+    println!("  GET  /api/v1/journeys/:id/pdf - Journey as a printable itinerary");
+    println!("  GET  /api/v1/journeys/:id/changes?since=N - Long-poll for journey changes");
+    println!("  GET  /api/v1/favourites       - Manage favourite destinations");
+    println!("  POST /api/v1/favourites/:name/plan - Plan from the session's train to a favourite");
+    println!("  GET  /api/v1/session          - Show the remembered train");
+    println!("  POST /api/v1/session/reset    - Forget the remembered train");
+    println!("  GET  /api/v1/admin/walkable   - Manage walkable connections (admin token)");
+    println!("  POST /api/v1/admin/reload     - Reload settings without a restart (admin token)");
+    println!("Unversioned API paths still work but are deprecated.");
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
@@ -0,0 +1,651 @@
+//! Delivery of alerts (e.g. missed connections) over external channels.
+//!
+//! Not every browser supports web push, so alerts can also go out over a
+//! Telegram bot, a generic webhook, or an SMS HTTP gateway. Which channels
+//! an alert uses is chosen per watched journey with a list of [`Channel`]s;
+//! the credentials they share (bot token, gateway key) are server-side
+//! [`NotifierConfig`].
+//!
+//! Each kind of channel is off until the operator enables it. Webhooks go
+//! only to https URLs whose host resolves to public addresses (or is on the
+//! operator's allow-list), and are sent to the address that was checked, so
+//! the server can't be pointed at itself or its network.
+
+use std::net::{IpAddr, SocketAddr};
+
+use reqwest::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::{Classify, ErrorKind};
+
+/// Default base URL for the Telegram Bot API.
+const TELEGRAM_BASE_URL: &str = "https://api.telegram.org";
+
+/// Where to deliver an alert.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Channel {
+    /// A Telegram chat, via the server's bot
+    Telegram { chat_id: String },
+
+    /// An HTTP endpoint that receives the alert as JSON
+    Webhook { url: String },
+
+    /// A phone number, via the server's SMS gateway
+    Sms { to: String },
+}
+
+impl Channel {
+    /// Short name for logs and errors.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Telegram { .. } => "telegram",
+            Channel::Webhook { .. } => "webhook",
+            Channel::Sms { .. } => "sms",
+        }
+    }
+}
+
+/// How urgently an alert needs the traveller's attention.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Worth knowing, e.g. a train running a few minutes late
+    #[default]
+    Normal,
+
+    /// Needs acting on now, e.g. a platform change before a connection
+    High,
+}
+
+impl Priority {
+    /// Short identifier, as used in the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+/// An alert to deliver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// One-line summary, e.g. "Connection at Reading missed"
+    pub title: String,
+
+    /// Details and what to do instead
+    pub body: String,
+
+    /// How urgent it is; webhooks are told, and the other channels put
+    /// "Urgent:" before the title of high-priority alerts
+    pub priority: Priority,
+}
+
+impl Notification {
+    /// Create a notification of normal priority.
+    pub fn new(title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            body: body.into(),
+            priority: Priority::Normal,
+        }
+    }
+
+    /// The same notification with the given priority.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Title and body as a single plain-text message.
+    pub fn text(&self) -> String {
+        let title = match self.priority {
+            Priority::High => format!("Urgent: {}", self.title),
+            Priority::Normal => self.title.clone(),
+        };
+        if self.body.is_empty() {
+            title
+        } else {
+            format!("{}\n{}", title, self.body)
+        }
+    }
+}
+
+/// Errors that can occur when delivering a notification.
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    /// HTTP request failed
+    #[error("HTTP error: {0}")]
+    Http(reqwest::Error),
+
+    /// The channel needs server configuration that isn't set
+    #[error("{channel} notifications are not configured")]
+    NotConfigured { channel: &'static str },
+
+    /// The channel's destination is one alerts may not be sent to
+    #[error("{channel} destination not allowed: {reason}")]
+    NotAllowed {
+        channel: &'static str,
+        reason: &'static str,
+    },
+
+    /// The channel's endpoint rejected the notification
+    #[error("{channel} returned {status}: {message}")]
+    Rejected {
+        channel: &'static str,
+        status: u16,
+        message: String,
+    },
+}
+
+impl From<reqwest::Error> for NotifyError {
+    /// Drop the URL from the error: a Telegram URL carries the bot token,
+    /// and these errors are logged.
+    fn from(e: reqwest::Error) -> Self {
+        NotifyError::Http(e.without_url())
+    }
+}
+
+impl Classify for NotifyError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            NotifyError::Http(_) => ErrorKind::Transient,
+            NotifyError::NotConfigured { .. } => ErrorKind::Config,
+            NotifyError::NotAllowed { .. } => ErrorKind::Permanent,
+            NotifyError::Rejected { status, .. } => ErrorKind::from_status(*status),
+        }
+    }
+}
+
+/// Server-side configuration shared by all notifications.
+#[derive(Debug, Clone)]
+pub struct NotifierConfig {
+    /// Token for the Telegram bot; Telegram channels fail without it
+    pub telegram_bot_token: Option<String>,
+
+    /// Base URL for the Telegram Bot API
+    pub telegram_base_url: String,
+
+    /// URL of the SMS gateway; SMS channels fail without it
+    pub sms_gateway_url: Option<String>,
+
+    /// Bearer token for the SMS gateway
+    pub sms_api_key: Option<String>,
+
+    /// Whether webhook channels may be used
+    pub webhooks: bool,
+
+    /// Hosts webhooks may be sent to (empty = any host with only public
+    /// addresses)
+    pub webhook_hosts: Vec<String>,
+
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            telegram_bot_token: None,
+            telegram_base_url: TELEGRAM_BASE_URL.to_string(),
+            sms_gateway_url: None,
+            sms_api_key: None,
+            webhooks: false,
+            webhook_hosts: Vec::new(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl NotifierConfig {
+    /// Enable Telegram channels using the given bot token.
+    pub fn with_telegram_bot_token(mut self, token: impl Into<String>) -> Self {
+        self.telegram_bot_token = Some(token.into());
+        self
+    }
+
+    /// Set a custom Telegram API base URL (for testing).
+    pub fn with_telegram_base_url(mut self, url: impl Into<String>) -> Self {
+        self.telegram_base_url = url.into();
+        self
+    }
+
+    /// Enable SMS channels using the given gateway.
+    ///
+    /// The gateway receives `{"to": ..., "message": ...}` as JSON, with the
+    /// API key (if any) as a bearer token.
+    pub fn with_sms_gateway(mut self, url: impl Into<String>, api_key: Option<String>) -> Self {
+        self.sms_gateway_url = Some(url.into());
+        self.sms_api_key = api_key;
+        self
+    }
+
+    /// Enable webhook channels, to the given hosts only unless `hosts` is
+    /// empty.
+    pub fn with_webhooks(mut self, hosts: Vec<String>) -> Self {
+        self.webhooks = true;
+        self.webhook_hosts = hosts;
+        self
+    }
+
+    /// Whether any kind of channel is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.telegram_bot_token.is_some() || self.sms_gateway_url.is_some() || self.webhooks
+    }
+}
+
+/// Whether an address is on the public internet, rather than loopback,
+/// private, link-local (such as cloud metadata services) or reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                // Carrier-grade NAT, IETF protocol assignments, benchmarking
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b == 18 || b == 19)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                let documentation = ip.segments()[..2] == [0x2001, 0xdb8];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || documentation)
+            }
+        },
+    }
+}
+
+/// An HTTP client for notifications, which doesn't follow redirects (a
+/// webhook could otherwise redirect to an address it may not use).
+fn client_builder(timeout_secs: u64) -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(timeout_secs))
+        .redirect(reqwest::redirect::Policy::none())
+}
+
+/// Sends notifications to channels.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    http: reqwest::Client,
+    config: NotifierConfig,
+}
+
+impl Notifier {
+    /// Create a notifier.
+    pub fn new(config: NotifierConfig) -> Result<Self, NotifyError> {
+        let http = client_builder(config.timeout_secs).build()?;
+        Ok(Self { http, config })
+    }
+
+    /// Check that alerts may be sent to a channel: its kind is enabled, and
+    /// a webhook's URL is allowed.
+    pub async fn check(&self, channel: &Channel) -> Result<(), NotifyError> {
+        let enabled = match channel {
+            Channel::Telegram { .. } => self.config.telegram_bot_token.is_some(),
+            Channel::Sms { .. } => self.config.sms_gateway_url.is_some(),
+            Channel::Webhook { url } => return self.webhook_target(url).await.map(|_| ()),
+        };
+        if !enabled {
+            return Err(NotifyError::NotConfigured {
+                channel: channel.name(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Where a webhook may be delivered: its host and the address to send
+    /// to, if its URL is https and its host is allowed and resolves only to
+    /// public addresses.
+    async fn webhook_target(&self, url: &str) -> Result<(String, SocketAddr), NotifyError> {
+        let not_allowed = |reason| NotifyError::NotAllowed {
+            channel: "webhook",
+            reason,
+        };
+        if !self.config.webhooks {
+            return Err(NotifyError::NotConfigured { channel: "webhook" });
+        }
+        let url = reqwest::Url::parse(url).map_err(|_| not_allowed("not a URL"))?;
+        if url.scheme() != "https" {
+            return Err(not_allowed("only https URLs are allowed"));
+        }
+        let host = url.host_str().ok_or_else(|| not_allowed("no host"))?;
+        let hosts = &self.config.webhook_hosts;
+        if !hosts.is_empty() && !hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+            return Err(not_allowed("host is not on the allow-list"));
+        }
+
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = match host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| not_allowed("host does not resolve"))?
+                .collect(),
+        };
+        match addrs.first() {
+            None => Err(not_allowed("host does not resolve")),
+            Some(_) if addrs.iter().any(|a| !is_public(a.ip())) => {
+                Err(not_allowed("host has a private or reserved address"))
+            }
+            Some(addr) => Ok((host.to_string(), *addr)),
+        }
+    }
+
+    /// Deliver a notification to one channel.
+    pub async fn send(
+        &self,
+        channel: &Channel,
+        notification: &Notification,
+    ) -> Result<(), NotifyError> {
+        let request = self.request(channel, notification)?;
+        let response = match channel {
+            // Connect to the address just checked, so the host can't resolve
+            // somewhere else by the time the request is made
+            Channel::Webhook { url } => {
+                let (host, addr) = self.webhook_target(url).await?;
+                let client = client_builder(self.config.timeout_secs)
+                    .resolve(&host, addr)
+                    .build()?;
+                client.execute(request).await?
+            }
+            _ => self.http.execute(request).await?,
+        };
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(NotifyError::Rejected {
+                channel: channel.name(),
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(())
+    }
+
+    /// Deliver a notification to every channel, returning the failures.
+    ///
+    /// One channel failing doesn't stop delivery to the others.
+    pub async fn send_all(
+        &self,
+        channels: &[Channel],
+        notification: &Notification,
+    ) -> Vec<(Channel, NotifyError)> {
+        let mut failures = Vec::new();
+        for channel in channels {
+            if let Err(e) = self.send(channel, notification).await {
+                tracing::warn!(channel = channel.name(), error = %e, "Failed to send notification");
+                failures.push((channel.clone(), e));
+            }
+        }
+        failures
+    }
+
+    /// Build the HTTP request that delivers a notification to a channel.
+    fn request(
+        &self,
+        channel: &Channel,
+        notification: &Notification,
+    ) -> Result<reqwest::Request, NotifyError> {
+        let not_configured = || NotifyError::NotConfigured {
+            channel: channel.name(),
+        };
+        let builder = match channel {
+            Channel::Telegram { chat_id } => {
+                let token = self.config.telegram_bot_token.as_ref();
+                let token = token.ok_or_else(not_configured)?;
+                let url = format!("{}/bot{}/sendMessage", self.config.telegram_base_url, token);
+                self.http.post(url).json(&json!({
+                    "chat_id": chat_id,
+                    "text": notification.text(),
+                }))
+            }
+            Channel::Webhook { url } => self.http.post(url).json(&json!({
+                "title": notification.title,
+                "body": notification.body,
+                "priority": notification.priority.as_str(),
+            })),
+            Channel::Sms { to } => {
+                let url = self.config.sms_gateway_url.as_ref();
+                let url = url.ok_or_else(not_configured)?;
+                let builder = self.http.post(url).json(&json!({
+                    "to": to,
+                    "message": notification.text(),
+                }));
+                match &self.config.sms_api_key {
+                    Some(key) => builder.header(AUTHORIZATION, format!("Bearer {key}")),
+                    None => builder,
+                }
+            }
+        };
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification() -> Notification {
+        Notification::new(
+            "Connection at Reading missed",
+            "Take the 10:45 to Bristol Temple Meads instead.",
+        )
+    }
+
+    fn body(request: &reqwest::Request) -> serde_json::Value {
+        let bytes = request.body().and_then(|b| b.as_bytes()).unwrap();
+        serde_json::from_slice(bytes).unwrap()
+    }
+
+    #[test]
+    fn telegram_request() {
+        let notifier = Notifier::new(
+            NotifierConfig::default()
+                .with_telegram_bot_token("123:abc")
+                .with_telegram_base_url("http://localhost:8080"),
+        )
+        .unwrap();
+        let channel = Channel::Telegram {
+            chat_id: "42".to_string(),
+        };
+
+        let request = notifier.request(&channel, &notification()).unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:8080/bot123:abc/sendMessage"
+        );
+        assert_eq!(
+            body(&request),
+            json!({
+                "chat_id": "42",
+                "text": "Connection at Reading missed\nTake the 10:45 to Bristol Temple Meads instead.",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn failures_do_not_reveal_the_bot_token() {
+        // Nothing listens on port 1, so the request fails
+        let notifier = Notifier::new(
+            NotifierConfig::default()
+                .with_telegram_bot_token("123:secret")
+                .with_telegram_base_url("http://127.0.0.1:1"),
+        )
+        .unwrap();
+        let channel = Channel::Telegram {
+            chat_id: "42".to_string(),
+        };
+
+        let err = notifier.send(&channel, &notification()).await.unwrap_err();
+        assert!(matches!(err, NotifyError::Http(_)));
+        assert!(!err.to_string().contains("secret"), "{err}");
+        assert!(!format!("{err:?}").contains("secret"), "{err:?}");
+    }
+
+    #[test]
+    fn webhook_request() {
+        let notifier = Notifier::new(NotifierConfig::default()).unwrap();
+        let channel = Channel::Webhook {
+            url: "https://example.com/hook".to_string(),
+        };
+
+        let request = notifier.request(&channel, &notification()).unwrap();
+        assert_eq!(request.url().as_str(), "https://example.com/hook");
+        assert_eq!(body(&request)["title"], "Connection at Reading missed");
+        assert_eq!(body(&request)["priority"], "normal");
+    }
+
+    #[test]
+    fn high_priority_is_flagged() {
+        let urgent = notification().with_priority(Priority::High);
+        assert_eq!(
+            urgent.text(),
+            "Urgent: Connection at Reading missed\nTake the 10:45 to Bristol Temple Meads instead."
+        );
+
+        let notifier = Notifier::new(NotifierConfig::default()).unwrap();
+        let channel = Channel::Webhook {
+            url: "https://example.com/hook".to_string(),
+        };
+        let request = notifier.request(&channel, &urgent).unwrap();
+        assert_eq!(body(&request)["title"], "Connection at Reading missed");
+        assert_eq!(body(&request)["priority"], "high");
+    }
+
+    #[test]
+    fn sms_request_uses_gateway_key() {
+        let notifier = Notifier::new(
+            NotifierConfig::default()
+                .with_sms_gateway("https://sms.example.com/send", Some("secret".to_string())),
+        )
+        .unwrap();
+        let channel = Channel::Sms {
+            to: "+447700900123".to_string(),
+        };
+
+        let request = notifier.request(&channel, &notification()).unwrap();
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer secret");
+        assert_eq!(body(&request)["to"], "+447700900123");
+    }
+
+    #[test]
+    fn unconfigured_channels_fail() {
+        let notifier = Notifier::new(NotifierConfig::default()).unwrap();
+        let telegram = Channel::Telegram {
+            chat_id: "42".to_string(),
+        };
+        let sms = Channel::Sms {
+            to: "+447700900123".to_string(),
+        };
+
+        for channel in [telegram, sms] {
+            let err = notifier.request(&channel, &notification()).unwrap_err();
+            assert!(matches!(err, NotifyError::NotConfigured { .. }));
+            assert_eq!(err.kind(), ErrorKind::Config);
+        }
+    }
+
+    #[tokio::test]
+    async fn webhooks_only_go_to_public_https_hosts() {
+        let not_allowed = |result| matches!(result, Err(NotifyError::NotAllowed { .. }));
+        let webhook = |url: &str| Channel::Webhook {
+            url: url.to_string(),
+        };
+
+        let disabled = Notifier::new(NotifierConfig::default()).unwrap();
+        assert!(matches!(
+            disabled.check(&webhook("https://1.1.1.1/hook")).await,
+            Err(NotifyError::NotConfigured { .. })
+        ));
+
+        let notifier = Notifier::new(NotifierConfig::default().with_webhooks(vec![])).unwrap();
+        assert!(
+            notifier
+                .check(&webhook("https://1.1.1.1/hook"))
+                .await
+                .is_ok()
+        );
+        for url in [
+            "http://1.1.1.1/hook",
+            "https://127.0.0.1/hook",
+            "https://localhost:8443/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://10.0.0.5/hook",
+            "https://100.64.0.1/hook",
+            "https://[::1]/hook",
+            "https://[fd00::1]/hook",
+            "https://[::ffff:192.168.0.1]/hook",
+            "not a url",
+        ] {
+            assert!(not_allowed(notifier.check(&webhook(url)).await), "{url}");
+        }
+
+        let listed = NotifierConfig::default().with_webhooks(vec!["hooks.example.com".into()]);
+        let notifier = Notifier::new(listed).unwrap();
+        assert!(not_allowed(
+            notifier.check(&webhook("https://1.1.1.1/hook")).await
+        ));
+    }
+
+    #[tokio::test]
+    async fn channels_are_off_until_enabled() {
+        let sms = Channel::Sms {
+            to: "+447700900123".to_string(),
+        };
+        let telegram = Channel::Telegram {
+            chat_id: "42".to_string(),
+        };
+
+        let notifier = Notifier::new(NotifierConfig::default()).unwrap();
+        assert!(!NotifierConfig::default().is_enabled());
+        for channel in [&sms, &telegram] {
+            let err = notifier.check(channel).await.unwrap_err();
+            assert!(matches!(err, NotifyError::NotConfigured { .. }));
+        }
+
+        let config = NotifierConfig::default().with_sms_gateway("https://sms.example.com", None);
+        assert!(config.is_enabled());
+        let notifier = Notifier::new(config).unwrap();
+        assert!(notifier.check(&sms).await.is_ok());
+        assert!(notifier.check(&telegram).await.is_err());
+    }
+
+    #[test]
+    fn channels_deserialize_from_tagged_json() {
+        let channels: Vec<Channel> = serde_json::from_str(
+            r#"[{"type": "telegram", "chat_id": "42"}, {"type": "sms", "to": "+447700900123"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            channels,
+            vec![
+                Channel::Telegram {
+                    chat_id: "42".to_string()
+                },
+                Channel::Sms {
+                    to: "+447700900123".to_string()
+                },
+            ]
+        );
+    }
+}
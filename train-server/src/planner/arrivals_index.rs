@@ -4,11 +4,18 @@
 //! a train that arrives at the destination. By fetching the arrivals board first,
 //! we get all candidate "final trains" and their previous calling points in one
 //! API call. This dramatically reduces API calls compared to forward BFS.
+//!
+//! A journey can also end with a short walk from a nearby "egress" station,
+//! so the arrivals boards of stations within walking distance of the
+//! destination can be added too; their services are indexed as reaching
+//! the destination on foot.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
-use crate::domain::{CallIndex, Crs, RailTime, Service};
+use chrono::Duration;
+
+use crate::domain::{CallIndex, Crs, Leg, RailTime, Segment, Service, Walk};
 
 /// Information about a train that can be boarded to reach the destination.
 #[derive(Debug, Clone)]
@@ -17,16 +24,34 @@ pub struct FeederInfo {
     pub service: Arc<Service>,
     /// Index of the call where we'd board this service.
     pub board_index: CallIndex,
+    /// Index of the call where we'd leave it: the destination, or an egress
+    /// station.
+    pub alight_index: CallIndex,
     /// Expected departure time from the boarding station.
     pub board_time: RailTime,
-    /// Expected arrival time at destination.
+    /// Expected arrival time at destination, including any walk.
     pub dest_arrival: RailTime,
+    /// Walk from the egress station to the destination, if the service
+    /// doesn't call there.
+    pub egress: Option<Walk>,
+}
+
+impl FeederInfo {
+    /// The segments finishing a journey on this service: its leg, and any
+    /// walk on to the destination.
+    pub fn segments(&self) -> Option<Vec<Segment>> {
+        let leg = Leg::new(self.service.clone(), self.board_index, self.alight_index).ok()?;
+        let mut segments = vec![Segment::Train(leg)];
+        segments.extend(self.egress.clone().map(Segment::Walk));
+        Some(segments)
+    }
 }
 
 /// Index of services arriving at destination, keyed by their calling points.
 ///
-/// This allows O(1) lookup of "which services can I board at station X to reach
-/// the destination?"
+/// This allows quick lookup of "which services can I board at station X to
+/// reach the destination?" Stations are kept in order, so iterating them
+/// doesn't depend on hashing.
 #[derive(Debug)]
 pub struct ArrivalsIndex {
     /// Destination station.
@@ -37,7 +62,11 @@ pub struct ArrivalsIndex {
 
     /// Map from station -> services arriving at destination that call at this station.
     /// Value includes the boarding time at that station.
-    feeders: HashMap<Crs, Vec<FeederInfo>>,
+    feeders: BTreeMap<Crs, Vec<FeederInfo>>,
+
+    /// Earliest a service left off the boards could reach the destination
+    /// (see [`ArrivalsIndex::unlisted_arrival_bound`]).
+    unlisted_bound: Option<RailTime>,
 }
 
 impl ArrivalsIndex {
@@ -48,55 +77,107 @@ impl ArrivalsIndex {
     /// * `destination` - The destination station CRS
     /// * `arrivals` - Services arriving at the destination, with their previous calling points
     pub fn from_arrivals(destination: Crs, arrivals: Vec<Arc<Service>>) -> Self {
-        let mut feeders: HashMap<Crs, Vec<FeederInfo>> = HashMap::new();
-
+        let mut index = Self {
+            destination,
+            arriving_services: Vec::new(),
+            feeders: BTreeMap::new(),
+            unlisted_bound: None,
+        };
         for service in &arrivals {
-            // Find the destination call in this service
-            // Note: services may continue past the destination, so we can't assume last call
-            let dest_call_idx = match service.calls.iter().position(|c| c.station == destination) {
-                Some(idx) => idx,
-                None => continue, // Service doesn't call at destination (shouldn't happen)
-            };
+            index.add_service(service, &destination, None);
+        }
+        index.bound_unlisted(&destination, Duration::zero(), &arrivals);
+        index.arriving_services = arrivals;
+        index
+    }
 
-            let dest_call = &service.calls[dest_call_idx];
+    /// Add the arrivals board of `station`, a walk of `walk` from the
+    /// destination, so its services can finish a journey on foot.
+    ///
+    /// Services that go on to the destination are indexed here too, as
+    /// getting off early and walking can be quicker; boarding before a call
+    /// at the destination itself is left out.
+    pub fn add_egress(&mut self, station: Crs, walk: Duration, arrivals: &[Arc<Service>]) {
+        let egress = Walk::new(station, self.destination, walk);
+        for service in arrivals {
+            self.add_service(service, &station, Some(&egress));
+        }
+        self.bound_unlisted(&station, walk, arrivals);
+    }
 
-            // Get arrival time at destination
-            let dest_arrival = match dest_call.expected_arrival() {
-                Some(t) => t,
-                None => continue, // Can't determine arrival time
-            };
+    /// Lower the bound on services left off the boards, given `station`'s
+    /// board, a walk of `walk` from the destination.
+    ///
+    /// Boards list the earliest arrivals, so a service left off arrives no
+    /// earlier than the last one listed.
+    fn bound_unlisted(&mut self, station: &Crs, walk: Duration, arrivals: &[Arc<Service>]) {
+        let latest = arrivals
+            .iter()
+            .filter_map(|s| s.board_call_at(station)?.1.expected_arrival())
+            .max();
+        if let Some(latest) = latest {
+            let bound = latest + walk;
+            self.unlisted_bound = Some(self.unlisted_bound.map_or(bound, |b| b.min(bound)));
+        }
+    }
 
-            // Skip if destination call is cancelled
-            if dest_call.is_cancelled {
+    /// Index the calls of `service` before it reaches `alight_at`, walking
+    /// from there to the destination if `egress` is given.
+    fn add_service(&mut self, service: &Arc<Service>, alight_at: &Crs, egress: Option<&Walk>) {
+        // Note: services may continue past the station, so we can't assume
+        // it's the last call
+        let Some((alight_idx, alight_call)) = service.board_call_at(alight_at) else {
+            return; // Service doesn't call there (shouldn't happen)
+        };
+        let Some(arrival) = alight_call.expected_arrival() else {
+            return; // Can't determine arrival time
+        };
+        if alight_call.is_cancelled || !alight_call.can_alight() {
+            return;
+        }
+        let dest_arrival = arrival + egress.map_or(Duration::zero(), |w| w.duration);
+
+        // Boarding before a call at the destination itself would pass
+        // through it on the way to the egress station; the destination's
+        // own board covers those
+        let first = match egress {
+            Some(_) => service.calls[..alight_idx.0]
+                .iter()
+                .rposition(|c| c.station == self.destination)
+                .map_or(0, |idx| idx + 1),
+            None => 0,
+        };
+
+        // Index all calling points BEFORE the station alighted at
+        for (idx, call) in service
+            .calls
+            .iter()
+            .enumerate()
+            .take(alight_idx.0)
+            .skip(first)
+        {
+            // Skip cancelled calls, and those only setting down
+            if call.is_cancelled || !call.can_board() {
                 continue;
             }
 
-            // Index all calling points BEFORE the destination
-            for (idx, call) in service.calls.iter().enumerate().take(dest_call_idx) {
-                // Skip cancelled calls
-                if call.is_cancelled {
-                    continue;
-                }
-
-                // Need departure time to board here
-                let board_time = match call.expected_departure() {
-                    Some(t) => t,
-                    None => continue, // Can't board here (no departure time)
-                };
+            // Need departure time to board here
+            let board_time = match call.expected_departure() {
+                Some(t) => t,
+                None => continue, // Can't board here (no departure time)
+            };
 
-                feeders.entry(call.station).or_default().push(FeederInfo {
+            self.feeders
+                .entry(call.station)
+                .or_default()
+                .push(FeederInfo {
                     service: service.clone(),
                     board_index: CallIndex(idx),
+                    alight_index: alight_idx,
                     board_time,
                     dest_arrival,
+                    egress: egress.cloned(),
                 });
-            }
-        }
-
-        Self {
-            destination,
-            arriving_services: arrivals,
-            feeders,
         }
     }
 
@@ -113,7 +194,7 @@ impl ArrivalsIndex {
         self.feeders.contains_key(station)
     }
 
-    /// Get all feeder stations.
+    /// Get all feeder stations, in alphabetical order.
     pub fn feeder_stations(&self) -> impl Iterator<Item = &Crs> {
         self.feeders.keys()
     }
@@ -128,6 +209,21 @@ impl ArrivalsIndex {
         &self.arriving_services
     }
 
+    /// Stations where arriving services last call before the destination.
+    pub fn stops_before_destination(&self) -> BTreeSet<Crs> {
+        self.arriving_services
+            .iter()
+            .filter_map(|service| {
+                let (dest_idx, _) = service.board_call_at(&self.destination)?;
+                service.calls[..dest_idx.0]
+                    .iter()
+                    .rev()
+                    .find(|call| !call.is_cancelled && call.station != self.destination)
+                    .map(|call| call.station)
+            })
+            .collect()
+    }
+
     /// Get the number of feeder stations.
     pub fn feeder_station_count(&self) -> usize {
         self.feeders.len()
@@ -148,6 +244,32 @@ impl ArrivalsIndex {
             .map(|f| f.dest_arrival)
             .min()
     }
+
+    /// Get the earliest a service left off the boards (the destination's and
+    /// any egress stations') could reach the destination.
+    ///
+    /// A service left off a full board arrives after all those listed, but
+    /// after a walk from an egress station it can still beat services
+    /// listed on the destination's board, so a search can't stop at the
+    /// best indexed connection if this is earlier. `None` if the boards
+    /// list nothing.
+    pub fn unlisted_arrival_bound(&self) -> Option<RailTime> {
+        self.unlisted_bound
+    }
+
+    /// Get the earliest arrival time at destination on indexed services
+    /// boarded at or after `time`, anywhere.
+    ///
+    /// A route that carries on from a station at `time` can arrive no
+    /// earlier than this, if it ends on an indexed service.
+    pub fn earliest_arrival_boarding_after(&self, time: RailTime) -> Option<RailTime> {
+        self.feeders
+            .values()
+            .flatten()
+            .filter(|f| f.board_time >= time)
+            .map(|f| f.dest_arrival)
+            .min()
+    }
 }
 
 #[cfg(test)]
@@ -198,6 +320,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -240,6 +363,35 @@ mod tests {
         assert_eq!(rdg_feeders[0].board_index, CallIndex(2));
     }
 
+    #[test]
+    fn stops_before_destination_skip_cancelled_calls() {
+        let stopping = make_arriving_service(
+            "S1",
+            &[
+                ("SWI", "Swindon", "", "10:00"),
+                ("RDG", "Reading", "10:35", "10:37"),
+                ("PAD", "Paddington", "11:00", ""),
+            ],
+        );
+        let mut fast = make_arriving_service(
+            "S2",
+            &[
+                ("OXF", "Oxford", "", "10:00"),
+                ("DID", "Didcot", "10:12", "10:14"),
+                ("SLO", "Slough", "10:40", "10:41"),
+                ("PAD", "Paddington", "10:55", ""),
+            ],
+        );
+        Arc::make_mut(&mut fast).calls[2].is_cancelled = true;
+
+        let index = ArrivalsIndex::from_arrivals(crs("PAD"), vec![stopping, fast]);
+
+        assert_eq!(
+            index.stops_before_destination(),
+            BTreeSet::from([crs("RDG"), crs("DID")])
+        );
+    }
+
     #[test]
     fn multiple_services_same_feeder_station() {
         // Two services both calling at RDG before PAD
@@ -342,8 +494,52 @@ mod tests {
         let index = ArrivalsIndex::from_arrivals(crs("PAD"), vec![service]);
 
         let stations: Vec<_> = index.feeder_stations().collect();
-        assert_eq!(stations.len(), 2);
-        assert!(stations.contains(&&crs("SWI")));
-        assert!(stations.contains(&&crs("RDG")));
+        assert_eq!(stations, [&crs("RDG"), &crs("SWI")]);
+    }
+
+    #[test]
+    fn egress_feeders_finish_on_foot() {
+        // Destination EUS, a walk of 8 minutes from STP
+        let direct = make_arriving_service(
+            "D1",
+            &[
+                ("RDG", "Reading", "", "10:00"),
+                ("EUS", "Euston", "11:00", ""),
+            ],
+        );
+        // Calls at EUS on the way to STP: only boarding after EUS counts
+        let through = make_arriving_service(
+            "T1",
+            &[
+                ("WFJ", "Watford Junction", "", "10:00"),
+                ("EUS", "Euston", "10:20", "10:22"),
+                ("KGX", "King's Cross", "10:30", "10:31"),
+                ("STP", "St Pancras", "10:35", ""),
+            ],
+        );
+        let egress = make_arriving_service(
+            "E1",
+            &[
+                ("LUT", "Luton", "", "10:00"),
+                ("STP", "St Pancras", "10:40", ""),
+            ],
+        );
+
+        let mut index = ArrivalsIndex::from_arrivals(crs("EUS"), vec![direct]);
+        assert_eq!(index.unlisted_arrival_bound(), Some(time("11:00")));
+
+        index.add_egress(crs("STP"), Duration::minutes(8), &[through, egress]);
+
+        assert!(!index.is_feeder(&crs("WFJ")));
+        assert_eq!(index.feeders_at(&crs("KGX")).len(), 1);
+        let luton = &index.feeders_at(&crs("LUT"))[0];
+        assert_eq!(luton.dest_arrival, time("10:48"));
+        let segments = luton.segments().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(matches!(&segments[1], Segment::Walk(w) if w.to == crs("EUS")));
+
+        // Anything left off STP's board arrives there after 10:40
+        assert_eq!(index.unlisted_arrival_bound(), Some(time("10:48")));
+        assert_eq!(index.earliest_arrival(), Some(time("10:43")));
     }
 }
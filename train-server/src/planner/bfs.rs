@@ -6,19 +6,39 @@
 //!    destination with limited arrivals), so BFS explores paths not in the index
 //!
 //! The key optimization is that whenever we reach a feeder station (one with direct
-//! service to the destination), we can complete the journey via the ArrivalsIndex
-//! without further exploration.
+//! service to the destination), we can complete the journey via the ArrivalsIndex.
+//! If that connection arrives as early as any indexed service boarded later,
+//! no route on from the feeder can arrive sooner, so it isn't explored further.
+//!
+//! With a [`TravelTimes`] matrix, states are also pruned A*-style: if even the
+//! quickest known way from a state's station to the destination (changing
+//! or walking where quicker, less `config.travel_time_margin_mins` for
+//! trains not yet observed) would arrive after a journey already found with
+//! no more changes, every completion of that state would be dominated, so
+//! it isn't explored. Pruning only sees arrival times and changes, so it's
+//! off when `config.dominance` keeps journeys that walk less or cost less.
+//!
+//! Each level explores journeys with one more change than the last, which
+//! are rarely better than what's already been found. With
+//! `config.bfs_target_journeys` set, BFS stops before the next level once
+//! that many journeys found (by any phase) arrive within
+//! `config.bfs_arrival_margin_mins` of the earliest, trading the chance of
+//! a faster journey with more changes for fewer departure fetches.
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use chrono::Duration;
-use futures::future::join_all;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, trace};
 
 use super::arrivals_index::ArrivalsIndex;
-use super::config::SearchConfig;
+use super::config::{DominanceCriterion, SearchConfig};
+use super::pacing::batch_fetch_departures;
+use super::quality::DataQuality;
+use super::rank::RouteKey;
 use super::search::ServiceProvider;
+use super::travel_times::{LowerBounds, TravelTimes};
 use crate::domain::{CallIndex, Crs, Journey, Leg, RailTime, Segment, Service, Walk};
 use crate::walkable::WalkableConnections;
 
@@ -35,6 +55,15 @@ struct BfsState {
 pub struct BfsResult {
     pub journeys: Vec<Journey>,
     pub api_calls: usize,
+    /// States dropped because they couldn't beat a journey already found.
+    pub pruned: usize,
+    /// States whose departures had already been fetched.
+    pub cache_hits: usize,
+    /// Whether BFS stopped early with enough journeys, leaving levels
+    /// unexplored.
+    pub stopped_early: bool,
+    /// Stations whose departures couldn't be fetched.
+    pub data_quality: DataQuality,
 }
 
 /// Parameters for BFS search, bundled for cleaner function signature.
@@ -43,6 +72,54 @@ pub struct BfsParams<'a> {
     pub current_position: CallIndex,
     pub destination: Crs,
     pub start_time: RailTime,
+    /// Checked between BFS levels and fetch batches; BFS stops early once set.
+    pub cancel: Option<&'a CancellationToken>,
+    /// Lower bounds for pruning; no pruning without it.
+    pub travel_times: Option<&'a TravelTimes>,
+    /// (arrival time, changes) of journeys found before BFS started.
+    pub found: Vec<(RailTime, usize)>,
+}
+
+/// Whether a state can be pruned.
+///
+/// Completing the state takes at least one more leg, so the journey would
+/// have at least `changes_so_far + 1` changes and arrive no earlier than the
+/// state's available time plus the lower bound to the destination. If a
+/// journey already found is no worse on both counts, the completion would
+/// be dominated (or a duplicate), as long as nothing else is compared (see
+/// [`prunes_soundly`]).
+fn can_prune(state: &BfsState, bounds: &LowerBounds, found: &[(RailTime, usize)]) -> bool {
+    let earliest_arrival = state.available_time + bounds.from(&state.station);
+    let min_changes = state.changes_so_far + 1;
+    found
+        .iter()
+        .any(|&(arrival, changes)| arrival < earliest_arrival && changes <= min_changes)
+}
+
+/// Whether a completion [`can_prune`] drops would also be dominated under
+/// `config.dominance`.
+///
+/// Pruning compares arrival and changes alone. Duration follows arrival, as
+/// every journey starts on the user's train, but a completion that walks
+/// less or is in a lower price band (when priced) could still be kept.
+fn prunes_soundly(config: &SearchConfig) -> bool {
+    let criteria = &config.dominance;
+    let compares_price =
+        criteria.contains(DominanceCriterion::Price) && config.price_weight_mins > 0;
+    !criteria.contains(DominanceCriterion::Walking) && !compares_price
+}
+
+/// Whether at least `target` of the journeys `found` arrive within
+/// `margin` of the earliest of them.
+fn enough_found(found: &[(RailTime, usize)], target: usize, margin: Duration) -> bool {
+    let Some(earliest) = found.iter().map(|&(arrival, _)| arrival).min() else {
+        return false;
+    };
+    found
+        .iter()
+        .filter(|&&(arrival, _)| arrival <= earliest + margin)
+        .count()
+        >= target
 }
 
 /// Run BFS fallback search.
@@ -52,8 +129,8 @@ pub struct BfsParams<'a> {
 /// - `journeys.len() < max_results`: ArrivalsIndex may be incomplete
 ///
 /// Uses forward BFS with a key optimization: whenever we reach a feeder
-/// station, we can complete the journey via the ArrivalsIndex without
-/// further exploration.
+/// station, we can complete the journey via the ArrivalsIndex, and stop
+/// there unless a longer route could still arrive sooner.
 pub async fn find_bfs_journeys<P: ServiceProvider>(
     params: &BfsParams<'_>,
     index: &ArrivalsIndex,
@@ -64,6 +141,11 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 ) -> BfsResult {
     let mut journeys = Vec::new();
     let mut api_calls = 0;
+    let mut pruned = 0;
+    let mut cache_hits = 0;
+    let mut stopped_early = false;
+    let mut data_quality = DataQuality::default();
+    let mut found = params.found.clone();
 
     let min_connection = config.min_connection();
     let max_journey = config.max_journey();
@@ -74,12 +156,11 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 
     // Initialize frontier with all stations on current train
     let train = params.current_service;
-    let pos = params.current_position.0;
 
     let mut frontier: Vec<BfsState> = Vec::new();
 
-    for (alight_idx, alight_call) in train.calls.iter().enumerate().skip(pos) {
-        if alight_call.is_cancelled {
+    for (alight_idx, alight_call) in train.calls_after(params.current_position) {
+        if alight_call.is_cancelled || !alight_call.can_alight() {
             continue;
         }
         if alight_call.station == params.destination {
@@ -95,11 +176,7 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
         };
 
         // Build first leg
-        let leg = match Leg::new(
-            train.clone(),
-            params.current_position,
-            CallIndex(alight_idx),
-        ) {
+        let leg = match Leg::new(train.clone(), params.current_position, alight_idx) {
             Ok(l) => l,
             Err(_) => continue,
         };
@@ -129,9 +206,43 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 
     // BFS: explore level by level (each level = one more change)
     while !frontier.is_empty() {
+        if is_cancelled(params.cancel) {
+            debug!("BFS fallback cancelled");
+            break;
+        }
+        if let Some((target, margin)) = config.bfs_early_exit()
+            && enough_found(&found, target, margin)
+        {
+            debug!(
+                found = found.len(),
+                "BFS fallback has enough journeys, stopping early"
+            );
+            stopped_early = true;
+            break;
+        }
+
+        // Bounds from everything observed so far, which the last level's
+        // fetches may have lowered
+        let bounds = params
+            .travel_times
+            .filter(|_| config.travel_time_pruning && prunes_soundly(config))
+            .map(|times| {
+                times.bounds_to(&params.destination, walkable, config.travel_time_margin())
+            });
+
         // First pass: filter frontier and collect stations needing departure fetches
         let mut valid_states: Vec<BfsState> = Vec::new();
-        let mut stations_to_fetch: HashSet<Crs> = HashSet::new();
+        // In the order first reached, so fetches (and which are cut off by
+        // cancellation) don't depend on hashing
+        let mut stations_to_fetch: Vec<Crs> = Vec::new();
+
+        // Only the first state at each station and level is explored, so
+        // take them earliest first: a later one can't catch any train the
+        // earliest can't. Ties go by route, so which is explored doesn't
+        // depend on the order boards list their trains
+        frontier.sort_by_cached_key(|state| {
+            (state.available_time, RouteKey::of_segments(&state.segments))
+        });
 
         for state in frontier {
             // Check if we've exceeded max changes
@@ -147,6 +258,14 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                 continue;
             }
 
+            // Skip if no completion could beat a journey already found
+            if let Some(bounds) = &bounds
+                && can_prune(&state, bounds, &found)
+            {
+                pruned += 1;
+                continue;
+            }
+
             // Skip if we've visited this state at this change level
             let state_key = (state.station, state.changes_so_far);
             if visited_states.contains(&state_key) {
@@ -156,7 +275,7 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 
             // If this station is a feeder, try to complete journey via ArrivalsIndex
             if index.is_feeder(&state.station) {
-                let mut found_connection = false;
+                let mut earliest_connection: Option<RailTime> = None;
                 for feeder in index.feeders_at(&state.station) {
                     let time_until_feeder = feeder
                         .board_time
@@ -172,58 +291,64 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                         continue;
                     }
 
-                    let alight_idx = match feeder
-                        .service
-                        .calls
-                        .iter()
-                        .position(|c| c.station == params.destination)
-                    {
-                        Some(idx) => idx,
-                        None => continue,
-                    };
-                    let final_leg = match Leg::new(
-                        feeder.service.clone(),
-                        feeder.board_index,
-                        CallIndex(alight_idx),
-                    ) {
-                        Ok(l) => l,
-                        Err(_) => continue,
+                    let Some(finish) = feeder.segments() else {
+                        continue;
                     };
-
                     let mut segments = state.segments.clone();
-                    segments.push(Segment::Train(final_leg));
+                    segments.extend(finish);
 
                     if let Ok(journey) = Journey::new(segments) {
+                        let arrival = journey.arrival_time();
+                        found.push((arrival, journey.change_count()));
+                        earliest_connection =
+                            Some(earliest_connection.map_or(arrival, |t| t.min(arrival)));
                         journeys.push(journey);
-                        found_connection = true;
                     }
                 }
-                // Only skip further exploration if we found a valid connection
-                if found_connection {
+                // Any route on from here boards its last train later, so if
+                // none of those beat this connection, nor could a train
+                // left off the boards, stop here
+                if let Some(connection) = earliest_connection
+                    && index
+                        .earliest_arrival_boarding_after(state.available_time)
+                        .is_none_or(|bound| connection <= bound)
+                    && index
+                        .unlisted_arrival_bound()
+                        .is_none_or(|bound| connection <= bound)
+                {
                     continue;
                 }
-                // Otherwise fall through to continue BFS exploration
             }
 
             // Need to fetch departures for this station (if not cached)
-            if !departures_cache.contains_key(&state.station) {
-                stations_to_fetch.insert(state.station);
+            if departures_cache.contains_key(&state.station) {
+                cache_hits += 1;
+            } else if !stations_to_fetch.contains(&state.station) {
+                stations_to_fetch.push(state.station);
             }
             valid_states.push(state);
         }
 
         // Batch fetch departures for all non-cached stations in parallel.
         // Uses start_time for all stations; see comment in find_two_change for rationale.
-        let stations_vec: Vec<Crs> = stations_to_fetch.into_iter().collect();
         let batch_calls = batch_fetch_departures(
-            &stations_vec,
+            &stations_to_fetch,
             params.start_time,
             departures_cache,
+            &mut data_quality,
             config,
             provider,
+            params.cancel,
         )
         .await;
         api_calls += batch_calls;
+        if let Some(travel_times) = params.travel_times {
+            for station in &stations_to_fetch {
+                for service in departures_cache.get(station).into_iter().flatten() {
+                    travel_times.observe(service);
+                }
+            }
+        }
 
         // Now process valid states using cached departures
         let mut next_frontier: Vec<BfsState> = Vec::new();
@@ -243,16 +368,10 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 
             // Explore each departing service
             for service in &departures {
-                let board_idx = match service
-                    .calls
-                    .iter()
-                    .position(|c| c.station == state.station)
-                {
-                    Some(idx) => idx,
-                    None => continue,
+                let (board_idx, board_call) = match service.board_call_at(&state.station) {
+                    Some(found) if found.1.can_board() => found,
+                    _ => continue,
                 };
-
-                let board_call = &service.calls[board_idx];
                 let board_time = match board_call.expected_departure() {
                     Some(t) => t,
                     None => continue,
@@ -262,20 +381,14 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                     continue;
                 }
 
-                for (alight_idx, alight_call) in
-                    service.calls.iter().enumerate().skip(board_idx + 1)
-                {
-                    if alight_call.is_cancelled {
+                for (alight_idx, alight_call) in service.calls_after(board_idx) {
+                    if alight_call.is_cancelled || !alight_call.can_alight() {
                         continue;
                     }
 
                     // If we reach destination directly, that's a valid journey
                     if alight_call.station == params.destination {
-                        let leg = match Leg::new(
-                            service.clone(),
-                            CallIndex(board_idx),
-                            CallIndex(alight_idx),
-                        ) {
+                        let leg = match Leg::new(service.clone(), board_idx, alight_idx) {
                             Ok(l) => l,
                             Err(_) => continue,
                         };
@@ -284,6 +397,7 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                         segments.push(Segment::Train(leg));
 
                         if let Ok(journey) = Journey::new(segments) {
+                            found.push((journey.arrival_time(), journey.change_count()));
                             journeys.push(journey);
                         }
                         continue;
@@ -302,11 +416,7 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                         continue;
                     }
 
-                    let leg = match Leg::new(
-                        service.clone(),
-                        CallIndex(board_idx),
-                        CallIndex(alight_idx),
-                    ) {
+                    let leg = match Leg::new(service.clone(), board_idx, alight_idx) {
                         Ok(l) => l,
                         Err(_) => continue,
                     };
@@ -314,6 +424,20 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                     let mut new_segments = state.segments.clone();
                     new_segments.push(Segment::Train(leg.clone()));
 
+                    // Near enough to walk the rest of the way
+                    if let Some(walk_time) = walkable.get(&alight_call.station, &params.destination)
+                        && walk_time <= max_walk
+                        && total_so_far + walk_time <= max_journey
+                    {
+                        let walk = Walk::new(alight_call.station, params.destination, walk_time);
+                        let mut segments = new_segments.clone();
+                        segments.push(Segment::Walk(walk));
+                        if let Ok(journey) = Journey::new(segments) {
+                            found.push((journey.arrival_time(), journey.change_count()));
+                            journeys.push(journey);
+                        }
+                    }
+
                     next_frontier.push(BfsState {
                         segments: new_segments.clone(),
                         station: alight_call.station,
@@ -325,7 +449,8 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
                     for (walkable_station, walk_time) in
                         walkable.walkable_from(&alight_call.station)
                     {
-                        if walk_time > max_walk {
+                        // Walks to the destination finish a journey above
+                        if walk_time > max_walk || walkable_station == params.destination {
                             continue;
                         }
                         let walk = Walk::new(alight_call.station, walkable_station, walk_time);
@@ -348,62 +473,20 @@ pub async fn find_bfs_journeys<P: ServiceProvider>(
 
     debug!(
         journeys = journeys.len(),
-        api_calls, "BFS fallback complete"
+        api_calls, pruned, stopped_early, "BFS fallback complete"
     );
 
     BfsResult {
         journeys,
         api_calls,
+        pruned,
+        cache_hits,
+        stopped_early,
+        data_quality,
     }
 }
 
-/// Batch fetch departures for multiple stations in parallel.
-///
-/// Fetches departures for all given stations, respecting `batch_size` for
-/// parallelism. Results are inserted into the cache. Returns the number
-/// of API calls made.
-async fn batch_fetch_departures<P: ServiceProvider>(
-    stations: &[Crs],
-    after: RailTime,
-    cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
-    config: &SearchConfig,
-    provider: &P,
-) -> usize {
-    if stations.is_empty() {
-        return 0;
-    }
-
-    let mut api_calls = 0;
-
-    for batch in stations.chunks(config.batch_size) {
-        let futures: Vec<_> = batch
-            .iter()
-            .map(|station| async move {
-                let result = provider.get_departures(station, after).await;
-                (*station, result)
-            })
-            .collect();
-
-        let results = join_all(futures).await;
-
-        for (station, result) in results {
-            api_calls += 1;
-            match result {
-                Ok(deps) => {
-                    cache.insert(station, deps);
-                }
-                Err(e) => {
-                    debug!(
-                        station = %station.as_str(),
-                        error = %e,
-                        "Failed to fetch departures, using empty"
-                    );
-                    // Insert empty vec so we don't retry
-                    cache.insert(station, vec![]);
-                }
-            }
-        }
-    }
-
-    api_calls
+/// Whether an optional cancellation token has been triggered.
+fn is_cancelled(cancel: Option<&CancellationToken>) -> bool {
+    cancel.is_some_and(|t| t.is_cancelled())
 }
@@ -0,0 +1,238 @@
+//! Reverse search from a departure board.
+//!
+//! [`Planner::search`] starts from a train the user is already on. Before
+//! boarding, the question is rather "which of the trains leaving here gets
+//! me there?". [`Planner::search_board`] answers it by running a
+//! pre-departure search from every train leaving a station within a time
+//! window, i.e. the identify-then-plan flow for each train on the board.
+//! Departures reaching the destination are ranked by how long they take
+//! from the start of the window, counting the wait for each train (see
+//! [`weighted_duration_from`]).
+//!
+//! The searches run a few at a time (`board_concurrency`), and one that
+//! fails only marks its own train as unavailable.
+
+use std::sync::Arc;
+
+use futures::stream::{self, StreamExt};
+use tracing::{info, warn};
+
+use super::continuation::Continuation;
+use super::phase::{BOARD_PHASE, in_phase};
+use super::quality::DataQuality;
+use super::rank::weighted_duration_from;
+use super::search::{Planner, SearchError, SearchRequest, SearchResult, ServiceProvider};
+use crate::domain::{CallIndex, Crs, Journey, RailTime, Service};
+
+/// Most departures searched from one board.
+///
+/// Each is a full search, so this bounds the cost of one board request.
+pub const MAX_BOARD_DEPARTURES: usize = 12;
+
+/// A request for the departures from a station that reach a destination.
+#[derive(Debug, Clone)]
+pub struct BoardRequest {
+    /// Station the user will board at
+    pub station: Crs,
+
+    /// Where the user is going
+    pub destination: Crs,
+
+    /// Earliest departure to consider
+    pub from: RailTime,
+
+    /// Latest departure to consider
+    pub until: RailTime,
+}
+
+impl BoardRequest {
+    /// Create a board request.
+    pub fn new(station: Crs, destination: Crs, from: RailTime, until: RailTime) -> Self {
+        Self {
+            station,
+            destination,
+            from,
+            until,
+        }
+    }
+}
+
+/// A train leaving the board station, and how it gets to the destination.
+#[derive(Debug, Clone)]
+pub struct BoardDeparture {
+    /// The departing train
+    pub service: Arc<Service>,
+
+    /// The board station's position in the train's calls
+    pub position: CallIndex,
+
+    /// Expected departure from the board station
+    pub departure: RailTime,
+
+    /// The best journey starting on this train, if any reaches the
+    /// destination within the configured number of changes
+    pub journey: Option<Journey>,
+
+    /// Position among the departures reaching the destination, best (0)
+    /// first, or `None` if this one doesn't
+    pub rank: Option<usize>,
+
+    /// Whether the search from this train failed, so it isn't known
+    /// whether it reaches the destination
+    pub unavailable: bool,
+}
+
+impl BoardDeparture {
+    /// Whether this train gets the user to the destination.
+    pub fn reaches_destination(&self) -> bool {
+        self.journey.is_some()
+    }
+}
+
+/// Departures from a board, annotated with how they reach the destination.
+#[derive(Debug, Clone)]
+pub struct BoardResult {
+    /// Departures in board order, including those that don't reach the
+    /// destination
+    pub departures: Vec<BoardDeparture>,
+
+    /// Number of API calls made, including the board itself
+    pub routes_explored: usize,
+
+    /// Stations whose boards couldn't be fetched, over all the searches
+    pub data_quality: DataQuality,
+
+    /// If no departure reaches the destination, the continuation (see
+    /// [`Continuation`]) getting closest to it
+    pub continuation: Option<Continuation>,
+}
+
+impl<P: ServiceProvider> Planner<'_, P> {
+    /// Search from every train leaving a station in a time window.
+    ///
+    /// Trains that are cancelled at the station or terminate there are left
+    /// out; at most [`MAX_BOARD_DEPARTURES`] are searched, at most
+    /// `board_concurrency` at a time. A train whose search fails is kept,
+    /// marked unavailable; only if every search fails (or the request is
+    /// cancelled) is the error returned.
+    pub async fn search_board(&self, request: &BoardRequest) -> Result<BoardResult, SearchError> {
+        if request.until < request.from {
+            return Err(SearchError::InvalidRequest(format!(
+                "Window ends at {} before it starts at {}",
+                request.until, request.from
+            )));
+        }
+
+        let board = self.provider.get_departures(&request.station, request.from);
+        let services = in_phase(BOARD_PHASE, board).await?;
+
+        let boardable: Vec<(Arc<Service>, CallIndex, RailTime)> = services
+            .into_iter()
+            .filter_map(|service| {
+                let (position, call) = service.board_call_at(&request.station)?;
+                let departure = call.expected_departure()?;
+                let in_window = departure >= request.from && departure <= request.until;
+                (!call.is_cancelled && call.can_board() && in_window)
+                    .then_some((service, position, departure))
+            })
+            .take(MAX_BOARD_DEPARTURES)
+            .collect();
+
+        info!(
+            station = %request.station,
+            departures = boardable.len(),
+            "Searching from each departure on the board"
+        );
+
+        let searches: Vec<_> = boardable
+            .iter()
+            .map(|(service, position, _)| {
+                let search = SearchRequest::new(service.clone(), *position, request.destination)
+                    .with_pre_departure();
+                async move { self.search(&search).await }
+            })
+            .collect();
+        let limit = self
+            .config
+            .board_concurrency_limit()
+            .unwrap_or(boardable.len())
+            .max(1);
+        let results: Vec<Result<SearchResult, SearchError>> =
+            stream::iter(searches).buffered(limit).collect().await;
+        if results
+            .iter()
+            .any(|r| matches!(r, Err(SearchError::Cancelled)))
+        {
+            return Err(SearchError::Cancelled);
+        }
+        if !results.is_empty() && results.iter().all(Result::is_err) {
+            let first = results.into_iter().find_map(Result::err);
+            return Err(first.expect("every search failed"));
+        }
+
+        let mut routes_explored = 1;
+        let mut data_quality = DataQuality::default();
+        let mut continuations = Vec::new();
+        let mut departures: Vec<BoardDeparture> = boardable
+            .into_iter()
+            .zip(results)
+            .map(|((service, position, departure), result)| {
+                let (journey, unavailable) = match result {
+                    Ok(result) => {
+                        routes_explored += result.routes_explored;
+                        data_quality.add(&result.data_quality);
+                        continuations.extend(result.continuation);
+                        (result.journeys.into_iter().next(), false)
+                    }
+                    Err(e) => {
+                        warn!(service = %service.service_ref.darwin_id, error = %e, "Search from board departure failed");
+                        (None, true)
+                    }
+                };
+                BoardDeparture {
+                    service,
+                    position,
+                    departure,
+                    journey,
+                    rank: None,
+                    unavailable,
+                }
+            })
+            .collect();
+        self.rank_departures(&mut departures, request.from);
+
+        let continuation = if departures.iter().any(BoardDeparture::reaches_destination) {
+            None
+        } else {
+            self.closest_continuation(continuations)
+        };
+
+        Ok(BoardResult {
+            departures,
+            routes_explored,
+            data_quality,
+            continuation,
+        })
+    }
+
+    /// Rank the departures reaching the destination by weighted duration
+    /// from `now`, then fewest changes, then earliest departure.
+    fn rank_departures(&self, departures: &mut [BoardDeparture], now: RailTime) {
+        let mut reaching: Vec<(usize, &Journey)> = departures
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| Some((i, d.journey.as_ref()?)))
+            .collect();
+        reaching.sort_by_key(|(_, journey)| {
+            (
+                weighted_duration_from(journey, now, self.config),
+                journey.change_count(),
+                journey.departure_time(),
+            )
+        });
+        let order: Vec<usize> = reaching.into_iter().map(|(i, _)| i).collect();
+        for (rank, i) in order.into_iter().enumerate() {
+            departures[i].rank = Some(rank);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Limits on how much board data a search holds.
+//!
+//! The 2-change and BFS phases keep every departure board they fetch for
+//! the rest of the search, and each service holds all its calling points.
+//! A misbehaving provider, or an enormous terminus board, could make that
+//! grow without bound, so each board is cut to `config.max_board_services`
+//! and the search as a whole to `config.max_held_calls` calling points.
+//! Boards are cut to their soonest departures, which are the ones most
+//! journeys use, and stations whose boards were cut are recorded in the
+//! search's [`DataQuality`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::debug;
+
+use super::config::SearchConfig;
+use super::quality::DataQuality;
+use crate::domain::{Crs, Service};
+
+/// How much more board data a search may hold.
+#[derive(Debug, Clone)]
+pub(super) struct Budget {
+    /// Most services kept from one board, if limited
+    board_services: Option<usize>,
+    /// Calling points that may still be held, if limited
+    calls_left: Option<usize>,
+}
+
+impl Budget {
+    /// The budget left for a search already holding the boards in `cache`.
+    pub(super) fn new(config: &SearchConfig, cache: &HashMap<Crs, Vec<Arc<Service>>>) -> Self {
+        let held: usize = cache.values().flatten().map(|s| s.calls.len()).sum();
+        Self {
+            board_services: config.board_service_limit(),
+            calls_left: config.held_calls_limit().map(|l| l.saturating_sub(held)),
+        }
+    }
+
+    /// Cut `station`'s departures to fit the budget, keeping the soonest,
+    /// and take what's kept from the budget.
+    pub(super) fn admit(
+        &mut self,
+        station: Crs,
+        mut services: Vec<Arc<Service>>,
+        quality: &mut DataQuality,
+    ) -> Vec<Arc<Service>> {
+        let total_calls: usize = services.iter().map(|s| s.calls.len()).sum();
+        let fits = self.board_services.is_none_or(|l| services.len() <= l)
+            && self.calls_left.is_none_or(|l| total_calls <= l);
+        if !fits {
+            let offered = services.len();
+            services.sort_by_key(|s| {
+                let departure = s
+                    .board_call_at(&station)
+                    .and_then(|(_, call)| call.expected_departure());
+                (departure.is_none(), departure)
+            });
+            services.truncate(self.board_services.unwrap_or(usize::MAX));
+            if let Some(left) = self.calls_left {
+                let mut calls = 0;
+                let kept = services
+                    .iter()
+                    .take_while(|s| {
+                        calls += s.calls.len();
+                        calls <= left
+                    })
+                    .count();
+                services.truncate(kept);
+            }
+            debug!(
+                station = %station.as_str(),
+                offered,
+                kept = services.len(),
+                "Departure board cut to fit the search's budget"
+            );
+            quality.record_truncated(station);
+        }
+
+        if let Some(left) = &mut self.calls_left {
+            *left -= services.iter().map(|s| s.calls.len()).sum::<usize>();
+        }
+        services
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ServiceBuilder, crs};
+
+    /// A service leaving RDG at `departs`, calling at `stops` stations.
+    fn service(id: &str, departs: &str, stops: usize) -> Arc<Service> {
+        (1..stops)
+            .fold(
+                ServiceBuilder::new(id).call("RDG").dep(departs),
+                |service, _| service.call("PAD"),
+            )
+            .build()
+    }
+
+    fn ids(services: &[Arc<Service>]) -> Vec<&str> {
+        services
+            .iter()
+            .map(|s| s.service_ref.darwin_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn boards_within_budget_are_kept_whole() {
+        let config = SearchConfig::default();
+        let mut budget = Budget::new(&config, &HashMap::new());
+        let mut quality = DataQuality::default();
+        let board = vec![service("LATER", "10:30", 5), service("SOON", "10:00", 5)];
+
+        let kept = budget.admit(crs("RDG"), board, &mut quality);
+
+        // Board order is left alone when nothing is cut
+        assert_eq!(ids(&kept), ["LATER", "SOON"]);
+        assert!(quality.is_complete());
+    }
+
+    #[test]
+    fn large_boards_keep_their_soonest_services() {
+        let config = SearchConfig {
+            max_board_services: 2,
+            ..SearchConfig::default()
+        };
+        let mut budget = Budget::new(&config, &HashMap::new());
+        let mut quality = DataQuality::default();
+        let board = vec![
+            service("LAST", "11:00", 3),
+            service("FIRST", "10:00", 3),
+            service("SECOND", "10:15", 3),
+        ];
+
+        let kept = budget.admit(crs("RDG"), board, &mut quality);
+
+        assert_eq!(ids(&kept), ["FIRST", "SECOND"]);
+        assert!(quality.truncated_stations.contains(&crs("RDG")));
+    }
+
+    #[test]
+    fn calls_held_across_boards_are_limited() {
+        let config = SearchConfig {
+            max_held_calls: 25,
+            ..SearchConfig::default()
+        };
+        let cache = HashMap::from([(crs("SWI"), vec![service("HELD", "09:00", 10)])]);
+        let mut budget = Budget::new(&config, &cache);
+        let mut quality = DataQuality::default();
+
+        let board = vec![service("B", "10:10", 5), service("A", "10:00", 5)];
+        let kept = budget.admit(crs("RDG"), board, &mut quality);
+        assert_eq!(ids(&kept), ["B", "A"]);
+
+        // Only 5 calls left, so only the soonest of these fits
+        let board = vec![service("D", "10:40", 3), service("C", "10:20", 3)];
+        let kept = budget.admit(crs("RDG"), board, &mut quality);
+        assert_eq!(ids(&kept), ["C"]);
+        assert!(!quality.is_complete());
+
+        let kept = budget.admit(crs("RDG"), vec![service("E", "10:50", 3)], &mut quality);
+        assert!(kept.is_empty());
+    }
+}
@@ -0,0 +1,501 @@
+//! How far a planned journey can be relied on.
+//!
+//! Every journey the planner returns works on the latest data, but some
+//! are safer bets than others: a change with a couple of minutes to spare
+//! is easily missed, a train already running late may lose more time, and
+//! a train with cancelled stops is running a disrupted service, and a train
+//! that only calls on request may run through early. [`assess`]
+//! is applied after the search and combines these signals into a single
+//! [`Confidence`] per journey, with the reasons for it, so that clients can
+//! colour-code options without modelling the risks themselves. Given a
+//! [`PerformanceHistory`], [`assess_with_history`] also weighs how often the
+//! train being changed from has arrived too late for the change on past
+//! days.
+
+use chrono::Duration;
+
+use crate::domain::{Change, Crs, Journey, Leg};
+
+use super::config::SearchConfig;
+use super::performance::PerformanceHistory;
+
+/// A train delayed by at least this many minutes lowers confidence to
+/// medium.
+const DELAYED_MINS: i64 = 5;
+
+/// A train delayed by at least this many minutes lowers confidence to low.
+const SEVERELY_DELAYED_MINS: i64 = 30;
+
+/// Days a train must have been recorded on before its history counts.
+const MIN_HISTORY_DAYS: usize = 5;
+
+/// A change missed on at least this share of recorded days lowers
+/// confidence to medium.
+const OFTEN_MISSED: f64 = 0.2;
+
+/// A change missed on at least this share of recorded days lowers
+/// confidence to low.
+const USUALLY_MISSED: f64 = 0.5;
+
+/// How far a journey can be relied on, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Nothing suggests the journey won't work
+    High,
+
+    /// The journey should work, but something could go wrong
+    Medium,
+
+    /// The journey is unlikely to work as planned
+    Low,
+}
+
+impl Confidence {
+    /// Short identifier, as used in the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Medium => "medium",
+            Self::Low => "low",
+        }
+    }
+}
+
+/// The kind of signal that lowered a journey's confidence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfidenceSignal {
+    /// A change leaves little or no time to spare
+    Connection,
+
+    /// A train is running late
+    Delay,
+
+    /// A train, or some of its stops, is cancelled
+    Cancellation,
+
+    /// A change relies on a train calling at a request stop
+    RequestStop,
+
+    /// The train being changed from has often arrived too late for the
+    /// change on past days
+    Punctuality,
+}
+
+/// Something that lowered a journey's confidence, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfidenceReason {
+    /// The kind of signal
+    pub signal: ConfidenceSignal,
+
+    /// The station it applies to: the change station, or where the train
+    /// is boarded
+    pub station: Crs,
+
+    /// How far the journey can be relied on, given this alone
+    pub confidence: Confidence,
+
+    /// Human-readable description
+    pub message: String,
+}
+
+/// A journey's overall confidence, and the reasons for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JourneyConfidence {
+    /// The lowest confidence of any reason, or high if there are none
+    pub confidence: Confidence,
+
+    /// The reasons, in journey order
+    pub reasons: Vec<ConfidenceReason>,
+}
+
+/// Assess how far a journey can be relied on.
+///
+/// Changes are judged against the minimum connection time in `config`: one
+/// leaving less is low confidence, and one leaving less than twice it is
+/// medium. A change getting off or on at a request stop is medium, since
+/// times there are unreliable.
+pub fn assess(journey: &Journey, config: &SearchConfig) -> JourneyConfidence {
+    assess_with_history(journey, config, None)
+}
+
+/// Assess how far a journey can be relied on, as [`assess`], also judging
+/// each change by how often its incoming train has arrived too late for it
+/// on the days in `history`.
+///
+/// A change missed on a fifth of recorded days is medium confidence, and
+/// one missed on half of them low. Trains recorded on fewer than five days
+/// are not judged.
+pub fn assess_with_history(
+    journey: &Journey,
+    config: &SearchConfig,
+    history: Option<&PerformanceHistory>,
+) -> JourneyConfidence {
+    let mut reasons = Vec::new();
+    let mut changes = journey.changes();
+    for (i, leg) in journey.legs().enumerate() {
+        if i > 0
+            && let Some(change) = changes.next()
+        {
+            reasons.extend(connection_risk(
+                change.slack(),
+                *change.to.board_station(),
+                change.to.board_station_name(),
+                config.min_connection(),
+            ));
+            reasons.extend(request_stops(&change));
+            if let Some(history) = history {
+                reasons.extend(punctuality(&change, history, config.min_connection()));
+            }
+        }
+        reasons.extend(cancellation(leg));
+        reasons.extend(delay(leg));
+    }
+
+    let confidence = reasons
+        .iter()
+        .map(|r| r.confidence)
+        .max()
+        .unwrap_or(Confidence::High);
+    JourneyConfidence {
+        confidence,
+        reasons,
+    }
+}
+
+fn connection_risk(
+    slack: Duration,
+    station: Crs,
+    station_name: &str,
+    min_connection: Duration,
+) -> Option<ConfidenceReason> {
+    let confidence = if slack < min_connection {
+        Confidence::Low
+    } else if slack < min_connection * 2 {
+        Confidence::Medium
+    } else {
+        return None;
+    };
+    Some(ConfidenceReason {
+        signal: ConfidenceSignal::Connection,
+        station,
+        confidence,
+        message: format!(
+            "Only {} min to change at {station_name}",
+            slack.num_minutes().max(0)
+        ),
+    })
+}
+
+fn request_stops(change: &Change) -> Vec<ConfidenceReason> {
+    let mut reasons = Vec::new();
+    if change.from.alight_call().is_request_stop {
+        reasons.push(request_stop(
+            change.from.alight_station(),
+            "tell the guard you want to get off",
+            change.from.alight_station_name(),
+        ));
+    }
+    if change.to.board_call().is_request_stop {
+        reasons.push(request_stop(
+            change.to.board_station(),
+            "signal the driver, and be there early in case the train is",
+            change.to.board_station_name(),
+        ));
+    }
+    reasons
+}
+
+fn request_stop(station: &Crs, advice: &str, station_name: &str) -> ConfidenceReason {
+    ConfidenceReason {
+        signal: ConfidenceSignal::RequestStop,
+        station: *station,
+        confidence: Confidence::Medium,
+        message: format!("{station_name} is a request stop: {advice}"),
+    }
+}
+
+fn punctuality(
+    change: &Change,
+    history: &PerformanceHistory,
+    min_connection: Duration,
+) -> Option<ConfidenceReason> {
+    let arriving = change.from;
+    let station = arriving.alight_station();
+    let record = history.punctuality(arriving.service(), station)?;
+    if record.days() < MIN_HISTORY_DAYS {
+        return None;
+    }
+
+    // Past delays are against the timetable, so the change is judged on
+    // its booked times
+    let booked_arrival = arriving.alight_call().booked_arrival()?;
+    let booked_departure = change.to.board_call().booked_departure()?;
+    let walk = change.walk.map_or(Duration::zero(), |w| w.duration);
+    let spare = booked_departure.signed_duration_since(booked_arrival) - walk - min_connection;
+    let missed = record.share_later_than(spare.max(Duration::zero()));
+    let confidence = if missed >= USUALLY_MISSED {
+        Confidence::Low
+    } else if missed >= OFTEN_MISSED {
+        Confidence::Medium
+    } else {
+        return None;
+    };
+    Some(ConfidenceReason {
+        signal: ConfidenceSignal::Punctuality,
+        station: *station,
+        confidence,
+        message: format!(
+            "{} arrives too late to change at {} on {:.0}% of days",
+            train_name(arriving),
+            arriving.alight_station_name(),
+            missed * 100.0
+        ),
+    })
+}
+
+fn cancellation(leg: &Leg) -> Option<ConfidenceReason> {
+    let (confidence, message) = if leg.is_cancelled() {
+        (Confidence::Low, "is cancelled")
+    } else if leg.calls().iter().any(|c| c.is_cancelled) {
+        (Confidence::Medium, "has cancelled stops")
+    } else {
+        return None;
+    };
+    Some(ConfidenceReason {
+        signal: ConfidenceSignal::Cancellation,
+        station: *leg.board_station(),
+        confidence,
+        message: format!("{} {message}", train_name(leg)),
+    })
+}
+
+fn delay(leg: &Leg) -> Option<ConfidenceReason> {
+    let delay = [
+        leg.board_call().departure_delay(),
+        leg.alight_call().arrival_delay(),
+    ]
+    .into_iter()
+    .flatten()
+    .max()?;
+    let confidence = if delay >= Duration::minutes(SEVERELY_DELAYED_MINS) {
+        Confidence::Low
+    } else if delay >= Duration::minutes(DELAYED_MINS) {
+        Confidence::Medium
+    } else {
+        return None;
+    };
+    Some(ConfidenceReason {
+        signal: ConfidenceSignal::Delay,
+        station: *leg.board_station(),
+        confidence,
+        message: format!(
+            "{} is running {} min late",
+            train_name(leg),
+            delay.num_minutes()
+        ),
+    })
+}
+
+/// How a leg's train is described, e.g. "The 14:15 from London Paddington".
+fn train_name(leg: &Leg) -> String {
+    let departs = leg
+        .board_call()
+        .booked_departure()
+        .unwrap_or_else(|| leg.departure_time());
+    format!("The {departs} from {}", leg.board_station_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, RailTime, Segment, Service, ServiceRef};
+    use crate::testing::ServiceBuilder;
+    use chrono::NaiveDate;
+    use std::sync::Arc;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    /// A leg calling at each `(station, name, time)`, all on time.
+    fn leg(id: &str, calls: &[(&str, &str, &str)]) -> Leg {
+        let calls: Vec<Call> = calls
+            .iter()
+            .map(|(station, name, t)| {
+                let mut call = Call::new(crs(station), (*name).to_string());
+                call.booked_arrival = Some(time(t));
+                call.booked_departure = Some(time(t));
+                call
+            })
+            .collect();
+        let last = CallIndex(calls.len() - 1);
+        let service = Service {
+            service_ref: ServiceRef::new(id.into(), calls[0].station),
+            headcode: None,
+            operator: "Test".into(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        };
+        Leg::new(Arc::new(service), CallIndex(0), last).unwrap()
+    }
+
+    fn journey(legs: Vec<Leg>) -> Journey {
+        Journey::new_allowing_missed(legs.into_iter().map(Segment::Train).collect()).unwrap()
+    }
+
+    fn config() -> SearchConfig {
+        SearchConfig {
+            min_connection_mins: 5,
+            ..SearchConfig::default()
+        }
+    }
+
+    /// Paddington to Reading, then Reading to Swindon departing at `departs`.
+    fn change_at_reading(departs: &str) -> Journey {
+        journey(vec![
+            leg(
+                "S1",
+                &[
+                    ("PAD", "London Paddington", "10:00"),
+                    ("RDG", "Reading", "10:25"),
+                ],
+            ),
+            leg(
+                "S2",
+                &[("RDG", "Reading", departs), ("SWI", "Swindon", "11:05")],
+            ),
+        ])
+    }
+
+    #[test]
+    fn comfortable_journey_is_high() {
+        let assessed = assess(&change_at_reading("10:40"), &config());
+        assert_eq!(assessed.confidence, Confidence::High);
+        assert!(assessed.reasons.is_empty());
+    }
+
+    #[test]
+    fn tight_connections_lower_confidence() {
+        let tight = assess(&change_at_reading("10:32"), &config());
+        assert_eq!(tight.confidence, Confidence::Medium);
+        assert_eq!(tight.reasons[0].signal, ConfidenceSignal::Connection);
+        assert_eq!(tight.reasons[0].station, crs("RDG"));
+        assert_eq!(tight.reasons[0].message, "Only 7 min to change at Reading");
+
+        let missed = assess(&change_at_reading("10:27"), &config());
+        assert_eq!(missed.confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn delays_and_cancellations_lower_confidence() {
+        let mut late = leg(
+            "S1",
+            &[
+                ("PAD", "London Paddington", "10:00"),
+                ("RDG", "Reading", "10:25"),
+            ],
+        );
+        let mut service = (**late.service()).clone();
+        service.calls[0].realtime_departure = Some(time("10:12"));
+        service.calls[1].realtime_arrival = Some(time("10:37"));
+        late = Leg::new(Arc::new(service.clone()), CallIndex(0), CallIndex(1)).unwrap();
+
+        let assessed = assess(&journey(vec![late]), &config());
+        assert_eq!(assessed.confidence, Confidence::Medium);
+        assert_eq!(
+            assessed.reasons[0].message,
+            "The 10:00 from London Paddington is running 12 min late"
+        );
+
+        service.calls[1].is_cancelled = true;
+        let cancelled = Leg::new(Arc::new(service), CallIndex(0), CallIndex(1)).unwrap();
+        let assessed = assess(&journey(vec![cancelled]), &config());
+        assert_eq!(assessed.confidence, Confidence::Low);
+        assert_eq!(assessed.reasons[0].signal, ConfidenceSignal::Cancellation);
+        assert_eq!(assessed.reasons.len(), 2);
+    }
+
+    #[test]
+    fn often_late_feeders_lower_confidence() {
+        // Booked 15 min at Reading, so the change needs the first train
+        // no more than 10 min late
+        let journey = change_at_reading("10:40");
+        let history = PerformanceHistory::new();
+        let record = |day: u32, late: i64| {
+            let arrived = time("10:25") + Duration::minutes(late);
+            let arriving = ServiceBuilder::new("S1")
+                .on(NaiveDate::from_ymd_opt(2024, 3, day).unwrap())
+                .call("PAD")
+                .named("London Paddington")
+                .dep("10:00")
+                .call("RDG")
+                .named("Reading")
+                .arr("10:25")
+                .expected_arr(&arrived.to_string())
+                .build();
+            history.record(&arriving);
+        };
+
+        for (day, late) in [(1, 0), (4, 15), (5, 2), (6, 0)] {
+            record(day, late);
+        }
+        // Too few days to go on
+        let assessed = assess_with_history(&journey, &config(), Some(&history));
+        assert_eq!(assessed.confidence, Confidence::High);
+
+        record(7, 12);
+        let assessed = assess_with_history(&journey, &config(), Some(&history));
+        assert_eq!(assessed.confidence, Confidence::Medium);
+        assert_eq!(assessed.reasons[0].signal, ConfidenceSignal::Punctuality);
+        assert_eq!(
+            assessed.reasons[0].message,
+            "The 10:00 from London Paddington arrives too late to change at Reading on 40% of days"
+        );
+
+        for day in 8..12 {
+            record(day, 11);
+        }
+        let assessed = assess_with_history(&journey, &config(), Some(&history));
+        assert_eq!(assessed.confidence, Confidence::Low);
+
+        // Without the history, only today counts
+        assert_eq!(assess(&journey, &config()).confidence, Confidence::High);
+    }
+
+    #[test]
+    fn changing_at_a_request_stop_is_medium() {
+        let mut arriving = leg(
+            "S1",
+            &[
+                ("PAD", "London Paddington", "10:00"),
+                ("RDG", "Reading", "10:25"),
+            ],
+        );
+        let mut service = (**arriving.service()).clone();
+        service.calls[1].is_request_stop = true;
+        arriving = Leg::new(Arc::new(service), CallIndex(0), CallIndex(1)).unwrap();
+        let journey = journey(vec![
+            arriving,
+            leg(
+                "S2",
+                &[("RDG", "Reading", "10:40"), ("SWI", "Swindon", "11:05")],
+            ),
+        ]);
+
+        let assessed = assess(&journey, &config());
+        assert_eq!(assessed.confidence, Confidence::Medium);
+        assert_eq!(assessed.reasons.len(), 1);
+        assert_eq!(assessed.reasons[0].signal, ConfidenceSignal::RequestStop);
+        assert_eq!(assessed.reasons[0].station, crs("RDG"));
+        assert_eq!(
+            assessed.reasons[0].message,
+            "Reading is a request stop: tell the guard you want to get off"
+        );
+    }
+}
@@ -1,7 +1,128 @@
 //! Search configuration for the journey planner.
 
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
 use chrono::Duration;
 
+use crate::domain::Journey;
+use crate::fares::{EstimatedFares, FareProvider};
+
+/// A way one journey can be better than another, compared when removing
+/// dominated journeys (see [`remove_dominated`](super::remove_dominated)).
+///
+/// Arrival time is always compared; these are the other dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DominanceCriterion {
+    /// Fewer changes
+    Changes,
+
+    /// Shorter time from first departure to arrival
+    Duration,
+
+    /// Less time walking between stations
+    Walking,
+
+    /// A lower price band, when ranking by price (`price_weight_mins`)
+    Price,
+}
+
+impl DominanceCriterion {
+    /// All criteria.
+    pub const ALL: [DominanceCriterion; 4] = [
+        DominanceCriterion::Changes,
+        DominanceCriterion::Duration,
+        DominanceCriterion::Walking,
+        DominanceCriterion::Price,
+    ];
+
+    /// Name used in configuration and requests.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DominanceCriterion::Changes => "changes",
+            DominanceCriterion::Duration => "duration",
+            DominanceCriterion::Walking => "walking",
+            DominanceCriterion::Price => "price",
+        }
+    }
+}
+
+/// The dimensions a journey must be no worse on than another, as well as
+/// arrival time, to hide it. Adding one keeps journeys that are better
+/// only on it, e.g. walking for users who would rather not walk between
+/// stations; dropping one lets them be hidden.
+///
+/// Written as a comma-separated list of names, e.g.
+/// `changes,duration,price`; an empty list compares arrival time alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DominanceCriteria(BTreeSet<DominanceCriterion>);
+
+impl DominanceCriteria {
+    /// Compare on arrival time alone.
+    pub fn arrival_only() -> Self {
+        Self(BTreeSet::new())
+    }
+
+    /// Whether journeys are compared on `criterion`.
+    pub fn contains(&self, criterion: DominanceCriterion) -> bool {
+        self.0.contains(&criterion)
+    }
+
+    /// Also compare on `criterion`.
+    pub fn with(mut self, criterion: DominanceCriterion) -> Self {
+        self.0.insert(criterion);
+        self
+    }
+
+    /// Stop comparing on `criterion`.
+    pub fn without(mut self, criterion: DominanceCriterion) -> Self {
+        self.0.remove(&criterion);
+        self
+    }
+}
+
+impl Default for DominanceCriteria {
+    /// Changes, duration and price. Walking is opted into.
+    fn default() -> Self {
+        Self::arrival_only()
+            .with(DominanceCriterion::Changes)
+            .with(DominanceCriterion::Duration)
+            .with(DominanceCriterion::Price)
+    }
+}
+
+impl fmt::Display for DominanceCriteria {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.0.iter().map(|c| c.as_str()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+impl FromStr for DominanceCriteria {
+    type Err = UnknownCriterion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                DominanceCriterion::ALL
+                    .into_iter()
+                    .find(|c| c.as_str().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| UnknownCriterion(name.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// A dominance criterion that doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown dominance criterion {0:?}; expected changes, duration, walking or price")]
+pub struct UnknownCriterion(pub String);
+
 /// Configuration parameters for journey search.
 #[derive(Debug, Clone)]
 pub struct SearchConfig {
@@ -18,6 +139,11 @@ pub struct SearchConfig {
     /// Connections tighter than this are rejected.
     pub min_connection_mins: i64,
 
+    /// Extra minutes a change needs on top of `min_connection_mins` when it
+    /// means crossing the footbridge to another platform, where the
+    /// planner is given a [`PlatformTopology`](crate::stations::PlatformTopology).
+    pub footbridge_connection_mins: i64,
+
     /// Maximum walking time to consider (minutes).
     /// Walks longer than this are not suggested.
     pub max_walk_mins: i64,
@@ -29,6 +155,117 @@ pub struct SearchConfig {
     /// Maximum number of states to batch for parallel departure fetching.
     /// Higher values increase parallelism but may do redundant work.
     pub batch_size: usize,
+
+    /// Largest gap between departures (minutes) for a service to count as
+    /// turn-up-and-go. Equivalent journeys on such a service are collapsed
+    /// into one. Zero disables collapsing.
+    pub frequent_max_headway_mins: i64,
+
+    /// Minimum number of equivalent journeys needed before they are
+    /// collapsed.
+    pub frequent_min_departures: usize,
+
+    /// Whether the BFS fallback prunes states using learned travel times.
+    pub travel_time_pruning: bool,
+
+    /// Minutes taken off learned travel times before pruning with them, in
+    /// case a faster train hasn't been observed yet.
+    pub travel_time_margin_mins: i64,
+
+    /// Whether to drop journeys that break the approximate routeing rules
+    /// (see [`RouteingRules`](super::RouteingRules)) rather than just flag them.
+    pub reject_invalid_routeing: bool,
+
+    /// Most journeys making their first change at the same station that are
+    /// ranked ahead of the rest. Further ones are moved below the others, so
+    /// the top results aren't all variations through one interchange. Zero
+    /// disables the limit.
+    pub max_per_interchange: usize,
+
+    /// Most minutes later a journey may arrive and still be ranked ahead of
+    /// one that leaves the same train earlier, so passengers aren't told to
+    /// change sooner than they need to. Each minute longer on the train is
+    /// worth a minute of arrival time, up to this limit. Zero disables the
+    /// preference.
+    pub stay_on_mins: i64,
+
+    /// Extra weight on waiting for the first train when ranking departures
+    /// from a station, as a percentage of the wait: at 50, each minute on
+    /// the platform counts as a minute and a half of journey time. Zero
+    /// ranks on time to arrival alone.
+    pub wait_weight_pct: i64,
+
+    /// Most departure boards fetched at once within a batch. Darwin enforces
+    /// per-second burst limits, so this keeps a large `batch_size` from
+    /// firing every request together. Zero fetches the whole batch at once.
+    pub fetch_concurrency: usize,
+
+    /// Most searches from one departure board run at once (see
+    /// [`Planner::search_board`](super::Planner::search_board)). Each is a
+    /// full search, so this keeps one board request from fetching as much
+    /// as a dozen searches together. Zero runs them all at once.
+    pub board_concurrency: usize,
+
+    /// Pause between batches of departure fetches (milliseconds), plus up
+    /// to half as much again at random. Zero fetches batches back to back.
+    pub batch_delay_ms: u64,
+
+    /// Seed for the jitter added to `batch_delay_ms`, so a search's pacing
+    /// can be replayed exactly. Zero jitters at random.
+    pub jitter_seed: u64,
+
+    /// How long (seconds) a destination's arrivals board is reused by the
+    /// same user's later searches, where the planner is given their
+    /// [`RecentArrivals`](super::RecentArrivals). Zero always fetches it.
+    pub arrivals_reuse_secs: u64,
+
+    /// Most further pages of the destination's arrivals board fetched when
+    /// the first stops short of the earliest the user could get there (by
+    /// the planner's [`TravelTimes`](super::TravelTimes)), so long-distance
+    /// searches aren't left without feeders. Zero fetches one page only.
+    pub max_arrivals_pages: usize,
+
+    /// Minutes of arrival time each step up in price band is worth when
+    /// ranking, so budget-conscious users see cheaper journeys above
+    /// slightly faster ones. Zero ranks without regard to price.
+    pub price_weight_mins: i64,
+
+    /// Journeys the BFS fallback looks for before stopping early: once this
+    /// many found so far arrive within `bfs_arrival_margin_mins` of the
+    /// earliest, no further levels (more changes) are explored. Zero
+    /// explores every level.
+    pub bfs_target_journeys: usize,
+
+    /// How much later than the earliest arrival (minutes) a journey may
+    /// arrive and still count towards `bfs_target_journeys`.
+    pub bfs_arrival_margin_mins: i64,
+
+    /// Most services kept from one departure board during a search; later
+    /// departures beyond this are dropped. Zero keeps every service.
+    pub max_board_services: usize,
+
+    /// Most calling points a search holds across all the departure boards
+    /// it fetches, so an enormous board can't balloon memory. Boards that
+    /// don't fit are cut to their soonest services. Zero is unlimited.
+    pub max_held_calls: usize,
+
+    /// Whether to leave out walks needing a lift at a station where lifts
+    /// are reported out of service, rather than just warn about them (see
+    /// [`LiftOutages`](crate::walkable::LiftOutages)).
+    pub avoid_lift_outages: bool,
+
+    /// Whether journeys to a station group that take the same trains and
+    /// only finish differently (leaving the last train at another station,
+    /// or walking from it) are combined into the quickest, listing the
+    /// others as its alternative endings (see [`super::combine_endings`]).
+    pub combine_group_endings: bool,
+
+    /// What, besides arrival time, a journey must be no worse on than
+    /// another to hide it (see [`DominanceCriteria`]).
+    pub dominance: DominanceCriteria,
+
+    /// Where journeys' price bands come from.
+    pub fares: Arc<dyn FareProvider>,
 }
 
 impl SearchConfig {
@@ -50,6 +287,7 @@ impl SearchConfig {
             max_walk_mins,
             max_journey_mins,
             batch_size,
+            ..Self::default()
         }
     }
 
@@ -63,6 +301,11 @@ impl SearchConfig {
         Duration::minutes(self.min_connection_mins)
     }
 
+    /// Returns the minimum time for a change over the footbridge.
+    pub fn footbridge_connection(&self) -> Duration {
+        Duration::minutes(self.min_connection_mins + self.footbridge_connection_mins.max(0))
+    }
+
     /// Returns the maximum walk time as a Duration.
     pub fn max_walk(&self) -> Duration {
         Duration::minutes(self.max_walk_mins)
@@ -72,6 +315,108 @@ impl SearchConfig {
     pub fn max_journey(&self) -> Duration {
         Duration::minutes(self.max_journey_mins)
     }
+
+    /// Returns the largest turn-up-and-go headway, or `None` if collapsing
+    /// is disabled.
+    pub fn frequent_max_headway(&self) -> Option<Duration> {
+        (self.frequent_max_headway_mins > 0)
+            .then(|| Duration::minutes(self.frequent_max_headway_mins))
+    }
+
+    /// Returns how much is taken off learned travel times before pruning.
+    pub fn travel_time_margin(&self) -> Duration {
+        Duration::minutes(self.travel_time_margin_mins.max(0))
+    }
+
+    /// Returns the per-interchange limit on top results, or `None` if
+    /// diversity ranking is disabled.
+    pub fn interchange_limit(&self) -> Option<usize> {
+        (self.max_per_interchange > 0).then_some(self.max_per_interchange)
+    }
+
+    /// Returns the most a journey's arrival is excused for staying longer
+    /// on its first train, or `None` if the preference is disabled.
+    pub fn stay_on_allowance(&self) -> Option<Duration> {
+        (self.stay_on_mins > 0).then(|| Duration::minutes(self.stay_on_mins))
+    }
+
+    /// Returns the extra weight given to a wait for the first train.
+    pub fn wait_penalty(&self, wait: Duration) -> Duration {
+        wait * self.wait_weight_pct.max(0) as i32 / 100
+    }
+
+    /// Returns the most departure boards to fetch at once, or `None` if
+    /// each batch is fetched all at once.
+    pub fn fetch_concurrency_limit(&self) -> Option<usize> {
+        (self.fetch_concurrency > 0).then_some(self.fetch_concurrency)
+    }
+
+    /// Returns the most searches to run at once from one board, or `None`
+    /// if they all run together.
+    pub fn board_concurrency_limit(&self) -> Option<usize> {
+        (self.board_concurrency > 0).then_some(self.board_concurrency)
+    }
+
+    /// Returns how long an arrivals board may be reused, or `None` if
+    /// reuse is disabled.
+    pub fn arrivals_reuse(&self) -> Option<std::time::Duration> {
+        (self.arrivals_reuse_secs > 0)
+            .then(|| std::time::Duration::from_secs(self.arrivals_reuse_secs))
+    }
+
+    /// Returns the most further pages of an arrivals board to fetch, or
+    /// `None` if paging is disabled.
+    pub fn arrivals_paging(&self) -> Option<usize> {
+        (self.max_arrivals_pages > 0).then_some(self.max_arrivals_pages)
+    }
+
+    /// Returns how much later a journey is ranked for its price band, or
+    /// zero if price-aware ranking is disabled or it can't be priced.
+    pub fn price_penalty(&self, journey: &Journey) -> Duration {
+        if self.price_weight_mins <= 0 {
+            return Duration::zero();
+        }
+        self.fares
+            .price_band(journey)
+            .map_or(Duration::zero(), |band| {
+                Duration::minutes(self.price_weight_mins) * band.steps_above_low()
+            })
+    }
+
+    /// Returns how many journeys close to the earliest arrival let the BFS
+    /// fallback stop early, and how close they must be, or `None` if it
+    /// explores every level.
+    pub fn bfs_early_exit(&self) -> Option<(usize, Duration)> {
+        (self.bfs_target_journeys > 0).then(|| {
+            (
+                self.bfs_target_journeys,
+                Duration::minutes(self.bfs_arrival_margin_mins.max(0)),
+            )
+        })
+    }
+
+    /// Returns the most services kept from one board, or `None` if boards
+    /// are kept whole.
+    pub fn board_service_limit(&self) -> Option<usize> {
+        (self.max_board_services > 0).then_some(self.max_board_services)
+    }
+
+    /// Returns the most calling points a search holds, or `None` if
+    /// unlimited.
+    pub fn held_calls_limit(&self) -> Option<usize> {
+        (self.max_held_calls > 0).then_some(self.max_held_calls)
+    }
+
+    /// Returns the pause between batches of departure fetches, before
+    /// jitter, or `None` if pacing is disabled.
+    pub fn batch_delay(&self) -> Option<std::time::Duration> {
+        (self.batch_delay_ms > 0).then(|| std::time::Duration::from_millis(self.batch_delay_ms))
+    }
+
+    /// Returns the seed for pacing jitter, or `None` if it's random.
+    pub fn jitter_seed(&self) -> Option<u64> {
+        (self.jitter_seed > 0).then_some(self.jitter_seed)
+    }
 }
 
 impl Default for SearchConfig {
@@ -81,9 +426,34 @@ impl Default for SearchConfig {
             max_results: 10,
             time_window_mins: 120, // 2 hours
             min_connection_mins: 5,
+            footbridge_connection_mins: 3,
             max_walk_mins: 15,
             max_journey_mins: 360, // 6 hours
             batch_size: 8,
+            frequent_max_headway_mins: 15,
+            frequent_min_departures: 3,
+            travel_time_pruning: true,
+            travel_time_margin_mins: 10,
+            reject_invalid_routeing: false,
+            max_per_interchange: 3,
+            stay_on_mins: 3,
+            wait_weight_pct: 50,
+            fetch_concurrency: 4,
+            board_concurrency: 3,
+            batch_delay_ms: 0,
+            jitter_seed: 0,
+            arrivals_reuse_secs: 90,
+            max_arrivals_pages: 3,
+            price_weight_mins: 0,
+            bfs_target_journeys: 0,
+            bfs_arrival_margin_mins: 30,
+            // Darwin's own maximum rows per board
+            max_board_services: 150,
+            max_held_calls: 200_000,
+            avoid_lift_outages: false,
+            combine_group_endings: true,
+            dominance: DominanceCriteria::default(),
+            fares: Arc::new(EstimatedFares),
         }
     }
 }
@@ -100,9 +470,50 @@ mod tests {
         assert_eq!(config.max_results, 10);
         assert_eq!(config.time_window_mins, 120);
         assert_eq!(config.min_connection_mins, 5);
+        assert_eq!(config.footbridge_connection_mins, 3);
         assert_eq!(config.max_walk_mins, 15);
         assert_eq!(config.max_journey_mins, 360);
         assert_eq!(config.batch_size, 8);
+        assert_eq!(config.frequent_max_headway_mins, 15);
+        assert_eq!(config.frequent_min_departures, 3);
+        assert!(config.travel_time_pruning);
+        assert_eq!(config.travel_time_margin_mins, 10);
+        assert!(!config.reject_invalid_routeing);
+        assert_eq!(config.max_per_interchange, 3);
+        assert_eq!(config.stay_on_mins, 3);
+        assert_eq!(config.wait_weight_pct, 50);
+        assert_eq!(config.fetch_concurrency, 4);
+        assert_eq!(config.board_concurrency, 3);
+        assert_eq!(config.batch_delay_ms, 0);
+        assert_eq!(config.jitter_seed, 0);
+        assert_eq!(config.arrivals_reuse_secs, 90);
+        assert_eq!(config.max_arrivals_pages, 3);
+        assert_eq!(config.price_weight_mins, 0);
+        assert_eq!(config.bfs_target_journeys, 0);
+        assert_eq!(config.bfs_arrival_margin_mins, 30);
+        assert_eq!(config.max_board_services, 150);
+        assert_eq!(config.max_held_calls, 200_000);
+    }
+
+    #[test]
+    fn dominance_criteria_parse_and_print() {
+        let criteria: DominanceCriteria = " Price, changes,,duration ".parse().unwrap();
+        assert!(criteria.contains(DominanceCriterion::Changes));
+        assert!(!criteria.contains(DominanceCriterion::Walking));
+        assert_eq!(criteria.to_string(), "changes,duration,price");
+
+        assert_eq!(
+            "".parse::<DominanceCriteria>(),
+            Ok(DominanceCriteria::arrival_only())
+        );
+        assert_eq!(
+            "changes,stops".parse::<DominanceCriteria>(),
+            Err(UnknownCriterion("stops".to_string()))
+        );
+        assert_eq!(
+            DominanceCriteria::default().to_string(),
+            "changes,duration,price"
+        );
     }
 
     #[test]
@@ -111,8 +522,94 @@ mod tests {
 
         assert_eq!(config.time_window(), Duration::minutes(120));
         assert_eq!(config.min_connection(), Duration::minutes(5));
+        assert_eq!(config.footbridge_connection(), Duration::minutes(8));
         assert_eq!(config.max_walk(), Duration::minutes(15));
         assert_eq!(config.max_journey(), Duration::minutes(360));
+        assert_eq!(config.frequent_max_headway(), Some(Duration::minutes(15)));
+
+        let disabled = SearchConfig {
+            frequent_max_headway_mins: 0,
+            ..SearchConfig::default()
+        };
+        assert_eq!(disabled.frequent_max_headway(), None);
+        assert_eq!(config.interchange_limit(), Some(3));
+
+        let unlimited = SearchConfig {
+            max_per_interchange: 0,
+            ..SearchConfig::default()
+        };
+        assert_eq!(unlimited.interchange_limit(), None);
+        assert_eq!(config.stay_on_allowance(), Some(Duration::minutes(3)));
+
+        let changes_early = SearchConfig {
+            stay_on_mins: 0,
+            ..SearchConfig::default()
+        };
+        assert_eq!(changes_early.stay_on_allowance(), None);
+        assert_eq!(
+            config.wait_penalty(Duration::minutes(10)),
+            Duration::minutes(5)
+        );
+        assert_eq!(config.fetch_concurrency_limit(), Some(4));
+        assert_eq!(config.board_concurrency_limit(), Some(3));
+        assert_eq!(config.batch_delay(), None);
+        assert_eq!(config.jitter_seed(), None);
+        assert_eq!(
+            config.arrivals_reuse(),
+            Some(std::time::Duration::from_secs(90))
+        );
+
+        assert_eq!(config.arrivals_paging(), Some(3));
+        assert_eq!(config.board_service_limit(), Some(150));
+        assert_eq!(config.held_calls_limit(), Some(200_000));
+
+        let paced = SearchConfig {
+            fetch_concurrency: 0,
+            board_concurrency: 0,
+            batch_delay_ms: 250,
+            jitter_seed: 42,
+            arrivals_reuse_secs: 0,
+            max_arrivals_pages: 0,
+            max_board_services: 0,
+            max_held_calls: 0,
+            ..SearchConfig::default()
+        };
+        assert_eq!(paced.board_service_limit(), None);
+        assert_eq!(paced.held_calls_limit(), None);
+        assert_eq!(paced.jitter_seed(), Some(42));
+        assert_eq!(paced.fetch_concurrency_limit(), None);
+        assert_eq!(paced.board_concurrency_limit(), None);
+        assert_eq!(paced.arrivals_reuse(), None);
+        assert_eq!(paced.arrivals_paging(), None);
+        assert_eq!(
+            paced.batch_delay(),
+            Some(std::time::Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn bfs_early_exit() {
+        assert_eq!(SearchConfig::default().bfs_early_exit(), None);
+
+        let early_exit = SearchConfig {
+            bfs_target_journeys: 5,
+            bfs_arrival_margin_mins: 20,
+            ..SearchConfig::default()
+        };
+        assert_eq!(
+            early_exit.bfs_early_exit(),
+            Some((5, Duration::minutes(20)))
+        );
+
+        let negative_margin = SearchConfig {
+            bfs_target_journeys: 5,
+            bfs_arrival_margin_mins: -10,
+            ..SearchConfig::default()
+        };
+        assert_eq!(
+            negative_margin.bfs_early_exit(),
+            Some((5, Duration::zero()))
+        );
     }
 
     #[test]
@@ -0,0 +1,213 @@
+//! Continuations: planning journeys further ahead than Darwin can see.
+//!
+//! Darwin's boards only reach a couple of hours ahead, so a long journey
+//! (Penzance to Thurso, say) can't be planned end to end: the trains that
+//! would finish it aren't on the destination's board yet. Rather than
+//! offering nothing, a search that finds neither a journey nor a near miss
+//! returns the first portion it can plan, ending as close to the destination
+//! as it can, with a [`ContinuationHint`] saying where and when to plan the
+//! rest. Once departures from there come into view, a search of that
+//! station's board (see [`ContinuationHint::board_request`]) gives the rest,
+//! which [`Continuation::join`] puts after the first portion.
+//!
+//! "Closest" is straight-line distance, so continuations need station
+//! locations (see [`Planner::with_locations`]); without them none are
+//! offered.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::Duration;
+
+use super::board::BoardRequest;
+use super::search::{Planner, SearchRequest, ServiceProvider};
+use crate::domain::{Crs, Journey, Leg, RailTime, Segment, Service};
+
+/// Where and when to plan the rest of a journey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContinuationHint {
+    /// Station the first portion ends at, to plan on from
+    pub station: Crs,
+
+    /// Earliest departure from there: the first portion's arrival plus the
+    /// minimum connection time
+    pub after: RailTime,
+
+    /// Where the user is going
+    pub destination: Crs,
+
+    /// When enough departures after `after` are in view to plan the rest
+    pub opens_at: RailTime,
+}
+
+impl ContinuationHint {
+    /// Whether the rest of the journey can be planned at `now`.
+    pub fn is_open(&self, now: RailTime) -> bool {
+        now >= self.opens_at
+    }
+
+    /// The board search planning the rest: departures from the station
+    /// within `window` of the earliest.
+    pub fn board_request(&self, window: Duration) -> BoardRequest {
+        BoardRequest::new(
+            self.station,
+            self.destination,
+            self.after,
+            self.after + window,
+        )
+    }
+}
+
+/// The part of a journey that can be planned now, and how to plan the rest.
+#[derive(Debug, Clone)]
+pub struct Continuation {
+    /// The first portion, ending at the hint's station
+    pub journey: Journey,
+
+    /// Where and when to plan on from
+    pub hint: ContinuationHint,
+}
+
+impl Continuation {
+    /// The whole journey: the first portion followed by `rest`, which should
+    /// start at the hint's station no earlier than its `after`.
+    ///
+    /// Returns `None` if `rest` doesn't carry on from the first portion.
+    pub fn join(&self, rest: &Journey) -> Option<Journey> {
+        if rest.origin() != &self.hint.station || rest.departure_time() < self.hint.after {
+            return None;
+        }
+        let segments = self
+            .journey
+            .segments()
+            .iter()
+            .chain(rest.segments())
+            .cloned()
+            .collect();
+        Journey::new(segments).ok()
+    }
+}
+
+impl<P: ServiceProvider> Planner<'_, P> {
+    /// The first portion of a journey that found no way to the destination,
+    /// using the current train and the departures the search already
+    /// fetched (so no API calls).
+    ///
+    /// Portions stay on the current train or change once onto a fetched
+    /// departure, and must end closer to the destination than the user is
+    /// now. Returns `None` without station locations for the destination.
+    pub(super) fn find_continuation(
+        &self,
+        request: &SearchRequest,
+        departures_cache: &HashMap<Crs, Vec<Arc<Service>>>,
+    ) -> Option<Continuation> {
+        let train = &request.current_service;
+        let pos = request.current_position;
+        let here = train.calls.get(pos.0)?.station;
+        let limit = self.remaining_metres(&here, &request.destination)?;
+
+        let mut portions = Vec::new();
+        for (idx, call) in train.calls_after(pos) {
+            if call.is_cancelled || !call.can_alight() {
+                continue;
+            }
+            let (Some(arrival), Ok(first)) =
+                (call.expected_arrival(), Leg::new(train.clone(), pos, idx))
+            else {
+                continue;
+            };
+            portions.push(vec![Segment::Train(first.clone())]);
+            if self.config.max_changes == 0 {
+                continue;
+            }
+
+            let ready = arrival + self.config.min_connection();
+            for service in departures_cache.get(&call.station).into_iter().flatten() {
+                let Some((board, board_call)) = service.board_call_at(&call.station) else {
+                    continue;
+                };
+                if service.service_ref == train.service_ref
+                    || board_call.is_cancelled
+                    || !board_call.can_board()
+                    || board_call.expected_departure().is_none_or(|d| d < ready)
+                {
+                    continue;
+                }
+                for (alight, _) in service
+                    .calls_after(board)
+                    .filter(|(_, c)| !c.is_cancelled && c.can_alight())
+                {
+                    if let Ok(second) = Leg::new(service.clone(), board, alight) {
+                        portions.push(vec![Segment::Train(first.clone()), Segment::Train(second)]);
+                    }
+                }
+            }
+        }
+
+        let journeys = portions
+            .into_iter()
+            .filter_map(|segments| Journey::new(segments).ok());
+        let journey = self
+            .closest(journeys, |journey| journey, &request.destination)
+            .filter(|(remaining, _)| *remaining < limit)?
+            .1;
+        Some(self.continuation(journey, request.destination))
+    }
+
+    /// The continuation whose first portion ends closest to the destination.
+    pub(super) fn closest_continuation(
+        &self,
+        continuations: impl IntoIterator<Item = Continuation>,
+    ) -> Option<Continuation> {
+        let mut continuations = continuations.into_iter().peekable();
+        let destination = continuations.peek()?.hint.destination;
+        self.closest(continuations, |c| &c.journey, &destination)
+            .map(|(_, continuation)| continuation)
+    }
+
+    /// Continue `journey` on towards `destination`.
+    fn continuation(&self, journey: Journey, destination: Crs) -> Continuation {
+        let after = journey.arrival_time() + self.config.min_connection();
+        // Half a search window of departures is enough to plan on with
+        let opens_at = after
+            .checked_sub(self.config.time_window() / 2)
+            .unwrap_or(after);
+        let hint = ContinuationHint {
+            station: *journey.destination(),
+            after,
+            destination,
+            opens_at,
+        };
+        Continuation { journey, hint }
+    }
+
+    /// The item whose journey ends closest to `destination`, then arrives
+    /// earliest, then has fewest changes, with how far it ends from the
+    /// destination in metres.
+    fn closest<T>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        journey: impl Fn(&T) -> &Journey,
+        destination: &Crs,
+    ) -> Option<(f64, T)> {
+        items
+            .into_iter()
+            .filter_map(|item| {
+                let remaining = self.remaining_metres(journey(&item).destination(), destination)?;
+                Some((remaining, item))
+            })
+            .min_by(|(a, x), (b, y)| {
+                let (x, y) = (journey(x), journey(y));
+                a.total_cmp(b)
+                    .then_with(|| x.arrival_time().cmp(&y.arrival_time()))
+                    .then_with(|| x.change_count().cmp(&y.change_count()))
+                    .then_with(|| x.fingerprint().cmp(&y.fingerprint()))
+            })
+    }
+
+    /// Straight-line distance between two stations, if both are located.
+    fn remaining_metres(&self, from: &Crs, to: &Crs) -> Option<f64> {
+        let locations = self.locations?;
+        Some(locations.get(from)?.distance_metres(locations.get(to)?))
+    }
+}
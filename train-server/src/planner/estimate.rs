@@ -0,0 +1,410 @@
+//! Estimating what a search will cost before running it.
+//!
+//! Clients and operators tuning API budgets want to know how many Darwin
+//! calls a search would make without spending them. [`Planner::estimate`]
+//! works that out from the current train's stops, the configuration and
+//! which boards the provider already holds. It can't know what the boards
+//! it hasn't fetched will show, so phases that only run depending on
+//! earlier results are marked as such, and the BFS fallback's later levels
+//! (which fetch boards at wherever earlier levels reach) aren't counted.
+
+use std::collections::HashSet;
+
+use super::search::{Planner, SearchError, SearchRequest, ServiceProvider};
+use crate::domain::{Crs, RailTime};
+
+/// Whether a search phase will run, from least to most likely.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PhaseRun {
+    /// The search finishes before reaching it
+    #[default]
+    Skipped,
+
+    /// Depends on what earlier phases find
+    Maybe,
+
+    /// Always runs
+    Always,
+}
+
+impl PhaseRun {
+    /// Short name for the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhaseRun::Skipped => "skipped",
+            PhaseRun::Maybe => "maybe",
+            PhaseRun::Always => "always",
+        }
+    }
+}
+
+/// What one search phase is expected to fetch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseEstimate {
+    /// Whether the phase runs.
+    pub runs: PhaseRun,
+
+    /// Boards the phase is known to look at.
+    pub boards: usize,
+
+    /// Of those, boards already cached or fetched by an earlier phase.
+    pub cached: usize,
+
+    /// Whether the phase may fetch more boards than are known up front,
+    /// depending on what the first ones show.
+    pub open_ended: bool,
+}
+
+impl PhaseEstimate {
+    /// API calls the known boards need.
+    pub fn api_calls(&self) -> usize {
+        self.boards - self.cached
+    }
+
+    /// Add the same phase of another search.
+    fn add(&mut self, other: &PhaseEstimate) {
+        self.runs = self.runs.max(other.runs);
+        self.boards += other.boards;
+        self.cached += other.cached;
+        self.open_ended |= other.open_ended;
+    }
+}
+
+/// What a search is expected to fetch, broken down by phase as in
+/// [`SearchStats`](super::SearchStats).
+///
+/// Counts assume each fetch succeeds first time; a failed fetch of the
+/// destination's arrivals board is retried once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchEstimate {
+    /// Staying on the current train.
+    pub direct: PhaseEstimate,
+
+    /// One change, including fetching the destination's arrivals board.
+    pub one_change: PhaseEstimate,
+
+    /// Two changes via departure boards at intermediate stations.
+    pub two_change: PhaseEstimate,
+
+    /// BFS fallback.
+    pub bfs: PhaseEstimate,
+
+    /// Direct journeys already known from the current train.
+    pub direct_journeys: usize,
+}
+
+impl SearchEstimate {
+    /// Combine the estimates for searches to each station of a group.
+    pub fn merge(estimates: &[SearchEstimate]) -> Self {
+        let mut merged = SearchEstimate::default();
+        for estimate in estimates {
+            merged.direct.add(&estimate.direct);
+            merged.one_change.add(&estimate.one_change);
+            merged.two_change.add(&estimate.two_change);
+            merged.bfs.add(&estimate.bfs);
+            merged.direct_journeys += estimate.direct_journeys;
+        }
+        merged
+    }
+
+    /// API calls made by the phases that always run.
+    pub fn min_api_calls(&self) -> usize {
+        self.phases()
+            .filter(|p| p.runs == PhaseRun::Always)
+            .map(PhaseEstimate::api_calls)
+            .sum()
+    }
+
+    /// API calls made if every phase that may run does, not counting the
+    /// further boards an open-ended phase may need.
+    pub fn max_api_calls(&self) -> usize {
+        self.phases()
+            .filter(|p| p.runs != PhaseRun::Skipped)
+            .map(PhaseEstimate::api_calls)
+            .sum()
+    }
+
+    /// Whether the search may make more calls than [`Self::max_api_calls`].
+    pub fn open_ended(&self) -> bool {
+        self.phases()
+            .any(|p| p.runs != PhaseRun::Skipped && p.open_ended)
+    }
+
+    fn phases(&self) -> impl Iterator<Item = &PhaseEstimate> {
+        [&self.direct, &self.one_change, &self.two_change, &self.bfs].into_iter()
+    }
+}
+
+impl<P: ServiceProvider> Planner<'_, P> {
+    /// Estimate what [`Planner::search`] would fetch for `request`,
+    /// without fetching anything.
+    pub fn estimate(&self, request: &SearchRequest) -> Result<SearchEstimate, SearchError> {
+        request.validate()?;
+        let mut estimate = SearchEstimate {
+            direct: PhaseEstimate {
+                runs: PhaseRun::Always,
+                ..PhaseEstimate::default()
+            },
+            direct_journeys: self.find_direct(request).len(),
+            ..SearchEstimate::default()
+        };
+        if estimate.direct_journeys > 0 && self.config.max_changes == 0 {
+            return Ok(estimate);
+        }
+
+        let current_time = request.current_time().ok_or_else(|| {
+            SearchError::InvalidRequest("Cannot determine current time".to_string())
+        })?;
+
+        // The destination's arrivals board, unless reused from an earlier
+        // search, and for changes those within walking distance of it
+        let reused = self.recent_arrivals.is_some_and(|recent| {
+            self.config.arrivals_reuse().is_some_and(|freshness| {
+                recent
+                    .get(&request.destination, current_time, freshness)
+                    .is_some()
+            })
+        });
+        let mut one_change = PhaseEstimate {
+            runs: PhaseRun::Always,
+            boards: 1,
+            cached: usize::from(
+                reused
+                    || self
+                        .provider
+                        .has_arrivals(&request.destination, current_time),
+            ),
+            // A board ending before the user could get there is paged forward
+            open_ended: !reused
+                && self
+                    .earliest_possible_arrival(request, current_time)
+                    .is_some(),
+        };
+        if self.config.max_changes >= 1 {
+            let max_walk = self.config.max_walk();
+            for (station, walk) in self.walkable.walkable_from(&request.destination) {
+                if walk <= max_walk {
+                    one_change.boards += 1;
+                    one_change.cached +=
+                        usize::from(self.provider.has_arrivals(&station, current_time));
+                }
+            }
+        }
+        estimate.one_change = one_change;
+        if self.config.max_changes == 0 {
+            return Ok(estimate);
+        }
+
+        // Departure boards fetched by one phase are reused by the next.
+        // Either may be skipped if earlier phases find enough journeys.
+        let mut fetched = HashSet::new();
+        if self.config.max_changes >= 2 {
+            let stations: Vec<Crs> = self
+                .two_change_stations(request)
+                .into_iter()
+                .map(|(_, _, station, _)| station)
+                .collect();
+            estimate.two_change = PhaseEstimate {
+                runs: PhaseRun::Maybe,
+                ..self.departures_estimate(&stations, current_time, &mut fetched)
+            };
+        }
+        let stations = self.bfs_first_stations(request);
+        estimate.bfs = PhaseEstimate {
+            runs: PhaseRun::Maybe,
+            // Further levels fetch boards wherever the first one's trains go
+            open_ended: self.config.max_changes >= 2,
+            ..self.departures_estimate(&stations, current_time, &mut fetched)
+        };
+        Ok(estimate)
+    }
+
+    /// The boards among `stations` and how many are cached or already in
+    /// `fetched`, which they're then added to.
+    fn departures_estimate(
+        &self,
+        stations: &[Crs],
+        after: RailTime,
+        fetched: &mut HashSet<Crs>,
+    ) -> PhaseEstimate {
+        let mut phase = PhaseEstimate::default();
+        let mut seen = HashSet::new();
+        for station in stations {
+            if !seen.insert(*station) {
+                continue;
+            }
+            phase.boards += 1;
+            if !fetched.insert(*station) || self.provider.has_departures(station, after) {
+                phase.cached += 1;
+            }
+        }
+        phase
+    }
+
+    /// The stations whose departures the first level of BFS looks at: the
+    /// current train's later stops, and those within walking distance.
+    fn bfs_first_stations(&self, request: &SearchRequest) -> Vec<Crs> {
+        let max_walk = self.config.max_walk();
+        let mut stations = Vec::new();
+        for (_, call) in request
+            .current_service
+            .calls_after(request.current_position)
+        {
+            if call.is_cancelled || !call.can_alight() || call.station == request.destination {
+                continue;
+            }
+            if call
+                .expected_arrival()
+                .or_else(|| call.expected_departure())
+                .is_none()
+            {
+                continue;
+            }
+            stations.push(call.station);
+            stations.extend(
+                self.walkable
+                    .walkable_from(&call.station)
+                    .into_iter()
+                    .filter(|(_, walk)| *walk <= max_walk)
+                    .map(|(station, _)| station),
+            );
+        }
+        stations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::domain::{CallIndex, Service};
+    use crate::planner::SearchConfig;
+    use crate::testing::{Network, NetworkBuilder, ServiceBuilder, crs};
+
+    /// A network whose boards at `cached` count as already held.
+    struct Cached {
+        network: Network,
+        cached: Vec<Crs>,
+    }
+
+    impl ServiceProvider for Cached {
+        async fn get_departures(
+            &self,
+            station: &Crs,
+            after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            self.network.get_departures(station, after).await
+        }
+
+        async fn get_arrivals(
+            &self,
+            station: &Crs,
+            after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            self.network.get_arrivals(station, after).await
+        }
+
+        fn has_departures(&self, station: &Crs, _after: RailTime) -> bool {
+            self.cached.contains(station)
+        }
+
+        fn has_arrivals(&self, station: &Crs, _after: RailTime) -> bool {
+            self.cached.contains(station)
+        }
+    }
+
+    /// The user's train, PAD to SWI via RDG and DID.
+    fn train() -> Arc<Service> {
+        ServiceBuilder::new("A")
+            .call("PAD")
+            .dep("10:00")
+            .call("RDG")
+            .arr("10:25")
+            .dep("10:27")
+            .call("DID")
+            .arr("10:40")
+            .dep("10:41")
+            .call("SWI")
+            .arr("11:00")
+            .build()
+    }
+
+    fn provider(cached: &[&str]) -> Cached {
+        Cached {
+            network: NetworkBuilder::new()
+                .service(train())
+                .walk("BRI", "BPW", 10)
+                .build(),
+            cached: cached.iter().map(|s| crs(s)).collect(),
+        }
+    }
+
+    fn config(max_changes: usize) -> SearchConfig {
+        SearchConfig {
+            max_changes,
+            ..SearchConfig::default()
+        }
+    }
+
+    #[test]
+    fn direct_journey_with_no_changes_needs_no_calls() {
+        let provider = provider(&[]);
+        let config = config(0);
+        let planner = Planner::new(&provider, provider.network.walkable(), &config);
+
+        let request = SearchRequest::new(train(), CallIndex(0), crs("SWI"));
+        let estimate = planner.estimate(&request).unwrap();
+
+        assert_eq!(estimate.direct_journeys, 1);
+        assert_eq!(estimate.one_change.runs, PhaseRun::Skipped);
+        assert_eq!(estimate.max_api_calls(), 0);
+        assert_eq!(provider.network.requests(), 0);
+    }
+
+    #[test]
+    fn phases_count_the_boards_they_look_at() {
+        let provider = provider(&["RDG"]);
+        let config = config(2);
+        let planner = Planner::new(&provider, provider.network.walkable(), &config);
+
+        let request = SearchRequest::new(train(), CallIndex(0), crs("BRI"));
+        let estimate = planner.estimate(&request).unwrap();
+
+        // BRI's arrivals, and BPW's a walk away
+        assert_eq!(estimate.one_change.runs, PhaseRun::Always);
+        assert_eq!(estimate.one_change.api_calls(), 2);
+        // Departures from PAD, RDG, DID and SWI, with RDG cached
+        assert_eq!(estimate.two_change.boards, 4);
+        assert_eq!(estimate.two_change.api_calls(), 3);
+        // BFS starts after PAD, so only needs boards two-change fetched
+        assert_eq!(estimate.bfs.boards, 3);
+        assert_eq!(estimate.bfs.api_calls(), 0);
+        assert!(estimate.open_ended());
+
+        assert_eq!(estimate.min_api_calls(), 2);
+        assert_eq!(estimate.max_api_calls(), 5);
+        assert_eq!(provider.network.requests(), 0);
+    }
+
+    #[test]
+    fn groups_add_up() {
+        let provider = provider(&[]);
+        let config = config(1);
+        let planner = Planner::new(&provider, provider.network.walkable(), &config);
+
+        let estimates: Vec<SearchEstimate> = ["BRI", "BPW"]
+            .into_iter()
+            .map(|dest| {
+                let request = SearchRequest::new(train(), CallIndex(0), crs(dest));
+                planner.estimate(&request).unwrap()
+            })
+            .collect();
+        let merged = SearchEstimate::merge(&estimates);
+
+        assert_eq!(merged.one_change.boards, 4);
+        assert_eq!(merged.two_change.runs, PhaseRun::Skipped);
+        assert_eq!(merged.bfs.runs, PhaseRun::Maybe);
+        assert!(!merged.open_ended());
+    }
+}
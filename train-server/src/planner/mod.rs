@@ -11,11 +11,49 @@
 
 mod arrivals_index;
 mod bfs;
+mod board;
+mod budget;
+mod confidence;
 mod config;
+mod continuation;
+mod estimate;
+mod near_miss;
+mod pacing;
+mod performance;
+pub mod phase;
+mod quality;
 mod rank;
+mod rationale;
+mod recent_arrivals;
+mod routeing;
 mod search;
+mod stats;
+mod travel_times;
 
 pub use arrivals_index::{ArrivalsIndex, FeederInfo};
-pub use config::SearchConfig;
-pub use rank::{deduplicate, rank_journeys, remove_dominated};
-pub use search::{Planner, SearchError, SearchRequest, SearchResult, ServiceProvider};
+pub use board::{BoardDeparture, BoardRequest, BoardResult, MAX_BOARD_DEPARTURES};
+pub use confidence::{
+    Confidence, ConfidenceReason, ConfidenceSignal, JourneyConfidence, assess as assess_confidence,
+    assess_with_history as assess_confidence_with_history,
+};
+pub use config::{DominanceCriteria, DominanceCriterion, SearchConfig, UnknownCriterion};
+pub use continuation::{Continuation, ContinuationHint};
+pub use estimate::{PhaseEstimate, PhaseRun, SearchEstimate};
+pub use near_miss::{NearMiss, NearMissReason};
+#[cfg(feature = "darwin")]
+pub(crate) use pacing::splitmix64;
+pub use performance::{
+    PerformanceHistory, PerformanceStore, PerformanceStoreError, Punctuality, ServiceKey,
+};
+pub use quality::DataQuality;
+pub use rank::{
+    combine_endings, deduplicate, rank_journeys, remove_dominated, weighted_duration_from,
+};
+pub use rationale::{RankReason, rank_reasons};
+pub use recent_arrivals::RecentArrivals;
+pub use routeing::{RouteingIssue, RouteingRule, RouteingRules};
+pub use search::{
+    DynServiceProvider, Planner, SearchError, SearchRequest, SearchResult, ServiceProvider,
+};
+pub use stats::{PhaseStats, SearchStats};
+pub use travel_times::{LowerBounds, TravelTimes};
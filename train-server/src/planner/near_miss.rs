@@ -0,0 +1,278 @@
+//! Near misses: what to offer when no journey reaches the destination.
+//!
+//! A search that finds nothing still knows a lot: the trains arriving at the
+//! destination, and the departures it fetched looking for connections onto
+//! them. [`Planner::search`] relaxes the search over those, offering
+//! journeys that end one stop short of the destination or at a station a
+//! long walk from it, and journeys that get there but take longer than the
+//! maximum journey time. Each says how it falls short (see
+//! [`NearMissReason`]), so it isn't mistaken for a real option.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use chrono::Duration;
+
+use super::arrivals_index::ArrivalsIndex;
+use super::config::SearchConfig;
+use super::quality::DataQuality;
+use super::rank::{RouteKey, deduplicate, remove_dominated};
+use super::search::{Planner, SearchError, SearchRequest, ServiceProvider};
+use crate::domain::{CallIndex, Crs, Journey, Leg, Segment, Service};
+
+/// Longest journey a near miss may take, in minutes.
+const RELAXED_MAX_JOURNEY_MINS: i64 = 24 * 60;
+
+/// How a near miss falls short of the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NearMissReason {
+    /// Ends at a station more than the maximum walk from the destination
+    Nearby {
+        /// The walk from there to the destination
+        walk: Duration,
+    },
+
+    /// Ends at the stop before the destination on a train arriving there
+    StopShort,
+
+    /// Reaches the destination, but takes longer than the maximum journey
+    /// time
+    TooLong,
+}
+
+impl NearMissReason {
+    /// Short identifier, as used in the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Nearby { .. } => "nearby",
+            Self::StopShort => "stop_short",
+            Self::TooLong => "too_long",
+        }
+    }
+}
+
+/// A journey that doesn't quite do what was asked.
+#[derive(Debug, Clone)]
+pub struct NearMiss {
+    /// The journey
+    pub journey: Journey,
+
+    /// How it falls short
+    pub reason: NearMissReason,
+}
+
+impl NearMiss {
+    /// A short, human-readable description of how the journey falls short.
+    pub fn label(&self) -> String {
+        let end = self
+            .journey
+            .legs()
+            .last()
+            .map_or("", |leg| leg.alight_station_name());
+        match self.reason {
+            NearMissReason::Nearby { walk } => format!(
+                "Ends at {end}, a {} min walk from your destination",
+                walk.num_minutes()
+            ),
+            NearMissReason::StopShort => {
+                format!("Ends at {end}, one stop short of your destination")
+            }
+            NearMissReason::TooLong => {
+                let duration = self.journey.total_duration();
+                format!(
+                    "Reaches your destination, but takes {}h {}m",
+                    duration.num_hours(),
+                    duration.num_minutes() % 60
+                )
+            }
+        }
+    }
+}
+
+impl<P: ServiceProvider> Planner<'_, P> {
+    /// Find near misses for a search that found no journeys, using its
+    /// arrivals index and the departures it already fetched.
+    ///
+    /// Returns the near misses, earliest-arriving first, and the number of
+    /// API calls made (usually none). Stations whose departures couldn't be
+    /// fetched are recorded in `quality`.
+    pub(super) async fn find_near_misses(
+        &self,
+        request: &SearchRequest,
+        index: &ArrivalsIndex,
+        departures_cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
+        quality: &mut DataQuality,
+    ) -> Result<(Vec<NearMiss>, usize), SearchError> {
+        let mut near_misses = self.find_short(request, index);
+
+        let (too_long, api_calls) = self
+            .find_too_long(request, index, departures_cache, quality)
+            .await?;
+        near_misses.extend(too_long.into_iter().map(|journey| NearMiss {
+            journey,
+            reason: NearMissReason::TooLong,
+        }));
+
+        sort_near_misses(&mut near_misses);
+        near_misses.truncate(self.config.max_results);
+        Ok((near_misses, api_calls))
+    }
+
+    /// Journeys staying on the current train (or a portion of it) to a
+    /// station short of the destination.
+    fn find_short(&self, request: &SearchRequest, index: &ArrivalsIndex) -> Vec<NearMiss> {
+        let stops_before = index.stops_before_destination();
+        let train = &request.current_service;
+        let pos = request.current_position;
+
+        let mut near_misses = self.find_short_on(train, pos, pos, request, &stops_before);
+        for (divides, portion) in train.portions_after(pos) {
+            let portion = Arc::new(portion);
+            near_misses.extend(self.find_short_on(&portion, pos, divides, request, &stops_before));
+        }
+        near_misses
+    }
+
+    /// Journeys on `train` from `board` to each station after `alight_after`
+    /// that is near the destination or one stop short of it.
+    fn find_short_on(
+        &self,
+        train: &Arc<Service>,
+        board: CallIndex,
+        alight_after: CallIndex,
+        request: &SearchRequest,
+        stops_before: &BTreeSet<Crs>,
+    ) -> Vec<NearMiss> {
+        train
+            .calls_after(alight_after)
+            .filter(|(_, call)| {
+                !call.is_cancelled && call.can_alight() && call.station != request.destination
+            })
+            .filter_map(|(idx, call)| {
+                let reason = match self.walkable.get(&call.station, &request.destination) {
+                    Some(walk) => NearMissReason::Nearby { walk },
+                    None if stops_before.contains(&call.station) => NearMissReason::StopShort,
+                    None => return None,
+                };
+                let leg = Leg::new(train.clone(), board, idx).ok()?;
+                let journey = Journey::new(vec![Segment::Train(leg)]).ok()?;
+                Some(NearMiss { journey, reason })
+            })
+            .collect()
+    }
+
+    /// Journeys with one or two changes that reach the destination but take
+    /// longer than the maximum journey time, with the number of API calls
+    /// made finding them.
+    async fn find_too_long(
+        &self,
+        request: &SearchRequest,
+        index: &ArrivalsIndex,
+        departures_cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
+        quality: &mut DataQuality,
+    ) -> Result<(Vec<Journey>, usize), SearchError> {
+        let relaxed = SearchConfig {
+            max_journey_mins: RELAXED_MAX_JOURNEY_MINS,
+            ..self.config.clone()
+        };
+        let mut planner = Planner::new(self.provider, self.walkable, &relaxed);
+        planner.cancel = self.cancel.clone();
+
+        let mut journeys = Vec::new();
+        let mut api_calls = 0;
+        if relaxed.max_changes >= 1 {
+            journeys.extend(planner.find_one_change(request, index));
+        }
+        if relaxed.max_changes >= 2 {
+            let (two_change, calls, _) = planner
+                .find_two_change(request, index, departures_cache, quality)
+                .await?;
+            journeys.extend(two_change);
+            api_calls += calls;
+        }
+
+        let max_journey = self.config.max_journey();
+        let journeys = journeys
+            .into_iter()
+            .filter(|journey| journey.total_duration() > max_journey)
+            .collect();
+        let journeys = remove_dominated(deduplicate(journeys, self.config), self.config);
+        Ok((journeys, api_calls))
+    }
+}
+
+/// Sort near misses earliest-arriving first, and those arriving together
+/// by route.
+pub(super) fn sort_near_misses(near_misses: &mut [NearMiss]) {
+    near_misses.sort_by_cached_key(|near_miss| {
+        (
+            near_miss.journey.arrival_time(),
+            RouteKey::of_segments(near_miss.journey.segments()),
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, RailTime, ServiceRef};
+    use chrono::NaiveDate;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn service(calls: &[(&str, &str, &str)]) -> Arc<Service> {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let calls = calls
+            .iter()
+            .map(|(station, name, time)| {
+                let mut call = Call::new(crs(station), (*name).to_string());
+                let time = RailTime::parse_hhmm(time, date).unwrap();
+                call.booked_arrival = Some(time);
+                call.booked_departure = Some(time);
+                call
+            })
+            .collect();
+        Arc::new(Service {
+            service_ref: ServiceRef::new("S1".into(), crs("PAD")),
+            headcode: None,
+            operator: "Test".into(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    fn near_miss(reason: NearMissReason) -> NearMiss {
+        let train = service(&[
+            ("PAD", "London Paddington", "10:00"),
+            ("RDG", "Reading", "10:25"),
+            ("SWI", "Swindon", "11:05"),
+        ]);
+        let leg = Leg::new(train, CallIndex(0), CallIndex(2)).unwrap();
+        NearMiss {
+            journey: Journey::new(vec![Segment::Train(leg)]).unwrap(),
+            reason,
+        }
+    }
+
+    #[test]
+    fn labels_say_how_journey_falls_short() {
+        let walk = Duration::minutes(25);
+        assert_eq!(
+            near_miss(NearMissReason::Nearby { walk }).label(),
+            "Ends at Swindon, a 25 min walk from your destination"
+        );
+        assert_eq!(
+            near_miss(NearMissReason::StopShort).label(),
+            "Ends at Swindon, one stop short of your destination"
+        );
+        assert_eq!(
+            near_miss(NearMissReason::TooLong).label(),
+            "Reaches your destination, but takes 1h 5m"
+        );
+        assert_eq!(NearMissReason::StopShort.as_str(), "stop_short");
+    }
+}
@@ -0,0 +1,257 @@
+//! Batched departure fetches, paced to respect Darwin's rate limits.
+//!
+//! The 2-change and BFS phases fetch departure boards for many stations at
+//! once. Darwin enforces per-second burst limits, and tripping them fails
+//! every call in the burst, so fetches are made in batches of
+//! `config.batch_size`, at most `config.fetch_concurrency` at a time, with
+//! a pause of `config.batch_delay_ms` (plus jitter, so concurrent searches
+//! don't fall into step) between batches. Setting `config.jitter_seed`
+//! makes the jitter repeatable.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::stream;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use super::budget::Budget;
+use super::config::SearchConfig;
+use super::quality::DataQuality;
+use super::search::ServiceProvider;
+use crate::domain::{Crs, RailTime, Service};
+
+/// Batch fetch departures for multiple stations in parallel.
+///
+/// Fetches departures for all given stations, paced as described in the
+/// module docs. Results are inserted into the cache, cut to the search's
+/// [`Budget`]; a failed fetch is cached as no departures so it isn't
+/// retried, and its station recorded in `quality`. Returns the number of API
+/// calls made. Stops early (leaving remaining stations unfetched) if
+/// `cancel` is triggered between batches.
+pub(super) async fn batch_fetch_departures<P: ServiceProvider>(
+    stations: &[Crs],
+    after: RailTime,
+    cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
+    quality: &mut DataQuality,
+    config: &SearchConfig,
+    provider: &P,
+    cancel: Option<&CancellationToken>,
+) -> usize {
+    if stations.is_empty() {
+        return 0;
+    }
+
+    let mut api_calls = 0;
+    let mut budget = Budget::new(config, cache);
+    let concurrency = config.fetch_concurrency_limit();
+
+    for (i, batch) in stations.chunks(config.batch_size.max(1)).enumerate() {
+        if i > 0
+            && let Some(delay) = config.batch_delay()
+        {
+            tokio::time::sleep(jittered(delay, config.jitter_seed(), i)).await;
+        }
+        if cancel.is_some_and(|t| t.is_cancelled()) {
+            break;
+        }
+
+        let futures: Vec<_> = batch
+            .iter()
+            .map(|station| async move {
+                let result = provider.get_departures(station, after).await;
+                (*station, result)
+            })
+            .collect();
+
+        let results: Vec<_> = stream::iter(futures)
+            .buffer_unordered(concurrency.unwrap_or(batch.len()))
+            .collect()
+            .await;
+
+        for (station, result) in results {
+            api_calls += 1;
+            match result {
+                Ok(deps) => {
+                    cache.insert(station, budget.admit(station, deps, quality));
+                }
+                Err(e) => {
+                    debug!(
+                        station = %station.as_str(),
+                        error = %e,
+                        "Failed to fetch departures, using empty"
+                    );
+                    // Insert empty vec so we don't retry
+                    cache.insert(station, vec![]);
+                    quality.record_unavailable(station);
+                }
+            }
+        }
+    }
+
+    api_calls
+}
+
+/// `delay` plus up to half as much again: at random, or with a `seed`, the
+/// same each time for the same `batch`.
+fn jittered(delay: Duration, seed: Option<u64>, batch: usize) -> Duration {
+    let sample = match seed {
+        Some(seed) => splitmix64(seed.wrapping_add(batch as u64)) as u16,
+        None => {
+            let mut bytes = [0u8; 2];
+            if getrandom::getrandom(&mut bytes).is_err() {
+                return delay;
+            }
+            u16::from_le_bytes(bytes)
+        }
+    };
+    let fraction = f64::from(sample) / f64::from(u16::MAX);
+    delay + delay.mul_f64(fraction / 2.0)
+}
+
+/// The SplitMix64 mixing function, spreading consecutive seeds apart.
+pub(crate) fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::SearchError;
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider recording how many departure fetches were in flight at once.
+    #[derive(Default)]
+    struct ConcurrencyProvider {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        calls: AtomicUsize,
+    }
+
+    impl ServiceProvider for ConcurrencyProvider {
+        async fn get_departures(
+            &self,
+            _station: &Crs,
+            _after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![])
+        }
+
+        async fn get_arrivals(
+            &self,
+            _station: &Crs,
+            _after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            Ok(vec![])
+        }
+    }
+
+    fn stations(n: usize) -> Vec<Crs> {
+        (b'A'..)
+            .take(n)
+            .map(|c| Crs::parse(&format!("XX{}", c as char)).unwrap())
+            .collect()
+    }
+
+    fn after() -> RailTime {
+        RailTime::parse_hhmm("10:00", NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetches_are_capped_and_paced() {
+        let provider = ConcurrencyProvider::default();
+        let config = SearchConfig {
+            batch_size: 8,
+            fetch_concurrency: 3,
+            batch_delay_ms: 20,
+            ..SearchConfig::default()
+        };
+        let stations = stations(16);
+        let mut cache = HashMap::new();
+
+        let started = std::time::Instant::now();
+        let calls = batch_fetch_departures(
+            &stations,
+            after(),
+            &mut cache,
+            &mut DataQuality::default(),
+            &config,
+            &provider,
+            None,
+        )
+        .await;
+
+        assert_eq!(calls, 16);
+        assert_eq!(cache.len(), 16);
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 3);
+        // One pause between the two batches
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_between_batches() {
+        let provider = ConcurrencyProvider::default();
+        let config = SearchConfig {
+            batch_size: 4,
+            ..SearchConfig::default()
+        };
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let mut cache = HashMap::new();
+
+        let calls = batch_fetch_departures(
+            &stations(8),
+            after(),
+            &mut cache,
+            &mut DataQuality::default(),
+            &config,
+            &provider,
+            Some(&cancel),
+        )
+        .await;
+
+        assert_eq!(calls, 0);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn jitter_adds_up_to_half() {
+        let delay = Duration::from_millis(100);
+        for _ in 0..50 {
+            let paced = jittered(delay, None, 1);
+            assert!(paced >= delay);
+            assert!(paced <= Duration::from_millis(150));
+        }
+        assert_eq!(jittered(Duration::ZERO, None, 1), Duration::ZERO);
+    }
+
+    #[test]
+    fn seeded_jitter_repeats() {
+        let delay = Duration::from_millis(100);
+        let paced: Vec<_> = (1..20)
+            .map(|batch| jittered(delay, Some(7), batch))
+            .collect();
+        let again: Vec<_> = (1..20)
+            .map(|batch| jittered(delay, Some(7), batch))
+            .collect();
+        assert_eq!(paced, again);
+        assert!(
+            paced
+                .iter()
+                .all(|&d| d >= delay && d <= Duration::from_millis(150))
+        );
+        // Batches aren't all paced alike
+        assert!(paced.iter().any(|&d| d != paced[0]));
+    }
+}
@@ -0,0 +1,420 @@
+//! How punctual services have been on past days.
+//!
+//! A change with ten minutes to spare is comfortable after a train that is
+//! nearly always on time, but not after one that is late most evenings.
+//! Live data can't tell those apart until the day itself goes wrong, so the
+//! history records how late each service arrived at each of its stops, one
+//! figure per day, from the boards the planner sees (or from recorded boards
+//! replayed into it). Services are recognised from day to day by headcode,
+//! route (origin and terminus) and booked departure time from the origin.
+//!
+//! The confidence assessment looks up the train being changed from, so a
+//! journey can say "the 17:04 is too late for this change on 40% of days".
+//! The history is optional: without it, confidence only uses today's data.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Crs, Headcode, Service, ServiceIdentity};
+use crate::error::{Classify, ErrorKind};
+
+/// Days of history kept per service and station; older days are dropped.
+const MAX_DAYS: usize = 90;
+
+/// Cap on the number of service and station pairs tracked, to bound memory
+/// use. Once reached, known pairs still get new days but no new pairs are
+/// added.
+const MAX_ENTRIES: usize = 200_000;
+
+/// How a service is recognised from one day to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServiceKey {
+    /// Headcode, if the board gave one
+    pub headcode: Option<Headcode>,
+
+    /// First station of the service
+    pub origin: Crs,
+
+    /// Last station of the service
+    pub terminus: Crs,
+
+    /// Booked departure from the origin, without the date
+    pub departs: NaiveTime,
+}
+
+impl ServiceKey {
+    /// The key a service is recorded under.
+    ///
+    /// Returns `None` if the service has no calls or its origin has no
+    /// booked departure.
+    pub fn of(service: &Service) -> Option<Self> {
+        let identity = ServiceIdentity::of(service)?;
+        Some(Self {
+            headcode: service.headcode,
+            origin: identity.origin,
+            terminus: identity.terminus,
+            departs: identity.origin_departure.time(),
+        })
+    }
+}
+
+/// How a service has arrived at one station over the days recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Punctuality {
+    /// Minutes late arriving on each day, `None` if it was cancelled there
+    days: Vec<Option<i64>>,
+}
+
+impl Punctuality {
+    /// Number of days recorded.
+    pub fn days(&self) -> usize {
+        self.days.len()
+    }
+
+    /// Share of the days recorded (0 to 1) on which the service arrived
+    /// more than `delay` late, counting days it was cancelled.
+    pub fn share_later_than(&self, delay: Duration) -> f64 {
+        if self.days.is_empty() {
+            return 0.0;
+        }
+        let late = self
+            .days
+            .iter()
+            .filter(|d| d.is_none_or(|mins| mins > delay.num_minutes()))
+            .count();
+        late as f64 / self.days.len() as f64
+    }
+}
+
+/// Minutes late arriving at a station, by day (`None` = cancelled).
+type DailyDelays = BTreeMap<NaiveDate, Option<i64>>;
+
+/// Each recorded service's past arrivals, per station.
+///
+/// Shared between searches; recording takes `&self`.
+#[derive(Debug, Default)]
+pub struct PerformanceHistory {
+    arrivals: RwLock<HashMap<(ServiceKey, Crs), DailyDelays>>,
+}
+
+impl PerformanceHistory {
+    /// Create an empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record how late an observed service is arriving at each stop.
+    ///
+    /// Only stops with a realtime arrival (or a cancellation) are recorded,
+    /// and a later observation on the same day replaces an earlier one, so
+    /// estimates give way to actual times as the service runs.
+    pub fn record(&self, service: &Service) {
+        let Some(key) = ServiceKey::of(service) else {
+            return;
+        };
+        let observed: Vec<(Crs, NaiveDate, Option<i64>)> = service
+            .calls
+            .iter()
+            .filter_map(|call| {
+                let booked = call.booked_arrival?;
+                let late = if call.is_cancelled {
+                    None
+                } else {
+                    let actual = call.realtime_arrival?;
+                    Some(actual.signed_duration_since(booked).num_minutes().max(0))
+                };
+                Some((call.station, booked.date(), late))
+            })
+            .collect();
+        if observed.is_empty() {
+            return;
+        }
+
+        let mut arrivals = self.arrivals.write().unwrap_or_else(|e| e.into_inner());
+        for (station, date, late) in observed {
+            let full = arrivals.len() >= MAX_ENTRIES;
+            let days = match arrivals.get_mut(&(key, station)) {
+                Some(days) => days,
+                None if !full => arrivals.entry((key, station)).or_default(),
+                None => continue,
+            };
+            days.insert(date, late);
+            while days.len() > MAX_DAYS {
+                days.pop_first();
+            }
+        }
+    }
+
+    /// How punctually a service has arrived at a station on the days
+    /// recorded, or `None` if it never has been.
+    pub fn punctuality(&self, service: &Service, station: &Crs) -> Option<Punctuality> {
+        let key = ServiceKey::of(service)?;
+        let arrivals = self.arrivals.read().unwrap_or_else(|e| e.into_inner());
+        let days = arrivals.get(&(key, *station))?;
+        Some(Punctuality {
+            days: days.values().copied().collect(),
+        })
+    }
+
+    /// Number of service and station pairs recorded.
+    pub fn len(&self) -> usize {
+        self.arrivals
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Returns true if nothing has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Errors reading or writing the history file.
+#[derive(Debug, thiserror::Error)]
+pub enum PerformanceStoreError {
+    /// Reading or writing the file failed
+    #[error("performance history I/O error: {message}")]
+    Io { message: String },
+
+    /// The file contents were not valid
+    #[error("performance history format error: {message}")]
+    Format { message: String },
+}
+
+impl Classify for PerformanceStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            PerformanceStoreError::Io { .. } => ErrorKind::Transient,
+            PerformanceStoreError::Format { .. } => ErrorKind::Config,
+        }
+    }
+}
+
+/// One service's arrivals at one station, as stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredArrivals {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    headcode: Option<String>,
+    origin: String,
+    terminus: String,
+    /// Booked departure from the origin, as HH:MM
+    departs: String,
+    station: String,
+    /// Minutes late by ISO date, null if cancelled
+    days: BTreeMap<String, Option<i64>>,
+}
+
+/// JSON file store for the history, so it builds up across restarts.
+#[derive(Debug, Clone)]
+pub struct PerformanceStore {
+    path: PathBuf,
+}
+
+impl PerformanceStore {
+    /// Create a store backed by the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The file the history is kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the history from the file, or an empty one if the file doesn't
+    /// exist yet.
+    ///
+    /// Entries naming invalid stations or headcodes are skipped.
+    pub fn load(&self) -> Result<PerformanceHistory, PerformanceStoreError> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(PerformanceHistory::new());
+            }
+            Err(e) => {
+                return Err(PerformanceStoreError::Io {
+                    message: format!("failed to read {}: {}", self.path.display(), e),
+                });
+            }
+        };
+        let stored: Vec<StoredArrivals> =
+            serde_json::from_str(&contents).map_err(|e| PerformanceStoreError::Format {
+                message: e.to_string(),
+            })?;
+
+        let mut arrivals = HashMap::new();
+        for entry in stored {
+            let headcode = match entry.headcode.as_deref().map(Headcode::parse) {
+                Some(None) => continue,
+                Some(headcode) => headcode,
+                None => None,
+            };
+            let (Ok(origin), Ok(terminus), Ok(station), Ok(departs)) = (
+                Crs::parse(&entry.origin),
+                Crs::parse(&entry.terminus),
+                Crs::parse(&entry.station),
+                NaiveTime::parse_from_str(&entry.departs, "%H:%M"),
+            ) else {
+                continue;
+            };
+            let key = ServiceKey {
+                headcode,
+                origin,
+                terminus,
+                departs,
+            };
+            let days = entry
+                .days
+                .into_iter()
+                .filter_map(|(date, late)| Some((date.parse().ok()?, late)))
+                .collect();
+            arrivals.insert((key, station), days);
+        }
+        Ok(PerformanceHistory {
+            arrivals: RwLock::new(arrivals),
+        })
+    }
+
+    /// Save the history to the file, replacing what was there.
+    pub fn save(&self, history: &PerformanceHistory) -> Result<(), PerformanceStoreError> {
+        let stored: Vec<StoredArrivals> = {
+            let arrivals = history.arrivals.read().unwrap_or_else(|e| e.into_inner());
+            arrivals
+                .iter()
+                .map(|((key, station), days)| StoredArrivals {
+                    headcode: key.headcode.map(|h| h.as_str().to_string()),
+                    origin: key.origin.as_str().to_string(),
+                    terminus: key.terminus.as_str().to_string(),
+                    departs: key.departs.format("%H:%M").to_string(),
+                    station: station.as_str().to_string(),
+                    days: days
+                        .iter()
+                        .map(|(date, late)| (date.to_string(), *late))
+                        .collect(),
+                })
+                .collect()
+        };
+        let json = serde_json::to_string(&stored).map_err(|e| PerformanceStoreError::Format {
+            message: e.to_string(),
+        })?;
+        std::fs::write(&self.path, json).map_err(|e| PerformanceStoreError::Io {
+            message: format!("failed to write {}: {}", self.path.display(), e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::RailTime;
+    use crate::testing::{ServiceBuilder, crs};
+    use std::sync::Arc;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    /// A Leeds to Sheffield leaving at `departs` on `date`, as far as
+    /// Wakefield, arriving there `late` minutes late.
+    fn to_wakefield(date: NaiveDate, departs: &str, late: i64) -> ServiceBuilder {
+        let due = RailTime::parse_hhmm("17:16", date).unwrap();
+        ServiceBuilder::new(format!("S{date}"))
+            .on(date)
+            .headcode("1L23")
+            .call("LDS")
+            .named("Leeds")
+            .dep(departs)
+            .call("WKF")
+            .named("Wakefield Westgate")
+            .arr("17:16")
+            .expected_arr(&(due + Duration::minutes(late)).to_string())
+    }
+
+    /// The rest of the way to Sheffield, with no realtime arrival there.
+    fn to_sheffield(service: ServiceBuilder) -> Arc<Service> {
+        service.call("SHF").named("Sheffield").arr("17:45").build()
+    }
+
+    /// The 17:04 Leeds to Sheffield via Wakefield on day `d`, arriving at
+    /// Wakefield `late` minutes late.
+    fn service(d: u32, late: i64) -> Arc<Service> {
+        to_sheffield(to_wakefield(day(d), "17:04", late))
+    }
+
+    #[test]
+    fn records_one_figure_per_day() {
+        let history = PerformanceHistory::new();
+        history.record(&service(1, 0));
+        history.record(&service(2, 12));
+        // A later observation on the same day replaces the estimate
+        history.record(&service(2, 3));
+        history.record(&service(3, 20));
+
+        let wakefield = history.punctuality(&service(4, 0), &crs("WKF")).unwrap();
+        assert_eq!(wakefield.days(), 3);
+        assert!((wakefield.share_later_than(Duration::minutes(5)) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(wakefield.share_later_than(Duration::minutes(30)), 0.0);
+
+        // No realtime arrival at Sheffield, so nothing recorded there
+        assert!(history.punctuality(&service(4, 0), &crs("SHF")).is_none());
+    }
+
+    #[test]
+    fn cancellations_count_as_late() {
+        let history = PerformanceHistory::new();
+        let cancelled = to_sheffield(to_wakefield(day(1), "17:04", 0).cancelled());
+        history.record(&cancelled);
+        history.record(&service(2, 0));
+
+        let wakefield = history.punctuality(&service(3, 0), &crs("WKF")).unwrap();
+        assert_eq!(wakefield.share_later_than(Duration::minutes(60)), 0.5);
+    }
+
+    #[test]
+    fn other_services_are_kept_apart() {
+        let history = PerformanceHistory::new();
+        history.record(&service(1, 10));
+
+        let earlier = to_sheffield(to_wakefield(day(2), "16:04", 0));
+        assert!(history.punctuality(&earlier, &crs("WKF")).is_none());
+    }
+
+    #[test]
+    fn keeps_only_recent_days() {
+        let history = PerformanceHistory::new();
+        let start = day(1);
+        for i in 0..(MAX_DAYS as i64 + 10) {
+            history.record(&to_sheffield(to_wakefield(
+                start + Duration::days(i),
+                "17:04",
+                0,
+            )));
+        }
+        let wakefield = history.punctuality(&service(1, 0), &crs("WKF")).unwrap();
+        assert_eq!(wakefield.days(), MAX_DAYS);
+    }
+
+    #[test]
+    fn store_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PerformanceStore::new(dir.path().join("performance.json"));
+        assert!(store.load().unwrap().is_empty());
+
+        let history = PerformanceHistory::new();
+        history.record(&service(1, 0));
+        history.record(&service(2, 25));
+        store.save(&history).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(
+            loaded.punctuality(&service(3, 0), &crs("WKF")),
+            history.punctuality(&service(3, 0), &crs("WKF"))
+        );
+    }
+}
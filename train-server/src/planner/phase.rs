@@ -0,0 +1,63 @@
+//! Which part of a search a provider call is made for.
+//!
+//! The planner runs each phase of a search (see [`SearchStats`]) inside
+//! [`in_phase`], so a provider that counts its calls (such as Darwin's
+//! instrumentation) can attribute them with [`current_phase`]. Code
+//! outside the planner that fetches boards of its own names its phase the
+//! same way. The innermost phase wins. Finding direct journeys makes no
+//! calls, so has no phase.
+//!
+//! [`SearchStats`]: super::SearchStats
+
+use std::future::Future;
+
+/// Phase of calls made outside [`in_phase`].
+pub const UNATTRIBUTED_PHASE: &str = "other";
+
+/// Fetching the destination's arrivals board and finding one-change
+/// journeys through it.
+pub const ONE_CHANGE_PHASE: &str = "one_change";
+
+/// Fetching departure boards at intermediate stations for two-change
+/// journeys.
+pub const TWO_CHANGE_PHASE: &str = "two_change";
+
+/// The BFS fallback.
+pub const BFS_PHASE: &str = "bfs";
+
+/// Looking for near misses when nothing reaches the destination.
+pub const NEAR_MISS_PHASE: &str = "near_miss";
+
+/// Fetching the departure board a board search starts from.
+pub const BOARD_PHASE: &str = "board";
+
+tokio::task_local! {
+    static PHASE: &'static str;
+}
+
+/// Attribute the provider calls made by `future` to `phase`.
+pub async fn in_phase<F: Future>(phase: &'static str, future: F) -> F::Output {
+    PHASE.scope(phase, future).await
+}
+
+/// The phase the current task's calls are attributed to.
+pub fn current_phase() -> &'static str {
+    PHASE.try_with(|p| *p).unwrap_or(UNATTRIBUTED_PHASE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn innermost_phase_wins() {
+        assert_eq!(current_phase(), UNATTRIBUTED_PHASE);
+        let phases = in_phase(BOARD_PHASE, async {
+            let outer = current_phase();
+            let inner = in_phase(BFS_PHASE, async { current_phase() }).await;
+            (outer, inner, current_phase())
+        })
+        .await;
+        assert_eq!(phases, (BOARD_PHASE, BFS_PHASE, BOARD_PHASE));
+    }
+}
@@ -0,0 +1,74 @@
+//! How complete the data behind a search was.
+//!
+//! The 2-change and BFS phases fetch many departure boards, and a failed
+//! fetch is treated as a station with no departures rather than failing the
+//! whole search. The journeys found are still real, but some may be
+//! missing, so the stations whose boards couldn't be fetched are reported
+//! alongside them. So are stations whose boards were cut short to keep
+//! the search within its memory budget (see [`super::budget`]).
+
+use std::collections::BTreeSet;
+
+use crate::domain::Crs;
+
+/// Stations whose data a search had to do without.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataQuality {
+    /// Stations whose departure boards couldn't be fetched.
+    pub unavailable_stations: BTreeSet<Crs>,
+
+    /// Stations whose departure boards were cut to their soonest services.
+    pub truncated_stations: BTreeSet<Crs>,
+}
+
+impl DataQuality {
+    /// Whether all the data the search asked for was available.
+    pub fn is_complete(&self) -> bool {
+        self.unavailable_stations.is_empty() && self.truncated_stations.is_empty()
+    }
+
+    /// Note that `station`'s departures couldn't be fetched.
+    pub(super) fn record_unavailable(&mut self, station: Crs) {
+        self.unavailable_stations.insert(station);
+    }
+
+    /// Note that `station`'s departures were cut short.
+    pub(super) fn record_truncated(&mut self, station: Crs) {
+        self.truncated_stations.insert(station);
+    }
+
+    /// Add the data quality of another search, e.g. one run concurrently
+    /// to another station of a group.
+    pub fn add(&mut self, other: &DataQuality) {
+        self.unavailable_stations
+            .extend(other.unavailable_stations.iter().copied());
+        self.truncated_stations
+            .extend(other.truncated_stations.iter().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_until_a_station_is_unavailable() {
+        let rdg = Crs::parse("RDG").unwrap();
+        let swi = Crs::parse("SWI").unwrap();
+
+        let mut quality = DataQuality::default();
+        assert!(quality.is_complete());
+
+        quality.record_unavailable(swi);
+        let mut other = DataQuality::default();
+        other.record_unavailable(rdg);
+        other.record_unavailable(swi);
+        quality.add(&other);
+
+        assert!(!quality.is_complete());
+        assert_eq!(
+            quality.unavailable_stations.into_iter().collect::<Vec<_>>(),
+            [rdg, swi]
+        );
+    }
+}
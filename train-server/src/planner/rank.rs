@@ -3,7 +3,12 @@
 //! Ranks journeys by a combination of factors to present the most useful
 //! options first.
 
-use crate::domain::Journey;
+use std::collections::{HashMap, HashSet};
+
+use chrono::Duration;
+
+use super::{DominanceCriterion, SearchConfig};
+use crate::domain::{CallIndex, Crs, Journey, Leg, RailTime, Segment, Service, ServiceIdentity};
 
 /// Rank journeys by preference.
 ///
@@ -11,12 +16,37 @@ use crate::domain::Journey;
 /// 1. Arrival time (earlier is better)
 /// 2. Number of changes (fewer is better)
 /// 3. Total duration (shorter is better)
+/// 4. Stations stopped at on the way (fewer is better)
+///
+/// If `config.stay_on_mins` is set, a journey that stays on its first train
+/// longer than another starting on the same train is ranked as if it
+/// arrived a minute earlier for each minute more on the train, up to that
+/// limit. Passengers would rather not change before they need to.
+///
+/// If `config.price_weight_mins` is set, each step up in a journey's price
+/// band (see [`crate::fares`]) ranks it as if it arrived that many minutes
+/// later.
+///
+/// If `config.max_per_interchange` is set, only that many journeys making
+/// their first change at any one station keep their place; the rest are
+/// moved, still in order, below all the others. Direct journeys are never
+/// limited.
+///
+/// Journeys still tied are ordered by their trains' Darwin IDs and calls, so
+/// the ranking doesn't depend on the order they were found in.
 ///
 /// Returns journeys sorted best-first.
-pub fn rank_journeys(mut journeys: Vec<Journey>) -> Vec<Journey> {
-    journeys.sort_by(|a, b| {
-        // Primary: arrival time
-        let arr_cmp = a.arrival_time().cmp(&b.arrival_time());
+pub fn rank_journeys(journeys: Vec<Journey>, config: &SearchConfig) -> Vec<Journey> {
+    let credits = stay_on_credits(&journeys, config.stay_on_allowance());
+    let mut ranked: Vec<(Duration, Journey)> = credits
+        .into_iter()
+        .zip(journeys)
+        .map(|(credit, journey)| (credit - config.price_penalty(&journey), journey))
+        .collect();
+    ranked.sort_by(|(a_credit, a), (b_credit, b)| {
+        // Primary: arrival time, less any credit for staying on and plus
+        // any penalty for price
+        let arr_cmp = (a.arrival_time() + -*a_credit).cmp(&(b.arrival_time() + -*b_credit));
         if arr_cmp != std::cmp::Ordering::Equal {
             return arr_cmp;
         }
@@ -28,10 +58,114 @@ pub fn rank_journeys(mut journeys: Vec<Journey>) -> Vec<Journey> {
         }
 
         // Tertiary: shorter duration
-        a.total_duration().cmp(&b.total_duration())
+        let duration_cmp = a.total_duration().cmp(&b.total_duration());
+        if duration_cmp != std::cmp::Ordering::Equal {
+            return duration_cmp;
+        }
+
+        // Then fewer stops on the way
+        let stops_cmp = a.stops_count().cmp(&b.stops_count());
+        if stops_cmp != std::cmp::Ordering::Equal {
+            return stops_cmp;
+        }
+
+        // Finally, by route, so ties don't depend on the order journeys
+        // were found in
+        RouteKey::of(a).cmp(&RouteKey::of(b))
     });
+    let journeys = ranked.into_iter().map(|(_, journey)| journey).collect();
+
+    match config.interchange_limit() {
+        Some(limit) => diversify(journeys, limit),
+        None => journeys,
+    }
+}
+
+/// How long a journey takes from `now`, for ranking journeys that start at
+/// different times.
+///
+/// This is the time until arrival (see [`Journey::duration_from`]), with
+/// the wait for the first train weighted by `config.wait_weight_pct` on
+/// top: passengers would rather be moving than stand on a platform.
+pub fn weighted_duration_from(journey: &Journey, now: RailTime, config: &SearchConfig) -> Duration {
+    journey.duration_from(now) + config.wait_penalty(journey.initial_wait(now))
+}
+
+/// The first leg of a journey, if it starts on a train.
+fn first_leg(journey: &Journey) -> Option<&Leg> {
+    match journey.segments().first()? {
+        Segment::Train(leg) => Some(leg),
+        Segment::Walk(_) => None,
+    }
+}
+
+/// The train a journey starts on, and where it's boarded.
+fn first_train(journey: &Journey) -> Option<(TrainKey, CallIndex)> {
+    let leg = first_leg(journey)?;
+    Some((TrainKey::of(leg.service()), leg.board_idx()))
+}
+
+/// How much longer `a` stays on its first train than `b`, if both start on
+/// the same train and `a` gets off it later.
+fn stays_on_longer(a: &Journey, b: &Journey) -> Option<Duration> {
+    if first_train(a)? != first_train(b)? {
+        return None;
+    }
+    let longer = first_leg(a)?
+        .arrival_time()
+        .signed_duration_since(first_leg(b)?.arrival_time());
+    (longer > Duration::zero()).then_some(longer)
+}
+
+/// Each journey's credit for staying on its first train: how much longer it
+/// stays on than the journey leaving that train soonest, up to `allowance`.
+fn stay_on_credits(journeys: &[Journey], allowance: Option<Duration>) -> Vec<Duration> {
+    let Some(allowance) = allowance else {
+        return vec![Duration::zero(); journeys.len()];
+    };
+
+    let mut soonest: HashMap<(TrainKey, CallIndex), &Journey> = HashMap::new();
+    for journey in journeys {
+        if let Some(train) = first_train(journey) {
+            let entry = soonest.entry(train).or_insert(journey);
+            if stays_on_longer(entry, journey).is_some() {
+                *entry = journey;
+            }
+        }
+    }
 
     journeys
+        .iter()
+        .map(|journey| {
+            first_train(journey)
+                .and_then(|train| stays_on_longer(journey, soonest[&train]))
+                .map_or(Duration::zero(), |longer| longer.min(allowance))
+        })
+        .collect()
+}
+
+/// Move journeys beyond the `limit`th through the same first interchange
+/// below the rest, keeping relative order within each part.
+fn diversify(journeys: Vec<Journey>, limit: usize) -> Vec<Journey> {
+    let mut seen: HashMap<Crs, usize> = HashMap::new();
+    let (mut kept, mut demoted) = (Vec::with_capacity(journeys.len()), Vec::new());
+
+    for journey in journeys {
+        let interchange = journey.changes().next().map(|c| *c.from.alight_station());
+        let over = interchange.is_some_and(|crs| {
+            let count = seen.entry(crs).or_default();
+            *count += 1;
+            *count > limit
+        });
+        if over {
+            demoted.push(journey);
+        } else {
+            kept.push(journey);
+        }
+    }
+
+    kept.extend(demoted);
+    kept
 }
 
 /// Remove dominated journeys.
@@ -40,36 +174,68 @@ pub fn rank_journeys(mut journeys: Vec<Journey>) -> Vec<Journey> {
 /// - Arrives at the same time or earlier
 /// - Has the same or fewer changes
 /// - Has the same or shorter duration
+/// - Has the same or less walking
+/// - Is in the same or a lower price band
+///
+/// and is strictly better on at least one of them. Only the dimensions in
+/// `config.dominance` are compared, besides arrival time. Walking isn't
+/// by default, so a journey that only walks less is hidden; a user who
+/// would rather not walk can opt in to keep it for ranking.
 ///
-/// This prunes journeys that are strictly worse than others.
-pub fn remove_dominated(journeys: Vec<Journey>) -> Vec<Journey> {
+/// This prunes journeys that are strictly worse than others. A journey
+/// staying on its first train longer than the other is kept if it arrives
+/// no more than `config.stay_on_mins` later, so that ranking can prefer it
+/// (see [`rank_journeys`]). Prices only differ with
+/// `config.price_weight_mins` set.
+pub fn remove_dominated(journeys: Vec<Journey>, config: &SearchConfig) -> Vec<Journey> {
     if journeys.len() <= 1 {
         return journeys;
     }
 
-    let mut result = Vec::with_capacity(journeys.len());
+    let allowance = config.stay_on_allowance();
+    let criteria = &config.dominance;
+    let dominates = |a: &Journey, b: &Journey| {
+        let mut better = a.arrival_time() < b.arrival_time();
+        let mut no_worse = a.arrival_time() <= b.arrival_time();
+        let mut compare = |criterion, ordering: std::cmp::Ordering| {
+            if criteria.contains(criterion) {
+                better |= ordering.is_lt();
+                no_worse &= ordering.is_le();
+            }
+        };
+        compare(
+            DominanceCriterion::Changes,
+            a.change_count().cmp(&b.change_count()),
+        );
+        compare(
+            DominanceCriterion::Duration,
+            a.total_duration().cmp(&b.total_duration()),
+        );
+        compare(
+            DominanceCriterion::Walking,
+            a.total_walk_duration().cmp(&b.total_walk_duration()),
+        );
+        compare(
+            DominanceCriterion::Price,
+            config.price_penalty(a).cmp(&config.price_penalty(b)),
+        );
+        // Must be strictly better in at least one dimension
+        no_worse
+            && better
+            && !allowance.is_some_and(|allowance| {
+                stays_on_longer(b, a).is_some()
+                    && b.arrival_time().signed_duration_since(a.arrival_time()) <= allowance
+            })
+    };
+
+    let mut result: Vec<Journey> = Vec::with_capacity(journeys.len());
 
     for journey in journeys {
-        let dominated = result.iter().any(|existing: &Journey| {
-            existing.arrival_time() <= journey.arrival_time()
-                && existing.change_count() <= journey.change_count()
-                && existing.total_duration() <= journey.total_duration()
-                // Must be strictly better in at least one dimension
-                && (existing.arrival_time() < journey.arrival_time()
-                    || existing.change_count() < journey.change_count()
-                    || existing.total_duration() < journey.total_duration())
-        });
+        let dominated = result.iter().any(|existing| dominates(existing, &journey));
 
         if !dominated {
             // Also remove any existing journeys dominated by this one
-            result.retain(|existing: &Journey| {
-                !(journey.arrival_time() <= existing.arrival_time()
-                    && journey.change_count() <= existing.change_count()
-                    && journey.total_duration() <= existing.total_duration()
-                    && (journey.arrival_time() < existing.arrival_time()
-                        || journey.change_count() < existing.change_count()
-                        || journey.total_duration() < existing.total_duration()))
-            });
+            result.retain(|existing| !dominates(&journey, existing));
             result.push(journey);
         }
     }
@@ -79,18 +245,32 @@ pub fn remove_dominated(journeys: Vec<Journey>) -> Vec<Journey> {
 
 /// Deduplicate journeys that are effectively identical.
 ///
-/// Two journeys are considered duplicates if they:
-/// - Arrive at the same time
-/// - Depart at the same time
-/// - Have the same number of changes
+/// Two journeys are considered duplicates if they take the same trains,
+/// boarding and alighting each at the same calling point, with the same
+/// walks between them. Trains are told apart by their [`ServiceIdentity`]
+/// (origin, booked departure and terminus) rather than their Darwin IDs,
+/// which differ between boards, so the same train found on two stations'
+/// boards is recognised, and two different trains that happen to call at
+/// the same times are both kept.
+///
+/// When duplicates exist, keeps the earliest-arriving, then the one with
+/// shortest duration.
 ///
-/// When duplicates exist, keeps the one with shortest duration.
-pub fn deduplicate(mut journeys: Vec<Journey>) -> Vec<Journey> {
+/// Journeys that then differ only in which departure of a frequent service
+/// they use (same stations, operators, calling patterns and walks) are
+/// grouped, and each run of a group arriving at regular intervals no more
+/// than `config.frequent_max_headway_mins` apart is collapsed into its
+/// earliest-arriving journey, annotated with the typical headway (see
+/// [`Journey::headway`]). Runs shorter than `config.frequent_min_departures`
+/// are left alone.
+pub fn deduplicate(mut journeys: Vec<Journey>, config: &SearchConfig) -> Vec<Journey> {
     if journeys.len() <= 1 {
         return journeys;
     }
 
-    // Sort by (arrival, departure, changes, duration) to group duplicates
+    // Sort by (arrival, departure, changes, duration) so the best of each
+    // group of duplicates comes first, then by route so the result doesn't
+    // depend on the order journeys were found in
     journeys.sort_by(|a, b| {
         let arr = a.arrival_time().cmp(&b.arrival_time());
         if arr != std::cmp::Ordering::Equal {
@@ -104,33 +284,230 @@ pub fn deduplicate(mut journeys: Vec<Journey>) -> Vec<Journey> {
         if changes != std::cmp::Ordering::Equal {
             return changes;
         }
-        a.total_duration().cmp(&b.total_duration())
+        let duration = a.total_duration().cmp(&b.total_duration());
+        if duration != std::cmp::Ordering::Equal {
+            return duration;
+        }
+        RouteKey::of(a).cmp(&RouteKey::of(b))
     });
 
-    // Keep first of each (arrival, departure, changes) group
-    let mut result = Vec::with_capacity(journeys.len());
-    let mut last_key: Option<(_, _, _)> = None;
+    // Keep the first journey on each route
+    let mut seen = HashSet::new();
+    let result: Vec<Journey> = journeys
+        .into_iter()
+        .filter(|journey| seen.insert(RouteKey::of(journey)))
+        .collect();
 
+    match config.frequent_max_headway() {
+        Some(max_headway) => collapse_frequent(result, max_headway, config.frequent_min_departures),
+        None => result,
+    }
+}
+
+/// Combine journeys that take the same trains and only finish differently.
+///
+/// Journeys are the same up to their finish if they take the same trains,
+/// boarding the last one at the same calling point, with the same walks
+/// between them. Of each such group the earliest-arriving is kept (then the
+/// one walking least), and the rest become its alternative endings (see
+/// [`Journey::alternative_endings`]), earliest-arriving first: getting off
+/// the last train somewhere else, perhaps walking from there.
+///
+/// Only sensible for journeys to equivalent destinations, such as the
+/// stations of a group, or a journey could stand in for one to somewhere
+/// else entirely.
+pub fn combine_endings(journeys: Vec<Journey>) -> Vec<Journey> {
+    let mut index: HashMap<(RouteKey, TrainKey, CallIndex), usize> = HashMap::new();
+    let mut groups: Vec<Vec<Journey>> = Vec::new();
     for journey in journeys {
-        let key = (
-            journey.arrival_time(),
-            journey.departure_time(),
-            journey.change_count(),
-        );
+        let i = *index.entry(approach(&journey)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(journey);
+    }
 
-        if last_key != Some(key) {
-            result.push(journey);
-            last_key = Some(key);
+    groups
+        .into_iter()
+        .filter_map(|mut group| {
+            group.sort_by(|a, b| {
+                a.arrival_time()
+                    .cmp(&b.arrival_time())
+                    .then_with(|| a.total_walk_duration().cmp(&b.total_walk_duration()))
+                    .then_with(|| RouteKey::of(a).cmp(&RouteKey::of(b)))
+            });
+            let mut group = group.into_iter();
+            let best = group.next()?;
+            let mut endings = best.alternative_endings().to_vec();
+            for other in group {
+                endings.push(other.ending());
+                endings.extend_from_slice(other.alternative_endings());
+            }
+            endings.sort_by_key(|ending| ending.arrival_time());
+            Some(best.with_alternative_endings(endings))
+        })
+        .collect()
+}
+
+/// How a journey gets to its last train: the route before it, and the
+/// train and where it's boarded.
+fn approach(journey: &Journey) -> (RouteKey, TrainKey, CallIndex) {
+    let segments = journey.segments();
+    // Safe: there is always a train
+    let last = segments.iter().rposition(Segment::is_train).unwrap();
+    let leg = journey.legs().last().unwrap();
+    (
+        RouteKey::of_segments(&segments[..last]),
+        TrainKey::of(leg.service()),
+        leg.board_idx(),
+    )
+}
+
+/// A train, recognised whichever board it was fetched from.
+///
+/// Darwin service IDs only hold within the board they came from, so the
+/// same train fetched from two boards is matched by its
+/// [`ServiceIdentity`]; only a train without one falls back to its ID.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) enum TrainKey {
+    Identity(ServiceIdentity),
+    DarwinId(String),
+}
+
+impl TrainKey {
+    fn of(service: &Service) -> Self {
+        match ServiceIdentity::of(service) {
+            Some(identity) => Self::Identity(identity),
+            None => Self::DarwinId(service.service_ref.darwin_id.clone()),
         }
     }
+}
 
-    result
+/// One step of a [`RouteKey`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) enum RouteStep {
+    /// A train, with where it's boarded and alighted. The alighting station
+    /// tells apart portions of a dividing train, which share an ID but not
+    /// calls after the division.
+    Train(TrainKey, CallIndex, CallIndex, Crs),
+    /// A walk between two stations
+    Walk(Crs, Crs),
+}
+
+/// What makes two journeys the same, whatever their real-time estimates.
+/// Ordered by the route's steps, to break ties deterministically.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) struct RouteKey(Vec<RouteStep>);
+
+impl RouteKey {
+    fn of(journey: &Journey) -> Self {
+        Self::of_segments(journey.segments())
+    }
+
+    /// The key of a journey, or part of one, made of `segments`.
+    pub(super) fn of_segments(segments: &[Segment]) -> Self {
+        let steps = segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Train(leg) => RouteStep::Train(
+                    TrainKey::of(leg.service()),
+                    leg.board_idx(),
+                    leg.alight_idx(),
+                    *leg.alight_station(),
+                ),
+                Segment::Walk(walk) => RouteStep::Walk(walk.from, walk.to),
+            })
+            .collect();
+        Self(steps)
+    }
+}
+
+/// The stations, operators and walks a journey uses, with the stations
+/// each train stops at on the way, ignoring times.
+type Pattern = Vec<(Crs, Crs, Option<String>, Vec<Crs>)>;
+
+fn pattern(journey: &Journey) -> Pattern {
+    journey
+        .segments()
+        .iter()
+        .map(|segment| match segment {
+            Segment::Train(leg) => (
+                *leg.board_station(),
+                *leg.alight_station(),
+                Some(leg.service().operator.clone()),
+                leg.intermediate_calls()
+                    .filter(|call| !call.is_cancelled)
+                    .map(|call| call.station)
+                    .collect(),
+            ),
+            Segment::Walk(walk) => (walk.from, walk.to, None, Vec::new()),
+        })
+        .collect()
+}
+
+/// Collapse runs of same-pattern journeys that arrive at regular, short
+/// intervals. Expects `journeys` sorted by arrival time.
+fn collapse_frequent(
+    journeys: Vec<Journey>,
+    max_headway: Duration,
+    min_departures: usize,
+) -> Vec<Journey> {
+    let mut groups: HashMap<Pattern, Vec<usize>> = HashMap::new();
+    for (i, journey) in journeys.iter().enumerate() {
+        groups.entry(pattern(journey)).or_default().push(i);
+    }
+
+    // Index of each collapsed group's representative, with its headway;
+    // the other members are dropped.
+    let mut representatives: HashMap<usize, Duration> = HashMap::new();
+    let mut dropped = vec![false; journeys.len()];
+
+    let regular = |a: &usize, b: &usize| {
+        let gap = journeys[*b]
+            .arrival_time()
+            .signed_duration_since(journeys[*a].arrival_time());
+        gap > Duration::zero() && gap <= max_headway
+    };
+    for run in groups
+        .values()
+        .flat_map(|members| members.chunk_by(regular))
+    {
+        if run.len() < min_departures.max(2) {
+            continue;
+        }
+
+        let first = run[0];
+        let last = run[run.len() - 1];
+        let span = journeys[last]
+            .arrival_time()
+            .signed_duration_since(journeys[first].arrival_time())
+            .num_minutes();
+        let intervals = (run.len() - 1) as i64;
+        let headway = Duration::minutes((span + intervals / 2) / intervals);
+
+        representatives.insert(first, headway);
+        for &member in &run[1..] {
+            dropped[member] = true;
+        }
+    }
+
+    journeys
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !dropped[*i])
+        .map(|(i, journey)| match representatives.get(&i) {
+            Some(headway) => journey.with_headway(*headway),
+            None => journey,
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::domain::{Call, CallIndex, Crs, Leg, RailTime, Segment, Service, ServiceRef};
+    use crate::planner::DominanceCriteria;
+    use crate::testing::ServiceBuilder;
     use chrono::NaiveDate;
     use std::sync::Arc;
 
@@ -179,6 +556,7 @@ mod tests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -215,7 +593,7 @@ mod tests {
         let j1 = make_journey(vec![(svc1, 0, 1)]);
         let j2 = make_journey(vec![(svc2, 0, 1)]);
 
-        let ranked = rank_journeys(vec![j2.clone(), j1.clone()]);
+        let ranked = rank_journeys(vec![j2.clone(), j1.clone()], &SearchConfig::default());
 
         // Earlier arrival should be first
         assert_eq!(ranked[0].arrival_time(), time("10:30"));
@@ -251,13 +629,87 @@ mod tests {
         let j_direct = make_journey(vec![(direct, 0, 1)]);
         let j_change = make_journey(vec![(leg1, 0, 1), (leg2, 0, 1)]);
 
-        let ranked = rank_journeys(vec![j_change.clone(), j_direct.clone()]);
+        let ranked = rank_journeys(
+            vec![j_change.clone(), j_direct.clone()],
+            &SearchConfig::default(),
+        );
 
         // Same arrival, but direct has fewer changes
         assert_eq!(ranked[0].change_count(), 0);
         assert_eq!(ranked[1].change_count(), 1);
     }
 
+    #[test]
+    fn rank_prefers_fewer_stops_when_times_tie() {
+        let stopping = make_service(
+            "A",
+            &[
+                ("PAD", "Paddington", "", "10:00"),
+                ("SLO", "Slough", "10:15", "10:16"),
+                ("RDG", "Reading", "10:30", ""),
+            ],
+        );
+        let fast = make_service(
+            "B",
+            &[
+                ("PAD", "Paddington", "", "10:00"),
+                ("RDG", "Reading", "10:30", ""),
+            ],
+        );
+
+        let ranked = rank_journeys(
+            vec![
+                make_journey(vec![(stopping, 0, 2)]),
+                make_journey(vec![(fast, 0, 1)]),
+            ],
+            &SearchConfig::default(),
+        );
+
+        assert_eq!(ranked[0].stops_count(), 0);
+        assert_eq!(ranked[1].stops_count(), 1);
+    }
+
+    #[test]
+    fn rank_breaks_full_ties_by_train() {
+        // Two trains at the same times to Reading, one going on to Swindon,
+        // given in either order
+        let train = |id, terminus: &[(&str, &str, &str, &str)]| {
+            let calls = [
+                [
+                    ("PAD", "Paddington", "", "10:00"),
+                    ("RDG", "Reading", "10:30", "10:32"),
+                ]
+                .as_slice(),
+                terminus,
+            ]
+            .concat();
+            make_journey(vec![(make_service(id, &calls), 0, 1)])
+        };
+        let ids = |journeys: Vec<Journey>| -> Vec<String> {
+            journeys
+                .iter()
+                .map(|j| {
+                    j.legs()
+                        .next()
+                        .unwrap()
+                        .service()
+                        .service_ref
+                        .darwin_id
+                        .clone()
+                })
+                .collect()
+        };
+        let config = SearchConfig::default();
+
+        let a = || train("A", &[]);
+        let b = || train("B", &[("SWI", "Swindon", "11:00", "")]);
+
+        let ranked = rank_journeys(vec![b(), a()], &config);
+        assert_eq!(ids(ranked), ["A", "B"]);
+        let ranked = rank_journeys(vec![a(), b()], &config);
+        assert_eq!(ids(ranked), ["A", "B"]);
+    }
+
     #[test]
     fn remove_dominated_keeps_pareto_optimal() {
         // Journey A: arrives 10:30, 0 changes
@@ -297,7 +749,7 @@ mod tests {
         let j_b = make_journey(vec![(svc_b, 0, 1)]);
         let j_c = make_journey(vec![(svc_c1, 0, 1), (svc_c2, 0, 1)]);
 
-        let result = remove_dominated(vec![j_a, j_b, j_c]);
+        let result = remove_dominated(vec![j_a, j_b, j_c], &SearchConfig::default());
 
         // B should be removed (dominated by A)
         // A and C should remain (neither dominates the other)
@@ -305,8 +757,9 @@ mod tests {
     }
 
     #[test]
-    fn deduplicate_same_times() {
-        // Two journeys with same arrival/departure/changes
+    fn deduplicate_keeps_different_trains_at_same_times() {
+        // Two trains with the same arrival/departure/changes, going on to
+        // different places
         let svc1 = make_service(
             "X",
             &[
@@ -318,24 +771,502 @@ mod tests {
             "Y",
             &[
                 ("PAD", "Paddington", "", "10:00"),
-                ("RDG", "Reading", "10:30", ""),
+                ("RDG", "Reading", "10:30", "10:32"),
+                ("SWI", "Swindon", "11:00", ""),
             ],
         );
 
         let j1 = make_journey(vec![(svc1, 0, 1)]);
         let j2 = make_journey(vec![(svc2, 0, 1)]);
 
-        let result = deduplicate(vec![j1, j2]);
+        let result = deduplicate(vec![j1, j2], &SearchConfig::default());
+
+        assert_eq!(result.len(), 2);
+    }
+
+    #[test]
+    fn deduplicate_same_train_from_two_boards() {
+        // Each board gives the train its own Darwin ID
+        let train = |id: &str| {
+            ServiceBuilder::new(id)
+                .call("PAD")
+                .dep("10:00")
+                .call("RDG")
+                .arr("10:25")
+                .dep("10:27")
+                .call("SWI")
+                .arr("10:55")
+        };
+        let from_paddington = train("PADboard1").build();
+        // Fetched a little later, with a newer estimate
+        let from_reading = train("RDGboard7").expected_arr("10:57").board_at(1).build();
+
+        let result = deduplicate(
+            vec![
+                make_journey(vec![(from_reading, 0, 2)]),
+                make_journey(vec![(from_paddington, 0, 2)]),
+            ],
+            &SearchConfig::default(),
+        );
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].arrival_time(), time("10:55"));
+    }
+
+    /// Direct PAD -> RDG journeys on the given operator, one per departure.
+    fn shuttle(operator: &str, departures: &[&str]) -> Vec<Journey> {
+        departures
+            .iter()
+            .enumerate()
+            .map(|(i, dep)| {
+                let dep = time(dep);
+                let arr = dep + chrono::Duration::minutes(25);
+                let mut svc = (*make_service(
+                    &format!("{operator}{i}"),
+                    &[
+                        ("PAD", "Paddington", "", &dep.to_string()),
+                        ("RDG", "Reading", &arr.to_string(), ""),
+                    ],
+                ))
+                .clone();
+                svc.operator = operator.to_string();
+                make_journey(vec![(Arc::new(svc), 0, 1)])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deduplicate_collapses_frequent_service() {
+        let journeys = shuttle("Elizabeth", &["10:00", "10:10", "10:20", "10:30"]);
+
+        let result = deduplicate(journeys, &SearchConfig::default());
 
-        // Should keep only one
         assert_eq!(result.len(), 1);
+        assert_eq!(result[0].departure_time(), time("10:00"));
+        assert_eq!(result[0].headway(), Some(chrono::Duration::minutes(10)));
+    }
+
+    #[test]
+    fn deduplicate_keeps_infrequent_or_few_departures() {
+        let config = SearchConfig::default();
+
+        let irregular = shuttle("GWR", &["10:00", "10:10", "10:40"]);
+        let result = deduplicate(irregular, &config);
+        assert_eq!(result.len(), 3);
+        assert!(result.iter().all(|j| j.headway().is_none()));
+
+        let few = shuttle("GWR", &["10:00", "10:10"]);
+        assert_eq!(deduplicate(few, &config).len(), 2);
+    }
+
+    /// PAD -> RDG journeys stopping at `stops` on the way, five minutes
+    /// apart, one per departure.
+    fn stopping_at(stops: &[&str], departures: &[&str]) -> Vec<Journey> {
+        departures
+            .iter()
+            .enumerate()
+            .map(|(i, dep)| {
+                let mut at = time(dep);
+                let mut service = ServiceBuilder::new(format!("{}{i}", stops.join("")))
+                    .call("PAD")
+                    .dep(&at.to_string());
+                for stop in stops {
+                    at = at + chrono::Duration::minutes(5);
+                    service = service.call(stop).arr(&at.to_string()).dep(&at.to_string());
+                }
+                at = at + chrono::Duration::minutes(20);
+                let service = service.call("RDG").arr(&at.to_string()).build();
+                make_journey(vec![(service, 0, stops.len() + 1)])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deduplicate_groups_by_calling_pattern() {
+        let mut journeys = stopping_at(&[], &["10:00", "10:10", "10:20"]);
+        journeys.extend(stopping_at(&["EAL", "SLO"], &["10:05", "10:15", "10:25"]));
+        journeys.sort_by_key(Journey::arrival_time);
+
+        let result = deduplicate(journeys, &SearchConfig::default());
+
+        // The fast and stopping trains are each collapsed, not into one
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].departure_time(), time("10:00"));
+        assert_eq!(result[1].departure_time(), time("10:05"));
+        assert!(
+            result
+                .iter()
+                .all(|j| j.headway() == Some(chrono::Duration::minutes(10)))
+        );
+    }
+
+    #[test]
+    fn deduplicate_collapses_regular_runs_of_a_group() {
+        let journeys = shuttle(
+            "Elizabeth",
+            &[
+                "10:00", "10:10", "10:20", "10:30", "11:30", "11:40", "11:50",
+            ],
+        );
+
+        let result = deduplicate(journeys, &SearchConfig::default());
+
+        // The gap splits the service into two runs, each collapsed
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].departure_time(), time("10:00"));
+        assert_eq!(result[1].departure_time(), time("11:30"));
+        assert!(
+            result
+                .iter()
+                .all(|j| j.headway() == Some(chrono::Duration::minutes(10)))
+        );
+    }
+
+    #[test]
+    fn deduplicate_groups_by_operator() {
+        let mut journeys = shuttle("Elizabeth", &["10:00", "10:10", "10:20"]);
+        journeys.extend(shuttle("GWR", &["10:05"]));
+
+        let result = deduplicate(journeys, &SearchConfig::default());
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].headway(), Some(chrono::Duration::minutes(10)));
+        assert_eq!(result[1].headway(), None);
+    }
+
+    #[test]
+    fn deduplicate_collapse_can_be_disabled() {
+        let config = SearchConfig {
+            frequent_max_headway_mins: 0,
+            ..SearchConfig::default()
+        };
+        let journeys = shuttle("Elizabeth", &["10:00", "10:10", "10:20", "10:30"]);
+
+        assert_eq!(deduplicate(journeys, &config).len(), 4);
+    }
+
+    /// PAD -> BRI journeys changing at `via`, departing at the given times.
+    fn via(interchange: &str, departures: &[&str]) -> Vec<Journey> {
+        departures
+            .iter()
+            .enumerate()
+            .map(|(i, dep)| {
+                let dep = time(dep);
+                let change = (dep + chrono::Duration::minutes(30)).to_string();
+                let onward = (dep + chrono::Duration::minutes(40)).to_string();
+                let arr = (dep + chrono::Duration::minutes(90)).to_string();
+                let first = make_service(
+                    &format!("{interchange}{i}a"),
+                    &[
+                        ("PAD", "Paddington", "", &dep.to_string()),
+                        (interchange, interchange, &change, ""),
+                    ],
+                );
+                let second = make_service(
+                    &format!("{interchange}{i}b"),
+                    &[
+                        (interchange, interchange, "", &onward),
+                        ("BRI", "Bristol", &arr, ""),
+                    ],
+                );
+                make_journey(vec![(first, 0, 1), (second, 0, 1)])
+            })
+            .collect()
+    }
+
+    fn interchanges(journeys: &[Journey]) -> Vec<String> {
+        journeys
+            .iter()
+            .map(|j| match j.changes().next() {
+                Some(change) => change.from.alight_station().to_string(),
+                None => "direct".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rank_limits_journeys_per_interchange() {
+        let mut journeys = via("RDG", &["10:00", "10:05", "10:10", "10:15", "10:20"]);
+        journeys.extend(via("SWI", &["10:30"]));
+        journeys.extend(shuttle("GWR", &["11:40"]));
+        let config = SearchConfig {
+            max_per_interchange: 2,
+            ..SearchConfig::default()
+        };
+
+        let ranked = rank_journeys(journeys, &config);
+
+        assert_eq!(
+            interchanges(&ranked),
+            ["RDG", "RDG", "SWI", "direct", "RDG", "RDG", "RDG"]
+        );
+        // Demoted journeys keep their order
+        assert_eq!(ranked[4].departure_time(), time("10:10"));
+        assert_eq!(ranked[6].departure_time(), time("10:20"));
+    }
+
+    #[test]
+    fn rank_interchange_limit_can_be_disabled() {
+        let mut journeys = via("RDG", &["10:00", "10:05", "10:10", "10:15"]);
+        journeys.extend(via("SWI", &["10:30"]));
+        let config = SearchConfig {
+            max_per_interchange: 0,
+            ..SearchConfig::default()
+        };
+
+        let ranked = rank_journeys(journeys, &config);
+
+        assert_eq!(interchanges(&ranked), ["RDG", "RDG", "RDG", "RDG", "SWI"]);
+    }
+
+    /// Journeys from the 10:00 Paddington to Bristol stopping service,
+    /// changing at Reading or Swindon onto trains reaching Bristol at the
+    /// given times.
+    fn stay_on_journeys(via_reading: &str, via_swindon: &str) -> (Journey, Journey) {
+        let stopper = make_service(
+            "STOPPER",
+            &[
+                ("PAD", "Paddington", "", "10:00"),
+                ("RDG", "Reading", "10:25", "10:27"),
+                ("SWI", "Swindon", "10:55", "10:57"),
+                ("BRI", "Bristol", "11:45", ""),
+            ],
+        );
+        let from_reading = make_service(
+            "FAST",
+            &[
+                ("RDG", "Reading", "", "10:35"),
+                ("BRI", "Bristol", via_reading, ""),
+            ],
+        );
+        let from_swindon = make_service(
+            "SEMI",
+            &[
+                ("SWI", "Swindon", "", "11:05"),
+                ("BRI", "Bristol", via_swindon, ""),
+            ],
+        );
+        (
+            make_journey(vec![(stopper.clone(), 0, 1), (from_reading, 0, 1)]),
+            make_journey(vec![(stopper, 0, 2), (from_swindon, 0, 1)]),
+        )
+    }
+
+    fn first_change(journey: &Journey) -> String {
+        journey
+            .changes()
+            .next()
+            .unwrap()
+            .from
+            .alight_station()
+            .to_string()
+    }
+
+    #[test]
+    fn rank_prefers_staying_on_when_arrivals_close() {
+        let (reading, swindon) = stay_on_journeys("11:28", "11:30");
+
+        let ranked = rank_journeys(
+            vec![reading.clone(), swindon.clone()],
+            &SearchConfig::default(),
+        );
+        assert_eq!(first_change(&ranked[0]), "SWI");
+
+        let changes_early = SearchConfig {
+            stay_on_mins: 0,
+            ..SearchConfig::default()
+        };
+        let ranked = rank_journeys(vec![reading, swindon], &changes_early);
+        assert_eq!(first_change(&ranked[0]), "RDG");
+    }
+
+    #[test]
+    fn rank_staying_on_only_excuses_allowance() {
+        let (reading, swindon) = stay_on_journeys("11:20", "11:30");
+
+        let ranked = rank_journeys(vec![swindon, reading], &SearchConfig::default());
+
+        assert_eq!(first_change(&ranked[0]), "RDG");
+    }
+
+    /// The same 25-minute trip in the morning peak (a Friday) and just
+    /// after it, when it's a price band cheaper.
+    fn peak_and_off_peak() -> (Journey, Journey) {
+        let peak = make_service(
+            "PEAK",
+            &[
+                ("PAD", "Paddington", "", "09:00"),
+                ("RDG", "Reading", "09:25", ""),
+            ],
+        );
+        let off_peak = make_service(
+            "OFFPEAK",
+            &[
+                ("PAD", "Paddington", "", "09:35"),
+                ("RDG", "Reading", "10:00", ""),
+            ],
+        );
+        (
+            make_journey(vec![(peak, 0, 1)]),
+            make_journey(vec![(off_peak, 0, 1)]),
+        )
+    }
+
+    #[test]
+    fn price_weight_ranks_cheaper_journeys_higher() {
+        let (peak, off_peak) = peak_and_off_peak();
+
+        let ranked = rank_journeys(
+            vec![off_peak.clone(), peak.clone()],
+            &SearchConfig::default(),
+        );
+        assert_eq!(ranked[0].departure_time(), peak.departure_time());
+
+        // A band's worth more than the 35 minutes' later arrival
+        let budget = SearchConfig {
+            price_weight_mins: 60,
+            ..SearchConfig::default()
+        };
+        let ranked = rank_journeys(vec![peak.clone(), off_peak.clone()], &budget);
+        assert_eq!(ranked[0].departure_time(), off_peak.departure_time());
+
+        // The later journey is only worth keeping for being cheaper
+        assert_eq!(
+            remove_dominated(
+                vec![peak.clone(), off_peak.clone()],
+                &SearchConfig::default()
+            )
+            .len(),
+            1
+        );
+        assert_eq!(remove_dominated(vec![peak, off_peak], &budget).len(), 2);
+    }
+
+    #[test]
+    fn staying_on_within_allowance_is_not_dominated() {
+        let (reading, swindon) = stay_on_journeys("11:28", "11:30");
+        let kept = remove_dominated(
+            vec![reading.clone(), swindon.clone()],
+            &SearchConfig::default(),
+        );
+        assert_eq!(kept.len(), 2);
+
+        let changes_early = SearchConfig {
+            stay_on_mins: 0,
+            ..SearchConfig::default()
+        };
+        let kept = remove_dominated(vec![reading, swindon], &changes_early);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(first_change(&kept[0]), "RDG");
+
+        let (reading, swindon) = stay_on_journeys("11:20", "11:30");
+        let kept = remove_dominated(vec![reading, swindon], &SearchConfig::default());
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[test]
+    fn walking_is_compared_when_opted_into() {
+        let to_stp = make_service(
+            "EM",
+            &[
+                ("BDM", "Bedford", "", "10:00"),
+                ("STP", "St Pancras", "10:30", ""),
+            ],
+        );
+        let to_kgx = make_service(
+            "GN",
+            &[
+                ("BDM", "Bedford", "", "10:00"),
+                ("KGX", "Kings Cross", "10:45", ""),
+            ],
+        );
+        let walking = Journey::new(vec![
+            Segment::Train(Leg::new(to_stp, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(crate::domain::Walk::new(
+                crs("STP"),
+                crs("KGX"),
+                Duration::minutes(10),
+            )),
+        ])
+        .unwrap();
+        let direct = make_journey(vec![(to_kgx, 0, 1)]);
+
+        let kept = remove_dominated(
+            vec![walking.clone(), direct.clone()],
+            &SearchConfig::default(),
+        );
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].final_walk().is_some());
+
+        let rather_not_walk = SearchConfig {
+            dominance: DominanceCriteria::default().with(DominanceCriterion::Walking),
+            ..SearchConfig::default()
+        };
+        let kept = remove_dominated(vec![walking, direct], &rather_not_walk);
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn journeys_finishing_differently_are_combined() {
+        let thameslink = make_service(
+            "TL",
+            &[
+                ("BDM", "Bedford", "", "09:00"),
+                ("STP", "St Pancras", "10:00", "10:02"),
+                ("ZFD", "Farringdon", "10:06", "10:07"),
+                ("BFR", "Blackfriars", "10:12", ""),
+            ],
+        );
+        let other = make_service(
+            "EM",
+            &[
+                ("BDM", "Bedford", "", "09:05"),
+                ("STP", "St Pancras", "10:01", ""),
+            ],
+        );
+        let leg = |service: &Arc<Service>, alight| {
+            Segment::Train(Leg::new(service.clone(), CallIndex(0), CallIndex(alight)).unwrap())
+        };
+        let walk = |from, to, mins| {
+            Segment::Walk(crate::domain::Walk::new(
+                crs(from),
+                crs(to),
+                Duration::minutes(mins),
+            ))
+        };
+        let to_kgx = Journey::new(vec![leg(&thameslink, 1), walk("STP", "KGX", 5)]).unwrap();
+        let to_blackfriars = Journey::new(vec![leg(&thameslink, 3)]).unwrap();
+        let to_farringdon = Journey::new(vec![leg(&thameslink, 2)]).unwrap();
+        let other_train = Journey::new(vec![leg(&other, 1)]).unwrap();
+
+        let combined = combine_endings(vec![
+            to_blackfriars,
+            to_kgx,
+            other_train.clone(),
+            to_farringdon,
+        ]);
+
+        assert_eq!(combined.len(), 2);
+        let kept = &combined[0];
+        assert_eq!(*kept.destination(), crs("KGX"));
+        let endings: Vec<(Crs, RailTime)> = kept
+            .alternative_endings()
+            .iter()
+            .map(|e| (*e.destination(), e.arrival_time()))
+            .collect();
+        assert_eq!(
+            endings,
+            [(crs("ZFD"), time("10:06")), (crs("BFR"), time("10:12"))]
+        );
+        assert!(combined[1].alternative_endings().is_empty());
+        assert_eq!(RouteKey::of(&combined[1]), RouteKey::of(&other_train));
     }
 
     #[test]
     fn empty_input() {
-        assert!(rank_journeys(vec![]).is_empty());
-        assert!(remove_dominated(vec![]).is_empty());
-        assert!(deduplicate(vec![]).is_empty());
+        assert!(rank_journeys(vec![], &SearchConfig::default()).is_empty());
+        assert!(remove_dominated(vec![], &SearchConfig::default()).is_empty());
+        assert!(deduplicate(vec![], &SearchConfig::default()).is_empty());
     }
 }
 
@@ -385,6 +1316,7 @@ mod proptests {
             operator_code: None,
             calls: vec![origin_call, dest_call],
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
@@ -430,6 +1362,7 @@ mod proptests {
             operator_code: None,
             calls: vec![s1_origin, s1_dest],
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         // Second service: RDG -> BRI
@@ -446,6 +1379,7 @@ mod proptests {
             operator_code: None,
             calls: vec![s2_origin, s2_dest],
             board_station_idx: CallIndex(0),
+            formation: None,
         });
 
         let leg1 = Leg::new(svc1, CallIndex(0), CallIndex(1)).unwrap();
@@ -501,7 +1435,12 @@ mod proptests {
     proptest! {
         #[test]
         fn rank_journeys_is_sorted(journeys in journeys_strategy()) {
-            let ranked = rank_journeys(journeys);
+            let config = SearchConfig {
+                max_per_interchange: 0,
+                stay_on_mins: 0,
+                ..SearchConfig::default()
+            };
+            let ranked = rank_journeys(journeys, &config);
 
             // Reference: check sorted by (arrival, changes, duration)
             for window in ranked.windows(2) {
@@ -523,7 +1462,7 @@ mod proptests {
         #[test]
         fn rank_journeys_preserves_elements(journeys in journeys_strategy()) {
             let original_len = journeys.len();
-            let ranked = rank_journeys(journeys);
+            let ranked = rank_journeys(journeys, &SearchConfig::default());
 
             prop_assert_eq!(ranked.len(), original_len);
         }
@@ -531,6 +1470,16 @@ mod proptests {
 
     // ========== remove_dominated properties ==========
 
+    /// Config pruning without the preference for staying on; the journeys
+    /// generated have no walks or prices, so only arrival, changes and
+    /// duration tell them apart.
+    fn pareto() -> SearchConfig {
+        SearchConfig {
+            stay_on_mins: 0,
+            ..SearchConfig::default()
+        }
+    }
+
     /// Check if journey `a` dominates journey `b`
     fn dominates(a: &Journey, b: &Journey) -> bool {
         a.arrival_time() <= b.arrival_time()
@@ -544,7 +1493,7 @@ mod proptests {
     proptest! {
         #[test]
         fn remove_dominated_no_internal_domination(journeys in journeys_strategy()) {
-            let result = remove_dominated(journeys);
+            let result = remove_dominated(journeys, &pareto());
 
             // No journey in result should dominate another
             for (i, a) in result.iter().enumerate() {
@@ -564,7 +1513,7 @@ mod proptests {
         #[test]
         fn remove_dominated_subset(journeys in journeys_strategy()) {
             let original_len = journeys.len();
-            let result = remove_dominated(journeys);
+            let result = remove_dominated(journeys, &pareto());
 
             prop_assert!(result.len() <= original_len);
         }
@@ -582,7 +1531,7 @@ mod proptests {
 
         let _ = runner.run(&journeys_strategy(), |journeys| {
             let original_len = journeys.len();
-            let result = remove_dominated(journeys);
+            let result = remove_dominated(journeys, &pareto());
 
             if result.len() < original_len {
                 dominated_removed_count.set(dominated_removed_count.get() + 1);
@@ -607,7 +1556,7 @@ mod proptests {
         /// (the Pareto front is never empty for non-empty input).
         #[test]
         fn remove_dominated_nonempty_guarantee(journeys in prop::collection::vec(journey_strategy(), 1..10)) {
-            let result = remove_dominated(journeys);
+            let result = remove_dominated(journeys, &pareto());
 
             prop_assert!(
                 !result.is_empty(),
@@ -618,7 +1567,7 @@ mod proptests {
         /// Property: single journey is never dominated (trivially Pareto-optimal).
         #[test]
         fn single_journey_preserved(journey in journey_strategy()) {
-            let result = remove_dominated(vec![journey.clone()]);
+            let result = remove_dominated(vec![journey.clone()], &pareto());
 
             prop_assert_eq!(
                 result.len(),
@@ -648,7 +1597,7 @@ mod proptests {
                 Journey::new(vec![Segment::Train(leg)]).unwrap()
             };
 
-            let result = remove_dominated(vec![j1, j2]);
+            let result = remove_dominated(vec![j1, j2], &pareto());
 
             // Neither dominates the other (they're equal on all metrics)
             // so both should be kept
@@ -665,14 +1614,14 @@ mod proptests {
     proptest! {
         #[test]
         fn deduplicate_no_duplicate_keys(journeys in journeys_strategy()) {
-            let result = deduplicate(journeys);
+            let result = deduplicate(journeys, &SearchConfig::default());
 
-            // No two journeys should have same (arrival, departure, changes)
+            // No two journeys should take the same route
             for (i, a) in result.iter().enumerate() {
                 for (j, b) in result.iter().enumerate() {
                     if i != j {
-                        let a_key = (a.arrival_time(), a.departure_time(), a.change_count());
-                        let b_key = (b.arrival_time(), b.departure_time(), b.change_count());
+                        let a_key = RouteKey::of(a);
+                        let b_key = RouteKey::of(b);
                         prop_assert!(
                             a_key != b_key,
                             "Duplicate key at {} and {}: {:?}",
@@ -688,7 +1637,7 @@ mod proptests {
         #[test]
         fn deduplicate_subset(journeys in journeys_strategy()) {
             let original_len = journeys.len();
-            let result = deduplicate(journeys);
+            let result = deduplicate(journeys, &SearchConfig::default());
 
             prop_assert!(result.len() <= original_len);
         }
@@ -726,7 +1675,7 @@ mod proptests {
 
         let _ = runner.run(&dup_strategy, |journeys| {
             let original_len = journeys.len();
-            let result = deduplicate(journeys);
+            let result = deduplicate(journeys, &SearchConfig::default());
 
             if result.len() < original_len {
                 duplicates_removed_count.set(duplicates_removed_count.get() + 1);
@@ -0,0 +1,168 @@
+//! Why each journey in a ranked list stands out.
+//!
+//! Ranking weighs arrival time against changes, duration, price and more
+//! (see [`rank_journeys`](super::rank_journeys)), so the order alone doesn't
+//! tell users why a journey is where it is. [`rank_reasons`] labels the
+//! journeys that are best on one measure: the earliest arrival, the fewest
+//! changes and the least walking, taking the highest ranked where several
+//! tie. A measure every journey shares picks nobody out, so isn't used.
+
+use crate::domain::Journey;
+
+/// Something a journey does better than the others it was ranked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankReason {
+    /// No other journey was found
+    OnlyOption,
+
+    /// Arrives earliest
+    Fastest,
+
+    /// Changes trains the fewest times
+    FewestChanges,
+
+    /// Walks the least between stations
+    LeastWalking,
+}
+
+impl RankReason {
+    /// Short identifier, as used in the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::OnlyOption => "only_option",
+            Self::Fastest => "fastest",
+            Self::FewestChanges => "fewest_changes",
+            Self::LeastWalking => "least_walking",
+        }
+    }
+
+    /// Label for a journey card.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::OnlyOption => "Only option",
+            Self::Fastest => "Fastest",
+            Self::FewestChanges => "Fewest changes",
+            Self::LeastWalking => "Least walking",
+        }
+    }
+}
+
+/// What each of `ranked` (best first) does better than the rest, in the
+/// same order.
+pub fn rank_reasons(ranked: &[Journey]) -> Vec<Vec<RankReason>> {
+    let mut reasons = vec![Vec::new(); ranked.len()];
+    if let [_] = ranked {
+        reasons[0].push(RankReason::OnlyOption);
+        return reasons;
+    }
+
+    let best = [
+        (RankReason::Fastest, best_by(ranked, Journey::arrival_time)),
+        (
+            RankReason::FewestChanges,
+            best_by(ranked, Journey::change_count),
+        ),
+        (
+            RankReason::LeastWalking,
+            best_by(ranked, Journey::total_walk_duration),
+        ),
+    ];
+    for (reason, index) in best {
+        if let Some(index) = index {
+            reasons[index].push(reason);
+        }
+    }
+    reasons
+}
+
+/// The highest ranked of the journeys with the least `key`, unless they
+/// all have the same.
+fn best_by<K: Ord>(ranked: &[Journey], key: impl Fn(&Journey) -> K) -> Option<usize> {
+    let keys: Vec<K> = ranked.iter().map(key).collect();
+    let least = keys.iter().min()?;
+    if keys.iter().all(|k| k == least) {
+        return None;
+    }
+    keys.iter().position(|k| k == least)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CallIndex, Leg, Segment, Walk};
+    use crate::testing::{ServiceBuilder, crs};
+    use chrono::Duration;
+
+    /// A journey on one train from PAD at `departs` to `to` at `arrives`.
+    fn direct(id: &str, departs: &str, to: &str, arrives: &str) -> Journey {
+        let service = ServiceBuilder::new(id)
+            .call("PAD")
+            .dep(departs)
+            .call(to)
+            .arr(arrives)
+            .build();
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        Journey::new(vec![Segment::Train(leg)]).unwrap()
+    }
+
+    /// Two trains changing at RDG, then a walk from BRI to BRX.
+    fn changing_and_walking() -> Journey {
+        let first = ServiceBuilder::new("A")
+            .call("PAD")
+            .dep("10:00")
+            .call("RDG")
+            .arr("10:25")
+            .build();
+        let second = ServiceBuilder::new("B")
+            .call("RDG")
+            .dep("10:30")
+            .call("BRI")
+            .arr("11:10")
+            .build();
+        Journey::new(vec![
+            Segment::Train(Leg::new(first, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Train(Leg::new(second, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(Walk::new(crs("BRI"), crs("BRX"), Duration::minutes(5))),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn a_lone_journey_is_the_only_option() {
+        let journeys = [direct("A", "10:00", "BRX", "11:30")];
+        assert_eq!(rank_reasons(&journeys), [vec![RankReason::OnlyOption]]);
+        assert!(rank_reasons(&[]).is_empty());
+    }
+
+    #[test]
+    fn journeys_are_picked_out_for_what_they_do_best() {
+        let journeys = [
+            changing_and_walking(),
+            direct("C", "10:05", "BRX", "11:30"),
+            direct("D", "10:20", "BRX", "11:45"),
+        ];
+
+        let reasons = rank_reasons(&journeys);
+
+        assert_eq!(reasons[0], [RankReason::Fastest]);
+        // Both direct journeys change least and walk least; the higher
+        // ranked is picked
+        assert_eq!(
+            reasons[1],
+            [RankReason::FewestChanges, RankReason::LeastWalking]
+        );
+        assert!(reasons[2].is_empty());
+    }
+
+    #[test]
+    fn measures_every_journey_shares_pick_out_nobody() {
+        let journeys = [
+            direct("A", "10:00", "BRX", "11:30"),
+            direct("B", "10:15", "BRX", "11:40"),
+        ];
+
+        let reasons = rank_reasons(&journeys);
+
+        assert_eq!(reasons, [vec![RankReason::Fastest], vec![]]);
+    }
+}
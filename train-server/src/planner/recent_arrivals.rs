@@ -0,0 +1,184 @@
+//! Arrivals boards kept between a user's searches.
+//!
+//! Someone on a train tends to search again a minute or two later, to the
+//! same destination. The destination's arrivals board is fetched relative to
+//! the search's start time, so the provider's board cache misses as soon as
+//! that time moves on. A board fetched for an earlier start still lists
+//! every train arriving after a later one, so within a freshness window it
+//! can be reused instead of fetched again.
+//!
+//! Reused boards are kept up to date where fresher copies of their services
+//! turn up: the user's own train at the start of each search, and services
+//! on departure boards fetched during it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::{Crs, RailTime, Service, ServiceIdentity};
+
+/// An arrivals board as fetched for a search.
+#[derive(Debug)]
+struct RecentBoard {
+    /// The time the board was fetched for
+    after: RailTime,
+
+    /// When the board was fetched
+    fetched: Instant,
+
+    /// Services arriving at the destination, as last updated
+    arrivals: Vec<Arc<Service>>,
+}
+
+/// Arrivals boards from one user's recent searches, by destination.
+///
+/// Shared between the searches of a session; all methods take `&self`.
+#[derive(Debug, Default)]
+pub struct RecentArrivals {
+    boards: Mutex<HashMap<Crs, RecentBoard>>,
+}
+
+impl RecentArrivals {
+    /// Create an empty set of boards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A board for `destination` fetched within `freshness`, that lists
+    /// every arrival a search starting at `after` needs.
+    ///
+    /// Returns `None` if there's no such board, so it must be fetched.
+    pub fn get(
+        &self,
+        destination: &Crs,
+        after: RailTime,
+        freshness: Duration,
+    ) -> Option<Vec<Arc<Service>>> {
+        let boards = self.boards.lock().unwrap_or_else(|e| e.into_inner());
+        let board = boards.get(destination)?;
+        (board.after <= after && board.fetched.elapsed() <= freshness)
+            .then(|| board.arrivals.clone())
+    }
+
+    /// Remember a board just fetched for a search starting at `after`.
+    pub fn insert(&self, destination: Crs, after: RailTime, arrivals: Vec<Arc<Service>>) {
+        let board = RecentBoard {
+            after,
+            fetched: Instant::now(),
+            arrivals,
+        };
+        self.boards
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(destination, board);
+    }
+
+    /// Replace services on the remembered boards with fresher copies.
+    ///
+    /// Services are matched by [`ServiceIdentity`], since the same train has
+    /// a different Darwin ID on each board. Services not on any remembered
+    /// board are ignored. Returns the number of services replaced.
+    pub fn update<'s>(&self, services: impl IntoIterator<Item = &'s Arc<Service>>) -> usize {
+        let mut boards = self.boards.lock().unwrap_or_else(|e| e.into_inner());
+        if boards.is_empty() {
+            return 0;
+        }
+
+        let mut updated = 0;
+        for service in services {
+            let Some(identity) = ServiceIdentity::of(service) else {
+                continue;
+            };
+            for (destination, board) in boards.iter_mut() {
+                // A copy from a board cut short before the destination would
+                // lose the arrival the board is for
+                if service.board_call_at(destination).is_none() {
+                    continue;
+                }
+                for arrival in board.arrivals.iter_mut() {
+                    if identity.matches(arrival) && !Arc::ptr_eq(arrival, service) {
+                        *arrival = service.clone();
+                        updated += 1;
+                    }
+                }
+            }
+        }
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, ServiceRef};
+    use chrono::NaiveDate;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, date()).unwrap()
+    }
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    /// A service from RDG to PAD, with the given expected arrival at PAD.
+    fn service(id: &str, expected_arrival: &str) -> Arc<Service> {
+        let mut origin = Call::new(crs("RDG"), "Reading".into());
+        origin.booked_departure = Some(time("10:00"));
+        let mut dest = Call::new(crs("PAD"), "London Paddington".into());
+        dest.booked_arrival = Some(time("10:25"));
+        dest.realtime_arrival = Some(time(expected_arrival));
+        Arc::new(Service {
+            service_ref: ServiceRef::new(id.into(), crs("RDG")),
+            headcode: None,
+            operator: "GWR".into(),
+            operator_code: None,
+            calls: vec![origin, dest],
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    #[test]
+    fn reuses_fresh_boards_for_later_searches() {
+        let recent = RecentArrivals::new();
+        let pad = crs("PAD");
+        let minute = Duration::from_secs(60);
+        assert!(recent.get(&pad, time("10:00"), minute).is_none());
+
+        recent.insert(pad, time("10:00"), vec![service("A", "10:25")]);
+
+        assert_eq!(recent.get(&pad, time("10:01"), minute).unwrap().len(), 1);
+        assert_eq!(recent.get(&pad, time("10:00"), minute).unwrap().len(), 1);
+        // Earlier trains wouldn't be on the board
+        assert!(recent.get(&pad, time("09:59"), minute).is_none());
+        // Other destinations aren't shared
+        assert!(recent.get(&crs("RDG"), time("10:01"), minute).is_none());
+        // Stale boards are fetched again
+        assert!(recent.get(&pad, time("10:01"), Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn updates_services_from_fresher_copies() {
+        let recent = RecentArrivals::new();
+        let pad = crs("PAD");
+        recent.insert(pad, time("10:00"), vec![service("A", "10:25")]);
+
+        // The same train under another board's ID, now running late
+        let late = service("B", "10:31");
+        assert_eq!(recent.update([&late]), 1);
+        let arrivals = recent.get(&pad, time("10:00"), Duration::MAX).unwrap();
+        assert!(Arc::ptr_eq(&arrivals[0], &late));
+        // Updating with the same copy again changes nothing
+        assert_eq!(recent.update([&late]), 0);
+
+        // A copy that doesn't reach the destination is ignored
+        let mut cut_short = (*service("C", "10:25")).clone();
+        cut_short.calls.truncate(1);
+        assert_eq!(recent.update([&Arc::new(cut_short)]), 0);
+    }
+}
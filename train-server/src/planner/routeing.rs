@@ -0,0 +1,260 @@
+//! Approximate routeing checks for planned journeys.
+//!
+//! The National Routeing Guide decides which routes a ticket is valid on.
+//! We don't model it, but a few of its rules are easy to approximate from
+//! a journey alone, and a journey breaking them is one a guard is likely
+//! to reject. [`RouteingRules`] is a table of such rules, applied after
+//! the search, plus easements: stations where the guide explicitly allows
+//! doubling back.
+
+use std::collections::HashSet;
+
+use crate::domain::{Crs, Journey, Segment, ServiceIdentity};
+
+/// A routeing rule a journey can break.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteingRule {
+    /// Passing through the same station twice, e.g. travelling out past a
+    /// change station and back to it
+    DoublingBack,
+
+    /// Leaving a train and boarding the same train again later
+    RejoinsService,
+}
+
+/// A rule a journey breaks, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteingIssue {
+    /// The rule broken
+    pub rule: RouteingRule,
+
+    /// The station where it is broken
+    pub station: Crs,
+
+    /// Human-readable description
+    pub message: String,
+}
+
+/// The rules to check journeys against.
+#[derive(Debug, Clone)]
+pub struct RouteingRules {
+    rules: Vec<RouteingRule>,
+    easements: HashSet<Crs>,
+}
+
+impl Default for RouteingRules {
+    fn default() -> Self {
+        Self::new(vec![
+            RouteingRule::DoublingBack,
+            RouteingRule::RejoinsService,
+        ])
+    }
+}
+
+impl RouteingRules {
+    /// Check the given rules, with no easements.
+    pub fn new(rules: Vec<RouteingRule>) -> Self {
+        Self {
+            rules,
+            easements: HashSet::new(),
+        }
+    }
+
+    /// Allow doubling back through a station.
+    pub fn with_easement(mut self, station: Crs) -> Self {
+        self.easements.insert(station);
+        self
+    }
+
+    /// The rules a journey breaks, in rule order.
+    pub fn check(&self, journey: &Journey) -> Vec<RouteingIssue> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match rule {
+                RouteingRule::DoublingBack => self.doubling_back(journey),
+                RouteingRule::RejoinsService => rejoins_service(journey),
+            })
+            .collect()
+    }
+
+    /// Whether a journey breaks none of the rules.
+    pub fn allows(&self, journey: &Journey) -> bool {
+        self.check(journey).is_empty()
+    }
+
+    /// The first station the journey passes through twice, other than at
+    /// an easement.
+    fn doubling_back(&self, journey: &Journey) -> Option<RouteingIssue> {
+        let mut seen = HashSet::new();
+        for station in stations_passed(journey) {
+            if !seen.insert(station) && !self.easements.contains(&station) {
+                let message = if &station == journey.origin() {
+                    format!("Doubles back through the origin, {station}")
+                } else {
+                    format!("Passes through {station} twice")
+                };
+                return Some(RouteingIssue {
+                    rule: RouteingRule::DoublingBack,
+                    station,
+                    message,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Every station the journey passes through, in order, with each change
+/// station listed once.
+fn stations_passed(journey: &Journey) -> Vec<Crs> {
+    let mut stations: Vec<Crs> = Vec::new();
+    for segment in journey.segments() {
+        let passed: Vec<Crs> = match segment {
+            Segment::Train(leg) => leg.calls().iter().map(|c| c.station).collect(),
+            Segment::Walk(walk) => vec![walk.from, walk.to],
+        };
+        for station in passed {
+            // Arriving at a station and leaving it again isn't passing twice
+            if stations.last() != Some(&station) {
+                stations.push(station);
+            }
+        }
+    }
+    stations
+}
+
+/// The first station where the journey boards a train it has already left.
+fn rejoins_service(journey: &Journey) -> Option<RouteingIssue> {
+    let mut ridden: Vec<ServiceIdentity> = Vec::new();
+    for leg in journey.legs() {
+        let Some(identity) = ServiceIdentity::of(leg.service()) else {
+            continue;
+        };
+        if ridden.contains(&identity) {
+            let station = *leg.board_station();
+            return Some(RouteingIssue {
+                rule: RouteingRule::RejoinsService,
+                station,
+                message: format!("Rejoins a train already travelled on at {station}"),
+            });
+        }
+        ridden.push(identity);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, Leg, RailTime, Service, ServiceRef, Walk};
+    use chrono::{Duration, NaiveDate};
+    use std::sync::Arc;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    /// A service calling at (station, time) in order.
+    fn service(id: &str, calls: &[(&str, &str)]) -> Arc<Service> {
+        let calls = calls
+            .iter()
+            .map(|(station, t)| {
+                let mut call = Call::new(crs(station), station.to_string());
+                call.booked_arrival = Some(time(t));
+                call.booked_departure = Some(time(t));
+                call
+            })
+            .collect();
+        Arc::new(Service {
+            service_ref: ServiceRef::new(id.to_string(), crs("PAD")),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    /// The leg of a service between two of its calls.
+    fn leg(service: &Arc<Service>, board: usize, alight: usize) -> Segment {
+        Segment::Train(Leg::new(service.clone(), CallIndex(board), CallIndex(alight)).unwrap())
+    }
+
+    #[test]
+    fn ordinary_change_is_allowed() {
+        let first = service("A", &[("PAD", "10:00"), ("RDG", "10:25")]);
+        let second = service("B", &[("RDG", "10:35"), ("SWI", "11:00")]);
+        let journey = Journey::new(vec![leg(&first, 0, 1), leg(&second, 0, 1)]).unwrap();
+
+        assert!(RouteingRules::default().allows(&journey));
+    }
+
+    #[test]
+    fn doubling_back_through_origin_is_flagged() {
+        // Out past Reading to Didcot, then back through Reading to Paddington
+        let out = service("A", &[("RDG", "10:00"), ("DID", "10:15")]);
+        let back = service("B", &[("DID", "10:25"), ("RDG", "10:40"), ("PAD", "11:05")]);
+        let journey = Journey::new(vec![leg(&out, 0, 1), leg(&back, 0, 2)]).unwrap();
+
+        let issues = RouteingRules::default().check(&journey);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, RouteingRule::DoublingBack);
+        assert_eq!(issues[0].station, crs("RDG"));
+        assert_eq!(issues[0].message, "Doubles back through the origin, RDG");
+    }
+
+    #[test]
+    fn easement_allows_doubling_back() {
+        let out = service("A", &[("RDG", "10:00"), ("DID", "10:15")]);
+        let back = service("B", &[("DID", "10:25"), ("RDG", "10:40"), ("PAD", "11:05")]);
+        let journey = Journey::new(vec![leg(&out, 0, 1), leg(&back, 0, 2)]).unwrap();
+
+        let rules = RouteingRules::default().with_easement(crs("RDG"));
+        assert!(rules.allows(&journey));
+    }
+
+    #[test]
+    fn walk_between_stations_is_allowed() {
+        let first = service("A", &[("PAD", "10:00"), ("KGX", "10:20")]);
+        let second = service("B", &[("STP", "10:35"), ("LUT", "11:00")]);
+        let journey = Journey::new(vec![
+            leg(&first, 0, 1),
+            Segment::Walk(Walk::new(crs("KGX"), crs("STP"), Duration::minutes(5))),
+            leg(&second, 0, 1),
+        ])
+        .unwrap();
+
+        assert!(RouteingRules::default().allows(&journey));
+    }
+
+    #[test]
+    fn rejoining_a_train_is_flagged() {
+        // Off at Reading for a faster train to Didcot, then back on the
+        // original train there (listed under another ID on Didcot's board)
+        let calls = [
+            ("PAD", "10:00"),
+            ("RDG", "10:25"),
+            ("DID", "10:45"),
+            ("SWI", "11:10"),
+        ];
+        let main = service("A", &calls);
+        let main_at_didcot = service("A2", &calls);
+        let fast = service("B", &[("RDG", "10:28"), ("DID", "10:40")]);
+        let journey = Journey::new(vec![
+            leg(&main, 0, 1),
+            leg(&fast, 0, 1),
+            leg(&main_at_didcot, 2, 3),
+        ])
+        .unwrap();
+
+        let issues = RouteingRules::default().check(&journey);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, RouteingRule::RejoinsService);
+        assert_eq!(issues[0].station, crs("DID"));
+    }
+}
@@ -9,18 +9,35 @@
 //!
 //! This reduces API calls from ~2000 to ~1-10 for typical journeys.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 use chrono::Duration;
-use futures::future::join_all;
+use futures::future::BoxFuture;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, instrument, trace};
 
-use super::arrivals_index::ArrivalsIndex;
+use super::arrivals_index::{ArrivalsIndex, FeederInfo};
 use super::bfs::{BfsParams, find_bfs_journeys};
 use super::config::SearchConfig;
-use super::rank::{deduplicate, rank_journeys, remove_dominated};
-use crate::domain::{CallIndex, Crs, Journey, Leg, RailTime, Segment, Service, Walk};
+use super::continuation::Continuation;
+use super::near_miss::{NearMiss, sort_near_misses};
+use super::pacing;
+use super::performance::PerformanceHistory;
+use super::phase::{BFS_PHASE, NEAR_MISS_PHASE, ONE_CHANGE_PHASE, TWO_CHANGE_PHASE, in_phase};
+use super::quality::DataQuality;
+use super::rank::{combine_endings, deduplicate, rank_journeys, remove_dominated};
+use super::recent_arrivals::RecentArrivals;
+use super::routeing::RouteingRules;
+use super::stats::{PhaseStats, SearchStats};
+use super::travel_times::TravelTimes;
+use crate::domain::{
+    Call, CallIndex, Crs, Journey, Leg, RailTime, Segment, Service, TimelineGlyphs, Walk,
+    render_timeline,
+};
+use crate::error::{Classify, ErrorKind};
+use crate::stations::{PlatformChange, PlatformTopology, StationLocation, uk_station_usage};
 use crate::walkable::WalkableConnections;
 
 /// Provider of train service information.
@@ -40,6 +57,123 @@ pub trait ServiceProvider: Send + Sync {
         station: &Crs,
         after: RailTime,
     ) -> impl std::future::Future<Output = Result<Vec<Arc<Service>>, SearchError>> + Send;
+
+    /// Whether `get_departures` would be answered without an API call,
+    /// for estimating a search's cost. Providers that can't tell say no.
+    fn has_departures(&self, _station: &Crs, _after: RailTime) -> bool {
+        false
+    }
+
+    /// Whether `get_arrivals` would be answered without an API call.
+    fn has_arrivals(&self, _station: &Crs, _after: RailTime) -> bool {
+        false
+    }
+}
+
+/// Object-safe form of [`ServiceProvider`], for holding providers behind
+/// `Arc<dyn DynServiceProvider>` and choosing one at runtime.
+///
+/// Every `ServiceProvider` is a `DynServiceProvider`, and
+/// `dyn DynServiceProvider` is a `ServiceProvider` again, so the planner
+/// takes either.
+pub trait DynServiceProvider: Send + Sync {
+    /// Get departures from a station after a given time.
+    fn get_departures_dyn<'a>(
+        &'a self,
+        station: &'a Crs,
+        after: RailTime,
+    ) -> BoxFuture<'a, Result<Vec<Arc<Service>>, SearchError>>;
+
+    /// Get arrivals at a station (for destination-first search).
+    fn get_arrivals_dyn<'a>(
+        &'a self,
+        station: &'a Crs,
+        after: RailTime,
+    ) -> BoxFuture<'a, Result<Vec<Arc<Service>>, SearchError>>;
+
+    /// Whether departures would be answered without an API call.
+    fn has_departures_dyn(&self, station: &Crs, after: RailTime) -> bool;
+
+    /// Whether arrivals would be answered without an API call.
+    fn has_arrivals_dyn(&self, station: &Crs, after: RailTime) -> bool;
+}
+
+impl<P: ServiceProvider> DynServiceProvider for P {
+    fn get_departures_dyn<'a>(
+        &'a self,
+        station: &'a Crs,
+        after: RailTime,
+    ) -> BoxFuture<'a, Result<Vec<Arc<Service>>, SearchError>> {
+        Box::pin(self.get_departures(station, after))
+    }
+
+    fn get_arrivals_dyn<'a>(
+        &'a self,
+        station: &'a Crs,
+        after: RailTime,
+    ) -> BoxFuture<'a, Result<Vec<Arc<Service>>, SearchError>> {
+        Box::pin(self.get_arrivals(station, after))
+    }
+
+    fn has_departures_dyn(&self, station: &Crs, after: RailTime) -> bool {
+        self.has_departures(station, after)
+    }
+
+    fn has_arrivals_dyn(&self, station: &Crs, after: RailTime) -> bool {
+        self.has_arrivals(station, after)
+    }
+}
+
+impl ServiceProvider for dyn DynServiceProvider + '_ {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.get_departures_dyn(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.get_arrivals_dyn(station, after).await
+    }
+
+    fn has_departures(&self, station: &Crs, after: RailTime) -> bool {
+        self.has_departures_dyn(station, after)
+    }
+
+    fn has_arrivals(&self, station: &Crs, after: RailTime) -> bool {
+        self.has_arrivals_dyn(station, after)
+    }
+}
+
+impl<P: ServiceProvider + ?Sized> ServiceProvider for Arc<P> {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        (**self).get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        (**self).get_arrivals(station, after).await
+    }
+
+    fn has_departures(&self, station: &Crs, after: RailTime) -> bool {
+        (**self).has_departures(station, after)
+    }
+
+    fn has_arrivals(&self, station: &Crs, after: RailTime) -> bool {
+        (**self).has_arrivals(station, after)
+    }
 }
 
 /// Error type for search operations.
@@ -51,11 +185,31 @@ pub enum SearchError {
 
     /// Failed to fetch service data.
     #[error("failed to fetch services at {station}: {message}")]
-    FetchError { station: Crs, message: String },
+    FetchError {
+        station: Crs,
+        message: String,
+        /// Classification of the underlying failure.
+        kind: ErrorKind,
+    },
 
     /// Search timed out.
     #[error("search timed out")]
     Timeout,
+
+    /// Search was abandoned by the caller (e.g. the client disconnected).
+    #[error("search cancelled")]
+    Cancelled,
+}
+
+impl Classify for SearchError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SearchError::InvalidRequest(_) => ErrorKind::Permanent,
+            SearchError::Cancelled => ErrorKind::Cancelled,
+            SearchError::FetchError { kind, .. } => *kind,
+            SearchError::Timeout => ErrorKind::Transient,
+        }
+    }
 }
 
 /// A request to search for journeys.
@@ -69,6 +223,10 @@ pub struct SearchRequest {
 
     /// The destination station.
     pub destination: Crs,
+
+    /// Whether the user is waiting at `current_position` to board a train
+    /// that hasn't left yet, rather than already on it.
+    pub pre_departure: bool,
 }
 
 impl SearchRequest {
@@ -82,13 +240,27 @@ impl SearchRequest {
             current_service,
             current_position,
             destination,
+            pre_departure: false,
         }
     }
 
+    /// Plan from the station the user is about to board at, before the
+    /// train departs.
+    ///
+    /// The search starts from the train's departure there rather than its
+    /// arrival. That's the estimated departure, and the booked one only if
+    /// there's no estimate: connections planned from the booked time of a
+    /// late train would already be missed, and a later re-plan could never
+    /// see the departure slip (see [`SearchResult::departure_slipped`]).
+    pub fn with_pre_departure(mut self) -> Self {
+        self.pre_departure = true;
+        self
+    }
+
     /// Validate the search request.
     pub fn validate(&self) -> Result<(), SearchError> {
         // Check position is valid
-        if self.current_position.0 >= self.current_service.calls.len() {
+        if self.current_call().is_none() {
             return Err(SearchError::InvalidRequest(format!(
                 "Position {} is out of bounds for train with {} calls",
                 self.current_position.0,
@@ -96,17 +268,48 @@ impl SearchRequest {
             )));
         }
 
+        if self.pre_departure && self.current_time().is_none() {
+            return Err(SearchError::InvalidRequest(format!(
+                "Train does not depart from {}",
+                self.current_station()
+            )));
+        }
+
+        if self.pre_departure && self.current_call().is_some_and(|c| !c.can_board()) {
+            return Err(SearchError::InvalidRequest(format!(
+                "Train only sets down at {}",
+                self.current_station()
+            )));
+        }
+
         Ok(())
     }
 
+    /// The call at the current position, if the position is valid.
+    fn current_call(&self) -> Option<&Call> {
+        self.current_service.calls.get(self.current_position.0)
+    }
+
     /// Get the current station.
+    ///
+    /// # Panics
+    ///
+    /// If the request hasn't passed [`validate`](Self::validate).
     pub fn current_station(&self) -> &Crs {
-        &self.current_service.calls[self.current_position.0].station
+        &self.current_call().expect("validated position").station
     }
 
     /// Get the current time (expected departure from current position).
+    ///
+    /// On the train, the arrival time is used if there's no departure time.
+    /// Before departure only the departure counts: a train that terminates
+    /// at the board station can't be boarded. Either way an estimate wins
+    /// over the booked time.
     pub fn current_time(&self) -> Option<RailTime> {
-        let call = &self.current_service.calls[self.current_position.0];
+        let call = self.current_call()?;
+        if self.pre_departure {
+            return call.expected_departure();
+        }
         call.expected_departure().or(call.expected_arrival())
     }
 }
@@ -119,6 +322,26 @@ pub struct SearchResult {
 
     /// Number of API calls made during search.
     pub routes_explored: usize,
+
+    /// Time, API calls and journeys found, per search phase.
+    pub stats: SearchStats,
+
+    /// For a pre-departure search, the departure of the user's train that
+    /// the journeys assume.
+    pub departure: Option<RailTime>,
+
+    /// If no journeys were found, ones that fall just short of the request
+    /// (see [`NearMiss`]).
+    pub near_misses: Vec<NearMiss>,
+
+    /// Stations whose boards couldn't be fetched, so journeys through them
+    /// may be missing.
+    pub data_quality: DataQuality,
+
+    /// If nothing reached the destination or came close, the part of the
+    /// journey that can be planned now and where to plan on from (see
+    /// [`Continuation`]).
+    pub continuation: Option<Continuation>,
 }
 
 impl SearchResult {
@@ -127,15 +350,82 @@ impl SearchResult {
         Self {
             journeys: Vec::new(),
             routes_explored: 0,
+            stats: SearchStats::default(),
+            departure: None,
+            near_misses: Vec::new(),
+            data_quality: DataQuality::default(),
+            continuation: None,
         }
     }
+
+    /// Whether the user's train now departs later than `planned`, the
+    /// departure an earlier plan assumed, so connections may have been lost
+    /// and the journey should be re-planned.
+    ///
+    /// Always false for a search made on the train.
+    pub fn departure_slipped(&self, planned: RailTime) -> bool {
+        self.departure.is_some_and(|d| d > planned)
+    }
+
+    /// Combine the results of searches to different destinations (e.g. the
+    /// stations of a group) into one ranked result.
+    ///
+    /// Journeys are deduplicated and ranked together, so one to a nearby
+    /// station of the group can dominate a slower one to another. With
+    /// `config.combine_group_endings` set, journeys on the same trains that
+    /// only finish at different stations are first combined into one (see
+    /// [`combine_endings`]), so they aren't listed as separate options. Near
+    /// misses are only kept if no search found a journey, and a
+    /// continuation (the one planning on soonest) only if no search found
+    /// either.
+    pub fn merge(results: Vec<SearchResult>, config: &SearchConfig) -> Self {
+        let mut merged = Self::empty();
+        let mut journeys = Vec::new();
+        let mut near_misses = Vec::new();
+        let mut continuations = Vec::new();
+        for result in results {
+            journeys.extend(result.journeys);
+            near_misses.extend(result.near_misses);
+            continuations.extend(result.continuation);
+            merged.routes_explored += result.routes_explored;
+            merged.stats.add(&result.stats);
+            merged.data_quality.add(&result.data_quality);
+            merged.departure = merged.departure.or(result.departure);
+        }
+        let mut journeys = deduplicate(journeys, config);
+        if config.combine_group_endings {
+            journeys = combine_endings(journeys);
+        }
+        let journeys = remove_dominated(journeys, config);
+        merged.journeys = rank_journeys(journeys, config)
+            .into_iter()
+            .take(config.max_results)
+            .collect();
+        if merged.journeys.is_empty() {
+            sort_near_misses(&mut near_misses);
+            near_misses.truncate(config.max_results);
+            merged.near_misses = near_misses;
+        }
+        if merged.journeys.is_empty() && merged.near_misses.is_empty() {
+            merged.continuation = continuations
+                .into_iter()
+                .min_by_key(|c: &Continuation| c.hint.after);
+        }
+        merged
+    }
 }
 
 /// Journey planner using arrivals-first search.
 pub struct Planner<'a, P: ServiceProvider> {
-    provider: &'a P,
-    walkable: &'a WalkableConnections,
-    config: &'a SearchConfig,
+    pub(super) provider: &'a P,
+    pub(super) walkable: &'a WalkableConnections,
+    pub(super) config: &'a SearchConfig,
+    pub(super) cancel: Option<CancellationToken>,
+    travel_times: Option<&'a TravelTimes>,
+    performance: Option<&'a PerformanceHistory>,
+    pub(super) recent_arrivals: Option<&'a RecentArrivals>,
+    pub(super) locations: Option<&'a HashMap<Crs, StationLocation>>,
+    platforms: Option<&'a PlatformTopology>,
 }
 
 impl<'a, P: ServiceProvider> Planner<'a, P> {
@@ -149,9 +439,325 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             provider,
             walkable,
             config,
+            cancel: None,
+            travel_times: None,
+            performance: None,
+            recent_arrivals: None,
+            locations: None,
+            platforms: None,
         }
     }
 
+    /// Attach a travel time matrix.
+    ///
+    /// Services seen during the search are recorded in it, and the BFS
+    /// fallback uses it to prune partial journeys that can't beat those
+    /// already found.
+    pub fn with_travel_times(mut self, travel_times: &'a TravelTimes) -> Self {
+        self.travel_times = Some(travel_times);
+        self
+    }
+
+    /// Attach a punctuality history.
+    ///
+    /// Services seen during the search are recorded in it, building up the
+    /// day-by-day record that confidence assessment draws on.
+    pub fn with_performance(mut self, performance: &'a PerformanceHistory) -> Self {
+        self.performance = Some(performance);
+        self
+    }
+
+    /// Attach station locations.
+    ///
+    /// With them, a search that can't reach the destination within what
+    /// the boards show offers a first portion to continue from (see
+    /// [`Continuation`]).
+    pub fn with_locations(mut self, locations: &'a HashMap<Crs, StationLocation>) -> Self {
+        self.locations = Some(locations);
+        self
+    }
+
+    /// Attach station platform layouts.
+    ///
+    /// With them, changes that cross the footbridge need
+    /// `config.footbridge_connection_mins` more than other changes.
+    pub fn with_platforms(mut self, platforms: &'a PlatformTopology) -> Self {
+        self.platforms = Some(platforms);
+        self
+    }
+
+    /// Record services in the travel time matrix and punctuality history,
+    /// if there are any.
+    fn observe<'s>(&self, services: impl IntoIterator<Item = &'s Arc<Service>>) {
+        let services: Vec<&Arc<Service>> = services.into_iter().collect();
+        if let Some(travel_times) = self.travel_times {
+            for service in &services {
+                travel_times.observe(service);
+            }
+        }
+        if let Some(performance) = self.performance {
+            for service in &services {
+                performance.record(service);
+            }
+        }
+        if let Some(recent) = self.recent_arrivals {
+            recent.update(services);
+        }
+    }
+
+    /// A recent arrivals board for the request's destination, fresh enough
+    /// to reuse, if reuse is enabled.
+    fn reusable_arrivals(
+        &self,
+        request: &SearchRequest,
+        current_time: RailTime,
+    ) -> Option<Vec<Arc<Service>>> {
+        let recent = self.recent_arrivals?;
+        let freshness = self.config.arrivals_reuse()?;
+        // The user's train has just been looked up, so is the freshest copy
+        recent.update([&request.current_service]);
+        recent.get(&request.destination, current_time, freshness)
+    }
+
+    /// Fetch the arrivals board for the request's destination, paging it
+    /// forward if it stops short (see [`page_arrivals`](Self::page_arrivals)).
+    ///
+    /// Returns the arrivals and the number of API calls made.
+    async fn fetch_arrivals(
+        &self,
+        request: &SearchRequest,
+        current_time: RailTime,
+    ) -> Result<(Vec<Arc<Service>>, usize), SearchError> {
+        let (mut arrivals, calls) = match self
+            .provider
+            .get_arrivals(&request.destination, current_time)
+            .await
+        {
+            // Everything hinges on this board, so give it one more try
+            Err(e) if e.is_retryable() => {
+                debug!(error = %e, "Arrivals fetch failed, retrying once");
+                if let Some(delay) = e.kind().retry_delay() {
+                    tokio::time::sleep(delay).await;
+                }
+                self.check_cancelled()?;
+                let arrivals = self
+                    .provider
+                    .get_arrivals(&request.destination, current_time)
+                    .await?;
+                (arrivals, 2)
+            }
+            result => (result?, 1),
+        };
+        let pages = self
+            .page_arrivals(request, current_time, &mut arrivals)
+            .await?;
+        Ok((arrivals, calls + pages))
+    }
+
+    /// The earliest the user could reach the destination, by the quickest
+    /// route over the travel time matrix (changing or walking where
+    /// quicker, as BFS pruning bounds it), if that's known and arrivals
+    /// paging is enabled.
+    pub(super) fn earliest_possible_arrival(
+        &self,
+        request: &SearchRequest,
+        current_time: RailTime,
+    ) -> Option<RailTime> {
+        self.config.arrivals_paging()?;
+        let bound = self
+            .travel_times?
+            .bounds_to(
+                &request.destination,
+                self.walkable,
+                self.config.travel_time_margin(),
+            )
+            .from(request.current_station());
+        (bound > Duration::zero()).then_some(current_time + bound)
+    }
+
+    /// Page the destination's arrivals board forward until it reaches the
+    /// earliest the user could get there.
+    ///
+    /// A board only lists so many arrivals, so for a long journey the
+    /// first page can end before any train the user could catch arrives,
+    /// leaving no feeders. Each further page starts at the latest arrival
+    /// on the board so far, or the end of the search window if the board
+    /// is empty, up to `config.max_arrivals_pages` of them, stopping early
+    /// if a page adds nothing. A page that can't be fetched
+    /// ends the paging; the arrivals already fetched are kept.
+    ///
+    /// Returns the number of API calls made.
+    async fn page_arrivals(
+        &self,
+        request: &SearchRequest,
+        current_time: RailTime,
+        arrivals: &mut Vec<Arc<Service>>,
+    ) -> Result<usize, SearchError> {
+        let Some(target) = self.earliest_possible_arrival(request, current_time) else {
+            return Ok(0);
+        };
+        let destination = request.destination;
+        let latest_arrival = |arrivals: &[Arc<Service>]| {
+            arrivals
+                .iter()
+                .filter_map(|s| s.board_call_at(&destination)?.1.expected_arrival())
+                .max()
+        };
+
+        let mut api_calls = 0;
+        for _ in 0..self.config.max_arrivals_pages {
+            let latest = latest_arrival(arrivals)
+                .unwrap_or_else(|| current_time + self.config.time_window());
+            if latest >= target {
+                break;
+            }
+            self.check_cancelled()?;
+            api_calls += 1;
+            let page = match self.provider.get_arrivals(&destination, latest).await {
+                Ok(page) => page,
+                Err(e) => {
+                    debug!(error = %e, "Failed to page arrivals board forward");
+                    break;
+                }
+            };
+            let before = arrivals.len();
+            for service in page {
+                let id = &service.service_ref.darwin_id;
+                if !arrivals.iter().any(|s| &s.service_ref.darwin_id == id) {
+                    arrivals.push(service);
+                }
+            }
+            debug!(
+                from = %latest,
+                target = %target,
+                added = arrivals.len() - before,
+                "Paged arrivals board forward"
+            );
+            if arrivals.len() == before {
+                break;
+            }
+        }
+        Ok(api_calls)
+    }
+
+    /// Add the arrivals boards of stations within walking distance of the
+    /// destination to `index`, so journeys can finish on foot.
+    ///
+    /// A board that can't be fetched is left out and its station recorded
+    /// in `quality`. Returns the number of API calls made.
+    async fn add_egress_arrivals(
+        &self,
+        request: &SearchRequest,
+        current_time: RailTime,
+        index: &mut ArrivalsIndex,
+        quality: &mut DataQuality,
+    ) -> Result<usize, SearchError> {
+        let max_walk = self.config.max_walk();
+        let mut egress: Vec<(Crs, Duration)> = self
+            .walkable
+            .walkable_from(&request.destination)
+            .into_iter()
+            .filter(|(_, walk)| *walk <= max_walk)
+            .collect();
+        egress.sort();
+
+        let mut api_calls = 0;
+        for (station, walk) in egress {
+            self.check_cancelled()?;
+            api_calls += 1;
+            match self.provider.get_arrivals(&station, current_time).await {
+                Ok(arrivals) => {
+                    self.observe(&arrivals);
+                    index.add_egress(station, walk, &arrivals);
+                }
+                Err(e) => {
+                    debug!(
+                        station = %station.as_str(),
+                        error = %e,
+                        "Failed to fetch egress arrivals, leaving them out"
+                    );
+                    quality.record_unavailable(station);
+                }
+            }
+        }
+        Ok(api_calls)
+    }
+
+    /// Attach arrivals boards kept from the user's earlier searches.
+    ///
+    /// Within `config.arrivals_reuse_secs` of fetching a destination's
+    /// arrivals board, a later search there reuses it rather than fetching
+    /// it again (see [`RecentArrivals`]). Boards this search fetches are
+    /// kept for the next.
+    pub fn with_recent_arrivals(mut self, recent: &'a RecentArrivals) -> Self {
+        self.recent_arrivals = Some(recent);
+        self
+    }
+
+    /// Attach a cancellation token.
+    ///
+    /// The token is checked between search phases and between batches of
+    /// departure fetches; once cancelled, no further API calls are issued and
+    /// `search` returns [`SearchError::Cancelled`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Drop journeys that break the routeing rules, if configured to.
+    ///
+    /// Runs before dominance removal, so a valid journey isn't lost to an
+    /// invalid one that arrives earlier.
+    fn check_routeing(&self, journeys: Vec<Journey>) -> Vec<Journey> {
+        if !self.config.reject_invalid_routeing {
+            return journeys;
+        }
+        let rules = RouteingRules::default();
+        let before = journeys.len();
+        let journeys: Vec<Journey> = journeys.into_iter().filter(|j| rules.allows(j)).collect();
+        debug!(
+            rejected = before - journeys.len(),
+            "Rejected journeys breaking routeing rules"
+        );
+        journeys
+    }
+
+    /// Drop journeys with a change over the footbridge that's too tight,
+    /// if the planner has platform layouts.
+    ///
+    /// The search phases only hold changes to `config.min_connection_mins`,
+    /// not knowing the platforms involved, so this runs on what they find.
+    fn check_platform_changes(&self, journeys: Vec<Journey>) -> Vec<Journey> {
+        let Some(platforms) = self.platforms else {
+            return journeys;
+        };
+        let needed = self.config.footbridge_connection();
+        let before = journeys.len();
+        let journeys: Vec<Journey> = journeys
+            .into_iter()
+            .filter(|j| {
+                j.changes().all(|change| {
+                    platforms.for_change(&change) != Some(PlatformChange::Footbridge)
+                        || change.slack() >= needed
+                })
+            })
+            .collect();
+        debug!(
+            rejected = before - journeys.len(),
+            "Rejected journeys too tight to cross the footbridge"
+        );
+        journeys
+    }
+
+    /// Return `Err(Cancelled)` if the search has been cancelled.
+    fn check_cancelled(&self) -> Result<(), SearchError> {
+        if self.cancel.as_ref().is_some_and(|t| t.is_cancelled()) {
+            debug!("Search cancelled");
+            return Err(SearchError::Cancelled);
+        }
+        Ok(())
+    }
+
     /// Search for journeys from current position to destination.
     #[instrument(skip(self, request), fields(
         destination = %request.destination.as_str(),
@@ -164,22 +770,37 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             "Starting arrivals-first journey search"
         );
         request.validate()?;
+        self.check_cancelled()?;
 
         let mut journeys = Vec::new();
         let mut api_calls = 0;
+        let mut stats = SearchStats::default();
         let mut departures_cache: HashMap<Crs, Vec<Arc<Service>>> = HashMap::new();
+        let mut data_quality = DataQuality::default();
+        let departure = request
+            .pre_departure
+            .then(|| request.current_time())
+            .flatten();
 
         // Phase 1: Check direct journey (current train goes to destination)
-        if let Some(j) = self.find_direct(request) {
-            debug!("Direct route found on current train");
-            journeys.push(j);
+        let started = Instant::now();
+        let direct = self.find_direct(request);
+        if !direct.is_empty() {
+            debug!(count = direct.len(), "Direct route found on current train");
+            journeys.extend(direct);
         }
+        stats.direct = PhaseStats::finished(started, journeys.len(), 0);
 
         // Early exit: if direct journey exists and no changes allowed, we're done
         if !journeys.is_empty() && self.config.max_changes == 0 {
             return Ok(SearchResult {
                 journeys,
                 routes_explored: api_calls,
+                stats,
+                departure,
+                near_misses: Vec::new(),
+                data_quality,
+                continuation: None,
             });
         }
 
@@ -188,18 +809,39 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             SearchError::InvalidRequest("Cannot determine current time".to_string())
         })?;
 
-        let arrivals = self
-            .provider
-            .get_arrivals(&request.destination, current_time)
-            .await?;
-        api_calls += 1;
+        self.check_cancelled()?;
+        let started = Instant::now();
+        let arrivals = match self.reusable_arrivals(request, current_time) {
+            Some(arrivals) => {
+                debug!(
+                    arrivals = arrivals.len(),
+                    "Reusing arrivals board from an earlier search"
+                );
+                arrivals
+            }
+            None => {
+                let (arrivals, calls) =
+                    in_phase(ONE_CHANGE_PHASE, self.fetch_arrivals(request, current_time)).await?;
+                api_calls += calls;
+                if let Some(recent) = self.recent_arrivals {
+                    recent.insert(request.destination, current_time, arrivals.clone());
+                }
+                arrivals
+            }
+        };
 
         debug!(
             arrivals = arrivals.len(),
             "Built arrivals index for destination"
         );
+        self.observe(std::iter::once(&request.current_service).chain(&arrivals));
 
-        let index = ArrivalsIndex::from_arrivals(request.destination, arrivals);
+        let mut index = ArrivalsIndex::from_arrivals(request.destination, arrivals);
+        if self.config.max_changes >= 1 {
+            let egress =
+                self.add_egress_arrivals(request, current_time, &mut index, &mut data_quality);
+            api_calls += in_phase(ONE_CHANGE_PHASE, egress).await?;
+        }
         debug!(
             feeder_stations = index.feeder_station_count(),
             total_feeders = index.total_feeder_count(),
@@ -207,11 +849,15 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         );
 
         // Phase 3: Find 1-change journeys (0 API calls)
+        let mut one_change_found = 0;
         if self.config.max_changes >= 1 {
-            let one_change = self.find_one_change(request, &index);
+            let one_change = self.check_platform_changes(self.find_one_change(request, &index));
             debug!(found = one_change.len(), "Found 1-change journeys");
+            one_change_found = one_change.len();
             journeys.extend(one_change);
         }
+        // The arrivals board is only fetched for changes, so it counts here
+        stats.one_change = PhaseStats::finished(started, one_change_found, api_calls);
 
         // Early exit: if we have max_results journeys and one achieves the earliest
         // possible arrival (per ArrivalsIndex), 2-change/BFS can't improve results.
@@ -225,30 +871,41 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
                 "Early exit: have {} journeys with one achieving earliest possible arrival",
                 journeys.len()
             );
-            let journeys = remove_dominated(journeys);
-            let journeys = deduplicate(journeys);
-            let journeys = rank_journeys(journeys);
+            let journeys = deduplicate(journeys, self.config);
+            let journeys = self.check_routeing(journeys);
+            let journeys = remove_dominated(journeys, self.config);
+            let journeys = rank_journeys(journeys, self.config);
             let journeys: Vec<Journey> =
                 journeys.into_iter().take(self.config.max_results).collect();
 
             return Ok(SearchResult {
                 journeys,
                 routes_explored: api_calls,
+                stats,
+                departure,
+                near_misses: Vec::new(),
+                data_quality,
+                continuation: None,
             });
         }
 
         // Phase 4: Find 2-change journeys (limited API calls)
         if self.config.max_changes >= 2 {
-            let (two_change, calls) = self
-                .find_two_change(request, &index, &mut departures_cache)
-                .await?;
+            self.check_cancelled()?;
+            let started = Instant::now();
+            let search =
+                self.find_two_change(request, &index, &mut departures_cache, &mut data_quality);
+            let (two_change, calls, cache_hits) = in_phase(TWO_CHANGE_PHASE, search).await?;
             debug!(
                 found = two_change.len(),
                 api_calls = calls,
                 "Found 2-change journeys"
             );
+            stats.two_change = PhaseStats::finished(started, two_change.len(), calls);
+            stats.cache_hits += cache_hits;
             journeys.extend(two_change);
             api_calls += calls;
+            self.check_cancelled()?;
         }
 
         // Phase 5: BFS fallback
@@ -258,59 +915,138 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         let need_bfs_fallback =
             self.config.max_changes > 2 || journeys.len() < self.config.max_results;
         if need_bfs_fallback && self.config.max_changes >= 1 {
+            let started = Instant::now();
+            self.observe(departures_cache.values().flatten());
             let bfs_params = BfsParams {
                 current_service: &request.current_service,
                 current_position: request.current_position,
                 destination: request.destination,
                 start_time: current_time,
+                cancel: self.cancel.as_ref(),
+                travel_times: self.travel_times,
+                found: journeys
+                    .iter()
+                    .map(|j| (j.arrival_time(), j.change_count()))
+                    .collect(),
             };
-            let bfs_result = find_bfs_journeys(
+            let bfs = find_bfs_journeys(
                 &bfs_params,
                 &index,
                 &mut departures_cache,
                 self.walkable,
                 self.config,
                 self.provider,
-            )
-            .await;
+            );
+            let bfs_result = in_phase(BFS_PHASE, bfs).await;
             debug!(
                 found = bfs_result.journeys.len(),
                 api_calls = bfs_result.api_calls,
+                pruned = bfs_result.pruned,
+                stopped_early = bfs_result.stopped_early,
                 "Found BFS fallback journeys"
             );
+            stats.bfs =
+                PhaseStats::finished(started, bfs_result.journeys.len(), bfs_result.api_calls);
+            stats.cache_hits += bfs_result.cache_hits;
+            data_quality.add(&bfs_result.data_quality);
             journeys.extend(bfs_result.journeys);
             api_calls += bfs_result.api_calls;
+            self.check_cancelled()?;
         }
 
-        // Phase 6: Rank, deduplicate, and limit results
-        let journeys = remove_dominated(journeys);
-        let journeys = deduplicate(journeys);
-        let journeys = rank_journeys(journeys);
+        // Phase 6: Deduplicate, rank, and limit results. Deduplicating first
+        // lets frequent services collapse before dominance removes all but
+        // their earliest departure.
+        let journeys = self.check_platform_changes(journeys);
+        let journeys = deduplicate(journeys, self.config);
+        let journeys = self.check_routeing(journeys);
+        let journeys = remove_dominated(journeys, self.config);
+        let journeys = rank_journeys(journeys, self.config);
         let journeys: Vec<Journey> = journeys.into_iter().take(self.config.max_results).collect();
 
+        // Nothing reaches the destination, so offer what comes closest
+        let mut near_misses = Vec::new();
+        if journeys.is_empty() {
+            let search =
+                self.find_near_misses(request, &index, &mut departures_cache, &mut data_quality);
+            let (found, calls) = in_phase(NEAR_MISS_PHASE, search).await?;
+            debug!(found = found.len(), api_calls = calls, "Found near misses");
+            near_misses = found;
+            api_calls += calls;
+        }
+
+        // Nor anything close: the destination may be beyond what the boards
+        // show, so offer as much of the journey as can be planned now
+        let continuation = if journeys.is_empty() && near_misses.is_empty() {
+            self.find_continuation(request, &departures_cache)
+        } else {
+            None
+        };
+
         info!(
             api_calls,
             journeys = journeys.len(),
+            direct_ms = stats.direct.elapsed.as_millis() as u64,
+            one_change_ms = stats.one_change.elapsed.as_millis() as u64,
+            two_change_ms = stats.two_change.elapsed.as_millis() as u64,
+            bfs_ms = stats.bfs.elapsed.as_millis() as u64,
+            cache_hit_ratio = stats.cache_hit_ratio(),
             "Arrivals-first search complete"
         );
+        for (rank, journey) in journeys.iter().enumerate() {
+            debug!(
+                rank = rank + 1,
+                "Returning journey\n{}",
+                render_timeline(journey, TimelineGlyphs::Ascii)
+            );
+        }
 
         Ok(SearchResult {
             journeys,
             routes_explored: api_calls,
+            stats,
+            departure,
+            near_misses,
+            data_quality,
+            continuation,
         })
     }
 
-    /// Find a direct journey (staying on current train to destination).
-    fn find_direct(&self, request: &SearchRequest) -> Option<Journey> {
+    /// Find direct journeys: staying on the current train to the destination,
+    /// or if it divides on the way, on a portion dividing from it.
+    ///
+    /// Journeys on a portion keep to it past the division, and their legs
+    /// advise which end of the train to be in (see [`Leg::boarding_advice`]).
+    pub(super) fn find_direct(&self, request: &SearchRequest) -> Vec<Journey> {
         let train = &request.current_service;
-        let pos = request.current_position.0;
+        let pos = request.current_position;
 
+        let mut journeys: Vec<Journey> = self
+            .find_direct_on(train, pos, pos, request)
+            .into_iter()
+            .collect();
+        for (divides, portion) in train.portions_after(pos) {
+            // Stops up to the division are the same on either portion
+            let portion = Arc::new(portion);
+            journeys.extend(self.find_direct_on(&portion, pos, divides, request));
+        }
+        journeys
+    }
+
+    /// Find a direct journey on `train` from `board`, alighting strictly
+    /// after `alight_after`.
+    fn find_direct_on(
+        &self,
+        train: &Arc<Service>,
+        board: CallIndex,
+        alight_after: CallIndex,
+        request: &SearchRequest,
+    ) -> Option<Journey> {
         // Check if any call after current position is the destination
-        // Note: skip(pos + 1) to avoid trying to create a leg from pos to pos
-        for (idx, call) in train.calls.iter().enumerate().skip(pos + 1) {
-            if call.station == request.destination && !call.is_cancelled {
+        for (idx, call) in train.calls_after(alight_after) {
+            if call.station == request.destination && !call.is_cancelled && call.can_alight() {
                 // Found direct journey
-                let leg = match Leg::new(train.clone(), request.current_position, CallIndex(idx)) {
+                let leg = match Leg::new(train.clone(), board, idx) {
                     Ok(l) => l,
                     Err(_) => continue,
                 };
@@ -319,8 +1055,8 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         }
 
         // Also check walkable destinations from any stop
-        for (idx, call) in train.calls.iter().enumerate().skip(pos) {
-            if call.is_cancelled {
+        for (idx, call) in train.calls_after(alight_after) {
+            if call.is_cancelled || !call.can_alight() {
                 continue;
             }
 
@@ -333,8 +1069,7 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
 
                 // Only if walk is within limits
                 if walk_duration <= self.config.max_walk() {
-                    let leg =
-                        Leg::new(train.clone(), request.current_position, CallIndex(idx)).ok()?;
+                    let leg = Leg::new(train.clone(), board, idx).ok()?;
                     let walk = Walk::new(call.station, request.destination, walk_duration);
                     return Journey::new(vec![Segment::Train(leg), Segment::Walk(walk)]).ok();
                 }
@@ -349,10 +1084,13 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
     /// For each station on the current train after our position, check if it's
     /// a feeder station (has services going to destination). If so, check timing
     /// constraints for valid connections.
-    fn find_one_change(&self, request: &SearchRequest, index: &ArrivalsIndex) -> Vec<Journey> {
+    pub(super) fn find_one_change(
+        &self,
+        request: &SearchRequest,
+        index: &ArrivalsIndex,
+    ) -> Vec<Journey> {
         let mut journeys = Vec::new();
         let train = &request.current_service;
-        let pos = request.current_position.0;
         let min_connection = self.config.min_connection();
         let max_journey = self.config.max_journey();
         let max_walk = self.config.max_walk();
@@ -362,8 +1100,8 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         };
 
         // For each station on current train after our position
-        for (alight_idx, alight_call) in train.calls.iter().enumerate().skip(pos) {
-            if alight_call.is_cancelled {
+        for (alight_idx, alight_call) in train.calls_after(request.current_position) {
+            if alight_call.is_cancelled || !alight_call.can_alight() {
                 continue;
             }
 
@@ -422,13 +1160,11 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
                     if let Some(journey) = self.build_one_change_journey(
                         train,
                         request.current_position,
-                        CallIndex(alight_idx),
-                        &feeder.service,
-                        feeder.board_index,
+                        alight_idx,
                         &alight_call.station,
                         &feeder_station,
                         walk_time,
-                        &request.destination,
+                        feeder,
                     ) {
                         journeys.push(journey);
                     }
@@ -446,28 +1182,13 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         first_train: &Arc<Service>,
         board_first: CallIndex,
         alight_first: CallIndex,
-        second_train: &Arc<Service>,
-        board_second: CallIndex,
         alight_station: &Crs,
         board_station: &Crs,
         walk_time: Duration,
-        destination: &Crs,
+        feeder: &FeederInfo,
     ) -> Option<Journey> {
         let leg1 = Leg::new(first_train.clone(), board_first, alight_first).ok()?;
 
-        // Find where second train arrives at destination
-        // Note: service may continue past destination, so find actual destination call
-        let alight_second_idx = second_train
-            .calls
-            .iter()
-            .position(|c| c.station == *destination)?;
-        let leg2 = Leg::new(
-            second_train.clone(),
-            board_second,
-            CallIndex(alight_second_idx),
-        )
-        .ok()?;
-
         let mut segments = vec![Segment::Train(leg1)];
 
         // Add walk if changing between different stations
@@ -479,39 +1200,37 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             )));
         }
 
-        segments.push(Segment::Train(leg2));
+        // The second train goes to the destination, or near enough to walk
+        segments.extend(feeder.segments()?);
 
         Journey::new(segments).ok()
     }
 
-    /// Find 2-change journeys.
+    /// The stations whose departures the 2-change phase looks at: every
+    /// stop on the current train from the current one onwards, and the
+    /// stations within walking distance of them, busiest first.
     ///
-    /// For each station on the current train that is NOT a feeder station,
-    /// fetch departures and check if any of those services call at a feeder station.
-    async fn find_two_change(
+    /// Each is paired with the stop it's reached from and the walk there.
+    pub(super) fn two_change_stations<'r>(
         &self,
-        request: &SearchRequest,
-        index: &ArrivalsIndex,
-        departures_cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
-    ) -> Result<(Vec<Journey>, usize), SearchError> {
-        let mut journeys = Vec::new();
-
+        request: &'r SearchRequest,
+    ) -> Vec<(CallIndex, &'r Call, Crs, Duration)> {
         let train = &request.current_service;
-        let pos = request.current_position.0;
-        let min_connection = self.config.min_connection();
-        let max_journey = self.config.max_journey();
         let max_walk = self.config.max_walk();
-        let start_time = match request.current_time() {
-            Some(t) => t,
-            None => return Ok((journeys, 0)),
-        };
 
         // Collect stations to query (all stops on current train, including feeders)
         // Also include walkable stations from each stop
-        let mut stations_to_query: Vec<(usize, Crs, Duration)> = Vec::new();
+        let mut stations_to_query: Vec<(CallIndex, &Call, Crs, Duration)> = Vec::new();
 
-        for (alight_idx, alight_call) in train.calls.iter().enumerate().skip(pos) {
-            if alight_call.is_cancelled {
+        // From the current stop onwards
+        let current = request
+            .current_call()
+            .map(|call| (request.current_position, call));
+        for (alight_idx, alight_call) in current
+            .into_iter()
+            .chain(train.calls_after(request.current_position))
+        {
+            if alight_call.is_cancelled || !alight_call.can_alight() {
                 continue;
             }
 
@@ -523,39 +1242,78 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             // Include ALL stations (including feeders) for 2-change exploration.
             // Even if a station is a feeder, we need to explore 2-change paths through it
             // because the 1-change via that feeder might be rejected (too long, bad timing).
-            stations_to_query.push((alight_idx, alight_call.station, Duration::zero()));
+            stations_to_query.push((
+                alight_idx,
+                alight_call,
+                alight_call.station,
+                Duration::zero(),
+            ));
 
             // Also check walkable neighbours
             for (walkable_station, walk_time) in self.walkable.walkable_from(&alight_call.station) {
                 if walk_time <= max_walk {
-                    stations_to_query.push((alight_idx, walkable_station, walk_time));
+                    stations_to_query.push((alight_idx, alight_call, walkable_station, walk_time));
                 }
             }
         }
 
         // Deduplicate by station (keep the one with earliest arrival at query station)
         // Sort by station (as string), then by arrival time at query station
-        stations_to_query.sort_by(|(idx_a, s_a, w_a), (idx_b, s_b, w_b)| {
-            let arrival_at_query = |idx: usize, walk: &Duration| {
-                train.calls[idx]
-                    .expected_arrival()
-                    .or_else(|| train.calls[idx].expected_departure())
+        stations_to_query.sort_by(|(_, call_a, s_a, w_a), (_, call_b, s_b, w_b)| {
+            let arrival_at_query = |call: &Call, walk: &Duration| {
+                call.expected_arrival()
+                    .or_else(|| call.expected_departure())
                     .map(|t| t + *walk)
             };
 
             s_a.as_str()
                 .cmp(s_b.as_str())
-                .then(arrival_at_query(*idx_a, w_a).cmp(&arrival_at_query(*idx_b, w_b)))
+                .then(arrival_at_query(call_a, w_a).cmp(&arrival_at_query(call_b, w_b)))
         });
-        stations_to_query.dedup_by(|a, b| a.1 == b.1);
+        stations_to_query.dedup_by(|a, b| a.2 == b.2);
 
-        // Collect unique stations that need fetching (not in cache)
+        // Busier stations first: they're fetched before quieter ones if the
+        // search is cut short, and their journeys win ties in ranking
+        let usage = uk_station_usage();
+        stations_to_query.sort_by(|a, b| usage.busiest_first(&a.2, &b.2));
+
+        stations_to_query
+    }
+
+    /// Find 2-change journeys.
+    ///
+    /// For each station on the current train that is NOT a feeder station,
+    /// fetch departures and check if any of those services call at a feeder station.
+    ///
+    /// Returns the journeys, the number of API calls made, and the number of
+    /// stations whose departures were already in `departures_cache`. Stations
+    /// whose departures couldn't be fetched are recorded in `quality`.
+    pub(super) async fn find_two_change(
+        &self,
+        request: &SearchRequest,
+        index: &ArrivalsIndex,
+        departures_cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
+        quality: &mut DataQuality,
+    ) -> Result<(Vec<Journey>, usize, usize), SearchError> {
+        let mut journeys = Vec::new();
+
+        let train = &request.current_service;
+        let min_connection = self.config.min_connection();
+        let max_journey = self.config.max_journey();
+        let max_walk = self.config.max_walk();
+        let start_time = match request.current_time() {
+            Some(t) => t,
+            None => return Ok((journeys, 0, 0)),
+        };
+
+        let stations_to_query = self.two_change_stations(request);
+
+        // Collect stations that need fetching (not in cache), each once
+        // as they're already deduplicated
         let uncached_stations: Vec<Crs> = stations_to_query
             .iter()
-            .map(|(_, station, _)| *station)
+            .map(|(_, _, station, _)| *station)
             .filter(|s| !departures_cache.contains_key(s))
-            .collect::<HashSet<_>>()
-            .into_iter()
             .collect();
 
         debug!(
@@ -563,6 +1321,7 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             uncached = uncached_stations.len(),
             "Fetching departures for 2-change search"
         );
+        let cache_hits = stations_to_query.len() - uncached_stations.len();
 
         // Batch fetch departures in parallel.
         // We use start_time (current position) for all stations rather than per-station
@@ -570,13 +1329,11 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         // (now + 120 min max); using an earlier start fetches a superset of departures.
         // The filtering at line ~569 discards departures we can't actually catch.
         let api_calls = self
-            .batch_fetch_departures(&uncached_stations, start_time, departures_cache)
+            .batch_fetch_departures(&uncached_stations, start_time, departures_cache, quality)
             .await;
 
         // Now process synchronously using the cache
-        for (alight_idx, query_station, walk_to_query) in stations_to_query {
-            let alight_call = &train.calls[alight_idx];
-
+        for (alight_idx, alight_call, query_station, walk_to_query) in stations_to_query {
             let arrival_at_alight = match alight_call
                 .expected_arrival()
                 .or_else(|| alight_call.expected_departure())
@@ -603,17 +1360,17 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             // Check each departing service for connections to feeder stations
             for bridge_service in &departures {
                 // Find where we board this service
-                let bridge_board_idx = match bridge_service
-                    .calls
-                    .iter()
-                    .position(|c| c.station == query_station)
-                {
-                    Some(idx) => idx,
-                    None => continue,
-                };
+                let (bridge_board_idx, bridge_board_call) =
+                    match bridge_service.board_call_at(&query_station) {
+                        Some(found) => found,
+                        None => continue,
+                    };
+
+                if !bridge_board_call.can_board() {
+                    continue;
+                }
 
                 // Check if service departs after we're available
-                let bridge_board_call = &bridge_service.calls[bridge_board_idx];
                 let bridge_depart = match bridge_board_call.expected_departure() {
                     Some(t) => t,
                     None => continue,
@@ -623,13 +1380,9 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
                 }
 
                 // For each call on the bridge service AFTER where we board
-                for (bridge_alight_idx, bridge_call) in bridge_service
-                    .calls
-                    .iter()
-                    .enumerate()
-                    .skip(bridge_board_idx + 1)
+                for (bridge_alight_idx, bridge_call) in bridge_service.calls_after(bridge_board_idx)
                 {
-                    if bridge_call.is_cancelled {
+                    if bridge_call.is_cancelled || !bridge_call.can_alight() {
                         continue;
                     }
 
@@ -673,19 +1426,17 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
                             if let Some(journey) = self.build_two_change_journey(
                                 train,
                                 request.current_position,
-                                CallIndex(alight_idx),
+                                alight_idx,
                                 &alight_call.station,
                                 &query_station,
                                 walk_to_query,
                                 bridge_service,
-                                CallIndex(bridge_board_idx),
-                                CallIndex(bridge_alight_idx),
+                                bridge_board_idx,
+                                bridge_alight_idx,
                                 &bridge_call.station,
                                 &feeder_station,
                                 walk_to_feeder,
-                                &feeder.service,
-                                feeder.board_index,
-                                &request.destination,
+                                feeder,
                             ) {
                                 journeys.push(journey);
                             }
@@ -695,57 +1446,30 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             }
         }
 
-        Ok((journeys, api_calls))
+        Ok((journeys, api_calls, cache_hits))
     }
 
-    /// Batch fetch departures for multiple stations in parallel.
+    /// Batch fetch departures for multiple stations, paced as configured.
     ///
-    /// Fetches departures for all given stations, respecting `batch_size` for
-    /// parallelism. Results are inserted into the cache. Returns the number
-    /// of API calls made.
+    /// See [`pacing::batch_fetch_departures`]; stops early (leaving
+    /// remaining stations unfetched) if the search is cancelled.
     async fn batch_fetch_departures(
         &self,
         stations: &[Crs],
         after: RailTime,
         cache: &mut HashMap<Crs, Vec<Arc<Service>>>,
+        quality: &mut DataQuality,
     ) -> usize {
-        if stations.is_empty() {
-            return 0;
-        }
-
-        let mut api_calls = 0;
-
-        for batch in stations.chunks(self.config.batch_size) {
-            let futures: Vec<_> = batch
-                .iter()
-                .map(|station| async move {
-                    let result = self.provider.get_departures(station, after).await;
-                    (*station, result)
-                })
-                .collect();
-
-            let results = join_all(futures).await;
-
-            for (station, result) in results {
-                api_calls += 1;
-                match result {
-                    Ok(deps) => {
-                        cache.insert(station, deps);
-                    }
-                    Err(e) => {
-                        debug!(
-                            station = %station.as_str(),
-                            error = %e,
-                            "Failed to fetch departures, using empty"
-                        );
-                        // Insert empty vec so we don't retry
-                        cache.insert(station, vec![]);
-                    }
-                }
-            }
-        }
-
-        api_calls
+        pacing::batch_fetch_departures(
+            stations,
+            after,
+            cache,
+            quality,
+            self.config,
+            self.provider,
+            self.cancel.as_ref(),
+        )
+        .await
     }
 
     /// Build a 2-change journey from components.
@@ -764,26 +1488,11 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
         alight_second_station: &Crs,
         board_third_station: &Crs,
         walk_to_third: Duration,
-        third_train: &Arc<Service>,
-        board_third: CallIndex,
-        destination: &Crs,
+        feeder: &FeederInfo,
     ) -> Option<Journey> {
         let leg1 = Leg::new(first_train.clone(), board_first, alight_first).ok()?;
         let leg2 = Leg::new(second_train.clone(), board_second, alight_second).ok()?;
 
-        // Third train goes to destination
-        // Note: service may continue past destination, so find actual destination call
-        let alight_third_idx = third_train
-            .calls
-            .iter()
-            .position(|c| c.station == *destination)?;
-        let leg3 = Leg::new(
-            third_train.clone(),
-            board_third,
-            CallIndex(alight_third_idx),
-        )
-        .ok()?;
-
         let mut segments = vec![Segment::Train(leg1)];
 
         // Walk between first and second train if needed
@@ -806,7 +1515,8 @@ impl<'a, P: ServiceProvider> Planner<'a, P> {
             )));
         }
 
-        segments.push(Segment::Train(leg3));
+        // Third train goes to the destination, or near enough to walk
+        segments.extend(feeder.segments()?);
 
         Journey::new(segments).ok()
     }
@@ -820,10 +1530,10 @@ mod tests;
 #[cfg(test)]
 mod proptests {
     use super::*;
-    use crate::domain::{Call, ServiceRef};
+    use crate::domain::ServiceRef;
+    use crate::testing::{Network, NetworkBuilder, ServiceBuilder};
     use chrono::{NaiveDate, NaiveTime};
     use proptest::prelude::*;
-    use std::collections::HashMap;
 
     // ========== Test infrastructure ==========
 
@@ -878,107 +1588,15 @@ mod proptests {
             operator_code: None,
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         })
     }
 
-    /// Mock provider that serves from pre-configured data.
-    /// Simulates Darwin API behavior: services sorted by time, limited by max_rows.
-    struct TestProvider {
-        /// Departures at each station, sorted by departure time.
-        departures: HashMap<Crs, Vec<Arc<Service>>>,
-        /// Arrivals at each station, sorted by arrival time.
-        arrivals: HashMap<Crs, Vec<Arc<Service>>>,
-        /// Maximum arrivals to return (simulates Darwin num_rows limit).
-        max_arrivals: usize,
-    }
-
-    impl TestProvider {
-        fn new(services: &[Arc<Service>]) -> Self {
-            Self::with_max_arrivals(services, usize::MAX)
-        }
-
-        /// Create provider with limited arrivals but unlimited departures.
-        /// This simulates the real-world scenario: busy destination has many
-        /// arrivals (filling the limit), but intermediate stations have fewer
-        /// departures (all available).
-        fn with_max_arrivals(services: &[Arc<Service>], max_arrivals: usize) -> Self {
-            let mut departures: HashMap<Crs, Vec<Arc<Service>>> = HashMap::new();
-            let mut arrivals: HashMap<Crs, Vec<Arc<Service>>> = HashMap::new();
-
-            for service in services {
-                // Add to departures for each station (except last - can't depart from terminus)
-                for call in service
-                    .calls
-                    .iter()
-                    .take(service.calls.len().saturating_sub(1))
-                {
-                    departures
-                        .entry(call.station)
-                        .or_default()
-                        .push(service.clone());
-                }
-                // Add to arrivals for each station (except first - that's origin/departure only)
-                // This matches Darwin API behavior: arrivals at station X includes all services
-                // that call at X, not just those terminating there
-                for call in service.calls.iter().skip(1) {
-                    arrivals
-                        .entry(call.station)
-                        .or_default()
-                        .push(service.clone());
-                }
-            }
-
-            // Sort departures by departure time at each station
-            for (station, station_services) in departures.iter_mut() {
-                station_services.sort_by_key(|s| {
-                    s.calls
-                        .iter()
-                        .find(|c| c.station == *station)
-                        .and_then(|c| c.expected_departure())
-                });
-            }
-
-            // Sort arrivals by arrival time at each station
-            for (station, station_services) in arrivals.iter_mut() {
-                station_services.sort_by_key(|s| {
-                    s.calls
-                        .iter()
-                        .find(|c| c.station == *station)
-                        .and_then(|c| c.expected_arrival())
-                });
-            }
-
-            Self {
-                departures,
-                arrivals,
-                max_arrivals,
-            }
-        }
-    }
-
-    impl ServiceProvider for TestProvider {
-        async fn get_departures(
-            &self,
-            station: &Crs,
-            _after: RailTime,
-        ) -> Result<Vec<Arc<Service>>, SearchError> {
-            // Departures are unlimited - intermediate stations typically have
-            // fewer services than a busy destination's arrivals
-            Ok(self.departures.get(station).cloned().unwrap_or_default())
-        }
-
-        async fn get_arrivals(
-            &self,
-            station: &Crs,
-            _after: RailTime,
-        ) -> Result<Vec<Arc<Service>>, SearchError> {
-            // Arrivals are limited to simulate Darwin's num_rows constraint
-            Ok(self
-                .arrivals
-                .get(station)
-                .map(|s| s.iter().take(self.max_arrivals).cloned().collect())
-                .unwrap_or_default())
-        }
+    /// Every service in `services`, on unlimited boards.
+    fn network(services: &[Arc<Service>]) -> Network {
+        NetworkBuilder::new()
+            .services(services.iter().cloned())
+            .build()
     }
 
     // ========== Naive BFS reference implementation ==========
@@ -1012,11 +1630,11 @@ mod proptests {
 
         // Check direct journey first
         let train = &request.current_service;
-        let pos = request.current_position.0;
+        let pos = request.current_position;
 
-        for (idx, call) in train.calls.iter().enumerate().skip(pos) {
-            if call.station == request.destination && !call.is_cancelled {
-                let leg = Leg::new(train.clone(), request.current_position, CallIndex(idx)).ok();
+        for (idx, call) in train.calls_after(pos) {
+            if call.station == request.destination && !call.is_cancelled && call.can_alight() {
+                let leg = Leg::new(train.clone(), pos, idx).ok();
                 if let Some(leg) = leg
                     && let Ok(j) = Journey::new(vec![Segment::Train(leg)])
                 {
@@ -1028,8 +1646,11 @@ mod proptests {
         // Initialize frontier
         let mut frontier: Vec<State> = Vec::new();
 
-        for (alight_idx, alight_call) in train.calls.iter().enumerate().skip(pos) {
-            if alight_call.is_cancelled || alight_call.station == request.destination {
+        for (alight_idx, alight_call) in train.calls_after(pos) {
+            if alight_call.is_cancelled
+                || !alight_call.can_alight()
+                || alight_call.station == request.destination
+            {
                 continue;
             }
 
@@ -1041,11 +1662,7 @@ mod proptests {
                 None => continue,
             };
 
-            let leg = match Leg::new(
-                train.clone(),
-                request.current_position,
-                CallIndex(alight_idx),
-            ) {
+            let leg = match Leg::new(train.clone(), pos, alight_idx) {
                 Ok(l) => l,
                 Err(_) => continue,
             };
@@ -1092,16 +1709,10 @@ mod proptests {
                     .await?;
 
                 for service in &departures {
-                    let board_idx = match service
-                        .calls
-                        .iter()
-                        .position(|c| c.station == state.station)
-                    {
-                        Some(idx) => idx,
-                        None => continue,
+                    let (board_idx, board_call) = match service.board_call_at(&state.station) {
+                        Some(found) if found.1.can_board() => found,
+                        _ => continue,
                     };
-
-                    let board_call = &service.calls[board_idx];
                     let board_time = match board_call.expected_departure() {
                         Some(t) => t,
                         None => continue,
@@ -1111,10 +1722,8 @@ mod proptests {
                         continue;
                     }
 
-                    for (alight_idx, alight_call) in
-                        service.calls.iter().enumerate().skip(board_idx + 1)
-                    {
-                        if alight_call.is_cancelled {
+                    for (alight_idx, alight_call) in service.calls_after(board_idx) {
+                        if alight_call.is_cancelled || !alight_call.can_alight() {
                             continue;
                         }
 
@@ -1131,11 +1740,7 @@ mod proptests {
                             continue;
                         }
 
-                        let leg = match Leg::new(
-                            service.clone(),
-                            CallIndex(board_idx),
-                            CallIndex(alight_idx),
-                        ) {
+                        let leg = match Leg::new(service.clone(), board_idx, alight_idx) {
                             Ok(l) => l,
                             Err(_) => continue,
                         };
@@ -1167,22 +1772,20 @@ mod proptests {
                                 continue;
                             }
 
-                            // Check if walk reaches destination
+                            let walk = Walk::new(alight_call.station, walkable_station, walk_time);
+                            let mut walk_segments = new_segments.clone();
+                            walk_segments.push(Segment::Walk(walk));
+
+                            // A walk to the destination finishes a journey
                             if walkable_station == request.destination {
-                                let walk =
-                                    Walk::new(alight_call.station, walkable_station, walk_time);
-                                let mut walk_segments = new_segments.clone();
-                                walk_segments.push(Segment::Walk(walk));
-                                if let Ok(j) = Journey::new(walk_segments) {
+                                if total_so_far + walk_time <= max_journey
+                                    && let Ok(j) = Journey::new(walk_segments)
+                                {
                                     journeys.push(j);
                                 }
                                 continue;
                             }
 
-                            let walk = Walk::new(alight_call.station, walkable_station, walk_time);
-                            let mut walk_segments = new_segments.clone();
-                            walk_segments.push(Segment::Walk(walk));
-
                             next_frontier.push(State {
                                 segments: walk_segments,
                                 station: walkable_station,
@@ -1201,81 +1804,144 @@ mod proptests {
     }
 
     // ========== Proptest strategies ==========
+    //
+    // Scenarios are generated as plain data (station indices and minutes)
+    // and only built into services when run, so a failing case shrinks to
+    // a small network with few walks rather than getting stuck in
+    // `prop_flat_map`.
+
+    /// A generated service: distinct stations, the minute it leaves the
+    /// first, and the minutes between arrivals at each subsequent call.
+    #[derive(Debug, Clone)]
+    struct ServiceSpec {
+        stations: Vec<usize>,
+        start: u16,
+        gaps: Vec<u16>,
+    }
 
-    /// Generate a valid service (sequence of calls with increasing times).
-    /// Ensures no station is visited twice (no loops).
-    fn service_strategy(id: usize) -> impl Strategy<Value = Arc<Service>> {
-        // Generate 2-5 UNIQUE station indices
-        (
-            // Use prop_shuffle to get unique stations
-            Just(Vec::from_iter(0..STATIONS.len()))
-                .prop_shuffle()
-                .prop_map(|v| v.into_iter().take(5).collect::<Vec<_>>()),
-            // Number of calls (2-5, but at most the number of unique stations)
-            2usize..=5,
-            // Start time in minutes from midnight (6am - 10pm)
-            360u16..1320,
-        )
-            .prop_flat_map(move |(shuffled_stations, n_calls, start_time)| {
-                let n_calls = n_calls.min(shuffled_stations.len());
-                let station_indices: Vec<usize> =
-                    shuffled_stations.into_iter().take(n_calls).collect();
-
-                // Generate time gaps between stations (10-60 mins each)
-                let n_gaps = station_indices.len().saturating_sub(1);
-                prop::collection::vec(10u16..60, n_gaps).prop_map(move |gaps| {
-                    let mut calls_data = Vec::new();
-                    let mut current_time = start_time;
-
-                    for (i, &station_idx) in station_indices.iter().enumerate() {
-                        let arr_mins = if i == 0 { 0 } else { current_time };
-                        let dep_mins = if i == station_indices.len() - 1 {
-                            0
-                        } else {
-                            current_time + 2 // 2 min dwell time
-                        };
-                        calls_data.push((station_idx, arr_mins, dep_mins));
-
-                        if i < gaps.len() {
-                            current_time += gaps[i];
-                        }
-                    }
+    impl ServiceSpec {
+        fn build(&self, id: usize) -> Arc<Service> {
+            let clock = |mins: u16| format!("{:02}:{:02}", mins / 60 % 24, mins % 60);
+            let last = self.stations.len() - 1;
+            let mut service = ServiceBuilder::new(format!("SVC{id}"));
+            let mut arrives = self.start;
+            for (i, &station) in self.stations.iter().enumerate() {
+                service = service.call(STATIONS[station]);
+                if i > 0 {
+                    service = service.arr(&clock(arrives));
+                }
+                if i < last {
+                    service = service.dep(&clock(arrives + 2)); // 2 min dwell
+                    arrives += self.gaps[i];
+                }
+            }
+            service.build()
+        }
+    }
 
-                    make_service(id, calls_data)
-                })
-            })
+    /// A generated network, walks between its stations, and a search
+    /// across it.
+    #[derive(Debug, Clone)]
+    struct Scenario {
+        services: Vec<ServiceSpec>,
+        /// `(station, station, minutes)`; walks from a station to itself
+        /// are skipped
+        walks: Vec<(usize, usize, i64)>,
+        /// Stations a relay of trains runs between from 07:00, each
+        /// connecting with the next. If set, the user is on the first and
+        /// bound for the last station, so the search needs several changes.
+        relay: Vec<usize>,
+        /// Index of the service the user is on, from its first stop, if
+        /// there's no relay
+        current: usize,
+        destination: usize,
+        max_changes: usize,
     }
 
-    /// Generate a network of services.
-    fn network_strategy() -> impl Strategy<Value = Vec<Arc<Service>>> {
-        // Generate 3-8 services
-        (3usize..=8).prop_flat_map(|n_services| {
-            let strategies: Vec<_> = (0..n_services).map(service_strategy).collect();
-            strategies
-                .into_iter()
-                .collect::<Vec<_>>()
-                .prop_map(|services| services)
-        })
+    impl Scenario {
+        /// The relay's trains, then the other services.
+        fn services(&self) -> Vec<Arc<Service>> {
+            let relay = self
+                .relay
+                .windows(2)
+                .enumerate()
+                .map(|(i, stations)| ServiceSpec {
+                    stations: stations.to_vec(),
+                    start: 420 + 30 * i as u16,
+                    gaps: vec![20],
+                });
+            relay
+                .chain(self.services.iter().cloned())
+                .enumerate()
+                .map(|(id, spec)| spec.build(id))
+                .collect()
+        }
+
+        fn walkable(&self) -> WalkableConnections {
+            let mut walkable = WalkableConnections::new();
+            for &(a, b, minutes) in &self.walks {
+                if a != b {
+                    walkable
+                        .set(station_crs(a), station_crs(b), minutes)
+                        .unwrap();
+                }
+            }
+            walkable
+        }
+
+        fn request(&self, services: &[Arc<Service>]) -> SearchRequest {
+            match self.relay.last() {
+                Some(&destination) => {
+                    SearchRequest::new(services[0].clone(), CallIndex(0), station_crs(destination))
+                }
+                None => {
+                    let current = services[self.current % services.len()].clone();
+                    SearchRequest::new(current, CallIndex(0), station_crs(self.destination))
+                }
+            }
+        }
     }
 
-    /// Generate a search request for a given network.
-    fn search_request_strategy(
-        services: Vec<Arc<Service>>,
-    ) -> impl Strategy<Value = (Vec<Arc<Service>>, SearchRequest, Crs)> {
-        // Pick a random service as current train
-        let n_services = services.len();
-        (0..n_services, 0usize..STATIONS.len()).prop_map(move |(svc_idx, dest_idx)| {
-            let current_service = services[svc_idx % services.len()].clone();
-            let pos = 0; // Start at first stop
-            let destination = station_crs(dest_idx);
-            let request = SearchRequest::new(current_service, CallIndex(pos), destination);
-            (services.clone(), request, destination)
-        })
+    /// Generate a valid service: 2-4 distinct stations, 10-60 minutes apart,
+    /// leaving between 06:00 and 10:00 so that connections line up.
+    fn service_strategy() -> impl Strategy<Value = ServiceSpec> {
+        (
+            prop::sample::subsequence(Vec::from_iter(0..STATIONS.len()), 2..=4).prop_shuffle(),
+            360u16..600,
+            prop::collection::vec(10u16..60, 4),
+        )
+            .prop_map(|(stations, start, gaps)| ServiceSpec {
+                stations,
+                start,
+                gaps,
+            })
     }
 
-    /// Combined strategy: generate network + search request.
-    fn scenario_strategy() -> impl Strategy<Value = (Vec<Arc<Service>>, SearchRequest, Crs)> {
-        network_strategy().prop_flat_map(search_request_strategy)
+    /// Generate a scenario: 4-14 services, up to 4 walks of 3-15 minutes,
+    /// half the time a relay needing 2-4 changes, and a search allowing 1-4
+    /// changes.
+    fn scenario_strategy() -> impl Strategy<Value = Scenario> {
+        (
+            prop::collection::vec(service_strategy(), 4..=14),
+            prop::collection::vec((0..STATIONS.len(), 0..STATIONS.len(), 3i64..=15), 0..=4),
+            prop_oneof![
+                Just(Vec::new()),
+                prop::sample::subsequence(Vec::from_iter(0..STATIONS.len()), 4..=6).prop_shuffle(),
+            ],
+            0usize..14,
+            0..STATIONS.len(),
+            1usize..=4,
+        )
+            .prop_map(
+                |(services, walks, relay, current, destination, max_changes)| Scenario {
+                    services,
+                    walks,
+                    relay,
+                    current,
+                    destination,
+                    max_changes,
+                },
+            )
     }
 
     // ========== Property tests ==========
@@ -1290,20 +1956,25 @@ mod proptests {
     /// arrivals-first sees a limited view while naive BFS sees all services.
     /// This tests that arrivals-first handles incomplete ArrivalsIndex correctly.
     fn arrivals_first_dominates_naive_arrival_times(
-        services: Vec<Arc<Service>>,
-        request: SearchRequest,
+        scenario: &Scenario,
         max_rows: usize,
     ) -> Result<(), TestCaseError> {
         let rt = tokio::runtime::Runtime::new().unwrap();
+        let services = scenario.services();
+        let request = scenario.request(&services);
 
         rt.block_on(async {
             // Naive BFS uses unlimited provider - it represents "all possible journeys"
-            let unlimited_provider = TestProvider::new(&services);
-            // Arrivals-first uses limited arrivals - simulates busy destination
-            let limited_provider = TestProvider::with_max_arrivals(&services, max_rows);
-            let walkable = WalkableConnections::new();
+            let unlimited_provider = network(&services);
+            // Arrivals-first uses limited arrivals - simulates busy destination,
+            // while intermediate stations' departures are all available
+            let limited_provider = NetworkBuilder::new()
+                .services(services.iter().cloned())
+                .max_arrivals(max_rows)
+                .build();
+            let walkable = scenario.walkable();
             let config = SearchConfig {
-                max_changes: 2,
+                max_changes: scenario.max_changes,
                 max_results: 100,
                 ..SearchConfig::default()
             };
@@ -1380,8 +2051,8 @@ mod proptests {
 
         /// Test with unlimited arrivals - basic correctness.
         #[test]
-        fn arrivals_first_complete((services, request, _dest) in scenario_strategy()) {
-            arrivals_first_dominates_naive_arrival_times(services, request, usize::MAX)?;
+        fn arrivals_first_complete(scenario in scenario_strategy()) {
+            arrivals_first_dominates_naive_arrival_times(&scenario, usize::MAX)?;
         }
 
         /// Test with limited arrivals - simulates Darwin's num_rows limit.
@@ -1389,13 +2060,103 @@ mod proptests {
         /// even when the ArrivalsIndex doesn't have valid connections.
         #[test]
         fn arrivals_first_complete_with_limited_arrivals(
-            (services, request, _dest) in scenario_strategy(),
+            scenario in scenario_strategy(),
             max_rows in 2usize..=5
         ) {
-            arrivals_first_dominates_naive_arrival_times(services, request, max_rows)?;
+            arrivals_first_dominates_naive_arrival_times(&scenario, max_rows)?;
+        }
+    }
+
+    /// What a search returned, leaving out timings: its journeys and near
+    /// misses in order, and the API calls made.
+    fn outcome(result: &SearchResult) -> String {
+        format!(
+            "{:?}\n{:?}\n{}",
+            result.journeys, result.near_misses, result.routes_explored
+        )
+    }
+
+    /// Search `scenario` on a network with its services added in `order`.
+    fn search_outcome(
+        rt: &tokio::runtime::Runtime,
+        scenario: &Scenario,
+        order: &[Arc<Service>],
+    ) -> String {
+        let services = scenario.services();
+        let network = network(order);
+        let walkable = scenario.walkable();
+        let config = SearchConfig {
+            max_changes: scenario.max_changes,
+            ..SearchConfig::default()
+        };
+        let planner = Planner::new(&network, &walkable, &config);
+        let result = rt
+            .block_on(planner.search(&scenario.request(&services)))
+            .unwrap();
+        outcome(&result)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(50))]
+
+        /// The same search gives the same results and makes the same number
+        /// of API calls each time, and whatever order boards list trains
+        /// leaving at the same time in.
+        #[test]
+        fn search_is_deterministic(scenario in scenario_strategy()) {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            let services = scenario.services();
+            let reversed: Vec<_> = services.iter().rev().cloned().collect();
+
+            let first = search_outcome(&rt, &scenario, &services);
+            for _ in 0..3 {
+                prop_assert_eq!(&search_outcome(&rt, &scenario, &services), &first);
+            }
+            prop_assert_eq!(&search_outcome(&rt, &scenario, &reversed), &first);
         }
     }
 
+    /// The scenarios include journeys the old fixed-depth, walk-free
+    /// strategy never produced: ones needing a walk, and ones with 3 or
+    /// more changes.
+    #[test]
+    fn scenarios_exercise_walks_and_deep_changes() {
+        use proptest::test_runner::{Config, TestRunner};
+        use std::cell::Cell;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut runner = TestRunner::new(Config::with_cases(300));
+        let with_walk = Cell::new(0u32);
+        let deep = Cell::new(0u32);
+
+        let _ = runner.run(&scenario_strategy(), |scenario| {
+            let services = scenario.services();
+            let request = scenario.request(&services);
+            let config = SearchConfig {
+                max_changes: scenario.max_changes,
+                ..SearchConfig::default()
+            };
+            let journeys = rt
+                .block_on(naive_bfs_search(
+                    &network(&services),
+                    &scenario.walkable(),
+                    &config,
+                    &request,
+                ))
+                .unwrap();
+            if journeys.iter().any(|j| j.walks().next().is_some()) {
+                with_walk.set(with_walk.get() + 1);
+            }
+            if journeys.iter().any(|j| j.change_count() >= 3) {
+                deep.set(deep.get() + 1);
+            }
+            Ok(())
+        });
+
+        assert!(with_walk.get() > 0, "Should test some journeys with walks");
+        assert!(deep.get() > 0, "Should test some journeys with 3+ changes");
+    }
+
     // ========== Focused tests for edge cases ==========
 
     /// Test with a scenario requiring exactly 3 changes.
@@ -1439,7 +2200,7 @@ mod proptests {
 
         let services = vec![current_train.clone(), bridge1, bridge2, final_service];
 
-        let provider = TestProvider::new(&services);
+        let provider = network(&services);
         let walkable = WalkableConnections::new();
         let config = SearchConfig {
             max_changes: 3,
@@ -1487,6 +2248,47 @@ mod proptests {
         );
     }
 
+    /// A feeder's connection doesn't stop the search there if a route with
+    /// more changes arrives sooner.
+    #[tokio::test]
+    async fn bfs_explores_on_from_feeder_when_longer_route_is_sooner() {
+        let leg = |id: &str, from: &str, dep: &str, to: &str, arr: &str| {
+            ServiceBuilder::new(id)
+                .call(from)
+                .dep(dep)
+                .call(to)
+                .arr(arr)
+                .build()
+        };
+        let current_train = leg("CT", "RDG", "07:00", "PAD", "07:20");
+        let services = vec![
+            current_train.clone(),
+            // One change at Paddington, arriving 08:51
+            leg("SLOW", "PAD", "08:29", "OXF", "08:51"),
+            // Three changes, arriving 08:50
+            leg("R1", "PAD", "07:32", "KGX", "07:50"),
+            leg("R2", "KGX", "08:02", "DID", "08:20"),
+            leg("R3", "DID", "08:32", "OXF", "08:50"),
+        ];
+        let provider = network(&services);
+        let config = SearchConfig {
+            max_changes: 3,
+            ..SearchConfig::default()
+        };
+
+        let request = SearchRequest::new(current_train, CallIndex(0), crs("OXF"));
+        let planner = Planner::new(&provider, provider.walkable(), &config);
+        let result = planner.search(&request).await.unwrap();
+
+        let found: Vec<_> = result
+            .journeys
+            .iter()
+            .map(|j| (j.arrival_time().to_string(), j.change_count()))
+            .collect();
+        assert!(found.contains(&("08:51".to_string(), 1)), "{found:?}");
+        assert!(found.contains(&("08:50".to_string(), 3)), "{found:?}");
+    }
+
     /// Walks before first connection should not count as a change.
     ///
     /// Regression test: naive_bfs_search previously set `changes: 1` for initial
@@ -1519,11 +2321,11 @@ mod proptests {
         );
 
         let services = vec![current_train.clone(), connecting_train];
-        let provider = TestProvider::new(&services);
+        let provider = network(&services);
 
         // OXF -> DID is walkable (10 minutes)
         let mut walkable = WalkableConnections::new();
-        walkable.add(crs("OXF"), crs("DID"), 10);
+        walkable.add(crs("OXF"), crs("DID"), 10).unwrap();
 
         let config = SearchConfig {
             max_changes: 1, // Key: only 1 change allowed
@@ -1,53 +1,32 @@
 //! Unit tests for the arrivals-first search algorithm.
 
 use super::*;
-use crate::domain::{Call, ServiceRef};
+use crate::planner::{BoardRequest, ContinuationHint, DominanceCriterion};
+use crate::stations::StationLocation;
+use crate::testing::{NetworkBuilder, ServiceBuilder, crs, time};
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-fn date() -> chrono::NaiveDate {
-    chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
-}
-
-fn time(s: &str) -> RailTime {
-    RailTime::parse_hhmm(s, date()).unwrap()
-}
-
-fn crs(s: &str) -> Crs {
-    Crs::parse(s).unwrap()
-}
-
 fn make_service(
     id: &str,
     calls_data: &[(&str, &str, &str, &str)], // (crs, name, arr, dep)
 ) -> Arc<Service> {
-    let calls: Vec<Call> = calls_data
+    calls_data
         .iter()
-        .map(|(station, name, arr, dep)| {
-            let mut call = Call::new(crs(station), (*name).to_string());
-            if !arr.is_empty() {
-                call.booked_arrival = Some(time(arr));
-            }
-            if !dep.is_empty() {
-                call.booked_departure = Some(time(dep));
-            }
-            call
-        })
-        .collect();
-
-    let board_crs = calls
-        .first()
-        .map(|c| c.station)
-        .unwrap_or_else(|| crs("XXX"));
-
-    Arc::new(Service {
-        service_ref: ServiceRef::new(id.to_string(), board_crs),
-        headcode: None,
-        operator: "Test".to_string(),
-        operator_code: None,
-        calls,
-        board_station_idx: CallIndex(0),
-    })
+        .fold(
+            ServiceBuilder::new(id),
+            |service, (station, name, arr, dep)| {
+                let mut service = service.call(station).named(name);
+                if !arr.is_empty() {
+                    service = service.arr(arr);
+                }
+                if !dep.is_empty() {
+                    service = service.dep(dep);
+                }
+                service
+            },
+        )
+        .build()
 }
 
 /// Mock service provider for testing.
@@ -127,6 +106,113 @@ async fn direct_journey_found() {
     assert_eq!(result.journeys[0].destination(), &crs("BRI"));
 }
 
+/// PAD -> RDG -> SWI -> BRI, with a rear portion dividing at Swindon for
+/// Kemble and Cheltenham.
+fn dividing_train() -> Arc<Service> {
+    use crate::domain::{Formation, Portion};
+
+    let portion = make_service(
+        "",
+        &[
+            ("KEM", "Kemble", "11:10", "11:11"),
+            ("CNM", "Cheltenham Spa", "11:45", ""),
+        ],
+    );
+    let mut train = (*make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("SWI", "Swindon", "10:50", "10:55"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    ))
+    .clone();
+    train.formation = Some(Formation {
+        length: Some(8),
+        portions: vec![Portion {
+            divides_at: crs("SWI"),
+            destination: crs("CNM"),
+            destination_name: "Cheltenham Spa".into(),
+            remaining_coaches: Some(5),
+            calls: portion.calls.clone(),
+        }],
+        ..Formation::default()
+    });
+    Arc::new(train)
+}
+
+#[tokio::test]
+async fn direct_journey_on_dividing_portion() {
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(dividing_train(), CallIndex(0), crs("CNM"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    let journey = &result.journeys[0];
+    assert!(journey.is_direct());
+    assert_eq!(journey.arrival_time(), time("11:45"));
+
+    let advice = journey.legs().next().unwrap().boarding_advice().unwrap();
+    assert_eq!(
+        advice.hint(),
+        "Travel in the rear 3 coaches: the train divides at Swindon and the front goes to Bristol."
+    );
+}
+
+#[tokio::test]
+async fn direct_journey_before_division_found_once() {
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(dividing_train(), CallIndex(0), crs("RDG"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    let leg = result.journeys[0].legs().next().unwrap();
+    assert!(leg.boarding_advice().is_none());
+}
+
+#[tokio::test]
+async fn search_through_dynamic_provider() {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("MAI", "Maidenhead", "10:12", "10:13"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let arriving_service = make_service(
+        "AR",
+        &[
+            ("RDG", "Reading", "", "10:35"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    );
+    let mut mock = MockProvider::new();
+    mock.add_arrivals(crs("BRI"), vec![arriving_service]);
+    let provider: Arc<dyn DynServiceProvider> = Arc::new(mock);
+
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert_eq!(result.journeys[0].change_count(), 1);
+    assert_eq!(result.journeys[0].arrival_time(), time("11:20"));
+}
+
 #[tokio::test]
 async fn direct_journey_needs_zero_api_calls_when_max_changes_zero() {
     let current_train = make_service(
@@ -197,6 +283,45 @@ async fn one_change_journey_found() {
     assert_eq!(result.routes_explored, 3);
 }
 
+#[tokio::test]
+async fn one_change_via_service_calling_at_destination_twice() {
+    // The arriving service loops: it leaves BRI, calls at RDG, and comes
+    // back to BRI. Its arrivals board entry is the second BRI call.
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+
+    let mut looping = (*make_service(
+        "LOOP",
+        &[
+            ("BRI", "Bristol", "", "09:50"),
+            ("RDG", "Reading", "10:33", "10:35"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    ))
+    .clone();
+    looping.board_station_idx = CallIndex(2);
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![Arc::new(looping)]);
+
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    let journey = &result.journeys[0];
+    assert_eq!(journey.change_count(), 1);
+    assert_eq!(journey.arrival_time(), time("11:20"));
+}
+
 #[tokio::test]
 async fn one_change_needs_only_arrivals_when_max_changes_is_one() {
     // Same setup as one_change_journey_found but with max_changes=1
@@ -237,6 +362,57 @@ async fn one_change_needs_only_arrivals_when_max_changes_is_one() {
     assert_eq!(result.routes_explored, 1);
 }
 
+#[tokio::test]
+async fn later_search_reuses_recent_arrivals() {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("MAI", "Maidenhead", "10:12", "10:13"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let arriving_service = make_service(
+        "AR",
+        &[
+            ("RDG", "Reading", "", "10:35"),
+            ("SWI", "Swindon", "10:55", "10:57"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![arriving_service]);
+
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        ..SearchConfig::default()
+    };
+    let recent = RecentArrivals::new();
+    let planner = Planner::new(&provider, &walkable, &config).with_recent_arrivals(&recent);
+
+    // The arrivals board is fetched as part of the 1-change phase
+    let first = SearchRequest::new(current_train.clone(), CallIndex(0), crs("BRI"));
+    let result = planner.search(&first).await.unwrap();
+    assert_eq!(result.stats.one_change.api_calls, 1);
+
+    // A minute later, further along the same train
+    let later = SearchRequest::new(current_train.clone(), CallIndex(1), crs("BRI"));
+    let result = planner.search(&later).await.unwrap();
+    assert_eq!(result.stats.one_change.api_calls, 0);
+    assert!(!result.journeys.is_empty());
+
+    // With reuse disabled, the board is fetched every time
+    let without_reuse = SearchConfig {
+        arrivals_reuse_secs: 0,
+        ..config.clone()
+    };
+    let planner = Planner::new(&provider, &walkable, &without_reuse).with_recent_arrivals(&recent);
+    let result = planner.search(&later).await.unwrap();
+    assert_eq!(result.stats.one_change.api_calls, 1);
+}
+
 #[tokio::test]
 async fn one_change_with_walk() {
     // Current train: PAD -> KGX
@@ -264,7 +440,7 @@ async fn one_change_with_walk() {
 
     // KGX -> STP is walkable
     let mut walkable = WalkableConnections::new();
-    walkable.add(crs("KGX"), crs("STP"), 5);
+    walkable.add(crs("KGX"), crs("STP"), 5).unwrap();
 
     let config = SearchConfig::default();
 
@@ -280,6 +456,46 @@ async fn one_change_with_walk() {
     assert!(journey.walks().count() > 0);
 }
 
+#[tokio::test]
+async fn one_change_finishing_with_walk() {
+    // Current train: PAD -> RDG
+    // Feeder: RDG -> STP, nothing arriving at EUS itself
+    // Walk STP -> EUS
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let feeder = make_service(
+        "FD",
+        &[
+            ("RDG", "Reading", "", "10:35"),
+            ("STP", "St Pancras", "11:10", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("STP"), vec![feeder]);
+
+    let mut walkable = WalkableConnections::new();
+    walkable.add(crs("STP"), crs("EUS"), 8).unwrap();
+
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("EUS"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    let journey = &result.journeys[0];
+    assert_eq!(journey.change_count(), 1);
+    assert_eq!(journey.destination(), &crs("EUS"));
+    assert_eq!(journey.arrival_time(), time("11:18"));
+    let walk = journey.final_walk().unwrap();
+    assert_eq!((walk.from, walk.to), (crs("STP"), crs("EUS")));
+}
+
 #[tokio::test]
 async fn respects_min_connection_time() {
     // Current train: PAD -> RDG arriving 10:25
@@ -318,6 +534,58 @@ async fn respects_min_connection_time() {
     assert!(result.journeys.is_empty());
 }
 
+#[tokio::test]
+async fn footbridge_changes_need_a_larger_margin() {
+    // Arrive at RDG platform 7 at 10:25; 6 min is enough to change to the
+    // other face of the island, but not to cross to platform 1
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("RDG")
+        .arr("10:25")
+        .platform("7")
+        .build();
+    let island = ServiceBuilder::new("IS")
+        .call("RDG")
+        .dep("10:31")
+        .platform("8")
+        .call("BRI")
+        .arr("11:10")
+        .build();
+    let across = ServiceBuilder::new("AC")
+        .call("RDG")
+        .dep("10:31")
+        .platform("1")
+        .call("BRI")
+        .arr("11:05")
+        .build();
+
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        min_connection_mins: 5,
+        footbridge_connection_mins: 3,
+        ..SearchConfig::default()
+    };
+    let mut platforms = PlatformTopology::new();
+    platforms.insert(crs("RDG"), &[&["1"], &["7", "8"]]);
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    for (onward, kept_with_platforms) in [(island, true), (across, false)] {
+        let mut provider = MockProvider::new();
+        provider.add_arrivals(crs("BRI"), vec![onward]);
+
+        // Without platform layouts, every change just needs the minimum
+        let planner = Planner::new(&provider, &walkable, &config);
+        let result = planner.search(&request).await.unwrap();
+        assert_eq!(result.journeys.len(), 1);
+
+        let planner = Planner::new(&provider, &walkable, &config).with_platforms(&platforms);
+        let result = planner.search(&request).await.unwrap();
+        assert_eq!(!result.journeys.is_empty(), kept_with_platforms);
+    }
+}
+
 #[tokio::test]
 async fn two_change_journey_found() {
     // Current train: PAD -> OXF (not a feeder station)
@@ -371,6 +639,93 @@ async fn two_change_journey_found() {
     assert_eq!(result.routes_explored, 3);
 }
 
+#[tokio::test]
+async fn search_stats_break_down_phases() {
+    // Same network as two_change_journey_found: PAD -> OXF, OXF -> RDG, RDG -> BRI
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("OXF", "Oxford", "11:00", ""),
+        ],
+    );
+    let arriving_service = make_service(
+        "AR",
+        &[
+            ("RDG", "Reading", "", "12:00"),
+            ("BRI", "Bristol", "12:30", ""),
+        ],
+    );
+    let bridge_service = make_service(
+        "BR",
+        &[
+            ("OXF", "Oxford", "", "11:10"),
+            ("RDG", "Reading", "11:45", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![arriving_service]);
+    provider.add_departures(crs("OXF"), vec![bridge_service]);
+
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+    let stats = result.stats;
+
+    assert!(stats.direct.ran);
+    assert_eq!(stats.direct.journeys, 0);
+    assert_eq!(stats.direct.api_calls, 0);
+
+    // The arrivals board is attributed to the 1-change phase
+    assert!(stats.one_change.ran);
+    assert_eq!(stats.one_change.journeys, 0);
+    assert_eq!(stats.one_change.api_calls, 1);
+
+    assert!(stats.two_change.ran);
+    assert_eq!(stats.two_change.journeys, 1);
+    assert_eq!(stats.two_change.api_calls, 2);
+
+    // max_changes > 2, so BFS runs, reusing the boards 2-change fetched
+    assert!(stats.bfs.ran);
+    assert!(stats.cache_hits > 0);
+    assert!(stats.cache_hit_ratio().unwrap() > 0.0);
+
+    assert_eq!(stats.api_calls(), result.routes_explored);
+}
+
+#[tokio::test]
+async fn search_stats_skip_phases_not_run() {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("BRI", "Bristol", "11:30", ""),
+        ],
+    );
+
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 0,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let stats = planner.search(&request).await.unwrap().stats;
+
+    assert!(stats.direct.ran);
+    assert_eq!(stats.direct.journeys, 1);
+    assert!(!stats.one_change.ran);
+    assert!(!stats.two_change.ran);
+    assert!(!stats.bfs.ran);
+    assert_eq!(stats.cache_hit_ratio(), None);
+}
+
 #[tokio::test]
 async fn api_calls_bounded() {
     // Train with many stops, none are feeders
@@ -931,8 +1286,8 @@ async fn two_change_dedup_prefers_earliest_arrival_at_query_station() {
     // Set up walkable connections: both STA and STB can walk to QRY
     // but with very different walk times
     let mut walkable = WalkableConnections::new();
-    walkable.add(crs("STA"), crs("QRY"), 14); // 14 min walk
-    walkable.add(crs("STB"), crs("QRY"), 1); // 1 min walk
+    walkable.add(crs("STA"), crs("QRY"), 14).unwrap(); // 14 min walk
+    walkable.add(crs("STB"), crs("QRY"), 1).unwrap(); // 1 min walk
 
     let config = SearchConfig::default(); // 5 min min_connection
 
@@ -966,3 +1321,1729 @@ async fn two_change_dedup_prefers_earliest_arrival_at_query_station() {
     );
     assert_eq!(walk.to, crs("QRY"));
 }
+
+#[tokio::test]
+async fn cancelled_before_start_makes_no_api_calls() {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let token = CancellationToken::new();
+    token.cancel();
+
+    let planner = Planner::new(&provider, &walkable, &config).with_cancellation(token);
+    let result = planner.search(&request).await;
+
+    assert!(matches!(result, Err(SearchError::Cancelled)));
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+    assert_eq!(provider.api_call_count(), 0);
+}
+
+/// Provider that cancels the search as soon as the arrivals board is fetched,
+/// simulating a client disconnecting mid-search.
+struct CancellingProvider {
+    inner: MockProvider,
+    token: CancellationToken,
+}
+
+impl ServiceProvider for CancellingProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.token.cancel();
+        self.inner.get_arrivals(station, after).await
+    }
+}
+
+#[tokio::test]
+async fn cancelled_mid_search_skips_departure_fetches() {
+    // Current train calls at several non-feeder stations, which would
+    // normally each need a departures fetch for the 2-change search.
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("DID", "Didcot", "10:40", "10:42"),
+            ("OXF", "Oxford", "11:00", ""),
+        ],
+    );
+
+    let token = CancellationToken::new();
+    let provider = CancellingProvider {
+        inner: MockProvider::new(),
+        token: token.clone(),
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config).with_cancellation(token);
+    let result = planner.search(&request).await;
+
+    assert!(matches!(result, Err(SearchError::Cancelled)));
+    // Only the arrivals board was fetched
+    assert_eq!(provider.inner.api_call_count(), 1);
+}
+
+/// Provider whose arrivals board fails a set number of times before
+/// succeeding, with a chosen error classification.
+struct FlakyArrivalsProvider {
+    inner: MockProvider,
+    failures_left: Mutex<usize>,
+    kind: ErrorKind,
+}
+
+impl ServiceProvider for FlakyArrivalsProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        let should_fail = {
+            let mut failures_left = self.failures_left.lock().unwrap();
+            let fail = *failures_left > 0;
+            *failures_left = failures_left.saturating_sub(1);
+            fail
+        };
+        if should_fail {
+            return Err(SearchError::FetchError {
+                station: *station,
+                message: "upstream failure".to_string(),
+                kind: self.kind,
+            });
+        }
+        self.inner.get_arrivals(station, after).await
+    }
+}
+
+fn direct_train_to_bri() -> Arc<Service> {
+    make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("BRI", "Bristol Temple Meads", "11:30", ""),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn transient_arrivals_failure_is_retried() {
+    let provider = FlakyArrivalsProvider {
+        inner: MockProvider::new(),
+        failures_left: Mutex::new(1),
+        kind: ErrorKind::Transient,
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(direct_train_to_bri(), CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert!(!result.journeys.is_empty());
+    assert_eq!(*provider.failures_left.lock().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn config_arrivals_failure_is_not_retried() {
+    let provider = FlakyArrivalsProvider {
+        inner: MockProvider::new(),
+        failures_left: Mutex::new(1),
+        kind: ErrorKind::Config,
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(direct_train_to_bri(), CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await;
+
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Config);
+    assert!(!err.is_retryable());
+    // The successful board was never requested
+    assert_eq!(provider.inner.api_call_count(), 0);
+}
+
+/// Provider noting which phase each board was fetched in.
+struct PhaseRecordingProvider {
+    inner: MockProvider,
+    calls: Mutex<Vec<(&'static str, Crs, &'static str)>>,
+}
+
+impl ServiceProvider for PhaseRecordingProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        let phase = crate::planner::phase::current_phase();
+        self.calls
+            .lock()
+            .unwrap()
+            .push(("departures", *station, phase));
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        let phase = crate::planner::phase::current_phase();
+        self.calls
+            .lock()
+            .unwrap()
+            .push(("arrivals", *station, phase));
+        self.inner.get_arrivals(station, after).await
+    }
+}
+
+#[tokio::test]
+async fn provider_calls_are_made_in_their_search_phase() {
+    use crate::planner::phase::{
+        BFS_PHASE, BOARD_PHASE, ONE_CHANGE_PHASE, TWO_CHANGE_PHASE, UNATTRIBUTED_PHASE,
+    };
+
+    // As in two_change_journey_found: PAD -> OXF, OXF -> RDG, RDG -> BRI
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("OXF", "Oxford", "11:00", ""),
+        ],
+    );
+    let mut inner = MockProvider::new();
+    inner.add_arrivals(
+        crs("BRI"),
+        vec![make_service(
+            "AR",
+            &[
+                ("RDG", "Reading", "", "12:00"),
+                ("BRI", "Bristol", "12:30", ""),
+            ],
+        )],
+    );
+    inner.add_departures(
+        crs("OXF"),
+        vec![make_service(
+            "BR",
+            &[
+                ("OXF", "Oxford", "", "11:10"),
+                ("RDG", "Reading", "11:45", ""),
+            ],
+        )],
+    );
+    inner.add_departures(crs("PAD"), vec![current_train.clone()]);
+    let provider = PhaseRecordingProvider {
+        inner,
+        calls: Mutex::new(Vec::new()),
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let result = planner.search(&request).await.unwrap();
+    assert!(!result.journeys.is_empty());
+
+    let calls = provider.calls.lock().unwrap().clone();
+    assert!(calls.contains(&("arrivals", crs("BRI"), ONE_CHANGE_PHASE)));
+    assert!(calls.contains(&("departures", crs("OXF"), TWO_CHANGE_PHASE)));
+    for (operation, station, phase) in &calls {
+        match *operation {
+            "arrivals" => assert_eq!(*phase, ONE_CHANGE_PHASE, "arrivals at {station}"),
+            _ => assert!(
+                [TWO_CHANGE_PHASE, BFS_PHASE].contains(phase),
+                "departures at {station} in {phase}"
+            ),
+        }
+    }
+
+    // A board search fetches its own board first, then searches from each train
+    provider.calls.lock().unwrap().clear();
+    let board = BoardRequest::new(crs("PAD"), crs("BRI"), time("09:55"), time("10:30"));
+    planner.search_board(&board).await.unwrap();
+    let calls = provider.calls.lock().unwrap().clone();
+    assert_eq!(calls[0], ("departures", crs("PAD"), BOARD_PHASE));
+    assert!(
+        calls
+            .iter()
+            .all(|(_, _, phase)| *phase != UNATTRIBUTED_PHASE)
+    );
+}
+
+/// Provider whose departure boards fail at some stations.
+struct UnavailableDeparturesProvider {
+    inner: MockProvider,
+    unavailable: Vec<Crs>,
+}
+
+impl ServiceProvider for UnavailableDeparturesProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        if self.unavailable.contains(station) {
+            return Err(SearchError::FetchError {
+                station: *station,
+                message: "upstream failure".to_string(),
+                kind: ErrorKind::Transient,
+            });
+        }
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.inner.get_arrivals(station, after).await
+    }
+}
+
+#[tokio::test]
+async fn failed_departure_fetches_reported_in_data_quality() {
+    // As in two_change_journey_found, the only route is via OXF
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("OXF", "Oxford", "11:00", ""),
+        ],
+    );
+    let mut inner = MockProvider::new();
+    inner.add_arrivals(
+        crs("BRI"),
+        vec![make_service(
+            "AR",
+            &[
+                ("RDG", "Reading", "", "12:00"),
+                ("BRI", "Bristol", "12:30", ""),
+            ],
+        )],
+    );
+    inner.add_departures(
+        crs("OXF"),
+        vec![make_service(
+            "BR",
+            &[
+                ("OXF", "Oxford", "", "11:10"),
+                ("RDG", "Reading", "11:45", ""),
+            ],
+        )],
+    );
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let provider = UnavailableDeparturesProvider {
+        inner,
+        unavailable: vec![crs("OXF")],
+    };
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    // The search carries on without OXF, but says so
+    assert!(result.journeys.is_empty());
+    assert_eq!(
+        result.data_quality.unavailable_stations,
+        [crs("OXF")].into()
+    );
+
+    let provider = UnavailableDeparturesProvider {
+        unavailable: Vec::new(),
+        ..provider
+    };
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+    assert!(!result.journeys.is_empty());
+    assert!(result.data_quality.is_complete());
+}
+
+#[tokio::test]
+async fn oversized_boards_keep_their_soonest_departures() {
+    // As in two_change_journey_found, but OXF's board has more services
+    // than the search keeps
+    let train = |id: &str, from: &str, dep: &str, to: &str, arr: &str| {
+        ServiceBuilder::new(id)
+            .call(from)
+            .dep(dep)
+            .call(to)
+            .arr(arr)
+            .build()
+    };
+    let current_train = train("CT", "PAD", "10:00", "OXF", "11:00");
+    let network = NetworkBuilder::new()
+        .service(train("AR", "RDG", "12:00", "BRI", "12:30"))
+        .service(train("LATER", "OXF", "11:50", "DID", "12:05"))
+        .service(train("BR", "OXF", "11:10", "RDG", "11:45"))
+        .build();
+    let config = SearchConfig {
+        max_board_services: 1,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let planner = Planner::new(&network, network.walkable(), &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert!(!result.journeys.is_empty());
+    assert!(result.data_quality.truncated_stations.contains(&crs("OXF")));
+}
+
+/// A scenario where BFS would explore past a direct journey it can't beat.
+///
+/// The direct train reaches BRI at 11:00. BFS would otherwise follow the
+/// bridge AAA -> BBB and fetch BBB's departures, but BBB is an hour from BRI.
+fn prunable_provider() -> (MockProvider, Arc<Service>) {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("AAA", "Station A", "10:30", "10:32"),
+            ("BRI", "Bristol", "11:00", ""),
+        ],
+    );
+
+    // Too early to connect to, but shows BBB -> BRI takes an hour
+    let early_feeder = make_service(
+        "EF",
+        &[
+            ("BBB", "Station B", "", "09:00"),
+            ("BRI", "Bristol", "10:00", ""),
+        ],
+    );
+
+    let bridge = make_service(
+        "BR",
+        &[
+            ("AAA", "Station A", "", "10:40"),
+            ("BBB", "Station B", "11:10", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![early_feeder]);
+    provider.add_departures(crs("PAD"), vec![]);
+    provider.add_departures(crs("AAA"), vec![bridge]);
+    (provider, current_train)
+}
+
+#[tokio::test]
+async fn travel_times_prune_hopeless_bfs_states() {
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 3,
+        ..SearchConfig::default()
+    };
+
+    // Without travel times, BFS fetches BBB's departures
+    let (provider, current_train) = prunable_provider();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let unpruned = Planner::new(&provider, &walkable, &config)
+        .search(&request)
+        .await
+        .unwrap();
+    assert_eq!(unpruned.routes_explored, 4);
+
+    // With them, BFS knows nothing from AAA can beat the direct train
+    let (provider, current_train) = prunable_provider();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let travel_times = TravelTimes::new();
+    let pruned = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+    assert_eq!(pruned.routes_explored, 3);
+
+    assert_eq!(pruned.journeys.len(), unpruned.journeys.len());
+    assert!(pruned.journeys[0].is_direct());
+}
+
+#[tokio::test]
+async fn walking_journeys_only_prune_when_walking_isnt_compared() {
+    let train = |id: &str, from: &str, dep: &str, to: &str, arr: &str| {
+        ServiceBuilder::new(id)
+            .call(from)
+            .dep(dep)
+            .call(to)
+            .arr(arr)
+            .build()
+    };
+    // Staying on to St Pancras and walking reaches King's Cross at 11:10
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("AAA")
+        .arr("10:30")
+        .dep("10:32")
+        .call("STP")
+        .arr("11:00")
+        .build();
+    // Earlier searches saw BBB is an hour from King's Cross, but changing
+    // at CCC gets there without a walk
+    let travel_times = TravelTimes::new();
+    travel_times.observe(&train("OLD", "BBB", "08:00", "KGX", "09:00"));
+    let network = NetworkBuilder::new()
+        .service(current_train.clone())
+        .service(train("BR", "AAA", "10:40", "BBB", "10:45"))
+        .service(train("LK", "BBB", "10:50", "CCC", "10:55"))
+        .service(train("FA", "CCC", "11:00", "KGX", "11:20"))
+        .walk("STP", "KGX", 10)
+        .build();
+    let search = |config: SearchConfig| {
+        let network = &network;
+        let travel_times = &travel_times;
+        let request = SearchRequest::new(current_train.clone(), CallIndex(0), crs("KGX"));
+        async move {
+            Planner::new(network, network.walkable(), &config)
+                .with_travel_times(travel_times)
+                .search(&request)
+                .await
+                .unwrap()
+        }
+    };
+    let walk_free = |result: &SearchResult| {
+        result
+            .journeys
+            .iter()
+            .any(|j| j.total_walk_duration().is_zero())
+    };
+
+    let config = SearchConfig {
+        max_changes: 3,
+        ..SearchConfig::default()
+    };
+    let result = search(config.clone()).await;
+    assert!(!result.journeys.is_empty());
+    assert!(!walk_free(&result));
+
+    let config = SearchConfig {
+        dominance: config.dominance.with(DominanceCriterion::Walking),
+        ..config
+    };
+    let result = search(config).await;
+    assert!(walk_free(&result));
+    assert!(result.journeys.iter().any(|j| j.final_walk().is_some()));
+}
+
+#[tokio::test]
+async fn pruning_keeps_states_with_a_quicker_way_than_observed_direct_trains() {
+    let train = |id: &str, from: &str, dep: &str, to: &str, arr: &str| {
+        ServiceBuilder::new(id)
+            .call(from)
+            .dep(dep)
+            .call(to)
+            .arr(arr)
+            .build()
+    };
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("AAA")
+        .arr("10:30")
+        .dep("10:32")
+        .call("BRI")
+        .arr("11:30")
+        .build();
+    // Earlier searches saw an hour-long direct train from BBB to Bristol,
+    // and the trains that change at CCC
+    let travel_times = TravelTimes::new();
+    travel_times.observe(&train("OLD", "BBB", "08:00", "BRI", "09:00"));
+    travel_times.observe(&train("OL1", "BBB", "08:00", "CCC", "08:10"));
+    travel_times.observe(&train("OL2", "CCC", "08:20", "BRI", "08:35"));
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(
+        crs("BRI"),
+        vec![train("FA", "CCC", "11:06", "BRI", "11:20")],
+    );
+    provider.add_departures(crs("PAD"), vec![]);
+    provider.add_departures(
+        crs("AAA"),
+        vec![train("BR", "AAA", "10:35", "BBB", "10:40")],
+    );
+    provider.add_departures(
+        crs("BBB"),
+        vec![train("LK", "BBB", "10:45", "DDD", "10:50")],
+    );
+    provider.add_departures(
+        crs("DDD"),
+        vec![train("L2", "DDD", "10:55", "CCC", "10:58")],
+    );
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 4,
+        ..SearchConfig::default()
+    };
+
+    // From BBB, the direct train would arrive after the one already found,
+    // but changing gets there first
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let result = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+    assert_eq!(result.journeys[0].arrival_time(), time("11:20"));
+}
+
+#[tokio::test]
+async fn bfs_stops_early_with_enough_close_journeys() {
+    let walkable = WalkableConnections::new();
+    let request = |train| SearchRequest::new(train, CallIndex(0), crs("BRI"));
+    let exhaustive = SearchConfig {
+        max_changes: 3,
+        ..SearchConfig::default()
+    };
+    let (provider, current_train) = prunable_provider();
+    let full = Planner::new(&provider, &walkable, &exhaustive)
+        .search(&request(current_train))
+        .await
+        .unwrap();
+    assert!(full.stats.bfs.api_calls > 0);
+
+    // The direct train is enough, so BFS fetches nothing more
+    let config = SearchConfig {
+        bfs_target_journeys: 1,
+        ..exhaustive.clone()
+    };
+    let (provider, current_train) = prunable_provider();
+    let early = Planner::new(&provider, &walkable, &config)
+        .search(&request(current_train))
+        .await
+        .unwrap();
+    assert_eq!(early.stats.bfs.api_calls, 0);
+    assert!(early.routes_explored < full.routes_explored);
+    assert!(early.journeys[0].is_direct());
+
+    // One journey isn't enough for a target of two
+    let config = SearchConfig {
+        bfs_target_journeys: 2,
+        ..exhaustive
+    };
+    let (provider, current_train) = prunable_provider();
+    let unmet = Planner::new(&provider, &walkable, &config)
+        .search(&request(current_train))
+        .await
+        .unwrap();
+    assert_eq!(unmet.routes_explored, full.routes_explored);
+}
+
+#[tokio::test]
+async fn travel_times_learned_during_search() {
+    let (provider, current_train) = prunable_provider();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let travel_times = TravelTimes::new();
+
+    Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        travel_times.lower_bound(&crs("BBB"), &crs("BRI")),
+        Some(Duration::minutes(60))
+    );
+    assert_eq!(
+        travel_times.lower_bound(&crs("AAA"), &crs("BRI")),
+        Some(Duration::minutes(28))
+    );
+}
+
+/// Provider whose arrivals boards list only the first `rows` services
+/// arriving after the requested time, as a busy station's board does, and
+/// with a `window` only those arriving within it.
+struct PagedArrivalsProvider {
+    inner: MockProvider,
+    rows: usize,
+    window: Option<Duration>,
+    arrivals_requested: Mutex<Vec<RailTime>>,
+}
+
+impl ServiceProvider for PagedArrivalsProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.arrivals_requested.lock().unwrap().push(after);
+        let mut arrivals: Vec<Arc<Service>> = self
+            .inner
+            .get_arrivals(station, after)
+            .await?
+            .into_iter()
+            .filter(|s| {
+                s.board_call_at(station)
+                    .and_then(|(_, c)| c.expected_arrival())
+                    .is_some_and(|t| {
+                        t >= after && self.window.is_none_or(|window| t < after + window)
+                    })
+            })
+            .collect();
+        arrivals.truncate(self.rows);
+        Ok(arrivals)
+    }
+}
+
+/// PAD to Reading, with Bristol's board filling up with trains the user
+/// can't reach before the 10:35 from Reading arrives at 11:20.
+fn long_distance_provider() -> (PagedArrivalsProvider, Arc<Service>) {
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let local = |id: &str, arr: &str| {
+        make_service(
+            id,
+            &[("SWI", "Swindon", "", "10:00"), ("BRI", "Bristol", arr, "")],
+        )
+    };
+    let arriving_service = make_service(
+        "AR",
+        &[
+            ("RDG", "Reading", "", "10:35"),
+            ("SWI", "Swindon", "10:55", "10:57"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    );
+    let mut inner = MockProvider::new();
+    inner.add_arrivals(
+        crs("BRI"),
+        vec![
+            local("L1", "10:30"),
+            local("L2", "10:40"),
+            local("L3", "10:50"),
+            arriving_service,
+        ],
+    );
+    let provider = PagedArrivalsProvider {
+        inner,
+        rows: 2,
+        window: None,
+        arrivals_requested: Mutex::new(Vec::new()),
+    };
+    (provider, current_train)
+}
+
+/// A travel time matrix that knows Paddington to Bristol takes at least
+/// 70 minutes.
+fn pad_to_bri_travel_times() -> TravelTimes {
+    let travel_times = TravelTimes::new();
+    travel_times.observe(&make_service(
+        "OBS",
+        &[
+            ("PAD", "Paddington", "", "08:00"),
+            ("BRI", "Bristol", "09:10", ""),
+        ],
+    ));
+    travel_times
+}
+
+#[tokio::test]
+async fn arrivals_board_paged_forward_to_earliest_possible_arrival() {
+    let (provider, current_train) = long_distance_provider();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let travel_times = pad_to_bri_travel_times();
+
+    let result = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    assert_eq!(result.journeys[0].arrival_time(), time("11:20"));
+    // Each page ends short of 11:00 until the third reaches 11:20
+    assert_eq!(
+        *provider.arrivals_requested.lock().unwrap(),
+        [time("10:00"), time("10:40"), time("10:50")]
+    );
+}
+
+#[tokio::test]
+async fn arrivals_board_paged_when_only_routes_with_changes_are_known() {
+    let (provider, current_train) = long_distance_provider();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    // No train seen runs from Paddington to Bristol, but changing at
+    // Swindon takes at least 70 minutes
+    let travel_times = TravelTimes::new();
+    travel_times.observe(
+        &ServiceBuilder::new("OB1")
+            .call("PAD")
+            .dep("08:00")
+            .call("SWI")
+            .arr("08:40")
+            .build(),
+    );
+    travel_times.observe(
+        &ServiceBuilder::new("OB2")
+            .call("SWI")
+            .dep("09:00")
+            .call("BRI")
+            .arr("09:30")
+            .build(),
+    );
+    assert_eq!(travel_times.lower_bound(&crs("PAD"), &crs("BRI")), None);
+
+    let result = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    assert_eq!(
+        *provider.arrivals_requested.lock().unwrap(),
+        [time("10:00"), time("10:40"), time("10:50")]
+    );
+}
+
+#[tokio::test]
+async fn empty_arrivals_board_paged_from_the_end_of_the_window() {
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("RDG")
+        .arr("10:25")
+        .build();
+    // Nothing reaches Bristol in the first two hours
+    let mut inner = MockProvider::new();
+    inner.add_arrivals(
+        crs("BRI"),
+        vec![
+            ServiceBuilder::new("AR")
+                .call("RDG")
+                .dep("11:35")
+                .call("BRI")
+                .arr("13:10")
+                .build(),
+        ],
+    );
+    let provider = PagedArrivalsProvider {
+        inner,
+        rows: 2,
+        window: Some(Duration::minutes(120)),
+        arrivals_requested: Mutex::new(Vec::new()),
+    };
+    let travel_times = TravelTimes::new();
+    travel_times.observe(
+        &ServiceBuilder::new("OBS")
+            .call("PAD")
+            .dep("06:00")
+            .call("BRI")
+            .arr("09:10")
+            .build(),
+    );
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        time_window_mins: 120,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+
+    let result = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    assert_eq!(result.journeys[0].arrival_time(), time("13:10"));
+    assert_eq!(
+        *provider.arrivals_requested.lock().unwrap(),
+        [time("10:00"), time("12:00")]
+    );
+}
+
+#[tokio::test]
+async fn arrivals_board_not_paged_without_travel_times_or_when_disabled() {
+    let request_for = |train| SearchRequest::new(train, CallIndex(0), crs("BRI"));
+    let walkable = WalkableConnections::new();
+
+    let (provider, current_train) = long_distance_provider();
+    let config = SearchConfig {
+        max_changes: 1,
+        ..SearchConfig::default()
+    };
+    let result = Planner::new(&provider, &walkable, &config)
+        .search(&request_for(current_train))
+        .await
+        .unwrap();
+    assert!(result.journeys.is_empty());
+    assert_eq!(provider.arrivals_requested.lock().unwrap().len(), 1);
+
+    let (provider, current_train) = long_distance_provider();
+    let config = SearchConfig {
+        max_changes: 1,
+        max_arrivals_pages: 0,
+        ..SearchConfig::default()
+    };
+    let travel_times = pad_to_bri_travel_times();
+    let result = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request_for(current_train))
+        .await
+        .unwrap();
+    assert!(result.journeys.is_empty());
+    assert_eq!(provider.arrivals_requested.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn arrivals_paging_stops_when_pages_add_nothing() {
+    // Nothing arrives after 10:30, so the second page is the first again
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let mut inner = MockProvider::new();
+    inner.add_arrivals(
+        crs("BRI"),
+        vec![make_service(
+            "L1",
+            &[
+                ("SWI", "Swindon", "", "10:00"),
+                ("BRI", "Bristol", "10:30", ""),
+            ],
+        )],
+    );
+    let provider = PagedArrivalsProvider {
+        inner,
+        rows: 2,
+        window: None,
+        arrivals_requested: Mutex::new(Vec::new()),
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        max_changes: 1,
+        ..SearchConfig::default()
+    };
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let travel_times = pad_to_bri_travel_times();
+
+    Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&travel_times)
+        .search(&request)
+        .await
+        .unwrap();
+
+    assert_eq!(provider.arrivals_requested.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn pre_departure_search_starts_from_departure() {
+    // User waiting at Paddington; the train is 5 minutes late
+    let mut train = (*make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("BRI", "Bristol", "11:20", ""),
+        ],
+    ))
+    .clone();
+    train.calls[0].realtime_departure = Some(time("10:05"));
+    let train = Arc::new(train);
+
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = SearchRequest::new(train.clone(), CallIndex(0), crs("BRI")).with_pre_departure();
+    let result = planner.search(&request).await.unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    assert_eq!(result.departure, Some(time("10:05")));
+    assert!(result.departure_slipped(time("10:00")));
+    assert!(!result.departure_slipped(time("10:05")));
+
+    // On the train there's no departure to wait for
+    let request = SearchRequest::new(train, CallIndex(0), crs("BRI"));
+    let result = planner.search(&request).await.unwrap();
+    assert_eq!(result.departure, None);
+    assert!(!result.departure_slipped(time("10:00")));
+}
+
+#[test]
+fn pre_departure_plans_from_the_estimated_departure() {
+    let train = |late: bool| {
+        let builder = ServiceBuilder::new("CT").call("PAD").dep("10:00");
+        let builder = if late {
+            builder.expected_dep("10:07")
+        } else {
+            builder
+        };
+        builder.call("BRI").arr("11:20").build()
+    };
+
+    // Not the booked 10:00: the user can't leave before the train does
+    let request = SearchRequest::new(train(true), CallIndex(0), crs("BRI")).with_pre_departure();
+    assert_eq!(request.current_time(), Some(time("10:07")));
+
+    let request = SearchRequest::new(train(false), CallIndex(0), crs("BRI")).with_pre_departure();
+    assert_eq!(request.current_time(), Some(time("10:00")));
+}
+
+#[tokio::test]
+async fn pre_departure_needs_a_departure() {
+    // The train terminates at Reading, so it can't be boarded there
+    let train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+
+    let request = SearchRequest::new(train.clone(), CallIndex(1), crs("BRI"));
+    assert!(request.validate().is_ok());
+
+    let request = request.with_pre_departure();
+    assert!(matches!(
+        request.validate(),
+        Err(SearchError::InvalidRequest(_))
+    ));
+}
+
+#[tokio::test]
+async fn board_search_annotates_each_departure() {
+    // Two trains leave Reading: one to Bristol, one to Oxford
+    let to_bristol = make_service(
+        "BR",
+        &[
+            ("RDG", "Reading", "", "10:05"),
+            ("SWI", "Swindon", "10:30", "10:32"),
+            ("BRI", "Bristol", "11:00", ""),
+        ],
+    );
+    let to_oxford = make_service(
+        "OX",
+        &[
+            ("RDG", "Reading", "", "10:10"),
+            ("OXF", "Oxford", "10:35", ""),
+        ],
+    );
+    // Departs after the window
+    let later = make_service(
+        "LT",
+        &[
+            ("RDG", "Reading", "", "11:30"),
+            ("BRI", "Bristol", "12:30", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_departures(crs("RDG"), vec![to_bristol, to_oxford, later]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let result = planner.search_board(&request).await.unwrap();
+
+    assert_eq!(result.departures.len(), 2);
+    let bristol = &result.departures[0];
+    assert_eq!(bristol.service.service_ref.darwin_id, "BR");
+    assert_eq!(bristol.departure, time("10:05"));
+    assert!(bristol.reaches_destination());
+    assert!(bristol.journey.as_ref().unwrap().is_direct());
+
+    assert_eq!(bristol.rank, Some(0));
+
+    let oxford = &result.departures[1];
+    assert_eq!(oxford.service.service_ref.darwin_id, "OX");
+    assert!(!oxford.reaches_destination());
+    assert_eq!(oxford.rank, None);
+}
+
+fn reading_to_bristol_board(count: usize) -> Vec<Arc<Service>> {
+    (0..count)
+        .map(|i| {
+            let departs = format!("10:{:02}", 5 + 10 * i);
+            let arrives = format!("11:{:02}", 5 + 10 * i);
+            make_service(
+                &format!("BR{i}"),
+                &[
+                    ("RDG", "Reading", "", &departs),
+                    ("BRI", "Bristol", &arrives, ""),
+                ],
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn board_search_marks_failed_departures_unavailable() {
+    let mut inner = MockProvider::new();
+    inner.add_departures(crs("RDG"), reading_to_bristol_board(2));
+    // The first search's arrivals board fails; the second's doesn't
+    let provider = FlakyArrivalsProvider {
+        inner,
+        failures_left: Mutex::new(1),
+        kind: ErrorKind::Config,
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let result = planner.search_board(&request).await.unwrap();
+
+    assert_eq!(result.departures.len(), 2);
+    let (failed, found): (Vec<_>, Vec<_>) = result.departures.iter().partition(|d| d.unavailable);
+    assert_eq!(failed.len(), 1);
+    assert!(!failed[0].reaches_destination());
+    assert_eq!(failed[0].rank, None);
+    assert!(found[0].reaches_destination());
+    assert_eq!(found[0].rank, Some(0));
+}
+
+#[tokio::test]
+async fn board_search_fails_when_every_departure_does() {
+    let mut inner = MockProvider::new();
+    inner.add_departures(crs("RDG"), reading_to_bristol_board(2));
+    let provider = FlakyArrivalsProvider {
+        inner,
+        failures_left: Mutex::new(usize::MAX),
+        kind: ErrorKind::Config,
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let err = planner.search_board(&request).await.unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Config);
+}
+
+/// Provider recording the most arrivals boards being fetched at once.
+struct OverlapProvider {
+    inner: MockProvider,
+    fetching: Mutex<(usize, usize)>,
+}
+
+impl ServiceProvider for OverlapProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        self.inner.get_departures(station, after).await
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        {
+            let mut fetching = self.fetching.lock().unwrap();
+            fetching.0 += 1;
+            fetching.1 = fetching.1.max(fetching.0);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        self.fetching.lock().unwrap().0 -= 1;
+        self.inner.get_arrivals(station, after).await
+    }
+}
+
+#[tokio::test]
+async fn board_search_runs_a_few_searches_at_a_time() {
+    let mut inner = MockProvider::new();
+    inner.add_departures(crs("RDG"), reading_to_bristol_board(5));
+    let provider = OverlapProvider {
+        inner,
+        fetching: Mutex::new((0, 0)),
+    };
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig {
+        board_concurrency: 2,
+        ..SearchConfig::default()
+    };
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let result = planner.search_board(&request).await.unwrap();
+
+    assert_eq!(result.departures.len(), 5);
+    assert_eq!(provider.fetching.lock().unwrap().1, 2);
+}
+
+#[tokio::test]
+async fn board_search_ranks_with_wait_weight() {
+    // The stopping train leaves soon; the fast one arrives two minutes
+    // earlier but means waiting 40 minutes on the platform
+    let stopping = make_service(
+        "SL",
+        &[
+            ("RDG", "Reading", "", "10:05"),
+            ("SWI", "Swindon", "10:30", "10:32"),
+            ("BRI", "Bristol", "11:00", ""),
+        ],
+    );
+    let fast = make_service(
+        "FS",
+        &[
+            ("RDG", "Reading", "", "10:40"),
+            ("BRI", "Bristol", "10:58", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_departures(crs("RDG"), vec![stopping, fast]);
+    let walkable = WalkableConnections::new();
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search_board(&request).await.unwrap();
+    assert_eq!(result.departures[0].rank, Some(0));
+    assert_eq!(result.departures[1].rank, Some(1));
+
+    let config = SearchConfig {
+        wait_weight_pct: 0,
+        ..SearchConfig::default()
+    };
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search_board(&request).await.unwrap();
+    assert_eq!(result.departures[0].rank, Some(1));
+    assert_eq!(result.departures[1].rank, Some(0));
+}
+
+#[tokio::test]
+async fn board_search_skips_terminating_trains() {
+    let terminates = make_service(
+        "TM",
+        &[
+            ("PAD", "Paddington", "", "09:40"),
+            ("RDG", "Reading", "10:05", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_departures(crs("RDG"), vec![terminates]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let result = planner.search_board(&request).await.unwrap();
+
+    assert!(result.departures.is_empty());
+    assert_eq!(result.routes_explored, 1);
+}
+
+#[tokio::test]
+async fn board_search_rejects_backwards_window() {
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("11:00"), time("10:00"));
+    assert!(matches!(
+        planner.search_board(&request).await,
+        Err(SearchError::InvalidRequest(_))
+    ));
+}
+
+#[tokio::test]
+async fn set_down_only_calls_are_never_boarded() {
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("RDG")
+        .arr("10:25")
+        .build();
+    // The sooner train to Bristol only sets down at Reading
+    let sets_down = ServiceBuilder::new("SD")
+        .call("PAD")
+        .dep("10:05")
+        .call("RDG")
+        .arr("10:30")
+        .dep("10:32")
+        .set_down_only()
+        .call("BRI")
+        .arr("11:10")
+        .build();
+    let stopping = ServiceBuilder::new("ST")
+        .call("RDG")
+        .dep("10:45")
+        .call("BRI")
+        .arr("11:30")
+        .build();
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![sets_down.clone(), stopping.clone()]);
+    provider.add_departures(crs("RDG"), vec![sets_down, stopping]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let result = planner.search(&request).await.unwrap();
+    assert!(!result.journeys.is_empty());
+    for journey in &result.journeys {
+        assert!(
+            journey
+                .legs()
+                .all(|leg| leg.service().service_ref.darwin_id != "SD")
+        );
+    }
+
+    // Nor from the platform, before it departs
+    let request = BoardRequest::new(crs("RDG"), crs("BRI"), time("10:00"), time("11:00"));
+    let result = planner.search_board(&request).await.unwrap();
+    let boarded: Vec<&str> = result
+        .departures
+        .iter()
+        .map(|d| d.service.service_ref.darwin_id.as_str())
+        .collect();
+    assert_eq!(boarded, ["ST"]);
+}
+
+#[tokio::test]
+async fn pre_departure_search_rejected_where_train_only_sets_down() {
+    let train = ServiceBuilder::new("SD")
+        .call("PAD")
+        .dep("10:05")
+        .call("RDG")
+        .arr("10:30")
+        .dep("10:32")
+        .set_down_only()
+        .call("BRI")
+        .arr("11:10")
+        .build();
+
+    let provider = MockProvider::new();
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = SearchRequest::new(train.clone(), CallIndex(1), crs("BRI")).with_pre_departure();
+    assert!(matches!(
+        planner.search(&request).await,
+        Err(SearchError::InvalidRequest(_))
+    ));
+
+    // Someone already on board just stays on
+    let request = SearchRequest::new(train, CallIndex(1), crs("BRI"));
+    let result = planner.search(&request).await.unwrap();
+    assert!(result.journeys[0].is_direct());
+}
+
+#[tokio::test]
+async fn pick_up_only_calls_are_never_alighted_at() {
+    // The current train only picks up at Reading, so Reading can't be
+    // reached on it, nor used as a change point
+    let current_train = ServiceBuilder::new("CT")
+        .call("PAD")
+        .dep("10:00")
+        .call("RDG")
+        .arr("10:25")
+        .dep("10:27")
+        .pick_up_only()
+        .call("SWI")
+        .arr("10:50")
+        .build();
+    let from_reading = ServiceBuilder::new("FR")
+        .call("RDG")
+        .dep("10:35")
+        .call("BRI")
+        .arr("11:10")
+        .build();
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![from_reading.clone()]);
+    provider.add_departures(crs("RDG"), vec![from_reading]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let planner = Planner::new(&provider, &walkable, &config);
+
+    let request = SearchRequest::new(current_train.clone(), CallIndex(0), crs("RDG"));
+    let result = planner.search(&request).await.unwrap();
+    assert!(result.journeys.iter().all(|j| !j.is_direct()));
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let result = planner.search(&request).await.unwrap();
+    assert!(result.journeys.iter().all(|j| {
+        j.legs()
+            .all(|leg| leg.service().service_ref.darwin_id != "FR")
+    }));
+}
+
+#[tokio::test]
+async fn near_misses_when_nothing_reaches_destination() {
+    use crate::planner::NearMissReason;
+
+    // The current train ends at Chippenham, one stop short of Bristol on a
+    // train that has already left
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("SWI", "Swindon", "10:50", "10:52"),
+            ("CPM", "Chippenham", "11:05", ""),
+        ],
+    );
+    let gone = make_service(
+        "GONE",
+        &[
+            ("CPM", "Chippenham", "", "09:20"),
+            ("BRI", "Bristol", "09:40", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![gone]);
+    let mut walkable = WalkableConnections::new();
+    walkable.add(crs("SWI"), crs("BRI"), 90).unwrap();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert!(result.journeys.is_empty());
+    let near_misses: Vec<(Crs, NearMissReason)> = result
+        .near_misses
+        .iter()
+        .map(|n| (*n.journey.destination(), n.reason))
+        .collect();
+    assert_eq!(
+        near_misses,
+        [
+            (
+                crs("SWI"),
+                NearMissReason::Nearby {
+                    walk: Duration::minutes(90)
+                }
+            ),
+            (crs("CPM"), NearMissReason::StopShort),
+        ]
+    );
+    assert_eq!(
+        result.near_misses[1].label(),
+        "Ends at Chippenham, one stop short of your destination"
+    );
+}
+
+#[tokio::test]
+async fn near_miss_over_max_journey_time() {
+    use crate::planner::NearMissReason;
+
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", ""),
+        ],
+    );
+    let slow = make_service(
+        "SLOW",
+        &[
+            ("RDG", "Reading", "", "10:40"),
+            ("SWI", "Swindon", "16:30", "16:31"),
+            ("BRI", "Bristol", "17:00", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("BRI"), vec![slow]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("BRI"));
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert!(result.journeys.is_empty());
+    assert_eq!(result.near_misses.len(), 1);
+    let near_miss = &result.near_misses[0];
+    assert_eq!(near_miss.reason, NearMissReason::TooLong);
+    assert_eq!(near_miss.journey.arrival_time(), time("17:00"));
+    assert_eq!(near_miss.journey.change_count(), 1);
+}
+
+#[tokio::test]
+async fn no_near_misses_when_journey_found() {
+    let mut provider = MockProvider::new();
+    provider.add_arrivals(crs("RDG"), Vec::new());
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+
+    let request = SearchRequest::new(dividing_train(), CallIndex(0), crs("RDG"));
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+
+    assert_eq!(result.journeys.len(), 1);
+    assert!(result.near_misses.is_empty());
+}
+
+/// Rough locations along the line from London to Penzance, and Oxford off
+/// to one side.
+fn western_locations() -> HashMap<Crs, StationLocation> {
+    [
+        ("PAD", 51.516, -0.177),
+        ("RDG", 51.459, -0.972),
+        ("OXF", 51.753, -1.270),
+        ("SWI", 51.565, -1.785),
+        ("BRI", 51.449, -2.581),
+        ("TAU", 51.023, -3.103),
+        ("PNZ", 50.122, -5.532),
+    ]
+    .into_iter()
+    .map(|(station, lat, lon)| (crs(station), StationLocation::new(lat, lon).unwrap()))
+    .collect()
+}
+
+#[tokio::test]
+async fn continuation_when_destination_beyond_boards() {
+    // Nothing arriving at Penzance is on its board yet. The current train
+    // reaches Swindon, from where a train goes on to Taunton.
+    let current_train = make_service(
+        "CT",
+        &[
+            ("PAD", "Paddington", "", "10:00"),
+            ("RDG", "Reading", "10:25", "10:27"),
+            ("SWI", "Swindon", "10:50", ""),
+        ],
+    );
+    let westward = make_service(
+        "WS",
+        &[
+            ("SWI", "Swindon", "", "11:00"),
+            ("BRI", "Bristol", "11:40", "11:45"),
+            ("TAU", "Taunton", "12:30", ""),
+        ],
+    );
+    let to_oxford = make_service(
+        "OX",
+        &[
+            ("RDG", "Reading", "", "10:40"),
+            ("OXF", "Oxford", "11:05", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_departures(crs("SWI"), vec![westward]);
+    provider.add_departures(crs("RDG"), vec![to_oxford]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let locations = western_locations();
+    let request = SearchRequest::new(current_train, CallIndex(0), crs("PNZ"));
+
+    // Without locations there's no telling which way is closer
+    let planner = Planner::new(&provider, &walkable, &config);
+    let result = planner.search(&request).await.unwrap();
+    assert!(result.journeys.is_empty());
+    assert!(result.continuation.is_none());
+
+    let planner = Planner::new(&provider, &walkable, &config).with_locations(&locations);
+    let result = planner.search(&request).await.unwrap();
+    assert!(result.journeys.is_empty());
+    assert!(result.near_misses.is_empty());
+
+    let continuation = result.continuation.unwrap();
+    assert_eq!(continuation.journey.destination(), &crs("TAU"));
+    assert_eq!(continuation.journey.change_count(), 1);
+    let after = time("12:30") + config.min_connection();
+    assert_eq!(
+        continuation.hint,
+        ContinuationHint {
+            station: crs("TAU"),
+            after,
+            destination: crs("PNZ"),
+            opens_at: after.checked_sub(Duration::minutes(60)).unwrap(),
+        }
+    );
+    assert!(!continuation.hint.is_open(time("11:00")));
+    assert!(continuation.hint.is_open(time("11:35")));
+
+    // The rest, once planned from Taunton, follows on
+    let rest = make_service(
+        "TP",
+        &[
+            ("TAU", "Taunton", "", "12:45"),
+            ("PNZ", "Penzance", "15:00", ""),
+        ],
+    );
+    let rest = Journey::new(vec![Segment::Train(
+        Leg::new(rest, CallIndex(0), CallIndex(1)).unwrap(),
+    )])
+    .unwrap();
+    let whole = continuation.join(&rest).unwrap();
+    assert_eq!(whole.destination(), &crs("PNZ"));
+    assert_eq!(whole.change_count(), 2);
+    assert!(continuation.join(&continuation.journey).is_none());
+}
+
+#[tokio::test]
+async fn board_search_continues_from_closest_departure() {
+    let to_bristol = make_service(
+        "BR",
+        &[
+            ("SWI", "Swindon", "", "11:00"),
+            ("BRI", "Bristol", "11:40", ""),
+        ],
+    );
+    let to_taunton = make_service(
+        "TA",
+        &[
+            ("SWI", "Swindon", "", "11:10"),
+            ("TAU", "Taunton", "12:20", ""),
+        ],
+    );
+
+    let mut provider = MockProvider::new();
+    provider.add_departures(crs("SWI"), vec![to_bristol, to_taunton]);
+    let walkable = WalkableConnections::new();
+    let config = SearchConfig::default();
+    let locations = western_locations();
+    let planner = Planner::new(&provider, &walkable, &config).with_locations(&locations);
+
+    let request = BoardRequest::new(crs("SWI"), crs("PNZ"), time("11:00"), time("12:00"));
+    let result = planner.search_board(&request).await.unwrap();
+
+    assert!(result.departures.iter().all(|d| !d.reaches_destination()));
+    let continuation = result.continuation.unwrap();
+    assert_eq!(continuation.hint.station, crs("TAU"));
+    assert_eq!(continuation.journey.departure_time(), time("11:10"));
+}
+
+/// Trains leaving together, in whichever order the boards list them: the
+/// same journeys, ranked the same, and the same API calls.
+#[tokio::test]
+async fn search_breaks_ties_by_route_whatever_the_board_order() {
+    let train = |id: &str, from: &str, dep: &str, to: &str, arr: &str| {
+        ServiceBuilder::new(id)
+            .call(from)
+            .dep(dep)
+            .call(to)
+            .arr(arr)
+            .build()
+    };
+    let going_on = |id: &str, from: &str, dep: &str, to: &str, arr: &str, terminus: &str| {
+        ServiceBuilder::new(id)
+            .call(from)
+            .dep(dep)
+            .call(to)
+            .arr(arr)
+            .dep(arr)
+            .call(terminus)
+            .arr("13:30")
+            .build()
+    };
+    let current = train("CT", "PAD", "10:00", "RDG", "10:25");
+    let services = vec![
+        // Two trains to Bristol at the same time, needing one change; the
+        // second goes on beyond
+        going_on("TB", "RDG", "10:40", "BRI", "13:00", "WSM"),
+        train("TA", "RDG", "10:40", "BRI", "13:00"),
+        // Two trains to Oxford at the same time, only one of which the BFS
+        // carries on from
+        going_on("RY", "RDG", "10:35", "OXF", "11:00", "WOS"),
+        train("RX", "RDG", "10:35", "OXF", "11:00"),
+        train("OD", "OXF", "11:10", "SWI", "11:30"),
+        train("SD", "SWI", "11:40", "DID", "12:00"),
+        train("DB", "DID", "12:10", "BRI", "12:40"),
+    ];
+    let config = SearchConfig {
+        max_changes: 4,
+        ..SearchConfig::default()
+    };
+
+    let mut outcomes = Vec::new();
+    for order in [services.clone(), services.iter().rev().cloned().collect()] {
+        let network = NetworkBuilder::new().services(order).build();
+        let request = SearchRequest::new(current.clone(), CallIndex(0), crs("BRI"));
+        let planner = Planner::new(&network, network.walkable(), &config);
+        let result = planner.search(&request).await.unwrap();
+
+        let trains: Vec<Vec<String>> = result
+            .journeys
+            .iter()
+            .map(|journey| {
+                journey
+                    .legs()
+                    .map(|leg| leg.service().service_ref.darwin_id.clone())
+                    .collect()
+            })
+            .collect();
+        outcomes.push((trains, result.routes_explored));
+    }
+
+    assert_eq!(outcomes[0], outcomes[1]);
+    assert_eq!(
+        outcomes[0].0,
+        [
+            vec!["CT", "RX", "OD", "SD", "DB"],
+            vec!["CT", "TA"],
+            vec!["CT", "TB"],
+        ]
+    );
+}
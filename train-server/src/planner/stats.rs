@@ -0,0 +1,160 @@
+//! Per-phase statistics for a journey search.
+//!
+//! Used to tune [`SearchConfig`](super::SearchConfig): how much each phase
+//! costs in time and API calls, and how much it contributes.
+
+use std::time::{Duration, Instant};
+
+/// What one search phase cost and found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    /// Whether the phase ran at all.
+    pub ran: bool,
+
+    /// Wall-clock time spent in the phase.
+    pub elapsed: Duration,
+
+    /// Journeys the phase found, before deduplication and ranking.
+    pub journeys: usize,
+
+    /// API calls the phase made.
+    pub api_calls: usize,
+}
+
+impl PhaseStats {
+    /// Stats for a phase that started at `started` and has just finished.
+    pub(super) fn finished(started: Instant, journeys: usize, api_calls: usize) -> Self {
+        Self {
+            ran: true,
+            elapsed: started.elapsed(),
+            journeys,
+            api_calls,
+        }
+    }
+
+    /// Add another run of the same phase.
+    fn add(&mut self, other: &PhaseStats) {
+        self.ran |= other.ran;
+        self.elapsed += other.elapsed;
+        self.journeys += other.journeys;
+        self.api_calls += other.api_calls;
+    }
+}
+
+/// Statistics for a whole search, broken down by phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Staying on the current train.
+    pub direct: PhaseStats,
+
+    /// One change, including fetching the destination's arrivals board.
+    pub one_change: PhaseStats,
+
+    /// Two changes via departure boards at intermediate stations.
+    pub two_change: PhaseStats,
+
+    /// BFS fallback.
+    pub bfs: PhaseStats,
+
+    /// Departure board lookups answered from boards already fetched during
+    /// this search.
+    pub cache_hits: usize,
+}
+
+impl SearchStats {
+    /// Total API calls across all phases.
+    pub fn api_calls(&self) -> usize {
+        self.phases().map(|p| p.api_calls).sum()
+    }
+
+    /// Total time spent across all phases.
+    pub fn elapsed(&self) -> Duration {
+        self.phases().map(|p| p.elapsed).sum()
+    }
+
+    /// Fraction of departure board lookups answered without an API call.
+    ///
+    /// Returns `None` if no departure boards were needed.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let departure_fetches = self.two_change.api_calls + self.bfs.api_calls;
+        let lookups = self.cache_hits + departure_fetches;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// Add the stats of another search, e.g. one run concurrently to
+    /// another station of a group. Elapsed times are summed.
+    pub fn add(&mut self, other: &SearchStats) {
+        self.direct.add(&other.direct);
+        self.one_change.add(&other.one_change);
+        self.two_change.add(&other.two_change);
+        self.bfs.add(&other.bfs);
+        self.cache_hits += other.cache_hits;
+    }
+
+    fn phases(&self) -> impl Iterator<Item = &PhaseStats> {
+        [&self.direct, &self.one_change, &self.two_change, &self.bfs].into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_and_cache_ratio() {
+        let stats = SearchStats {
+            direct: PhaseStats {
+                ran: true,
+                elapsed: Duration::from_millis(1),
+                journeys: 1,
+                api_calls: 0,
+            },
+            one_change: PhaseStats {
+                ran: true,
+                elapsed: Duration::from_millis(10),
+                journeys: 4,
+                api_calls: 1,
+            },
+            two_change: PhaseStats {
+                ran: true,
+                elapsed: Duration::from_millis(20),
+                journeys: 2,
+                api_calls: 3,
+            },
+            bfs: PhaseStats::default(),
+            cache_hits: 1,
+        };
+
+        assert_eq!(stats.api_calls(), 4);
+        assert_eq!(stats.elapsed(), Duration::from_millis(31));
+        // The arrivals board isn't a departure lookup
+        assert_eq!(stats.cache_hit_ratio(), Some(0.25));
+    }
+
+    #[test]
+    fn add_sums_phases() {
+        let one = SearchStats {
+            one_change: PhaseStats {
+                ran: true,
+                elapsed: Duration::from_millis(5),
+                journeys: 2,
+                api_calls: 1,
+            },
+            cache_hits: 1,
+            ..SearchStats::default()
+        };
+        let mut total = one;
+        total.add(&one);
+
+        assert!(total.one_change.ran);
+        assert!(!total.bfs.ran);
+        assert_eq!(total.one_change.journeys, 4);
+        assert_eq!(total.api_calls(), 2);
+        assert_eq!(total.cache_hits, 2);
+    }
+
+    #[test]
+    fn no_departure_lookups_has_no_ratio() {
+        assert_eq!(SearchStats::default().cache_hit_ratio(), None);
+    }
+}
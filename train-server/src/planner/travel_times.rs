@@ -0,0 +1,288 @@
+//! Typical minimum travel times between stations.
+//!
+//! Learned from the services the planner sees: every time a service is
+//! observed, each pair of its calls gives an upper bound on the fastest rail
+//! time between those stations, and the matrix keeps the smallest seen. The
+//! BFS fallback uses these for A*-style lower bounds (see [`LowerBounds`])
+//! to drop partial journeys that cannot beat journeys already found.
+//!
+//! Observed minimums aren't true lower bounds: a faster train that has
+//! never been seen makes them too high. So a bound is the quickest way to
+//! the destination over everything observed, changing trains and walking
+//! where that's quicker, less a safety margin; stations nothing is known
+//! about get no bound at all.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::RwLock;
+
+use chrono::Duration;
+
+use crate::domain::{CallIndex, Crs, Service};
+use crate::walkable::WalkableConnections;
+
+/// Cap on the number of station pairs tracked, to bound memory use.
+/// Once reached, known pairs are still refined but no new ones are added.
+const MAX_PAIRS: usize = 500_000;
+
+/// Matrix of the fastest observed rail time between station pairs.
+///
+/// Shared between searches; observing takes `&self`.
+#[derive(Debug, Default)]
+pub struct TravelTimes {
+    /// Minutes, keyed by (from, to)
+    minimums: RwLock<HashMap<(Crs, Crs), i64>>,
+}
+
+impl TravelTimes {
+    /// Create an empty matrix.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Learn from the calls of an observed service.
+    pub fn observe(&self, service: &Service) {
+        let mut observed = Vec::new();
+        for (i, from) in service.calls.iter().enumerate() {
+            if from.is_cancelled {
+                continue;
+            }
+            let Some(departure) = from.expected_departure() else {
+                continue;
+            };
+            for (_, to) in service.calls_after(CallIndex(i)) {
+                if to.is_cancelled || to.station == from.station {
+                    continue;
+                }
+                let Some(arrival) = to.expected_arrival().or_else(|| to.expected_departure())
+                else {
+                    continue;
+                };
+                let mins = arrival.signed_duration_since(departure).num_minutes();
+                if mins >= 0 {
+                    observed.push(((from.station, to.station), mins));
+                }
+            }
+        }
+
+        let mut minimums = self.minimums.write().unwrap_or_else(|e| e.into_inner());
+        for (pair, mins) in observed {
+            let full = minimums.len() >= MAX_PAIRS;
+            match minimums.get_mut(&pair) {
+                Some(existing) => *existing = (*existing).min(mins),
+                None if !full => {
+                    minimums.insert(pair, mins);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Fastest observed rail time from one station to another.
+    ///
+    /// Returns `None` if the pair has never been observed.
+    pub fn lower_bound(&self, from: &Crs, to: &Crs) -> Option<Duration> {
+        let minimums = self.minimums.read().unwrap_or_else(|e| e.into_inner());
+        minimums.get(&(*from, *to)).map(|m| Duration::minutes(*m))
+    }
+
+    /// Lower bounds on the time from each station to `destination`.
+    ///
+    /// Each is the quickest route over the observed times and the walks in
+    /// `walkable` (whichever way), less `margin` for faster trains not yet
+    /// observed.
+    pub fn bounds_to(
+        &self,
+        destination: &Crs,
+        walkable: &WalkableConnections,
+        margin: Duration,
+    ) -> LowerBounds {
+        // Edges into each station, as (from, minutes)
+        let mut into: HashMap<Crs, Vec<(Crs, i64)>> = HashMap::new();
+        {
+            let minimums = self.minimums.read().unwrap_or_else(|e| e.into_inner());
+            for (&(from, to), &mins) in minimums.iter() {
+                into.entry(to).or_default().push((from, mins));
+            }
+        }
+        for (a, b, walk) in walkable.iter() {
+            let mins = walk.num_minutes();
+            into.entry(b).or_default().push((a, mins));
+            into.entry(a).or_default().push((b, mins));
+        }
+
+        // Dijkstra backwards from the destination
+        let mut minutes = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, *destination))]);
+        while let Some(Reverse((mins, station))) = queue.pop() {
+            if minutes.contains_key(&station) {
+                continue;
+            }
+            minutes.insert(station, mins);
+            for &(from, edge) in into.get(&station).into_iter().flatten() {
+                if !minutes.contains_key(&from) {
+                    queue.push(Reverse((mins + edge, from)));
+                }
+            }
+        }
+
+        LowerBounds {
+            minutes,
+            margin: margin.num_minutes().max(0),
+        }
+    }
+
+    /// Number of station pairs with a known time.
+    pub fn len(&self) -> usize {
+        self.minimums
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Returns true if nothing has been observed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Lower bounds on the time from each station to one destination, built by
+/// [`TravelTimes::bounds_to`].
+#[derive(Debug, Clone, Default)]
+pub struct LowerBounds {
+    /// Quickest observed route to the destination, in minutes
+    minutes: HashMap<Crs, i64>,
+
+    /// Minutes taken off each route, for trains not yet observed
+    margin: i64,
+}
+
+impl LowerBounds {
+    /// The least time it could take from `station` to the destination; zero
+    /// if nothing is known.
+    pub fn from(&self, station: &Crs) -> Duration {
+        let mins = self
+            .minutes
+            .get(station)
+            .map_or(0, |mins| (mins - self.margin).max(0));
+        Duration::minutes(mins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, RailTime, ServiceRef};
+    use chrono::NaiveDate;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    fn service(calls: &[(&str, &str, &str)]) -> Service {
+        let calls = calls
+            .iter()
+            .map(|(station, arr, dep)| {
+                let mut call = Call::new(crs(station), (*station).to_string());
+                call.booked_arrival = (!arr.is_empty()).then(|| time(arr));
+                call.booked_departure = (!dep.is_empty()).then(|| time(dep));
+                call
+            })
+            .collect();
+        Service {
+            service_ref: ServiceRef::new("S".to_string(), crs("PAD")),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        }
+    }
+
+    #[test]
+    fn learns_every_forward_pair() {
+        let times = TravelTimes::new();
+        times.observe(&service(&[
+            ("PAD", "", "10:00"),
+            ("RDG", "10:25", "10:27"),
+            ("SWI", "10:50", ""),
+        ]));
+
+        assert_eq!(times.len(), 3);
+        assert_eq!(
+            times.lower_bound(&crs("PAD"), &crs("RDG")),
+            Some(Duration::minutes(25))
+        );
+        assert_eq!(
+            times.lower_bound(&crs("RDG"), &crs("SWI")),
+            Some(Duration::minutes(23))
+        );
+        assert_eq!(
+            times.lower_bound(&crs("PAD"), &crs("SWI")),
+            Some(Duration::minutes(50))
+        );
+        // Directed: nothing learned about going backwards
+        assert_eq!(times.lower_bound(&crs("SWI"), &crs("PAD")), None);
+    }
+
+    #[test]
+    fn keeps_fastest_observation() {
+        let times = TravelTimes::new();
+        times.observe(&service(&[("PAD", "", "10:00"), ("RDG", "10:30", "")]));
+        times.observe(&service(&[("PAD", "", "11:00"), ("RDG", "11:22", "")]));
+        times.observe(&service(&[("PAD", "", "12:00"), ("RDG", "12:40", "")]));
+
+        assert_eq!(
+            times.lower_bound(&crs("PAD"), &crs("RDG")),
+            Some(Duration::minutes(22))
+        );
+    }
+
+    #[test]
+    fn bounds_allow_for_changes_walks_and_unobserved_trains() {
+        let times = TravelTimes::new();
+        // A slow direct train from Paddington, and quicker ones changing at
+        // Reading
+        times.observe(&service(&[("PAD", "", "10:00"), ("BRI", "11:40", "")]));
+        times.observe(&service(&[("PAD", "", "10:00"), ("RDG", "10:25", "")]));
+        times.observe(&service(&[("RDG", "", "10:30"), ("BRI", "11:30", "")]));
+        times.observe(&service(&[("SWI", "", "10:00"), ("BRI", "10:40", "")]));
+        let walkable = crate::walkable::WalkableConnectionsBuilder::new()
+            .add("KGX", "STP", 5)
+            .add("STP", "SWI", 10)
+            .build();
+
+        let bounds = times.bounds_to(&crs("BRI"), &walkable, Duration::zero());
+        assert_eq!(bounds.from(&crs("PAD")), Duration::minutes(85));
+        assert_eq!(bounds.from(&crs("SWI")), Duration::minutes(40));
+        // Walking to Swindon, however it was got to
+        assert_eq!(bounds.from(&crs("KGX")), Duration::minutes(55));
+        assert_eq!(bounds.from(&crs("BRI")), Duration::zero());
+        assert_eq!(bounds.from(&crs("XYZ")), Duration::zero());
+
+        let bounds = times.bounds_to(&crs("BRI"), &walkable, Duration::minutes(50));
+        assert_eq!(bounds.from(&crs("PAD")), Duration::minutes(35));
+        assert_eq!(bounds.from(&crs("SWI")), Duration::zero());
+    }
+
+    #[test]
+    fn skips_cancelled_calls() {
+        let mut svc = service(&[
+            ("PAD", "", "10:00"),
+            ("RDG", "10:25", "10:27"),
+            ("SWI", "10:50", ""),
+        ]);
+        svc.calls[1].is_cancelled = true;
+
+        let times = TravelTimes::new();
+        times.observe(&svc);
+
+        assert_eq!(times.len(), 1);
+        assert!(times.lower_bound(&crs("PAD"), &crs("RDG")).is_none());
+    }
+}
@@ -0,0 +1,268 @@
+//! Bringing a planned journey's real-time details up to date.
+//!
+//! Someone watching a journey only cares about the trains in it, so rather
+//! than re-running the whole search, [`JourneyRefresher`] finds each leg's
+//! service again and rebuilds the leg from its latest estimates. Services
+//! are found on their boarding station's departures board (usually already
+//! cached by other searches), or by identity once their IDs have expired
+//! (see [`ServiceResolver`]). Only when a change can no longer be made does
+//! the journey need planning again from scratch.
+
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate};
+use tracing::debug;
+
+use crate::cache::CachedDarwinClient;
+use crate::domain::{Journey, Leg, Segment, Service, ServiceIdentity};
+use crate::planner::phase::in_phase;
+use crate::resolver::ServiceResolver;
+
+/// Phase the Darwin calls refreshing a journey are attributed to.
+pub const TRACK_PHASE: &str = "track";
+
+/// The outcome of refreshing a journey.
+#[derive(Debug)]
+pub enum Refresh {
+    /// Every leg was found again and every change can still be made.
+    Updated(Journey),
+
+    /// Every leg was found again, but a leg is cancelled or a change is now
+    /// shorter than the minimum connection time, so the journey should be
+    /// planned again.
+    Infeasible(Journey),
+
+    /// A leg's service couldn't be found (e.g. it has finished its run), so
+    /// the journey was left as it was.
+    Lost,
+}
+
+/// Refreshes journeys leg by leg.
+pub struct JourneyRefresher<'a> {
+    resolver: ServiceResolver<'a>,
+    min_connection: Duration,
+}
+
+impl<'a> JourneyRefresher<'a> {
+    /// Create a refresher that looks at boards for the given date and time,
+    /// and treats changes shorter than `min_connection` as broken.
+    pub fn new(
+        darwin: &'a CachedDarwinClient,
+        date: NaiveDate,
+        current_mins: u16,
+        min_connection: Duration,
+    ) -> Self {
+        Self {
+            resolver: ServiceResolver::new(darwin, date, current_mins),
+            min_connection,
+        }
+    }
+
+    /// Rebuild a journey from the latest data for each of its trains.
+    ///
+    /// Walks and the stations of each leg are kept; only the services'
+    /// times, platforms and cancellations change.
+    pub async fn refresh(&self, journey: &Journey) -> Refresh {
+        let mut segments = Vec::with_capacity(journey.segment_count());
+        for segment in journey.segments() {
+            match segment {
+                Segment::Train(leg) => match in_phase(TRACK_PHASE, self.refresh_leg(leg)).await {
+                    Some(leg) => segments.push(Segment::Train(leg)),
+                    None => return Refresh::Lost,
+                },
+                Segment::Walk(walk) => segments.push(Segment::Walk(walk.clone())),
+            }
+        }
+
+        // Delays may have made a change impossible, which is what the caller
+        // needs to hear about
+        let Ok(mut refreshed) = Journey::new_allowing_missed(segments) else {
+            return Refresh::Lost;
+        };
+        if let Some(headway) = journey.headway() {
+            refreshed = refreshed.with_headway(headway);
+        }
+
+        if feasible(&refreshed, self.min_connection) {
+            Refresh::Updated(refreshed)
+        } else {
+            Refresh::Infeasible(refreshed)
+        }
+    }
+
+    async fn refresh_leg(&self, leg: &Leg) -> Option<Leg> {
+        let service_ref = &leg.service().service_ref;
+        let identity = ServiceIdentity::of(leg.service());
+        let service = self
+            .resolver
+            .resolve(
+                &service_ref.darwin_id,
+                &service_ref.board_crs,
+                identity.as_ref(),
+            )
+            .await;
+        let leg = service.and_then(|service| leg_between(service, leg));
+        if leg.is_none() {
+            debug!(service_id = %service_ref.darwin_id, "Couldn't find leg's service again");
+        }
+        leg
+    }
+}
+
+/// The leg of `service` between the stations `like` boards and alights at.
+///
+/// A leg staying on a portion that divides from the train is found on that
+/// portion.
+fn leg_between(service: Arc<Service>, like: &Leg) -> Option<Leg> {
+    let (board, _) = service.board_call_at(like.board_station())?;
+    if let Some((alight, _)) = service.call_at(like.alight_station(), board) {
+        return Leg::new(service, board, alight).ok();
+    }
+    service
+        .portions_after(board)
+        .into_iter()
+        .find_map(|(divides, portion)| {
+            let (alight, _) = portion.call_at(like.alight_station(), divides)?;
+            Leg::new(Arc::new(portion), board, alight).ok()
+        })
+}
+
+/// Whether every leg is running and every change leaves at least
+/// `min_connection` once any walk is done.
+fn feasible(journey: &Journey, min_connection: Duration) -> bool {
+    journey.legs().all(|leg| !leg.is_cancelled())
+        && journey
+            .changes()
+            .all(|change| change.slack() >= min_connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::domain::Crs;
+    use crate::web::diff;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 3).unwrap()
+    }
+
+    fn cached(mock: &MockDarwinClient) -> CachedDarwinClient {
+        CachedDarwinClient::new(
+            DarwinClientImpl::Mock(mock.clone()),
+            &CacheConfig::default(),
+        )
+    }
+
+    /// Paddington 14:15 to Reading, then the 14:58 from Reading towards
+    /// Oxford as far as Didcot.
+    async fn journey(darwin: &CachedDarwinClient) -> Journey {
+        let resolver = ServiceResolver::new(darwin, date(), 14 * 60);
+        let first = resolver
+            .resolve("pad_service_1", &crs("PAD"), None)
+            .await
+            .unwrap();
+        let second = resolver
+            .resolve("rdg_service_2", &crs("RDG"), None)
+            .await
+            .unwrap();
+        let legs = [
+            (first, &[crs("PAD"), crs("RDG")]),
+            (second, &[crs("RDG"), crs("DID")]),
+        ];
+        let legs = legs
+            .into_iter()
+            .map(|(service, [from, to])| {
+                let (board, _) = service.board_call_at(from).unwrap();
+                let (alight, _) = service.call_at(to, board).unwrap();
+                Leg::new(service, board, alight).unwrap()
+            })
+            .map(Segment::Train)
+            .collect();
+        Journey::new(legs).unwrap()
+    }
+
+    fn refresher(darwin: &CachedDarwinClient) -> JourneyRefresher<'_> {
+        JourneyRefresher::new(darwin, date(), 14 * 60, Duration::minutes(5))
+    }
+
+    #[tokio::test]
+    async fn unchanged_journey_is_updated_in_place() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let darwin = cached(&mock);
+        let journey = journey(&darwin).await;
+
+        let Refresh::Updated(refreshed) = refresher(&darwin).refresh(&journey).await else {
+            panic!("expected an update");
+        };
+        assert_eq!(refreshed.fingerprint(), journey.fingerprint());
+        assert!(diff(&journey, &refreshed).is_empty());
+    }
+
+    #[tokio::test]
+    async fn delay_updates_leg_times() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let journey = journey(&cached(&mock)).await;
+
+        mock.delay_service("pad_service_1", 5).await;
+        let darwin = cached(&mock);
+        let Refresh::Updated(refreshed) = refresher(&darwin).refresh(&journey).await else {
+            panic!("expected an update");
+        };
+
+        assert_eq!(refreshed.departure_time().to_string(), "14:20");
+        let changes = diff(&journey, &refreshed);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.leg == 0));
+    }
+
+    #[tokio::test]
+    async fn missed_connection_is_infeasible() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let journey = journey(&cached(&mock)).await;
+
+        // Arrives in Reading at 14:57 for the 14:59
+        mock.delay_service("pad_service_1", 17).await;
+        let darwin = cached(&mock);
+        let Refresh::Infeasible(refreshed) = refresher(&darwin).refresh(&journey).await else {
+            panic!("expected the connection to break");
+        };
+
+        let change = refreshed.changes().next().unwrap();
+        assert_eq!(change.slack(), Duration::minutes(2));
+    }
+
+    #[tokio::test]
+    async fn connection_left_before_arrival_is_infeasible() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let journey = journey(&cached(&mock)).await;
+
+        // Arrives in Reading at 15:05, after the 14:59 has gone
+        mock.delay_service("pad_service_1", 25).await;
+        let darwin = cached(&mock);
+        let Refresh::Infeasible(refreshed) = refresher(&darwin).refresh(&journey).await else {
+            panic!("expected the connection to break");
+        };
+
+        let change = refreshed.changes().next().unwrap();
+        assert_eq!(change.slack(), Duration::minutes(-6));
+    }
+
+    #[tokio::test]
+    async fn vanished_service_is_lost() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let journey = journey(&cached(&mock)).await;
+
+        mock.expire_service("rdg_service_2").await;
+        let darwin = cached(&mock);
+        assert!(matches!(
+            refresher(&darwin).refresh(&journey).await,
+            Refresh::Lost
+        ));
+    }
+}
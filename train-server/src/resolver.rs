@@ -0,0 +1,204 @@
+//! Re-finding a service after its Darwin ID has expired.
+//!
+//! Darwin service IDs are only valid while a service is on the board it was
+//! found on, roughly until two minutes after it departs. A user who
+//! identified their train at the station they boarded at will lose that ID
+//! partway through the journey. Other boards still list the service, under
+//! a different ID, so we recognise it by its [`ServiceIdentity`] instead:
+//! where it started, when, and where it's going.
+//!
+//! The terminus's arrivals board lists the service for the rest of its run,
+//! so it is the most reliable place to look.
+
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use tracing::debug;
+
+use crate::cache::CachedDarwinClient;
+use crate::domain::{Crs, Service, ServiceIdentity};
+
+/// Phase the Darwin calls finding a remembered train are attributed to
+/// (see [`in_phase`](crate::planner::phase::in_phase)), where the caller
+/// has no phase of its own.
+pub const RESOLVE_PHASE: &str = "resolve";
+
+/// Stations whose boards are searched as a last resort for a service ID.
+const COMMON_STATIONS: [&str; 8] = ["PAD", "EUS", "KGX", "VIC", "WAT", "LIV", "BHM", "MAN"];
+
+/// Finds a service by ID, falling back to its identity once the ID expires.
+pub struct ServiceResolver<'a> {
+    darwin: &'a CachedDarwinClient,
+    date: NaiveDate,
+    current_mins: u16,
+}
+
+impl<'a> ServiceResolver<'a> {
+    /// Create a resolver that looks at boards for the given date and time.
+    pub fn new(darwin: &'a CachedDarwinClient, date: NaiveDate, current_mins: u16) -> Self {
+        Self {
+            darwin,
+            date,
+            current_mins,
+        }
+    }
+
+    /// Find a service.
+    ///
+    /// Looks for `service_id` on `board_station`'s departures first. If it
+    /// has gone and `identity` is known, looks for the same service under
+    /// any ID on that board and then on the terminus's arrivals board.
+    /// Finally, looks for the ID on the boards of a few major stations.
+    ///
+    /// The returned service may have a different Darwin ID from the one
+    /// asked for.
+    pub async fn resolve(
+        &self,
+        service_id: &str,
+        board_station: &Crs,
+        identity: Option<&ServiceIdentity>,
+    ) -> Option<Arc<Service>> {
+        let board = self.departures(board_station).await;
+        if let Some(service) = find(&board, |s| s.service_ref.darwin_id == service_id) {
+            return Some(service);
+        }
+
+        if let Some(identity) = identity {
+            if let Some(service) = find(&board, |s| identity.matches(s)) {
+                debug!(service_id, new_id = %service.service_ref.darwin_id, "Re-resolved service on its board station");
+                return Some(service);
+            }
+            let arrivals = self.arrivals(&identity.terminus).await;
+            if let Some(service) = find(&arrivals, |s| identity.matches(s)) {
+                debug!(service_id, new_id = %service.service_ref.darwin_id, "Re-resolved service on its terminus arrivals board");
+                return Some(service);
+            }
+        }
+
+        for station in COMMON_STATIONS {
+            let Ok(crs) = Crs::parse(station) else {
+                continue;
+            };
+            if &crs == board_station {
+                continue; // Already searched
+            }
+            let board = self.departures(&crs).await;
+            if let Some(service) = find(&board, |s| s.service_ref.darwin_id == service_id) {
+                return Some(service);
+            }
+        }
+
+        None
+    }
+
+    async fn departures(&self, station: &Crs) -> Vec<Arc<Service>> {
+        self.darwin
+            .get_departures_with_details(station, self.date, self.current_mins, 0, 120)
+            .await
+            .map(|board| board.iter().map(|s| Arc::new(s.service.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    async fn arrivals(&self, station: &Crs) -> Vec<Arc<Service>> {
+        self.darwin
+            .get_arrivals_with_details(station, self.date, self.current_mins, 0, 120)
+            .await
+            .map(|board| board.iter().map(|s| Arc::new(s.service.clone())).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn find(services: &[Arc<Service>], predicate: impl Fn(&Service) -> bool) -> Option<Arc<Service>> {
+    services.iter().find(|s| predicate(s)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, 3).unwrap()
+    }
+
+    /// Mock boards, with a handle for scripting changes to them.
+    fn mock() -> (MockDarwinClient, CachedDarwinClient) {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let cached = CachedDarwinClient::new(
+            DarwinClientImpl::Mock(mock.clone()),
+            &CacheConfig::default(),
+        );
+        (mock, cached)
+    }
+
+    #[tokio::test]
+    async fn finds_service_by_id() {
+        let (_, darwin) = mock();
+        let resolver = ServiceResolver::new(&darwin, date(), 14 * 60);
+
+        let service = resolver
+            .resolve("pad_service_1", &crs("PAD"), None)
+            .await
+            .unwrap();
+        assert_eq!(service.service_ref.darwin_id, "pad_service_1");
+    }
+
+    #[tokio::test]
+    async fn expired_id_re_resolved_by_identity() {
+        let (mock, darwin) = mock();
+        let resolver = ServiceResolver::new(&darwin, date(), 14 * 60);
+        let original = resolver
+            .resolve("pad_service_1", &crs("PAD"), None)
+            .await
+            .unwrap();
+        let identity = ServiceIdentity::of(&original).unwrap();
+
+        // The train leaves Paddington and drops off its board, and the
+        // cached copy of the board has since expired
+        mock.expire_service("pad_service_1").await;
+        let darwin = CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default());
+        let resolver = ServiceResolver::new(&darwin, date(), 14 * 60);
+
+        // Without its identity, the service is lost
+        assert!(
+            resolver
+                .resolve("pad_service_1", &crs("PAD"), None)
+                .await
+                .is_none()
+        );
+
+        // With it, the service is found on the terminus's arrivals board
+        let resolved = resolver
+            .resolve("pad_service_1", &crs("PAD"), Some(&identity))
+            .await
+            .unwrap();
+        assert_eq!(resolved.service_ref.darwin_id, "bri_service_1");
+        assert_eq!(resolved.calls.len(), original.calls.len());
+        assert!(identity.matches(&resolved));
+    }
+
+    #[tokio::test]
+    async fn identity_distinguishes_services() {
+        let (_, darwin) = mock();
+        let resolver = ServiceResolver::new(&darwin, date(), 14 * 60);
+        let first = resolver
+            .resolve("pad_service_1", &crs("PAD"), None)
+            .await
+            .unwrap();
+        let second = resolver
+            .resolve("pad_service_2", &crs("PAD"), None)
+            .await
+            .unwrap();
+
+        let identity = ServiceIdentity::of(&first).unwrap();
+        assert_eq!(identity.origin, crs("PAD"));
+        assert_eq!(identity.terminus, crs("BRI"));
+        assert!(identity.matches(&first));
+        assert!(!identity.matches(&second));
+    }
+}
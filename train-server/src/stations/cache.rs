@@ -149,10 +149,16 @@ mod tests {
             StationDto {
                 crs_code: "KGX".to_string(),
                 name: "London Kings Cross".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
             },
             StationDto {
                 crs_code: "PAD".to_string(),
                 name: "London Paddington".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
             },
         ];
 
@@ -174,6 +180,9 @@ mod tests {
         let stations = vec![StationDto {
             crs_code: "KGX".to_string(),
             name: "London Kings Cross".to_string(),
+            latitude: None,
+            longitude: None,
+            opening_hours: None,
         }];
 
         cache.save(&stations).unwrap();
@@ -200,6 +209,9 @@ mod tests {
         let stations = vec![StationDto {
             crs_code: "KGX".to_string(),
             name: "London Kings Cross".to_string(),
+            latitude: None,
+            longitude: None,
+            opening_hours: None,
         }];
 
         cache.save(&stations).unwrap();
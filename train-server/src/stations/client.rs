@@ -1,6 +1,8 @@
 //! National Rail Station API client.
 
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+use reqwest::header::HeaderValue;
 use serde::Deserialize;
 
 use super::error::StationError;
@@ -14,12 +16,29 @@ pub struct StationsResponse {
     pub stations: Vec<StationDto>,
 }
 
-/// Minimal DTO for station data - we only need CRS and name.
+/// Minimal DTO for station data - we only need CRS, name, location and hours.
 #[derive(Debug, Clone, Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StationDto {
     pub crs_code: String,
     pub name: String,
+    /// Latitude; absent in older disk caches.
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    /// Longitude; absent in older disk caches.
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// Daily opening hours; absent for stations open all day and in older
+    /// disk caches.
+    #[serde(default)]
+    pub opening_hours: Option<OpeningHoursDto>,
+}
+
+/// A station's daily opening hours, as "HH:MM" times.
+#[derive(Debug, Clone, Deserialize, serde::Serialize)]
+pub struct OpeningHoursDto {
+    pub open: String,
+    pub close: String,
 }
 
 /// Configuration for the Station API client.
@@ -54,30 +73,35 @@ impl StationClientConfig {
 #[derive(Debug, Clone)]
 pub struct StationClient {
     http: reqwest::Client,
+    api_key: HeaderValue,
     base_url: String,
+    timeout: Duration,
 }
 
 impl StationClient {
     /// Create a new Station API client.
     pub fn new(config: StationClientConfig) -> Result<Self, StationError> {
-        let mut headers = HeaderMap::new();
-
-        // Use x-apikey header for Rail Data Marketplace authentication
-        let api_key_header =
-            HeaderValue::from_str(&config.api_key).map_err(|_| StationError::Api {
-                status: 0,
-                message: "Invalid API key format".to_string(),
-            })?;
-        headers.insert(HeaderName::from_static("x-apikey"), api_key_header);
+        let http = reqwest::Client::builder().build()?;
+        Self::with_http_client(config, http)
+    }
 
-        let http = reqwest::Client::builder()
-            .default_headers(headers)
-            .timeout(std::time::Duration::from_secs(config.timeout_secs))
-            .build()?;
+    /// Create a Station API client that sends its requests through `http`,
+    /// e.g. the Darwin client's, so that the two share a connection pool.
+    pub fn with_http_client(
+        config: StationClientConfig,
+        http: reqwest::Client,
+    ) -> Result<Self, StationError> {
+        // Rail Data Marketplace authenticates with the x-apikey header
+        let api_key = HeaderValue::from_str(&config.api_key).map_err(|_| StationError::Api {
+            status: 0,
+            message: "Invalid API key format".to_string(),
+        })?;
 
         Ok(Self {
             http,
+            api_key,
             base_url: config.base_url,
+            timeout: Duration::from_secs(config.timeout_secs),
         })
     }
 
@@ -85,7 +109,13 @@ impl StationClient {
     pub async fn fetch_all(&self) -> Result<Vec<StationDto>, StationError> {
         let url = format!("{}/stations", self.base_url);
 
-        let response = self.http.get(&url).send().await?;
+        let response = self
+            .http
+            .get(&url)
+            .header("x-apikey", &self.api_key)
+            .timeout(self.timeout)
+            .send()
+            .await?;
         let status = response.status();
 
         if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
@@ -128,4 +158,12 @@ mod tests {
             StationClientConfig::new("test-api-key").with_base_url("http://localhost:8080");
         assert_eq!(config.base_url, "http://localhost:8080");
     }
+
+    #[test]
+    fn client_rejects_invalid_api_key() {
+        let http = reqwest::Client::new();
+        let config = StationClientConfig::new("bad\nkey");
+        assert!(StationClient::with_http_client(config, http.clone()).is_err());
+        assert!(StationClient::with_http_client(StationClientConfig::new("key"), http).is_ok());
+    }
 }
@@ -1,5 +1,7 @@
 //! Station API error types.
 
+use crate::error::{Classify, ErrorKind};
+
 /// Errors that can occur when interacting with the Station API.
 #[derive(Debug, thiserror::Error)]
 pub enum StationError {
@@ -23,3 +25,14 @@ pub enum StationError {
     #[error("cache error: {message}")]
     Cache { message: String },
 }
+
+impl Classify for StationError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            StationError::Http(_) => ErrorKind::Transient,
+            StationError::Unauthorized => ErrorKind::Config,
+            StationError::Api { status, .. } => ErrorKind::from_status(*status),
+            StationError::Json { .. } | StationError::Cache { .. } => ErrorKind::Permanent,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Station groups.
+//!
+//! Some cities have several stations that count as one for tickets and
+//! interchange (e.g. "Glasgow" is Central and Queen Street). Asking for a
+//! group's name searches all of its stations.
+
+use crate::domain::Crs;
+
+/// Stations treated as one destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StationGroup {
+    /// Name users search by, e.g. "Glasgow"
+    pub name: String,
+
+    /// Member stations; the first is the group's main station
+    pub members: Vec<Crs>,
+}
+
+impl StationGroup {
+    /// The group's main station.
+    pub fn lead(&self) -> Crs {
+        self.members[0]
+    }
+}
+
+/// A table of station groups.
+#[derive(Debug, Clone, Default)]
+pub struct StationGroups {
+    groups: Vec<StationGroup>,
+}
+
+impl StationGroups {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a group.
+    ///
+    /// Groups with fewer than two valid members are ignored.
+    pub fn add(&mut self, name: impl Into<String>, members: &[&str]) {
+        let members: Vec<Crs> = members.iter().filter_map(|m| Crs::parse(m).ok()).collect();
+        if members.len() >= 2 {
+            self.groups.push(StationGroup {
+                name: name.into(),
+                members,
+            });
+        }
+    }
+
+    /// The group with this name (case-insensitive).
+    pub fn find(&self, name: &str) -> Option<&StationGroup> {
+        let name = name.trim();
+        self.groups
+            .iter()
+            .find(|g| g.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The group whose main station is `crs`.
+    pub fn led_by(&self, crs: &Crs) -> Option<&StationGroup> {
+        self.groups.iter().find(|g| &g.lead() == crs)
+    }
+
+    /// Number of groups.
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Whether there are no groups.
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+/// Groups for the larger interchange cities outside London.
+pub fn uk_station_groups() -> StationGroups {
+    let mut groups = StationGroups::new();
+    groups.add("Glasgow", &["GLC", "GLQ"]);
+    groups.add("Edinburgh", &["EDB", "HYM"]);
+    groups.add("Manchester", &["MAN", "MCV", "MCO"]);
+    groups.add("Birmingham", &["BHM", "BMO", "BSW"]);
+    groups.add("Liverpool", &["LIV", "LVC", "MRF"]);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[test]
+    fn find_by_name_ignores_case() {
+        let groups = uk_station_groups();
+        let glasgow = groups.find(" glasgow ").unwrap();
+        assert_eq!(glasgow.members, vec![crs("GLC"), crs("GLQ")]);
+        assert_eq!(glasgow.lead(), crs("GLC"));
+        assert!(groups.find("Gotham").is_none());
+    }
+
+    #[test]
+    fn led_by_main_station_only() {
+        let groups = uk_station_groups();
+        assert_eq!(groups.led_by(&crs("GLC")).unwrap().name, "Glasgow");
+        assert!(groups.led_by(&crs("GLQ")).is_none());
+    }
+
+    #[test]
+    fn single_station_groups_ignored() {
+        let mut groups = StationGroups::new();
+        groups.add("Nowhere", &["PAD", "bad"]);
+        assert!(groups.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! Station opening hours.
+//!
+//! Late at night, an interchange walk between termini can run into a
+//! station that has shut its doors between the last train and the first.
+//! The stations feed gives each station's daily opening hours;
+//! [`closed_walk_stations`] finds the walks in a journey that need a station
+//! while it is closed.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::NaiveTime;
+
+use crate::domain::{Crs, Journey, RailTime, Segment};
+
+/// The hours a station is open each day.
+///
+/// `close` earlier than `open` means the station closes after midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningHours {
+    /// Opening time
+    pub open: NaiveTime,
+    /// Closing time
+    pub close: NaiveTime,
+}
+
+impl OpeningHours {
+    /// Parse "HH:MM" opening and closing times.
+    ///
+    /// Returns `None` if either is malformed, or they are equal (the feed's
+    /// way of saying "open all day" is to omit the hours).
+    pub fn parse(open: &str, close: &str) -> Option<Self> {
+        let open = NaiveTime::parse_from_str(open, "%H:%M").ok()?;
+        let close = NaiveTime::parse_from_str(close, "%H:%M").ok()?;
+        (open != close).then_some(Self { open, close })
+    }
+
+    /// Whether the station is open at `time`.
+    pub fn is_open_at(&self, time: NaiveTime) -> bool {
+        if self.open < self.close {
+            self.open <= time && time < self.close
+        } else {
+            time >= self.open || time < self.close
+        }
+    }
+}
+
+impl fmt::Display for OpeningHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}–{}",
+            self.open.format("%H:%M"),
+            self.close.format("%H:%M")
+        )
+    }
+}
+
+/// A station a walk passes through while it is closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedStation {
+    /// The closed station
+    pub station: Crs,
+    /// When the walk needs it
+    pub at: RailTime,
+    /// Its opening hours
+    pub hours: OpeningHours,
+}
+
+impl fmt::Display for ClosedStation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is closed at {} (open {})",
+            self.station, self.at, self.hours
+        )
+    }
+}
+
+/// The stations the journey's walks need while they are closed.
+///
+/// A walk leaves its first station when the previous train arrives and
+/// reaches its second after the walk's duration. Stations with no known
+/// hours are assumed open.
+pub fn closed_walk_stations(
+    journey: &Journey,
+    hours: &HashMap<Crs, OpeningHours>,
+) -> Vec<ClosedStation> {
+    let mut closed = Vec::new();
+    let mut arrived = journey.departure_time();
+    for segment in journey.segments() {
+        match segment {
+            Segment::Train(leg) => arrived = leg.arrival_time(),
+            Segment::Walk(walk) => {
+                let reached = arrived + walk.duration;
+                for (station, at) in [(walk.from, arrived), (walk.to, reached)] {
+                    if let Some(h) = hours.get(&station)
+                        && !h.is_open_at(at.time())
+                    {
+                        closed.push(ClosedStation {
+                            station,
+                            at,
+                            hours: *h,
+                        });
+                    }
+                }
+                arrived = reached;
+            }
+        }
+    }
+    closed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, Leg, Service, ServiceRef, Walk};
+    use chrono::{Duration, NaiveDate};
+    use std::sync::Arc;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    fn hm(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    fn leg(from: &str, departs: &str, to: &str, arrives: &str) -> Segment {
+        let mut board = Call::new(crs(from), from.to_string());
+        board.booked_departure = Some(time(departs));
+        let mut alight = Call::new(crs(to), to.to_string());
+        alight.booked_arrival = Some(time(arrives));
+        let service = Arc::new(Service {
+            service_ref: ServiceRef::new(format!("{from}{to}"), crs(from)),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            calls: vec![board, alight],
+            board_station_idx: CallIndex(0),
+            formation: None,
+        });
+        Segment::Train(Leg::new(service, CallIndex(0), CallIndex(1)).unwrap())
+    }
+
+    /// Into King's Cross, walk to St Pancras, on to Luton.
+    fn journey(arrive_kgx: &str, depart_stp: &str) -> Journey {
+        Journey::new(vec![
+            leg("PBO", "22:00", "KGX", arrive_kgx),
+            Segment::Walk(Walk::new(crs("KGX"), crs("STP"), Duration::minutes(10))),
+            leg("STP", depart_stp, "LUT", "23:59"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn hours_past_midnight() {
+        let hours = OpeningHours::parse("05:00", "00:30").unwrap();
+        assert!(hours.is_open_at(hm("23:00")));
+        assert!(hours.is_open_at(hm("00:15")));
+        assert!(!hours.is_open_at(hm("01:00")));
+        assert!(hours.is_open_at(hm("05:00")));
+        assert_eq!(hours.to_string(), "05:00–00:30");
+    }
+
+    #[test]
+    fn same_day_hours() {
+        let hours = OpeningHours::parse("06:00", "23:00").unwrap();
+        assert!(hours.is_open_at(hm("22:59")));
+        assert!(!hours.is_open_at(hm("23:00")));
+        assert!(!hours.is_open_at(hm("05:59")));
+    }
+
+    #[test]
+    fn malformed_or_empty_hours_rejected() {
+        assert!(OpeningHours::parse("25:00", "23:00").is_none());
+        assert!(OpeningHours::parse("06:00", "06:00").is_none());
+    }
+
+    #[test]
+    fn walk_into_closed_station_is_flagged() {
+        let hours = HashMap::from([(crs("STP"), OpeningHours::parse("05:00", "23:30").unwrap())]);
+
+        // Arrive KGX 23:25, reach STP at 23:35, after it shuts
+        let closed = closed_walk_stations(&journey("23:25", "23:45"), &hours);
+
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].station, crs("STP"));
+        assert_eq!(closed[0].at, time("23:35"));
+        assert_eq!(
+            closed[0].to_string(),
+            "STP is closed at 23:35 (open 05:00–23:30)"
+        );
+    }
+
+    #[test]
+    fn walk_while_open_is_fine() {
+        let hours = HashMap::from([(crs("STP"), OpeningHours::parse("05:00", "23:30").unwrap())]);
+        assert!(closed_walk_stations(&journey("23:00", "23:20"), &hours).is_empty());
+    }
+}
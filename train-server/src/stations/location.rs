@@ -0,0 +1,62 @@
+//! Station geographic locations.
+
+/// Mean radius of the Earth, in metres.
+const EARTH_RADIUS_METRES: f64 = 6_371_000.0;
+
+/// A station's location (WGS84 decimal degrees).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StationLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl StationLocation {
+    /// Create a location, returning `None` if the coordinates are out of range.
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        let valid = (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude);
+        valid.then_some(Self {
+            latitude,
+            longitude,
+        })
+    }
+
+    /// Great-circle distance to another location, in metres.
+    pub fn distance_metres(&self, other: &StationLocation) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METRES * a.sqrt().asin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_accepts_valid_coordinates() {
+        let kgx = StationLocation::new(51.5320, -0.1233).unwrap();
+        assert_eq!(kgx.latitude, 51.5320);
+        assert_eq!(kgx.longitude, -0.1233);
+    }
+
+    #[test]
+    fn new_rejects_out_of_range() {
+        assert!(StationLocation::new(91.0, 0.0).is_none());
+        assert!(StationLocation::new(0.0, -181.0).is_none());
+        assert!(StationLocation::new(f64::NAN, 0.0).is_none());
+    }
+
+    #[test]
+    fn distance_between_stations() {
+        let kgx = StationLocation::new(51.5320, -0.1233).unwrap();
+        let stp = StationLocation::new(51.5309, -0.1262).unwrap();
+        let lst = StationLocation::new(51.5178, -0.0817).unwrap();
+
+        assert_eq!(kgx.distance_metres(&kgx), 0.0);
+        assert!((200.0..250.0).contains(&kgx.distance_metres(&stp)));
+        assert!((3_200.0..3_350.0).contains(&kgx.distance_metres(&lst)));
+        assert_eq!(kgx.distance_metres(&lst), lst.distance_metres(&kgx));
+    }
+}
@@ -3,15 +3,40 @@
 //! Provides CRS code → station name mapping, fetched from the
 //! National Rail Station API at startup and refreshed daily.
 //!
+//! Also defines station groups (several stations searched as one), daily
+//! opening hours for spotting walks through closed stations, how busy
+//! stations are, which are request stops, and which platforms share one
+//! physical platform. Only these are built without the `darwin` feature.
+//!
 //! Supports disk-based caching to avoid hitting the expensive
 //! stations API on every server restart.
 
+#[cfg(feature = "darwin")]
 mod cache;
+#[cfg(feature = "darwin")]
 mod client;
+#[cfg(feature = "darwin")]
 mod error;
+mod groups;
+mod hours;
+mod location;
+#[cfg(feature = "darwin")]
 mod names;
+mod platforms;
+mod request_stops;
+mod usage;
 
+#[cfg(feature = "darwin")]
 pub use cache::{StationCache, StationCacheConfig};
+#[cfg(feature = "darwin")]
 pub use client::{StationClient, StationClientConfig};
+#[cfg(feature = "darwin")]
 pub use error::StationError;
+pub use groups::{StationGroup, StationGroups, uk_station_groups};
+pub use hours::{ClosedStation, OpeningHours, closed_walk_stations};
+pub use location::StationLocation;
+#[cfg(feature = "darwin")]
 pub use names::{StationMatch, StationNames};
+pub use platforms::{PlatformChange, PlatformTopology};
+pub use request_stops::is_request_stop;
+pub use usage::{StationUsage, uk_station_usage};
@@ -1,14 +1,45 @@
 //! Station name lookup.
 
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
+use tracing::warn;
 
 use crate::domain::Crs;
 
 use super::cache::StationCache;
 use super::client::{StationClient, StationDto};
 use super::error::StationError;
+use super::hours::OpeningHours;
+use super::location::StationLocation;
+use super::usage::uk_station_usage;
+
+/// Station data from one fetch.
+///
+/// Replaced whole on refresh, so names, locations and hours never come from
+/// different fetches.
+#[derive(Debug, Default)]
+struct StationData {
+    names: HashMap<Crs, String>,
+    locations: Arc<HashMap<Crs, StationLocation>>,
+    hours: HashMap<Crs, OpeningHours>,
+    /// Codes asked for by [`StationNames::names`] but not in this data,
+    /// already logged
+    unknown: Mutex<HashSet<Crs>>,
+}
+
+impl StationData {
+    fn from_stations(stations: Vec<StationDto>) -> Self {
+        let locations = build_locations(&stations);
+        let hours = build_hours(&stations);
+        Self {
+            names: build_map(stations),
+            locations: Arc::new(locations),
+            hours,
+            unknown: Mutex::default(),
+        }
+    }
+}
 
 /// Thread-safe station name lookup.
 ///
@@ -16,7 +47,7 @@ use super::error::StationError;
 /// and optional disk caching.
 #[derive(Clone)]
 pub struct StationNames {
-    inner: Arc<RwLock<HashMap<Crs, String>>>,
+    data: Arc<RwLock<Arc<StationData>>>,
     client: StationClient,
     cache: Option<StationCache>,
 }
@@ -27,13 +58,11 @@ impl StationNames {
     /// This will fail if the API is unreachable.
     pub async fn fetch(client: StationClient) -> Result<Self, StationError> {
         let stations = client.fetch_all().await?;
-        let map = build_map(stations);
-
-        Ok(Self {
-            inner: Arc::new(RwLock::new(map)),
+        Ok(Self::with_data(
+            StationData::from_stations(stations),
             client,
-            cache: None,
-        })
+            None,
+        ))
     }
 
     /// Create a new StationNames, loading from disk cache if valid,
@@ -49,13 +78,9 @@ impl StationNames {
     ) -> Result<(Self, bool), StationError> {
         // Try loading from cache first
         if let Some(stations) = cache.load() {
-            let map = build_map(stations);
+            let data = StationData::from_stations(stations);
             return Ok((
-                Self {
-                    inner: Arc::new(RwLock::new(map)),
-                    client,
-                    cache: Some(cache),
-                },
+                Self::with_data(data, client, Some(cache)),
                 true, // loaded from cache
             ));
         }
@@ -68,13 +93,9 @@ impl StationNames {
             eprintln!("Warning: failed to save station cache: {}", e);
         }
 
-        let map = build_map(stations);
+        let data = StationData::from_stations(stations);
         Ok((
-            Self {
-                inner: Arc::new(RwLock::new(map)),
-                client,
-                cache: Some(cache),
-            },
+            Self::with_data(data, client, Some(cache)),
             false, // fetched from API
         ))
     }
@@ -83,29 +104,121 @@ impl StationNames {
     ///
     /// This is useful when station name lookup is not needed.
     pub fn empty(client: StationClient) -> Self {
+        Self::with_data(StationData::default(), client, None)
+    }
+
+    /// Create a StationNames from a fixed set of stations, e.g. a bundled
+    /// fixture network.
+    pub fn from_known(
+        client: StationClient,
+        names: HashMap<Crs, String>,
+        locations: HashMap<Crs, StationLocation>,
+    ) -> Self {
+        let data = StationData {
+            names,
+            locations: Arc::new(locations),
+            hours: HashMap::new(),
+            unknown: Mutex::default(),
+        };
+        Self::with_data(data, client, None)
+    }
+
+    fn with_data(data: StationData, client: StationClient, cache: Option<StationCache>) -> Self {
         Self {
-            inner: Arc::new(RwLock::new(HashMap::new())),
+            data: Arc::new(RwLock::new(Arc::new(data))),
             client,
-            cache: None,
+            cache,
         }
     }
 
+    /// The current station data.
+    async fn data(&self) -> Arc<StationData> {
+        self.data.read().await.clone()
+    }
+
+    /// Look up a station's location by CRS code.
+    ///
+    /// Returns `None` if the station is unknown or the feed has no coordinates for it.
+    pub async fn location(&self, crs: &Crs) -> Option<StationLocation> {
+        self.data().await.locations.get(crs).copied()
+    }
+
+    /// Look up the locations of several stations at once.
+    ///
+    /// Stations without a known location are omitted from the result.
+    pub async fn locations(
+        &self,
+        stations: impl IntoIterator<Item = Crs>,
+    ) -> HashMap<Crs, StationLocation> {
+        let data = self.data().await;
+        stations
+            .into_iter()
+            .filter_map(|crs| data.locations.get(&crs).map(|loc| (crs, *loc)))
+            .collect()
+    }
+
+    /// The locations of every station that has one, e.g. for the planner to
+    /// tell which stations are closer to a destination.
+    pub async fn all_locations(&self) -> Arc<HashMap<Crs, StationLocation>> {
+        self.data().await.locations.clone()
+    }
+
+    /// Look up the opening hours of several stations at once.
+    ///
+    /// Stations open all day, or with unknown hours, are omitted.
+    pub async fn opening_hours(
+        &self,
+        stations: impl IntoIterator<Item = Crs>,
+    ) -> HashMap<Crs, OpeningHours> {
+        let data = self.data().await;
+        stations
+            .into_iter()
+            .filter_map(|crs| data.hours.get(&crs).map(|hours| (crs, *hours)))
+            .collect()
+    }
+
     /// Look up a station name by CRS code.
     pub async fn get(&self, crs: &Crs) -> Option<String> {
-        let guard = self.inner.read().await;
-        guard.get(crs).cloned()
+        self.data().await.names.get(crs).cloned()
+    }
+
+    /// Look up the names of several stations at once, e.g. all those in a
+    /// response.
+    ///
+    /// Stations without a known name are omitted from the result. Each is
+    /// logged the first time it's asked for until the data is next
+    /// refreshed, as a sign the data is stale, rather than on every lookup.
+    pub async fn names(&self, stations: impl IntoIterator<Item = Crs>) -> HashMap<Crs, String> {
+        let data = self.data().await;
+        let mut names = HashMap::new();
+        let mut unknown = Vec::new();
+        for crs in stations {
+            match data.names.get(&crs) {
+                Some(name) => {
+                    names.insert(crs, name.clone());
+                }
+                None => unknown.push(crs),
+            }
+        }
+        if !unknown.is_empty() {
+            let mut logged = data.unknown.lock().unwrap_or_else(|e| e.into_inner());
+            for crs in unknown {
+                if logged.insert(crs) {
+                    warn!(crs = %crs, known = data.names.len(), "Unknown station code; station data may need refreshing");
+                }
+            }
+        }
+        names
     }
 
     /// Get the number of stations in the lookup.
     pub async fn len(&self) -> usize {
-        let guard = self.inner.read().await;
-        guard.len()
+        self.data().await.names.len()
     }
 
     /// Check if the lookup is empty.
     pub async fn is_empty(&self) -> bool {
-        let guard = self.inner.read().await;
-        guard.is_empty()
+        self.data().await.names.is_empty()
     }
 
     /// Refresh the station data from the API.
@@ -122,13 +235,24 @@ impl StationNames {
             eprintln!("Warning: failed to save station cache: {}", e);
         }
 
-        let map = build_map(stations);
-        let count = map.len();
+        Ok(self.replace(StationData::from_stations(stations)).await)
+    }
 
-        let mut guard = self.inner.write().await;
-        *guard = map;
+    /// Reload the station data from the disk cache, e.g. after it has been
+    /// replaced by another instance.
+    ///
+    /// Returns the number of stations loaded, or `None` (keeping the current
+    /// data) if there's no cache configured or it's missing or expired.
+    pub async fn reload_cache(&self) -> Option<usize> {
+        let stations = self.cache.as_ref()?.load()?;
+        Some(self.replace(StationData::from_stations(stations)).await)
+    }
 
-        Ok(count)
+    /// Swap in new station data, returning the number of stations.
+    async fn replace(&self, data: StationData) -> usize {
+        let count = data.names.len();
+        *self.data.write().await = Arc::new(data);
+        count
     }
 
     /// Returns whether this instance is using disk caching.
@@ -142,7 +266,8 @@ impl StationNames {
     /// - The CRS code exactly matches (case-insensitive), or
     /// - The station name contains the query as a substring (case-insensitive)
     ///
-    /// Results are sorted: exact CRS matches first, then by name length (shorter first).
+    /// Results are sorted: exact CRS matches first, then by name length (shorter first),
+    /// then busiest station first.
     pub async fn search(&self, query: &str, limit: usize) -> Vec<StationMatch> {
         let query_upper = query.trim().to_uppercase();
         if query_upper.is_empty() {
@@ -150,9 +275,10 @@ impl StationNames {
         }
 
         let query_lower = query.trim().to_lowercase();
-        let guard = self.inner.read().await;
+        let data = self.data().await;
 
-        let mut results: Vec<StationMatch> = guard
+        let mut results: Vec<StationMatch> = data
+            .names
             .iter()
             .filter_map(|(crs, name)| {
                 let crs_str = crs.as_str();
@@ -196,12 +322,87 @@ impl StationNames {
             })
             .collect();
 
-        // Sort by score (lower is better), then alphabetically by name
-        results.sort_by(|a, b| a.score.cmp(&b.score).then_with(|| a.name.cmp(&b.name)));
+        // Sort by score (lower is better), then busiest first, then
+        // alphabetically by name
+        let usage = uk_station_usage();
+        let busyness = |m: &StationMatch| {
+            Crs::parse(&m.crs)
+                .ok()
+                .and_then(|crs| usage.entries_exits(&crs))
+                .unwrap_or(0)
+        };
+        results.sort_by(|a, b| {
+            a.score
+                .cmp(&b.score)
+                .then_with(|| busyness(b).cmp(&busyness(a)))
+                .then_with(|| a.name.cmp(&b.name))
+        });
 
         results.truncate(limit);
         results
     }
+
+    /// Whether a CRS code is a known station.
+    pub async fn contains(&self, crs: &Crs) -> bool {
+        self.data().await.names.contains_key(crs)
+    }
+
+    /// Suggest stations for input that isn't a known CRS code.
+    ///
+    /// Three-letter input is compared against every CRS code by edit distance,
+    /// so a typo like "KGS" suggests KGX. Anything else (or three letters with
+    /// no close code) falls back to a name search, so "kings cross" still helps.
+    ///
+    /// Results are sorted by edit distance, then name; `score` holds the distance.
+    pub async fn suggest(&self, input: &str, limit: usize) -> Vec<StationMatch> {
+        let input_upper = input.trim().to_uppercase();
+
+        if input_upper.len() == 3 {
+            let data = self.data().await;
+            let mut results: Vec<StationMatch> = data
+                .names
+                .iter()
+                .filter_map(|(crs, name)| {
+                    let distance = edit_distance(crs.as_str(), &input_upper);
+                    (distance <= MAX_SUGGESTION_DISTANCE).then(|| StationMatch {
+                        crs: crs.as_str().to_string(),
+                        name: name.clone(),
+                        score: distance,
+                    })
+                })
+                .collect();
+
+            if !results.is_empty() {
+                results.sort_by(|a, b| a.score.cmp(&b.score).then_with(|| a.name.cmp(&b.name)));
+                results.truncate(limit);
+                return results;
+            }
+        }
+
+        self.search(input, limit).await
+    }
+}
+
+/// Maximum edit distance between input and a CRS code to suggest it.
+const MAX_SUGGESTION_DISTANCE: usize = 1;
+
+/// Levenshtein distance between two ASCII strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, &ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
 }
 
 /// A station search result with ranking score.
@@ -224,9 +425,40 @@ fn build_map(stations: Vec<StationDto>) -> HashMap<Crs, String> {
         .collect()
 }
 
+/// Build the CRS → location map from station DTOs.
+///
+/// Stations with missing or out-of-range coordinates are skipped.
+fn build_locations(stations: &[StationDto]) -> HashMap<Crs, StationLocation> {
+    stations
+        .iter()
+        .filter_map(|s| {
+            let crs = Crs::parse(&s.crs_code.to_uppercase()).ok()?;
+            let location = StationLocation::new(s.latitude?, s.longitude?)?;
+            Some((crs, location))
+        })
+        .collect()
+}
+
+/// Build the CRS → opening hours map from station DTOs.
+///
+/// Stations with missing or malformed hours are skipped, i.e. treated as
+/// open all day.
+fn build_hours(stations: &[StationDto]) -> HashMap<Crs, OpeningHours> {
+    stations
+        .iter()
+        .filter_map(|s| {
+            let crs = Crs::parse(&s.crs_code.to_uppercase()).ok()?;
+            let dto = s.opening_hours.as_ref()?;
+            Some((crs, OpeningHours::parse(&dto.open, &dto.close)?))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stations::StationCacheConfig;
+    use crate::stations::client::OpeningHoursDto;
 
     #[test]
     fn build_map_filters_invalid_crs() {
@@ -234,14 +466,23 @@ mod tests {
             StationDto {
                 crs_code: "KGX".to_string(),
                 name: "London Kings Cross".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
             },
             StationDto {
                 crs_code: "invalid".to_string(),
                 name: "Bad Station".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
             },
             StationDto {
                 crs_code: "PAD".to_string(),
                 name: "London Paddington".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
             },
         ];
 
@@ -257,15 +498,169 @@ mod tests {
         );
     }
 
+    fn names(entries: &[(&str, &str)]) -> StationNames {
+        let client = StationClient::new(crate::stations::StationClientConfig::new("")).unwrap();
+        let map = entries
+            .iter()
+            .map(|(crs, name)| (Crs::parse(crs).unwrap(), name.to_string()))
+            .collect();
+        StationNames::from_known(client, map, HashMap::new())
+    }
+
+    #[tokio::test]
+    async fn search_ties_prefer_busier_stations() {
+        let names = names(&[("IPS", "Test Alpha"), ("WAT", "Test Omega")]);
+
+        let results = names.search("test", 5).await;
+        let crs: Vec<&str> = results.iter().map(|m| m.crs.as_str()).collect();
+        assert_eq!(crs, vec!["WAT", "IPS"]);
+    }
+
+    #[test]
+    fn edit_distance_basics() {
+        assert_eq!(edit_distance("KGX", "KGX"), 0);
+        assert_eq!(edit_distance("KGX", "KGS"), 1);
+        assert_eq!(edit_distance("KGX", "XGK"), 2);
+        assert_eq!(edit_distance("", "ABC"), 3);
+        assert_eq!(edit_distance("PAD", "PADD"), 1);
+    }
+
+    #[tokio::test]
+    async fn suggest_typo_crs() {
+        let names = names(&[
+            ("KGX", "London Kings Cross"),
+            ("PAD", "London Paddington"),
+            ("KGL", "Kings Langley"),
+        ]);
+
+        let suggestions = names.suggest("kgs", 5).await;
+        let crs: Vec<&str> = suggestions.iter().map(|m| m.crs.as_str()).collect();
+        assert_eq!(crs, vec!["KGL", "KGX"]);
+    }
+
+    #[tokio::test]
+    async fn suggest_falls_back_to_name_search() {
+        let names = names(&[("KGX", "London Kings Cross"), ("PAD", "London Paddington")]);
+
+        let suggestions = names.suggest("kings cross", 5).await;
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].crs, "KGX");
+    }
+
+    #[tokio::test]
+    async fn contains_known_crs() {
+        let names = names(&[("KGX", "London Kings Cross")]);
+        assert!(names.contains(&Crs::parse("KGX").unwrap()).await);
+        assert!(!names.contains(&Crs::parse("KGS").unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn names_looked_up_together_noting_unknown_codes() {
+        let names = names(&[("KGX", "London Kings Cross"), ("STP", "St Pancras")]);
+        let kgx = Crs::parse("KGX").unwrap();
+        let xyz = Crs::parse("XYZ").unwrap();
+
+        let found = names.names([kgx, xyz, kgx]).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[&kgx], "London Kings Cross");
+        assert!(names.data().await.unknown.lock().unwrap().contains(&xyz));
+
+        // Fresh data forgets which codes were already logged
+        names.replace(StationData::default()).await;
+        assert!(names.data().await.unknown.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn build_locations_skips_missing_coordinates() {
+        let stations = vec![
+            StationDto {
+                crs_code: "kgx".to_string(),
+                name: "London Kings Cross".to_string(),
+                latitude: Some(51.5320),
+                longitude: Some(-0.1233),
+                opening_hours: None,
+            },
+            StationDto {
+                crs_code: "PAD".to_string(),
+                name: "London Paddington".to_string(),
+                latitude: None,
+                longitude: None,
+                opening_hours: None,
+            },
+        ];
+
+        let locations = build_locations(&stations);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(
+            locations.get(&Crs::parse("KGX").unwrap()),
+            StationLocation::new(51.5320, -0.1233).as_ref()
+        );
+    }
+
     #[test]
     fn build_map_handles_lowercase_crs() {
         let stations = vec![StationDto {
             crs_code: "kgx".to_string(),
             name: "London Kings Cross".to_string(),
+            latitude: None,
+            longitude: None,
+            opening_hours: None,
         }];
 
         let map = build_map(stations);
         assert_eq!(map.len(), 1);
         assert!(map.contains_key(&Crs::parse("KGX").unwrap()));
     }
+
+    #[test]
+    fn build_hours_skips_missing_and_malformed() {
+        let station = |crs: &str, hours: Option<(&str, &str)>| StationDto {
+            crs_code: crs.to_string(),
+            name: crs.to_string(),
+            latitude: None,
+            longitude: None,
+            opening_hours: hours.map(|(open, close)| OpeningHoursDto {
+                open: open.to_string(),
+                close: close.to_string(),
+            }),
+        };
+        let stations = vec![
+            station("stp", Some(("05:00", "00:30"))),
+            station("KGX", None),
+            station("PAD", Some(("late", "00:30"))),
+        ];
+
+        let hours = build_hours(&stations);
+        assert_eq!(hours.len(), 1);
+        assert_eq!(
+            hours.get(&Crs::parse("STP").unwrap()),
+            OpeningHours::parse("05:00", "00:30").as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn reload_cache_replaces_all_station_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StationCacheConfig::new(dir.path().join("stations.json"));
+        let cache = StationCache::new(config);
+        let client = StationClient::new(crate::stations::StationClientConfig::new("")).unwrap();
+        let names = StationNames::with_data(StationData::default(), client, Some(cache.clone()));
+
+        // Nothing cached yet, so the current data is kept
+        assert_eq!(names.reload_cache().await, None);
+
+        let kgx = StationDto {
+            crs_code: "kgx".to_string(),
+            name: "London Kings Cross".to_string(),
+            latitude: Some(51.5320),
+            longitude: Some(-0.1233),
+            opening_hours: None,
+        };
+        cache.save(&[kgx]).unwrap();
+
+        assert_eq!(names.reload_cache().await, Some(1));
+        let crs = Crs::parse("KGX").unwrap();
+        assert_eq!(names.get(&crs).await.as_deref(), Some("London Kings Cross"));
+        assert!(names.location(&crs).await.is_some());
+    }
 }
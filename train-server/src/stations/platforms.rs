@@ -0,0 +1,210 @@
+//! Station platform layouts.
+//!
+//! Changing trains on the same platform, or across an island platform
+//! whose two faces are numbered separately, takes moments; changing to a
+//! platform on the other side of the tracks means climbing a footbridge
+//! (or going through a subway), which can take several minutes with
+//! luggage. [`PlatformTopology`] records which platforms at a station share
+//! one physical platform, so the planner can ask for a larger margin on
+//! changes over the footbridge and journeys can say which kind each change
+//! is.
+//!
+//! The dataset is optional and partial: stations not in it, and platforms
+//! not listed at a station that is, are unknown, and changes there are
+//! treated as before.
+
+use std::collections::HashMap;
+
+use crate::domain::{Change, Crs};
+
+/// How to get between two platforms at a station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlatformChange {
+    /// The same platform, or the other face of an island platform
+    SamePlatform,
+
+    /// A platform across the tracks, over the footbridge
+    Footbridge,
+}
+
+impl PlatformChange {
+    /// Lowercase name, as used in the JSON API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlatformChange::SamePlatform => "same_platform",
+            PlatformChange::Footbridge => "footbridge",
+        }
+    }
+
+    /// A short hint for someone changing to platform `to`.
+    pub fn hint(&self, to: &str) -> String {
+        match self {
+            PlatformChange::SamePlatform => {
+                format!("Same platform: your next train leaves from platform {to}")
+            }
+            PlatformChange::Footbridge => format!("Over the footbridge to platform {to}"),
+        }
+    }
+}
+
+/// Which platforms share one physical platform, for the stations known.
+#[derive(Debug, Clone, Default)]
+pub struct PlatformTopology {
+    /// Each station's physical platforms, as the platform numbers on them
+    stations: HashMap<Crs, Vec<Vec<String>>>,
+}
+
+impl PlatformTopology {
+    /// Create an empty topology.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a station's physical platforms, each given as the platform
+    /// numbers served from it (two for an island), replacing any existing
+    /// entry.
+    ///
+    /// Platforms not listed are unknown.
+    pub fn insert(&mut self, station: Crs, platforms: &[&[&str]]) {
+        let platforms = platforms
+            .iter()
+            .filter(|faces| !faces.is_empty())
+            .map(|faces| faces.iter().map(|f| f.trim().to_string()).collect())
+            .collect();
+        self.stations.insert(station, platforms);
+    }
+
+    /// Parse a topology from JSON mapping CRS codes to their physical
+    /// platforms, e.g. `{"RDG": [["1"], ["4", "5"], ["7", "8"]]}`.
+    ///
+    /// Entries with invalid CRS codes are skipped.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let entries: HashMap<String, Vec<Vec<String>>> = serde_json::from_str(json)?;
+        let mut topology = Self::new();
+        for (station, platforms) in entries {
+            let Ok(station) = Crs::parse(&station) else {
+                continue;
+            };
+            let platforms: Vec<Vec<&str>> = platforms
+                .iter()
+                .map(|faces| faces.iter().map(String::as_str).collect())
+                .collect();
+            let platforms: Vec<&[&str]> = platforms.iter().map(Vec::as_slice).collect();
+            topology.insert(station, &platforms);
+        }
+        Ok(topology)
+    }
+
+    /// The physical platform serving platform number `platform`.
+    fn physical(&self, station: &Crs, platform: &str) -> Option<usize> {
+        let platform = platform.trim();
+        self.stations
+            .get(station)?
+            .iter()
+            .position(|faces| faces.iter().any(|f| f.eq_ignore_ascii_case(platform)))
+    }
+
+    /// How to get from platform `from` to platform `to` at a station, if
+    /// both are known.
+    pub fn change(&self, station: &Crs, from: &str, to: &str) -> Option<PlatformChange> {
+        if from.trim().eq_ignore_ascii_case(to.trim()) {
+            return Some(PlatformChange::SamePlatform);
+        }
+        let from = self.physical(station, from)?;
+        let to = self.physical(station, to)?;
+        Some(if from == to {
+            PlatformChange::SamePlatform
+        } else {
+            PlatformChange::Footbridge
+        })
+    }
+
+    /// How to make a change between trains, if it's within one station and
+    /// both platforms are known.
+    pub fn for_change(&self, change: &Change) -> Option<PlatformChange> {
+        if change.walk.is_some() {
+            return None;
+        }
+        self.change(
+            change.from.alight_station(),
+            change.from.alight_platform()?,
+            change.to.board_platform()?,
+        )
+    }
+
+    /// Returns the number of stations with a layout.
+    pub fn len(&self) -> usize {
+        self.stations.len()
+    }
+
+    /// Returns true if no station has a layout.
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn reading() -> PlatformTopology {
+        let mut topology = PlatformTopology::new();
+        topology.insert(crs("RDG"), &[&["1"], &["4", "5"], &["7", "8"]]);
+        topology
+    }
+
+    #[test]
+    fn island_faces_are_the_same_platform() {
+        let topology = reading();
+        let rdg = crs("RDG");
+
+        assert_eq!(
+            topology.change(&rdg, "7", "8"),
+            Some(PlatformChange::SamePlatform)
+        );
+        assert_eq!(
+            topology.change(&rdg, "4", "4"),
+            Some(PlatformChange::SamePlatform)
+        );
+        assert_eq!(
+            topology.change(&rdg, "1", "5"),
+            Some(PlatformChange::Footbridge)
+        );
+        // Unlisted platforms and stations are unknown, unless unchanged
+        assert_eq!(topology.change(&rdg, "1", "13"), None);
+        assert_eq!(topology.change(&crs("SWI"), "1", "3"), None);
+        assert_eq!(
+            topology.change(&crs("SWI"), "3", "3"),
+            Some(PlatformChange::SamePlatform)
+        );
+    }
+
+    #[test]
+    fn parses_json_skipping_bad_codes() {
+        let topology =
+            PlatformTopology::from_json(r#"{"RDG": [["1"], ["7", "8"]], "nope": [["1"]]}"#)
+                .unwrap();
+        assert_eq!(topology.len(), 1);
+        assert_eq!(
+            topology.change(&crs("RDG"), "8", "7"),
+            Some(PlatformChange::SamePlatform)
+        );
+        assert!(PlatformTopology::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn hints_name_the_next_platform() {
+        assert_eq!(
+            PlatformChange::Footbridge.hint("5"),
+            "Over the footbridge to platform 5"
+        );
+        assert_eq!(
+            PlatformChange::SamePlatform.hint("8"),
+            "Same platform: your next train leaves from platform 8"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Request stops.
+//!
+//! Some rural stations are only served on request: passengers getting off
+//! tell the guard, and those getting on signal the driver. Trains that
+//! don't stop run through early, so times there are unreliable. Darwin's
+//! staff API marks these calls with an activity code; for responses without
+//! one, a bundled list is used instead.
+
+use crate::domain::Crs;
+
+/// Stations where trains stop only on request.
+///
+/// An incomplete list of well-known request stops, mostly on the Far North,
+/// Kyle and Heart of Wales lines. Must stay sorted.
+const UK_REQUEST_STOPS: &[&str] = &[
+    "ABC", // Altnabreac
+    "CUA", // Culrain
+    "CYN", // Cynghordy
+    "DCG", // Duncraig
+    "DNO", // Dunrobin Castle
+    "DOL", // Dolau
+    "KIL", // Kildonan
+    "KNU", // Knucklas
+    "LGO", // Llangynllo
+    "ROG", // Rogart
+    "SCT", // Scotscalder
+    "SUG", // Sugar Loaf
+];
+
+/// Whether trains are known to stop at `station` only on request.
+pub fn is_request_stop(station: &Crs) -> bool {
+    UK_REQUEST_STOPS.binary_search(&station.as_str()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_list_is_sorted() {
+        assert!(
+            UK_REQUEST_STOPS.windows(2).all(|w| w[0] < w[1]),
+            "UK_REQUEST_STOPS must be sorted with no duplicates"
+        );
+        assert!(UK_REQUEST_STOPS.iter().all(|crs| Crs::parse(crs).is_ok()));
+    }
+
+    #[test]
+    fn recognises_request_stops() {
+        assert!(is_request_stop(&Crs::parse("SUG").unwrap()));
+        assert!(!is_request_stop(&Crs::parse("PAD").unwrap()));
+    }
+}
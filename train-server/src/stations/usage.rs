@@ -0,0 +1,148 @@
+//! Station usage.
+//!
+//! How busy a station is, from the ORR's Estimates of Station Usage
+//! (entries and exits per year). Busier stations make better interchanges
+//! and likelier destinations, so where options are otherwise tied, the
+//! planner, train identification and station search prefer them.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::domain::Crs;
+
+/// Annual entries and exits at major stations, in millions.
+///
+/// Rounded, approximate figures from the ORR's Estimates of Station Usage,
+/// for ordering only. Stations not listed count as quiet.
+const UK_USAGE_MILLIONS: &[(&str, u64)] = &[
+    ("BFR", 12),
+    ("BHM", 35),
+    ("BRI", 10),
+    ("BTN", 14),
+    ("CBG", 11),
+    ("CDF", 11),
+    ("CHX", 20),
+    ("CLJ", 28),
+    ("CST", 14),
+    ("ECR", 17),
+    ("EDB", 20),
+    ("EUS", 30),
+    ("FPK", 11),
+    ("FST", 12),
+    ("GLC", 25),
+    ("GLQ", 15),
+    ("GTW", 19),
+    ("IPS", 3),
+    ("KGX", 28),
+    ("LBG", 40),
+    ("LDS", 25),
+    ("LIV", 13),
+    ("LST", 55),
+    ("MAN", 25),
+    ("MCV", 8),
+    ("MKC", 6),
+    ("MOG", 12),
+    ("MYB", 13),
+    ("NCL", 7),
+    ("OXF", 7),
+    ("PAD", 35),
+    ("RDG", 15),
+    ("SHF", 7),
+    ("SRA", 50),
+    ("STP", 30),
+    ("VIC", 36),
+    ("VXH", 15),
+    ("WAT", 41),
+    ("WLO", 8),
+    ("YRK", 8),
+];
+
+/// Annual entries and exits by station.
+#[derive(Debug, Clone, Default)]
+pub struct StationUsage {
+    usage: HashMap<Crs, u64>,
+    busiest: u64,
+}
+
+impl StationUsage {
+    /// Create a table from `(station, entries and exits)` pairs.
+    pub fn new(usage: impl IntoIterator<Item = (Crs, u64)>) -> Self {
+        let usage: HashMap<Crs, u64> = usage.into_iter().collect();
+        let busiest = usage.values().copied().max().unwrap_or(0);
+        Self { usage, busiest }
+    }
+
+    /// A station's annual entries and exits, if known.
+    pub fn entries_exits(&self, crs: &Crs) -> Option<u64> {
+        self.usage.get(crs).copied()
+    }
+
+    /// How busy a station is, from 0 (unknown or unused) to 1 (the busiest).
+    ///
+    /// Log-scaled, since usage spans several orders of magnitude.
+    pub fn popularity(&self, crs: &Crs) -> f64 {
+        match self.entries_exits(crs) {
+            Some(usage) if self.busiest > 0 => {
+                (usage as f64).ln_1p() / (self.busiest as f64).ln_1p()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Order two stations busiest first, for breaking ties.
+    pub fn busiest_first(&self, a: &Crs, b: &Crs) -> Ordering {
+        let usage = |crs| self.entries_exits(crs).unwrap_or(0);
+        usage(b).cmp(&usage(a))
+    }
+}
+
+/// Usage of major stations in Great Britain, bundled with the server.
+pub fn uk_station_usage() -> &'static StationUsage {
+    static USAGE: OnceLock<StationUsage> = OnceLock::new();
+    USAGE.get_or_init(|| {
+        StationUsage::new(
+            UK_USAGE_MILLIONS
+                .iter()
+                .filter_map(|(crs, millions)| Some((Crs::parse(crs).ok()?, millions * 1_000_000))),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[test]
+    fn bundled_table_is_sorted_and_valid() {
+        assert!(
+            UK_USAGE_MILLIONS.windows(2).all(|w| w[0].0 < w[1].0),
+            "UK_USAGE_MILLIONS must be sorted by CRS with no duplicates"
+        );
+        assert_eq!(uk_station_usage().usage.len(), UK_USAGE_MILLIONS.len());
+    }
+
+    #[test]
+    fn popularity_is_relative_to_busiest() {
+        let usage = StationUsage::new([(crs("LST"), 1_000_000), (crs("IPS"), 1_000)]);
+
+        assert_eq!(usage.popularity(&crs("LST")), 1.0);
+        assert!((0.4..0.6).contains(&usage.popularity(&crs("IPS"))));
+        assert_eq!(usage.popularity(&crs("XYZ")), 0.0);
+        assert_eq!(StationUsage::default().popularity(&crs("LST")), 0.0);
+    }
+
+    #[test]
+    fn orders_busiest_first() {
+        let usage = uk_station_usage();
+        let mut stations = vec![crs("XYZ"), crs("IPS"), crs("WAT")];
+
+        stations.sort_by(|a, b| usage.busiest_first(a, b));
+
+        assert_eq!(stations, vec![crs("WAT"), crs("IPS"), crs("XYZ")]);
+    }
+}
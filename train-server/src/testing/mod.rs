@@ -0,0 +1,54 @@
+//! Test data builders.
+//!
+//! Fluent builders for the services and networks that planner tests run
+//! against, so tests (here and in crates using the planner) describe a
+//! timetable rather than assembling `Call`s by hand:
+//!
+//! ```
+//! use train_server::testing::{ServiceBuilder, crs};
+//!
+//! let service = ServiceBuilder::new("CT")
+//!     .call("PAD").named("London Paddington").dep("10:00")
+//!     .call("RDG").arr("10:25").dep("10:27")
+//!     .call("BRI").arr("11:20")
+//!     .build();
+//!
+//! assert_eq!(service.calls.len(), 3);
+//! assert_eq!(service.calls[2].station, crs("BRI"));
+//! ```
+//!
+//! Builders panic on malformed input (bad station codes or times), since
+//! they're for fixtures written by hand.
+
+mod network;
+mod service;
+
+pub use network::{Network, NetworkBuilder};
+pub use service::ServiceBuilder;
+
+use chrono::NaiveDate;
+
+use crate::domain::{Crs, RailTime};
+
+/// The date fixtures run on unless given another: Friday 15 March 2024.
+pub fn test_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 3, 15).expect("valid date")
+}
+
+/// Parse a CRS code.
+///
+/// # Panics
+///
+/// If `s` isn't a valid CRS code.
+pub fn crs(s: &str) -> Crs {
+    Crs::parse(s).unwrap_or_else(|e| panic!("bad CRS {s:?}: {e}"))
+}
+
+/// Parse an "HH:MM" time on [`test_date`].
+///
+/// # Panics
+///
+/// If `s` isn't a valid time.
+pub fn time(s: &str) -> RailTime {
+    RailTime::parse_hhmm(s, test_date()).unwrap_or_else(|e| panic!("bad time {s:?}: {e}"))
+}
@@ -0,0 +1,219 @@
+//! Building a network of services to search.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::domain::{Crs, Service};
+use crate::walkable::WalkableConnections;
+
+use super::crs;
+
+/// Builds a [`Network`] from services and walks.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct NetworkBuilder {
+    services: Vec<Arc<Service>>,
+    walkable: WalkableConnections,
+    max_arrivals: Option<usize>,
+}
+
+impl NetworkBuilder {
+    /// Start an empty network.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a service.
+    pub fn service(mut self, service: impl Into<Arc<Service>>) -> Self {
+        self.services.push(service.into());
+        self
+    }
+
+    /// Add several services.
+    pub fn services(mut self, services: impl IntoIterator<Item = Arc<Service>>) -> Self {
+        self.services.extend(services);
+        self
+    }
+
+    /// Add a walk of `minutes` between two stations, in both directions.
+    ///
+    /// # Panics
+    ///
+    /// If the walk is from a station to itself, or isn't a positive number
+    /// of minutes.
+    pub fn walk(mut self, a: &str, b: &str, minutes: i64) -> Self {
+        self.walkable
+            .set(crs(a), crs(b), minutes)
+            .expect("valid walk");
+        self
+    }
+
+    /// Return at most `limit` arrivals per station, as Darwin limits its
+    /// boards. Departures are never limited.
+    pub fn max_arrivals(mut self, limit: usize) -> Self {
+        self.max_arrivals = Some(limit);
+        self
+    }
+
+    /// Build the network.
+    pub fn build(self) -> Network {
+        let mut departures: HashMap<Crs, Vec<Arc<Service>>> = HashMap::new();
+        let mut arrivals: HashMap<Crs, Vec<Arc<Service>>> = HashMap::new();
+        for service in &self.services {
+            // Like Darwin, every call but the last is a departure, and every
+            // call but the first an arrival
+            let last = service.calls.len().saturating_sub(1);
+            for (i, call) in service.calls.iter().enumerate() {
+                if i < last {
+                    departures
+                        .entry(call.station)
+                        .or_default()
+                        .push(service.clone());
+                }
+                if i > 0 {
+                    arrivals
+                        .entry(call.station)
+                        .or_default()
+                        .push(service.clone());
+                }
+            }
+        }
+
+        // Boards are in time order; the sorts are stable, so services at the
+        // same time stay in the order they were added
+        for (station, services) in &mut departures {
+            services.sort_by_key(|s| {
+                s.calls
+                    .iter()
+                    .find(|c| c.station == *station)
+                    .and_then(|c| c.expected_departure())
+            });
+        }
+        for (station, services) in &mut arrivals {
+            services.sort_by_key(|s| {
+                s.calls
+                    .iter()
+                    .find(|c| c.station == *station)
+                    .and_then(|c| c.expected_arrival())
+            });
+            if let Some(limit) = self.max_arrivals {
+                services.truncate(limit);
+            }
+        }
+
+        Network {
+            services: self.services,
+            departures,
+            arrivals,
+            walkable: self.walkable,
+            requests: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A fixed timetable, serving every train calling at a station as its
+/// departures and arrivals boards.
+///
+/// With the `planner-core` feature, a `Network` is a
+/// [`ServiceProvider`](crate::planner::ServiceProvider). Boards aren't
+/// filtered by time: searches get the whole day's services.
+#[derive(Debug)]
+pub struct Network {
+    services: Vec<Arc<Service>>,
+    departures: HashMap<Crs, Vec<Arc<Service>>>,
+    arrivals: HashMap<Crs, Vec<Arc<Service>>>,
+    walkable: WalkableConnections,
+    requests: AtomicUsize,
+}
+
+impl Network {
+    /// Every service, in the order added.
+    pub fn services(&self) -> &[Arc<Service>] {
+        &self.services
+    }
+
+    /// Services departing `station`, earliest first.
+    pub fn departures(&self, station: &Crs) -> Vec<Arc<Service>> {
+        self.departures.get(station).cloned().unwrap_or_default()
+    }
+
+    /// Services arriving at `station`, earliest first.
+    pub fn arrivals(&self, station: &Crs) -> Vec<Arc<Service>> {
+        self.arrivals.get(station).cloned().unwrap_or_default()
+    }
+
+    /// The walks between stations.
+    pub fn walkable(&self) -> &WalkableConnections {
+        &self.walkable
+    }
+
+    /// How many boards have been requested from the network.
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "planner-core")]
+impl crate::planner::ServiceProvider for Network {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        _after: crate::domain::RailTime,
+    ) -> Result<Vec<Arc<Service>>, crate::planner::SearchError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Ok(self.departures(station))
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        _after: crate::domain::RailTime,
+    ) -> Result<Vec<Arc<Service>>, crate::planner::SearchError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Ok(self.arrivals(station))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ServiceBuilder;
+
+    #[test]
+    fn boards_list_calling_services_in_time_order() {
+        let network = NetworkBuilder::new()
+            .service(
+                ServiceBuilder::new("LATE")
+                    .call("PAD")
+                    .dep("11:00")
+                    .call("RDG")
+                    .arr("11:25"),
+            )
+            .service(
+                ServiceBuilder::new("EARLY")
+                    .call("PAD")
+                    .dep("10:00")
+                    .call("RDG")
+                    .arr("10:25")
+                    .dep("10:27")
+                    .call("BRI")
+                    .arr("11:20"),
+            )
+            .walk("BRI", "BPW", 10)
+            .max_arrivals(1)
+            .build();
+
+        let ids = |services: Vec<Arc<Service>>| -> Vec<String> {
+            services
+                .iter()
+                .map(|s| s.service_ref.darwin_id.clone())
+                .collect()
+        };
+        assert_eq!(ids(network.departures(&crs("PAD"))), ["EARLY", "LATE"]);
+        assert_eq!(ids(network.departures(&crs("RDG"))), ["EARLY"]);
+        assert_eq!(ids(network.arrivals(&crs("RDG"))), ["EARLY"]);
+        assert!(network.departures(&crs("BRI")).is_empty());
+        assert!(network.walkable().is_walkable(&crs("BPW"), &crs("BRI")));
+    }
+}
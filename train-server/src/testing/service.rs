@@ -0,0 +1,279 @@
+//! Building a single service.
+
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+
+use crate::domain::{
+    AtocCode, Call, CallIndex, Headcode, RailTime, Service, ServiceRef, parse_time_sequence,
+};
+
+use super::{crs, test_date};
+
+/// A call being built, with its times still as written.
+#[derive(Debug, Clone)]
+struct CallSpec {
+    call: Call,
+    arr: Option<String>,
+    dep: Option<String>,
+    expected_arr: Option<String>,
+    expected_dep: Option<String>,
+}
+
+/// Builds a [`Service`] one call at a time.
+///
+/// [`call`](Self::call) starts a new call; the other methods set details of
+/// the most recent one (or of the service, where they say so). Times are
+/// "HH:MM" and roll over midnight as Darwin's do, so a service can run
+/// 23:50, 00:10.
+///
+/// # Panics
+///
+/// Per-call methods panic if no call has been started yet, and
+/// [`build`](Self::build) panics if the times don't parse.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct ServiceBuilder {
+    id: String,
+    date: NaiveDate,
+    headcode: Option<Headcode>,
+    operator: String,
+    operator_code: Option<AtocCode>,
+    board_station_idx: CallIndex,
+    calls: Vec<CallSpec>,
+}
+
+impl ServiceBuilder {
+    /// Start a service with the given Darwin ID, on [`test_date`].
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            date: test_date(),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            board_station_idx: CallIndex(0),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Run the service on `date` (its first time's date).
+    pub fn on(mut self, date: NaiveDate) -> Self {
+        self.date = date;
+        self
+    }
+
+    /// Set the service's headcode.
+    pub fn headcode(mut self, headcode: &str) -> Self {
+        self.headcode = Some(Headcode::parse(headcode).expect("valid headcode"));
+        self
+    }
+
+    /// Set the service's operator, by name and ATOC code.
+    pub fn operator(mut self, name: &str, code: &str) -> Self {
+        self.operator = name.to_string();
+        self.operator_code = Some(AtocCode::parse(code).expect("valid ATOC code"));
+        self
+    }
+
+    /// Board the service at the `index`th call (the first by default).
+    pub fn board_at(mut self, index: usize) -> Self {
+        self.board_station_idx = CallIndex(index);
+        self
+    }
+
+    /// Add a call at `station`, named after its CRS code.
+    pub fn call(mut self, station: &str) -> Self {
+        self.calls.push(CallSpec {
+            call: Call::new(crs(station), station.to_string()),
+            arr: None,
+            dep: None,
+            expected_arr: None,
+            expected_dep: None,
+        });
+        self
+    }
+
+    /// Name the call's station.
+    pub fn named(mut self, name: &str) -> Self {
+        self.last().call.station_name = name.to_string();
+        self
+    }
+
+    /// Set the call's booked arrival.
+    pub fn arr(mut self, time: &str) -> Self {
+        self.last().arr = Some(time.to_string());
+        self
+    }
+
+    /// Set the call's booked departure.
+    pub fn dep(mut self, time: &str) -> Self {
+        self.last().dep = Some(time.to_string());
+        self
+    }
+
+    /// Set the call's realtime arrival.
+    pub fn expected_arr(mut self, time: &str) -> Self {
+        self.last().expected_arr = Some(time.to_string());
+        self
+    }
+
+    /// Set the call's realtime departure.
+    pub fn expected_dep(mut self, time: &str) -> Self {
+        self.last().expected_dep = Some(time.to_string());
+        self
+    }
+
+    /// Set the call's platform.
+    pub fn platform(mut self, platform: &str) -> Self {
+        self.last().call.platform = Some(platform.to_string());
+        self
+    }
+
+    /// Cancel the call.
+    pub fn cancelled(mut self) -> Self {
+        self.last().call.is_cancelled = true;
+        self
+    }
+
+    /// Make the call a request stop.
+    pub fn request_stop(mut self) -> Self {
+        self.last().call.is_request_stop = true;
+        self
+    }
+
+    /// Make the call set down only: passengers may alight but not board.
+    pub fn set_down_only(mut self) -> Self {
+        self.last().call.is_set_down_only = true;
+        self
+    }
+
+    /// Make the call pick up only: passengers may board but not alight.
+    pub fn pick_up_only(mut self) -> Self {
+        self.last().call.is_pick_up_only = true;
+        self
+    }
+
+    fn last(&mut self) -> &mut CallSpec {
+        self.calls
+            .last_mut()
+            .expect("start a call with .call() first")
+    }
+
+    /// Build the service.
+    pub fn build(self) -> Arc<Service> {
+        let booked = self.times(|spec| [spec.arr.as_deref(), spec.dep.as_deref()]);
+        // Parse realtime times in the same sequence as booked ones, so they
+        // roll over midnight together
+        let realtime = self.times(|spec| {
+            [
+                spec.expected_arr.as_deref().or(spec.arr.as_deref()),
+                spec.expected_dep.as_deref().or(spec.dep.as_deref()),
+            ]
+        });
+
+        let board_crs = self
+            .calls
+            .get(self.board_station_idx.0)
+            .expect("board call exists")
+            .call
+            .station;
+        let calls = self
+            .calls
+            .into_iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let mut call = spec.call;
+                call.booked_arrival = booked[2 * i];
+                call.booked_departure = booked[2 * i + 1];
+                if spec.expected_arr.is_some() {
+                    call.realtime_arrival = realtime[2 * i];
+                }
+                if spec.expected_dep.is_some() {
+                    call.realtime_departure = realtime[2 * i + 1];
+                }
+                call
+            })
+            .collect();
+
+        Arc::new(Service {
+            service_ref: ServiceRef::new(self.id, board_crs),
+            headcode: self.headcode,
+            operator: self.operator,
+            operator_code: self.operator_code,
+            calls,
+            board_station_idx: self.board_station_idx,
+            formation: None,
+        })
+    }
+
+    /// Each call's arrival and departure, in order, as chosen by `pick`.
+    fn times<'a>(
+        &'a self,
+        pick: impl Fn(&'a CallSpec) -> [Option<&'a str>; 2],
+    ) -> Vec<Option<RailTime>> {
+        let times: Vec<_> = self.calls.iter().flat_map(pick).collect();
+        parse_time_sequence(&times, self.date).expect("valid times")
+    }
+}
+
+impl From<ServiceBuilder> for Arc<Service> {
+    fn from(builder: ServiceBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::time;
+
+    #[test]
+    fn builds_calls_in_order() {
+        let service = ServiceBuilder::new("CT")
+            .call("PAD")
+            .named("London Paddington")
+            .dep("10:00")
+            .platform("1")
+            .call("RDG")
+            .arr("10:25")
+            .dep("10:27")
+            .expected_dep("10:30")
+            .call("BRI")
+            .arr("11:20")
+            .cancelled()
+            .build();
+
+        assert_eq!(service.service_ref.darwin_id, "CT");
+        assert_eq!(service.service_ref.board_crs, crs("PAD"));
+        assert_eq!(service.calls[0].station_name, "London Paddington");
+        assert_eq!(service.calls[0].platform.as_deref(), Some("1"));
+        assert_eq!(service.calls[0].booked_arrival, None);
+        assert_eq!(service.calls[1].station_name, "RDG");
+        assert_eq!(service.calls[1].booked_departure, Some(time("10:27")));
+        assert_eq!(service.calls[1].realtime_departure, Some(time("10:30")));
+        assert_eq!(service.calls[1].realtime_arrival, None);
+        assert!(service.calls[2].is_cancelled);
+    }
+
+    #[test]
+    fn times_roll_over_midnight() {
+        let service = ServiceBuilder::new("SLEEPER")
+            .call("EUS")
+            .dep("23:50")
+            .call("WFJ")
+            .arr("00:05")
+            .expected_arr("00:12")
+            .build();
+
+        let next_day = test_date().succ_opt().unwrap();
+        assert_eq!(service.calls[1].booked_arrival.unwrap().date(), next_day);
+        assert_eq!(service.calls[1].realtime_arrival.unwrap().date(), next_day);
+    }
+
+    #[test]
+    #[should_panic(expected = "start a call")]
+    fn details_need_a_call() {
+        let _ = ServiceBuilder::new("CT").dep("10:00");
+    }
+}
@@ -0,0 +1,179 @@
+//! Position tracking along the user's current train.
+//!
+//! A position (`CallIndex`) captured when the user identified their train goes
+//! stale as the train moves. This module advances it as expected (realtime,
+//! falling back to booked) times pass, so re-plans start from where the user
+//! actually is.
+
+use crate::domain::{CallIndex, RailTime, Service};
+
+/// Advance a position along a service to reflect the current time.
+///
+/// Moves forward to the latest call the train has reached by `now`, i.e.
+/// the last call whose expected arrival (or departure, for calls with no
+/// arrival time) is at or before `now`. Cancelled calls are skipped: the
+/// train doesn't stop there, so the user can't be "at" one.
+///
+/// Never moves backwards, and returns `current` unchanged if it is out of
+/// bounds for the service.
+pub fn advance_position(service: &Service, current: CallIndex, now: RailTime) -> CallIndex {
+    if current.0 >= service.calls.len() {
+        return current;
+    }
+
+    let mut position = current;
+    for (idx, call) in service.calls.iter().enumerate().skip(current.0 + 1) {
+        if call.is_cancelled {
+            continue;
+        }
+
+        let reached_at = match call
+            .expected_arrival()
+            .or_else(|| call.expected_departure())
+        {
+            Some(t) => t,
+            // No times at all: can't tell whether we've passed it, so keep going
+            // and let a later timed call decide.
+            None => continue,
+        };
+
+        if reached_at > now {
+            break;
+        }
+        position = CallIndex(idx);
+    }
+
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, Crs, ServiceRef};
+    use std::sync::Arc;
+
+    fn date() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, date()).unwrap()
+    }
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn make_service(calls_data: &[(&str, &str, &str)]) -> Arc<Service> {
+        let calls: Vec<Call> = calls_data
+            .iter()
+            .map(|(station, arr, dep)| {
+                let mut call = Call::new(crs(station), station.to_string());
+                if !arr.is_empty() {
+                    call.booked_arrival = Some(time(arr));
+                }
+                if !dep.is_empty() {
+                    call.booked_departure = Some(time(dep));
+                }
+                call
+            })
+            .collect();
+
+        Arc::new(Service {
+            service_ref: ServiceRef::new("TEST".to_string(), crs("PAD")),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    fn service() -> Arc<Service> {
+        make_service(&[
+            ("PAD", "", "10:00"),
+            ("RDG", "10:25", "10:27"),
+            ("SWI", "10:55", "10:57"),
+            ("BRI", "11:30", ""),
+        ])
+    }
+
+    #[test]
+    fn stays_put_before_next_arrival() {
+        let svc = service();
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("10:10")),
+            CallIndex(0)
+        );
+    }
+
+    #[test]
+    fn advances_once_next_call_reached() {
+        let svc = service();
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("10:25")),
+            CallIndex(1)
+        );
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("11:00")),
+            CallIndex(2)
+        );
+    }
+
+    #[test]
+    fn stops_at_terminus() {
+        let svc = service();
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("12:00")),
+            CallIndex(3)
+        );
+    }
+
+    #[test]
+    fn never_moves_backwards() {
+        let svc = service();
+        assert_eq!(
+            advance_position(&svc, CallIndex(2), time("10:00")),
+            CallIndex(2)
+        );
+    }
+
+    #[test]
+    fn uses_realtime_when_delayed() {
+        let svc = service();
+        let mut svc = (*svc).clone();
+        svc.calls[1].realtime_arrival = Some(time("10:40"));
+
+        // Booked arrival at RDG has passed, but the train is running late
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("10:30")),
+            CallIndex(0)
+        );
+    }
+
+    #[test]
+    fn skips_cancelled_calls() {
+        let svc = service();
+        let mut svc = (*svc).clone();
+        svc.calls[1].is_cancelled = true;
+
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("10:30")),
+            CallIndex(0)
+        );
+        assert_eq!(
+            advance_position(&svc, CallIndex(0), time("11:00")),
+            CallIndex(2)
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_unchanged() {
+        let svc = service();
+        assert_eq!(
+            advance_position(&svc, CallIndex(10), time("12:00")),
+            CallIndex(10)
+        );
+    }
+}
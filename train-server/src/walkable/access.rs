@@ -0,0 +1,230 @@
+//! Step-free access on walks between stations.
+//!
+//! Some interchange walks can't be made without a lift (a deep Underground
+//! passage, a footbridge with no ramp) and some have stairs on the way.
+//! Each connection can be flagged with its [`WalkAccess`], and lifts
+//! reported out of service are held in [`LiftOutages`], which a disruption
+//! feed or the admin API keeps up to date. Searches can leave out walks
+//! needing a lift that's out of service (see
+//! [`WalkableConnections::without_lift_walks_at`](super::WalkableConnections::without_lift_walks_at));
+//! otherwise [`access_warnings`] says which of a journey's walks need a
+//! lift or have stairs.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use super::WalkableConnections;
+use crate::domain::{Crs, Journey, Segment};
+
+/// What a walk between two stations needs, for travellers who can't
+/// manage steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkAccess {
+    /// The walk can only be made step-free by lift
+    pub requires_lift: bool,
+
+    /// The walk has stairs, whether or not a lift avoids them
+    pub requires_stairs: bool,
+}
+
+impl WalkAccess {
+    /// Whether the walk is step-free with no lift.
+    pub fn is_level(&self) -> bool {
+        !self.requires_lift && !self.requires_stairs
+    }
+}
+
+/// Stations whose lifts are reported out of service.
+///
+/// Cheap to clone; clones share the same set.
+#[derive(Debug, Clone, Default)]
+pub struct LiftOutages {
+    stations: Arc<RwLock<BTreeSet<Crs>>>,
+}
+
+impl LiftOutages {
+    /// No outages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a lift outage at `station`. Returns `true` if it's new.
+    pub fn report(&self, station: Crs) -> bool {
+        self.write().insert(station)
+    }
+
+    /// Record that `station`'s lifts are back in service. Returns `true`
+    /// if an outage was recorded there.
+    pub fn restore(&self, station: &Crs) -> bool {
+        self.write().remove(station)
+    }
+
+    /// Replace all outages, e.g. with a disruption feed's latest list.
+    pub fn replace(&self, stations: impl IntoIterator<Item = Crs>) {
+        *self.write() = stations.into_iter().collect();
+    }
+
+    /// The stations with an outage now.
+    pub fn current(&self) -> BTreeSet<Crs> {
+        self.stations
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, BTreeSet<Crs>> {
+        self.stations.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// What a journey's walk needs that a traveller might not manage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessIssue {
+    /// The walk needs the lift at this station, which is out of service
+    LiftOutOfService(Crs),
+
+    /// The walk needs a lift
+    NeedsLift,
+
+    /// The walk has stairs
+    HasStairs,
+}
+
+/// An access issue on one of a journey's walks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessWarning {
+    /// Where the walk starts
+    pub from: Crs,
+    /// Where the walk ends
+    pub to: Crs,
+    /// What it needs
+    pub issue: AccessIssue,
+}
+
+impl fmt::Display for AccessWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (from, to) = (self.from, self.to);
+        match self.issue {
+            AccessIssue::LiftOutOfService(station) => write!(
+                f,
+                "The walk from {from} to {to} needs the lift at {station}, which is out of service"
+            ),
+            AccessIssue::NeedsLift => write!(f, "The walk from {from} to {to} needs a lift"),
+            AccessIssue::HasStairs => write!(f, "The walk from {from} to {to} has stairs"),
+        }
+    }
+}
+
+/// The access issues on the journey's walks, given the lifts out of
+/// service at `outages`.
+///
+/// A walk needing a lift that's out is only reported as such, as that
+/// matters more than its stairs.
+pub fn access_warnings(
+    journey: &Journey,
+    walkable: &WalkableConnections,
+    outages: &BTreeSet<Crs>,
+) -> Vec<AccessWarning> {
+    let mut warnings = Vec::new();
+    for segment in journey.segments() {
+        let Segment::Walk(walk) = segment else {
+            continue;
+        };
+        let (from, to) = (walk.from, walk.to);
+        let access = walkable.access(&from, &to);
+        let out = [from, to].into_iter().find(|s| outages.contains(s));
+        let issues = match out {
+            Some(station) if access.requires_lift => vec![AccessIssue::LiftOutOfService(station)],
+            _ => [
+                access.requires_lift.then_some(AccessIssue::NeedsLift),
+                access.requires_stairs.then_some(AccessIssue::HasStairs),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        };
+        warnings.extend(
+            issues
+                .into_iter()
+                .map(|issue| AccessWarning { from, to, issue }),
+        );
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CallIndex, Leg, Walk};
+    use crate::testing::{ServiceBuilder, crs};
+    use chrono::Duration;
+
+    /// A train from PAD to KGX, then a walk to STP.
+    fn journey_walking_to_stp() -> Journey {
+        let service = ServiceBuilder::new("A")
+            .call("PAD")
+            .dep("10:00")
+            .call("KGX")
+            .arr("10:20")
+            .build();
+        Journey::new(vec![
+            Segment::Train(Leg::new(service, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Walk(Walk::new(crs("KGX"), crs("STP"), Duration::minutes(3))),
+        ])
+        .unwrap()
+    }
+
+    fn walkable(access: WalkAccess) -> WalkableConnections {
+        let mut walkable = WalkableConnections::new();
+        walkable.add(crs("KGX"), crs("STP"), 3).unwrap();
+        walkable.set_access(crs("STP"), crs("KGX"), access);
+        walkable
+    }
+
+    #[test]
+    fn level_walks_have_no_warnings() {
+        let warnings = access_warnings(
+            &journey_walking_to_stp(),
+            &walkable(WalkAccess::default()),
+            &BTreeSet::from([crs("KGX")]),
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn walks_needing_lifts_or_stairs_are_warned_about() {
+        let access = WalkAccess {
+            requires_lift: true,
+            requires_stairs: true,
+        };
+        let journey = journey_walking_to_stp();
+
+        let warnings = access_warnings(&journey, &walkable(access), &BTreeSet::new());
+        let issues: Vec<AccessIssue> = warnings.iter().map(|w| w.issue).collect();
+        assert_eq!(issues, [AccessIssue::NeedsLift, AccessIssue::HasStairs]);
+
+        let warnings = access_warnings(&journey, &walkable(access), &BTreeSet::from([crs("STP")]));
+        assert_eq!(
+            warnings[0].to_string(),
+            "The walk from KGX to STP needs the lift at STP, which is out of service"
+        );
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn outages_are_shared_between_clones() {
+        let outages = LiftOutages::new();
+        let feed = outages.clone();
+
+        assert!(feed.report(crs("KGX")));
+        assert!(!feed.report(crs("KGX")));
+        assert_eq!(outages.current(), BTreeSet::from([crs("KGX")]));
+
+        assert!(outages.restore(&crs("KGX")));
+        assert!(feed.current().is_empty());
+
+        feed.replace([crs("EUS"), crs("STP")]);
+        assert_eq!(outages.current().len(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+//! Plausibility checks for walking times.
+//!
+//! A walk that's far too quick for the distance between its stations (a
+//! mistyped minute count, or the wrong station code) would let the planner
+//! make changes nobody could, so walks are checked against the straight-line
+//! distance between the stations before they're accepted.
+
+use crate::domain::Crs;
+
+use super::{WalkableConnections, WalkableError};
+
+/// Fastest believable pace between stations, in metres per minute.
+///
+/// Brisker than walking (about 80), since some connections are quicker by a
+/// Tube hop, and the straight line is never the route taken anyway.
+const MAX_METRES_PER_MINUTE: f64 = 170.0;
+
+/// Distance allowed on top of the pace, in metres, since a station's
+/// coordinates can be some way from the entrance used.
+const DISTANCE_ALLOWANCE_METRES: f64 = 250.0;
+
+/// Check a walk of `minutes` between stations `distance_metres` apart is
+/// believable.
+pub fn check_walk(
+    from: Crs,
+    to: Crs,
+    minutes: i64,
+    distance_metres: f64,
+) -> Result<(), WalkableError> {
+    let reachable = minutes.max(0) as f64 * MAX_METRES_PER_MINUTE + DISTANCE_ALLOWANCE_METRES;
+    if distance_metres > reachable {
+        return Err(WalkableError::Implausible {
+            from,
+            to,
+            minutes,
+            metres: distance_metres.round() as u32,
+        });
+    }
+    Ok(())
+}
+
+impl WalkableConnections {
+    /// Remove connections too quick for the distance between their stations.
+    ///
+    /// `distance` gives the straight-line distance in metres between two
+    /// stations, or `None` if either location is unknown; those connections
+    /// are kept. Returns why each removed connection was rejected.
+    pub fn remove_implausible(
+        &mut self,
+        distance: impl Fn(&Crs, &Crs) -> Option<f64>,
+    ) -> Vec<WalkableError> {
+        let rejected: Vec<WalkableError> = self
+            .pairs()
+            .into_iter()
+            .filter_map(|(from, to, minutes)| {
+                let metres = distance(&from, &to)?;
+                check_walk(from, to, minutes, metres).err()
+            })
+            .collect();
+        for error in &rejected {
+            if let WalkableError::Implausible { from, to, .. } = error {
+                self.remove(from, to);
+            }
+        }
+        rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::walkable::london_connections;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[test]
+    fn quick_walks_between_distant_stations_are_rejected() {
+        assert!(check_walk(crs("KGX"), crs("STP"), 3, 235.0).is_ok());
+        assert!(check_walk(crs("CHX"), crs("LST"), 20, 3_170.0).is_ok());
+        assert_eq!(
+            check_walk(crs("KGX"), crs("LST"), 5, 3_282.7),
+            Err(WalkableError::Implausible {
+                from: crs("KGX"),
+                to: crs("LST"),
+                minutes: 5,
+                metres: 3_283,
+            })
+        );
+    }
+
+    #[test]
+    fn removes_only_implausible_connections() {
+        let mut wc = london_connections();
+        wc.add(crs("KGX"), crs("LST"), 5).unwrap();
+        let distances = [
+            ((crs("KGX"), crs("LST")), 3_283.0),
+            ((crs("EUS"), crs("KGX")), 700.0),
+        ];
+
+        let rejected = wc.remove_implausible(|a, b| {
+            distances
+                .iter()
+                .find(|((x, y), _)| (x, y) == (a, b))
+                .map(|(_, metres)| *metres)
+        });
+
+        assert_eq!(rejected.len(), 1);
+        assert!(!wc.is_walkable(&crs("KGX"), &crs("LST")));
+        // Plausible, and unknown, distances are kept
+        assert!(wc.is_walkable(&crs("EUS"), &crs("KGX")));
+        assert_eq!(wc.len(), london_connections().len());
+    }
+}
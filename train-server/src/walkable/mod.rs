@@ -4,23 +4,85 @@
 //! that don't appear in the rail network (e.g., London termini).
 //! This module provides lookup for walkable station pairs and their durations.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use chrono::Duration;
 
 use crate::domain::Crs;
+use crate::error::{Classify, ErrorKind};
+
+mod access;
+mod guard;
+mod store;
+
+pub use access::{AccessIssue, AccessWarning, LiftOutages, WalkAccess, access_warnings};
+pub use guard::check_walk;
+pub use store::{WalkableStore, WalkableStoreError};
+
+/// Errors editing walkable connections.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WalkableError {
+    /// A connection from a station to itself, which has no meaning
+    #[error("{0} cannot be walkable to itself")]
+    SelfConnection(Crs),
+
+    /// A walk too quick for the distance between its stations
+    #[error(
+        "a {minutes}-minute walk between {from} and {to} is implausible, as they are {metres}m apart"
+    )]
+    Implausible {
+        from: Crs,
+        to: Crs,
+        minutes: i64,
+        metres: u32,
+    },
+}
+
+impl Classify for WalkableError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WalkableError::SelfConnection(_) | WalkableError::Implausible { .. } => {
+                ErrorKind::Permanent
+            }
+        }
+    }
+}
+
+/// What [`WalkableConnections::add`] does with a pair that's already there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep whichever walking time is shorter
+    #[default]
+    KeepShortest,
+
+    /// Replace the walking time with the one added last
+    KeepLatest,
+}
 
 /// A collection of walkable connections between stations.
 ///
 /// Connections are symmetric: if you can walk from A to B, you can walk from B to A
-/// in the same time.
-#[derive(Debug, Clone, Default)]
+/// in the same time. Each pair is stored once, under its stations in
+/// alphabetical order, so lookups and counts can't disagree between the
+/// two directions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct WalkableConnections {
-    /// Map from (from, to) to walk duration in minutes.
-    /// Stored in both directions for O(1) lookup.
+    /// Map from (first, second) in alphabetical order to walk duration in
+    /// minutes.
     connections: HashMap<(Crs, Crs), i64>,
-    /// Count of unique pairs (not counting both directions).
-    pair_count: usize,
+    /// Access needs of the pairs that aren't level, under the same keys.
+    access: HashMap<(Crs, Crs), WalkAccess>,
+    /// What `add` does with a pair that's already there.
+    policy: DuplicatePolicy,
+}
+
+/// The key a pair of stations is stored under, whichever way round.
+fn pair_key(a: Crs, b: Crs) -> (Crs, Crs) {
+    if a.as_str() <= b.as_str() {
+        (a, b)
+    } else {
+        (b, a)
+    }
 }
 
 impl WalkableConnections {
@@ -29,36 +91,137 @@ impl WalkableConnections {
         Self::default()
     }
 
+    /// Set what [`add`](Self::add) does with a pair that's already there.
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// What [`add`](Self::add) does with a pair that's already there.
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.policy
+    }
+
     /// Add a walkable connection between two stations.
     ///
-    /// The connection is stored symmetrically (both A→B and B→A).
-    /// If the connection already exists, keeps the shorter duration.
-    /// Self-connections (A→A) are ignored as they have no meaning.
-    pub fn add(&mut self, from: Crs, to: Crs, duration_minutes: i64) {
-        // Ignore self-connections - walking from a station to itself is meaningless
+    /// The connection applies in both directions. If the pair already
+    /// exists, the [`DuplicatePolicy`] decides which walking time is kept
+    /// (by default the shorter). Returns `true` if the pair was new.
+    pub fn add(
+        &mut self,
+        from: Crs,
+        to: Crs,
+        duration_minutes: i64,
+    ) -> Result<bool, WalkableError> {
+        let key = Self::key(from, to)?;
+        match self.connections.get_mut(&key) {
+            Some(existing) => {
+                let replace = match self.policy {
+                    DuplicatePolicy::KeepShortest => duration_minutes < *existing,
+                    DuplicatePolicy::KeepLatest => true,
+                };
+                if replace {
+                    *existing = duration_minutes;
+                }
+                Ok(false)
+            }
+            None => {
+                self.connections.insert(key, duration_minutes);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Set the walk duration between two stations, replacing any existing one.
+    ///
+    /// Unlike [`add`](Self::add), this can lengthen a connection whatever
+    /// the duplicate policy. Returns `true` if the pair was new.
+    pub fn set(
+        &mut self,
+        from: Crs,
+        to: Crs,
+        duration_minutes: i64,
+    ) -> Result<bool, WalkableError> {
+        let key = Self::key(from, to)?;
+        Ok(self.connections.insert(key, duration_minutes).is_none())
+    }
+
+    /// The key for a pair, rejecting self-connections.
+    fn key(from: Crs, to: Crs) -> Result<(Crs, Crs), WalkableError> {
         if from == to {
-            return;
+            return Err(WalkableError::SelfConnection(from));
         }
+        Ok(pair_key(from, to))
+    }
 
-        // Check if this pair already exists
-        let existing = self.connections.get(&(from, to)).copied();
+    /// Remove the connection between two stations (in both directions).
+    ///
+    /// Returns `true` if there was a connection to remove.
+    pub fn remove(&mut self, from: &Crs, to: &Crs) -> bool {
+        let key = pair_key(*from, *to);
+        self.access.remove(&key);
+        self.connections.remove(&key).is_some()
+    }
 
-        match existing {
-            Some(existing_duration) => {
-                // Keep the shorter duration
-                if duration_minutes < existing_duration {
-                    self.connections.insert((from, to), duration_minutes);
-                    self.connections.insert((to, from), duration_minutes);
-                }
-                // If new duration is longer or equal, don't update
-            }
-            None => {
-                // New pair - insert and increment count
-                self.connections.insert((from, to), duration_minutes);
-                self.connections.insert((to, from), duration_minutes);
-                self.pair_count += 1;
+    /// Set what the walk between two stations needs for step-free access.
+    ///
+    /// Returns `false`, changing nothing, if the stations aren't walkable.
+    pub fn set_access(&mut self, from: Crs, to: Crs, access: WalkAccess) -> bool {
+        let key = pair_key(from, to);
+        if !self.connections.contains_key(&key) {
+            return false;
+        }
+        if access.is_level() {
+            self.access.remove(&key);
+        } else {
+            self.access.insert(key, access);
+        }
+        true
+    }
+
+    /// What the walk between two stations needs for step-free access (level
+    /// if it isn't flagged, or the stations aren't walkable).
+    pub fn access(&self, from: &Crs, to: &Crs) -> WalkAccess {
+        self.access
+            .get(&pair_key(*from, *to))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// These connections without the walks that need a lift at any of
+    /// `outages`, for travellers avoiding lifts that are out of service.
+    pub fn without_lift_walks_at(&self, outages: &BTreeSet<Crs>) -> Self {
+        let mut kept = self.clone();
+        for ((a, b), access) in &self.access {
+            if access.requires_lift && (outages.contains(a) || outages.contains(b)) {
+                kept.remove(a, b);
             }
         }
+        kept
+    }
+
+    /// Iterate over the connections, one entry per pair, in no particular
+    /// order.
+    ///
+    /// Each pair is reported with the alphabetically-first station first.
+    pub fn iter(&self) -> impl Iterator<Item = (Crs, Crs, Duration)> + '_ {
+        self.connections
+            .iter()
+            .map(|((a, b), mins)| (*a, *b, Duration::minutes(*mins)))
+    }
+
+    /// All connections as `(from, to, minutes)`, one entry per pair.
+    ///
+    /// Each pair is reported once with the alphabetically-first station as
+    /// `from`, sorted for stable output.
+    pub fn pairs(&self) -> Vec<(Crs, Crs, i64)> {
+        let mut pairs: Vec<(Crs, Crs, i64)> = self
+            .connections
+            .iter()
+            .map(|((a, b), mins)| (*a, *b, *mins))
+            .collect();
+        pairs.sort_by(|a, b| (a.0.as_str(), a.1.as_str()).cmp(&(b.0.as_str(), b.1.as_str())));
+        pairs
     }
 
     /// Get the walk duration between two stations, if walkable.
@@ -66,32 +229,38 @@ impl WalkableConnections {
     /// Returns `None` if the stations are not walkable.
     pub fn get(&self, from: &Crs, to: &Crs) -> Option<Duration> {
         self.connections
-            .get(&(*from, *to))
+            .get(&pair_key(*from, *to))
             .map(|mins| Duration::minutes(*mins))
     }
 
     /// Check if two stations are walkable.
     pub fn is_walkable(&self, from: &Crs, to: &Crs) -> bool {
-        self.connections.contains_key(&(*from, *to))
+        self.connections.contains_key(&pair_key(*from, *to))
     }
 
     /// Get all stations walkable from a given station.
     pub fn walkable_from(&self, from: &Crs) -> Vec<(Crs, Duration)> {
-        self.connections
-            .iter()
-            .filter(|((f, _), _)| f == from)
-            .map(|((_, t), mins)| (*t, Duration::minutes(*mins)))
+        self.iter()
+            .filter_map(|(a, b, duration)| {
+                if a == *from {
+                    Some((b, duration))
+                } else if b == *from {
+                    Some((a, duration))
+                } else {
+                    None
+                }
+            })
             .collect()
     }
 
     /// Returns the number of walkable pairs (counting A→B and B→A as one).
     pub fn len(&self) -> usize {
-        self.pair_count
+        self.connections.len()
     }
 
     /// Returns true if there are no walkable connections.
     pub fn is_empty(&self) -> bool {
-        self.pair_count == 0
+        self.connections.is_empty()
     }
 
     /// Create a closure suitable for `Journey::from_legs`.
@@ -130,9 +299,12 @@ impl WalkableConnectionsBuilder {
     }
 
     /// Add a walkable connection.
+    ///
+    /// Connections with an invalid CRS code, or from a station to itself,
+    /// are skipped.
     pub fn add(mut self, from: &str, to: &str, duration_minutes: i64) -> Self {
         if let (Some(from_crs), Some(to_crs)) = (Crs::parse(from).ok(), Crs::parse(to).ok()) {
-            self.inner.add(from_crs, to_crs, duration_minutes);
+            let _ = self.inner.add(from_crs, to_crs, duration_minutes);
         }
         self
     }
@@ -154,7 +326,6 @@ pub fn london_connections() -> WalkableConnections {
         .add("EUS", "KGX", 5) // Euston ↔ King's Cross (same complex)
         .add("KGX", "STP", 3) // King's Cross ↔ St Pancras (adjacent)
         .add("EUS", "STP", 7) // Euston ↔ St Pancras
-        .add("PAD", "PAD", 0) // Paddington (self, for completeness)
         .add("VIC", "VXH", 15) // Victoria ↔ Vauxhall (via Tube or walk)
         .add("WAT", "WLO", 5) // Waterloo ↔ Waterloo East
         .add("CHX", "LST", 20) // Charing Cross ↔ Liverpool Street (via Tube)
@@ -186,7 +357,7 @@ mod tests {
     #[test]
     fn add_and_lookup() {
         let mut wc = WalkableConnections::new();
-        wc.add(crs("EUS"), crs("KGX"), 5);
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
 
         assert!(!wc.is_empty());
         assert_eq!(wc.len(), 1);
@@ -204,7 +375,7 @@ mod tests {
     #[test]
     fn is_walkable() {
         let mut wc = WalkableConnections::new();
-        wc.add(crs("EUS"), crs("KGX"), 5);
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
 
         assert!(wc.is_walkable(&crs("EUS"), &crs("KGX")));
         assert!(wc.is_walkable(&crs("KGX"), &crs("EUS")));
@@ -214,8 +385,8 @@ mod tests {
     #[test]
     fn walkable_from() {
         let mut wc = WalkableConnections::new();
-        wc.add(crs("KGX"), crs("EUS"), 5);
-        wc.add(crs("KGX"), crs("STP"), 3);
+        wc.add(crs("KGX"), crs("EUS"), 5).unwrap();
+        wc.add(crs("KGX"), crs("STP"), 3).unwrap();
 
         let from_kgx = wc.walkable_from(&crs("KGX"));
         assert_eq!(from_kgx.len(), 2);
@@ -268,6 +439,68 @@ mod tests {
         assert_eq!(lookup(&crs("EUS"), &crs("KGX")), Some(Duration::minutes(5)));
         assert!(lookup(&crs("PAD"), &crs("EUS")).is_none());
     }
+
+    #[test]
+    fn set_replaces_duration() {
+        let mut wc = WalkableConnections::new();
+        assert!(wc.set(crs("EUS"), crs("KGX"), 5).unwrap());
+        assert!(!wc.set(crs("KGX"), crs("EUS"), 9).unwrap());
+
+        assert_eq!(wc.len(), 1);
+        assert_eq!(wc.get(&crs("EUS"), &crs("KGX")), Some(Duration::minutes(9)));
+        assert_eq!(wc.get(&crs("KGX"), &crs("EUS")), Some(Duration::minutes(9)));
+        assert!(wc.set(crs("PAD"), crs("PAD"), 1).is_err());
+    }
+
+    #[test]
+    fn remove_both_directions() {
+        let mut wc = WalkableConnections::new();
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
+        wc.add(crs("KGX"), crs("STP"), 3).unwrap();
+
+        assert!(wc.remove(&crs("KGX"), &crs("EUS")));
+        assert!(!wc.remove(&crs("KGX"), &crs("EUS")));
+
+        assert_eq!(wc.len(), 1);
+        assert!(!wc.is_walkable(&crs("EUS"), &crs("KGX")));
+        assert!(!wc.is_walkable(&crs("KGX"), &crs("EUS")));
+        assert!(wc.is_walkable(&crs("KGX"), &crs("STP")));
+    }
+
+    #[test]
+    fn pairs_lists_each_pair_once() {
+        let mut wc = WalkableConnections::new();
+        wc.add(crs("STP"), crs("KGX"), 3).unwrap();
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
+
+        assert_eq!(
+            wc.pairs(),
+            vec![(crs("EUS"), crs("KGX"), 5), (crs("KGX"), crs("STP"), 3)]
+        );
+
+        let mut iterated: Vec<_> = wc.iter().collect();
+        iterated.sort_by_key(|(from, _, _)| from.as_str().to_string());
+        assert_eq!(
+            iterated,
+            vec![
+                (crs("EUS"), crs("KGX"), Duration::minutes(5)),
+                (crs("KGX"), crs("STP"), Duration::minutes(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_latest_policy_replaces_duplicates() {
+        let mut wc = WalkableConnections::new();
+        assert_eq!(wc.duplicate_policy(), DuplicatePolicy::KeepShortest);
+
+        wc = wc.with_duplicate_policy(DuplicatePolicy::KeepLatest);
+        assert!(wc.add(crs("EUS"), crs("KGX"), 5).unwrap());
+        assert!(!wc.add(crs("KGX"), crs("EUS"), 8).unwrap());
+
+        assert_eq!(wc.len(), 1);
+        assert_eq!(wc.get(&crs("EUS"), &crs("KGX")), Some(Duration::minutes(8)));
+    }
 }
 
 /// Tests for fixed behavior that was previously buggy.
@@ -279,18 +512,21 @@ mod fixed_behavior_tests {
         Crs::parse(s).unwrap()
     }
 
-    /// FIXED: Self-connections are now ignored.
+    /// FIXED: Self-connections are now rejected.
     ///
-    /// Walking from a station to itself is meaningless, so add() ignores these.
+    /// Walking from a station to itself is meaningless, so add() refuses these.
     #[test]
     fn self_connections_ignored() {
         let mut wc = WalkableConnections::new();
 
         // Add a normal connection
-        wc.add(crs("EUS"), crs("KGX"), 5);
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
 
-        // Try to add a self-connection - should be ignored
-        wc.add(crs("PAD"), crs("PAD"), 0);
+        // Try to add a self-connection - should be rejected
+        assert_eq!(
+            wc.add(crs("PAD"), crs("PAD"), 0),
+            Err(WalkableError::SelfConnection(crs("PAD")))
+        );
 
         // Only the real connection should exist
         assert_eq!(wc.len(), 1, "Self-connection should be ignored");
@@ -300,7 +536,7 @@ mod fixed_behavior_tests {
 
     /// FIXED: london_connections() len is correct.
     ///
-    /// PAD→PAD is ignored, leaving 12 valid connections.
+    /// There are 12 connections, each counted once.
     #[test]
     fn london_connections_len_correct() {
         let wc = london_connections();
@@ -308,12 +544,13 @@ mod fixed_behavior_tests {
         // Count the actual connections defined in london_connections():
         // EUS↔KGX, KGX↔STP, EUS↔STP, VIC↔VXH, WAT↔WLO,
         // CHX↔LST, CST↔MOG, LST↔MOG, FST↔CST, FST↔LST, LBG↔WAT, LBG↔CST
-        // = 12 pairs (PAD→PAD is ignored as a self-connection)
+        // = 12 pairs
         assert_eq!(
             wc.len(),
             12,
-            "london_connections() should have 12 valid pairs (PAD→PAD ignored)"
+            "london_connections() should have 12 valid pairs"
         );
+        assert_eq!(wc.iter().count(), wc.len());
     }
 
     /// FIXED: Adding same connection twice keeps shorter duration.
@@ -321,8 +558,8 @@ mod fixed_behavior_tests {
     fn duplicate_connection_keeps_shorter() {
         let mut wc = WalkableConnections::new();
 
-        wc.add(crs("EUS"), crs("KGX"), 5);
-        wc.add(crs("EUS"), crs("KGX"), 10); // Longer duration - should be ignored
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
+        wc.add(crs("EUS"), crs("KGX"), 10).unwrap(); // Longer duration - should be ignored
 
         assert_eq!(wc.len(), 1, "Duplicate add should not increase len");
 
@@ -339,8 +576,8 @@ mod fixed_behavior_tests {
     fn duplicate_connection_updates_to_shorter() {
         let mut wc = WalkableConnections::new();
 
-        wc.add(crs("EUS"), crs("KGX"), 10); // Longer first
-        wc.add(crs("EUS"), crs("KGX"), 5); // Shorter second - should update
+        wc.add(crs("EUS"), crs("KGX"), 10).unwrap(); // Longer first
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap(); // Shorter second - should update
 
         assert_eq!(wc.len(), 1, "Duplicate add should not increase len");
 
@@ -351,4 +588,29 @@ mod fixed_behavior_tests {
             "Should update to shorter duration"
         );
     }
+
+    #[test]
+    fn lift_walks_are_dropped_during_outages() {
+        let mut wc = london_connections();
+        let lift = WalkAccess {
+            requires_lift: true,
+            requires_stairs: false,
+        };
+        assert!(wc.set_access(crs("KGX"), crs("STP"), lift));
+        assert!(!wc.set_access(crs("PAD"), crs("STP"), lift));
+
+        let kept = wc.without_lift_walks_at(&BTreeSet::from([crs("STP")]));
+        assert!(!kept.is_walkable(&crs("KGX"), &crs("STP")));
+        assert!(kept.is_walkable(&crs("EUS"), &crs("STP")));
+        assert_eq!(kept.len(), wc.len() - 1);
+
+        // Outages elsewhere leave the walk alone
+        let kept = wc.without_lift_walks_at(&BTreeSet::from([crs("EUS")]));
+        assert_eq!(kept.access(&crs("STP"), &crs("KGX")), lift);
+
+        // Removing a connection forgets its access needs
+        wc.remove(&crs("KGX"), &crs("STP"));
+        wc.add(crs("KGX"), crs("STP"), 3).unwrap();
+        assert!(wc.access(&crs("KGX"), &crs("STP")).is_level());
+    }
 }
@@ -0,0 +1,232 @@
+//! Disk persistence for walkable connections.
+//!
+//! Lets connections edited at runtime survive restarts. The file holds the
+//! complete set, so it replaces the built-in defaults once it exists.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Crs;
+use crate::error::{Classify, ErrorKind};
+
+use super::{WalkAccess, WalkableConnections};
+
+/// Errors reading or writing the walkable connections file.
+#[derive(Debug, thiserror::Error)]
+pub enum WalkableStoreError {
+    /// Reading or writing the file failed
+    #[error("walkable store I/O error: {message}")]
+    Io { message: String },
+
+    /// The file contents were not valid
+    #[error("walkable store format error: {message}")]
+    Format { message: String },
+}
+
+impl Classify for WalkableStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WalkableStoreError::Io { .. } => ErrorKind::Transient,
+            WalkableStoreError::Format { .. } => ErrorKind::Config,
+        }
+    }
+}
+
+/// On-disk representation of the connections.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredConnections {
+    connections: Vec<StoredConnection>,
+}
+
+/// A single stored connection.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredConnection {
+    from: String,
+    to: String,
+    minutes: i64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    requires_lift: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    requires_stairs: bool,
+}
+
+/// JSON file store for walkable connections.
+#[derive(Debug, Clone)]
+pub struct WalkableStore {
+    path: PathBuf,
+}
+
+impl WalkableStore {
+    /// Create a store backed by the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Load connections from the file.
+    ///
+    /// Returns `Ok(None)` if the file doesn't exist yet.
+    pub fn load(&self) -> Result<Option<WalkableConnections>, WalkableStoreError> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(WalkableStoreError::Io {
+                    message: format!("failed to read {}: {}", self.path.display(), e),
+                });
+            }
+        };
+
+        let stored: StoredConnections =
+            serde_json::from_str(&contents).map_err(|e| WalkableStoreError::Format {
+                message: e.to_string(),
+            })?;
+
+        let mut connections = WalkableConnections::new();
+        for c in stored.connections {
+            let from = Crs::parse(&c.from).map_err(|e| WalkableStoreError::Format {
+                message: e.to_string(),
+            })?;
+            let to = Crs::parse(&c.to).map_err(|e| WalkableStoreError::Format {
+                message: e.to_string(),
+            })?;
+            connections
+                .set(from, to, c.minutes)
+                .map_err(|e| WalkableStoreError::Format {
+                    message: e.to_string(),
+                })?;
+            let access = WalkAccess {
+                requires_lift: c.requires_lift,
+                requires_stairs: c.requires_stairs,
+            };
+            connections.set_access(from, to, access);
+        }
+
+        Ok(Some(connections))
+    }
+
+    /// Save connections to the file, replacing its contents.
+    ///
+    /// Writes to a temporary file first so a crash can't leave a truncated
+    /// file behind. Creates parent directories if they don't exist.
+    pub fn save(&self, connections: &WalkableConnections) -> Result<(), WalkableStoreError> {
+        let stored = StoredConnections {
+            connections: connections
+                .pairs()
+                .into_iter()
+                .map(|(from, to, minutes)| {
+                    let access = connections.access(&from, &to);
+                    StoredConnection {
+                        from: from.as_str().to_string(),
+                        to: to.as_str().to_string(),
+                        minutes,
+                        requires_lift: access.requires_lift,
+                        requires_stairs: access.requires_stairs,
+                    }
+                })
+                .collect(),
+        };
+
+        let io_err = |e: std::io::Error| WalkableStoreError::Io {
+            message: format!("failed to write {}: {}", self.path.display(), e),
+        };
+
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent).map_err(io_err)?;
+        }
+
+        let json =
+            serde_json::to_string_pretty(&stored).map_err(|e| WalkableStoreError::Format {
+                message: e.to_string(),
+            })?;
+
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(io_err)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_err)?;
+
+        Ok(())
+    }
+
+    /// Get the store file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[test]
+    fn missing_file_loads_none() {
+        let dir = tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("walkable.json"));
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let dir = tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("nested/walkable.json"));
+
+        let mut wc = WalkableConnections::new();
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
+        wc.add(crs("KGX"), crs("STP"), 3).unwrap();
+        store.save(&wc).unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded.get(&crs("STP"), &crs("KGX")),
+            Some(Duration::minutes(3))
+        );
+    }
+
+    #[test]
+    fn access_needs_are_kept() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("walkable.json");
+        let store = WalkableStore::new(&path);
+
+        let mut wc = WalkableConnections::new();
+        wc.add(crs("EUS"), crs("KGX"), 5).unwrap();
+        wc.add(crs("KGX"), crs("STP"), 3).unwrap();
+        let lift = WalkAccess {
+            requires_lift: true,
+            requires_stairs: false,
+        };
+        wc.set_access(crs("STP"), crs("KGX"), lift);
+        store.save(&wc).unwrap();
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.access(&crs("KGX"), &crs("STP")), lift);
+        assert!(loaded.access(&crs("EUS"), &crs("KGX")).is_level());
+        // Level walks are saved as before
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved.matches("requires_lift").count(), 1);
+        assert!(!saved.contains("requires_stairs"));
+    }
+
+    #[test]
+    fn invalid_file_is_format_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("walkable.json");
+        std::fs::write(
+            &path,
+            r#"{"connections":[{"from":"??","to":"KGX","minutes":5}]}"#,
+        )
+        .unwrap();
+
+        let err = WalkableStore::new(path).load().unwrap_err();
+        assert!(matches!(err, WalkableStoreError::Format { .. }));
+        assert_eq!(err.kind(), ErrorKind::Config);
+    }
+}
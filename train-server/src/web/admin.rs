@@ -0,0 +1,678 @@
+//! Admin API for editing walkable connections at runtime, reporting lifts
+//! out of service, reloading settings without a restart, and reading the
+//! anonymous usage analytics.
+//!
+//! Disabled unless an admin token is configured; every request must carry
+//! it as `Authorization: Bearer <token>`. Edits are persisted to the
+//! walkable store (if configured) before they take effect, so a failed
+//! write leaves the live connections unchanged. Walks too quick for the
+//! distance between their stations are rejected, where both locations are
+//! known. Lift outages are held in memory only, as they're meant to be kept
+//! current by whoever reports them.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use sha2::{Digest, Sha256};
+
+use crate::domain::Crs;
+use crate::walkable::{WalkAccess, WalkableConnections, check_walk};
+
+use super::analytics::{MAX_DAYS, UsageAnalytics};
+use super::dto::{
+    AnalyticsCountsResult, AnalyticsPairResult, AnalyticsPairsResponse, AnalyticsQuery,
+    AnalyticsSummaryResponse, LiftOutagesResponse, ReloadResponse, UpdateWalkableRequest,
+    WalkableConnectionDto, WalkableListResponse,
+};
+use super::reload::{Snapshot, reload};
+use super::routes::{AppError, parse_station};
+use super::state::AppState;
+
+/// Longest walk the admin API accepts, in minutes.
+const MAX_WALK_MINUTES: i64 = 120;
+
+/// Days of analytics added up unless asked otherwise.
+const DEFAULT_ANALYTICS_DAYS: u32 = 7;
+
+/// Station pairs listed unless asked otherwise.
+const DEFAULT_ANALYTICS_PAIRS: usize = 20;
+
+/// Routes for the admin API, relative to the versioned API prefix.
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/walkable", get(list_walkable).post(add_walkable))
+        .route(
+            "/admin/walkable/:from/:to",
+            put(update_walkable).delete(delete_walkable),
+        )
+        .route("/admin/lift-outages", get(list_lift_outages))
+        .route(
+            "/admin/lift-outages/:station",
+            put(report_lift_outage).delete(restore_lift),
+        )
+        .route("/admin/reload", post(reload_settings))
+        .route("/admin/analytics", get(analytics_summary))
+        .route("/admin/analytics/pairs", get(analytics_pairs))
+}
+
+/// Check the request's bearer token against the configured admin token.
+///
+/// Returns 404 when the admin API is disabled, so its existence isn't
+/// advertised, and 401 for a missing or wrong token.
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let expected = state
+        .admin_token
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound {
+            message: "Not found".to_string(),
+        })?;
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized {
+            message: "Missing bearer token".to_string(),
+        })?;
+
+    // Compare digests so the comparison time doesn't depend on how much of
+    // the token matched.
+    if Sha256::digest(provided.as_bytes()) != Sha256::digest(expected.as_bytes()) {
+        return Err(AppError::Unauthorized {
+            message: "Invalid bearer token".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Validate a walking time from a request.
+fn validate_minutes(minutes: i64) -> Result<i64, AppError> {
+    if !(1..=MAX_WALK_MINUTES).contains(&minutes) {
+        return Err(AppError::BadRequest {
+            message: format!("minutes must be between 1 and {MAX_WALK_MINUTES}"),
+        });
+    }
+    Ok(minutes)
+}
+
+/// Check a walking time is believable for the distance between the
+/// stations.
+///
+/// Walks between stations without a known location are allowed.
+async fn check_distance(
+    state: &AppState,
+    from: Crs,
+    to: Crs,
+    minutes: i64,
+) -> Result<(), AppError> {
+    let locations = state.station_names.locations([from, to]).await;
+    if let (Some(a), Some(b)) = (locations.get(&from), locations.get(&to)) {
+        check_walk(from, to, minutes, a.distance_metres(b))?;
+    }
+    Ok(())
+}
+
+/// Parse and validate the two ends of a connection.
+async fn parse_pair(state: &AppState, from: &str, to: &str) -> Result<(Crs, Crs), AppError> {
+    let from = parse_station(state, from, "from").await?;
+    let to = parse_station(state, to, "to").await?;
+    Ok((from, to))
+}
+
+/// Apply an edit to the walkable connections.
+///
+/// The edit is made on a copy, persisted, and only then swapped in. Edits
+/// are made one at a time, but the store is written without holding up
+/// searches reading the settings. An edit that changes nothing isn't
+/// saved, and a rejected edit is neither persisted nor applied.
+async fn edit_walkable<T>(
+    state: &AppState,
+    edit: impl FnOnce(&mut WalkableConnections) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let _writing = state.settings.lock_writes().await;
+    let current = state.settings.snapshot().walkable.clone();
+    let mut updated = (*current).clone();
+    let result = edit(&mut updated)?;
+    if updated == *current {
+        return Ok(result);
+    }
+
+    let updated = Arc::new(updated);
+    if let Some(store) = state.walkable_store.clone() {
+        let saving = updated.clone();
+        tokio::task::spawn_blocking(move || store.save(&saving))
+            .await
+            .map_err(|e| AppError::Internal {
+                message: e.to_string(),
+            })?
+            .map_err(|e| AppError::Internal {
+                message: e.to_string(),
+            })?;
+    }
+
+    state.settings.update(|current| {
+        let next = Snapshot {
+            config: current.config.clone(),
+            walkable: updated,
+        };
+        Ok((next, result))
+    })
+}
+
+fn to_dto(from: Crs, to: Crs, minutes: i64, access: WalkAccess) -> WalkableConnectionDto {
+    WalkableConnectionDto {
+        from: from.as_str().to_string(),
+        to: to.as_str().to_string(),
+        minutes,
+        requires_lift: access.requires_lift,
+        requires_stairs: access.requires_stairs,
+    }
+}
+
+/// List all walkable connections.
+async fn list_walkable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<WalkableListResponse>, AppError> {
+    authorize(&state, &headers)?;
+
+    let walkable = state.settings.snapshot().walkable.clone();
+    let connections = walkable
+        .pairs()
+        .into_iter()
+        .map(|(from, to, minutes)| to_dto(from, to, minutes, walkable.access(&from, &to)))
+        .collect();
+
+    Ok(Json(WalkableListResponse { connections }))
+}
+
+/// Add a walkable connection, or replace the walking time of an existing one.
+///
+/// Returns 201 for a new connection and 200 for a replacement.
+async fn add_walkable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<WalkableConnectionDto>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize(&state, &headers)?;
+    let (from, to) = parse_pair(&state, &req.from, &req.to).await?;
+    let minutes = validate_minutes(req.minutes)?;
+    check_distance(&state, from, to, minutes).await?;
+
+    let access = WalkAccess {
+        requires_lift: req.requires_lift,
+        requires_stairs: req.requires_stairs,
+    };
+
+    let is_new = edit_walkable(&state, |wc| {
+        let is_new = wc.set(from, to, minutes)?;
+        wc.set_access(from, to, access);
+        Ok(is_new)
+    })
+    .await?;
+
+    let status = if is_new {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(to_dto(from, to, minutes, access))))
+}
+
+/// Change the walking time of an existing connection, and its access
+/// needs if given.
+async fn update_walkable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((from, to)): Path<(String, String)>,
+    Json(req): Json<UpdateWalkableRequest>,
+) -> Result<Json<WalkableConnectionDto>, AppError> {
+    authorize(&state, &headers)?;
+    let (from, to) = parse_pair(&state, &from, &to).await?;
+    let minutes = validate_minutes(req.minutes)?;
+    check_distance(&state, from, to, minutes).await?;
+
+    let updated = edit_walkable(&state, |wc| {
+        if !wc.is_walkable(&from, &to) {
+            return Ok(None);
+        }
+        wc.set(from, to, minutes)?;
+        let current = wc.access(&from, &to);
+        let access = WalkAccess {
+            requires_lift: req.requires_lift.unwrap_or(current.requires_lift),
+            requires_stairs: req.requires_stairs.unwrap_or(current.requires_stairs),
+        };
+        wc.set_access(from, to, access);
+        Ok(Some(access))
+    })
+    .await?;
+
+    let Some(access) = updated else {
+        return Err(not_walkable(from, to));
+    };
+    Ok(Json(to_dto(from, to, minutes, access)))
+}
+
+/// Delete a walkable connection (in both directions).
+async fn delete_walkable(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((from, to)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    authorize(&state, &headers)?;
+    let (from, to) = parse_pair(&state, &from, &to).await?;
+
+    if !edit_walkable(&state, |wc| Ok(wc.remove(&from, &to))).await? {
+        return Err(not_walkable(from, to));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the stations whose lifts are reported out of service.
+async fn list_lift_outages(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<LiftOutagesResponse>, AppError> {
+    authorize(&state, &headers)?;
+
+    let stations = state
+        .lift_outages
+        .current()
+        .iter()
+        .map(|crs| crs.as_str().to_string())
+        .collect();
+    Ok(Json(LiftOutagesResponse { stations }))
+}
+
+/// Report a station's lifts out of service.
+///
+/// Returns 201 for a new outage and 200 if it was already reported.
+async fn report_lift_outage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(station): Path<String>,
+) -> Result<StatusCode, AppError> {
+    authorize(&state, &headers)?;
+    let station = parse_station(&state, &station, "station").await?;
+
+    Ok(if state.lift_outages.report(station) {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    })
+}
+
+/// Report a station's lifts back in service.
+async fn restore_lift(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(station): Path<String>,
+) -> Result<StatusCode, AppError> {
+    authorize(&state, &headers)?;
+    let station = parse_station(&state, &station, "station").await?;
+
+    if !state.lift_outages.restore(&station) {
+        return Err(AppError::NotFound {
+            message: format!("No lift outage reported at {}", station.as_str()),
+        });
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reload search configuration, walkable connections and the station cache.
+///
+/// Requests in flight finish with the settings they started with.
+async fn reload_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ReloadResponse>, AppError> {
+    authorize(&state, &headers)?;
+
+    let summary = reload(&state).await.map_err(|e| AppError::Internal {
+        message: e.to_string(),
+    })?;
+
+    Ok(Json(ReloadResponse {
+        config_reloaded: summary.config,
+        walkable_connections: summary.walkable,
+        walkable_rejected: summary.rejected.iter().map(|e| e.to_string()).collect(),
+        stations: summary.stations,
+    }))
+}
+
+/// The usage analytics, or not found if they're off.
+fn analytics(state: &AppState) -> Result<&UsageAnalytics, AppError> {
+    state
+        .analytics
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound {
+            message: "Usage analytics are disabled".to_string(),
+        })
+}
+
+/// The days a query asks to add up, at least one and at most the days kept.
+fn analytics_days(query: &AnalyticsQuery) -> u32 {
+    query
+        .days
+        .unwrap_or(DEFAULT_ANALYTICS_DAYS)
+        .clamp(1, MAX_DAYS)
+}
+
+/// All searches over the last `days` days.
+async fn analytics_summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<AnalyticsSummaryResponse>, AppError> {
+    authorize(&state, &headers)?;
+    let days = analytics_days(&query);
+    let counts = analytics(&state)?.summary(state.clock.now().date(), days);
+
+    Ok(Json(AnalyticsSummaryResponse {
+        days,
+        counts: AnalyticsCountsResult::from_counts(&counts),
+    }))
+}
+
+/// The most searched station pairs over the last `days` days.
+async fn analytics_pairs(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<AnalyticsPairsResponse>, AppError> {
+    authorize(&state, &headers)?;
+    let days = analytics_days(&query);
+    let mut pairs: Vec<_> = analytics(&state)?
+        .pairs(state.clock.now().date(), days)
+        .into_iter()
+        .collect();
+    pairs.sort_by(|((a_from, a_to), a), ((b_from, b_to), b)| {
+        b.searches
+            .cmp(&a.searches)
+            .then_with(|| a_from.as_str().cmp(b_from.as_str()))
+            .then_with(|| a_to.as_str().cmp(b_to.as_str()))
+    });
+    pairs.truncate(query.limit.unwrap_or(DEFAULT_ANALYTICS_PAIRS));
+
+    let pairs = pairs
+        .into_iter()
+        .map(|((origin, destination), counts)| AnalyticsPairResult {
+            origin: origin.as_str().to_string(),
+            destination: destination.as_str().to_string(),
+            counts: AnalyticsCountsResult::from_counts(&counts),
+        })
+        .collect();
+    Ok(Json(AnalyticsPairsResponse { days, pairs }))
+}
+
+fn not_walkable(from: Crs, to: Crs) -> AppError {
+    AppError::NotFound {
+        message: format!(
+            "No walkable connection between {} and {}",
+            from.as_str(),
+            to.as_str()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheConfig, CachedDarwinClient};
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::planner::SearchConfig;
+    use crate::stations::{StationClient, StationClientConfig, StationLocation, StationNames};
+    use crate::walkable::{WalkableStore, london_connections};
+    use axum::http::HeaderValue;
+    use std::collections::HashMap;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn state() -> AppState {
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        )
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn disabled_without_token() {
+        let result = authorize(&state(), &bearer("anything"));
+        assert!(matches!(result, Err(AppError::NotFound { .. })));
+    }
+
+    #[test]
+    fn requires_matching_token() {
+        let state = state().with_admin_token("secret");
+
+        assert!(authorize(&state, &bearer("secret")).is_ok());
+        assert!(matches!(
+            authorize(&state, &bearer("wrong")),
+            Err(AppError::Unauthorized { .. })
+        ));
+        assert!(matches!(
+            authorize(&state, &HeaderMap::new()),
+            Err(AppError::Unauthorized { .. })
+        ));
+    }
+
+    #[test]
+    fn minutes_must_be_sensible() {
+        assert!(validate_minutes(0).is_err());
+        assert!(validate_minutes(-5).is_err());
+        assert!(validate_minutes(MAX_WALK_MINUTES + 1).is_err());
+        assert_eq!(validate_minutes(10).unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn edits_are_persisted_and_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("walkable.json"));
+        let state = state().with_walkable_store(store.clone());
+
+        let is_new = edit_walkable(&state, |wc| Ok(wc.set(crs("PAD"), crs("MYB"), 25)?))
+            .await
+            .unwrap();
+        assert!(is_new);
+        assert!(
+            state
+                .settings
+                .snapshot()
+                .walkable
+                .is_walkable(&crs("MYB"), &crs("PAD"))
+        );
+
+        let removed = edit_walkable(&state, |wc| Ok(wc.remove(&crs("KGX"), &crs("STP"))))
+            .await
+            .unwrap();
+        assert!(removed);
+
+        let saved = store.load().unwrap().unwrap();
+        assert!(saved.is_walkable(&crs("PAD"), &crs("MYB")));
+        assert!(!saved.is_walkable(&crs("KGX"), &crs("STP")));
+        assert_eq!(saved.len(), london_connections().len());
+    }
+
+    #[tokio::test]
+    async fn failed_save_leaves_connections_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory where the file should be makes the write fail
+        let path = dir.path().join("walkable.json");
+        std::fs::create_dir(&path).unwrap();
+        let state = state().with_walkable_store(WalkableStore::new(path));
+
+        let result = edit_walkable(&state, |wc| Ok(wc.set(crs("PAD"), crs("MYB"), 25)?)).await;
+
+        assert!(result.is_err());
+        assert!(
+            !state
+                .settings
+                .snapshot()
+                .walkable
+                .is_walkable(&crs("PAD"), &crs("MYB"))
+        );
+    }
+
+    #[tokio::test]
+    async fn walks_must_fit_the_distance() {
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let locations = [("KGX", 51.5320, -0.1233), ("LST", 51.5178, -0.0817)]
+            .into_iter()
+            .map(|(c, lat, lon)| (crs(c), StationLocation::new(lat, lon).unwrap()))
+            .collect();
+        let names = StationNames::from_known(
+            StationClient::new(StationClientConfig::new("")).unwrap(),
+            HashMap::new(),
+            locations,
+        );
+        let state = AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        );
+
+        let quick = check_distance(&state, crs("KGX"), crs("LST"), 5).await;
+        assert!(matches!(quick, Err(AppError::BadRequest { .. })));
+        assert!(
+            check_distance(&state, crs("KGX"), crs("LST"), 25)
+                .await
+                .is_ok()
+        );
+        // Unknown locations can't be checked
+        assert!(
+            check_distance(&state, crs("KGX"), crs("PAD"), 1)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn lift_outages_are_reported_and_restored() {
+        let state = state().with_admin_token("secret");
+        let station = || Path("kgx".to_string());
+
+        let reported = report_lift_outage(State(state.clone()), bearer("secret"), station()).await;
+        assert_eq!(reported.unwrap(), StatusCode::CREATED);
+        let again = report_lift_outage(State(state.clone()), bearer("secret"), station()).await;
+        assert_eq!(again.unwrap(), StatusCode::OK);
+
+        let listed = list_lift_outages(State(state.clone()), bearer("secret"))
+            .await
+            .unwrap();
+        assert_eq!(listed.0.stations, ["KGX"]);
+
+        let restored = restore_lift(State(state.clone()), bearer("secret"), station()).await;
+        assert_eq!(restored.unwrap(), StatusCode::NO_CONTENT);
+        let missing = restore_lift(State(state.clone()), bearer("secret"), station()).await;
+        assert!(matches!(missing, Err(AppError::NotFound { .. })));
+        assert!(state.lift_outages.current().is_empty());
+    }
+
+    #[tokio::test]
+    async fn self_connections_are_rejected_unsaved() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("walkable.json"));
+        let state = state().with_walkable_store(store.clone());
+
+        let result = edit_walkable(&state, |wc| Ok(wc.set(crs("PAD"), crs("PAD"), 5)?)).await;
+
+        assert!(matches!(result, Err(AppError::BadRequest { .. })));
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn edits_that_change_nothing_are_not_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("walkable.json"));
+        let state = state().with_walkable_store(store.clone());
+
+        let removed = edit_walkable(&state, |wc| Ok(wc.remove(&crs("PAD"), &crs("MYB"))))
+            .await
+            .unwrap();
+
+        assert!(!removed);
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn analytics_list_the_busiest_pairs() {
+        use crate::web::analytics::SearchEvent;
+        use std::time::Duration;
+
+        let state = state().with_admin_token("secret");
+        let today = state.clock.now().date();
+        let analytics = state.analytics.clone().unwrap();
+        for (from, to, changes) in [
+            ("PAD", "BRI", Some(1)),
+            ("KGX", "YRK", Some(0)),
+            ("PAD", "BRI", None),
+        ] {
+            let event = SearchEvent {
+                origin: crs(from),
+                destination: crs(to),
+                changes,
+                latency: Duration::from_millis(400),
+            };
+            analytics.record(today, &event);
+        }
+        let query = || {
+            Query(AnalyticsQuery {
+                days: None,
+                limit: Some(1),
+            })
+        };
+
+        let summary = analytics_summary(State(state.clone()), bearer("secret"), query())
+            .await
+            .unwrap();
+        assert_eq!(summary.0.days, DEFAULT_ANALYTICS_DAYS);
+        assert_eq!(summary.0.counts.searches, 3);
+        assert_eq!(summary.0.counts.mean_latency_ms, Some(400));
+
+        let pairs = analytics_pairs(State(state.clone()), bearer("secret"), query())
+            .await
+            .unwrap();
+        assert_eq!(pairs.0.pairs.len(), 1);
+        assert_eq!(pairs.0.pairs[0].origin, "PAD");
+        assert_eq!(pairs.0.pairs[0].counts.no_journey, 1);
+
+        // Asking for more days than are kept reports those kept
+        let huge = Query(AnalyticsQuery {
+            days: Some(100_000_000),
+            limit: None,
+        });
+        let summary = analytics_summary(State(state.clone()), bearer("secret"), huge)
+            .await
+            .unwrap();
+        assert_eq!(summary.0.days, MAX_DAYS);
+        assert_eq!(summary.0.counts.searches, 3);
+
+        // Turned off, there's nothing to report
+        let state = state.with_analytics(None);
+        let off = analytics_summary(State(state), bearer("secret"), query()).await;
+        assert!(matches!(off, Err(AppError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,414 @@
+//! Anonymous usage analytics for the operator.
+//!
+//! Each journey search is counted by day under the stations it was from and
+//! to, with how many changes the best journey found needed and how long the
+//! search took. Nothing else is kept: no times of day, API keys, sessions,
+//! addresses or trains, and searches are only ever seen added up, so no one
+//! person's travel can be picked out. Requests sending `DNT: 1` or
+//! `Sec-GPC: 1` aren't counted, and `ANALYTICS_ENABLED=false` turns the
+//! whole thing off.
+//!
+//! The admin API summarises the counts over recent days (see
+//! [`super::admin`]); `ANALYTICS_PATH` keeps them across restarts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Crs;
+use crate::error::{Classify, ErrorKind};
+
+/// Days of counts kept; older days are dropped.
+pub(super) const MAX_DAYS: u32 = 90;
+
+/// Cap on the station pairs counted in one day, to bound memory use. Once
+/// reached, known pairs are still counted but no new pairs are added.
+const MAX_PAIRS_PER_DAY: usize = 20_000;
+
+/// Changes counted separately; journeys with more are counted with this.
+const MAX_CHANGES_COUNTED: usize = 3;
+
+/// One journey search, as counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchEvent {
+    /// Where the user was searching from
+    pub origin: Crs,
+
+    /// Where they were going
+    pub destination: Crs,
+
+    /// Changes in the best journey found (None = no journey found)
+    pub changes: Option<usize>,
+
+    /// How long the search took
+    pub latency: Duration,
+}
+
+/// Searches between one pair of stations, added up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairCounts {
+    /// Searches made
+    pub searches: u64,
+
+    /// Searches finding no journey
+    pub no_journey: u64,
+
+    /// Searches whose best journey was direct, or had one, two, or three or
+    /// more changes
+    pub changes: [u64; MAX_CHANGES_COUNTED + 1],
+
+    /// Total time the searches took, in milliseconds
+    pub latency_ms: u64,
+
+    /// Longest a search took, in milliseconds
+    pub max_latency_ms: u64,
+}
+
+impl PairCounts {
+    fn record(&mut self, event: &SearchEvent) {
+        self.searches += 1;
+        match event.changes {
+            Some(changes) => self.changes[changes.min(MAX_CHANGES_COUNTED)] += 1,
+            None => self.no_journey += 1,
+        }
+        let latency_ms = u64::try_from(event.latency.as_millis()).unwrap_or(u64::MAX);
+        self.latency_ms = self.latency_ms.saturating_add(latency_ms);
+        self.max_latency_ms = self.max_latency_ms.max(latency_ms);
+    }
+
+    fn add(&mut self, other: &PairCounts) {
+        self.searches += other.searches;
+        self.no_journey += other.no_journey;
+        for (total, count) in self.changes.iter_mut().zip(other.changes) {
+            *total += count;
+        }
+        self.latency_ms = self.latency_ms.saturating_add(other.latency_ms);
+        self.max_latency_ms = self.max_latency_ms.max(other.max_latency_ms);
+    }
+
+    /// Mean time a search took, in milliseconds (None = no searches).
+    pub fn mean_latency_ms(&self) -> Option<u64> {
+        self.latency_ms.checked_div(self.searches)
+    }
+
+    /// Mean changes in the best journeys found, counting three or more as
+    /// three (None = none found).
+    pub fn mean_changes(&self) -> Option<f64> {
+        let found: u64 = self.changes.iter().sum();
+        let changes: u64 = self
+            .changes
+            .iter()
+            .enumerate()
+            .map(|(n, count)| n as u64 * count)
+            .sum();
+        (found > 0).then(|| changes as f64 / found as f64)
+    }
+}
+
+/// Whether a request asks not to be tracked, by `DNT: 1` or `Sec-GPC: 1`.
+pub fn opted_out(headers: &HeaderMap) -> bool {
+    ["dnt", "sec-gpc"]
+        .iter()
+        .any(|name| headers.get(*name).is_some_and(|v| v.as_bytes() == b"1"))
+}
+
+/// Each day's searches, by station pair.
+type Days = BTreeMap<NaiveDate, HashMap<(Crs, Crs), PairCounts>>;
+
+/// Searches counted by day and station pair.
+#[derive(Debug, Default)]
+pub struct UsageAnalytics {
+    days: RwLock<Days>,
+}
+
+impl UsageAnalytics {
+    /// Create empty analytics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a search made on `date`.
+    pub fn record(&self, date: NaiveDate, event: &SearchEvent) {
+        let mut days = self.days.write().unwrap_or_else(|e| e.into_inner());
+        let pairs = days.entry(date).or_default();
+        let key = (event.origin, event.destination);
+        if !pairs.contains_key(&key) && pairs.len() >= MAX_PAIRS_PER_DAY {
+            return;
+        }
+        pairs.entry(key).or_default().record(event);
+
+        while days.len() > MAX_DAYS as usize {
+            days.pop_first();
+        }
+    }
+
+    /// Each station pair's searches over the `days` days up to and
+    /// including `today`.
+    pub fn pairs(&self, today: NaiveDate, days: u32) -> HashMap<(Crs, Crs), PairCounts> {
+        let since = today
+            .checked_sub_signed(chrono::Duration::days(i64::from(days.max(1)) - 1))
+            .unwrap_or(NaiveDate::MIN);
+        let recorded = self.days.read().unwrap_or_else(|e| e.into_inner());
+        let mut totals: HashMap<(Crs, Crs), PairCounts> = HashMap::new();
+        for pairs in recorded.range(since..=today).map(|(_, pairs)| pairs) {
+            for (key, counts) in pairs {
+                totals.entry(*key).or_default().add(counts);
+            }
+        }
+        totals
+    }
+
+    /// All searches over the `days` days up to and including `today`.
+    pub fn summary(&self, today: NaiveDate, days: u32) -> PairCounts {
+        let mut total = PairCounts::default();
+        for counts in self.pairs(today, days).values() {
+            total.add(counts);
+        }
+        total
+    }
+
+    /// Returns the number of days with searches counted.
+    pub fn len(&self) -> usize {
+        self.days.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true if no searches have been counted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Errors reading or writing the analytics file.
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyticsStoreError {
+    /// Reading or writing the file failed
+    #[error("analytics I/O error: {message}")]
+    Io { message: String },
+
+    /// The file contents were not valid
+    #[error("analytics format error: {message}")]
+    Format { message: String },
+}
+
+impl Classify for AnalyticsStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            AnalyticsStoreError::Io { .. } => ErrorKind::Transient,
+            AnalyticsStoreError::Format { .. } => ErrorKind::Config,
+        }
+    }
+}
+
+/// One day's searches between one pair of stations, as stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCounts {
+    /// ISO date
+    date: String,
+    origin: String,
+    destination: String,
+    #[serde(flatten)]
+    counts: PairCounts,
+}
+
+/// JSON file store for the analytics, so counts build up across restarts.
+#[derive(Debug, Clone)]
+pub struct AnalyticsStore {
+    path: PathBuf,
+}
+
+impl AnalyticsStore {
+    /// Create a store backed by the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The file the counts are kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the counts from the file, or empty analytics if the file doesn't
+    /// exist yet.
+    ///
+    /// Entries naming invalid dates or stations are skipped.
+    pub fn load(&self) -> Result<UsageAnalytics, AnalyticsStoreError> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(UsageAnalytics::new());
+            }
+            Err(e) => {
+                return Err(AnalyticsStoreError::Io {
+                    message: format!("failed to read {}: {}", self.path.display(), e),
+                });
+            }
+        };
+        let stored: Vec<StoredCounts> =
+            serde_json::from_str(&contents).map_err(|e| AnalyticsStoreError::Format {
+                message: e.to_string(),
+            })?;
+
+        let mut days = Days::new();
+        for entry in stored {
+            let (Ok(date), Ok(origin), Ok(destination)) = (
+                entry.date.parse::<NaiveDate>(),
+                Crs::parse(&entry.origin),
+                Crs::parse(&entry.destination),
+            ) else {
+                continue;
+            };
+            days.entry(date)
+                .or_default()
+                .insert((origin, destination), entry.counts);
+        }
+        while days.len() > MAX_DAYS as usize {
+            days.pop_first();
+        }
+        Ok(UsageAnalytics {
+            days: RwLock::new(days),
+        })
+    }
+
+    /// Save the counts to the file, replacing what was there.
+    pub fn save(&self, analytics: &UsageAnalytics) -> Result<(), AnalyticsStoreError> {
+        let stored: Vec<StoredCounts> = {
+            let days = analytics.days.read().unwrap_or_else(|e| e.into_inner());
+            days.iter()
+                .flat_map(|(date, pairs)| {
+                    pairs
+                        .iter()
+                        .map(|((origin, destination), counts)| StoredCounts {
+                            date: date.to_string(),
+                            origin: origin.as_str().to_string(),
+                            destination: destination.as_str().to_string(),
+                            counts: counts.clone(),
+                        })
+                })
+                .collect()
+        };
+        let json = serde_json::to_string(&stored).map_err(|e| AnalyticsStoreError::Format {
+            message: e.to_string(),
+        })?;
+        std::fs::write(&self.path, json).map_err(|e| AnalyticsStoreError::Io {
+            message: format!("failed to write {}: {}", self.path.display(), e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    fn search(from: &str, to: &str, changes: Option<usize>, ms: u64) -> SearchEvent {
+        SearchEvent {
+            origin: crs(from),
+            destination: crs(to),
+            changes,
+            latency: Duration::from_millis(ms),
+        }
+    }
+
+    #[test]
+    fn searches_are_added_up_by_pair_over_recent_days() {
+        let analytics = UsageAnalytics::new();
+        analytics.record(day(1), &search("PAD", "BRI", Some(0), 900));
+        analytics.record(day(10), &search("PAD", "BRI", Some(1), 300));
+        analytics.record(day(10), &search("PAD", "BRI", Some(5), 600));
+        analytics.record(day(11), &search("PAD", "BRI", None, 1200));
+        analytics.record(day(11), &search("KGX", "YRK", Some(0), 100));
+
+        let pairs = analytics.pairs(day(11), 7);
+        assert_eq!(pairs.len(), 2);
+        let pad_bri = &pairs[&(crs("PAD"), crs("BRI"))];
+        assert_eq!(pad_bri.searches, 3);
+        assert_eq!(pad_bri.no_journey, 1);
+        assert_eq!(pad_bri.changes, [0, 1, 0, 1]);
+        assert_eq!(pad_bri.mean_latency_ms(), Some(700));
+        assert_eq!(pad_bri.max_latency_ms, 1200);
+        assert_eq!(pad_bri.mean_changes(), Some(2.0));
+
+        let summary = analytics.summary(day(11), 1);
+        assert_eq!(summary.searches, 2);
+        assert_eq!(analytics.summary(day(11), 30).searches, 5);
+    }
+
+    #[test]
+    fn old_days_are_dropped() {
+        let analytics = UsageAnalytics::new();
+        let start = day(1);
+        for d in 0..(i64::from(MAX_DAYS) + 5) {
+            analytics.record(
+                start + chrono::Duration::days(d),
+                &search("PAD", "BRI", Some(0), 100),
+            );
+        }
+        assert_eq!(analytics.len(), MAX_DAYS as usize);
+    }
+
+    #[test]
+    fn any_number_of_days_can_be_added_up() {
+        let analytics = UsageAnalytics::new();
+        analytics.record(day(11), &search("PAD", "BRI", Some(0), 100));
+
+        assert_eq!(analytics.summary(day(11), u32::MAX).searches, 1);
+        assert_eq!(analytics.summary(NaiveDate::MIN, 100_000_000).searches, 0);
+    }
+
+    #[test]
+    fn requests_can_opt_out() {
+        let mut headers = HeaderMap::new();
+        assert!(!opted_out(&headers));
+        headers.insert("dnt", HeaderValue::from_static("0"));
+        assert!(!opted_out(&headers));
+        headers.insert("sec-gpc", HeaderValue::from_static("1"));
+        assert!(opted_out(&headers));
+    }
+
+    #[test]
+    fn store_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AnalyticsStore::new(dir.path().join("analytics.json"));
+        assert!(store.load().unwrap().is_empty());
+
+        let analytics = UsageAnalytics::new();
+        analytics.record(day(10), &search("PAD", "BRI", Some(1), 300));
+        analytics.record(day(11), &search("KGX", "YRK", None, 100));
+        store.save(&analytics).unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.pairs(day(11), 7), analytics.pairs(day(11), 7));
+
+        // Only counts are stored, nothing about who searched
+        let json = std::fs::read_to_string(store.path()).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let mut keys: Vec<&String> = entry[0].as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "changes",
+                "date",
+                "destination",
+                "latency_ms",
+                "max_latency_ms",
+                "no_journey",
+                "origin",
+                "searches"
+            ]
+        );
+    }
+}
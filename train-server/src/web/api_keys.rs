@@ -0,0 +1,89 @@
+//! API keys the operator has issued.
+//!
+//! Clients send their key in the `x-api-key` header. Any value there still
+//! names an owner for watches and favourites, but only a key on this list
+//! earns what costs the operator something: alert channels, a search queue
+//! slot of its own, and experiment overrides. Keys are kept as digests, so
+//! checking one takes the same time however much of it matched.
+
+use std::collections::HashSet;
+
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+
+use super::watches::api_key;
+
+/// The API keys the server accepts.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    digests: HashSet<[u8; 32]>,
+}
+
+impl ApiKeys {
+    /// Accept no keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept the keys in a comma-separated list, such as the `API_KEYS`
+    /// setting. Blank entries are skipped.
+    pub fn parse(list: &str) -> Self {
+        let mut keys = Self::new();
+        for key in list.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            keys.insert(key);
+        }
+        keys
+    }
+
+    /// Accept `key` too.
+    pub fn insert(&mut self, key: &str) {
+        self.digests.insert(digest(key));
+    }
+
+    /// Whether `key` was issued.
+    pub fn contains(&self, key: &str) -> bool {
+        self.digests.contains(&digest(key))
+    }
+
+    /// Number of keys accepted.
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+
+    /// Whether no keys are accepted.
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// The request's API key, if it sent one that was issued.
+    pub fn authenticate<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        api_key(headers).filter(|key| self.contains(key))
+    }
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::API_KEY_HEADER;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn only_issued_keys_authenticate() {
+        let keys = ApiKeys::parse(" alpha, ,beta ");
+        assert_eq!(keys.len(), 2);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(keys.authenticate(&headers), None);
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("beta"));
+        assert_eq!(keys.authenticate(&headers), Some("beta"));
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("made-up"));
+        assert_eq!(keys.authenticate(&headers), None);
+        assert_eq!(ApiKeys::new().authenticate(&headers), None);
+    }
+}
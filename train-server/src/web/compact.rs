@@ -0,0 +1,294 @@
+//! Compact JSON responses for clients on poor connections.
+//!
+//! With `?compact=true`, JSON responses drop null fields and use short field
+//! names. The compact form is derived from the normal DTOs rather than
+//! defined separately, so the two can't drift apart: every field keeps its
+//! meaning, only its name changes (see [`SHORT_NAMES`]).
+//!
+//! With `?hide_stops=true`, journey legs leave out their intermediate
+//! stops, which are most of a long journey's size.
+
+use axum::{
+    Json,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::time_format::HourCycle;
+
+/// Query parameters selecting the response format.
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    /// Whether to return compact JSON
+    #[serde(default)]
+    pub compact: bool,
+
+    /// Clock for display times (`12` or `24`); see
+    /// [`TimeFormat`](super::TimeFormat)
+    #[serde(default)]
+    pub clock: Option<HourCycle>,
+
+    /// Whether to leave out legs' intermediate stops
+    #[serde(default)]
+    pub hide_stops: bool,
+}
+
+/// Field names shortened in compact responses. Fields not listed (e.g.
+/// `crs`, `id`, `type`) are already short and keep their names.
+pub const SHORT_NAMES: &[(&str, &str)] = &[
+    ("access_warnings", "aw"),
+    ("alight", "alt"),
+    ("alighting", "al"),
+    ("alternative_endings", "ae"),
+    ("apple_maps", "am"),
+    ("archived_datetime", "ard"),
+    ("arrival_datetime", "adt"),
+    ("arrival_delay_mins", "adm"),
+    ("arrival_time", "at"),
+    ("board_station", "bs"),
+    ("calls", "cl"),
+    ("cancel_reason", "cr"),
+    ("changes", "ch"),
+    ("closure_warnings", "cw"),
+    ("confidence", "cf"),
+    ("continuation_hint", "chn"),
+    ("data_quality", "dq"),
+    ("datetime", "dtm"),
+    ("delay_reason", "dr"),
+    ("departure_datetime", "ddt"),
+    ("departure_time", "dt"),
+    ("destination", "dst"),
+    ("door_side", "ds"),
+    ("duration_from_now_mins", "dfn"),
+    ("duration_mins", "dm"),
+    ("expected_arrival", "ea"),
+    ("expected_arrival_datetime", "ead"),
+    ("expected_departure", "ed"),
+    ("expected_departure_datetime", "edd"),
+    ("final_destination", "fd"),
+    ("first_portion", "fp"),
+    ("from", "fr"),
+    ("google_maps", "gm"),
+    ("headcode", "hc"),
+    ("index", "i"),
+    ("is_cancelled", "cx"),
+    ("journey_id", "jid"),
+    ("journeys", "js"),
+    ("messages", "msg"),
+    ("name", "n"),
+    ("navigation", "nv"),
+    ("near_misses", "nm"),
+    ("next_calls", "nc"),
+    ("next_platform", "np"),
+    ("next_refresh", "nr"),
+    ("next_refresh_in_secs", "nrs"),
+    ("operator", "op"),
+    ("origin", "org"),
+    ("platform", "pl"),
+    ("position", "pos"),
+    ("price_band", "pb"),
+    ("rank", "rk"),
+    ("rank_reasons", "rr"),
+    ("routeing_warnings", "rw"),
+    ("routes_explored", "rx"),
+    ("runs_every_mins", "ev"),
+    ("scheduled_arrival", "sa"),
+    ("scheduled_arrival_datetime", "sad"),
+    ("scheduled_datetime", "sdt"),
+    ("scheduled_departure", "sd"),
+    ("scheduled_departure_datetime", "sdd"),
+    ("scheduled_time", "st"),
+    ("segments", "sg"),
+    ("service_id", "sid"),
+    ("services", "sv"),
+    ("station_messages", "sm"),
+    ("stations", "stn"),
+    ("stops", "sp"),
+    ("time", "t"),
+    ("truncated_stations", "ts"),
+    ("unavailable_stations", "us"),
+    ("version", "v"),
+    ("walk", "wk"),
+    ("warnings", "wn"),
+    ("watches", "ws"),
+];
+
+fn short_name(key: &str) -> Option<&'static str> {
+    SHORT_NAMES
+        .binary_search_by(|(long, _)| (*long).cmp(key))
+        .ok()
+        .map(|i| SHORT_NAMES[i].1)
+}
+
+/// Convert a JSON value to its compact form.
+pub fn compact_value(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let compacted: Map<String, Value> = map
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| {
+                    let key = short_name(&k).map(str::to_string).unwrap_or(k);
+                    (key, compact_value(v))
+                })
+                .collect();
+            Value::Object(compacted)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(compact_value).collect()),
+        other => other,
+    }
+}
+
+/// Remove journey legs' intermediate stops from a JSON value.
+pub fn without_stops(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(k, _)| k != "stops")
+                .map(|(k, v)| (k, without_stops(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_stops).collect()),
+        other => other,
+    }
+}
+
+/// Serialize a DTO as a JSON response, without stops and compacted if
+/// requested.
+pub fn json_response<T: Serialize>(dto: &T, format: &FormatQuery) -> Response {
+    if (format.compact || format.hide_stops)
+        && let Ok(mut value) = serde_json::to_value(dto)
+    {
+        if format.hide_stops {
+            value = without_stops(value);
+        }
+        if format.compact {
+            value = compact_value(value);
+        }
+        return Json(value).into_response();
+    }
+    // Serialization failures surface from the normal path as a 500
+    Json(dto).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn short_names_are_sorted_and_unambiguous() {
+        assert!(SHORT_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let mut shorts: Vec<_> = SHORT_NAMES.iter().map(|(_, s)| *s).collect();
+        shorts.sort_unstable();
+        shorts.dedup();
+        assert_eq!(shorts.len(), SHORT_NAMES.len());
+
+        // A short name must not be mistaken for a long name
+        for short in shorts {
+            assert!(short_name(short).is_none(), "{short} is also a long name");
+        }
+    }
+
+    #[test]
+    fn compacts_nested_values() {
+        let full = json!({
+            "services": [{
+                "service_id": "abc",
+                "headcode": null,
+                "platform": "4",
+                "calls": [{"crs": "KGX", "name": "London Kings Cross", "platform": null}],
+            }],
+        });
+
+        let compact = compact_value(full);
+
+        assert_eq!(
+            compact,
+            json!({
+                "sv": [{
+                    "sid": "abc",
+                    "pl": "4",
+                    "cl": [{"crs": "KGX", "n": "London Kings Cross"}],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn compact_flag_defaults_off() {
+        let format: FormatQuery = serde_json::from_value(json!({})).unwrap();
+        assert!(!format.compact);
+        assert!(!format.hide_stops);
+    }
+
+    #[test]
+    fn hides_stops_at_any_depth() {
+        let full = json!({
+            "journeys": [{
+                "segments": [
+                    {"type": "Train", "origin": {"crs": "PAD"}, "stops": [{"crs": "RDG"}]},
+                    {"type": "Walk", "from": {"crs": "BRI"}},
+                ],
+            }],
+        });
+
+        assert_eq!(
+            without_stops(full),
+            json!({
+                "journeys": [{
+                    "segments": [
+                        {"type": "Train", "origin": {"crs": "PAD"}},
+                        {"type": "Walk", "from": {"crs": "BRI"}},
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn app_compresses_and_compacts() {
+        use crate::cache::{CacheConfig, CachedDarwinClient};
+        use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+        use crate::planner::SearchConfig;
+        use crate::stations::{StationClient, StationClientConfig, StationNames};
+        use crate::walkable::london_connections;
+        use crate::web::{AppState, create_router};
+
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        let state = AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = create_router(state, "static");
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let client = reqwest::Client::new();
+
+        let about = client
+            .get(format!("http://{addr}/about"))
+            .header("accept-encoding", "gzip")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(about.headers()["content-encoding"], "gzip");
+
+        let stations: Value = client
+            .get(format!(
+                "http://{addr}/api/v1/stations/search?q=x&compact=true"
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(stations, json!({"stn": []}));
+    }
+}
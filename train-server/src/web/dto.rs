@@ -1,8 +1,40 @@
 //! Data transfer objects for web requests and responses.
+//!
+//! These types are the JSON API's schema, and change only in ways existing
+//! clients can ignore:
+//!
+//! - New response fields are optional: left out when empty or unknown, so
+//!   clients must ignore fields they don't know and treat missing ones as
+//!   empty.
+//! - Enums that may grow, such as [`SegmentResult`] and [`WarningKind`],
+//!   are `#[non_exhaustive]`; clients should skip values they don't
+//!   recognise (e.g. a future `"Bus"` segment) rather than fail.
+//! - Request fields keep their old names as aliases if renamed, and also
+//!   accept their compact names (see [`super::compact`]), so IDs from a
+//!   compact response can be sent straight back.
+//!
+//! The `contract_tests` below pin the serialized shapes.
+
+use std::collections::{BTreeSet, HashMap};
 
 use serde::{Deserialize, Serialize};
 
-use crate::domain::{Journey, Leg, RailTime, Segment, Service, Walk};
+use crate::alighting::{StationMetadata, StationMetadataTable};
+use crate::darwin::{ReasonTranslation, ReferenceTables};
+use crate::domain::{Call, Change, Crs, Ending, Journey, Leg, RailTime, Segment, Service, Walk};
+use crate::fares::PriceBand;
+use crate::identify::TrainMatch;
+use crate::notify::Channel;
+use crate::planner::{
+    ContinuationHint, DataQuality, DominanceCriteria, NearMiss, PerformanceHistory, PhaseEstimate,
+    RankReason, RouteingRules, SearchConfig, SearchEstimate, assess_confidence_with_history,
+};
+use crate::stations::{OpeningHours, PlatformTopology, StationLocation, closed_walk_stations};
+use crate::walkable::{WalkableConnections, access_warnings};
+
+use super::analytics::PairCounts;
+use super::journey_archive::{ArchivedCall, ArchivedJourney};
+use super::time_format::{TimeFormat, iso_datetime};
 
 /// Request to search stations by name or CRS code.
 #[derive(Debug, Deserialize)]
@@ -45,6 +77,47 @@ pub struct SearchServiceRequest {
 
     /// Optional headcode to search for (e.g., "1A23")
     pub headcode: Option<String>,
+
+    /// Only these platforms, comma-separated (e.g. "1,2,3")
+    pub platform: Option<String>,
+
+    /// Only this operator, by ATOC code or part of its name
+    pub operator: Option<String>,
+
+    /// Only trains whose destination name contains this
+    pub towards: Option<String>,
+}
+
+/// Default window for reachable departures, in minutes.
+pub const DEFAULT_BOARD_WINDOW_MINS: i64 = 60;
+
+/// Longest window for reachable departures, in minutes (Darwin boards
+/// reach two hours ahead).
+pub const MAX_BOARD_WINDOW_MINS: i64 = 120;
+
+/// Request for the departures from a station that reach a destination.
+#[derive(Debug, Deserialize)]
+pub struct BoardQuery {
+    /// Station to board at (CRS code or name)
+    pub station: String,
+
+    /// Destination (CRS code or name)
+    pub destination: String,
+
+    /// How far ahead to look, in minutes (defaults to
+    /// [`DEFAULT_BOARD_WINDOW_MINS`])
+    pub window_mins: Option<i64>,
+
+    /// Maximum number of changes (0 to [`MAX_CHANGES_CAP`])
+    pub max_changes: Option<usize>,
+}
+
+/// Query parameters for long-polling journey changes.
+#[derive(Debug, Deserialize)]
+pub struct JourneyChangesQuery {
+    /// Version the client already has (0 for none)
+    #[serde(default)]
+    pub since: u64,
 }
 
 /// Request to identify the user's current train.
@@ -55,6 +128,20 @@ pub struct IdentifyTrainWebRequest {
 
     /// Final destination of the train (optional).
     pub terminus: Option<String>,
+
+    /// Headcode, if visible (optional; used for scoring).
+    pub headcode: Option<String>,
+
+    /// When the train is due at the next station, HH:MM (optional; used
+    /// for scoring).
+    pub time: Option<String>,
+
+    /// Platform at the next station (optional; used for scoring).
+    pub platform: Option<String>,
+
+    /// Comma-separated stations the train calls at (optional; used for
+    /// scoring).
+    pub calling_at: Option<String>,
 }
 
 /// A service in search results.
@@ -63,6 +150,10 @@ pub struct ServiceResult {
     /// Darwin service ID (ephemeral)
     pub service_id: String,
 
+    /// Station whose board the service was found on (differs between
+    /// services on a station group's merged board)
+    pub board_station: String,
+
     /// Headcode (e.g., "1A23")
     pub headcode: Option<String>,
 
@@ -72,18 +163,32 @@ pub struct ServiceResult {
     /// Destination name
     pub destination: String,
 
-    /// Scheduled departure time
+    /// Scheduled departure time, for display
     pub scheduled_departure: String,
 
-    /// Expected departure time (may differ from scheduled)
+    /// Scheduled departure as an ISO 8601 datetime
+    pub scheduled_departure_datetime: Option<String>,
+
+    /// Expected departure time (may differ from scheduled), for display
     pub expected_departure: Option<String>,
 
+    /// Expected departure as an ISO 8601 datetime
+    pub expected_departure_datetime: Option<String>,
+
     /// Platform number
     pub platform: Option<String>,
 
     /// Whether the service is cancelled
     pub is_cancelled: bool,
 
+    /// Why the train is late here, in the request's language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_reason: Option<String>,
+
+    /// Why the train is cancelled here, in the request's language
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+
     /// Calling points
     pub calls: Vec<CallResult>,
 }
@@ -97,18 +202,30 @@ pub struct CallResult {
     /// Station name
     pub name: String,
 
-    /// Scheduled arrival time
+    /// Scheduled arrival time, for display
     pub scheduled_arrival: Option<String>,
 
-    /// Scheduled departure time
+    /// Scheduled arrival as an ISO 8601 datetime
+    pub scheduled_arrival_datetime: Option<String>,
+
+    /// Scheduled departure time, for display
     pub scheduled_departure: Option<String>,
 
-    /// Expected arrival time
+    /// Scheduled departure as an ISO 8601 datetime
+    pub scheduled_departure_datetime: Option<String>,
+
+    /// Expected arrival time, for display
     pub expected_arrival: Option<String>,
 
-    /// Expected departure time
+    /// Expected arrival as an ISO 8601 datetime
+    pub expected_arrival_datetime: Option<String>,
+
+    /// Expected departure time, for display
     pub expected_departure: Option<String>,
 
+    /// Expected departure as an ISO 8601 datetime
+    pub expected_departure_datetime: Option<String>,
+
     /// Platform
     pub platform: Option<String>,
 
@@ -124,46 +241,530 @@ pub struct CallResult {
 pub struct SearchServiceResponse {
     /// Matching services
     pub services: Vec<ServiceResult>,
+
+    /// Station messages on the board, as plain text
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<String>,
+}
+
+/// A train matched by identification.
+#[derive(Debug, Serialize)]
+pub struct IdentifyMatchResult {
+    /// The matched service
+    #[serde(flatten)]
+    pub service: ServiceResult,
+
+    /// The next few calling points after the board station
+    pub next_calls: Vec<CallResult>,
+
+    /// Final destination as shown on the board
+    pub final_destination: String,
+
+    /// Final destination CRS code, if known
+    pub final_destination_crs: Option<String>,
+
+    /// How well the train fits the headcode, time, platform and calling
+    /// points given, as a percentage (0 if none were given)
+    pub score: u8,
+
+    /// Why the train scored as it did, one line per observation checked
+    pub reasons: Vec<String>,
+}
+
+impl SearchServiceResponse {
+    /// Each station in the response, by CRS code, with its display name.
+    pub fn station_labels(&mut self) -> Vec<StationLabel<'_>> {
+        self.services
+            .iter_mut()
+            .flat_map(ServiceResult::station_labels)
+            .collect()
+    }
+}
+
+impl IdentifyTrainResponse {
+    /// Each station in the response, by CRS code, with its display name.
+    pub fn station_labels(&mut self) -> Vec<StationLabel<'_>> {
+        let mut labels = Vec::new();
+        for found in &mut self.services {
+            labels.extend(found.service.station_labels());
+            labels.extend(found.next_calls.iter_mut().map(CallResult::label));
+        }
+        labels
+    }
+}
+
+impl BoardResponse {
+    /// Each station in the response, by CRS code, with its display name.
+    pub fn station_labels(&mut self) -> Vec<StationLabel<'_>> {
+        let mut labels = Vec::new();
+        for departure in &mut self.departures {
+            labels.extend(departure.service.station_labels());
+            if let Some(journey) = &mut departure.journey {
+                labels.extend(journey.station_labels());
+            }
+        }
+        labels
+    }
+}
+
+impl IdentifyMatchResult {
+    /// Create from an identification match.
+    pub fn from_match(m: &TrainMatch, times: &TimeFormat) -> Self {
+        let calls = &m.service.service.calls;
+        Self {
+            service: ServiceResult::from_service(&m.service.service, times),
+            next_calls: m
+                .context
+                .next_calls
+                .iter()
+                .filter_map(|idx| {
+                    calls
+                        .get(idx.0)
+                        .map(|c| CallResult::from_call(c, idx.0, times))
+                })
+                .collect(),
+            final_destination: m.context.destination.clone(),
+            final_destination_crs: m.context.destination_crs.map(|c| c.as_str().to_string()),
+            score: m.score.score,
+            reasons: m.score.reasons.iter().map(|r| r.message.clone()).collect(),
+        }
+    }
+}
+
+/// A train leaving a station, and how it reaches the destination.
+#[derive(Debug, Serialize)]
+pub struct BoardDepartureResult {
+    /// The departing service
+    #[serde(flatten)]
+    pub service: ServiceResult,
+
+    /// The board station's position in the calls (for planning requests)
+    pub position: usize,
+
+    /// Whether this train gets to the destination
+    pub reaches_destination: bool,
+
+    /// Whether the search from this train failed, so `reaches_destination`
+    /// isn't known
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub unavailable: bool,
+
+    /// Position among the trains reaching the destination, best (0) first,
+    /// counting the wait for each train
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+
+    /// The best journey starting on this train
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub journey: Option<JourneyResult>,
+}
+
+/// Response for reachable departures.
+#[derive(Debug, Serialize)]
+pub struct BoardResponse {
+    /// Departures in board order, including those that don't reach the
+    /// destination
+    pub departures: Vec<BoardDepartureResult>,
+
+    /// Number of routes explored
+    pub routes_explored: usize,
+
+    /// Station messages on the board, as plain text
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<String>,
+
+    /// Stations whose data was unavailable, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_quality: Option<DataQualityResult>,
+}
+
+/// Response for train identification.
+#[derive(Debug, Serialize)]
+pub struct IdentifyTrainResponse {
+    /// Matching services, best first
+    pub services: Vec<IdentifyMatchResult>,
 }
 
 /// Request to plan a journey.
 #[derive(Debug, Deserialize)]
 pub struct PlanJourneyRequest {
     /// Darwin service ID of the current train
+    #[serde(alias = "sid")]
     pub service_id: String,
 
     /// Current position index in the service
+    #[serde(alias = "pos")]
     pub position: usize,
 
     /// Destination station CRS code
+    #[serde(alias = "dst")]
     pub destination: String,
 
     /// Station where the service was found (board station from identification)
+    #[serde(alias = "bs")]
     pub board_station: String,
+
+    /// Whether the user is waiting at `position` to board the train, rather
+    /// than already on it
+    #[serde(default)]
+    pub boarding: bool,
+
+    /// Per-request changes to the search configuration
+    #[serde(flatten)]
+    pub overrides: SearchOverrides,
+}
+
+/// Largest `max_changes` a request may ask for.
+pub const MAX_CHANGES_CAP: usize = 3;
+
+/// Largest `max_results` a request may ask for.
+pub const MAX_RESULTS_CAP: usize = 20;
+
+/// Largest `max_walk_mins` a request may ask for.
+pub const MAX_WALK_MINS_CAP: i64 = 30;
+
+/// Largest `min_connection_mins` a request may ask for.
+pub const MIN_CONNECTION_MINS_CAP: i64 = 30;
+
+/// Largest `price_weight_mins` a request may ask for.
+pub const PRICE_WEIGHT_MINS_CAP: i64 = 60;
+
+/// Search settings a request may override. Unset fields keep the server's
+/// configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SearchOverrides {
+    /// Maximum number of changes (0 to [`MAX_CHANGES_CAP`])
+    #[serde(default)]
+    pub max_changes: Option<usize>,
+
+    /// Maximum number of journeys to return (1 to [`MAX_RESULTS_CAP`])
+    #[serde(default)]
+    pub max_results: Option<usize>,
+
+    /// Longest walk between stations, in minutes (0 to [`MAX_WALK_MINS_CAP`])
+    #[serde(default)]
+    pub max_walk_mins: Option<i64>,
+
+    /// Shortest connection, in minutes (0 to [`MIN_CONNECTION_MINS_CAP`])
+    #[serde(default)]
+    pub min_connection_mins: Option<i64>,
+
+    /// Minutes of journey time worth one price band, for ranking cheaper
+    /// journeys higher (0 to [`PRICE_WEIGHT_MINS_CAP`])
+    #[serde(default)]
+    pub price_weight_mins: Option<i64>,
+
+    /// Leave out walks needing a lift that's out of service, rather than
+    /// warn about them
+    #[serde(default)]
+    pub avoid_lift_outages: Option<bool>,
+
+    /// What, besides arrival time, a journey must be no worse on than
+    /// another to hide it, comma-separated from `changes`, `duration`,
+    /// `walking` and `price`; e.g. add `walking` to keep journeys that
+    /// only walk less
+    #[serde(default)]
+    pub dominance: Option<String>,
+}
+
+impl SearchOverrides {
+    /// Check every override is within the server's caps.
+    ///
+    /// Returns a message naming the first field out of range.
+    pub fn validate(&self) -> Result<(), String> {
+        fn check<T: PartialOrd + std::fmt::Display>(
+            field: &str,
+            value: Option<T>,
+            min: T,
+            max: T,
+        ) -> Result<(), String> {
+            match value {
+                Some(v) if v < min || v > max => {
+                    Err(format!("{field} must be between {min} and {max}, got {v}"))
+                }
+                _ => Ok(()),
+            }
+        }
+        check("max_changes", self.max_changes, 0, MAX_CHANGES_CAP)?;
+        check("max_results", self.max_results, 1, MAX_RESULTS_CAP)?;
+        check("max_walk_mins", self.max_walk_mins, 0, MAX_WALK_MINS_CAP)?;
+        check(
+            "min_connection_mins",
+            self.min_connection_mins,
+            0,
+            MIN_CONNECTION_MINS_CAP,
+        )?;
+        check(
+            "price_weight_mins",
+            self.price_weight_mins,
+            0,
+            PRICE_WEIGHT_MINS_CAP,
+        )?;
+        if let Some(dominance) = &self.dominance {
+            dominance
+                .parse::<DominanceCriteria>()
+                .map_err(|e| format!("dominance: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the overrides to a configuration.
+    ///
+    /// Call [`validate`](Self::validate) first; values are clamped to the
+    /// caps regardless.
+    pub fn apply(&self, config: &SearchConfig) -> SearchConfig {
+        let mut config = config.clone();
+        if let Some(max_changes) = self.max_changes {
+            config.max_changes = max_changes.min(MAX_CHANGES_CAP);
+        }
+        if let Some(max_results) = self.max_results {
+            config.max_results = max_results.clamp(1, MAX_RESULTS_CAP);
+        }
+        if let Some(max_walk_mins) = self.max_walk_mins {
+            config.max_walk_mins = max_walk_mins.clamp(0, MAX_WALK_MINS_CAP);
+        }
+        if let Some(min_connection_mins) = self.min_connection_mins {
+            config.min_connection_mins = min_connection_mins.clamp(0, MIN_CONNECTION_MINS_CAP);
+        }
+        if let Some(price_weight_mins) = self.price_weight_mins {
+            config.price_weight_mins = price_weight_mins.clamp(0, PRICE_WEIGHT_MINS_CAP);
+        }
+        if let Some(avoid) = self.avoid_lift_outages {
+            config.avoid_lift_outages = avoid;
+        }
+        if let Some(Ok(dominance)) = self.dominance.as_deref().map(str::parse) {
+            config.dominance = dominance;
+        }
+        config
+    }
 }
 
 /// A journey option.
 #[derive(Debug, Serialize)]
 pub struct JourneyResult {
+    /// ID for follow-up requests (e.g. GeoJSON export), if the journey was stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
     /// Journey segments
     pub segments: Vec<SegmentResult>,
 
-    /// Departure time from origin
+    /// Departure time from origin, for display
     pub departure_time: String,
 
-    /// Arrival time at destination
+    /// Departure from origin as an ISO 8601 datetime
+    pub departure_datetime: String,
+
+    /// Arrival time at destination, for display
     pub arrival_time: String,
 
+    /// Arrival at destination as an ISO 8601 datetime
+    pub arrival_datetime: String,
+
     /// Total duration in minutes
     pub duration_mins: i64,
 
+    /// Minutes from the time of the request until arrival, including the
+    /// wait for the first train
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_from_now_mins: Option<i64>,
+
     /// Number of changes
     pub changes: usize,
+
+    /// Typical minutes between equivalent departures, if this journey is on
+    /// a turn-up-and-go service and stands in for the later ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runs_every_mins: Option<i64>,
+
+    /// Reasons a guard might reject this route (see `RouteingRules`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub routeing_warnings: Vec<String>,
+
+    /// Stations a walk needs while they are closed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub closure_warnings: Vec<String>,
+
+    /// Walks that need a lift (especially one out of service) or have
+    /// stairs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub access_warnings: Vec<String>,
+
+    /// Messages posted for the stations the journey changes at
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub station_messages: Vec<String>,
+
+    /// Every warning above, labelled with its kind, in the order routeing,
+    /// closure, access, station messages. New kinds of warning appear only
+    /// here
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<WarningResult>,
+
+    /// How far the journey can be relied on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<ConfidenceResult>,
+
+    /// Likely fare: "low", "medium" or "high"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_band: Option<&'static str>,
+
+    /// What the journey does better than the others returned with it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rank_reasons: Vec<RankReasonResult>,
+
+    /// If this is only the first portion of a journey, where and when the
+    /// rest will be planned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continuation_hint: Option<ContinuationHintResult>,
+
+    /// Other ways to finish on the same trains at another station of the
+    /// destination's group, earliest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternative_endings: Vec<EndingResult>,
+}
+
+/// Another way to finish a journey on the same trains.
+#[derive(Debug, Serialize)]
+pub struct EndingResult {
+    /// Where to leave the last train instead, with its arrival there
+    pub alight: StationInfo,
+
+    /// The walk from there, if it isn't the destination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub walk: Option<WalkResult>,
+
+    /// Arrival at the destination, for display
+    pub arrival_time: String,
+
+    /// Arrival at the destination as an ISO 8601 datetime
+    pub arrival_datetime: String,
+
+    /// e.g. "Or get off at Farringdon, arriving 10:06"
+    pub message: String,
+}
+
+impl EndingResult {
+    /// Create from a domain Ending.
+    pub fn from_ending(ending: &Ending, times: &TimeFormat) -> Self {
+        let call = ending.leg.alight_call();
+        Self {
+            alight: StationInfo::at_call(call, call.booked_arrival, call.expected_arrival(), times),
+            walk: ending.walk.as_ref().map(WalkResult::from_walk),
+            arrival_time: times.display(ending.arrival_time()),
+            arrival_datetime: iso_datetime(ending.arrival_time()),
+            message: ending_message(ending, times),
+        }
+    }
 }
 
-/// A segment of a journey.
+/// Where and when the rest of a journey will be planned.
+#[derive(Debug, Serialize)]
+pub struct ContinuationHintResult {
+    /// CRS code of the station to plan on from
+    pub station: String,
+
+    /// CRS code of the final destination
+    pub destination: String,
+
+    /// Earliest departure to plan on with, for display
+    pub after: String,
+
+    /// Earliest departure as an ISO 8601 datetime
+    pub after_datetime: String,
+
+    /// When the rest can be planned, as an ISO 8601 datetime; watching the
+    /// journey plans it then
+    pub opens_at_datetime: String,
+
+    /// e.g. "Plan the rest from Taunton after 12:35"
+    pub message: String,
+}
+
+impl ContinuationHintResult {
+    /// Create from a hint for continuing `journey`, its first portion.
+    pub fn from_hint(hint: &ContinuationHint, journey: &Journey, times: &TimeFormat) -> Self {
+        let station = journey
+            .legs()
+            .last()
+            .filter(|leg| leg.alight_station() == &hint.station)
+            .map_or(hint.station.as_str(), Leg::alight_station_name);
+        Self {
+            station: hint.station.as_str().to_string(),
+            destination: hint.destination.as_str().to_string(),
+            after: times.display(hint.after),
+            after_datetime: iso_datetime(hint.after),
+            opens_at_datetime: iso_datetime(hint.opens_at),
+            message: format!(
+                "Plan the rest from {station} after {}",
+                times.display(hint.after)
+            ),
+        }
+    }
+}
+
+/// What a journey warning is about.
+///
+/// More kinds may be added; clients should show warnings of kinds they
+/// don't know with their message as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A guard might reject the route (see `RouteingRules`)
+    Routeing,
+
+    /// A walk needs a station while it's closed
+    Closure,
+
+    /// A walk needs a lift or has stairs
+    Access,
+
+    /// A message posted for a station the journey changes at
+    StationMessage,
+}
+
+/// A warning about a journey.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WarningResult {
+    /// What it's about
+    pub kind: WarningKind,
+
+    /// Text to show
+    pub message: String,
+}
+
+/// Something a journey does better than the others, for clients to label
+/// journey cards.
+#[derive(Debug, Serialize)]
+pub struct RankReasonResult {
+    /// "only_option", "fastest", "fewest_changes" or "least_walking"
+    pub reason: &'static str,
+
+    /// Label to show, e.g. "Fewest changes"
+    pub label: &'static str,
+}
+
+/// How far a journey can be relied on, for clients to colour-code options.
+#[derive(Debug, Serialize)]
+pub struct ConfidenceResult {
+    /// "high", "medium" or "low"
+    pub level: &'static str,
+
+    /// What lowered it, in journey order (empty if high)
+    pub reasons: Vec<String>,
+}
+
+/// A segment of a journey, tagged by `type`.
+///
+/// More types of segment (e.g. `"Bus"` for rail replacement) may be added;
+/// clients should show what they can of a segment whose type they don't
+/// know, such as its `from`/`origin` and `to`/`destination`, and not fail.
+// Segments are built to be serialized straight away, so a leg's size
+// doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
+#[non_exhaustive]
 pub enum SegmentResult {
     Train(LegResult),
     Walk(WalkResult),
@@ -184,8 +785,109 @@ pub struct LegResult {
     /// Destination station
     pub destination: StationInfo,
 
-    /// Intermediate stops
+    /// Calls between the origin and destination, with their booked and
+    /// expected times. Left out with `?hide_stops=true`
     pub stops: Vec<StationInfo>,
+
+    /// Where to alight for the next connection, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alighting: Option<AlightingInfo>,
+
+    /// Where to sit, if the train divides before this leg ends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boarding: Option<BoardingInfo>,
+
+    /// How to get to the next train, if this leg ends in a change within
+    /// the station and both platforms are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_platform: Option<PlatformChangeInfo>,
+
+    /// Why the train is running late, when Darwin says
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_reason: Option<String>,
+
+    /// Why the train is cancelled, when Darwin says
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+}
+
+/// Advice on where to alight for a change.
+#[derive(Debug, Serialize)]
+pub struct AlightingInfo {
+    /// Part of the train nearest the exit ("front", "middle" or "rear")
+    pub position: Option<&'static str>,
+
+    /// Side the doors open on, facing forwards ("left" or "right")
+    pub door_side: Option<&'static str>,
+
+    /// Human-readable hint
+    pub hint: String,
+}
+
+impl AlightingInfo {
+    /// Create from station metadata, if it says anything.
+    pub fn from_metadata(metadata: &StationMetadata) -> Option<Self> {
+        Some(Self {
+            position: metadata.exit.map(|p| p.as_str()),
+            door_side: metadata.door_side.map(|s| s.as_str()),
+            hint: metadata.hint()?,
+        })
+    }
+}
+
+/// How to get between platforms for a change.
+#[derive(Debug, Serialize)]
+pub struct PlatformChangeInfo {
+    /// "same_platform" or "footbridge"
+    pub kind: &'static str,
+
+    /// Platform the next train leaves from
+    pub platform: String,
+
+    /// Human-readable hint, e.g. "Over the footbridge to platform 5"
+    pub hint: String,
+}
+
+impl PlatformChangeInfo {
+    /// Describe a change, if its platforms are known.
+    pub fn for_change(change: &Change, topology: &PlatformTopology) -> Option<Self> {
+        let kind = topology.for_change(change)?;
+        let platform = change.to.board_platform()?.to_string();
+        Some(Self {
+            kind: kind.as_str(),
+            hint: kind.hint(&platform),
+            platform,
+        })
+    }
+}
+
+/// Advice on where to sit on a train that divides.
+#[derive(Debug, Serialize)]
+pub struct BoardingInfo {
+    /// End of the train to travel in ("front" or "rear")
+    pub position: &'static str,
+
+    /// How many coaches at that end go all the way, if known
+    pub coaches: Option<usize>,
+
+    /// Station where the train divides
+    pub divides_at: String,
+
+    /// Human-readable hint
+    pub hint: String,
+}
+
+impl BoardingInfo {
+    /// Create from a leg, if its train divides before the leg ends.
+    pub fn from_leg(leg: &Leg) -> Option<Self> {
+        let advice = leg.boarding_advice()?;
+        Some(Self {
+            position: advice.end.as_str(),
+            coaches: advice.coaches,
+            hint: advice.hint(),
+            divides_at: advice.divides_at_name,
+        })
+    }
 }
 
 /// A walking segment.
@@ -199,6 +901,20 @@ pub struct WalkResult {
 
     /// Duration in minutes
     pub duration_mins: i64,
+
+    /// Walking directions in map apps, when both stations' locations are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub navigation: Option<NavigationLinks>,
+}
+
+/// Deep links that open walking directions in navigation apps.
+#[derive(Debug, Clone, Serialize)]
+pub struct NavigationLinks {
+    /// Google Maps directions URL
+    pub google_maps: String,
+
+    /// Apple Maps directions URL
+    pub apple_maps: String,
 }
 
 /// Station information for display.
@@ -210,13 +926,28 @@ pub struct StationInfo {
     /// Station name
     pub name: String,
 
-    /// Time at this station
+    /// Time at this station, for display: the realtime estimate if there
+    /// is one, else the booked time
     pub time: Option<String>,
 
+    /// Time at this station as an ISO 8601 datetime
+    pub datetime: Option<String>,
+
+    /// Booked time at this station, for display
+    pub scheduled_time: Option<String>,
+
+    /// Booked time at this station as an ISO 8601 datetime
+    pub scheduled_datetime: Option<String>,
+
     /// Platform
     pub platform: Option<String>,
 }
 
+/// A station's CRS code in a response, with the display name beside it,
+/// for filling in names the board left out (see
+/// [`name_stations`](crate::web::name_stations)).
+pub type StationLabel<'a> = (&'a str, &'a mut String);
+
 /// Response for journey planning.
 #[derive(Debug, Serialize)]
 pub struct PlanJourneyResponse {
@@ -225,6 +956,527 @@ pub struct PlanJourneyResponse {
 
     /// Number of routes explored
     pub routes_explored: usize,
+
+    /// Minutes until the user's train departs, when planning before boarding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub departs_in_mins: Option<i64>,
+
+    /// Whether the train now departs later than when the journey was last
+    /// planned, so earlier plans may no longer work
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub departure_slipped: bool,
+
+    /// If no journeys were found, ones that fall just short, best first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub near_misses: Vec<NearMissResult>,
+
+    /// If nothing reaches the destination or comes close, as much of the
+    /// journey as can be planned now, with a hint for planning the rest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_portion: Option<JourneyResult>,
+
+    /// Stations whose data was unavailable, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_quality: Option<DataQualityResult>,
+}
+
+/// Estimated cost of a journey search, from `POST /api/v1/journeys/estimate`.
+///
+/// Takes the same request as planning. Nothing is fetched: boards not
+/// already cached are counted as API calls the search would make.
+#[derive(Debug, Serialize)]
+pub struct SearchEstimateResponse {
+    /// Each search phase in the order it runs
+    pub phases: Vec<PhaseEstimateResult>,
+
+    /// API calls made by the phases that always run
+    pub min_api_calls: usize,
+
+    /// API calls made if every phase that may run does
+    pub max_api_calls: usize,
+
+    /// Whether the search may make more calls than `max_api_calls`, as the
+    /// BFS fallback fetches boards wherever its trains reach
+    pub open_ended: bool,
+
+    /// Direct journeys on the current train, found without any calls
+    pub direct_journeys: usize,
+}
+
+impl SearchEstimateResponse {
+    /// Create from the planner's estimate.
+    pub fn from_estimate(estimate: &SearchEstimate) -> Self {
+        let phases = [
+            ("direct", &estimate.direct),
+            ("one_change", &estimate.one_change),
+            ("two_change", &estimate.two_change),
+            ("bfs", &estimate.bfs),
+        ];
+        Self {
+            phases: phases
+                .into_iter()
+                .map(|(phase, e)| PhaseEstimateResult::from_estimate(phase, e))
+                .collect(),
+            min_api_calls: estimate.min_api_calls(),
+            max_api_calls: estimate.max_api_calls(),
+            open_ended: estimate.open_ended(),
+            direct_journeys: estimate.direct_journeys,
+        }
+    }
+}
+
+/// Estimated cost of one search phase.
+#[derive(Debug, Serialize)]
+pub struct PhaseEstimateResult {
+    /// Phase name, as in the per-phase search stats
+    pub phase: &'static str,
+
+    /// "always", "maybe" (depending on earlier phases' results) or
+    /// "skipped"
+    pub runs: &'static str,
+
+    /// Boards the phase is known to look at
+    pub boards: usize,
+
+    /// Boards already cached or fetched by an earlier phase
+    pub cached: usize,
+
+    /// API calls for the rest
+    pub api_calls: usize,
+
+    /// Whether the phase may fetch more boards than listed
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub open_ended: bool,
+}
+
+impl PhaseEstimateResult {
+    fn from_estimate(phase: &'static str, estimate: &PhaseEstimate) -> Self {
+        Self {
+            phase,
+            runs: estimate.runs.as_str(),
+            boards: estimate.boards,
+            cached: estimate.cached,
+            api_calls: estimate.api_calls(),
+            open_ended: estimate.open_ended,
+        }
+    }
+}
+
+/// Stations a search had to do without, so results may be incomplete.
+#[derive(Debug, Serialize)]
+pub struct DataQualityResult {
+    /// CRS codes of stations whose boards couldn't be fetched
+    pub unavailable_stations: Vec<String>,
+
+    /// CRS codes of stations whose boards were cut to their soonest
+    /// services, if any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated_stations: Vec<String>,
+
+    /// Warning naming the stations, e.g. "Results may be incomplete due to
+    /// data errors at Reading"
+    pub message: String,
+}
+
+impl DataQualityResult {
+    /// Create from a search's data quality, naming stations from `names`
+    /// where known and by CRS code otherwise.
+    ///
+    /// Returns `None` if no data was missing.
+    pub fn from_quality(quality: &DataQuality, names: &HashMap<Crs, String>) -> Option<Self> {
+        if quality.is_complete() {
+            return None;
+        }
+        let places = |stations: &BTreeSet<Crs>| {
+            let named: Vec<&str> = stations
+                .iter()
+                .map(|crs| names.get(crs).map_or(crs.as_str(), String::as_str))
+                .collect();
+            match named.split_last() {
+                Some((last, [])) => (*last).to_string(),
+                Some((last, rest)) => format!("{} and {last}", rest.join(", ")),
+                None => String::new(),
+            }
+        };
+        let codes = |stations: &BTreeSet<Crs>| {
+            stations
+                .iter()
+                .map(|crs| crs.as_str().to_string())
+                .collect()
+        };
+        let unavailable = &quality.unavailable_stations;
+        let truncated = &quality.truncated_stations;
+        let message = match (unavailable.is_empty(), truncated.is_empty()) {
+            (false, true) => format!(
+                "Results may be incomplete due to data errors at {}",
+                places(unavailable)
+            ),
+            (true, _) => format!(
+                "Results may be incomplete as only the soonest departures from {} were searched",
+                places(truncated)
+            ),
+            (false, false) => format!(
+                "Results may be incomplete due to data errors at {}, and as only the soonest departures from {} were searched",
+                places(unavailable),
+                places(truncated)
+            ),
+        };
+        Some(Self {
+            unavailable_stations: codes(unavailable),
+            truncated_stations: codes(truncated),
+            message,
+        })
+    }
+}
+
+/// A journey that falls just short of the request.
+#[derive(Debug, Serialize)]
+pub struct NearMissResult {
+    /// How it falls short ("nearby", "stop_short" or "too_long")
+    pub reason: &'static str,
+
+    /// Human-readable description of how it falls short
+    pub label: String,
+
+    /// The journey itself
+    #[serde(flatten)]
+    pub journey: JourneyResult,
+}
+
+impl PlanJourneyResponse {
+    /// Each station in the response, by CRS code, with its display name.
+    pub fn station_labels(&mut self) -> Vec<StationLabel<'_>> {
+        self.journeys
+            .iter_mut()
+            .chain(self.near_misses.iter_mut().map(|n| &mut n.journey))
+            .chain(self.first_portion.as_mut())
+            .flat_map(JourneyResult::station_labels)
+            .collect()
+    }
+}
+
+impl NearMissResult {
+    /// Create from a planner near miss.
+    pub fn from_near_miss(near_miss: &NearMiss, times: &TimeFormat) -> Self {
+        Self {
+            reason: near_miss.reason.as_str(),
+            label: near_miss.label(),
+            journey: JourneyResult::from_journey(&near_miss.journey, times),
+        }
+    }
+}
+
+/// The train remembered in the user's session.
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    /// Darwin service ID of the current train
+    pub service_id: String,
+
+    /// Station where the service was found
+    pub board_station: String,
+
+    /// Current position index in the service
+    pub position: usize,
+
+    /// Destination station CRS code
+    pub destination: String,
+}
+
+/// A walkable connection, as listed and edited by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalkableConnectionDto {
+    /// One end of the walk (CRS code)
+    pub from: String,
+
+    /// The other end of the walk (CRS code)
+    pub to: String,
+
+    /// Walking time in minutes (same in both directions)
+    pub minutes: i64,
+
+    /// Whether the walk can only be made step-free by lift
+    #[serde(default)]
+    pub requires_lift: bool,
+
+    /// Whether the walk has stairs
+    #[serde(default)]
+    pub requires_stairs: bool,
+}
+
+/// Response listing all walkable connections.
+#[derive(Debug, Serialize)]
+pub struct WalkableListResponse {
+    /// Each connection once, whichever direction it was added in
+    pub connections: Vec<WalkableConnectionDto>,
+}
+
+/// Stations whose lifts are out of service, as listed by the admin API.
+#[derive(Debug, Serialize)]
+pub struct LiftOutagesResponse {
+    /// CRS codes, in alphabetical order
+    pub stations: Vec<String>,
+}
+
+/// Response to an admin reload.
+#[derive(Debug, Serialize)]
+pub struct ReloadResponse {
+    /// Whether the search configuration was reloaded
+    pub config_reloaded: bool,
+
+    /// Walkable connections loaded from the store (null if it has no file,
+    /// so the current connections were kept)
+    pub walkable_connections: Option<usize>,
+
+    /// Why stored connections were rejected as implausible
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub walkable_rejected: Vec<String>,
+
+    /// Stations loaded from the station cache (null if there's no fresh
+    /// cache, so the current stations were kept)
+    pub stations: Option<usize>,
+}
+
+/// Query parameters for the admin analytics endpoints.
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    /// Days to add up, ending today (default 7, at most 90)
+    pub days: Option<u32>,
+
+    /// Most station pairs to list, busiest first (default 20)
+    pub limit: Option<usize>,
+}
+
+/// Searches added up over some days, as reported by the admin API.
+#[derive(Debug, Serialize)]
+pub struct AnalyticsCountsResult {
+    /// Searches made
+    pub searches: u64,
+
+    /// Searches finding no journey
+    pub no_journey: u64,
+
+    /// Searches whose best journey was direct, or had one, two, or three or
+    /// more changes
+    pub changes: [u64; 4],
+
+    /// Mean changes in the best journeys found (null if none found)
+    pub mean_changes: Option<f64>,
+
+    /// Mean time a search took, in milliseconds (null if no searches)
+    pub mean_latency_ms: Option<u64>,
+
+    /// Longest a search took, in milliseconds
+    pub max_latency_ms: u64,
+}
+
+impl AnalyticsCountsResult {
+    /// Report the given counts.
+    pub fn from_counts(counts: &PairCounts) -> Self {
+        Self {
+            searches: counts.searches,
+            no_journey: counts.no_journey,
+            changes: counts.changes,
+            mean_changes: counts.mean_changes(),
+            mean_latency_ms: counts.mean_latency_ms(),
+            max_latency_ms: counts.max_latency_ms,
+        }
+    }
+}
+
+/// All searches over recent days, from the admin API.
+#[derive(Debug, Serialize)]
+pub struct AnalyticsSummaryResponse {
+    /// Days added up, ending today
+    pub days: u32,
+
+    /// The searches made in them
+    #[serde(flatten)]
+    pub counts: AnalyticsCountsResult,
+}
+
+/// One station pair's searches, from the admin API.
+#[derive(Debug, Serialize)]
+pub struct AnalyticsPairResult {
+    /// CRS code searched from
+    pub origin: String,
+
+    /// CRS code searched to
+    pub destination: String,
+
+    /// The searches made between them
+    #[serde(flatten)]
+    pub counts: AnalyticsCountsResult,
+}
+
+/// The most searched station pairs over recent days, from the admin API.
+#[derive(Debug, Serialize)]
+pub struct AnalyticsPairsResponse {
+    /// Days added up, ending today
+    pub days: u32,
+
+    /// Pairs, busiest first
+    pub pairs: Vec<AnalyticsPairResult>,
+}
+
+/// Request to change the walking time of an existing connection.
+#[derive(Debug, Deserialize)]
+pub struct UpdateWalkableRequest {
+    /// New walking time in minutes
+    pub minutes: i64,
+
+    /// Whether the walk can only be made step-free by lift (unchanged if
+    /// left out)
+    #[serde(default)]
+    pub requires_lift: Option<bool>,
+
+    /// Whether the walk has stairs (unchanged if left out)
+    #[serde(default)]
+    pub requires_stairs: Option<bool>,
+}
+
+/// Request to watch a previously planned journey.
+#[derive(Debug, Deserialize)]
+pub struct CreateWatchRequest {
+    /// ID of the journey, as returned when it was planned
+    #[serde(alias = "jid")]
+    pub journey_id: String,
+
+    /// Where to send urgent alerts, such as platform changes; replaces
+    /// those of an existing watch of the journey unless left out
+    #[serde(default)]
+    pub channels: Option<Vec<Channel>>,
+}
+
+/// A watched journey and its latest known state.
+#[derive(Debug, Serialize)]
+pub struct WatchResult {
+    /// ID for deleting the watch
+    pub id: String,
+
+    /// ID of the watched journey
+    pub journey_id: String,
+
+    /// The journey as last refreshed, unless it has expired
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub journey: Option<JourneyResult>,
+
+    /// The journey's latest version (see the journey changes endpoint)
+    pub version: u64,
+
+    /// ID of a fresh plan for the same trip, if one of the journey's
+    /// changes can no longer be made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<String>,
+
+    /// When the journey's trains will next be polled, as an ISO 8601
+    /// datetime
+    pub next_refresh: String,
+
+    /// Seconds until the journey's trains will next be polled
+    pub next_refresh_in_secs: u64,
+}
+
+/// Response listing the caller's watched journeys.
+#[derive(Debug, Serialize)]
+pub struct WatchListResponse {
+    /// Watches, oldest first
+    pub watches: Vec<WatchResult>,
+}
+
+/// Request to save a favourite destination.
+#[derive(Debug, Deserialize)]
+pub struct SaveFavouriteRequest {
+    /// The station, by CRS code or name
+    pub station: String,
+}
+
+/// A destination saved under a name.
+#[derive(Debug, Serialize)]
+pub struct FavouriteResult {
+    /// What the caller calls it, e.g. "Home"
+    pub name: String,
+
+    /// CRS code of the station
+    pub station: String,
+
+    /// Name of the station, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station_name: Option<String>,
+}
+
+/// Response listing the caller's favourite destinations.
+#[derive(Debug, Serialize)]
+pub struct FavouriteListResponse {
+    /// Favourites, in the order they were first saved
+    pub favourites: Vec<FavouriteResult>,
+}
+
+/// A watched journey that has finished, as last observed.
+#[derive(Debug, Serialize)]
+pub struct RecentJourneyResult {
+    /// ID the journey was planned with
+    pub journey_id: String,
+
+    /// Where the first train was boarded: `time` is when it was last
+    /// observed to leave, `scheduled_time` when it was booked to
+    pub origin: StationInfo,
+
+    /// Where the last train was left, with its observed and booked arrival
+    pub destination: StationInfo,
+
+    /// Minutes later than planned the journey arrived (negative if early),
+    /// when known
+    pub arrival_delay_mins: Option<i64>,
+
+    /// Its trains, in order
+    pub legs: Vec<RecentLegResult>,
+
+    /// When the journey was archived, as an ISO 8601 datetime
+    pub archived_datetime: String,
+}
+
+/// One train of a finished journey.
+#[derive(Debug, Serialize)]
+pub struct RecentLegResult {
+    /// Operator name
+    pub operator: String,
+
+    /// Headcode
+    pub headcode: Option<String>,
+
+    /// Where it was boarded, with observed and booked departure times
+    pub origin: StationInfo,
+
+    /// Where it was left, with observed and booked arrival times
+    pub destination: StationInfo,
+
+    /// Whether it was cancelled
+    pub is_cancelled: bool,
+}
+
+/// Response listing the caller's recently finished journeys.
+#[derive(Debug, Serialize)]
+pub struct RecentJourneysResponse {
+    /// Journeys, most recently finished first
+    pub journeys: Vec<RecentJourneyResult>,
+}
+
+/// Request reporting that the user has boarded one of a journey's trains.
+#[derive(Debug, Deserialize)]
+pub struct BoardedLegRequest {
+    /// Index of the leg among the journey's train legs, from 0
+    pub leg: usize,
+}
+
+/// The rest of a journey, re-planned from the train the user boarded.
+#[derive(Debug, Serialize)]
+pub struct BoardedLegResponse {
+    /// The journey from the boarded train on, stored under a new ID that
+    /// replaces the old one
+    pub journey: JourneyResult,
+
+    /// How many of the caller's watches now follow the new journey
+    pub watches_moved: usize,
 }
 
 /// Error response.
@@ -232,28 +1484,64 @@ pub struct PlanJourneyResponse {
 pub struct ErrorResponse {
     /// Error message
     pub error: String,
+
+    /// Suggested stations, when the error is a bad station code
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<StationSearchResult>,
 }
 
 // Conversion implementations
 
+impl SessionResponse {
+    /// Create from a session.
+    pub fn from_session(session: &super::session::Session) -> Self {
+        Self {
+            service_id: session.service_id.clone(),
+            board_station: session.board_station.as_str().to_string(),
+            position: session.position,
+            destination: session.destination.as_str().to_string(),
+        }
+    }
+}
+
+impl CallResult {
+    fn label(&mut self) -> StationLabel<'_> {
+        (&self.crs, &mut self.name)
+    }
+
+    /// Create from a domain Call at `index` in its service.
+    pub fn from_call(c: &Call, index: usize, times: &TimeFormat) -> Self {
+        Self {
+            crs: c.station.as_str().to_string(),
+            name: c.station_name.clone(),
+            scheduled_arrival: c.booked_arrival.map(|t| times.display(t)),
+            scheduled_arrival_datetime: c.booked_arrival.map(iso_datetime),
+            scheduled_departure: c.booked_departure.map(|t| times.display(t)),
+            scheduled_departure_datetime: c.booked_departure.map(iso_datetime),
+            expected_arrival: c.expected_arrival().map(|t| times.display(t)),
+            expected_arrival_datetime: c.expected_arrival().map(iso_datetime),
+            expected_departure: c.expected_departure().map(|t| times.display(t)),
+            expected_departure_datetime: c.expected_departure().map(iso_datetime),
+            platform: c.platform.clone(),
+            is_cancelled: c.is_cancelled,
+            index,
+        }
+    }
+}
+
 impl ServiceResult {
+    /// Each call's station, by CRS code, with its display name.
+    pub fn station_labels(&mut self) -> impl Iterator<Item = StationLabel<'_>> {
+        self.calls.iter_mut().map(CallResult::label)
+    }
+
     /// Create from a domain Service.
-    pub fn from_service(service: &Service) -> Self {
+    pub fn from_service(service: &Service, times: &TimeFormat) -> Self {
         let calls: Vec<CallResult> = service
             .calls
             .iter()
             .enumerate()
-            .map(|(i, c)| CallResult {
-                crs: c.station.as_str().to_string(),
-                name: c.station_name.clone(),
-                scheduled_arrival: c.booked_arrival.map(|t| format_time(&t)),
-                scheduled_departure: c.booked_departure.map(|t| format_time(&t)),
-                expected_arrival: c.expected_arrival().map(|t| format_time(&t)),
-                expected_departure: c.expected_departure().map(|t| format_time(&t)),
-                platform: c.platform.clone(),
-                is_cancelled: c.is_cancelled,
-                index: i,
-            })
+            .map(|(i, c)| CallResult::from_call(c, i, times))
             .collect();
 
         let destination = service
@@ -265,15 +1553,12 @@ impl ServiceResult {
         let scheduled_departure = service
             .calls
             .get(service.board_station_idx.0)
-            .and_then(|c| c.booked_departure)
-            .map(|t| format_time(&t))
-            .unwrap_or_default();
+            .and_then(|c| c.booked_departure);
 
         let expected_departure = service
             .calls
             .get(service.board_station_idx.0)
-            .and_then(|c| c.expected_departure())
-            .map(|t| format_time(&t));
+            .and_then(|c| c.expected_departure());
 
         let platform = service
             .calls
@@ -287,78 +1572,315 @@ impl ServiceResult {
 
         Self {
             service_id: service.service_ref.darwin_id.clone(),
+            board_station: service.service_ref.board_crs.as_str().to_string(),
             headcode: service.headcode.as_ref().map(|h| h.to_string()),
             operator: service.operator.clone(),
             destination,
-            scheduled_departure,
-            expected_departure,
+            scheduled_departure: scheduled_departure
+                .map(|t| times.display(t))
+                .unwrap_or_default(),
+            scheduled_departure_datetime: scheduled_departure.map(iso_datetime),
+            expected_departure: expected_departure.map(|t| times.display(t)),
+            expected_departure_datetime: expected_departure.map(iso_datetime),
             platform,
             is_cancelled,
+            delay_reason: None,
+            cancel_reason: None,
             calls,
         }
     }
-}
 
-impl JourneyResult {
-    /// Create from a domain Journey.
-    pub fn from_journey(journey: &Journey) -> Self {
-        let segments: Vec<SegmentResult> = journey
-            .segments()
+    /// Explain why the train is late or cancelled at the board station, in
+    /// `translation`'s language (English without one).
+    ///
+    /// `service` must be the service this result was created from.
+    pub fn add_disruption_reasons(
+        &mut self,
+        service: &Service,
+        tables: &ReferenceTables,
+        translation: Option<&ReasonTranslation>,
+    ) {
+        let Some(call) = service.board_station_call() else {
+            return;
+        };
+        self.delay_reason = call
+            .delay_reason
+            .as_ref()
+            .and_then(|r| tables.delay_text_in(r, translation));
+        if call.is_cancelled {
+            self.cancel_reason = call
+                .cancel_reason
+                .as_ref()
+                .and_then(|r| tables.cancel_text_in(r, translation));
+        }
+    }
+}
+
+impl JourneyResult {
+    /// Create from a domain Journey.
+    pub fn from_journey(journey: &Journey, times: &TimeFormat) -> Self {
+        let segments: Vec<SegmentResult> = journey
+            .segments()
+            .iter()
+            .map(|s| match s {
+                Segment::Train(leg) => SegmentResult::Train(LegResult::from_leg(leg, times)),
+                Segment::Walk(walk) => SegmentResult::Walk(WalkResult::from_walk(walk)),
+            })
+            .collect();
+
+        let mut result = Self {
+            id: None,
+            segments,
+            departure_time: times.display(journey.departure_time()),
+            departure_datetime: iso_datetime(journey.departure_time()),
+            arrival_time: times.display(journey.arrival_time()),
+            arrival_datetime: iso_datetime(journey.arrival_time()),
+            duration_mins: journey.total_duration().num_minutes(),
+            duration_from_now_mins: None,
+            changes: journey.change_count(),
+            runs_every_mins: journey.headway().map(|h| h.num_minutes()),
+            routeing_warnings: Vec::new(),
+            closure_warnings: Vec::new(),
+            access_warnings: Vec::new(),
+            station_messages: Vec::new(),
+            warnings: Vec::new(),
+            confidence: None,
+            price_band: None,
+            rank_reasons: Vec::new(),
+            continuation_hint: None,
+            alternative_endings: journey
+                .alternative_endings()
+                .iter()
+                .map(|ending| EndingResult::from_ending(ending, times))
+                .collect(),
+        };
+        result.set_warnings(WarningKind::Routeing, routeing_warnings(journey));
+        result
+    }
+}
+
+impl JourneyResult {
+    /// Each station in the journey, by CRS code, with its display name:
+    /// the ends and stops of every leg and walk, and the alternative
+    /// endings.
+    pub fn station_labels(&mut self) -> Vec<StationLabel<'_>> {
+        let mut labels = Vec::new();
+        for segment in &mut self.segments {
+            match segment {
+                SegmentResult::Train(leg) => {
+                    labels.push(leg.origin.label());
+                    labels.extend(leg.stops.iter_mut().map(StationInfo::label));
+                    labels.push(leg.destination.label());
+                }
+                SegmentResult::Walk(walk) => labels.extend(walk.station_labels()),
+            }
+        }
+        for ending in &mut self.alternative_endings {
+            labels.push(ending.alight.label());
+            labels.extend(ending.walk.iter_mut().flat_map(WalkResult::station_labels));
+        }
+        labels
+    }
+
+    /// Add navigation links to walk segments.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_walk_navigation(
+        &mut self,
+        journey: &Journey,
+        locations: &HashMap<Crs, StationLocation>,
+    ) {
+        for (result, segment) in self.segments.iter_mut().zip(journey.segments()) {
+            if let (SegmentResult::Walk(walk_result), Segment::Walk(walk)) = (result, segment) {
+                walk_result.navigation = NavigationLinks::for_walk(walk, locations);
+            }
+        }
+    }
+
+    /// Add alighting advice to every leg that ends in a change.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_alighting_hints(&mut self, journey: &Journey, metadata: &StationMetadataTable) {
+        let last_train = journey.segments().iter().rposition(Segment::is_train);
+        for (i, (result, segment)) in self.segments.iter_mut().zip(journey.segments()).enumerate() {
+            if Some(i) == last_train {
+                continue;
+            }
+            if let (SegmentResult::Train(leg_result), Segment::Train(leg)) = (result, segment) {
+                leg_result.alighting = metadata
+                    .for_alighting(leg)
+                    .and_then(|m| AlightingInfo::from_metadata(&m));
+            }
+        }
+    }
+
+    /// Replace the warnings of one kind, both in their own list and in
+    /// [`warnings`](Self::warnings).
+    fn set_warnings(&mut self, kind: WarningKind, messages: Vec<String>) {
+        self.warnings.retain(|w| w.kind != kind);
+        self.warnings
+            .extend(messages.iter().map(|message| WarningResult {
+                kind,
+                message: message.clone(),
+            }));
+        self.warnings.sort_by_key(|w| w.kind);
+        match kind {
+            WarningKind::Routeing => self.routeing_warnings = messages,
+            WarningKind::Closure => self.closure_warnings = messages,
+            WarningKind::Access => self.access_warnings = messages,
+            WarningKind::StationMessage => self.station_messages = messages,
+        }
+    }
+
+    /// Say whether each change within a station is on the same platform or
+    /// over the footbridge, where `topology` knows.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_platform_hints(&mut self, journey: &Journey, topology: &PlatformTopology) {
+        let legs = self.segments.iter_mut().filter_map(|s| match s {
+            SegmentResult::Train(leg) => Some(leg),
+            SegmentResult::Walk(_) => None,
+        });
+        for (leg_result, change) in legs.zip(journey.changes()) {
+            leg_result.next_platform = PlatformChangeInfo::for_change(&change, topology);
+        }
+    }
+
+    /// Warn about walks through stations that are closed at the time.
+    pub fn add_closure_warnings(&mut self, journey: &Journey, hours: &HashMap<Crs, OpeningHours>) {
+        self.set_warnings(WarningKind::Closure, closure_warnings(journey, hours));
+    }
+
+    /// Pass on the messages posted for the stations the journey changes at.
+    pub fn add_station_messages(
+        &mut self,
+        journey: &Journey,
+        messages: &HashMap<Crs, Vec<String>>,
+    ) {
+        self.set_warnings(
+            WarningKind::StationMessage,
+            station_message_warnings(journey, messages),
+        );
+    }
+
+    /// Warn about walks that need a lift or have stairs, given the lifts
+    /// out of service at `outages`.
+    pub fn add_access_warnings(
+        &mut self,
+        journey: &Journey,
+        walkable: &WalkableConnections,
+        outages: &BTreeSet<Crs>,
+    ) {
+        self.set_warnings(
+            WarningKind::Access,
+            access_warning_messages(journey, walkable, outages),
+        );
+    }
+
+    /// Say how far the journey can be relied on, judging changes against
+    /// `config`'s minimum connection time and, given a `history`, how often
+    /// their incoming trains have been too late for them.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_confidence(
+        &mut self,
+        journey: &Journey,
+        config: &SearchConfig,
+        history: Option<&PerformanceHistory>,
+    ) {
+        let assessed = assess_confidence_with_history(journey, config, history);
+        self.confidence = Some(ConfidenceResult {
+            level: assessed.confidence.as_str(),
+            reasons: assessed.reasons.into_iter().map(|r| r.message).collect(),
+        });
+    }
+
+    /// Estimate the journey's fare with `config`'s fare provider.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_price_band(&mut self, journey: &Journey, config: &SearchConfig) {
+        self.price_band = config.fares.price_band(journey).map(PriceBand::as_str);
+    }
+
+    /// Add what the journey does better than the others it was returned
+    /// with (see [`rank_reasons`](crate::planner::rank_reasons)).
+    pub fn add_rank_reasons(&mut self, reasons: &[RankReason]) {
+        self.rank_reasons = reasons
             .iter()
-            .map(|s| match s {
-                Segment::Train(leg) => SegmentResult::Train(LegResult::from_leg(leg)),
-                Segment::Walk(walk) => SegmentResult::Walk(WalkResult::from_walk(walk)),
+            .map(|r| RankReasonResult {
+                reason: r.as_str(),
+                label: r.label(),
             })
             .collect();
+    }
 
-        Self {
-            segments,
-            departure_time: format_time(&journey.departure_time()),
-            arrival_time: format_time(&journey.arrival_time()),
-            duration_mins: journey.total_duration().num_minutes(),
-            changes: journey.change_count(),
+    /// Add where and when the rest of the journey will be planned, if this
+    /// is only its first portion.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_continuation_hint(
+        &mut self,
+        journey: &Journey,
+        hint: &ContinuationHint,
+        times: &TimeFormat,
+    ) {
+        self.continuation_hint = Some(ContinuationHintResult::from_hint(hint, journey, times));
+    }
+
+    /// Explain any delays and cancellations on the journey's trains, in
+    /// `translation`'s language (English without one), and name operators
+    /// the board gave only a code for.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_disruption_reasons(
+        &mut self,
+        journey: &Journey,
+        tables: &ReferenceTables,
+        translation: Option<&ReasonTranslation>,
+    ) {
+        for (result, segment) in self.segments.iter_mut().zip(journey.segments()) {
+            if let (SegmentResult::Train(leg_result), Segment::Train(leg)) = (result, segment) {
+                leg_result.add_disruption_reasons(leg, tables, translation);
+            }
         }
     }
+
+    /// Add how long the journey takes from `now`, including the wait for
+    /// the first train.
+    ///
+    /// `journey` must be the journey this result was created from.
+    pub fn add_duration_from(&mut self, journey: &Journey, now: RailTime) {
+        self.duration_from_now_mins = Some(journey.duration_from(now).num_minutes());
+    }
 }
 
 impl LegResult {
     /// Create from a domain Leg.
-    pub fn from_leg(leg: &Leg) -> Self {
-        let origin = StationInfo {
-            crs: leg.board_call().station.as_str().to_string(),
-            name: leg.board_call().station_name.clone(),
-            time: leg
-                .board_call()
-                .expected_departure()
-                .map(|t| format_time(&t)),
-            platform: leg.board_call().platform.clone(),
-        };
-
-        let destination = StationInfo {
-            crs: leg.alight_call().station.as_str().to_string(),
-            name: leg.alight_call().station_name.clone(),
-            time: leg
-                .alight_call()
-                .expected_arrival()
-                .map(|t| format_time(&t)),
-            platform: leg.alight_call().platform.clone(),
-        };
+    pub fn from_leg(leg: &Leg, times: &TimeFormat) -> Self {
+        let origin = StationInfo::at_call(
+            leg.board_call(),
+            leg.board_call().booked_departure,
+            leg.board_call().expected_departure(),
+            times,
+        );
+        let destination = StationInfo::at_call(
+            leg.alight_call(),
+            leg.alight_call().booked_arrival,
+            leg.alight_call().expected_arrival(),
+            times,
+        );
 
-        // Get intermediate stops (exclude board and alight)
-        let all_calls = leg.calls();
-        let stops: Vec<StationInfo> = if all_calls.len() > 2 {
-            all_calls[1..all_calls.len() - 1]
-                .iter()
-                .map(|c| StationInfo {
-                    crs: c.station.as_str().to_string(),
-                    name: c.station_name.clone(),
-                    time: c.expected_arrival().map(|t| format_time(&t)),
-                    platform: c.platform.clone(),
-                })
-                .collect()
-        } else {
-            Vec::new()
-        };
+        let stops: Vec<StationInfo> = leg
+            .intermediate_calls()
+            .map(|c| {
+                StationInfo::at_call(
+                    c,
+                    c.booked_arrival.or(c.booked_departure),
+                    c.expected_arrival().or_else(|| c.expected_departure()),
+                    times,
+                )
+            })
+            .collect();
 
         Self {
             operator: leg.service().operator.clone(),
@@ -366,11 +1888,58 @@ impl LegResult {
             origin,
             destination,
             stops,
+            alighting: None,
+            boarding: BoardingInfo::from_leg(leg),
+            next_platform: None,
+            delay_reason: None,
+            cancel_reason: None,
+        }
+    }
+}
+
+impl LegResult {
+    /// Explain the leg's delay and cancellation, preferring reasons given
+    /// for its own calls over the service's, and name its operator if the
+    /// board didn't.
+    fn add_disruption_reasons(
+        &mut self,
+        leg: &Leg,
+        tables: &ReferenceTables,
+        translation: Option<&ReasonTranslation>,
+    ) {
+        let service_call = leg.service().board_station_call();
+        let reason = |field: fn(&Call) -> Option<&String>, cancelled_only: bool| {
+            leg.calls()
+                .iter()
+                .filter(|c| !cancelled_only || c.is_cancelled)
+                .chain(service_call)
+                .find_map(field)
+        };
+
+        // Darwin only gives a delay reason while the train is late
+        self.delay_reason = reason(|c| c.delay_reason.as_ref(), false)
+            .and_then(|r| tables.delay_text_in(r, translation));
+        if leg.is_cancelled() {
+            self.cancel_reason = reason(|c| c.cancel_reason.as_ref(), true)
+                .and_then(|r| tables.cancel_text_in(r, translation));
+        }
+        if self.operator.is_empty()
+            && let Some(name) = leg
+                .service()
+                .operator_code
+                .as_ref()
+                .and_then(|code| tables.operator_name(code))
+        {
+            self.operator = name.to_string();
         }
     }
 }
 
 impl WalkResult {
+    fn station_labels(&mut self) -> [StationLabel<'_>; 2] {
+        [self.from.label(), self.to.label()]
+    }
+
     /// Create from a domain Walk.
     pub fn from_walk(walk: &Walk) -> Self {
         Self {
@@ -378,22 +1947,208 @@ impl WalkResult {
                 crs: walk.from.as_str().to_string(),
                 name: walk.from.as_str().to_string(), // We don't have the name
                 time: None,
+                datetime: None,
+                scheduled_time: None,
+                scheduled_datetime: None,
                 platform: None,
             },
             to: StationInfo {
                 crs: walk.to.as_str().to_string(),
                 name: walk.to.as_str().to_string(), // We don't have the name
                 time: None,
+                datetime: None,
+                scheduled_time: None,
+                scheduled_datetime: None,
                 platform: None,
             },
             duration_mins: walk.duration.num_minutes(),
+            navigation: None,
+        }
+    }
+}
+
+impl RecentJourneyResult {
+    /// Create from an archived journey.
+    pub fn from_archived(journey: &ArchivedJourney, times: &TimeFormat) -> Self {
+        Self {
+            journey_id: journey.journey_id.clone(),
+            origin: StationInfo::from_archived(journey.origin(), times),
+            destination: StationInfo::from_archived(journey.destination(), times),
+            arrival_delay_mins: journey.arrival_delay().map(|d| d.num_minutes()),
+            legs: journey
+                .legs()
+                .iter()
+                .map(|leg| RecentLegResult {
+                    operator: leg.operator.clone(),
+                    headcode: leg.headcode.map(|h| h.to_string()),
+                    origin: StationInfo::from_archived(&leg.board, times),
+                    destination: StationInfo::from_archived(&leg.alight, times),
+                    is_cancelled: leg.cancelled,
+                })
+                .collect(),
+            archived_datetime: iso_datetime(journey.archived),
+        }
+    }
+}
+
+impl StationInfo {
+    fn label(&mut self) -> StationLabel<'_> {
+        (&self.crs, &mut self.name)
+    }
+
+    /// Describe an archived call, at its observed and booked times.
+    fn from_archived(call: &ArchivedCall, times: &TimeFormat) -> Self {
+        Self {
+            crs: call.station.as_str().to_string(),
+            name: call.station_name.clone(),
+            time: call.actual.map(|t| times.display(t)),
+            datetime: call.actual.map(iso_datetime),
+            scheduled_time: call.planned.map(|t| times.display(t)),
+            scheduled_datetime: call.planned.map(iso_datetime),
+            platform: call.platform.clone(),
+        }
+    }
+
+    /// Describe a call's station, at the given booked and expected times
+    /// there.
+    fn at_call(
+        call: &Call,
+        scheduled: Option<RailTime>,
+        time: Option<RailTime>,
+        times: &TimeFormat,
+    ) -> Self {
+        Self {
+            crs: call.station.as_str().to_string(),
+            name: call.station_name.clone(),
+            time: time.map(|t| times.display(t)),
+            datetime: time.map(iso_datetime),
+            scheduled_time: scheduled.map(|t| times.display(t)),
+            scheduled_datetime: scheduled.map(iso_datetime),
+            platform: call.platform.clone(),
+        }
+    }
+}
+
+impl NavigationLinks {
+    /// Build walking-directions links between two locations.
+    pub fn walking(from: StationLocation, to: StationLocation) -> Self {
+        let from = format_coords(from);
+        let to = format_coords(to);
+        Self {
+            google_maps: format!(
+                "https://www.google.com/maps/dir/?api=1&origin={from}&destination={to}&travelmode=walking"
+            ),
+            apple_maps: format!("https://maps.apple.com/?saddr={from}&daddr={to}&dirflg=w"),
         }
     }
+
+    /// Build links for a walk, if both ends have known locations.
+    pub fn for_walk(walk: &Walk, locations: &HashMap<Crs, StationLocation>) -> Option<Self> {
+        let from = locations.get(&walk.from)?;
+        let to = locations.get(&walk.to)?;
+        Some(Self::walking(*from, *to))
+    }
+}
+
+/// Format a location as "lat,lon" for map URLs.
+///
+/// Five decimal places is about a metre, plenty for a station entrance.
+fn format_coords(loc: StationLocation) -> String {
+    format!("{:.5},{:.5}", loc.latitude, loc.longitude)
+}
+
+/// Stations at either end of the walks in a journey.
+///
+/// Use this to fetch just the locations needed for navigation links.
+pub fn walk_stations(journey: &Journey) -> impl Iterator<Item = Crs> + '_ {
+    journey.walks().flat_map(|w| [w.from, w.to])
+}
+
+/// How to finish a journey with `ending` instead, e.g. "Or get off at
+/// St Pancras and walk 5 min to KGX, arriving 10:05".
+pub fn ending_message(ending: &Ending, times: &TimeFormat) -> String {
+    let arriving = times.display(ending.arrival_time());
+    let station = ending.leg.alight_station_name();
+    match &ending.walk {
+        Some(walk) => format!(
+            "Or get off at {} and walk {} min to {}, arriving {}",
+            station,
+            walk.duration.num_minutes(),
+            walk.to_name(),
+            arriving
+        ),
+        None => format!("Or get off at {}, arriving {}", station, arriving),
+    }
+}
+
+/// Messages for the routeing rules a journey breaks.
+pub fn routeing_warnings(journey: &Journey) -> Vec<String> {
+    RouteingRules::default()
+        .check(journey)
+        .into_iter()
+        .map(|issue| issue.message)
+        .collect()
+}
+
+/// Messages for the stations a journey's walks need while they are closed.
+pub fn closure_warnings(journey: &Journey, hours: &HashMap<Crs, OpeningHours>) -> Vec<String> {
+    closed_walk_stations(journey, hours)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Messages for the walks in a journey that need a lift or have stairs.
+pub fn access_warning_messages(
+    journey: &Journey,
+    walkable: &WalkableConnections,
+    outages: &BTreeSet<Crs>,
+) -> Vec<String> {
+    access_warnings(journey, walkable, outages)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Stations a journey changes trains at: where each train but the last is
+/// left, and where each but the first is joined.
+///
+/// Use this to fetch just the station messages needed for warnings.
+pub fn change_stations(journey: &Journey) -> impl Iterator<Item = Crs> + '_ {
+    journey.changes().flat_map(|change| {
+        let left = *change.from.alight_station();
+        let joined = *change.to.board_station();
+        [Some(left), (joined != left).then_some(joined)]
+            .into_iter()
+            .flatten()
+    })
 }
 
-/// Format a RailTime as "HH:MM".
-fn format_time(time: &RailTime) -> String {
-    time.to_string()
+/// The messages posted for the stations a journey changes at, each
+/// prefixed with its station's name.
+pub fn station_message_warnings(
+    journey: &Journey,
+    messages: &HashMap<Crs, Vec<String>>,
+) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for change in journey.changes() {
+        let stations = [
+            (
+                change.from.alight_station(),
+                change.from.alight_station_name(),
+            ),
+            (change.to.board_station(), change.to.board_station_name()),
+        ];
+        for (station, name) in stations {
+            for message in messages.get(station).into_iter().flatten() {
+                let warning = format!("{name}: {message}");
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+        }
+    }
+    warnings
 }
 
 #[cfg(test)]
@@ -440,19 +2195,24 @@ mod tests {
             operator_code: crate::domain::AtocCode::parse("GW").ok(),
             calls,
             board_station_idx: CallIndex(0),
+            formation: None,
         }
     }
 
     #[test]
     fn service_result_from_service() {
         let service = make_test_service();
-        let result = ServiceResult::from_service(&service);
+        let result = ServiceResult::from_service(&service, &TimeFormat::default());
 
         assert_eq!(result.service_id, "ABC123");
         assert_eq!(result.headcode, Some("1A23".to_string()));
         assert_eq!(result.operator, "Great Western Railway");
         assert_eq!(result.destination, "Bristol Temple Meads");
         assert_eq!(result.scheduled_departure, "10:00");
+        assert_eq!(
+            result.scheduled_departure_datetime.as_deref(),
+            Some("2024-03-15T10:00:00+00:00")
+        );
         assert_eq!(result.platform, Some("1".to_string()));
         assert!(!result.is_cancelled);
         assert_eq!(result.calls.len(), 4);
@@ -461,7 +2221,7 @@ mod tests {
     #[test]
     fn call_result_fields() {
         let service = make_test_service();
-        let result = ServiceResult::from_service(&service);
+        let result = ServiceResult::from_service(&service, &TimeFormat::default());
 
         // Check first call (origin)
         let call0 = &result.calls[0];
@@ -490,7 +2250,7 @@ mod tests {
     fn leg_result_from_leg() {
         let service = Arc::new(make_test_service());
         let leg = Leg::new(service, CallIndex(0), CallIndex(3)).unwrap();
-        let result = LegResult::from_leg(&leg);
+        let result = LegResult::from_leg(&leg, &TimeFormat::default());
 
         assert_eq!(result.operator, "Great Western Railway");
         assert_eq!(result.headcode, Some("1A23".to_string()));
@@ -505,12 +2265,31 @@ mod tests {
         assert_eq!(result.stops[1].crs, "SWI");
     }
 
+    #[test]
+    fn leg_result_stops_have_booked_and_expected_times() {
+        let mut service = make_test_service();
+        service.calls[2].realtime_arrival = Some(make_time(10, 58));
+        let leg = Leg::new(Arc::new(service), CallIndex(0), CallIndex(3)).unwrap();
+        let result = LegResult::from_leg(&leg, &TimeFormat::default());
+
+        let swindon = &result.stops[1];
+        assert_eq!(swindon.scheduled_time.as_deref(), Some("10:52"));
+        assert_eq!(swindon.time.as_deref(), Some("10:58"));
+        assert_eq!(
+            swindon.scheduled_datetime.as_deref(),
+            Some("2024-03-15T10:52:00+00:00")
+        );
+        assert_eq!(result.stops[0].time, result.stops[0].scheduled_time);
+        assert_eq!(result.origin.scheduled_time.as_deref(), Some("10:00"));
+        assert_eq!(result.destination.scheduled_time.as_deref(), Some("11:30"));
+    }
+
     #[test]
     fn leg_result_direct() {
         // A direct leg with no intermediate stops
         let service = Arc::new(make_test_service());
         let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
-        let result = LegResult::from_leg(&leg);
+        let result = LegResult::from_leg(&leg, &TimeFormat::default());
 
         assert_eq!(result.origin.crs, "PAD");
         assert_eq!(result.destination.crs, "RDG");
@@ -527,12 +2306,43 @@ mod tests {
         assert_eq!(result.duration_mins, 5);
     }
 
+    #[test]
+    fn navigation_links_for_walk() {
+        let walk = Walk::new(
+            Crs::parse("KGX").unwrap(),
+            Crs::parse("STP").unwrap(),
+            chrono::Duration::minutes(5),
+        );
+        let mut locations = HashMap::new();
+        locations.insert(
+            Crs::parse("KGX").unwrap(),
+            StationLocation::new(51.53201, -0.12330).unwrap(),
+        );
+
+        // Missing destination location: no links
+        assert!(NavigationLinks::for_walk(&walk, &locations).is_none());
+
+        locations.insert(
+            Crs::parse("STP").unwrap(),
+            StationLocation::new(51.53179, -0.12699).unwrap(),
+        );
+        let links = NavigationLinks::for_walk(&walk, &locations).unwrap();
+        assert_eq!(
+            links.google_maps,
+            "https://www.google.com/maps/dir/?api=1&origin=51.53201,-0.12330&destination=51.53179,-0.12699&travelmode=walking"
+        );
+        assert_eq!(
+            links.apple_maps,
+            "https://maps.apple.com/?saddr=51.53201,-0.12330&daddr=51.53179,-0.12699&dirflg=w"
+        );
+    }
+
     #[test]
     fn journey_result_from_journey() {
         let service1 = Arc::new(make_test_service());
         let leg = Leg::new(service1, CallIndex(0), CallIndex(3)).unwrap();
         let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
-        let result = JourneyResult::from_journey(&journey);
+        let result = JourneyResult::from_journey(&journey, &TimeFormat::default());
 
         assert_eq!(result.departure_time, "10:00");
         assert_eq!(result.arrival_time, "11:30");
@@ -550,12 +2360,357 @@ mod tests {
     }
 
     #[test]
-    fn format_time_test() {
-        let time = make_time(14, 30);
-        assert_eq!(format_time(&time), "14:30");
+    fn near_miss_result_flattens_journey() {
+        use crate::planner::NearMissReason;
+
+        let service = Arc::new(make_test_service());
+        let leg = Leg::new(service, CallIndex(0), CallIndex(2)).unwrap();
+        let near_miss = NearMiss {
+            journey: Journey::new(vec![Segment::Train(leg)]).unwrap(),
+            reason: NearMissReason::StopShort,
+        };
+
+        let json = serde_json::to_value(NearMissResult::from_near_miss(
+            &near_miss,
+            &TimeFormat::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(json["reason"], "stop_short");
+        assert_eq!(
+            json["label"],
+            "Ends at Swindon, one stop short of your destination"
+        );
+        assert_eq!(json["arrival_time"], "10:52");
+    }
+
+    #[test]
+    fn disruption_reasons_are_explained() {
+        use crate::darwin::{ReasonCodeDto, ReferenceLists, TocDto};
+
+        let tables = ReferenceTables::from_lists(ReferenceLists {
+            reason_codes: vec![ReasonCodeDto {
+                code: 106,
+                late_reason: "Delayed by a signalling fault".into(),
+                canc_reason: "Cancelled because of a signalling fault".into(),
+            }],
+            operators: vec![TocDto {
+                toc: "GW".into(),
+                name: "Great Western Railway".into(),
+            }],
+        });
+        let mut service = make_test_service();
+        service.operator = String::new();
+        service.calls[0].delay_reason = Some("106".into());
+        service.calls[3].is_cancelled = true;
+        service.calls[3].cancel_reason = Some("106".into());
+        let service = Arc::new(service);
+
+        let leg = Leg::new(service.clone(), CallIndex(0), CallIndex(3)).unwrap();
+        let mut result = LegResult::from_leg(&leg, &TimeFormat::default());
+        result.add_disruption_reasons(&leg, &tables, None);
+        assert_eq!(
+            result.delay_reason.as_deref(),
+            Some("Delayed by a signalling fault")
+        );
+        assert_eq!(
+            result.cancel_reason.as_deref(),
+            Some("Cancelled because of a signalling fault")
+        );
+        assert_eq!(result.operator, "Great Western Railway");
+
+        // Off before the cancelled call: late, but not cancelled
+        let short = Leg::new(service, CallIndex(0), CallIndex(2)).unwrap();
+        let mut result = LegResult::from_leg(&short, &TimeFormat::default());
+        result.add_disruption_reasons(&short, &ReferenceTables::default(), None);
+        assert_eq!(result.delay_reason, None);
+        assert_eq!(result.cancel_reason, None);
+    }
+
+    #[test]
+    fn board_rows_give_reasons_in_the_requested_language() {
+        use crate::darwin::{ReasonCodeDto, ReasonTranslations, ReferenceLists};
+
+        let tables = ReferenceTables::from_lists(ReferenceLists {
+            reason_codes: vec![ReasonCodeDto {
+                code: 106,
+                late_reason: "Delayed by a signalling fault".into(),
+                canc_reason: "Cancelled because of a signalling fault".into(),
+            }],
+            operators: vec![],
+        });
+        let translations = ReasonTranslations::from_json(
+            r#"{"cy": {"late": {"106": "Wedi'i oedi gan nam signalau"}}}"#,
+        )
+        .unwrap();
+        let mut service = make_test_service();
+        service.calls[0].delay_reason = Some("Delayed by a signalling fault".into());
+        service.calls[0].cancel_reason = Some("106".into());
+
+        let mut row = ServiceResult::from_service(&service, &TimeFormat::default());
+        row.add_disruption_reasons(&service, &tables, translations.negotiate("cy-GB,en;q=0.5"));
+        assert_eq!(
+            row.delay_reason.as_deref(),
+            Some("Wedi'i oedi gan nam signalau")
+        );
+        // A reason given for a call that still runs isn't shown
+        assert_eq!(row.cancel_reason, None);
+
+        // English first keeps Darwin's own text
+        let mut row = ServiceResult::from_service(&service, &TimeFormat::default());
+        row.add_disruption_reasons(&service, &tables, translations.negotiate("en-GB,cy"));
+        assert_eq!(
+            row.delay_reason.as_deref(),
+            Some("Delayed by a signalling fault")
+        );
+    }
+
+    #[test]
+    fn boarding_advice_on_dividing_leg() {
+        use crate::domain::{Formation, Portion, TrainEnd};
+
+        let mut service = make_test_service();
+        service.formation = Some(Formation {
+            length: Some(9),
+            portions: vec![Portion {
+                divides_at: crs("RDG"),
+                destination: crs("OXF"),
+                destination_name: "Oxford".into(),
+                remaining_coaches: Some(5),
+                calls: Vec::new(),
+            }],
+            ..Formation::default()
+        });
+        let service = Arc::new(service);
+
+        let through = Leg::new(service.clone(), CallIndex(0), CallIndex(3)).unwrap();
+        let boarding = LegResult::from_leg(&through, &TimeFormat::default())
+            .boarding
+            .unwrap();
+        assert_eq!(boarding.position, "front");
+        assert_eq!(boarding.coaches, Some(5));
+        assert_eq!(boarding.divides_at, "Reading");
+
+        // Off before the train divides
+        let short = Leg::new(service.clone(), CallIndex(0), CallIndex(1)).unwrap();
+        assert!(
+            LegResult::from_leg(&short, &TimeFormat::default())
+                .boarding
+                .is_none()
+        );
+
+        // Staying on a portion at the rear
+        let mut rear = (*service).clone();
+        rear.formation.as_mut().unwrap().end = TrainEnd::Rear;
+        let through = Leg::new(Arc::new(rear), CallIndex(0), CallIndex(3)).unwrap();
+        let boarding = LegResult::from_leg(&through, &TimeFormat::default())
+            .boarding
+            .unwrap();
+        assert_eq!(boarding.position, "rear");
+    }
+
+    #[test]
+    fn confidence_added_after_search() {
+        let service = Arc::new(make_test_service());
+        let first = Leg::new(service.clone(), CallIndex(0), CallIndex(1)).unwrap();
+        let second = Leg::new(service, CallIndex(1), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(first), Segment::Train(second)]).unwrap();
+
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        assert!(serde_json::to_value(&result).unwrap()["confidence"].is_null());
+
+        let config = SearchConfig {
+            min_connection_mins: 5,
+            ..SearchConfig::default()
+        };
+        result.add_confidence(&journey, &config, None);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["confidence"]["level"], "low");
+        assert_eq!(
+            json["confidence"]["reasons"][0],
+            "Only 2 min to change at Reading"
+        );
+    }
+
+    #[test]
+    fn rank_reasons_label_the_journey() {
+        let service = Arc::new(make_test_service());
+        let leg = Leg::new(service, CallIndex(0), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        assert!(serde_json::to_value(&result).unwrap()["rank_reasons"].is_null());
+
+        result.add_rank_reasons(&[RankReason::Fastest, RankReason::FewestChanges]);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["rank_reasons"][0]["reason"], "fastest");
+        assert_eq!(json["rank_reasons"][1]["label"], "Fewest changes");
+    }
+
+    #[test]
+    fn alighting_hints_only_on_changes() {
+        use crate::alighting::{StationMetadata, TrainPosition};
+
+        let service = Arc::new(make_test_service());
+        let first = Leg::new(service.clone(), CallIndex(0), CallIndex(1)).unwrap();
+        let second = Leg::new(service, CallIndex(1), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(first), Segment::Train(second)]).unwrap();
+
+        let mut metadata = StationMetadataTable::new();
+        for station in ["RDG", "BRI"] {
+            metadata.insert(
+                crs(station),
+                StationMetadata::new().with_exit(TrainPosition::Front),
+            );
+        }
+
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        result.add_alighting_hints(&journey, &metadata);
+
+        let SegmentResult::Train(change) = &result.segments[0] else {
+            panic!("Expected Train segment");
+        };
+        let alighting = change.alighting.as_ref().unwrap();
+        assert_eq!(alighting.position, Some("front"));
+        assert_eq!(alighting.door_side, None);
+
+        // Arriving at the destination isn't a change
+        let SegmentResult::Train(last) = &result.segments[1] else {
+            panic!("Expected Train segment");
+        };
+        assert!(last.alighting.is_none());
+    }
+
+    #[test]
+    fn platform_hints_say_how_to_change() {
+        let mut arriving = make_test_service();
+        arriving.calls[1].platform = Some("4".into());
+        let mut onward = make_test_service();
+        onward.service_ref = ServiceRef::new("DEF456".into(), crs("RDG"));
+        onward.calls[1].platform = Some("1".into());
+        let first = Leg::new(Arc::new(arriving), CallIndex(0), CallIndex(1)).unwrap();
+        let second = Leg::new(Arc::new(onward), CallIndex(1), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(first), Segment::Train(second)]).unwrap();
+
+        let mut topology = PlatformTopology::new();
+        topology.insert(crs("RDG"), &[&["1"], &["4", "5"]]);
+
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        result.add_platform_hints(&journey, &topology);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["segments"][0]["next_platform"]["kind"], "footbridge");
+        assert_eq!(json["segments"][0]["next_platform"]["platform"], "1");
+        assert_eq!(
+            json["segments"][0]["next_platform"]["hint"],
+            "Over the footbridge to platform 1"
+        );
+        assert!(json["segments"][1]["next_platform"].is_null());
+    }
+
+    #[test]
+    fn recent_journeys_show_observed_and_booked_times() {
+        let mut service = make_test_service();
+        service.calls[3].realtime_arrival = Some(make_time(11, 42));
+        let leg = Leg::new(Arc::new(service), CallIndex(0), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+        let archived = ArchivedJourney::from_journey("j1", &journey, make_time(11, 45)).unwrap();
+
+        let result = RecentJourneyResult::from_archived(&archived, &TimeFormat::default());
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["journey_id"], "j1");
+        assert_eq!(json["origin"]["time"], "10:00");
+        assert_eq!(json["destination"]["time"], "11:42");
+        assert_eq!(json["destination"]["scheduled_time"], "11:30");
+        assert_eq!(json["arrival_delay_mins"], 12);
+        assert_eq!(json["legs"][0]["destination"]["platform"], "3");
+        assert_eq!(json["archived_datetime"], "2024-03-15T11:45:00+00:00");
+    }
+
+    #[test]
+    fn station_messages_only_for_changes() {
+        let service = Arc::new(make_test_service());
+        let first = Leg::new(service.clone(), CallIndex(0), CallIndex(1)).unwrap();
+        let second = Leg::new(service, CallIndex(1), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(first), Segment::Train(second)]).unwrap();
+        assert_eq!(change_stations(&journey).collect::<Vec<_>>(), [crs("RDG")]);
+
+        let messages = HashMap::from([
+            (crs("PAD"), vec!["Ticket office closed".to_string()]),
+            (crs("RDG"), vec!["Lifts out of service".to_string()]),
+        ]);
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        assert!(serde_json::to_value(&result).unwrap()["station_messages"].is_null());
+
+        result.add_station_messages(&journey, &messages);
+        assert_eq!(result.station_messages, ["Reading: Lifts out of service"]);
+    }
+
+    #[test]
+    fn data_quality_names_unavailable_stations() {
+        assert!(
+            DataQualityResult::from_quality(&DataQuality::default(), &HashMap::new()).is_none()
+        );
+
+        let quality = DataQuality {
+            unavailable_stations: [crs("SWI"), crs("RDG"), crs("XYZ")].into(),
+            ..DataQuality::default()
+        };
+        let names = HashMap::from([
+            (crs("RDG"), "Reading".to_string()),
+            (crs("SWI"), "Swindon".to_string()),
+        ]);
+        let result = DataQualityResult::from_quality(&quality, &names).unwrap();
+        assert_eq!(result.unavailable_stations, ["RDG", "SWI", "XYZ"]);
+        assert_eq!(
+            result.message,
+            "Results may be incomplete due to data errors at Reading, Swindon and XYZ"
+        );
+
+        let quality = DataQuality {
+            unavailable_stations: [crs("RDG")].into(),
+            ..DataQuality::default()
+        };
+        let result = DataQualityResult::from_quality(&quality, &names).unwrap();
+        assert_eq!(
+            result.message,
+            "Results may be incomplete due to data errors at Reading"
+        );
+        assert!(result.truncated_stations.is_empty());
+
+        let quality = DataQuality {
+            truncated_stations: [crs("SWI")].into(),
+            ..DataQuality::default()
+        };
+        let result = DataQualityResult::from_quality(&quality, &names).unwrap();
+        assert_eq!(result.truncated_stations, ["SWI"]);
+        assert_eq!(
+            result.message,
+            "Results may be incomplete as only the soonest departures from Swindon were searched"
+        );
+    }
+
+    #[test]
+    fn times_have_display_and_iso_forms() {
+        use crate::web::time_format::HourCycle;
+
+        let service = Arc::new(make_test_service());
+        let leg = Leg::new(service, CallIndex(0), CallIndex(3)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+        let result = JourneyResult::from_journey(&journey, &TimeFormat::new(HourCycle::Twelve));
+
+        assert_eq!(result.departure_time, "10:00am");
+        assert_eq!(result.departure_datetime, "2024-03-15T10:00:00+00:00");
+        assert_eq!(result.arrival_time, "11:30am");
+        assert_eq!(result.arrival_datetime, "2024-03-15T11:30:00+00:00");
 
-        let time = make_time(9, 5);
-        assert_eq!(format_time(&time), "09:05");
+        let SegmentResult::Train(leg_result) = &result.segments[0] else {
+            panic!("Expected Train segment");
+        };
+        assert_eq!(leg_result.stops[0].time.as_deref(), Some("10:25am"));
+        assert_eq!(
+            leg_result.stops[0].datetime.as_deref(),
+            Some("2024-03-15T10:25:00+00:00")
+        );
     }
 }
 
@@ -564,6 +2719,7 @@ mod tests {
 mod bug_tests {
     use super::*;
     use crate::domain::Crs;
+    use crate::planner::DominanceCriterion;
     use chrono::Duration;
 
     fn crs(s: &str) -> Crs {
@@ -615,4 +2771,260 @@ mod bug_tests {
         // A proper implementation would calculate these based on the
         // arrival time of the previous leg and the walk duration
     }
+
+    #[test]
+    fn plan_request_overrides_are_optional() {
+        let req: PlanJourneyRequest = serde_json::from_str(
+            r#"{"service_id": "S", "position": 0, "destination": "BRI", "board_station": "PAD"}"#,
+        )
+        .unwrap();
+        assert_eq!(req.overrides, SearchOverrides::default());
+
+        let req: PlanJourneyRequest = serde_json::from_str(
+            r#"{"service_id": "S", "position": 0, "destination": "BRI", "board_station": "PAD",
+                "max_changes": 1, "max_walk_mins": 5}"#,
+        )
+        .unwrap();
+        assert_eq!(req.overrides.max_changes, Some(1));
+        assert_eq!(req.overrides.max_walk_mins, Some(5));
+
+        let config = req.overrides.apply(&SearchConfig::default());
+        assert_eq!(config.max_changes, 1);
+        assert_eq!(config.max_walk_mins, 5);
+        assert_eq!(config.max_results, SearchConfig::default().max_results);
+    }
+
+    #[test]
+    fn overrides_beyond_caps_are_rejected() {
+        let too_many_changes = SearchOverrides {
+            max_changes: Some(MAX_CHANGES_CAP + 1),
+            ..Default::default()
+        };
+        assert_eq!(
+            too_many_changes.validate().unwrap_err(),
+            "max_changes must be between 0 and 3, got 4"
+        );
+
+        let no_results = SearchOverrides {
+            max_results: Some(0),
+            ..Default::default()
+        };
+        assert!(no_results.validate().is_err());
+
+        let negative_connection = SearchOverrides {
+            min_connection_mins: Some(-1),
+            ..Default::default()
+        };
+        assert!(negative_connection.validate().is_err());
+
+        let heavy_price = SearchOverrides {
+            price_weight_mins: Some(PRICE_WEIGHT_MINS_CAP + 1),
+            ..Default::default()
+        };
+        assert!(heavy_price.validate().is_err());
+
+        let unknown_criterion = SearchOverrides {
+            dominance: Some("changes,stops".to_string()),
+            ..Default::default()
+        };
+        assert!(unknown_criterion.validate().is_err());
+
+        assert!(SearchOverrides::default().validate().is_ok());
+    }
+
+    #[test]
+    fn dominance_can_be_overridden() {
+        let req: PlanJourneyRequest = serde_json::from_str(
+            r#"{"service_id": "S", "position": 0, "destination": "BRI", "board_station": "PAD",
+                "dominance": "changes,duration,walking"}"#,
+        )
+        .unwrap();
+        assert!(req.overrides.validate().is_ok());
+
+        let config = req.overrides.apply(&SearchConfig::default());
+        assert!(config.dominance.contains(DominanceCriterion::Walking));
+        assert!(!config.dominance.contains(DominanceCriterion::Price));
+    }
+}
+
+/// Tests pinning the JSON shapes clients depend on. A change that breaks
+/// one of these breaks existing clients: add fields rather than rename or
+/// remove them.
+#[cfg(test)]
+mod contract_tests {
+    use super::super::compact::{SHORT_NAMES, compact_value};
+    use super::*;
+    use crate::domain::CallIndex;
+    use crate::testing::{ServiceBuilder, crs};
+    use chrono::Duration;
+    use serde_json::json;
+
+    fn journey() -> Journey {
+        let service = ServiceBuilder::new("ABC123")
+            .headcode("1A23")
+            .operator("Great Western Railway", "GW")
+            .call("PAD")
+            .named("London Paddington")
+            .dep("10:00")
+            .platform("1")
+            .call("RDG")
+            .named("Reading")
+            .arr("10:25")
+            .dep("10:27")
+            .call("SWI")
+            .named("Swindon")
+            .arr("10:52")
+            .build();
+        let leg = Leg::new(service, CallIndex(0), CallIndex(2)).unwrap();
+        let walk = Walk::new(crs("SWI"), crs("SWX"), Duration::minutes(4));
+        Journey::new(vec![Segment::Train(leg), Segment::Walk(walk)]).unwrap()
+    }
+
+    #[test]
+    fn journey_shape() {
+        let journey = journey();
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+        result.set_warnings(WarningKind::Closure, vec!["Swindon is closed".into()]);
+
+        let station = |crs: &str, name: &str, time: Option<&str>, platform: Option<&str>| {
+            let datetime = time.map(|t| format!("2024-03-15T{t}:00+00:00"));
+            json!({
+                "crs": crs,
+                "name": name,
+                "time": time,
+                "scheduled_time": time,
+                "datetime": datetime,
+                "scheduled_datetime": datetime,
+                "platform": platform,
+            })
+        };
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({
+                "segments": [
+                    {
+                        "type": "Train",
+                        "operator": "Great Western Railway",
+                        "headcode": "1A23",
+                        "origin": station("PAD", "London Paddington", Some("10:00"), Some("1")),
+                        "destination": station("SWI", "Swindon", Some("10:52"), None),
+                        "stops": [station("RDG", "Reading", Some("10:25"), None)],
+                    },
+                    {
+                        "type": "Walk",
+                        "from": station("SWI", "SWI", None, None),
+                        "to": station("SWX", "SWX", None, None),
+                        "duration_mins": 4,
+                    },
+                ],
+                "departure_time": "10:00",
+                "departure_datetime": "2024-03-15T10:00:00+00:00",
+                "arrival_time": "10:56",
+                "arrival_datetime": "2024-03-15T10:56:00+00:00",
+                "duration_mins": 56,
+                "changes": 0,
+                "closure_warnings": ["Swindon is closed"],
+                "warnings": [{"kind": "closure", "message": "Swindon is closed"}],
+            })
+        );
+    }
+
+    #[test]
+    fn warnings_are_labelled_and_ordered_by_kind() {
+        let mut result = JourneyResult::from_journey(&journey(), &TimeFormat::default());
+        result.set_warnings(WarningKind::StationMessage, vec!["Swindon: lifts".into()]);
+        result.set_warnings(WarningKind::Access, vec!["Stairs".into()]);
+        result.set_warnings(WarningKind::Routeing, vec!["Not via".into()]);
+        // Setting a kind again replaces its warnings
+        result.set_warnings(WarningKind::Access, vec!["Lift".into(), "Stairs".into()]);
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            json["warnings"],
+            json!([
+                {"kind": "routeing", "message": "Not via"},
+                {"kind": "access", "message": "Lift"},
+                {"kind": "access", "message": "Stairs"},
+                {"kind": "station_message", "message": "Swindon: lifts"},
+            ])
+        );
+        assert_eq!(json["routeing_warnings"], json!(["Not via"]));
+        assert_eq!(json["access_warnings"], json!(["Lift", "Stairs"]));
+        assert_eq!(json["station_messages"], json!(["Swindon: lifts"]));
+    }
+
+    #[test]
+    fn optional_fields_are_left_out_when_unset() {
+        let json = serde_json::to_value(JourneyResult::from_journey(
+            &journey(),
+            &TimeFormat::default(),
+        ))
+        .unwrap();
+        for field in [
+            "id",
+            "duration_from_now_mins",
+            "runs_every_mins",
+            "routeing_warnings",
+            "warnings",
+            "confidence",
+            "price_band",
+            "rank_reasons",
+            "continuation_hint",
+            "alternative_endings",
+        ] {
+            assert!(json.get(field).is_none(), "{field} should be left out");
+        }
+        let leg = &json["segments"][0];
+        for field in ["alighting", "boarding", "delay_reason", "cancel_reason"] {
+            assert!(leg.get(field).is_none(), "{field} should be left out");
+        }
+    }
+
+    #[test]
+    fn requests_accept_compact_names() {
+        let long: PlanJourneyRequest = serde_json::from_value(json!({
+            "service_id": "S", "position": 2, "destination": "BRI", "board_station": "PAD",
+        }))
+        .unwrap();
+        let short: PlanJourneyRequest = serde_json::from_value(json!({
+            "sid": "S", "pos": 2, "dst": "BRI", "bs": "PAD", "max_changes": 1,
+        }))
+        .unwrap();
+        assert_eq!(short.service_id, long.service_id);
+        assert_eq!(short.position, long.position);
+        assert_eq!(short.destination, long.destination);
+        assert_eq!(short.board_station, long.board_station);
+        assert_eq!(short.overrides.max_changes, Some(1));
+
+        let watch: CreateWatchRequest = serde_json::from_value(json!({"jid": "J1"})).unwrap();
+        assert_eq!(watch.journey_id, "J1");
+        assert_eq!(watch.channels, None);
+
+        // The aliases are the names compact responses use
+        for (long, short) in [
+            ("service_id", "sid"),
+            ("position", "pos"),
+            ("destination", "dst"),
+            ("board_station", "bs"),
+            ("journey_id", "jid"),
+        ] {
+            assert!(SHORT_NAMES.contains(&(long, short)));
+        }
+    }
+
+    #[test]
+    fn session_round_trips_into_a_plan_request() {
+        let session = SessionResponse {
+            service_id: "S".into(),
+            board_station: "PAD".into(),
+            position: 1,
+            destination: "BRI".into(),
+        };
+        let compact = compact_value(serde_json::to_value(&session).unwrap());
+        let req: PlanJourneyRequest = serde_json::from_value(compact).unwrap();
+        assert_eq!(req.service_id, "S");
+        assert_eq!(req.board_station, "PAD");
+        assert_eq!(req.position, 1);
+        assert_eq!(req.destination, "BRI");
+    }
 }
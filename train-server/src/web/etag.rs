@@ -0,0 +1,177 @@
+//! Conditional requests for polled endpoints.
+//!
+//! Clients on a train poll boards and journeys every minute or so, and most
+//! of the time nothing has changed. Responses get an `ETag` computed from
+//! their body; a GET carrying a matching `If-None-Match` gets an empty 304
+//! instead. Journey IDs in the body are fingerprints (see
+//! [`crate::domain::Journey::fingerprint`]), which don't depend on the
+//! Darwin IDs a board refresh changes, so re-planning an unchanged journey
+//! produces an identical body and therefore the same tag.
+//!
+//! Tags are weak because the compression layer may re-encode the body.
+//! Bodies too large to buffer are passed through untagged.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+
+/// Largest response body that will be buffered to compute a tag.
+const MAX_TAGGED_BODY: usize = 4 * 1024 * 1024;
+
+/// Compute the entity tag for a response body.
+pub fn etag_for(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("W/\"{hex}\"")
+}
+
+/// Whether an `If-None-Match` header matches the given tag.
+///
+/// Uses weak comparison, as RFC 9110 requires for `If-None-Match`.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let wanted = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == wanted)
+}
+
+/// Tag successful responses and answer matching conditional GETs with 304.
+///
+/// Other methods get the tag too (so a client can poll the GET equivalent
+/// of a POST), but are always answered in full.
+pub async fn conditional(request: Request, next: Next) -> Response {
+    let conditional_get = matches!(*request.method(), Method::GET | Method::HEAD);
+    let request_headers = request.headers().clone();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut stream = body.into_data_stream();
+    let mut chunks = Vec::new();
+    let mut len = 0;
+    while let Some(chunk) = stream.next().await {
+        let Ok(chunk) = chunk else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        len += chunk.len();
+        chunks.push(chunk);
+        if len > MAX_TAGGED_BODY {
+            // Send what's been read, then the rest as it comes
+            let read = futures::stream::iter(chunks.into_iter().map(Ok));
+            return Response::from_parts(parts, Body::from_stream(read.chain(stream)));
+        }
+    }
+    let bytes = chunks.concat();
+
+    let etag = etag_for(&bytes);
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+
+    if conditional_get && if_none_match(&request_headers, &etag) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::get};
+
+    fn with_if_none_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn etag_depends_only_on_body() {
+        assert_eq!(etag_for(b"abc"), etag_for(b"abc"));
+        assert_ne!(etag_for(b"abc"), etag_for(b"abd"));
+        assert!(etag_for(b"").starts_with("W/\""));
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let tag = etag_for(b"abc");
+        let strong = tag.trim_start_matches("W/");
+
+        assert!(if_none_match(&with_if_none_match(&tag), &tag));
+        assert!(if_none_match(&with_if_none_match(strong), &tag));
+        assert!(if_none_match(&with_if_none_match("*"), &tag));
+        assert!(if_none_match(
+            &with_if_none_match(&format!("\"other\", {tag}")),
+            &tag
+        ));
+        assert!(!if_none_match(&with_if_none_match("\"other\""), &tag));
+        assert!(!if_none_match(&HeaderMap::new(), &tag));
+    }
+
+    /// Serve `router` with the conditional middleware, returning its base URL.
+    async fn serve(router: Router) -> String {
+        let router = router.layer(middleware::from_fn(conditional));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn unchanged_body_gets_304() {
+        let base = serve(Router::new().route("/board", get(|| async { "same every time" }))).await;
+        let client = reqwest::Client::new();
+        let url = format!("{base}/board");
+
+        let first = client.get(&url).send().await.unwrap();
+        assert_eq!(first.status(), 200);
+        let etag = first.headers()["etag"].clone();
+
+        let second = client
+            .get(&url)
+            .header("if-none-match", etag.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(second.status(), 304);
+        assert_eq!(second.headers()["etag"], etag);
+        assert!(second.bytes().await.unwrap().is_empty());
+
+        let stale = client
+            .get(&url)
+            .header("if-none-match", "W/\"stale\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(stale.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn large_body_is_sent_untagged() {
+        let large = "x".repeat(MAX_TAGGED_BODY + 1);
+        let body = large.clone();
+        let base = serve(Router::new().route("/board", get(move || async move { body }))).await;
+
+        let response = reqwest::get(format!("{base}/board")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().get("etag").is_none());
+        assert_eq!(response.text().await.unwrap(), large);
+    }
+}
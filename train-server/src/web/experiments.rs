@@ -0,0 +1,405 @@
+//! Per-request experiments for planner behaviours.
+//!
+//! Each [`Experiment`] switches one planner behaviour on or off. An
+//! experiment with a rollout percentage is on for that share of subjects
+//! (issued API keys, or sessions for anonymous users), chosen by a stable
+//! hash so a subject sees the same variant on every request. Overrides pin
+//! a variant for a particular issued API key.
+//!
+//! Experiments without a rollout or override leave the configured
+//! behaviour alone. Every evaluation is logged as an exposure, so the
+//! variants can be compared from the logs.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::planner::SearchConfig;
+
+use super::api_keys::ApiKeys;
+
+/// Header carrying the client's API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// A planner behaviour that can be switched per request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Experiment {
+    /// Prune BFS states using learned travel times
+    TravelTimePruning,
+
+    /// Collapse frequent equivalent journeys into one
+    FrequentCollapse,
+}
+
+impl Experiment {
+    /// All experiments.
+    pub const ALL: [Experiment; 2] = [Experiment::TravelTimePruning, Experiment::FrequentCollapse];
+
+    /// Name used in configuration and logs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Experiment::TravelTimePruning => "travel_time_pruning",
+            Experiment::FrequentCollapse => "frequent_collapse",
+        }
+    }
+
+    /// Switch the behaviour on or off in a search configuration.
+    fn apply(self, config: &mut SearchConfig, enabled: bool) {
+        match self {
+            Experiment::TravelTimePruning => config.travel_time_pruning = enabled,
+            Experiment::FrequentCollapse => {
+                if !enabled {
+                    config.frequent_max_headway_mins = 0;
+                } else if config.frequent_max_headway_mins == 0 {
+                    config.frequent_max_headway_mins =
+                        SearchConfig::default().frequent_max_headway_mins;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Experiment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Experiment {
+    type Err = ExperimentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Experiment::ALL
+            .into_iter()
+            .find(|e| e.as_str() == s)
+            .ok_or_else(|| ExperimentError::UnknownExperiment(s.to_string()))
+    }
+}
+
+/// Errors parsing experiment configuration.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExperimentError {
+    /// No experiment has this name
+    #[error("unknown experiment: {0}")]
+    UnknownExperiment(String),
+
+    /// An entry isn't of the expected form
+    #[error("invalid experiment setting: {0}")]
+    Invalid(String),
+}
+
+/// Rollouts and overrides for all experiments.
+#[derive(Debug, Clone, Default)]
+pub struct Experiments {
+    /// Percentage of subjects (0-100) with each experiment on
+    rollouts: HashMap<Experiment, u8>,
+
+    /// Pinned variants, by API key
+    overrides: HashMap<String, HashMap<Experiment, bool>>,
+}
+
+impl Experiments {
+    /// No experiments; every request gets the configured behaviour.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn an experiment on for `percent` of subjects (capped at 100).
+    pub fn with_rollout(mut self, experiment: Experiment, percent: u8) -> Self {
+        self.rollouts.insert(experiment, percent.min(100));
+        self
+    }
+
+    /// Pin an experiment on or off for one API key.
+    pub fn with_override(
+        mut self,
+        api_key: impl Into<String>,
+        experiment: Experiment,
+        enabled: bool,
+    ) -> Self {
+        self.overrides
+            .entry(api_key.into())
+            .or_default()
+            .insert(experiment, enabled);
+        self
+    }
+
+    /// Parse rollouts of the form `travel_time_pruning=50,frequent_collapse=0`.
+    pub fn parse_rollouts(mut self, spec: &str) -> Result<Self, ExperimentError> {
+        for entry in entries(spec) {
+            let (name, percent) = entry
+                .split_once('=')
+                .ok_or_else(|| ExperimentError::Invalid(entry.to_string()))?;
+            let percent: u8 = percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|p| *p <= 100)
+                .ok_or_else(|| ExperimentError::Invalid(entry.to_string()))?;
+            self = self.with_rollout(name.trim().parse()?, percent);
+        }
+        Ok(self)
+    }
+
+    /// Parse overrides of the form `key1:travel_time_pruning=off,key2:frequent_collapse=on`.
+    pub fn parse_overrides(mut self, spec: &str) -> Result<Self, ExperimentError> {
+        for entry in entries(spec) {
+            let invalid = || ExperimentError::Invalid(entry.to_string());
+            let (key, setting) = entry.rsplit_once(':').ok_or_else(invalid)?;
+            let (name, variant) = setting.split_once('=').ok_or_else(invalid)?;
+            let enabled = match variant.trim() {
+                "on" => true,
+                "off" => false,
+                _ => return Err(invalid()),
+            };
+            self = self.with_override(key.trim(), name.trim().parse()?, enabled);
+        }
+        Ok(self)
+    }
+
+    /// Whether there are no rollouts or overrides.
+    pub fn is_empty(&self) -> bool {
+        self.rollouts.is_empty() && self.overrides.is_empty()
+    }
+
+    /// The variant of an experiment for a subject, or `None` if the
+    /// experiment isn't running for it.
+    pub fn variant(&self, experiment: Experiment, subject: &Subject<'_>) -> Option<bool> {
+        if let Subject::ApiKey(key) = subject
+            && let Some(&enabled) = self.overrides.get(*key).and_then(|o| o.get(&experiment))
+        {
+            return Some(enabled);
+        }
+        let percent = *self.rollouts.get(&experiment)?;
+        Some(bucket(experiment, subject.id()) < percent)
+    }
+
+    /// The search configuration for a subject, with every running
+    /// experiment applied and its exposure logged.
+    pub fn configure(&self, base: &SearchConfig, subject: &Subject<'_>) -> SearchConfig {
+        let mut config = base.clone();
+        for experiment in Experiment::ALL {
+            if let Some(enabled) = self.variant(experiment, subject) {
+                info!(
+                    experiment = experiment.as_str(),
+                    variant = if enabled { "on" } else { "off" },
+                    subject = subject.kind(),
+                    bucket = bucket(experiment, subject.id()),
+                    "Experiment exposure"
+                );
+                experiment.apply(&mut config, enabled);
+            }
+        }
+        config
+    }
+}
+
+/// Who a request is assigned variants for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subject<'a> {
+    /// A client identified by its API key
+    ApiKey(&'a str),
+
+    /// An anonymous user, identified by their session
+    Session(&'a str),
+}
+
+impl<'a> Subject<'a> {
+    /// The subject for a request: its API key if it was issued one,
+    /// otherwise its session.
+    ///
+    /// Any other key is ignored, so clients can't pick their variants by
+    /// sending a key named in an override.
+    pub fn for_request(headers: &'a HeaderMap, keys: &ApiKeys, session_id: &'a str) -> Self {
+        keys.authenticate(headers)
+            .map_or(Subject::Session(session_id), Subject::ApiKey)
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            Subject::ApiKey(id) | Subject::Session(id) => id,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Subject::ApiKey(_) => "api_key",
+            Subject::Session(_) => "session",
+        }
+    }
+}
+
+/// Stable bucket (0-99) for a subject in an experiment.
+///
+/// Hashed per experiment, so the same subjects aren't always the ones
+/// trying new behaviour.
+fn bucket(experiment: Experiment, subject: &str) -> u8 {
+    let digest = Sha256::new()
+        .chain_update(experiment.as_str())
+        .chain_update(b":")
+        .chain_update(subject)
+        .finalize();
+    let n = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+    (n % 100) as u8
+}
+
+/// Non-empty comma-separated entries.
+fn entries(spec: &str) -> impl Iterator<Item = &str> {
+    spec.split(',').map(str::trim).filter(|e| !e.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn unconfigured_experiments_leave_config_alone() {
+        let experiments = Experiments::new();
+        let subject = Subject::Session("abc");
+        assert_eq!(
+            experiments.variant(Experiment::TravelTimePruning, &subject),
+            None
+        );
+
+        let config = experiments.configure(&SearchConfig::default(), &subject);
+        assert!(config.travel_time_pruning);
+        assert_eq!(config.frequent_max_headway_mins, 15);
+    }
+
+    #[test]
+    fn rollout_extremes() {
+        let on = Experiments::new().with_rollout(Experiment::FrequentCollapse, 100);
+        let off = Experiments::new().with_rollout(Experiment::FrequentCollapse, 0);
+
+        for i in 0..50 {
+            let id = format!("service_{i}");
+            let subject = Subject::Session(&id);
+            assert_eq!(
+                on.variant(Experiment::FrequentCollapse, &subject),
+                Some(true)
+            );
+            assert_eq!(
+                off.variant(Experiment::FrequentCollapse, &subject),
+                Some(false)
+            );
+        }
+    }
+
+    #[test]
+    fn rollout_is_stable_and_roughly_proportional() {
+        let experiments = Experiments::new().with_rollout(Experiment::TravelTimePruning, 30);
+        let ids: Vec<String> = (0..1000).map(|i| format!("key_{i}")).collect();
+
+        let enabled = ids
+            .iter()
+            .filter(|id| {
+                let subject = Subject::ApiKey(id);
+                let first = experiments.variant(Experiment::TravelTimePruning, &subject);
+                assert_eq!(
+                    first,
+                    experiments.variant(Experiment::TravelTimePruning, &subject)
+                );
+                first == Some(true)
+            })
+            .count();
+        assert!((200..400).contains(&enabled), "{enabled} of 1000 enabled");
+    }
+
+    #[test]
+    fn override_beats_rollout_for_api_key_only() {
+        let experiments = Experiments::new()
+            .with_rollout(Experiment::TravelTimePruning, 0)
+            .with_override("beta", Experiment::TravelTimePruning, true);
+
+        assert_eq!(
+            experiments.variant(Experiment::TravelTimePruning, &Subject::ApiKey("beta")),
+            Some(true)
+        );
+        // A session ID that happens to match the key isn't overridden
+        assert_eq!(
+            experiments.variant(Experiment::TravelTimePruning, &Subject::Session("beta")),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn configure_applies_variants() {
+        let experiments = Experiments::new()
+            .with_override("k", Experiment::TravelTimePruning, false)
+            .with_override("k", Experiment::FrequentCollapse, false);
+
+        let config = experiments.configure(&SearchConfig::default(), &Subject::ApiKey("k"));
+        assert!(!config.travel_time_pruning);
+        assert_eq!(config.frequent_max_headway(), None);
+    }
+
+    #[test]
+    fn parse_settings() {
+        let experiments = Experiments::new()
+            .parse_rollouts("travel_time_pruning=0, frequent_collapse=100")
+            .unwrap()
+            .parse_overrides("beta:travel_time_pruning=on")
+            .unwrap();
+
+        let anon = Subject::Session("s");
+        assert_eq!(
+            experiments.variant(Experiment::TravelTimePruning, &anon),
+            Some(false)
+        );
+        assert_eq!(
+            experiments.variant(Experiment::FrequentCollapse, &anon),
+            Some(true)
+        );
+        assert_eq!(
+            experiments.variant(Experiment::TravelTimePruning, &Subject::ApiKey("beta")),
+            Some(true)
+        );
+        assert!(Experiments::new().parse_rollouts("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Experiments::new()
+                .parse_rollouts("warp_drive=10")
+                .unwrap_err(),
+            ExperimentError::UnknownExperiment("warp_drive".to_string())
+        );
+        assert!(matches!(
+            Experiments::new().parse_rollouts("travel_time_pruning=101"),
+            Err(ExperimentError::Invalid(_))
+        ));
+        assert!(matches!(
+            Experiments::new().parse_overrides("beta:frequent_collapse=maybe"),
+            Err(ExperimentError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn subject_prefers_issued_api_key() {
+        let keys = ApiKeys::parse("beta");
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            Subject::for_request(&headers, &keys, "session"),
+            Subject::Session("session")
+        );
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("beta"));
+        assert_eq!(
+            Subject::for_request(&headers, &keys, "session"),
+            Subject::ApiKey("beta")
+        );
+
+        // An override for a key that wasn't issued can't be claimed
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("made-up"));
+        assert_eq!(
+            Subject::for_request(&headers, &keys, "session"),
+            Subject::Session("session")
+        );
+    }
+}
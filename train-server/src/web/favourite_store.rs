@@ -0,0 +1,244 @@
+//! Destinations users have saved under a name, e.g. "Home" for Sheffield.
+//!
+//! Favourites belong to the same owners as watches (see
+//! [`WatchOwner`]): a client's API key, or a browser's watcher cookie. They
+//! are kept in memory for as long as their owner keeps using them, so like
+//! watches they are local to this instance and don't survive a restart.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use moka::future::Cache as MokaCache;
+
+use crate::domain::Crs;
+use crate::error::{Classify, ErrorKind};
+
+use super::watch_store::WatchOwner;
+
+/// How long an owner's favourites are kept after they were last used.
+pub const FAVOURITES_IDLE_TTL: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Maximum number of owners with favourites.
+const MAX_OWNERS: u64 = 10_000;
+
+/// Most favourites one owner can save.
+pub const MAX_FAVOURITES_PER_OWNER: usize = 10;
+
+/// Longest name a favourite can have, in characters.
+pub const MAX_FAVOURITE_NAME_LEN: usize = 40;
+
+/// A destination saved under a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Favourite {
+    /// What the owner calls it, e.g. "Home"
+    pub name: String,
+
+    /// The station it stands for
+    pub station: Crs,
+}
+
+/// Errors saving a favourite.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FavouriteError {
+    /// The name is blank, too long or has control characters in it
+    #[error("Favourite names must be 1 to {MAX_FAVOURITE_NAME_LEN} printable characters")]
+    InvalidName,
+
+    /// The owner already has as many favourites as allowed
+    #[error("At most {0} favourites can be saved")]
+    TooMany(usize),
+}
+
+impl Classify for FavouriteError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            FavouriteError::InvalidName | FavouriteError::TooMany(_) => ErrorKind::Permanent,
+        }
+    }
+}
+
+/// Whether two names refer to the same favourite: names are matched
+/// ignoring case, so "home" finds "Home".
+fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// In-memory store of each owner's favourites, in the order they were
+/// first saved.
+#[derive(Clone)]
+pub struct FavouriteStore {
+    favourites: MokaCache<WatchOwner, Arc<Vec<Favourite>>>,
+}
+
+impl FavouriteStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            favourites: MokaCache::builder()
+                .time_to_idle(FAVOURITES_IDLE_TTL)
+                .max_capacity(MAX_OWNERS)
+                .build(),
+        }
+    }
+
+    /// Save a station under a name for an owner.
+    ///
+    /// Saving a name the owner already has points it at the new station,
+    /// keeping its place in the list.
+    pub async fn set(
+        &self,
+        owner: WatchOwner,
+        name: &str,
+        station: Crs,
+    ) -> Result<Favourite, FavouriteError> {
+        let name = name.trim();
+        if name.is_empty()
+            || name.chars().count() > MAX_FAVOURITE_NAME_LEN
+            || name.chars().any(char::is_control)
+        {
+            return Err(FavouriteError::InvalidName);
+        }
+
+        let mut favourites = self.list(&owner).await;
+        let favourite = Favourite {
+            name: name.to_string(),
+            station,
+        };
+        match favourites.iter().position(|f| same_name(&f.name, name)) {
+            Some(i) => favourites[i] = favourite.clone(),
+            None if favourites.len() >= MAX_FAVOURITES_PER_OWNER => {
+                return Err(FavouriteError::TooMany(MAX_FAVOURITES_PER_OWNER));
+            }
+            None => favourites.push(favourite.clone()),
+        }
+        self.favourites.insert(owner, Arc::new(favourites)).await;
+        Ok(favourite)
+    }
+
+    /// An owner's favourites, in the order they were first saved.
+    pub async fn list(&self, owner: &WatchOwner) -> Vec<Favourite> {
+        self.favourites
+            .get(owner)
+            .await
+            .map(|f| f.as_ref().clone())
+            .unwrap_or_default()
+    }
+
+    /// One of an owner's favourites, by name.
+    pub async fn get(&self, owner: &WatchOwner, name: &str) -> Option<Favourite> {
+        self.list(owner)
+            .await
+            .into_iter()
+            .find(|f| same_name(&f.name, name.trim()))
+    }
+
+    /// Forget a favourite. Returns `false` if the owner has none by that
+    /// name.
+    pub async fn remove(&self, owner: &WatchOwner, name: &str) -> bool {
+        let mut favourites = self.list(owner).await;
+        let before = favourites.len();
+        favourites.retain(|f| !same_name(&f.name, name.trim()));
+        if favourites.len() == before {
+            return false;
+        }
+        if favourites.is_empty() {
+            self.favourites.invalidate(owner).await;
+        } else {
+            self.favourites
+                .insert(owner.clone(), Arc::new(favourites))
+                .await;
+        }
+        true
+    }
+}
+
+impl Default for FavouriteStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn favourites_belong_to_their_owner() {
+        let store = FavouriteStore::new();
+        let alice = WatchOwner::api_key("alice");
+        let bob = WatchOwner::api_key("bob");
+
+        store.set(alice.clone(), "Home", crs("SHF")).await.unwrap();
+        store.set(alice.clone(), "Work", crs("MAN")).await.unwrap();
+        store.set(bob.clone(), "Home", crs("LDS")).await.unwrap();
+
+        let names: Vec<String> = store
+            .list(&alice)
+            .await
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(names, ["Home", "Work"]);
+        assert_eq!(
+            store.get(&bob, "home").await.map(|f| f.station),
+            Some(crs("LDS"))
+        );
+
+        assert!(!store.remove(&bob, "Work").await);
+        assert!(store.remove(&alice, "work").await);
+        assert_eq!(store.list(&alice).await.len(), 1);
+        assert_eq!(store.list(&bob).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn saving_a_name_again_replaces_it_in_place() {
+        let store = FavouriteStore::new();
+        let owner = WatchOwner::session("token");
+        store.set(owner.clone(), "Home", crs("SHF")).await.unwrap();
+        store.set(owner.clone(), "Work", crs("MAN")).await.unwrap();
+
+        store
+            .set(owner.clone(), " home ", crs("DON"))
+            .await
+            .unwrap();
+
+        let favourites = store.list(&owner).await;
+        assert_eq!(favourites.len(), 2);
+        assert_eq!(favourites[0].name, "home");
+        assert_eq!(favourites[0].station, crs("DON"));
+    }
+
+    #[tokio::test]
+    async fn names_and_numbers_are_limited() {
+        let store = FavouriteStore::new();
+        let owner = WatchOwner::api_key("key");
+        for name in [
+            "",
+            "  ",
+            "tab\there",
+            &"x".repeat(MAX_FAVOURITE_NAME_LEN + 1),
+        ] {
+            assert_eq!(
+                store.set(owner.clone(), name, crs("SHF")).await,
+                Err(FavouriteError::InvalidName)
+            );
+        }
+
+        for i in 0..MAX_FAVOURITES_PER_OWNER {
+            store
+                .set(owner.clone(), &format!("F{i}"), crs("SHF"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            store.set(owner.clone(), "One more", crs("SHF")).await,
+            Err(FavouriteError::TooMany(MAX_FAVOURITES_PER_OWNER))
+        );
+        // Existing names can still be changed
+        assert!(store.set(owner, "F0", crs("MAN")).await.is_ok());
+    }
+}
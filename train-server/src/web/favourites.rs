@@ -0,0 +1,152 @@
+//! REST API for favourite destinations.
+//!
+//! Clients save stations under names ("Home" = SHF) and can then plan from
+//! the train remembered in their session to one by name, so going home is
+//! one tap once the train is identified (see [`super::favourite_store`]).
+//! Favourites are owned like watches: by API key, or by the watcher cookie,
+//! which saving a favourite issues or renews for as long as favourites are
+//! kept.
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::Response,
+    routing::{get, post, put},
+};
+
+use super::compact::{FormatQuery, json_response};
+use super::dto::{FavouriteListResponse, FavouriteResult, SaveFavouriteRequest, SearchOverrides};
+use super::favourite_store::{FAVOURITES_IDLE_TTL, Favourite};
+use super::routes::{AppError, parse_station, plan_for_session};
+use super::session::Session;
+use super::state::AppState;
+use super::watch_store::{WatchOwner, random_id};
+use super::watches::{api_key, owner, set_watcher_cookie, watcher_cookie};
+
+/// Routes for the favourites API, relative to the versioned API prefix.
+pub fn favourite_routes() -> Router<AppState> {
+    Router::new()
+        .route("/favourites", get(list_favourites))
+        .route(
+            "/favourites/:name",
+            put(save_favourite).delete(delete_favourite),
+        )
+        .route("/favourites/:name/plan", post(plan_to_favourite))
+}
+
+async fn favourite_result(state: &AppState, favourite: Favourite) -> FavouriteResult {
+    FavouriteResult {
+        station_name: state.station_names.get(&favourite.station).await,
+        name: favourite.name,
+        station: favourite.station.as_str().to_string(),
+    }
+}
+
+/// The caller's favourites, as shown with identified trains.
+pub(super) async fn favourites_for(state: &AppState, headers: &HeaderMap) -> Vec<Favourite> {
+    match owner(headers) {
+        Some(owner) => state.favourites.list(&owner).await,
+        None => Vec::new(),
+    }
+}
+
+/// List the caller's favourites, in the order they were first saved.
+async fn list_favourites(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let mut favourites = Vec::new();
+    for favourite in favourites_for(&state, &headers).await {
+        favourites.push(favourite_result(&state, favourite).await);
+    }
+    json_response(&FavouriteListResponse { favourites }, &format)
+}
+
+/// Save a station under a name, replacing any favourite of that name.
+///
+/// Browsers are given a watcher cookie, or have theirs renewed, lasting as
+/// long as favourites are kept.
+async fn save_favourite(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(format): Query<FormatQuery>,
+    Json(req): Json<SaveFavouriteRequest>,
+) -> Result<Response, AppError> {
+    let station = parse_station(&state, &req.station, "favourite station").await?;
+
+    let (owner, cookie) = match api_key(&headers) {
+        Some(key) => (WatchOwner::api_key(key), None),
+        None => {
+            let token = watcher_cookie(&headers)
+                .map(str::to_string)
+                .unwrap_or_else(random_id);
+            let max_age = FAVOURITES_IDLE_TTL.as_secs() as u32;
+            (
+                WatchOwner::session(&token),
+                Some(set_watcher_cookie(&token, max_age)),
+            )
+        }
+    };
+
+    let favourite = state.favourites.set(owner, &name, station).await?;
+    let result = favourite_result(&state, favourite).await;
+    let mut response = json_response(&result, &format);
+    if let Some(cookie) = cookie {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
+}
+
+/// Forget a favourite.
+async fn delete_favourite(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let removed = match owner(&headers) {
+        Some(owner) => state.favourites.remove(&owner, &name).await,
+        None => false,
+    };
+    if !removed {
+        return Err(AppError::NotFound {
+            message: format!("Favourite {name} not found"),
+        });
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Plan from the train remembered in the session cookie to a favourite,
+/// responding as planning a journey does.
+async fn plan_to_favourite(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let favourite = match owner(&headers) {
+        Some(owner) => state.favourites.get(&owner, &name).await,
+        None => None,
+    }
+    .ok_or_else(|| AppError::NotFound {
+        message: format!("Favourite {name} not found"),
+    })?;
+    let mut session =
+        Session::from_headers(&headers, &state.session_key).ok_or_else(|| AppError::NotFound {
+            message: "No train remembered for this session".to_string(),
+        })?;
+    session.destination = favourite.station;
+    session.destination_group = false;
+
+    plan_for_session(
+        &state,
+        &headers,
+        &format,
+        session,
+        &SearchOverrides::default(),
+        true,
+    )
+    .await
+}
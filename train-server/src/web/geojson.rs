@@ -0,0 +1,290 @@
+//! GeoJSON rendering of journeys.
+//!
+//! Each train leg becomes a `LineString` through its calling points (straight
+//! lines between station coordinates, not the actual track), each walk a
+//! `LineString` between its two stations, and each change a `Point`.
+//! Stations without known coordinates are skipped.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::domain::{Crs, Journey, Leg, Segment, Walk};
+use crate::stations::StationLocation;
+
+/// A GeoJSON `FeatureCollection`.
+#[derive(Debug, Serialize)]
+pub struct FeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    pub features: Vec<Feature>,
+}
+
+/// A GeoJSON `Feature`.
+#[derive(Debug, Serialize)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    pub geometry: Geometry,
+    pub properties: FeatureProperties,
+}
+
+/// GeoJSON geometry. Positions are `[longitude, latitude]`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point { coordinates: [f64; 2] },
+    LineString { coordinates: Vec<[f64; 2]> },
+}
+
+/// Properties attached to each feature.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FeatureProperties {
+    /// A train leg
+    Train {
+        operator: String,
+        headcode: Option<String>,
+        from: String,
+        to: String,
+    },
+    /// A walk between stations
+    Walk {
+        from: String,
+        to: String,
+        duration_mins: i64,
+    },
+    /// A station where the user changes trains
+    Change { crs: String, name: String },
+}
+
+impl Feature {
+    fn new(geometry: Geometry, properties: FeatureProperties) -> Self {
+        Self {
+            kind: "Feature",
+            geometry,
+            properties,
+        }
+    }
+}
+
+/// Render a journey as a GeoJSON feature collection.
+///
+/// `locations` should contain every station called at by the journey's legs;
+/// see [`journey_stations`].
+pub fn journey_to_geojson(
+    journey: &Journey,
+    locations: &HashMap<Crs, StationLocation>,
+) -> FeatureCollection {
+    let position = |crs: &Crs| locations.get(crs).map(|l| [l.longitude, l.latitude]);
+    let mut features = Vec::new();
+
+    for (i, segment) in journey.segments().iter().enumerate() {
+        match segment {
+            Segment::Train(leg) => {
+                // Every leg after the first starts with a change
+                if i > 0
+                    && let Some(coordinates) = position(leg.board_station())
+                {
+                    features.push(Feature::new(
+                        Geometry::Point { coordinates },
+                        FeatureProperties::Change {
+                            crs: leg.board_station().as_str().to_string(),
+                            name: leg.board_station_name().to_string(),
+                        },
+                    ));
+                }
+
+                let coordinates: Vec<[f64; 2]> = leg
+                    .calls()
+                    .iter()
+                    .filter_map(|c| position(&c.station))
+                    .collect();
+                if coordinates.len() >= 2 {
+                    features.push(Feature::new(
+                        Geometry::LineString { coordinates },
+                        train_properties(leg),
+                    ));
+                }
+            }
+            Segment::Walk(walk) => {
+                if let (Some(from), Some(to)) = (position(&walk.from), position(&walk.to)) {
+                    features.push(Feature::new(
+                        Geometry::LineString {
+                            coordinates: vec![from, to],
+                        },
+                        walk_properties(walk),
+                    ));
+                }
+            }
+        }
+    }
+
+    FeatureCollection {
+        kind: "FeatureCollection",
+        features,
+    }
+}
+
+/// All stations whose locations are needed to render a journey.
+pub fn journey_stations(journey: &Journey) -> impl Iterator<Item = Crs> + '_ {
+    journey.segments().iter().flat_map(|segment| match segment {
+        Segment::Train(leg) => leg.calls().iter().map(|c| c.station).collect::<Vec<_>>(),
+        Segment::Walk(walk) => vec![walk.from, walk.to],
+    })
+}
+
+fn train_properties(leg: &Leg) -> FeatureProperties {
+    FeatureProperties::Train {
+        operator: leg.service().operator.clone(),
+        headcode: leg.service().headcode.map(|h| h.to_string()),
+        from: leg.board_station().as_str().to_string(),
+        to: leg.alight_station().as_str().to_string(),
+    }
+}
+
+fn walk_properties(walk: &Walk) -> FeatureProperties {
+    FeatureProperties::Walk {
+        from: walk.from.as_str().to_string(),
+        to: walk.to.as_str().to_string(),
+        duration_mins: walk.duration.num_minutes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, RailTime, Service, ServiceRef};
+    use std::sync::Arc;
+
+    fn date() -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, date()).unwrap()
+    }
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn make_service(id: &str, calls_data: &[(&str, &str, &str)]) -> Arc<Service> {
+        let calls: Vec<Call> = calls_data
+            .iter()
+            .map(|(station, arr, dep)| {
+                let mut call = Call::new(crs(station), format!("{station} station"));
+                if !arr.is_empty() {
+                    call.booked_arrival = Some(time(arr));
+                }
+                if !dep.is_empty() {
+                    call.booked_departure = Some(time(dep));
+                }
+                call
+            })
+            .collect();
+
+        Arc::new(Service {
+            service_ref: ServiceRef::new(id.to_string(), calls[0].station),
+            headcode: None,
+            operator: "Test".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    fn locations() -> HashMap<Crs, StationLocation> {
+        [
+            ("PAD", 51.5154, -0.1755),
+            ("RDG", 51.4588, -0.9718),
+            ("SWI", 51.5655, -1.7855),
+            ("BRI", 51.4491, -2.5813),
+        ]
+        .into_iter()
+        .map(|(c, lat, lon)| (crs(c), StationLocation::new(lat, lon).unwrap()))
+        .collect()
+    }
+
+    fn two_leg_journey() -> Journey {
+        let first = make_service("A", &[("PAD", "", "10:00"), ("RDG", "10:25", "")]);
+        let second = make_service(
+            "B",
+            &[
+                ("RDG", "", "10:35"),
+                ("SWI", "10:55", "10:57"),
+                ("BRI", "11:30", ""),
+            ],
+        );
+        Journey::new(vec![
+            Segment::Train(Leg::new(first, CallIndex(0), CallIndex(1)).unwrap()),
+            Segment::Train(Leg::new(second, CallIndex(0), CallIndex(2)).unwrap()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn legs_and_changes_become_features() {
+        let geojson = journey_to_geojson(&two_leg_journey(), &locations());
+
+        // Leg, change point, leg
+        assert_eq!(geojson.features.len(), 3);
+        assert!(matches!(
+            geojson.features[0].geometry,
+            Geometry::LineString { ref coordinates } if coordinates.len() == 2
+        ));
+        assert!(matches!(
+            geojson.features[1].properties,
+            FeatureProperties::Change { ref crs, .. } if crs == "RDG"
+        ));
+        assert!(matches!(
+            geojson.features[2].geometry,
+            Geometry::LineString { ref coordinates } if coordinates.len() == 3
+        ));
+    }
+
+    #[test]
+    fn coordinates_are_lon_lat() {
+        let geojson = journey_to_geojson(&two_leg_journey(), &locations());
+        let Geometry::LineString { coordinates } = &geojson.features[0].geometry else {
+            panic!("expected LineString");
+        };
+        assert_eq!(coordinates[0], [-0.1755, 51.5154]);
+    }
+
+    #[test]
+    fn unknown_stations_are_skipped() {
+        let mut locs = locations();
+        locs.remove(&crs("SWI"));
+        locs.remove(&crs("PAD"));
+
+        let geojson = journey_to_geojson(&two_leg_journey(), &locs);
+
+        // First leg has only one located station, so no line; change point and
+        // second leg (RDG, BRI) remain
+        assert_eq!(geojson.features.len(), 2);
+    }
+
+    #[test]
+    fn serializes_as_geojson() {
+        let geojson = journey_to_geojson(&two_leg_journey(), &locations());
+        let json = serde_json::to_value(&geojson).unwrap();
+
+        assert_eq!(json["type"], "FeatureCollection");
+        assert_eq!(json["features"][0]["type"], "Feature");
+        assert_eq!(json["features"][0]["geometry"]["type"], "LineString");
+        assert_eq!(json["features"][0]["properties"]["kind"], "train");
+        assert_eq!(json["features"][1]["geometry"]["type"], "Point");
+        assert_eq!(json["features"][1]["properties"]["kind"], "change");
+    }
+
+    #[test]
+    fn journey_stations_lists_all_calls() {
+        let stations: Vec<Crs> = journey_stations(&two_leg_journey()).collect();
+        assert_eq!(
+            stations,
+            vec![crs("PAD"), crs("RDG"), crs("RDG"), crs("SWI"), crs("BRI")]
+        );
+    }
+}
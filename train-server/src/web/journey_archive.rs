@@ -0,0 +1,477 @@
+//! Watched journeys that have finished, kept as a record of how they went.
+//!
+//! Once a watched journey has arrived, whether that's seen by refreshing it
+//! or by the periodic sweep of watches, the journey is archived for
+//! everyone watching it with each train's planned (booked) times beside the
+//! times last observed while it was watched. Only those times, the stations
+//! and the trains' operators and headcodes are kept, not the whole journey,
+//! so the archive can outlive the journey store by far. Each owner's archive
+//! is kept in memory for as long as they keep using it, and can be saved to
+//! a file (see [`ArchiveStore`]) so it survives restarts.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use moka::future::Cache as MokaCache;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Call, Crs, Headcode, Journey, Leg, RailTime};
+use crate::error::{Classify, ErrorKind};
+
+use super::watch_store::WatchOwner;
+
+/// How long an owner's archive is kept after it was last used.
+pub const ARCHIVE_IDLE_TTL: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Maximum number of owners with an archive.
+const MAX_OWNERS: u64 = 10_000;
+
+/// Most journeys kept for one owner; older ones are dropped.
+pub const MAX_ARCHIVED_PER_OWNER: usize = 50;
+
+/// A station a train called at, with its planned and observed times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedCall {
+    /// Station CRS code
+    pub station: Crs,
+
+    /// Station display name
+    pub station_name: String,
+
+    /// Platform, when known
+    pub platform: Option<String>,
+
+    /// Booked time
+    pub planned: Option<RailTime>,
+
+    /// Time last observed: the actual or estimated time, else the booked
+    pub actual: Option<RailTime>,
+}
+
+/// One train of an archived journey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedLeg {
+    /// Operator name
+    pub operator: String,
+
+    /// Headcode, if the board gave one
+    pub headcode: Option<Headcode>,
+
+    /// Where it was boarded, with departure times
+    pub board: ArchivedCall,
+
+    /// Where it was left, with arrival times
+    pub alight: ArchivedCall,
+
+    /// Whether it was cancelled
+    pub cancelled: bool,
+}
+
+impl ArchivedLeg {
+    fn from_leg(leg: &Leg) -> Self {
+        let call = |call: &Call, planned, actual| ArchivedCall {
+            station: call.station,
+            station_name: call.station_name.clone(),
+            platform: call.platform.clone(),
+            planned,
+            actual,
+        };
+        let board = leg.board_call();
+        let alight = leg.alight_call();
+        Self {
+            operator: leg.service().operator.clone(),
+            headcode: leg.service().headcode,
+            board: call(board, board.booked_departure, board.expected_departure()),
+            alight: call(alight, alight.booked_arrival, alight.expected_arrival()),
+            cancelled: leg.is_cancelled(),
+        }
+    }
+}
+
+/// A finished journey, as last observed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedJourney {
+    /// ID the journey was planned with
+    pub journey_id: String,
+
+    /// Its trains, in order; never empty. Walks between them aren't kept
+    legs: Vec<ArchivedLeg>,
+
+    /// When it was archived
+    pub archived: RailTime,
+}
+
+impl ArchivedJourney {
+    /// Archive a journey's trains as they were last observed.
+    ///
+    /// Returns `None` for a journey with no trains.
+    pub fn from_journey(journey_id: &str, journey: &Journey, archived: RailTime) -> Option<Self> {
+        let legs: Vec<ArchivedLeg> = journey.legs().map(ArchivedLeg::from_leg).collect();
+        (!legs.is_empty()).then(|| Self {
+            journey_id: journey_id.to_string(),
+            legs,
+            archived,
+        })
+    }
+
+    /// Its trains, in order; walks between them aren't kept.
+    pub fn legs(&self) -> &[ArchivedLeg] {
+        &self.legs
+    }
+
+    /// Where the first train was boarded.
+    pub fn origin(&self) -> &ArchivedCall {
+        &self.legs[0].board
+    }
+
+    /// Where the last train was left.
+    pub fn destination(&self) -> &ArchivedCall {
+        &self.legs[self.legs.len() - 1].alight
+    }
+
+    /// How much later than planned the last train arrived (negative if
+    /// early), if both times are known.
+    pub fn arrival_delay(&self) -> Option<chrono::Duration> {
+        let destination = self.destination();
+        Some(
+            destination
+                .actual?
+                .signed_duration_since(destination.planned?),
+        )
+    }
+}
+
+/// In-memory store of each owner's finished journeys, most recent first.
+#[derive(Clone)]
+pub struct JourneyArchive {
+    journeys: MokaCache<WatchOwner, Arc<Vec<ArchivedJourney>>>,
+}
+
+impl JourneyArchive {
+    /// Create an empty archive.
+    pub fn new() -> Self {
+        Self {
+            journeys: MokaCache::builder()
+                .time_to_idle(ARCHIVE_IDLE_TTL)
+                .max_capacity(MAX_OWNERS)
+                .build(),
+        }
+    }
+
+    /// Archive a journey for an owner.
+    ///
+    /// Archiving a journey again replaces the earlier record of it, keeping
+    /// its place in the list, so later observations win.
+    pub async fn archive(&self, owner: WatchOwner, journey: ArchivedJourney) {
+        let mut journeys = self.list(&owner).await;
+        match journeys
+            .iter()
+            .position(|j| j.journey_id == journey.journey_id)
+        {
+            Some(i) => journeys[i] = journey,
+            None => {
+                journeys.insert(0, journey);
+                journeys.truncate(MAX_ARCHIVED_PER_OWNER);
+            }
+        }
+        self.journeys.insert(owner, Arc::new(journeys)).await;
+    }
+
+    /// An owner's finished journeys, most recently archived first.
+    pub async fn list(&self, owner: &WatchOwner) -> Vec<ArchivedJourney> {
+        self.journeys
+            .get(owner)
+            .await
+            .map(|j| j.as_ref().clone())
+            .unwrap_or_default()
+    }
+
+    /// Every owner's finished journeys.
+    fn owners(&self) -> Vec<(WatchOwner, Arc<Vec<ArchivedJourney>>)> {
+        self.journeys
+            .iter()
+            .map(|(owner, journeys)| (owner.as_ref().clone(), journeys))
+            .collect()
+    }
+}
+
+impl Default for JourneyArchive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Errors reading or writing the archive file.
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveStoreError {
+    /// Reading or writing the file failed
+    #[error("archive I/O error: {message}")]
+    Io { message: String },
+
+    /// The file contents were not valid
+    #[error("archive format error: {message}")]
+    Format { message: String },
+}
+
+impl Classify for ArchiveStoreError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ArchiveStoreError::Io { .. } => ErrorKind::Transient,
+            ArchiveStoreError::Format { .. } => ErrorKind::Config,
+        }
+    }
+}
+
+/// One owner's archive, as stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredArchive {
+    owner: String,
+    journeys: Vec<StoredJourney>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredJourney {
+    journey_id: String,
+    legs: Vec<StoredLeg>,
+    archived: RailTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredLeg {
+    operator: String,
+    headcode: Option<String>,
+    board: StoredCall,
+    alight: StoredCall,
+    cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCall {
+    station: String,
+    station_name: String,
+    platform: Option<String>,
+    planned: Option<RailTime>,
+    actual: Option<RailTime>,
+}
+
+impl StoredCall {
+    fn new(call: &ArchivedCall) -> Self {
+        Self {
+            station: call.station.as_str().to_string(),
+            station_name: call.station_name.clone(),
+            platform: call.platform.clone(),
+            planned: call.planned,
+            actual: call.actual,
+        }
+    }
+
+    fn restore(self) -> Option<ArchivedCall> {
+        Some(ArchivedCall {
+            station: Crs::parse(&self.station).ok()?,
+            station_name: self.station_name,
+            platform: self.platform,
+            planned: self.planned,
+            actual: self.actual,
+        })
+    }
+}
+
+impl StoredJourney {
+    fn new(journey: &ArchivedJourney) -> Self {
+        Self {
+            journey_id: journey.journey_id.clone(),
+            legs: journey
+                .legs
+                .iter()
+                .map(|leg| StoredLeg {
+                    operator: leg.operator.clone(),
+                    headcode: leg.headcode.map(|h| h.to_string()),
+                    board: StoredCall::new(&leg.board),
+                    alight: StoredCall::new(&leg.alight),
+                    cancelled: leg.cancelled,
+                })
+                .collect(),
+            archived: journey.archived,
+        }
+    }
+
+    /// The archived journey, or `None` if it names an invalid station or
+    /// has no trains.
+    fn restore(self) -> Option<ArchivedJourney> {
+        let legs = self
+            .legs
+            .into_iter()
+            .map(|leg| {
+                Some(ArchivedLeg {
+                    operator: leg.operator,
+                    headcode: leg.headcode.as_deref().and_then(Headcode::parse),
+                    board: leg.board.restore()?,
+                    alight: leg.alight.restore()?,
+                    cancelled: leg.cancelled,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        (!legs.is_empty()).then_some(ArchivedJourney {
+            journey_id: self.journey_id,
+            legs,
+            archived: self.archived,
+        })
+    }
+}
+
+/// JSON file store for the archive, so finished journeys survive restarts.
+#[derive(Debug, Clone)]
+pub struct ArchiveStore {
+    path: PathBuf,
+}
+
+impl ArchiveStore {
+    /// Create a store backed by the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The file the archive is kept in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the archive from the file, or an empty archive if the file
+    /// doesn't exist yet.
+    ///
+    /// Journeys naming invalid stations are skipped.
+    pub async fn load(&self) -> Result<JourneyArchive, ArchiveStoreError> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(JourneyArchive::new());
+            }
+            Err(e) => {
+                return Err(ArchiveStoreError::Io {
+                    message: format!("failed to read {}: {}", self.path.display(), e),
+                });
+            }
+        };
+        let stored: Vec<StoredArchive> =
+            serde_json::from_str(&contents).map_err(|e| ArchiveStoreError::Format {
+                message: e.to_string(),
+            })?;
+
+        let archive = JourneyArchive::new();
+        for entry in stored {
+            let mut journeys: Vec<ArchivedJourney> = entry
+                .journeys
+                .into_iter()
+                .filter_map(StoredJourney::restore)
+                .collect();
+            journeys.truncate(MAX_ARCHIVED_PER_OWNER);
+            if !journeys.is_empty() {
+                archive
+                    .journeys
+                    .insert(WatchOwner::from_digest(entry.owner), Arc::new(journeys))
+                    .await;
+            }
+        }
+        Ok(archive)
+    }
+
+    /// Save the archive to the file, replacing what was there.
+    pub async fn save(&self, archive: &JourneyArchive) -> Result<(), ArchiveStoreError> {
+        let stored: Vec<StoredArchive> = archive
+            .owners()
+            .into_iter()
+            .map(|(owner, journeys)| StoredArchive {
+                owner: owner.as_str().to_string(),
+                journeys: journeys.iter().map(StoredJourney::new).collect(),
+            })
+            .collect();
+        let json = serde_json::to_string(&stored).map_err(|e| ArchiveStoreError::Format {
+            message: e.to_string(),
+        })?;
+        tokio::fs::write(&self.path, json)
+            .await
+            .map_err(|e| ArchiveStoreError::Io {
+                message: format!("failed to write {}: {}", self.path.display(), e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CallIndex, Segment};
+    use crate::testing::{ServiceBuilder, crs, time};
+
+    /// PAD to RDG, booked 10:00 to 10:25, expected into RDG at `expected`.
+    fn journey(id: &str, expected: &str) -> Journey {
+        let service = ServiceBuilder::new(id)
+            .call("PAD")
+            .dep("10:00")
+            .call("RDG")
+            .arr("10:25")
+            .expected_arr(expected)
+            .build();
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        Journey::new(vec![Segment::Train(leg)]).unwrap()
+    }
+
+    #[test]
+    fn keeps_planned_and_observed_times() {
+        let archived =
+            ArchivedJourney::from_journey("j1", &journey("S1", "10:32"), time("10:40")).unwrap();
+
+        assert_eq!(archived.origin().station, crs("PAD"));
+        assert_eq!(archived.destination().planned, Some(time("10:25")));
+        assert_eq!(archived.destination().actual, Some(time("10:32")));
+        assert_eq!(archived.arrival_delay(), Some(chrono::Duration::minutes(7)));
+    }
+
+    #[tokio::test]
+    async fn archive_is_per_owner_and_most_recent_first() {
+        let archive = JourneyArchive::new();
+        let owner = WatchOwner::session("token");
+        let other = WatchOwner::api_key("key");
+        let record = |id: &str, expected: &str| {
+            ArchivedJourney::from_journey(id, &journey(id, expected), time("11:00")).unwrap()
+        };
+
+        archive.archive(owner.clone(), record("j1", "10:25")).await;
+        archive.archive(owner.clone(), record("j2", "10:25")).await;
+        // Archiving again updates the record in place
+        archive.archive(owner.clone(), record("j1", "10:40")).await;
+
+        let listed = archive.list(&owner).await;
+        let ids: Vec<&str> = listed.iter().map(|j| j.journey_id.as_str()).collect();
+        assert_eq!(ids, ["j2", "j1"]);
+        assert_eq!(listed[1].destination().actual, Some(time("10:40")));
+        assert!(archive.list(&other).await.is_empty());
+
+        for i in 0..MAX_ARCHIVED_PER_OWNER {
+            archive
+                .archive(owner.clone(), record(&format!("k{i}"), "10:25"))
+                .await;
+        }
+        let listed = archive.list(&owner).await;
+        assert_eq!(listed.len(), MAX_ARCHIVED_PER_OWNER);
+        assert!(listed.iter().all(|j| j.journey_id.starts_with('k')));
+    }
+
+    #[tokio::test]
+    async fn archive_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArchiveStore::new(dir.path().join("archive.json"));
+        assert!(store.load().await.unwrap().owners().is_empty());
+
+        let archive = JourneyArchive::new();
+        let owner = WatchOwner::session("token");
+        let archived =
+            ArchivedJourney::from_journey("j1", &journey("S1", "10:32"), time("10:40")).unwrap();
+        archive.archive(owner.clone(), archived.clone()).await;
+        store.save(&archive).await.unwrap();
+
+        let loaded = store.load().await.unwrap();
+        assert_eq!(loaded.list(&owner).await, [archived]);
+        assert!(loaded.list(&WatchOwner::session("other")).await.is_empty());
+    }
+}
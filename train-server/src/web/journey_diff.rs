@@ -0,0 +1,287 @@
+//! Real-time differences between two plans of the same journey.
+//!
+//! Re-planning a route gives a journey with the same fingerprint but fresh
+//! Darwin estimates. [`diff`] lists what a traveller would care about:
+//! changed times, changed platforms and cancellations, leg by leg.
+//!
+//! A train moved to another platform is the commonest way to miss a
+//! connection at a big station, so those changes are high priority and
+//! [`JourneyChange::alert`] turns them into notifications for the journey's
+//! watchers.
+
+use serde::Serialize;
+
+use crate::domain::{Journey, Leg};
+use crate::notify::{Notification, Priority};
+
+/// What changed about a leg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Expected departure from the boarding station
+    Departure,
+    /// Expected arrival at the alighting station
+    Arrival,
+    /// Platform at the boarding station
+    BoardPlatform,
+    /// Platform at the alighting station
+    AlightPlatform,
+    /// The leg was cancelled (or reinstated)
+    Cancelled,
+}
+
+/// One change to one leg.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JourneyChange {
+    /// Index of the leg among the journey's train legs
+    pub leg: usize,
+
+    /// What changed
+    pub kind: ChangeKind,
+
+    /// Previous value ("" if unknown)
+    pub from: String,
+
+    /// New value ("" if unknown)
+    pub to: String,
+
+    /// Human-readable summary
+    pub message: String,
+
+    /// How urgently the traveller needs to know: high for a train moved
+    /// to another platform
+    pub priority: Priority,
+}
+
+impl JourneyChange {
+    /// The notification to send the journey's watchers straight away, if
+    /// the change is high priority.
+    pub fn alert(&self) -> Option<Notification> {
+        if self.priority != Priority::High {
+            return None;
+        }
+        let body = match self.kind {
+            ChangeKind::AlightPlatform => {
+                "Check how far you'll have to go to make your connection."
+            }
+            _ => "Allow time to get to the new platform.",
+        };
+        Some(Notification::new(&self.message, body).with_priority(Priority::High))
+    }
+}
+
+/// The real-time details of a leg that [`diff`] compares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct LegStatus {
+    /// How the train is named in alerts, e.g. "14:30 to Leeds"
+    train: String,
+    station: String,
+    alight_station: String,
+    departure: String,
+    arrival: String,
+    board_platform: String,
+    alight_platform: String,
+    cancelled: bool,
+}
+
+impl LegStatus {
+    fn of(leg: &Leg) -> Self {
+        let departs = leg
+            .board_call()
+            .booked_departure()
+            .unwrap_or_else(|| leg.departure_time());
+        let terminus = leg
+            .service()
+            .calls
+            .last()
+            .map_or(leg.alight_station_name(), |c| c.station_name.as_str());
+        Self {
+            train: format!("{departs} to {terminus}"),
+            station: leg.board_station_name().to_string(),
+            alight_station: leg.alight_station_name().to_string(),
+            departure: leg.departure_time().to_string(),
+            arrival: leg.arrival_time().to_string(),
+            board_platform: leg.board_platform().unwrap_or_default().to_string(),
+            alight_platform: leg.alight_platform().unwrap_or_default().to_string(),
+            cancelled: leg.is_cancelled(),
+        }
+    }
+}
+
+/// The real-time status of every train leg of a journey.
+pub(super) fn status(journey: &Journey) -> Vec<LegStatus> {
+    journey.legs().map(LegStatus::of).collect()
+}
+
+/// Changes from `old` to `new`, which must be plans of the same route
+/// (same fingerprint).
+pub fn diff(old: &Journey, new: &Journey) -> Vec<JourneyChange> {
+    let mut changes = Vec::new();
+    for (leg, (old, new)) in status(old).iter().zip(status(new)).enumerate() {
+        let mut push = |kind, from: &str, to: &str, message: String| {
+            changes.push(JourneyChange {
+                leg,
+                kind,
+                from: from.to_string(),
+                to: to.to_string(),
+                message,
+                priority: Priority::Normal,
+            });
+        };
+
+        if old.cancelled != new.cancelled {
+            let message = if new.cancelled {
+                format!("The {} from {} is cancelled", old.departure, old.station)
+            } else {
+                format!(
+                    "The {} from {} is running again",
+                    new.departure, new.station
+                )
+            };
+            push(
+                ChangeKind::Cancelled,
+                &old.cancelled.to_string(),
+                &new.cancelled.to_string(),
+                message,
+            );
+        }
+        if old.departure != new.departure {
+            push(
+                ChangeKind::Departure,
+                &old.departure,
+                &new.departure,
+                format!(
+                    "Now departs {} at {} (was {})",
+                    new.station, new.departure, old.departure
+                ),
+            );
+        }
+        if old.arrival != new.arrival {
+            push(
+                ChangeKind::Arrival,
+                &old.arrival,
+                &new.arrival,
+                format!(
+                    "Now arrives {} at {} (was {})",
+                    new.alight_station, new.arrival, old.arrival
+                ),
+            );
+        }
+        if old.board_platform != new.board_platform {
+            push(
+                ChangeKind::BoardPlatform,
+                &old.board_platform,
+                &new.board_platform,
+                platform_message(&new, &new.station, &new.board_platform, true),
+            );
+        }
+        if old.alight_platform != new.alight_platform {
+            push(
+                ChangeKind::AlightPlatform,
+                &old.alight_platform,
+                &new.alight_platform,
+                platform_message(&new, &new.alight_station, &new.alight_platform, false),
+            );
+        }
+    }
+
+    // A platform first announced or withdrawn isn't a move
+    for change in &mut changes {
+        if matches!(
+            change.kind,
+            ChangeKind::BoardPlatform | ChangeKind::AlightPlatform
+        ) && !change.from.is_empty()
+            && !change.to.is_empty()
+        {
+            change.priority = Priority::High;
+        }
+    }
+    changes
+}
+
+fn platform_message(leg: &LegStatus, station: &str, platform: &str, departs: bool) -> String {
+    let train = &leg.train;
+    match (platform.is_empty(), departs) {
+        (true, _) => format!("Platform for your {train} at {station} no longer confirmed"),
+        (false, true) => format!("Your {train} now departs {station} from platform {platform}"),
+        (false, false) => format!("Your {train} now arrives at {station} on platform {platform}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, Crs, RailTime, Segment, Service, ServiceRef};
+    use chrono::NaiveDate;
+    use std::sync::Arc;
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    /// PAD → RDG, with an optional delay and platform at Paddington.
+    fn journey(expected_departure: Option<&str>, platform: Option<&str>) -> Journey {
+        let mut pad = Call::new(Crs::parse("PAD").unwrap(), "London Paddington".into());
+        pad.booked_departure = Some(time("10:00"));
+        pad.realtime_departure = expected_departure.map(time);
+        pad.platform = platform.map(str::to_string);
+        let mut rdg = Call::new(Crs::parse("RDG").unwrap(), "Reading".into());
+        rdg.booked_arrival = Some(time("10:25"));
+
+        let service = Arc::new(Service {
+            service_ref: ServiceRef::new("S".into(), Crs::parse("PAD").unwrap()),
+            headcode: None,
+            operator: "GWR".into(),
+            operator_code: None,
+            calls: vec![pad, rdg],
+            board_station_idx: CallIndex(0),
+            formation: None,
+        });
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        Journey::new(vec![Segment::Train(leg)]).unwrap()
+    }
+
+    #[test]
+    fn unchanged_journey_has_no_changes() {
+        assert!(diff(&journey(None, Some("1")), &journey(None, Some("1"))).is_empty());
+    }
+
+    #[test]
+    fn delay_and_platform_change() {
+        let changes = diff(&journey(None, None), &journey(Some("10:07"), Some("4")));
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Departure);
+        assert_eq!(changes[0].from, "10:00");
+        assert_eq!(changes[0].to, "10:07");
+        assert_eq!(
+            changes[0].message,
+            "Now departs London Paddington at 10:07 (was 10:00)"
+        );
+        assert_eq!(changes[1].kind, ChangeKind::BoardPlatform);
+        assert_eq!(
+            changes[1].message,
+            "Your 10:00 to Reading now departs London Paddington from platform 4"
+        );
+        // Only just announced, so nobody has gone to the wrong platform
+        assert_eq!(changes[1].priority, Priority::Normal);
+        assert!(changes.iter().all(|c| c.alert().is_none()));
+    }
+
+    #[test]
+    fn platform_moves_are_high_priority_alerts() {
+        let changes = diff(&journey(None, Some("1")), &journey(None, Some("9b")));
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].priority, Priority::High);
+        let alert = changes[0].alert().unwrap();
+        assert_eq!(alert.priority, Priority::High);
+        assert_eq!(
+            alert.title,
+            "Your 10:00 to Reading now departs London Paddington from platform 9b"
+        );
+
+        let withdrawn = diff(&journey(None, Some("1")), &journey(None, None));
+        assert_eq!(withdrawn[0].priority, Priority::Normal);
+    }
+}
@@ -0,0 +1,512 @@
+//! Short-lived storage for planned journeys.
+//!
+//! Each journey returned by the planner is given an ID so clients can fetch
+//! derived views of it (e.g. GeoJSON) without re-running the search. The ID
+//! is the journey's fingerprint, so re-planning the same route gives the
+//! same ID (and an unchanged response body for conditional requests).
+//! Journeys are held in memory and expire after a few hours; they embed
+//! Darwin data that is stale by then anyway. With the `redis` feature they
+//! can also be shared with other instances, so follow-up requests work
+//! behind a load balancer.
+//!
+//! Each time a route is re-planned with different real-time data, its
+//! version number goes up. Clients can wait for the next version and get
+//! the changes since the one they have (see [`JourneyStore::changes_since`]).
+//! Versions are local to this instance.
+//!
+//! While clients are waiting on a journey, it is refreshed leg by leg (see
+//! [`crate::refresh`]) and the result stored under the same ID. If one of
+//! its changes can no longer be made, the route is planned again and the
+//! old journey points clients to the new one.
+//!
+//! A journey that is only the first portion of a trip too long to plan in
+//! one go keeps a [`ContinuationHint`] for planning the rest; once that is
+//! planned, the whole trip is stored as a new journey that replaces it.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use moka::future::Cache as MokaCache;
+use serde::Serialize;
+use tokio::sync::watch;
+
+#[cfg(feature = "redis")]
+use crate::cache::RedisCache;
+use crate::domain::Journey;
+use crate::planner::ContinuationHint;
+
+use super::journey_diff::{JourneyChange, diff, status};
+
+/// How long planned journeys are kept.
+const JOURNEY_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Maximum number of stored journeys.
+const MAX_JOURNEYS: u64 = 10_000;
+
+/// Versions of each journey kept for diffing.
+const HISTORY_LEN: usize = 8;
+
+/// Changes to a journey since a version the client already has.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JourneyChanges {
+    /// The latest version
+    pub version: u64,
+
+    /// Changes from the client's version to the latest
+    pub changes: Vec<JourneyChange>,
+
+    /// Whether the client's version is unknown (or too old to diff
+    /// against), so it should fetch the whole journey again
+    pub reset: bool,
+
+    /// ID of a fresh plan for the same trip, if one of this journey's
+    /// changes can no longer be made
+    pub replaced_by: Option<String>,
+}
+
+/// Recent versions of one journey.
+struct History {
+    /// (version, journey), oldest first; never empty
+    versions: Mutex<VecDeque<(u64, Arc<Journey>)>>,
+    /// Latest version, for waiting on changes
+    latest: watch::Sender<u64>,
+    /// When the journey's trains were last polled
+    refreshed: Mutex<Option<Instant>>,
+    /// A fresh plan to use instead, once a change has become impossible
+    replaced_by: Mutex<Option<String>>,
+    /// How to plan the rest, if this is the first portion of a journey
+    continuation: Mutex<Option<ContinuationHint>>,
+}
+
+impl History {
+    fn new(journey: Arc<Journey>) -> Self {
+        Self {
+            versions: Mutex::new(VecDeque::from([(1, journey)])),
+            latest: watch::Sender::new(1),
+            refreshed: Mutex::new(None),
+            replaced_by: Mutex::new(None),
+            continuation: Mutex::new(None),
+        }
+    }
+
+    /// Record a new plan, as a new version if its real-time data differs.
+    fn record(&self, journey: Arc<Journey>) {
+        let mut versions = self.versions.lock().expect("history lock poisoned");
+        let (version, latest) = versions.back().expect("history is never empty");
+        if status(latest) == status(&journey) {
+            return;
+        }
+        let version = version + 1;
+        versions.push_back((version, journey));
+        if versions.len() > HISTORY_LEN {
+            versions.pop_front();
+        }
+        drop(versions);
+        self.latest.send_replace(version);
+    }
+
+    fn changes_since(&self, since: u64) -> JourneyChanges {
+        let versions = self.versions.lock().expect("history lock poisoned");
+        let (version, latest) = versions.back().expect("history is never empty");
+        let (changes, reset) = if since == *version {
+            (Vec::new(), false)
+        } else {
+            match versions.iter().find(|(v, _)| *v == since) {
+                Some((_, old)) => (diff(old, latest), false),
+                None => (Vec::new(), true),
+            }
+        };
+        let replaced_by = self
+            .replaced_by
+            .lock()
+            .expect("replacement lock poisoned")
+            .clone();
+        JourneyChanges {
+            version: *version,
+            changes,
+            reset,
+            replaced_by,
+        }
+    }
+
+    /// How long until the trains are due to be polled again, at most every
+    /// `interval`.
+    fn next_refresh(&self, interval: Duration) -> Duration {
+        self.refreshed
+            .lock()
+            .expect("refresh lock poisoned")
+            .map_or(Duration::ZERO, |at| interval.saturating_sub(at.elapsed()))
+    }
+
+    /// Whether the trains were last polled at least `interval` ago; if so,
+    /// they count as polled now.
+    fn claim_refresh(&self, interval: Duration) -> bool {
+        let mut refreshed = self.refreshed.lock().expect("refresh lock poisoned");
+        if refreshed.is_some_and(|at| at.elapsed() < interval) {
+            return false;
+        }
+        *refreshed = Some(Instant::now());
+        true
+    }
+}
+
+/// In-memory store of recently planned journeys, keyed by fingerprint.
+#[derive(Clone)]
+pub struct JourneyStore {
+    journeys: MokaCache<String, Arc<Journey>>,
+    histories: MokaCache<String, Arc<History>>,
+    /// Store shared with other instances
+    #[cfg(feature = "redis")]
+    shared: Option<RedisCache>,
+}
+
+impl JourneyStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            journeys: MokaCache::builder()
+                .time_to_live(JOURNEY_TTL)
+                .max_capacity(MAX_JOURNEYS)
+                .build(),
+            histories: MokaCache::builder()
+                .time_to_live(JOURNEY_TTL)
+                .max_capacity(MAX_JOURNEYS)
+                .build(),
+            #[cfg(feature = "redis")]
+            shared: None,
+        }
+    }
+
+    /// Share stored journeys with other instances through Redis.
+    #[cfg(feature = "redis")]
+    pub fn with_shared_cache(mut self, shared: RedisCache) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    /// Store a journey, returning its ID.
+    ///
+    /// Replaces any stored journey with the same fingerprint, so the ID
+    /// always refers to the latest data for that route.
+    pub async fn insert(&self, journey: Journey) -> String {
+        let id = journey.fingerprint();
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            shared.put_journey(&id, &journey, JOURNEY_TTL).await;
+        }
+        let journey = Arc::new(journey);
+        self.record(&id, journey.clone()).await;
+        self.journeys.insert(id.clone(), journey).await;
+        id
+    }
+
+    /// Store a re-polled plan of a journey under its existing ID.
+    ///
+    /// The fingerprint may differ from the ID if a train was found again
+    /// under a new Darwin ID; clients keep using the ID they have.
+    pub async fn update(&self, id: &str, journey: Journey) {
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared {
+            shared.put_journey(id, &journey, JOURNEY_TTL).await;
+        }
+        let journey = Arc::new(journey);
+        self.record(id, journey.clone()).await;
+        self.journeys.insert(id.to_string(), journey).await;
+    }
+
+    /// Point clients waiting on a journey to a fresh plan for the same trip.
+    pub async fn replace(&self, id: &str, replacement: String) {
+        if let Some(history) = self.histories.get(id).await {
+            *history
+                .replaced_by
+                .lock()
+                .expect("replacement lock poisoned") = Some(replacement);
+        }
+    }
+
+    /// Note how to plan the rest of a journey that is only a first portion,
+    /// or with `None`, that there's nothing more to plan.
+    pub async fn set_continuation(&self, id: &str, hint: Option<ContinuationHint>) {
+        if let Some(history) = self.history(id).await {
+            *history
+                .continuation
+                .lock()
+                .expect("continuation lock poisoned") = hint;
+        }
+    }
+
+    /// How to plan the rest of a journey, if it is a first portion still to
+    /// be continued.
+    pub async fn continuation(&self, id: &str) -> Option<ContinuationHint> {
+        self.histories
+            .get(id)
+            .await?
+            .continuation
+            .lock()
+            .expect("continuation lock poisoned")
+            .clone()
+    }
+
+    /// Whether a journey's trains are due to be polled again, having last
+    /// been polled at least `interval` ago. If so, they count as polled now,
+    /// so concurrent watchers don't all poll. False for unknown journeys.
+    pub async fn claim_refresh(&self, id: &str, interval: Duration) -> bool {
+        match self.history(id).await {
+            Some(history) => history.claim_refresh(interval),
+            None => false,
+        }
+    }
+
+    /// How long until a journey's trains are due to be polled again, if
+    /// they are polled at most every `interval`. Zero if they're due now;
+    /// `None` for unknown journeys.
+    pub async fn next_refresh(&self, id: &str, interval: Duration) -> Option<Duration> {
+        Some(self.history(id).await?.next_refresh(interval))
+    }
+
+    /// A journey's version history, started here if it was stored by
+    /// another instance.
+    async fn history(&self, id: &str) -> Option<Arc<History>> {
+        match self.histories.get(id).await {
+            Some(history) => Some(history),
+            None => Some(self.record(id, self.get(id).await?).await),
+        }
+    }
+
+    /// Add a plan to a journey's version history.
+    async fn record(&self, id: &str, journey: Arc<Journey>) -> Arc<History> {
+        let entry = self
+            .histories
+            .entry_by_ref(id)
+            .or_insert_with(async { Arc::new(History::new(journey.clone())) })
+            .await;
+        let history = entry.into_value();
+        history.record(journey);
+        history
+    }
+
+    /// Changes to a journey since version `since`.
+    ///
+    /// If there is nothing newer than `since`, waits up to `wait` for the
+    /// journey to be re-planned with different real-time data. Pass
+    /// `since = 0` to just get the current version. Returns `None` if the
+    /// journey is unknown or expired.
+    pub async fn changes_since(
+        &self,
+        id: &str,
+        since: u64,
+        wait: Duration,
+    ) -> Option<JourneyChanges> {
+        let history = self.history(id).await?;
+
+        let mut latest = history.latest.subscribe();
+        if *latest.borrow_and_update() == since {
+            let _ = tokio::time::timeout(wait, latest.changed()).await;
+        }
+        Some(history.changes_since(since))
+    }
+
+    /// Look up a journey by ID.
+    pub async fn get(&self, id: &str) -> Option<Arc<Journey>> {
+        if let Some(journey) = self.journeys.get(id).await {
+            return Some(journey);
+        }
+
+        #[cfg(feature = "redis")]
+        if let Some(shared) = &self.shared
+            && let Some(journey) = shared.get_journey(id).await
+        {
+            let journey = Arc::new(journey);
+            self.journeys.insert(id.to_string(), journey.clone()).await;
+            return Some(journey);
+        }
+
+        None
+    }
+}
+
+impl Default for JourneyStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Crs, Segment, Walk};
+
+    fn journey() -> Journey {
+        let walk = Walk::new(
+            Crs::parse("KGX").unwrap(),
+            Crs::parse("STP").unwrap(),
+            chrono::Duration::minutes(5),
+        );
+        Journey::new(vec![Segment::Walk(walk)]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn insert_then_get() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey()).await;
+        assert_eq!(id.len(), 32);
+        assert!(store.get(&id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_id_is_none() {
+        let store = JourneyStore::new();
+        assert!(store.get("nope").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn same_route_gets_same_id() {
+        let store = JourneyStore::new();
+        let a = store.insert(journey()).await;
+        let b = store.insert(journey()).await;
+        assert_eq!(a, b);
+    }
+
+    fn journey_at(departure: &str) -> Journey {
+        use crate::domain::{Call, CallIndex, Leg, RailTime, Service, ServiceRef};
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let mut pad = Call::new(Crs::parse("PAD").unwrap(), "London Paddington".into());
+        pad.booked_departure = RailTime::parse_hhmm("10:00", date).ok();
+        pad.realtime_departure = RailTime::parse_hhmm(departure, date).ok();
+        let mut rdg = Call::new(Crs::parse("RDG").unwrap(), "Reading".into());
+        rdg.booked_arrival = RailTime::parse_hhmm("10:25", date).ok();
+        let service = Arc::new(Service {
+            service_ref: ServiceRef::new("S".into(), Crs::parse("PAD").unwrap()),
+            headcode: None,
+            operator: "GWR".into(),
+            operator_code: None,
+            calls: vec![pad, rdg],
+            board_station_idx: CallIndex(0),
+            formation: None,
+        });
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        Journey::new(vec![Segment::Train(leg)]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replanning_with_new_data_bumps_version() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+        let none = Duration::ZERO;
+
+        let first = store.changes_since(&id, 0, none).await.unwrap();
+        assert_eq!(first.version, 1);
+        assert!(first.reset);
+
+        // Same data: no new version
+        store.insert(journey_at("10:00")).await;
+        let same = store.changes_since(&id, 1, none).await.unwrap();
+        assert_eq!(same.version, 1);
+        assert!(same.changes.is_empty());
+        assert!(!same.reset);
+
+        store.insert(journey_at("10:05")).await;
+        let delayed = store.changes_since(&id, 1, none).await.unwrap();
+        assert_eq!(delayed.version, 2);
+        assert_eq!(delayed.changes.len(), 1);
+        assert_eq!(delayed.changes[0].to, "10:05");
+    }
+
+    #[tokio::test]
+    async fn waits_for_next_version() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+
+        let waiting = {
+            let store = store.clone();
+            let id = id.clone();
+            tokio::spawn(async move {
+                store
+                    .changes_since(&id, 1, Duration::from_secs(5))
+                    .await
+                    .unwrap()
+            })
+        };
+        tokio::task::yield_now().await;
+        store.insert(journey_at("10:10")).await;
+
+        let changes = waiting.await.unwrap();
+        assert_eq!(changes.version, 2);
+        assert_eq!(changes.changes[0].from, "10:00");
+    }
+
+    #[tokio::test]
+    async fn update_keeps_id_and_bumps_version() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+
+        // Stored under the given ID, whatever its fingerprint
+        store.update(&id, journey_at("10:04")).await;
+
+        let changes = store.changes_since(&id, 1, Duration::ZERO).await.unwrap();
+        assert_eq!(changes.version, 2);
+        assert_eq!(changes.changes[0].to, "10:04");
+        assert_eq!(changes.replaced_by, None);
+    }
+
+    #[tokio::test]
+    async fn replaced_journey_points_to_replacement() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+        let replacement = store.insert(journey()).await;
+
+        store.replace(&id, replacement.clone()).await;
+
+        let changes = store.changes_since(&id, 1, Duration::ZERO).await.unwrap();
+        assert_eq!(changes.replaced_by, Some(replacement));
+    }
+
+    #[tokio::test]
+    async fn first_portion_keeps_its_continuation() {
+        use crate::testing::{crs, time};
+
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+        assert_eq!(store.continuation(&id).await, None);
+
+        let hint = ContinuationHint {
+            station: crs("TAU"),
+            after: time("12:35"),
+            destination: crs("PNZ"),
+            opens_at: time("11:35"),
+        };
+        store.set_continuation(&id, Some(hint.clone())).await;
+        assert_eq!(store.continuation(&id).await, Some(hint));
+
+        store.set_continuation(&id, None).await;
+        assert_eq!(store.continuation(&id).await, None);
+        store.set_continuation("nope", None).await;
+    }
+
+    #[tokio::test]
+    async fn refresh_claimed_once_per_interval() {
+        let store = JourneyStore::new();
+        let id = store.insert(journey_at("10:00")).await;
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(store.next_refresh(&id, minute).await, Some(Duration::ZERO));
+        assert!(store.claim_refresh(&id, minute).await);
+        assert!(store.next_refresh(&id, minute).await.unwrap() > Duration::ZERO);
+        assert!(!store.claim_refresh(&id, minute).await);
+        assert!(store.claim_refresh(&id, Duration::ZERO).await);
+        assert!(!store.claim_refresh("nope", minute).await);
+    }
+
+    #[tokio::test]
+    async fn changes_for_unknown_journey_is_none() {
+        let store = JourneyStore::new();
+        assert!(
+            store
+                .changes_since("nope", 0, Duration::ZERO)
+                .await
+                .is_none()
+        );
+    }
+}
@@ -2,13 +2,66 @@
 //!
 //! Provides HTTP endpoints for searching services and planning journeys.
 
+mod admin;
+mod analytics;
+mod api_keys;
+mod compact;
 mod dto;
+mod etag;
+mod experiments;
+mod favourite_store;
+mod favourites;
+mod geojson;
+mod journey_archive;
+mod journey_diff;
+mod journey_store;
+mod narration;
+mod pdf;
+mod provider;
+mod qr;
+mod recent_journeys;
+mod reload;
 mod routes;
 mod rtt;
+mod search_queue;
+mod session;
 mod state;
+mod station_labels;
 pub mod templates;
+mod time_format;
+mod versioning;
+mod watch_store;
+mod watches;
 
+pub use analytics::{
+    AnalyticsStore, AnalyticsStoreError, PairCounts, SearchEvent, UsageAnalytics, opted_out,
+};
+pub use api_keys::ApiKeys;
+pub use compact::{FormatQuery, compact_value, without_stops};
 pub use dto::*;
+pub use experiments::{API_KEY_HEADER, Experiment, ExperimentError, Experiments, Subject};
+pub use favourite_store::{
+    Favourite, FavouriteError, FavouriteStore, MAX_FAVOURITE_NAME_LEN, MAX_FAVOURITES_PER_OWNER,
+};
+pub use geojson::{FeatureCollection, journey_to_geojson};
+pub use journey_archive::{
+    ARCHIVE_IDLE_TTL, ArchiveStore, ArchiveStoreError, ArchivedCall, ArchivedJourney, ArchivedLeg,
+    JourneyArchive, MAX_ARCHIVED_PER_OWNER,
+};
+pub use journey_diff::{ChangeKind, JourneyChange, diff};
+pub use journey_store::{JourneyChanges, JourneyStore};
+pub use narration::narrate;
+pub use provider::{CachedServiceProvider, DarwinProviders, ProviderSource};
+pub use reload::{
+    ConfigSource, LiveSettings, ReloadSummary, Snapshot, reload, remove_implausible_walks,
+};
 pub use routes::create_router;
+pub use search_queue::{QueueFull, SearchLimits, SearchPermit, SearchQueue};
+pub use session::{Session, SessionArrivals, SessionKey};
 pub use state::AppState;
+pub use station_labels::name_stations;
 pub use templates::*;
+pub use time_format::{HourCycle, TimeFormat, iso_datetime};
+pub use versioning::API_V1;
+pub use watch_store::{MAX_WATCHES_PER_OWNER, Watch, WatchError, WatchOwner, WatchStore};
+pub use watches::archive_arrived_watches;
@@ -0,0 +1,257 @@
+//! Plain-text narration of journeys.
+//!
+//! Describes a journey as a few short sentences, one step per line, for
+//! screen readers and SMS. The first leg is always the train the user is
+//! already on.
+
+use crate::alighting::StationMetadataTable;
+use crate::domain::{Change, Journey, Leg};
+
+/// Narrate a journey, one step per line.
+///
+/// Alighting hints from `metadata` are included for each change.
+pub fn narrate(journey: &Journey, metadata: &StationMetadataTable) -> String {
+    let mut lines = Vec::new();
+    let mut legs = journey.legs();
+    let Some(first) = legs.next() else {
+        return String::new();
+    };
+
+    lines.push(format!(
+        "Stay on this train until {}{}.",
+        first.alight_station_name(),
+        arriving(first)
+    ));
+
+    for change in journey.changes() {
+        if let Some(hint) = metadata.for_alighting(change.from).and_then(|m| m.hint()) {
+            lines.push(hint);
+        }
+        lines.extend(describe_change(&change));
+        lines.push(format!(
+            "Stay on until {}{}.",
+            change.to.alight_station_name(),
+            arriving(change.to)
+        ));
+    }
+
+    // Walks only know their stations' codes
+    let destination = match journey.final_walk() {
+        Some(walk) => {
+            lines.push(format!(
+                "Walk to {}, about {} minutes.",
+                walk.to,
+                walk.duration.num_minutes()
+            ));
+            walk.to.as_str()
+        }
+        None => journey
+            .legs()
+            .last()
+            .map_or("", |l| l.alight_station_name()),
+    };
+
+    let changes = match journey.change_count() {
+        0 => "direct".to_string(),
+        1 => "1 change".to_string(),
+        n => format!("{n} changes"),
+    };
+    lines.push(format!(
+        "You arrive at {} at {}. Journey time {}, {}.",
+        destination,
+        journey.arrival_time(),
+        duration(journey.total_duration().num_minutes()),
+        changes
+    ));
+
+    lines.join("\n")
+}
+
+/// ", arriving 10:25 at platform 4" (platform only if known).
+fn arriving(leg: &Leg) -> String {
+    match leg.alight_platform() {
+        Some(platform) => format!(", arriving {} at platform {}", leg.arrival_time(), platform),
+        None => format!(", arriving {}", leg.arrival_time()),
+    }
+}
+
+/// Sentences for getting from one train to the next.
+fn describe_change(change: &Change<'_>) -> Vec<String> {
+    let mut lines = Vec::new();
+    let next = change.to;
+    let service = format!(
+        "the {} {} service to {}",
+        next.departure_time(),
+        next.service().operator,
+        next.service()
+            .calls
+            .last()
+            .map_or("", |c| c.station_name.as_str())
+    );
+
+    if let Some(walk) = change.walk {
+        lines.push(format!(
+            "Walk to {}, about {} minutes.",
+            next.board_station_name(),
+            walk.duration.num_minutes()
+        ));
+    }
+
+    let board = match next.board_platform() {
+        Some(platform) if change.walk.is_some() => {
+            format!("Board {service} from platform {platform}.")
+        }
+        Some(platform) => format!("Cross to platform {platform} for {service}."),
+        None => format!("Change for {service}; the platform isn't confirmed yet."),
+    };
+    lines.push(board);
+
+    let slack = change.slack().num_minutes();
+    lines.push(match slack {
+        1 => "You have 1 minute to spare.".to_string(),
+        n => format!("You have {n} minutes to spare."),
+    });
+
+    lines
+}
+
+/// "1h 30m" or "25 minutes".
+fn duration(mins: i64) -> String {
+    if mins >= 60 {
+        format!("{}h {}m", mins / 60, mins % 60)
+    } else {
+        format!("{mins} minutes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alighting::{StationMetadata, TrainPosition};
+    use crate::domain::{Call, CallIndex, Crs, RailTime, Segment, Service, ServiceRef, Walk};
+    use chrono::{Duration, NaiveDate};
+    use std::sync::Arc;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    /// (crs, name, arr, dep, platform)
+    fn service(calls: &[(&str, &str, &str, &str, &str)]) -> Arc<Service> {
+        let calls = calls
+            .iter()
+            .map(|(station, name, arr, dep, platform)| {
+                let mut call = Call::new(crs(station), (*name).to_string());
+                call.booked_arrival = (!arr.is_empty()).then(|| time(arr));
+                call.booked_departure = (!dep.is_empty()).then(|| time(dep));
+                call.platform = (!platform.is_empty()).then(|| (*platform).to_string());
+                call
+            })
+            .collect();
+        Arc::new(Service {
+            service_ref: ServiceRef::new("S".to_string(), crs("PAD")),
+            headcode: None,
+            operator: "Great Western Railway".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        })
+    }
+
+    fn leg(service: Arc<Service>) -> Segment {
+        let last = service.calls.len() - 1;
+        Segment::Train(Leg::new(service, CallIndex(0), CallIndex(last)).unwrap())
+    }
+
+    #[test]
+    fn narrates_direct_journey() {
+        let journey = Journey::new(vec![leg(service(&[
+            ("PAD", "London Paddington", "", "10:00", "1"),
+            ("RDG", "Reading", "10:25", "", "4"),
+        ]))])
+        .unwrap();
+
+        assert_eq!(
+            narrate(&journey, &StationMetadataTable::new()),
+            "Stay on this train until Reading, arriving 10:25 at platform 4.\n\
+             You arrive at Reading at 10:25. Journey time 25 minutes, direct."
+        );
+    }
+
+    #[test]
+    fn narrates_change_with_hint() {
+        let journey = Journey::new(vec![
+            leg(service(&[
+                ("PAD", "London Paddington", "", "10:00", "1"),
+                ("RDG", "Reading", "10:25", "", "9"),
+            ])),
+            leg(service(&[
+                ("RDG", "Reading", "", "10:32", "8"),
+                ("BRI", "Bristol Temple Meads", "11:30", "", ""),
+            ])),
+        ])
+        .unwrap();
+        let mut metadata = StationMetadataTable::new();
+        metadata.insert(
+            crs("RDG"),
+            StationMetadata::new().with_exit(TrainPosition::Front),
+        );
+
+        assert_eq!(
+            narrate(&journey, &metadata),
+            "Stay on this train until Reading, arriving 10:25 at platform 9.\n\
+             Alight near the front for the quickest interchange.\n\
+             Cross to platform 8 for the 10:32 Great Western Railway service to Bristol Temple Meads.\n\
+             You have 7 minutes to spare.\n\
+             Stay on until Bristol Temple Meads, arriving 11:30.\n\
+             You arrive at Bristol Temple Meads at 11:30. Journey time 1h 30m, 1 change."
+        );
+    }
+
+    #[test]
+    fn narrates_walk_and_unknown_platform() {
+        let journey = Journey::new(vec![
+            leg(service(&[
+                ("PAD", "London Paddington", "", "10:00", ""),
+                ("KGX", "London Kings Cross", "10:30", "", ""),
+            ])),
+            Segment::Walk(Walk::new(crs("KGX"), crs("STP"), Duration::minutes(5))),
+            leg(service(&[
+                ("STP", "London St Pancras", "", "10:45", ""),
+                ("LUT", "Luton", "11:10", "", ""),
+            ])),
+        ])
+        .unwrap();
+
+        let text = narrate(&journey, &StationMetadataTable::new());
+        assert!(text.contains("Walk to London St Pancras, about 5 minutes."));
+        assert!(text.contains(
+            "Change for the 10:45 Great Western Railway service to Luton; the platform isn't confirmed yet."
+        ));
+        assert!(text.contains("You have 10 minutes to spare."));
+    }
+
+    #[test]
+    fn narrates_walk_to_destination() {
+        let journey = Journey::new(vec![
+            leg(service(&[
+                ("PAD", "London Paddington", "", "10:00", "1"),
+                ("STP", "London St Pancras", "10:40", "", ""),
+            ])),
+            Segment::Walk(Walk::new(crs("STP"), crs("EUS"), Duration::minutes(8))),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            narrate(&journey, &StationMetadataTable::new()),
+            "Stay on this train until London St Pancras, arriving 10:40.\n\
+             Walk to EUS, about 8 minutes.\n\
+             You arrive at EUS at 10:48. Journey time 48 minutes, direct."
+        );
+    }
+}
@@ -0,0 +1,635 @@
+//! Printable itineraries.
+//!
+//! Signal drops out on plenty of rural lines, so a planned journey can be
+//! saved or printed as a one-page PDF: each train with its times and
+//! platforms, each change, and a QR code linking back to the live journey.
+//! The PDF is written directly - one A4 page in the standard Helvetica
+//! fonts, which every viewer has built in - so no PDF library is needed.
+
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+
+use crate::domain::{Change, Journey, Leg};
+
+use super::qr::QrCode;
+
+/// A4, in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+
+/// Where times, station names and platforms start on each line.
+const NAME_X: f32 = MARGIN + 50.0;
+const PLATFORM_X: f32 = PAGE_WIDTH - MARGIN - 80.0;
+
+/// Side of one QR code module, in points.
+const QR_MODULE: f32 = 3.0;
+
+/// Modules of light border a QR code needs to scan.
+const QR_QUIET_ZONE: usize = 4;
+
+/// Render a journey as a one-page PDF itinerary.
+///
+/// `link` is where the QR code points, if anywhere; `printed_at` is noted
+/// on the page, since the times are only as fresh as the plan.
+pub fn journey_to_pdf(journey: &Journey, link: Option<&str>, printed_at: NaiveDateTime) -> Vec<u8> {
+    let mut page = Page::default();
+    let mut y = PAGE_HEIGHT - MARGIN - 18.0;
+
+    let origin = journey.legs().next().map_or("", Leg::board_station_name);
+    // Walks only know their stations' codes
+    let destination = match journey.final_walk() {
+        Some(walk) => walk.to.as_str(),
+        None => journey.legs().last().map_or("", Leg::alight_station_name),
+    };
+    let title = format!("{origin} to {destination}");
+    page.text(MARGIN, y, Font::Bold, 18.0, &title);
+    y -= 20.0;
+
+    let changes = match journey.change_count() {
+        0 => "direct".to_string(),
+        1 => "1 change".to_string(),
+        n => format!("{n} changes"),
+    };
+    page.text(
+        MARGIN,
+        y,
+        Font::Regular,
+        11.0,
+        &format!(
+            "{}, {} to {}, {} ({})",
+            journey.departure_time().date().format("%A %-d %B %Y"),
+            journey.departure_time(),
+            journey.arrival_time(),
+            duration(journey.total_duration().num_minutes()),
+            changes
+        ),
+    );
+    y -= 12.0;
+    page.rule(MARGIN, y, PAGE_WIDTH - MARGIN);
+    y -= 26.0;
+
+    if let Some(first) = journey.legs().next() {
+        y = draw_leg(&mut page, y, first);
+    }
+    for change in journey.changes() {
+        y = draw_change(&mut page, y, &change);
+        y = draw_leg(&mut page, y, change.to);
+    }
+    if let Some(walk) = journey.final_walk() {
+        let note = format!(
+            "Walk to {}, about {} minutes",
+            walk.to,
+            walk.duration.num_minutes()
+        );
+        page.text(NAME_X, y, Font::Oblique, 10.0, &note);
+    }
+
+    draw_link(&mut page, link, printed_at);
+    page.finish(&title)
+}
+
+/// A train: where and when to board, the service, where and when to alight.
+fn draw_leg(page: &mut Page, mut y: f32, leg: &Leg) -> f32 {
+    let stop = |page: &mut Page, y: f32, time: String, name: &str, platform: Option<&str>| {
+        page.text(MARGIN, y, Font::Bold, 12.0, &time);
+        page.text(NAME_X, y, Font::Bold, 12.0, name);
+        if let Some(platform) = platform {
+            page.text(
+                PLATFORM_X,
+                y,
+                Font::Regular,
+                12.0,
+                &format!("Platform {platform}"),
+            );
+        }
+    };
+
+    stop(
+        page,
+        y,
+        leg.departure_time().to_string(),
+        leg.board_station_name(),
+        leg.board_platform(),
+    );
+    y -= 16.0;
+
+    let service = leg.service();
+    let mut description = match service.headcode {
+        Some(headcode) => format!("{} {}", service.operator, headcode),
+        None => service.operator.clone(),
+    };
+    let _ = write!(description, " to {}", service.destination_name());
+    if leg.is_cancelled() {
+        description.push_str(" - CANCELLED");
+    }
+    page.text(NAME_X, y, Font::Regular, 10.0, &description);
+    y -= 16.0;
+
+    stop(
+        page,
+        y,
+        leg.arrival_time().to_string(),
+        leg.alight_station_name(),
+        leg.alight_platform(),
+    );
+    y - 24.0
+}
+
+/// Getting from one train to the next.
+fn draw_change(page: &mut Page, y: f32, change: &Change<'_>) -> f32 {
+    let minutes = change.connection_time().num_minutes();
+    let note = match change.walk {
+        Some(walk) => format!(
+            "Walk to {}, about {} minutes ({} minutes to change)",
+            change.to.board_station_name(),
+            walk.duration.num_minutes(),
+            minutes
+        ),
+        None => format!(
+            "Change at {}: {} minutes",
+            change.from.alight_station_name(),
+            minutes
+        ),
+    };
+    page.text(NAME_X, y, Font::Oblique, 10.0, &note);
+    y - 26.0
+}
+
+/// The QR code back to the live journey, at the foot of the page, and
+/// when the page was printed.
+fn draw_link(page: &mut Page, link: Option<&str>, printed_at: NaiveDateTime) {
+    let printed = format!(
+        "Printed {}. Times may change; check before you travel.",
+        printed_at.format("%H:%M on %-d %B %Y")
+    );
+    let Some((link, code)) = link.and_then(|l| Some((l, QrCode::encode(l.as_bytes())?))) else {
+        page.text(MARGIN, MARGIN, Font::Oblique, 9.0, &printed);
+        return;
+    };
+    let side = (code.size() + 2 * QR_QUIET_ZONE) as f32 * QR_MODULE;
+    let top = MARGIN + side;
+    for y in 0..code.size() {
+        for x in 0..code.size() {
+            if code.is_dark(x, y) {
+                page.rect(
+                    MARGIN + (x + QR_QUIET_ZONE) as f32 * QR_MODULE,
+                    top - (y + QR_QUIET_ZONE + 1) as f32 * QR_MODULE,
+                    QR_MODULE,
+                    QR_MODULE,
+                );
+            }
+        }
+    }
+
+    let x = MARGIN + side + 10.0;
+    page.text(x, top - 20.0, Font::Bold, 11.0, "Live times");
+    page.text(
+        x,
+        top - 36.0,
+        Font::Regular,
+        9.0,
+        "Scan for the latest version of this journey:",
+    );
+    page.text(x, top - 48.0, Font::Regular, 9.0, link);
+    page.text(x, top - 64.0, Font::Oblique, 9.0, &printed);
+}
+
+/// "1h 30m" or "25 minutes".
+fn duration(mins: i64) -> String {
+    if mins >= 60 {
+        format!("{}h {}m", mins / 60, mins % 60)
+    } else {
+        format!("{mins} minutes")
+    }
+}
+
+/// The built-in fonts the page uses.
+#[derive(Debug, Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+    Oblique,
+}
+
+impl Font {
+    const ALL: [Font; 3] = [Font::Regular, Font::Bold, Font::Oblique];
+
+    fn resource(self) -> &'static str {
+        match self {
+            Self::Regular => "F1",
+            Self::Bold => "F2",
+            Self::Oblique => "F3",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Self::Regular => "Helvetica",
+            Self::Bold => "Helvetica-Bold",
+            Self::Oblique => "Helvetica-Oblique",
+        }
+    }
+}
+
+/// A single-page PDF's content stream, in points from the bottom left.
+#[derive(Default)]
+struct Page {
+    content: String,
+}
+
+impl Page {
+    fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        let _ = writeln!(
+            self.content,
+            "BT /{} {size} Tf {x} {y} Td ({}) Tj ET",
+            font.resource(),
+            escape(text)
+        );
+    }
+
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(self.content, "{x} {y} {width} {height} re f");
+    }
+
+    fn rule(&mut self, from_x: f32, y: f32, to_x: f32) {
+        let _ = writeln!(self.content, "0.5 w {from_x} {y} m {to_x} {y} l S");
+    }
+
+    /// The complete file: catalog, page tree, page, fonts, content and
+    /// document info, then the cross-reference table.
+    fn finish(self, title: &str) -> Vec<u8> {
+        let fonts: String = Font::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, f)| format!("/{} {} 0 R ", f.resource(), i + 4))
+            .collect();
+        let content_id = 4 + Font::ALL.len();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << {fonts}>> >> /Contents {content_id} 0 R >>"
+            ),
+        ];
+        objects.extend(Font::ALL.iter().map(|f| {
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                f.base_font()
+            )
+        }));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            self.content.len(),
+            self.content
+        ));
+        objects.push(format!(
+            "<< /Title ({}) /Producer (train-server) >>",
+            escape(title)
+        ));
+
+        let mut pdf = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            let _ = write!(pdf, "{} 0 obj\n{object}\nendobj\n", i + 1);
+        }
+
+        let xref = pdf.len();
+        let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(pdf, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            pdf,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1,
+            objects.len()
+        );
+        pdf.into_bytes()
+    }
+}
+
+/// Escape text for a PDF string in WinAnsi encoding.
+///
+/// Bytes outside printable ASCII are written as octal escapes, so the
+/// content stream stays ASCII. Characters WinAnsi lacks become "?".
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+                continue;
+            }
+            ' '..='~' => {
+                escaped.push(c);
+                continue;
+            }
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '\u{a0}'..='\u{ff}' => c as u8,
+            _ => b'?',
+        };
+        let _ = write!(escaped, "\\{byte:03o}");
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Call, CallIndex, Crs, RailTime, Segment, Service, ServiceRef, Walk};
+    use crate::web::qr::tests::decode;
+    use chrono::{Duration, NaiveDate};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    /// (crs, name, arr, dep, platform)
+    fn leg(calls: &[(&str, &str, &str, &str, &str)]) -> Segment {
+        let calls: Vec<Call> = calls
+            .iter()
+            .map(|(station, name, arr, dep, platform)| {
+                let mut call = Call::new(crs(station), (*name).to_string());
+                call.booked_arrival = (!arr.is_empty()).then(|| time(arr));
+                call.booked_departure = (!dep.is_empty()).then(|| time(dep));
+                call.platform = (!platform.is_empty()).then(|| (*platform).to_string());
+                call
+            })
+            .collect();
+        let last = calls.len() - 1;
+        let service = Arc::new(Service {
+            service_ref: ServiceRef::new("S".to_string(), calls[0].station),
+            headcode: None,
+            operator: "Great Western Railway".to_string(),
+            operator_code: None,
+            calls,
+            board_station_idx: CallIndex(0),
+            formation: None,
+        });
+        Segment::Train(Leg::new(service, CallIndex(0), CallIndex(last)).unwrap())
+    }
+
+    fn journey() -> Journey {
+        Journey::new(vec![
+            leg(&[
+                ("PAD", "London Paddington", "", "10:00", "1"),
+                ("RDG", "Reading", "10:25", "", "9"),
+            ]),
+            leg(&[
+                ("RDG", "Reading", "", "10:32", "8"),
+                ("KGX", "London Kings Cross", "11:30", "", ""),
+            ]),
+            Segment::Walk(Walk::new(crs("KGX"), crs("STP"), Duration::minutes(5))),
+            leg(&[
+                ("STP", "London St Pancras", "", "11:45", "3"),
+                ("LUT", "Luton (Beds)", "12:10", "", ""),
+            ]),
+        ])
+        .unwrap()
+    }
+
+    fn render_with(link: Option<&str>) -> String {
+        let printed = time("09:50").to_datetime();
+        let pdf = journey_to_pdf(&journey(), link, printed);
+        String::from_utf8(pdf).expect("PDF is written as ASCII")
+    }
+
+    fn render() -> String {
+        render_with(Some("https://example.org/j/abc"))
+    }
+
+    /// What a viewer gets from the page: the text shown in each font, and
+    /// the filled rectangles.
+    #[derive(Debug, Default)]
+    struct Read {
+        text: Vec<(String, String)>,
+        rects: Vec<[f32; 4]>,
+    }
+
+    /// The object numbered `n 0 R` after `key` in a dictionary.
+    fn reference(dict: &str, key: &str) -> usize {
+        let after = &dict[dict.find(key).unwrap_or_else(|| panic!("no {key}")) + key.len()..];
+        let mut words = after.split_whitespace();
+        let n = words
+            .next()
+            .unwrap()
+            .trim_start_matches('[')
+            .parse()
+            .unwrap();
+        assert_eq!(words.next(), Some("0"));
+        assert!(words.next().unwrap().starts_with('R'));
+        n
+    }
+
+    /// Follow the trailer to the page and run its content stream, the way
+    /// a viewer would.
+    fn read(pdf: &str) -> Read {
+        let xref: usize = pdf
+            .rsplit("startxref\n")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut lines = pdf[xref..].lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines.next().unwrap()[2..].parse().unwrap();
+        let objects: HashMap<usize, &str> = lines
+            .take(count)
+            .enumerate()
+            .skip(1)
+            .map(|(n, entry)| {
+                let offset: usize = entry[..10].parse().unwrap();
+                let body = pdf[offset..].strip_prefix(&format!("{n} 0 obj\n")).unwrap();
+                (n, &body[..body.find("\nendobj\n").unwrap()])
+            })
+            .collect();
+
+        let trailer = &pdf[pdf.rfind("trailer").unwrap()..];
+        let catalog = objects[&reference(trailer, "/Root")];
+        assert!(catalog.contains("/Type /Catalog"));
+        let pages = objects[&reference(catalog, "/Pages")];
+        assert!(pages.contains("/Count 1"));
+        let page = objects[&reference(pages, "/Kids")];
+        assert!(page.contains("/Type /Page "));
+
+        let stream = objects[&reference(page, "/Contents")];
+        let length = number(stream, "/Length");
+        let content = &stream[stream.find("stream\n").unwrap() + 7..];
+        assert_eq!(&content[length..], "endstream");
+        let content = &content[..length];
+
+        let mut result = Read::default();
+        let mut operands: Vec<String> = Vec::new();
+        let mut font = String::new();
+        let mut chars = content.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' | '\n' => {}
+                '(' => {
+                    let mut text = String::new();
+                    loop {
+                        match chars.next().unwrap() {
+                            ')' => break,
+                            '\\' => match chars.next().unwrap() {
+                                d @ '0'..='7' => {
+                                    let octal: String =
+                                        [d, chars.next().unwrap(), chars.next().unwrap()]
+                                            .iter()
+                                            .collect();
+                                    text.push(char::from(u8::from_str_radix(&octal, 8).unwrap()));
+                                }
+                                escaped => text.push(escaped),
+                            },
+                            other => text.push(other),
+                        }
+                    }
+                    operands.push(text);
+                }
+                _ => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next == ' ' || next == '\n' || next == '(' {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+                    let is_operand = word.starts_with('/') || word.parse::<f32>().is_ok();
+                    if is_operand {
+                        operands.push(word);
+                        continue;
+                    }
+                    let numbers: Vec<f32> =
+                        operands.iter().filter_map(|o| o.parse().ok()).collect();
+                    match word.as_str() {
+                        "Tf" => {
+                            font = operands[0].trim_start_matches('/').to_string();
+                            let resource = format!("/{font} ");
+                            assert!(page.contains(&resource), "{font} isn't a page resource");
+                        }
+                        "Tj" => result.text.push((font.clone(), operands.pop().unwrap())),
+                        "re" => result
+                            .rects
+                            .push([numbers[0], numbers[1], numbers[2], numbers[3]]),
+                        "BT" | "ET" | "Td" | "f" | "w" | "m" | "l" | "S" => {}
+                        other => panic!("unexpected operator {other}"),
+                    }
+                    operands.clear();
+                }
+            }
+        }
+        result
+    }
+
+    /// The number after `key` in a dictionary.
+    fn number(dict: &str, key: &str) -> usize {
+        let after = &dict[dict.find(key).unwrap() + key.len()..];
+        after.split_whitespace().next().unwrap().parse().unwrap()
+    }
+
+    /// The QR code drawn as the page's rectangles, decoded.
+    fn scan(rects: &[[f32; 4]]) -> Result<Vec<u8>, String> {
+        let left = rects.iter().map(|r| r[0]).fold(f32::MAX, f32::min);
+        let top = rects.iter().map(|r| r[1]).fold(f32::MIN, f32::max);
+        let dark: Vec<(usize, usize)> = rects
+            .iter()
+            .map(|r| {
+                assert_eq!((r[2], r[3]), (QR_MODULE, QR_MODULE));
+                (
+                    ((r[0] - left) / QR_MODULE).round() as usize,
+                    ((top - r[1]) / QR_MODULE).round() as usize,
+                )
+            })
+            .collect();
+        let size = dark.iter().map(|&(x, y)| x.max(y)).max().unwrap() + 1;
+        decode(size, |x, y| dark.contains(&(x, y)))
+    }
+
+    #[test]
+    fn itinerary_lists_legs_changes_and_link() {
+        let pdf = render();
+        assert!(pdf.contains("(London Paddington to Luton \\(Beds\\))"));
+        assert!(pdf.contains("(Friday 15 March 2024, 10:00 to 12:10, 2h 10m \\(2 changes\\))"));
+        assert!(pdf.contains("(Platform 9)"));
+        assert!(pdf.contains("(Change at Reading: 7 minutes)"));
+        assert!(
+            pdf.contains("(Walk to London St Pancras, about 5 minutes \\(15 minutes to change\\))")
+        );
+        assert!(pdf.contains("(https://example.org/j/abc)"));
+        assert!(pdf.contains("(Printed 09:50 on 15 March 2024."));
+        // The QR code's modules
+        assert!(pdf.contains(" re f\n"));
+    }
+
+    #[test]
+    fn cross_references_point_at_objects() {
+        let pdf = render();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+
+        let startxref = pdf.rsplit("startxref\n").next().unwrap();
+        let xref: usize = startxref.lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with("xref\n0 9\n"));
+
+        let entries = pdf[xref..].lines().skip(3).take(8);
+        for (i, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+        }
+    }
+
+    #[test]
+    fn viewers_read_the_itinerary_and_scan_the_link() {
+        let link = "https://trains.example.org/api/v1/journeys/0123456789abcdef/text";
+        let page = read(&render_with(Some(link)));
+
+        let text: Vec<&str> = page.text.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(text[0], "London Paddington to Luton (Beds)");
+        assert_eq!(page.text[0].0, "F2");
+        assert!(text.contains(&"Change at Reading: 7 minutes"));
+        assert!(text.contains(&link));
+        assert_eq!(scan(&page.rects), Ok(link.as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn without_a_link_there_is_no_code() {
+        let page = read(&render_with(None));
+
+        assert!(page.rects.is_empty());
+        assert!(!page.text.iter().any(|(_, t)| t.contains("Scan")));
+        assert!(
+            page.text
+                .iter()
+                .any(|(_, t)| t.starts_with("Printed 09:50"))
+        );
+    }
+
+    #[test]
+    fn escapes_to_win_ansi() {
+        assert_eq!(escape("a (b) c\\d"), "a \\(b\\) c\\\\d");
+        assert_eq!(escape("05:00–00:30"), "05:00\\22600:30");
+        assert_eq!(escape("Café →"), "Caf\\351 \\077");
+    }
+}
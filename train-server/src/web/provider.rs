@@ -0,0 +1,359 @@
+//! Where searches get their trains from.
+//!
+//! Handlers ask the [`ProviderSource`] in [`AppState`](super::AppState) for
+//! a provider at the time of each request. The source sits behind a trait
+//! object, so the data behind searches can be chosen when the server starts
+//! (or swapped in tests) without handlers knowing which it is.
+//!
+//! Near midnight a board reaching into tomorrow is fetched as two, one
+//! ending at midnight and one starting from it, and the two merged. The
+//! part after midnight is then dated and cached as tomorrow's, so searches
+//! either side of midnight share it, and trains late in the evening can't
+//! crowd tomorrow's first trains off a board cut short by Darwin.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+
+use crate::cache::CachedDarwinClient;
+use crate::darwin::{BoardWindow, ConvertedService, DarwinError, TIME_WINDOW_RANGE};
+use crate::domain::{Crs, RailTime, Service};
+use crate::error::Classify;
+use crate::planner::{DynServiceProvider, SearchError, ServiceProvider};
+
+/// Makes the service providers searches use.
+pub trait ProviderSource: Send + Sync {
+    /// A provider for searches made at the given date and time of day (in
+    /// minutes since midnight).
+    fn provider(&self, date: NaiveDate, current_mins: u16) -> Arc<dyn DynServiceProvider>;
+}
+
+/// Minutes before midnight from which boards reaching past it are fetched
+/// as today's and tomorrow's, by default.
+const DEFAULT_MIDNIGHT_SPLIT_MINS: u16 = 60;
+
+/// Provides trains from Darwin, through the cache.
+pub struct DarwinProviders {
+    darwin: Arc<CachedDarwinClient>,
+    midnight_split_mins: u16,
+}
+
+impl DarwinProviders {
+    /// Provide trains from the given client.
+    pub fn new(darwin: Arc<CachedDarwinClient>) -> Self {
+        Self {
+            darwin,
+            midnight_split_mins: DEFAULT_MIDNIGHT_SPLIT_MINS,
+        }
+    }
+
+    /// Split boards reaching past midnight into today's and tomorrow's for
+    /// searches made within `mins` of it (0 = never split).
+    pub fn with_midnight_split(mut self, mins: u16) -> Self {
+        self.midnight_split_mins = mins;
+        self
+    }
+}
+
+impl ProviderSource for DarwinProviders {
+    fn provider(&self, date: NaiveDate, current_mins: u16) -> Arc<dyn DynServiceProvider> {
+        Arc::new(CachedServiceProvider {
+            darwin: self.darwin.clone(),
+            date,
+            current_mins,
+            midnight_split_mins: self.midnight_split_mins,
+        })
+    }
+}
+
+/// Service provider that uses the cached Darwin client.
+pub struct CachedServiceProvider {
+    darwin: Arc<CachedDarwinClient>,
+    date: NaiveDate,
+    current_mins: u16,
+    midnight_split_mins: u16,
+}
+
+impl ServiceProvider for CachedServiceProvider {
+    async fn get_departures(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        // Offset the board to 'after' so Darwin returns relevant departures.
+        // Without this, we fetch from "now" and may miss trains departing after 'after'.
+        let services = self
+            .fetch_boards(station, after, |time_offset, time_window| {
+                self.darwin.get_departures_with_details(
+                    station,
+                    self.date,
+                    self.current_mins,
+                    time_offset,
+                    time_window,
+                )
+            })
+            .await?;
+
+        // Filter to departures after the specified time
+        // (still needed because Darwin might return trains slightly before 'after')
+        let filtered: Vec<Arc<Service>> = services
+            .iter()
+            .filter(|s| {
+                s.candidate
+                    .expected_departure
+                    .or(Some(s.candidate.scheduled_departure))
+                    .is_some_and(|t| t >= after)
+            })
+            .map(|s| Arc::new(s.service.clone()))
+            .collect();
+
+        Ok(filtered)
+    }
+
+    async fn get_arrivals(
+        &self,
+        station: &Crs,
+        after: RailTime,
+    ) -> Result<Vec<Arc<Service>>, SearchError> {
+        // Offset the board to 'after' so Darwin returns relevant arrivals.
+        // For arrivals-first search, we want trains arriving at the destination after
+        // the user could possibly reach them.
+        let services = self
+            .fetch_boards(station, after, |time_offset, time_window| {
+                self.darwin.get_arrivals_with_details(
+                    station,
+                    self.date,
+                    self.current_mins,
+                    time_offset,
+                    time_window,
+                )
+            })
+            .await?;
+
+        // Convert to Arc<Service> - arrivals include previousCallingPoints
+        // which is what we need for the arrivals-first algorithm
+        let result: Vec<Arc<Service>> = services
+            .iter()
+            .map(|s| Arc::new(s.service.clone()))
+            .collect();
+
+        Ok(result)
+    }
+
+    fn has_departures(&self, station: &Crs, after: RailTime) -> bool {
+        // A board too far ahead to ask for is answered without a call
+        self.board_windows(after)
+            .into_iter()
+            .all(|(time_offset, time_window)| {
+                self.darwin.has_departures(
+                    station,
+                    self.date,
+                    self.current_mins,
+                    time_offset,
+                    time_window,
+                )
+            })
+    }
+
+    fn has_arrivals(&self, station: &Crs, after: RailTime) -> bool {
+        self.board_windows(after)
+            .into_iter()
+            .all(|(time_offset, time_window)| {
+                self.darwin.has_arrivals(
+                    station,
+                    self.date,
+                    self.current_mins,
+                    time_offset,
+                    time_window,
+                )
+            })
+    }
+}
+
+impl CachedServiceProvider {
+    /// The Darwin time offset and window for a board starting at `after`,
+    /// or `None` if that's too far ahead to ask for (see
+    /// [`BoardWindow::starting`]).
+    fn board_window(&self, after: RailTime) -> Option<(i16, u16)> {
+        let now = BoardWindow::at(self.date, self.current_mins, 0, 0).now();
+        BoardWindow::starting(now, after, *TIME_WINDOW_RANGE.end())
+            .map(|window| (window.time_offset(), window.time_window()))
+    }
+
+    /// The Darwin time offsets and windows of the boards to fetch for
+    /// `after`: none if it's too far ahead, two if the board reaches past
+    /// midnight and it's within `midnight_split_mins` of it, else one.
+    fn board_windows(&self, after: RailTime) -> Vec<(i16, u16)> {
+        let Some((time_offset, time_window)) = self.board_window(after) else {
+            return Vec::new();
+        };
+        let window = BoardWindow::at(self.date, self.current_mins, time_offset, time_window);
+        let Some(tomorrow) = self.date.succ_opt() else {
+            return vec![(time_offset, time_window)];
+        };
+        let midnight = RailTime::new(tomorrow, NaiveTime::MIN);
+
+        let near_midnight = midnight.signed_duration_since(window.now())
+            <= Duration::minutes(i64::from(self.midnight_split_mins));
+        if self.midnight_split_mins == 0
+            || !near_midnight
+            || window.start() >= midnight
+            || window.end() <= midnight
+        {
+            return vec![(time_offset, time_window)];
+        }
+
+        let today = midnight.signed_duration_since(window.start()).num_minutes() as u16;
+        vec![
+            (time_offset, today),
+            (time_offset + today as i16, time_window - today),
+        ]
+    }
+
+    /// Fetch the boards for `after` with `fetch`, given each one's time
+    /// offset and window, merging them with each service kept once.
+    async fn fetch_boards<F, Fut>(
+        &self,
+        station: &Crs,
+        after: RailTime,
+        fetch: F,
+    ) -> Result<Vec<Arc<ConvertedService>>, SearchError>
+    where
+        F: Fn(i16, u16) -> Fut,
+        Fut: Future<Output = Result<Arc<Vec<Arc<ConvertedService>>>, DarwinError>>,
+    {
+        let mut services = Vec::new();
+        let mut seen = HashSet::new();
+        for (time_offset, time_window) in self.board_windows(after) {
+            let board =
+                fetch(time_offset, time_window)
+                    .await
+                    .map_err(|e| SearchError::FetchError {
+                        station: *station,
+                        message: e.to_string(),
+                        kind: e.kind(),
+                    })?;
+            services.extend(
+                board
+                    .iter()
+                    .filter(|s| seen.insert(s.service.service_ref.darwin_id.clone()))
+                    .cloned(),
+            );
+        }
+        Ok(services)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheConfig;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::domain::CallIndex;
+
+    #[tokio::test]
+    async fn darwin_providers_fetch_from_client() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let darwin = CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default());
+        let source: Arc<dyn ProviderSource> = Arc::new(DarwinProviders::new(Arc::new(darwin)));
+
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let provider = source.provider(date, 14 * 60);
+        let pad = Crs::parse("PAD").unwrap();
+        let after = RailTime::parse_hhmm("14:00", date).unwrap();
+        let departures = provider.get_departures(&pad, after).await.unwrap();
+
+        assert!(!departures.is_empty());
+        assert!(departures.iter().all(|s| s.calls_at(&pad, CallIndex(0))));
+    }
+
+    #[tokio::test]
+    async fn boards_are_known_cached_once_fetched() {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let darwin = CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default());
+        let source = DarwinProviders::new(Arc::new(darwin));
+
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let provider = source.provider(date, 14 * 60);
+        let pad = Crs::parse("PAD").unwrap();
+        let after = RailTime::parse_hhmm("14:00", date).unwrap();
+        assert!(!provider.has_departures(&pad, after));
+
+        provider.get_departures(&pad, after).await.unwrap();
+        assert!(provider.has_departures(&pad, after));
+        assert!(!provider.has_arrivals(&pad, after));
+
+        // Nothing can be fetched that far ahead, so nothing would be
+        let tomorrow = RailTime::parse_hhmm("14:00", date.succ_opt().unwrap()).unwrap();
+        assert!(provider.has_arrivals(&pad, tomorrow));
+    }
+
+    fn provider_at(split_mins: u16, current_mins: u16) -> (CachedServiceProvider, NaiveDate) {
+        let mock = MockDarwinClient::new("data/mock_boards").unwrap();
+        let darwin = CachedDarwinClient::new(DarwinClientImpl::Mock(mock), &CacheConfig::default());
+        let date = NaiveDate::from_ymd_opt(2026, 1, 3).unwrap();
+        let source = DarwinProviders::new(Arc::new(darwin)).with_midnight_split(split_mins);
+        let provider = CachedServiceProvider {
+            darwin: source.darwin.clone(),
+            date,
+            current_mins,
+            midnight_split_mins: source.midnight_split_mins,
+        };
+        (provider, date)
+    }
+
+    #[test]
+    fn boards_split_at_midnight_only_near_it() {
+        let (provider, date) = provider_at(60, 23 * 60 + 30);
+        let after = RailTime::parse_hhmm("23:40", date).unwrap();
+        assert_eq!(provider.board_windows(after), [(10, 20), (30, 90)]);
+
+        // A board starting after midnight is tomorrow's already
+        let after = RailTime::parse_hhmm("00:05", date.succ_opt().unwrap()).unwrap();
+        assert_eq!(provider.board_windows(after), [(35, 85)]);
+
+        let (provider, date) = provider_at(60, 22 * 60 + 15);
+        let after = RailTime::parse_hhmm("22:30", date).unwrap();
+        assert_eq!(provider.board_windows(after), [(15, 105)]);
+
+        let (provider, date) = provider_at(0, 23 * 60 + 30);
+        let after = RailTime::parse_hhmm("23:40", date).unwrap();
+        assert_eq!(provider.board_windows(after), [(10, 110)]);
+    }
+
+    #[tokio::test]
+    async fn tomorrows_board_is_fetched_and_cached_as_tomorrows() {
+        let (provider, date) = provider_at(60, 23 * 60 + 30);
+        let pad = Crs::parse("PAD").unwrap();
+        let after = RailTime::parse_hhmm("23:30", date).unwrap();
+
+        let departures = provider.get_departures(&pad, after).await.unwrap();
+
+        // The mock serves the same trains on both boards; each is kept once
+        let mut ids: Vec<&str> = departures
+            .iter()
+            .map(|s| s.service_ref.darwin_id.as_str())
+            .collect();
+        let count = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+
+        let tomorrow = date.succ_opt().unwrap();
+        assert!(
+            provider
+                .darwin
+                .has_departures(&pad, date, 23 * 60 + 30, 0, 30)
+        );
+        assert!(
+            provider
+                .darwin
+                .has_departures(&pad, date, 23 * 60 + 30, 30, 90)
+        );
+        // A search just after midnight reuses tomorrow's part
+        assert!(provider.darwin.has_departures(&pad, tomorrow, 0, 0, 90));
+        assert!(provider.has_departures(&pad, after));
+    }
+}
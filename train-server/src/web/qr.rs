@@ -0,0 +1,764 @@
+//! QR codes for printed itineraries.
+//!
+//! A printed itinerary carries a QR code linking back to the journey, so a
+//! traveller can pick up live times again once they have signal. Only what
+//! that needs is implemented: byte mode at medium error correction, in
+//! versions 1 to 10 (up to 213 bytes, plenty for a link).
+
+/// Largest version encoded.
+const MAX_VERSION: usize = 10;
+
+/// Error correction codewords per block at level M, by version.
+const ECC_PER_BLOCK: [usize; MAX_VERSION] = [10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+
+/// Error correction blocks at level M, by version.
+const BLOCKS: [usize; MAX_VERSION] = [1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+
+/// Level M's two format bits.
+const LEVEL_M: u32 = 0b00;
+
+/// A QR code: a square of dark and light modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode bytes, or `None` if they don't fit in the largest version.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=MAX_VERSION)
+            .find(|&v| 4 + count_bits(v) + data.len() * 8 <= data_codewords(v) * 8)?;
+        let codewords = add_ecc_and_interleave(version, &data_codewords_for(version, data));
+
+        let mut grid = Grid::new(version);
+        grid.draw_function_patterns(version);
+        grid.draw_codewords(&codewords);
+
+        // Any mask is valid; the spec's penalty picks the easiest to scan
+        (0..8)
+            .map(|mask| {
+                let mut masked = grid.clone();
+                masked.apply_mask(mask);
+                masked.draw_format_bits(mask);
+                masked
+            })
+            .min_by_key(Grid::penalty)
+            .map(|grid| Self {
+                size: grid.size,
+                modules: grid.modules,
+            })
+    }
+
+    /// Modules along each side, excluding the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the module at column `x`, row `y` is dark.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+}
+
+/// Bits in the byte-mode character count.
+fn count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+/// Modules available for data and error correction.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let aligns = version / 7 + 2;
+        modules -= (25 * aligns - 10) * aligns - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// Data codewords, excluding error correction.
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_PER_BLOCK[version - 1] * BLOCKS[version - 1]
+}
+
+/// The data segment, terminated and padded to fill the version.
+fn data_codewords_for(version: usize, data: &[u8]) -> Vec<u8> {
+    let capacity = data_codewords(version) * 8;
+    let mut bits = Vec::with_capacity(capacity);
+    let mut push = |value: usize, len: usize| {
+        bits.extend((0..len).rev().map(|i| (value >> i) & 1 == 1));
+    };
+    push(0b0100, 4);
+    push(data.len(), count_bits(version));
+    for &b in data {
+        push(usize::from(b), 8);
+    }
+
+    let terminator = (capacity - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    bits.resize(bits.len().div_ceil(8) * 8, false);
+
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | u8::from(bit)))
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+/// Split data into blocks, append each block's error correction, and
+/// interleave the blocks.
+fn add_ecc_and_interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let blocks = BLOCKS[version - 1];
+    let ecc_len = ECC_PER_BLOCK[version - 1];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_len = raw_codewords / blocks;
+    let divisor = reed_solomon_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut start = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[start..start + len].to_vec();
+        start += len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < short_blocks {
+            // Placeholder so every block has the same length
+            block.push(0);
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..split[0].len() {
+        for (j, block) in split.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// The Reed-Solomon generator polynomial of the given degree, highest
+/// coefficient (always 1) omitted.
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+/// The error correction codewords for a block.
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &b in data {
+        let factor = b ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    result
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u8 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1D);
+        z ^= ((y >> i) & 1) * x;
+    }
+    z
+}
+
+/// Format bits (level M and mask, with BCH error correction).
+fn format_bits(mask: u32) -> u32 {
+    let data = (LEVEL_M << 3) | mask;
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+/// Version bits (with BCH error correction), for versions 7 and up.
+fn version_bits(version: usize) -> u32 {
+    let version = version as u32;
+    let mut rem = version;
+    for _ in 0..12 {
+        rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+    }
+    (version << 12) | rem
+}
+
+/// Alignment pattern centres along each axis.
+fn alignment_positions(version: usize, size: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let aligns = version / 7 + 2;
+    let step = (version * 8 + aligns * 3 + 5) / (aligns * 4 - 4) * 2;
+    let mut positions: Vec<usize> = (0..aligns - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+fn bit(bits: u32, i: usize) -> bool {
+    (bits >> i) & 1 == 1
+}
+
+/// A QR code under construction.
+#[derive(Clone)]
+struct Grid {
+    size: usize,
+    modules: Vec<bool>,
+    /// Modules holding fixed patterns rather than data
+    function: Vec<bool>,
+}
+
+impl Grid {
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self, version: usize) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        self.draw_finder(3, 3);
+        self.draw_finder(size - 4, 3);
+        self.draw_finder(3, size - 4);
+
+        let positions = alignment_positions(version, size);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                let on_finder = (i == 0 && (j == 0 || j == last)) || (i == last && j == 0);
+                if !on_finder {
+                    self.draw_alignment(x, y);
+                }
+            }
+        }
+
+        // Reserve the format areas; the real bits go in once a mask is chosen
+        self.draw_format_bits(0);
+
+        if version >= 7 {
+            let bits = version_bits(version);
+            for i in 0..18 {
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                self.set_function(a, b, bit(bits, i));
+                self.set_function(b, a, bit(bits, i));
+            }
+        }
+    }
+
+    /// A finder pattern and its separator, centred on (x, y).
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4..=4_isize {
+            for dx in -4..=4_isize {
+                let (Some(xx), Some(yy)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if xx < self.size && yy < self.size {
+                    let dist = dx.abs().max(dy.abs());
+                    self.set_function(xx, yy, dist != 2 && dist != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in 0..5_usize {
+            for dx in 0..5_usize {
+                let dist = dx.abs_diff(2).max(dy.abs_diff(2));
+                self.set_function(x + dx - 2, y + dy - 2, dist != 1);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let size = self.size;
+
+        // Around the top-left finder
+        for i in 0..6 {
+            self.set_function(8, i, bit(bits, i));
+        }
+        self.set_function(8, 7, bit(bits, 6));
+        self.set_function(8, 8, bit(bits, 7));
+        self.set_function(7, 8, bit(bits, 8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(bits, i));
+        }
+
+        // Split between the other two
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(bits, i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(bits, i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Place codewords in the zigzag of two-module columns, right to left.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let total = codewords.len() * 8;
+        let mut i = 0;
+        let mut right = self.size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..self.size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let y = if upward { self.size - 1 - vert } else { vert };
+                    if !self.function[y * self.size + x] && i < total {
+                        self.modules[y * self.size + x] =
+                            (codewords[i / 8] >> (7 - i % 8)) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let idx = y * self.size + x;
+                self.modules[idx] ^= invert && !self.function[idx];
+            }
+        }
+    }
+
+    /// The spec's penalty score: long runs, 2x2 blocks, finder-like
+    /// patterns and an unbalanced dark/light ratio all make scanning harder.
+    fn penalty(&self) -> usize {
+        const FINDER_LIKE: [[bool; 11]; 2] = [
+            [
+                true, false, true, true, true, false, true, false, false, false, false,
+            ],
+            [
+                false, false, false, false, true, false, true, true, true, false, true,
+            ],
+        ];
+        let size = self.size;
+        let mut penalty = 0;
+
+        let lines = (0..size)
+            .map(|y| (0..size).map(|x| self.get(x, y)).collect::<Vec<_>>())
+            .chain((0..size).map(|x| (0..size).map(|y| self.get(x, y)).collect()));
+        for line in lines {
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            penalty += line
+                .windows(11)
+                .filter(|w| FINDER_LIKE.iter().any(|p| p == w))
+                .count()
+                * 40;
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let c = self.get(x, y);
+                if c == self.get(x + 1, y) && c == self.get(x, y + 1) && c == self.get(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let percent = dark * 100 / self.modules.len();
+        penalty + percent.abs_diff(50) / 5 * 10
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Data codewords per block at level M, by version (ISO/IEC 18004
+    /// Table 9).
+    const SPEC_DATA_BLOCKS: [&[usize]; MAX_VERSION] = [
+        &[16],
+        &[28],
+        &[44],
+        &[32, 32],
+        &[43, 43],
+        &[27, 27, 27, 27],
+        &[31, 31, 31, 31],
+        &[38, 38, 39, 39],
+        &[36, 36, 36, 37, 37],
+        &[43, 43, 43, 43, 44],
+    ];
+
+    /// Error correction codewords per block at level M, by version.
+    const SPEC_ECC: [usize; MAX_VERSION] = [10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+
+    /// Remainder bits after the last codeword, by version.
+    const SPEC_REMAINDER_BITS: [usize; MAX_VERSION] = [0, 7, 7, 7, 7, 7, 0, 0, 0, 0];
+
+    /// Format information at level M, by mask (Table C.1).
+    const SPEC_FORMAT: [u32; 8] = [
+        0x5412, 0x5125, 0x5E7C, 0x5B4B, 0x45F9, 0x40CE, 0x4F97, 0x4AA0,
+    ];
+
+    /// Version information for versions 7 to 10 (Table D.1).
+    const SPEC_VERSION: [u32; 4] = [0x07C94, 0x085BC, 0x09A99, 0x0A4D3];
+
+    /// Alignment pattern centres, by version (Annex E).
+    const SPEC_ALIGNMENT: [&[usize]; MAX_VERSION] = [
+        &[],
+        &[6, 18],
+        &[6, 22],
+        &[6, 26],
+        &[6, 30],
+        &[6, 34],
+        &[6, 22, 38],
+        &[6, 24, 42],
+        &[6, 26, 46],
+        &[6, 28, 50],
+    ];
+
+    /// Read a level M, byte mode QR code the way a scanner would, working
+    /// only from the spec's tables: check the fixed patterns, read the
+    /// format and version information, unmask, de-interleave, check every
+    /// block's Reed-Solomon syndromes and return the bytes.
+    ///
+    /// `dark(x, y)` gives the module at column `x`, row `y`.
+    pub(in crate::web) fn decode(
+        size: usize,
+        dark: impl Fn(usize, usize) -> bool,
+    ) -> Result<Vec<u8>, String> {
+        if size < 21 || !(size - 17).is_multiple_of(4) || (size - 17) / 4 > MAX_VERSION {
+            return Err(format!("no version has {size} modules a side"));
+        }
+        let version = (size - 17) / 4;
+        let mut function = vec![false; size * size];
+        let mut expect = |x: usize, y: usize, want: bool| {
+            function[y * size + x] = true;
+            if dark(x, y) == want {
+                Ok(())
+            } else {
+                Err(format!(
+                    "module ({x}, {y}) should be {}",
+                    if want { "dark" } else { "light" }
+                ))
+            }
+        };
+
+        // Finders with their separators
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4..=4_isize {
+                for dx in -4..=4_isize {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let ring = dx.abs().max(dy.abs());
+                        expect(x as usize, y as usize, ring <= 3 && ring != 2)?;
+                    }
+                }
+            }
+        }
+        for i in 8..size - 8 {
+            expect(i, 6, i % 2 == 0)?;
+            expect(6, i, i % 2 == 0)?;
+        }
+        let centres = SPEC_ALIGNMENT[version - 1];
+        for &cx in centres {
+            for &cy in centres {
+                let on_finder =
+                    (cx == 6 && (cy == 6 || cy == size - 7)) || (cx == size - 7 && cy == 6);
+                if on_finder {
+                    continue;
+                }
+                for y in cy - 2..=cy + 2 {
+                    for x in cx - 2..=cx + 2 {
+                        expect(x, y, x.abs_diff(cx).max(y.abs_diff(cy)) != 1)?;
+                    }
+                }
+            }
+        }
+        expect(8, size - 8, true)?;
+
+        // Format information, in two copies
+        let read = |cells: &[(usize, usize)]| {
+            cells
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, &(x, y))| bits | (u32::from(dark(x, y)) << i))
+        };
+        let mut first: Vec<(usize, usize)> = (0..6).map(|i| (8, i)).collect();
+        first.extend([(8, 7), (8, 8), (7, 8)]);
+        first.extend((9..15).map(|i| (14 - i, 8)));
+        let mut second: Vec<(usize, usize)> = (0..8).map(|i| (size - 1 - i, 8)).collect();
+        second.extend((8..15).map(|i| (8, size - 15 + i)));
+        let format = read(&first);
+        if read(&second) != format {
+            return Err("format information copies differ".to_string());
+        }
+        let mask = SPEC_FORMAT
+            .iter()
+            .position(|&f| f == format)
+            .ok_or_else(|| format!("format information {format:#x} isn't level M"))?;
+        for &(x, y) in first.iter().chain(&second) {
+            function[y * size + x] = true;
+        }
+
+        if version >= 7 {
+            let block: Vec<(usize, usize)> = (0..18).map(|i| (size - 11 + i % 3, i / 3)).collect();
+            let transposed: Vec<(usize, usize)> = block.iter().map(|&(x, y)| (y, x)).collect();
+            for cells in [&block, &transposed] {
+                if read(cells) != SPEC_VERSION[version - 7] {
+                    return Err("wrong version information".to_string());
+                }
+                for &(x, y) in cells.iter() {
+                    function[y * size + x] = true;
+                }
+            }
+        }
+
+        // Unmask the data modules, read upwards then downwards in pairs of
+        // columns from the right, skipping the vertical timing pattern
+        let masked = |x: usize, y: usize| {
+            let (i, j) = (y, x);
+            match mask {
+                0 => (i + j) % 2 == 0,
+                1 => i % 2 == 0,
+                2 => j % 3 == 0,
+                3 => (i + j) % 3 == 0,
+                4 => (i / 2 + j / 3) % 2 == 0,
+                5 => i * j % 2 + i * j % 3 == 0,
+                6 => (i * j % 2 + i * j % 3) % 2 == 0,
+                _ => ((i + j) % 2 + i * j % 3) % 2 == 0,
+            }
+        };
+        let mut bits = Vec::new();
+        let mut upward = true;
+        let mut right = size - 1;
+        loop {
+            for step in 0..size {
+                let y = if upward { size - 1 - step } else { step };
+                for x in [right, right - 1] {
+                    if !function[y * size + x] {
+                        bits.push(dark(x, y) ^ masked(x, y));
+                    }
+                }
+            }
+            if right == 1 {
+                break;
+            }
+            upward = !upward;
+            right -= 2;
+            if right == 6 {
+                right = 5;
+            }
+        }
+
+        let data_blocks = SPEC_DATA_BLOCKS[version - 1];
+        let ecc = SPEC_ECC[version - 1];
+        let total = data_blocks.iter().sum::<usize>() + ecc * data_blocks.len();
+        if bits.len() != total * 8 + SPEC_REMAINDER_BITS[version - 1] {
+            return Err(format!(
+                "{} data modules, expected {}",
+                bits.len(),
+                total * 8
+            ));
+        }
+        let mut codewords = bits
+            .chunks_exact(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, &b| (acc << 1) | u8::from(b)));
+
+        let mut blocks: Vec<Vec<u8>> = vec![Vec::new(); data_blocks.len()];
+        for i in 0..data_blocks.iter().max().copied().unwrap_or(0) {
+            for (block, &len) in blocks.iter_mut().zip(data_blocks) {
+                if i < len {
+                    block.extend(codewords.next());
+                }
+            }
+        }
+        let data: Vec<u8> = blocks.concat();
+        for _ in 0..ecc {
+            for block in &mut blocks {
+                block.extend(codewords.next());
+            }
+        }
+        for (b, block) in blocks.iter().enumerate() {
+            let mut root = 1;
+            for _ in 0..ecc {
+                let syndrome = block.iter().fold(0, |s, &c| gf_multiply(s, root) ^ c);
+                if syndrome != 0 {
+                    return Err(format!("block {b} fails its error correction check"));
+                }
+                root = gf_multiply(root, 0x02);
+            }
+        }
+
+        // Byte mode segment, terminator and padding
+        let bit_at = |i: usize| (data[i / 8] >> (7 - i % 8)) & 1;
+        let field = |start: usize, len: usize| {
+            (start..start + len).fold(0usize, |acc, i| (acc << 1) | usize::from(bit_at(i)))
+        };
+        if field(0, 4) != 0b0100 {
+            return Err("not a byte mode segment".to_string());
+        }
+        let count_len = if version < 10 { 8 } else { 16 };
+        let len = field(4, count_len);
+        let start = 4 + count_len;
+        if start + len * 8 > data.len() * 8 {
+            return Err(format!("{len} bytes don't fit"));
+        }
+        let bytes: Vec<u8> = (0..len).map(|i| field(start + i * 8, 8) as u8).collect();
+        let end = start + len * 8;
+        let padded_from = (end + 4).min(data.len() * 8).div_ceil(8);
+        if (end..padded_from * 8).any(|i| bit_at(i) != 0) {
+            return Err("no terminator".to_string());
+        }
+        let padding = data[padded_from..].iter().zip([0xEC, 0x11].iter().cycle());
+        if padding.into_iter().any(|(a, b)| a != b) {
+            return Err("wrong padding".to_string());
+        }
+        Ok(bytes)
+    }
+
+    #[test]
+    fn codes_of_every_version_decode_per_the_spec() {
+        // Each version's capacity in bytes at level M
+        let capacities = [14, 26, 42, 62, 84, 106, 122, 152, 180, 213];
+        for (i, len) in capacities.into_iter().enumerate() {
+            let data: Vec<u8> = (0..len).map(|b| (b * 37 + len) as u8).collect();
+            let code = QrCode::encode(&data).unwrap();
+            assert_eq!(code.size(), (i + 1) * 4 + 17);
+            assert_eq!(decode(code.size(), |x, y| code.is_dark(x, y)), Ok(data));
+        }
+    }
+
+    #[test]
+    fn damaged_codes_fail_to_decode() {
+        let code = QrCode::encode(b"https://example.org/api/v1/journeys/abc/text").unwrap();
+        let size = code.size();
+        // A data module in the bottom-right corner
+        let damaged = |x: usize, y: usize| code.is_dark(x, y) ^ (x == size - 1 && y == size - 1);
+        assert!(
+            decode(size, damaged)
+                .unwrap_err()
+                .contains("error correction")
+        );
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_spec_example() {
+        // "01234567" at 1-M, from ISO/IEC 18004 Annex I
+        let data = [
+            16, 32, 12, 86, 97, 128, 236, 17, 236, 17, 236, 17, 236, 17, 236, 17,
+        ];
+        let ecc = reed_solomon_remainder(&data, &reed_solomon_divisor(10));
+        assert_eq!(ecc, [165, 36, 212, 193, 237, 54, 199, 135, 44, 85]);
+    }
+
+    #[test]
+    fn format_and_version_bits_match_the_spec_tables() {
+        assert_eq!(format_bits(0), 0b101010000010010);
+        assert_eq!(format_bits(5), 0b100000011001110);
+        assert_eq!(format_bits(7), 0b100101010100000);
+        assert_eq!(version_bits(7), 0x07C94);
+        assert_eq!(version_bits(10), 0x0A4D3);
+    }
+
+    #[test]
+    fn capacities() {
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(10), 216);
+        assert_eq!(alignment_positions(7, 45), [6, 22, 38]);
+    }
+
+    #[test]
+    fn picks_the_smallest_version_that_fits() {
+        assert_eq!(QrCode::encode(b"hello").unwrap().size(), 21);
+
+        // 64 bytes, one more than version 4 holds
+        let link = b"https://trains.example.org/api/v1/journeys/0123456789abcdef/text";
+        assert_eq!(QrCode::encode(link).unwrap().size(), 5 * 4 + 17);
+
+        assert!(QrCode::encode(&[b'x'; 213]).is_some());
+        assert!(QrCode::encode(&[b'x'; 214]).is_none());
+    }
+
+    #[test]
+    fn finder_patterns_in_three_corners() {
+        let code = QrCode::encode(b"hello").unwrap();
+        let last = code.size() - 1;
+        for (x, y) in [(0, 0), (last, 0), (0, last)] {
+            assert!(code.is_dark(x, y));
+        }
+        assert!(code.is_dark(3, 3));
+        assert!(!code.is_dark(1, 1));
+        assert!(code.is_dark(8, last - 7));
+    }
+}
@@ -0,0 +1,71 @@
+//! The caller's recently finished journeys.
+//!
+//! Watched journeys are archived once they arrive (see
+//! [`super::journey_archive`]); these routes list them, with each train's
+//! planned times beside the times last observed, as a page and as JSON.
+//! Like watches, journeys belong to the caller's API key or watcher cookie.
+
+use askama::Template;
+use axum::{
+    Router,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+};
+
+use super::compact::{FormatQuery, json_response};
+use super::dto::{RecentJourneyResult, RecentJourneysResponse};
+use super::journey_archive::ArchivedJourney;
+use super::state::AppState;
+use super::templates::{RecentJourneyView, RecentJourneysTemplate};
+use super::time_format::TimeFormat;
+use super::watches::owner;
+
+/// Routes for the recent journeys API, relative to the versioned API
+/// prefix.
+pub fn recent_journey_routes() -> Router<AppState> {
+    Router::new().route("/journeys/recent", get(list_recent_journeys))
+}
+
+/// The caller's finished journeys, most recent first.
+async fn recent_journeys_for(state: &AppState, headers: &HeaderMap) -> Vec<ArchivedJourney> {
+    match owner(headers) {
+        Some(owner) => state.archive.list(&owner).await,
+        None => Vec::new(),
+    }
+}
+
+/// List the caller's finished journeys, most recent first.
+async fn list_recent_journeys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let journeys = recent_journeys_for(&state, &headers)
+        .await
+        .iter()
+        .map(|journey| RecentJourneyResult::from_archived(journey, &times))
+        .collect();
+    json_response(&RecentJourneysResponse { journeys }, &format)
+}
+
+/// Page listing the caller's finished journeys.
+pub(super) async fn recent_journeys_page(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> impl IntoResponse {
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let journeys = recent_journeys_for(&state, &headers)
+        .await
+        .iter()
+        .map(|journey| RecentJourneyView::from_archived(journey, &times))
+        .collect();
+    Html(
+        RecentJourneysTemplate { journeys }
+            .render()
+            .unwrap_or_else(|e| format!("Template error: {}", e)),
+    )
+}
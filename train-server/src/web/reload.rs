@@ -0,0 +1,240 @@
+//! Reloading search settings while the server runs.
+//!
+//! The search configuration and walkable connections are held together as
+//! one immutable [`Snapshot`]. A request takes the current snapshot once and
+//! uses it throughout, and a reload or admin edit swaps in a whole new one,
+//! so no search sees a mix of old and new settings. Nothing else in
+//! [`AppState`] is rebuilt, so sessions, watches and open long-polls carry
+//! on across a reload.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::planner::SearchConfig;
+use crate::stations::StationNames;
+use crate::walkable::{WalkableConnections, WalkableError, WalkableStoreError};
+
+use super::state::AppState;
+
+/// Where a reload gets fresh search configuration from.
+pub type ConfigSource = Arc<dyn Fn() -> SearchConfig + Send + Sync>;
+
+/// The settings searches run with, as of one reload or edit.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Journey planner configuration
+    pub config: Arc<SearchConfig>,
+
+    /// Walkable connections between stations
+    pub walkable: Arc<WalkableConnections>,
+}
+
+/// The current [`Snapshot`], replaced whole by reloads and edits.
+#[derive(Clone)]
+pub struct LiveSettings {
+    current: Arc<RwLock<Arc<Snapshot>>>,
+
+    /// Held while an update is made, so concurrent updates can't lose each
+    /// other's changes
+    updates: Arc<Mutex<()>>,
+
+    /// Held by edits and reloads while they read or write the stores the
+    /// settings are kept in
+    writes: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl LiveSettings {
+    /// Start with the given settings.
+    pub fn new(config: SearchConfig, walkable: WalkableConnections) -> Self {
+        let snapshot = Snapshot {
+            config: Arc::new(config),
+            walkable: Arc::new(walkable),
+        };
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(snapshot))),
+            updates: Arc::new(Mutex::new(())),
+            writes: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// The settings as they are now.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the settings with ones made from the current settings.
+    ///
+    /// Updates are made one at a time. If `update` fails, the current
+    /// settings are kept.
+    pub fn update<T, E>(
+        &self,
+        update: impl FnOnce(&Snapshot) -> Result<(Snapshot, T), E>,
+    ) -> Result<T, E> {
+        let _updating = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let (next, result) = update(&self.snapshot())?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(next);
+        Ok(result)
+    }
+
+    /// Wait for other edits and reloads to finish, and hold off new ones
+    /// until the guard is dropped.
+    ///
+    /// Taken for the whole of an edit or reload, so the stores can be read
+    /// and written outside [`update`](Self::update) without one losing
+    /// another's changes.
+    pub async fn lock_writes(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.writes.lock().await
+    }
+}
+
+/// What a reload changed.
+#[derive(Debug, Default)]
+pub struct ReloadSummary {
+    /// Whether the search configuration was reloaded
+    pub config: bool,
+
+    /// Walkable connections loaded from the store, if it has a file
+    pub walkable: Option<usize>,
+
+    /// Stored walkable connections rejected as implausible
+    pub rejected: Vec<WalkableError>,
+
+    /// Stations loaded from the station cache, if it has a fresh one
+    pub stations: Option<usize>,
+}
+
+/// Reload the station cache, walkable connections and search configuration.
+///
+/// Each comes from where it came from at startup: the station cache file,
+/// the walkable store and the configuration source. Anything without a
+/// source keeps its current value. The new settings are swapped in together;
+/// if the walkable store can't be read, they're left as they were.
+pub async fn reload(state: &AppState) -> Result<ReloadSummary, WalkableStoreError> {
+    let _writing = state.settings.lock_writes().await;
+    let stations = state.station_names.reload_cache().await;
+
+    let mut walkable = match &state.walkable_store {
+        Some(store) => store.load()?,
+        None => None,
+    };
+    let rejected = match &mut walkable {
+        Some(walkable) => remove_implausible_walks(walkable, &state.station_names).await,
+        None => Vec::new(),
+    };
+    let config = state.config_source.as_ref().map(|source| source());
+
+    let summary = ReloadSummary {
+        config: config.is_some(),
+        walkable: walkable.as_ref().map(WalkableConnections::len),
+        rejected,
+        stations,
+    };
+    state.settings.update(|current| {
+        let next = Snapshot {
+            config: config.map_or_else(|| current.config.clone(), Arc::new),
+            walkable: walkable.map_or_else(|| current.walkable.clone(), Arc::new),
+        };
+        Ok((next, summary))
+    })
+}
+
+/// Remove walks too quick for the distance between their stations, as far
+/// as the stations' locations are known.
+///
+/// Returns why each removed connection was rejected.
+pub async fn remove_implausible_walks(
+    walkable: &mut WalkableConnections,
+    names: &StationNames,
+) -> Vec<WalkableError> {
+    let stations: Vec<_> = walkable.iter().flat_map(|(a, b, _)| [a, b]).collect();
+    let locations = names.locations(stations).await;
+    walkable.remove_implausible(|a, b| Some(locations.get(a)?.distance_metres(locations.get(b)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheConfig, CachedDarwinClient};
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::domain::Crs;
+    use crate::stations::{StationClient, StationClientConfig};
+    use crate::walkable::{WalkableStore, london_connections};
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn state() -> AppState {
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        )
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_in_new_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = WalkableStore::new(dir.path().join("walkable.json"));
+        let mut saved = london_connections();
+        saved.set(crs("PAD"), crs("MYB"), 25).unwrap();
+        store.save(&saved).unwrap();
+
+        let state = state()
+            .with_walkable_store(store)
+            .with_config_source(|| SearchConfig {
+                max_changes: 1,
+                ..SearchConfig::default()
+            });
+        let before = state.settings.snapshot();
+
+        let summary = reload(&state).await.unwrap();
+
+        assert!(summary.config);
+        assert_eq!(summary.walkable, Some(saved.len()));
+        assert_eq!(summary.stations, None);
+        let after = state.settings.snapshot();
+        assert_eq!(after.config.max_changes, 1);
+        assert!(after.walkable.is_walkable(&crs("MYB"), &crs("PAD")));
+        // Searches already holding the old snapshot keep it
+        assert!(!before.walkable.is_walkable(&crs("MYB"), &crs("PAD")));
+    }
+
+    #[tokio::test]
+    async fn unreadable_store_leaves_settings_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("walkable.json");
+        std::fs::write(&path, "not json").unwrap();
+        let state = state()
+            .with_walkable_store(WalkableStore::new(path))
+            .with_config_source(|| SearchConfig {
+                max_changes: 1,
+                ..SearchConfig::default()
+            });
+
+        assert!(reload(&state).await.is_err());
+        assert_eq!(
+            state.settings.snapshot().config.max_changes,
+            SearchConfig::default().max_changes
+        );
+    }
+
+    #[tokio::test]
+    async fn reload_without_sources_keeps_settings() {
+        let state = state();
+        let before = state.settings.snapshot();
+
+        let summary = reload(&state).await.unwrap();
+
+        assert!(!summary.config);
+        assert_eq!(summary.walkable, None);
+        let after = state.settings.snapshot();
+        assert!(Arc::ptr_eq(&before.config, &after.config));
+        assert!(Arc::ptr_eq(&before.walkable, &after.walkable));
+    }
+}
@@ -1,47 +1,182 @@
 //! HTTP route handlers.
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use askama::Template;
 use axum::body::Bytes;
 use axum::{
     Json, Router,
-    extract::{Query, State},
-    http::{HeaderMap, StatusCode, header},
-    response::{Html, IntoResponse, Response},
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post},
 };
-use chrono::{Local, NaiveDate, Timelike};
+use chrono::{NaiveDate, Timelike};
+use tower_http::compression::CompressionLayer;
 use tower_http::services::ServeDir;
 
-use crate::domain::{CallIndex, Crs, Service};
-use crate::planner::{Planner, SearchError, SearchRequest};
-
+use crate::darwin::{BoardFilter, BoardWindow, ReasonTranslation};
+use crate::domain::{CallIndex, Crs, Headcode, Journey, Leg, RailTime, Service, ServiceIdentity};
+use crate::error::{Classify, ErrorKind};
+use crate::planner::phase::in_phase;
+use crate::planner::{
+    BoardRequest, Continuation, DataQuality, Planner, SearchConfig, SearchError, SearchEstimate,
+    SearchRequest, SearchResult, rank_reasons,
+};
+use crate::refresh::{JourneyRefresher, Refresh};
+use crate::resolver::{RESOLVE_PHASE, ServiceResolver};
+use crate::tracking::advance_position;
+use crate::walkable::WalkableConnections;
+
+use super::admin::admin_routes;
+use super::analytics::{SearchEvent, opted_out};
+use super::compact::{FormatQuery, json_response};
 use super::dto::*;
+use super::etag::conditional;
+use super::experiments::Subject;
+use super::favourites::{favourite_routes, favourites_for};
+use super::geojson::{journey_stations, journey_to_geojson};
+use super::journey_diff::diff;
+use super::narration::narrate;
+use super::pdf::journey_to_pdf;
+use super::recent_journeys::{recent_journey_routes, recent_journeys_page};
+use super::search_queue::SearchPermit;
+use super::session::Session;
 use super::state::AppState;
+use super::station_labels::name_stations;
 use super::templates::*;
+use super::time_format::TimeFormat;
+use super::versioning::{API_V1, deprecate_unversioned, force_json};
+use super::watch_store::random_id;
+use super::watches::{alert_watchers, archive_if_arrived, owner, watch_routes};
 
 /// Create the application router.
 ///
 /// `static_dir` is the path to the static assets directory.
+///
+/// The JSON API is mounted under `/api/v1`; the original unversioned paths
+/// are kept as deprecated shims (see [`super::versioning`]).
 pub fn create_router(state: AppState, static_dir: &str) -> Router {
+    // Board and journey responses support conditional requests
+    let etag = middleware::from_fn(conditional);
+    let legacy = Router::new()
+        .route("/api/stations/search", get(search_stations))
+        .route("/search/service", get(search_service).layer(etag.clone()))
+        .route("/identify", get(identify_train).layer(etag.clone()))
+        .route("/journey/plan", post(plan_journey).layer(etag.clone()))
+        .route("/journey/current", get(current_journey).layer(etag.clone()))
+        .route(
+            "/api/journeys/:id/geojson",
+            get(journey_geojson).layer(etag.clone()),
+        )
+        .route("/session", get(get_session).delete(reset_session))
+        .route("/session/reset", post(reset_session))
+        .layer(middleware::from_fn(deprecate_unversioned));
+
     Router::new()
         .route("/", get(index_page))
         .route("/health", get(health))
+        .route("/metrics", get(metrics))
         .route("/about", get(about_page))
-        .route("/api/stations/search", get(search_stations))
-        .route("/search/service", get(search_service))
-        .route("/identify", get(identify_train))
-        .route("/journey/plan", post(plan_journey))
+        .route("/journeys/recent", get(recent_journeys_page))
+        .merge(legacy)
+        .nest(API_V1, api_v1_routes())
         .nest_service("/static", ServeDir::new(static_dir))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            mark_darwin_fallback,
+        ))
+        // Most users are on trains with patchy mobile data
+        .layer(CompressionLayer::new())
         .with_state(state)
 }
 
+/// Version 1 of the JSON API, relative to [`API_V1`].
+fn api_v1_routes() -> Router<AppState> {
+    let etag = middleware::from_fn(conditional);
+    Router::new()
+        .route("/stations/search", get(search_stations))
+        .route("/search/service", get(search_service).layer(etag.clone()))
+        .route("/identify", get(identify_train).layer(etag.clone()))
+        .route("/journey/plan", post(plan_journey).layer(etag.clone()))
+        .route("/journey/current", get(current_journey).layer(etag.clone()))
+        .route("/journeys/estimate", post(estimate_journey))
+        .route(
+            "/departures/reachable",
+            get(reachable_departures).layer(etag.clone()),
+        )
+        .route(
+            "/journeys/:id/geojson",
+            get(journey_geojson).layer(etag.clone()),
+        )
+        .route("/journeys/:id/text", get(journey_text).layer(etag.clone()))
+        .route("/journeys/:id/pdf", get(journey_pdf).layer(etag.clone()))
+        .route("/journeys/:id/changes", get(journey_changes))
+        .route("/journeys/:id/boarded", post(boarded_leg))
+        .route("/session", get(get_session).delete(reset_session))
+        .route("/session/reset", post(reset_session))
+        .merge(admin_routes())
+        .merge(watch_routes())
+        .merge(favourite_routes())
+        .merge(recent_journey_routes())
+        .layer(middleware::from_fn(force_json))
+}
+
+/// Response header set while Darwin data comes from the public API because
+/// the configured staff API rejected our credentials.
+const DARWIN_SOURCE_HEADER: &str = "x-darwin-source";
+
+/// Tag responses served while the staff API is degraded.
+///
+/// The fallback itself is transparent, so this header (alongside the
+/// counter on `/metrics`) is how operators notice a misconfigured staff key.
+async fn mark_darwin_fallback(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    if state.darwin.client().is_staff_degraded() {
+        response.headers_mut().insert(
+            DARWIN_SOURCE_HEADER,
+            HeaderValue::from_static("public-fallback"),
+        );
+    }
+    response
+}
+
 /// Health check endpoint.
 async fn health() -> &'static str {
     "ok"
 }
 
+/// Operational metrics in Prometheus text format.
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let client = state.darwin.client();
+    let body = format!(
+        "# HELP darwin_staff_fallbacks_total Staff API calls that fell back to the public API.\n\
+         # TYPE darwin_staff_fallbacks_total counter\n\
+         darwin_staff_fallbacks_total {}\n\
+         # HELP darwin_staff_degraded Whether the most recent staff API call fell back.\n\
+         # TYPE darwin_staff_degraded gauge\n\
+         darwin_staff_degraded {}\n\
+         # HELP searches_running Journey searches running now.\n\
+         # TYPE searches_running gauge\n\
+         searches_running {}\n\
+         # HELP searches_rejected_total Searches turned away because too many were running.\n\
+         # TYPE searches_rejected_total counter\n\
+         searches_rejected_total {}\n",
+        client.staff_fallbacks(),
+        u8::from(client.is_staff_degraded()),
+        state.search_queue.running(),
+        state.search_queue.rejected(),
+    ) + &state.darwin.call_metrics().render();
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
 /// Index page with search form.
 async fn index_page() -> impl IntoResponse {
     Html(
@@ -64,7 +199,8 @@ async fn about_page() -> impl IntoResponse {
 async fn search_stations(
     State(state): State<AppState>,
     Query(req): Query<StationSearchRequest>,
-) -> Json<StationSearchResponse> {
+    Query(format): Query<FormatQuery>,
+) -> Response {
     let limit = req.limit.unwrap_or(10).min(50);
     let matches = state.station_names.search(&req.q, limit).await;
 
@@ -76,7 +212,76 @@ async fn search_stations(
         })
         .collect();
 
-    Json(StationSearchResponse { stations })
+    json_response(&StationSearchResponse { stations }, &format)
+}
+
+/// Maximum number of station suggestions returned with an invalid CRS error.
+const MAX_STATION_SUGGESTIONS: usize = 5;
+
+/// A form field's value, or `None` if absent or blank.
+fn non_blank(field: &Option<String>) -> Option<&str> {
+    field.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// How long before a train is due at the next station its board starts,
+/// when identifying it, so a slightly early train or wrong guess is kept.
+const IDENTIFY_LEAD_MINS: i64 = 10;
+
+/// Parse a time of day the user gave, dated whichever day puts it nearest
+/// `now`, so "00:10" asked for just before midnight is tomorrow.
+fn parse_board_time(text: &str, now: RailTime) -> Result<RailTime, AppError> {
+    let time = RailTime::parse_hhmm(text, now.date()).map_err(|_| AppError::BadRequest {
+        message: format!("Invalid time: {}", text),
+    })?;
+    let date = BoardWindow::new(now, 0, 0).date_of(time.time());
+    Ok(RailTime::new(date, time.time()))
+}
+
+/// The board requested at `now` starting at `from`, or a bad request if
+/// that's beyond what Darwin shows.
+fn board_from(now: RailTime, from: RailTime, time_window: u16) -> Result<BoardWindow, AppError> {
+    BoardWindow::starting(now, from, time_window).ok_or_else(|| AppError::BadRequest {
+        message: format!("Boards only reach two hours ahead, so can't show {from}"),
+    })
+}
+
+/// Parse a station CRS code from request input.
+///
+/// Fails with suggestions if the input isn't a well-formed CRS code, or is
+/// well-formed but not a known station. Without station reference data (e.g.
+/// mock mode) any well-formed code is accepted.
+pub(super) async fn parse_station(
+    state: &AppState,
+    input: &str,
+    field: &str,
+) -> Result<Crs, AppError> {
+    if let Ok(crs) = Crs::parse_normalized(input.trim()) {
+        let names = &state.station_names;
+        if names.is_empty().await || names.contains(&crs).await {
+            return Ok(crs);
+        }
+    }
+
+    let suggestions: Vec<StationSearchResult> = state
+        .station_names
+        .suggest(input, MAX_STATION_SUGGESTIONS)
+        .await
+        .into_iter()
+        .map(|m| StationSearchResult {
+            crs: m.crs,
+            name: m.name,
+        })
+        .collect();
+
+    let mut message = format!("Invalid {field} CRS: {input}");
+    if let Some(best) = suggestions.first() {
+        message.push_str(&format!("; did you mean {} ({})?", best.crs, best.name));
+    }
+
+    Err(AppError::UnknownStation {
+        message,
+        suggestions,
+    })
 }
 
 /// Check if request accepts HTML.
@@ -87,77 +292,138 @@ fn accepts_html(headers: &HeaderMap) -> bool {
         .is_some_and(|accept| accept.contains("text/html"))
 }
 
+/// Phase the service search's Darwin calls are attributed to.
+const SEARCH_PHASE: &str = "search";
+
+/// Phase train identification's Darwin calls are attributed to.
+const IDENTIFY_PHASE: &str = "identify";
+
 /// Search for services from a station.
 async fn search_service(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(req): Query<SearchServiceRequest>,
+    Query(format): Query<FormatQuery>,
 ) -> Result<Response, AppError> {
-    // Parse origin CRS
-    let origin_crs = Crs::parse_normalized(&req.origin).map_err(|_| AppError::BadRequest {
-        message: format!("Invalid origin CRS: {}", req.origin),
-    })?;
-
     // Parse optional destination CRS
-    let dest_crs = req
-        .destination
-        .as_ref()
-        .map(|d| Crs::parse_normalized(d))
-        .transpose()
-        .map_err(|_| AppError::BadRequest {
-            message: format!(
-                "Invalid destination CRS: {}",
-                req.destination.as_deref().unwrap_or("")
-            ),
-        })?;
+    let dest_crs = match req.destination.as_deref() {
+        Some(d) => Some(parse_station(&state, d, "destination").await?),
+        None => None,
+    };
 
     // Get current time info
-    let now = Local::now();
-    let date = now.date_naive();
+    let now = state.clock.now();
+    let date = now.date();
     let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
 
+    // Start the board at the time asked for, which may be well after now
+    let board_now = BoardWindow::at(date, current_mins, 0, 0).now();
+    let from = match non_blank(&req.time) {
+        Some(time) => parse_board_time(time, board_now)?,
+        None => board_now,
+    };
+    let window = board_from(board_now, from, 120)?;
+    let (offset, span) = (window.time_offset(), window.time_window());
+
+    // A group's name gives the merged board of all its stations
+    let group = state.station_groups.find(&req.origin);
+
     // Fetch departures
-    let services = match dest_crs {
-        Some(dest) => state
-            .darwin
-            .get_departures_to(&origin_crs, date, current_mins, 0, 120, &dest)
-            .await
-            .map_err(AppError::from)?,
-        None => {
-            let all = state
+    let mut board_stations = Vec::new();
+    let services = if let Some(group) = group {
+        board_stations.extend(group.members.iter().copied());
+        let board =
+            state
                 .darwin
-                .get_departures_with_details(&origin_crs, date, current_mins, 0, 120)
-                .await
-                .map_err(AppError::from)?;
-            all.iter().cloned().collect()
+                .get_merged_departures(&group.members, date, current_mins, offset, span);
+        let all = in_phase(SEARCH_PHASE, board)
+            .await
+            .map_err(AppError::from)?;
+        match dest_crs {
+            Some(dest) => all
+                .into_iter()
+                .filter(|s| s.service.calls.iter().any(|c| c.station == dest))
+                .collect(),
+            None => all,
+        }
+    } else {
+        let origin_crs = parse_station(&state, &req.origin, "origin").await?;
+        board_stations.push(origin_crs);
+        match dest_crs {
+            Some(dest) => {
+                let board = state.darwin.get_departures_to(
+                    &origin_crs,
+                    date,
+                    current_mins,
+                    offset,
+                    span,
+                    &dest,
+                );
+                in_phase(SEARCH_PHASE, board)
+                    .await
+                    .map_err(AppError::from)?
+            }
+            None => {
+                let board = state.darwin.get_departures_with_details(
+                    &origin_crs,
+                    date,
+                    current_mins,
+                    offset,
+                    span,
+                );
+                let all = in_phase(SEARCH_PHASE, board)
+                    .await
+                    .map_err(AppError::from)?;
+                all.iter().cloned().collect()
+            }
         }
     };
 
-    // Filter by headcode if specified
-    let services: Vec<_> = if let Some(ref headcode) = req.headcode {
-        let headcode_upper = headcode.to_uppercase();
+    // Filter by headcode if specified (blank form fields mean no filter)
+    let headcode_filter = req.headcode.as_deref().filter(|h| !h.trim().is_empty());
+    let services: Vec<_> = if let Some(headcode) = headcode_filter {
+        let wanted = Headcode::parse_normalized(headcode).ok_or_else(|| AppError::BadRequest {
+            message: format!("Invalid headcode: {}", headcode),
+        })?;
         services
             .into_iter()
-            .filter(|s| {
-                s.service
-                    .headcode
-                    .as_ref()
-                    .is_some_and(|h| h.to_string() == headcode_upper)
-            })
+            .filter(|s| s.service.headcode == Some(wanted))
             .collect()
     } else {
         services
     };
 
+    let mut filter = BoardFilter::new();
+    if let Some(platforms) = &req.platform {
+        filter = filter.with_platforms(platforms);
+    }
+    if let Some(operator) = &req.operator {
+        filter = filter.with_operator(operator);
+    }
+    if let Some(towards) = &req.towards {
+        filter = filter.with_destination(towards);
+    }
+    let services = filter.apply(services);
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let messages = board_messages(&state, &board_stations);
+
     // Return HTML or JSON based on Accept header
     if accepts_html(&headers) {
         let service_views: Vec<ServiceView> = services
             .iter()
-            .map(|s| ServiceView::from_service(&s.service))
+            .map(|s| {
+                let view = ServiceView::from_service(&s.service, &times);
+                if group.is_some() {
+                    view.with_board_station(&s.service)
+                } else {
+                    view
+                }
+            })
             .collect();
 
         let template = ServiceListTemplate {
             services: service_views,
+            messages,
         };
         let html = template.render().map_err(|e| AppError::Internal {
             message: format!("Template error: {}", e),
@@ -166,12 +432,23 @@ async fn search_service(
         Ok(Html(html).into_response())
     } else {
         // JSON response
+        let reference = state.reference.tables().await;
+        let translation = reason_translation(&state, &headers);
         let results: Vec<ServiceResult> = services
             .iter()
-            .map(|s| ServiceResult::from_service(&s.service))
+            .map(|s| {
+                let mut result = ServiceResult::from_service(&s.service, &times);
+                result.add_disruption_reasons(&s.service, &reference, translation);
+                result
+            })
             .collect();
 
-        Ok(Json(SearchServiceResponse { services: results }).into_response())
+        let mut response = SearchServiceResponse {
+            services: results,
+            messages,
+        };
+        name_stations(&state.station_names, response.station_labels()).await;
+        Ok(json_response(&response, &format))
     }
 }
 
@@ -180,48 +457,79 @@ async fn identify_train(
     State(state): State<AppState>,
     headers: HeaderMap,
     Query(req): Query<IdentifyTrainWebRequest>,
+    Query(format): Query<FormatQuery>,
 ) -> Result<Response, AppError> {
     use super::rtt::rtt_search_url_default;
-    use crate::domain::MatchConfidence;
-    use crate::identify::filter_and_rank_matches;
+    use crate::domain::{IdentifyTrainRequest, MatchConfidence};
+    use crate::identify::{ScoringWeights, score_and_rank_matches};
 
     // Parse next station CRS
-    let next_station =
-        Crs::parse_normalized(&req.next_station).map_err(|_| AppError::BadRequest {
-            message: format!("Invalid next station CRS: {}", req.next_station),
-        })?;
+    let next_station = parse_station(&state, &req.next_station, "next station").await?;
 
     // Parse optional terminus CRS
-    let terminus = req
-        .terminus
-        .as_ref()
-        .filter(|t| !t.is_empty())
-        .map(|t| Crs::parse_normalized(t))
-        .transpose()
-        .map_err(|_| AppError::BadRequest {
-            message: format!(
-                "Invalid terminus CRS: {}",
-                req.terminus.as_deref().unwrap_or("")
-            ),
-        })?;
+    let terminus = match req.terminus.as_deref().filter(|t| !t.is_empty()) {
+        Some(t) => Some(parse_station(&state, t, "terminus").await?),
+        None => None,
+    };
 
     // Get current time info
-    let now = Local::now();
-    let date = now.date_naive();
+    let now = state.clock.now();
+    let date = now.date();
     let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
 
+    // Anything else the user can see is scored rather than filtered on
+    // (blank form fields mean not given)
+    let mut request = IdentifyTrainRequest::new(next_station, terminus);
+    if let Some(headcode) = non_blank(&req.headcode) {
+        let headcode =
+            Headcode::parse_normalized(headcode).ok_or_else(|| AppError::BadRequest {
+                message: format!("Invalid headcode: {}", headcode),
+            })?;
+        request = request.with_headcode(headcode);
+    }
+    // Look at the next station's boards around when the train is due
+    // there, which may be well after now if it's a long way off
+    let board_now = BoardWindow::at(date, current_mins, 0, 0).now();
+    let mut window = BoardWindow::new(board_now, 0, 30);
+    if let Some(time) = non_blank(&req.time) {
+        let time = parse_board_time(time, board_now)?;
+        let from = time
+            .checked_sub(chrono::Duration::minutes(IDENTIFY_LEAD_MINS))
+            .unwrap_or(time);
+        window = board_from(board_now, from.max(board_now), 30)?;
+        request = request.with_time(time);
+    }
+    let (offset, span) = (window.time_offset(), window.time_window());
+    if let Some(platform) = non_blank(&req.platform) {
+        request = request.with_platform(platform);
+    }
+    if let Some(calling_at) = non_blank(&req.calling_at) {
+        let mut stations = Vec::new();
+        for station in calling_at.split(',').filter(|s| !s.trim().is_empty()) {
+            stations.push(parse_station(&state, station, "calling point").await?);
+        }
+        request = request.with_calling_at(stations);
+    }
+
     // Query both boards and merge results.
     // - Departures board has subsequent calling points (where train is going)
     // - Arrivals board finds set-down-only trains that don't appear on departures
     // For services appearing on both, prefer departures data (has future stops).
-    let (departures, arrivals) = tokio::join!(
-        state
-            .darwin
-            .get_departures_with_details(&next_station, date, current_mins, 0, 30),
-        state
-            .darwin
-            .get_arrivals_with_details(&next_station, date, current_mins, 0, 30)
-    );
+    let (departures, arrivals) = in_phase(IDENTIFY_PHASE, async {
+        tokio::join!(
+            state.darwin.get_departures_with_details(
+                &next_station,
+                date,
+                current_mins,
+                offset,
+                span
+            ),
+            state
+                .darwin
+                .get_arrivals_with_details(&next_station, date, current_mins, offset, span)
+        )
+    })
+    .await;
 
     let departures = departures.unwrap_or_default();
     let arrivals = arrivals.unwrap_or_default();
@@ -244,7 +552,8 @@ async fn identify_train(
     let mut enhanced_arrivals = Vec::new();
     for svc in arrivals_only {
         let service_id = &svc.service.service_ref.darwin_id;
-        match state.darwin.get_service_details(service_id).await {
+        let details = state.darwin.get_service_details(service_id);
+        match in_phase(IDENTIFY_PHASE, details).await {
             Ok(details) => {
                 match crate::darwin::convert_service_details(
                     &details,
@@ -280,8 +589,10 @@ async fn identify_train(
         .chain(enhanced_arrivals)
         .collect();
 
-    // Filter and rank matches using the extracted logic
-    let matches = filter_and_rank_matches(&services, terminus.as_ref());
+    // Filter, score and rank matches using the extracted logic
+    let matches = score_and_rank_matches(&services, &request, &ScoringWeights::default());
+
+    let times = TimeFormat::for_request(&headers, format.clock);
 
     // Return HTML or JSON based on Accept header
     if accepts_html(&headers) {
@@ -311,7 +622,7 @@ async fn identify_train(
                 // show delay status.
                 let scheduled_arrival = board_call
                     .and_then(|c| c.booked_arrival.or(c.booked_departure))
-                    .map(|t| t.to_string())
+                    .map(|t| times.display(t))
                     .unwrap_or_default();
 
                 let expected_arrival = board_call.and_then(|c| {
@@ -319,7 +630,7 @@ async fn identify_train(
                     let sched = c.booked_arrival.or(c.booked_departure)?;
                     // Only show expected if different from scheduled
                     if exp != sched {
-                        Some(exp.to_string())
+                        Some(times.display(exp))
                     } else {
                         None
                     }
@@ -334,7 +645,7 @@ async fn identify_train(
 
                 let scheduled_terminus_arrival = terminus_call
                     .and_then(|c| c.booked_arrival)
-                    .map(|t| t.to_string())
+                    .map(|t| times.display(t))
                     .unwrap_or_default();
 
                 let expected_terminus_arrival = terminus_call.and_then(|c| {
@@ -342,14 +653,22 @@ async fn identify_train(
                     let sched = c.booked_arrival?;
                     // Only show expected if different from scheduled
                     if exp != sched {
-                        Some(exp.to_string())
+                        Some(times.display(exp))
                     } else {
                         None
                     }
                 });
 
+                let next_calls = m
+                    .context
+                    .next_calls
+                    .iter()
+                    .filter_map(|idx| m.service.service.calls.get(idx.0))
+                    .map(|c| c.station_name.clone())
+                    .collect();
+
                 TrainMatchView {
-                    service: ServiceView::from_service(&m.service.service),
+                    service: ServiceView::from_service(&m.service.service, &times),
                     rtt_url: rtt_search_url_default(&next_station, date, dep_time),
                     is_exact: m.confidence == MatchConfidence::Exact,
                     next_station_name,
@@ -358,13 +677,26 @@ async fn identify_train(
                     terminus_name,
                     scheduled_terminus_arrival,
                     expected_terminus_arrival,
+                    next_calls,
+                    final_destination: m.context.destination.clone(),
+                    score: m.score.score,
+                    reasons: m.score.reasons.iter().map(|r| r.message.clone()).collect(),
                     board_station_idx: m.service.service.board_station_idx.0,
                 }
             })
             .collect();
 
+        let favourites = favourites_for(&state, &headers)
+            .await
+            .into_iter()
+            .map(|f| FavouriteView {
+                name: f.name,
+                crs: f.station.as_str().to_string(),
+            })
+            .collect();
         let template = IdentifyResultsTemplate {
             matches: match_views,
+            favourites,
             next_station: next_station.as_str().to_string(),
             terminus: terminus.map(|t| t.as_str().to_string()),
         };
@@ -374,13 +706,24 @@ async fn identify_train(
 
         Ok(Html(html).into_response())
     } else {
-        // JSON response - reuse ServiceResult format
-        let results: Vec<ServiceResult> = matches
+        // JSON response - ServiceResult format plus where each train goes next
+        let reference = state.reference.tables().await;
+        let translation = reason_translation(&state, &headers);
+        let results: Vec<IdentifyMatchResult> = matches
             .iter()
-            .map(|m| ServiceResult::from_service(&m.service.service))
+            .map(|m| {
+                let mut result = IdentifyMatchResult::from_match(m, &times);
+                let service = &m.service.service;
+                result
+                    .service
+                    .add_disruption_reasons(service, &reference, translation);
+                result
+            })
             .collect();
 
-        Ok(Json(SearchServiceResponse { services: results }).into_response())
+        let mut response = IdentifyTrainResponse { services: results };
+        name_stations(&state.station_names, response.station_labels()).await;
+        Ok(json_response(&response, &format))
     }
 }
 
@@ -388,6 +731,7 @@ async fn identify_train(
 async fn plan_journey(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
     body: Bytes,
 ) -> Result<Response, AppError> {
     // Parse JSON manually so we can log the body on failure
@@ -398,258 +742,1071 @@ async fn plan_journey(
             message: format!("Invalid JSON: {e}"),
         }
     })?;
-    // Parse destination CRS
-    let dest_crs = Crs::parse_normalized(&req.destination).map_err(|_| AppError::BadRequest {
-        message: format!("Invalid destination CRS: {}", req.destination),
-    })?;
+    req.overrides
+        .validate()
+        .map_err(|message| AppError::BadRequest { message })?;
+
+    // Parse destination CRS; a group's name plans to whichever of its
+    // stations is best
+    let (dest_crs, destination_group) = match state.station_groups.find(&req.destination) {
+        Some(group) => (group.lead(), true),
+        None => (
+            parse_station(&state, &req.destination, "destination").await?,
+            false,
+        ),
+    };
 
     // Parse board station CRS
-    let board_station =
-        Crs::parse_normalized(&req.board_station).map_err(|_| AppError::BadRequest {
-            message: format!("Invalid board station CRS: {}", req.board_station),
+    let board_station = parse_station(&state, &req.board_station, "board station").await?;
+
+    let session = Session {
+        id: Session::id_for(&headers, &state.session_key),
+        service_id: req.service_id,
+        board_station,
+        position: req.position,
+        destination: dest_crs,
+        destination_group,
+        identity: None,
+        boarding: req.boarding,
+        planned_departure: None,
+    };
+
+    plan_for_session(&state, &headers, &format, session, &req.overrides, false).await
+}
+
+/// Estimate the API calls planning a journey would make, without making
+/// any.
+///
+/// Takes the same request as [`plan_journey`]. The current train is looked
+/// for only on its board station's cached departures, where identifying it
+/// leaves it; if it isn't there, planning would have to fetch it first.
+async fn estimate_journey(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let req: PlanJourneyRequest =
+        serde_json::from_slice(&body).map_err(|e| AppError::BadRequest {
+            message: format!("Invalid JSON: {e}"),
         })?;
+    req.overrides
+        .validate()
+        .map_err(|message| AppError::BadRequest { message })?;
+
+    let destinations = match state.station_groups.find(&req.destination) {
+        Some(group) => group.members.clone(),
+        None => vec![parse_station(&state, &req.destination, "destination").await?],
+    };
+    let board_station = parse_station(&state, &req.board_station, "board station").await?;
+
+    let now = state.clock.now();
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+    let service = state
+        .darwin
+        .cached_departures(&board_station, date, current_mins, 0, 120)
+        .await
+        .and_then(|board| {
+            board
+                .iter()
+                .find(|s| s.service.service_ref.darwin_id == req.service_id)
+                .map(|s| Arc::new(s.service.clone()))
+        })
+        .ok_or_else(|| AppError::NotFound {
+            message: format!(
+                "Service {} isn't on a cached board; identify the train first",
+                req.service_id
+            ),
+        })?;
+
+    // Configure the search as planning would
+    let settings = state.settings.snapshot();
+    let session_id = Session::id_for(&headers, &state.session_key);
+    let subject = Subject::for_request(&headers, &state.api_keys, &session_id);
+    let config = state.experiments.configure(&settings.config, &subject);
+    let config = req.overrides.apply(&config);
+    let walkable = usable_walks(&state, &settings.walkable, &config);
+    let session = Session {
+        id: session_id.clone(),
+        service_id: req.service_id.clone(),
+        board_station,
+        position: req.position,
+        destination: destinations[0],
+        destination_group: destinations.len() > 1,
+        identity: None,
+        boarding: req.boarding,
+        planned_departure: None,
+    };
+    let recent = state.recent_arrivals.for_session(&session).await;
+    let provider = state.providers.provider(date, current_mins);
+    let planner = Planner::new(&provider, &walkable, &config).with_recent_arrivals(&recent);
+
+    let estimates = destinations
+        .iter()
+        .map(|destination| {
+            let mut request =
+                SearchRequest::new(service.clone(), CallIndex(req.position), *destination);
+            if req.boarding {
+                request = request.with_pre_departure();
+            }
+            planner.estimate(&request)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let estimate = SearchEstimate::merge(&estimates);
+
+    Ok(Json(SearchEstimateResponse::from_estimate(&estimate)).into_response())
+}
+
+/// Re-plan a journey for the train remembered in the session cookie.
+///
+/// Lets a page reload show fresh results without re-identifying the train.
+/// The remembered position is advanced to wherever the train has got to.
+async fn current_journey(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let session =
+        Session::from_headers(&headers, &state.session_key).ok_or_else(|| AppError::NotFound {
+            message: "No train remembered for this session".to_string(),
+        })?;
+
+    plan_for_session(
+        &state,
+        &headers,
+        &format,
+        session,
+        &SearchOverrides::default(),
+        true,
+    )
+    .await
+}
+
+/// Wait for a slot to run a search for the request's API key.
+///
+/// Only issued keys get slots of their own; requests with any other key
+/// queue with those sending none. Held until the search is done; turned
+/// away with 429 if no slot frees up in time.
+async fn search_slot(state: &AppState, headers: &HeaderMap) -> Result<SearchPermit, AppError> {
+    let queue = &state.search_queue;
+    queue
+        .acquire(state.api_keys.authenticate(headers))
+        .await
+        .map_err(|e| AppError::TooManyRequests {
+            message: e.to_string(),
+            retry_after: queue.retry_after(),
+        })
+}
+
+/// Plan a journey for a session and remember it in the session cookie.
+///
+/// `overrides` must already have been validated.
+///
+/// If `advance` is set, the session's position is first moved along the
+/// service to account for time that has passed since it was recorded.
+pub(super) async fn plan_for_session(
+    state: &AppState,
+    headers: &HeaderMap,
+    format: &FormatQuery,
+    mut session: Session,
+    overrides: &SearchOverrides,
+    advance: bool,
+) -> Result<Response, AppError> {
+    let _permit = search_slot(state, headers).await?;
 
     // Get current time info
-    let now = Local::now();
-    let date = now.date_naive();
+    let now = state.clock.now();
+    let date = now.date();
     let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
 
-    // Find the service from the board station's departure board
-    let service = find_service_by_id(&state, &req.service_id, &board_station, date, current_mins)
+    // Find the service from the board station's departure board, or by its
+    // identity elsewhere if its ID has expired since it was remembered
+    let resolver = ServiceResolver::new(&state.darwin, date, current_mins);
+    let resolve = resolver.resolve(
+        &session.service_id,
+        &session.board_station,
+        session.identity.as_ref(),
+    );
+    let service = in_phase(RESOLVE_PHASE, resolve)
         .await
         .ok_or_else(|| AppError::NotFound {
-            message: format!("Service {} not found or expired", req.service_id),
+            message: format!("Service {} not found or expired", session.service_id),
         })?;
+    session.service_id = service.service_ref.darwin_id.clone();
+    session.board_station = service.service_ref.board_crs;
+    session.identity = ServiceIdentity::of(&service);
+
+    // The user is only waiting to board until the train leaves
+    let now = RailTime::new(date, now.time());
+    session.boarding = session.boarding
+        && service
+            .calls
+            .get(session.position)
+            .and_then(|c| c.expected_departure())
+            .is_some_and(|departs| departs > now);
+    if !session.boarding {
+        session.planned_departure = None;
+    }
 
-    // Create the search request
-    let search_request = SearchRequest::new(service.clone(), CallIndex(req.position), dest_crs);
+    if advance {
+        session.position = advance_position(&service, CallIndex(session.position), now).0;
+    }
 
-    // Create a service provider that uses the cached Darwin client
-    let provider = CachedServiceProvider {
-        darwin: state.darwin.clone(),
-        date,
-        current_mins,
+    // Search to each station of a group destination
+    let destinations = match state.station_groups.led_by(&session.destination) {
+        Some(group) if session.destination_group => group.members.clone(),
+        _ => vec![session.destination],
     };
 
-    // Run the planner
-    let planner = Planner::new(&provider, &state.walkable, &state.config);
-    let result = planner
-        .search(&search_request)
+    let provider = state.providers.provider(date, current_mins);
+
+    // The planner runs in this handler's future rather than a task of its
+    // own, so if the client disconnects, axum drops it mid-search and no
+    // further Darwin calls are made
+
+    // Snapshot the settings so reloads and admin edits don't change them
+    // under a running search
+    let settings = state.settings.snapshot();
+
+    // Apply any experiments running for this client
+    if session.id.is_empty() {
+        session.id = random_id();
+    }
+    let subject = Subject::for_request(headers, &state.api_keys, &session.id);
+    let config = state.experiments.configure(&settings.config, &subject);
+    let config = overrides.apply(&config);
+
+    // Run the planner, reusing arrivals boards from the session's last search
+    let recent = state.recent_arrivals.for_session(&session).await;
+    let station_locations = state.station_names.all_locations().await;
+    let walkable = usable_walks(state, &settings.walkable, &config);
+    let planner = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&state.travel_times)
+        .with_performance(&state.performance)
+        .with_platforms(&state.platforms)
+        .with_recent_arrivals(&recent)
+        .with_locations(&station_locations);
+    let planner = &planner;
+    let started = Instant::now();
+    let searches = destinations.iter().map(|destination| {
+        let mut request =
+            SearchRequest::new(service.clone(), CallIndex(session.position), *destination);
+        if session.boarding {
+            request = request.with_pre_departure();
+        }
+        async move { planner.search(&request).await }
+    });
+    let mut results = futures::future::try_join_all(searches)
         .await
         .map_err(AppError::from)?;
+    let result = if results.len() == 1 {
+        results.remove(0)
+    } else {
+        SearchResult::merge(results, &config)
+    };
+    record_search(
+        state,
+        headers,
+        &service,
+        &session,
+        &result,
+        started.elapsed(),
+    );
+
+    // A later departure than last planned may have broken connections; the
+    // fresh plan replaces the old one and tells the client so
+    let departure_slipped = session
+        .planned_departure
+        .is_some_and(|planned| result.departure_slipped(planned));
+    session.planned_departure = result.departure;
+    let departs_in_mins = result
+        .departure
+        .map(|departs| departs.signed_duration_since(now).num_minutes());
+
+    let set_cookie = session.set_cookie(&state.session_key);
+
+    // Station locations for walk navigation links
+    let locations = state
+        .station_names
+        .locations(result.journeys.iter().flat_map(walk_stations))
+        .await;
+    let hours = state
+        .station_names
+        .opening_hours(result.journeys.iter().flat_map(walk_stations))
+        .await;
+    let messages = change_station_messages(state, &result.journeys);
+    let data_quality = data_quality_result(state, &result.data_quality).await;
+    let reasons = rank_reasons(&result.journeys);
+    let reference = state.reference.tables().await;
+    let translation = reason_translation(state, headers);
+    let outages = state.lift_outages.current();
 
     // Return HTML or JSON based on Accept header
-    if accepts_html(&headers) {
+    let times = TimeFormat::for_request(headers, format.clock);
+    let response = if accepts_html(headers) {
         let journey_views: Vec<JourneyView> = result
             .journeys
             .iter()
-            .map(JourneyView::from_journey)
+            .zip(&reasons)
+            .map(|(j, reasons)| {
+                let mut view = JourneyView::from_journey(j, &times);
+                view.add_rank_reasons(reasons);
+                view.add_walk_navigation(j, &locations);
+                view.add_alighting_hints(j, &state.station_metadata);
+                view.add_platform_hints(j, &state.platforms);
+                view.add_closure_warnings(j, &hours);
+                view.add_access_warnings(j, &walkable, &outages);
+                view.add_station_messages(j, &messages);
+                view
+            })
             .collect();
 
         let template = JourneyResultsTemplate {
             journeys: journey_views,
+            departs_in_mins,
+            departure_slipped,
+            near_misses: result
+                .near_misses
+                .iter()
+                .map(|n| NearMissView::from_near_miss(n, &times))
+                .collect(),
+            first_portion: result
+                .continuation
+                .as_ref()
+                .map(|c| FirstPortionView::from_continuation(c, &times)),
+            data_quality: data_quality.map(|q| q.message),
         };
         let html = template.render().map_err(|e| AppError::Internal {
             message: format!("Template error: {}", e),
         })?;
 
-        Ok(Html(html).into_response())
+        Html(html).into_response()
     } else {
-        // JSON response
-        let journeys: Vec<JourneyResult> = result
-            .journeys
-            .iter()
-            .map(JourneyResult::from_journey)
-            .collect();
+        // JSON response; journeys are stored so clients can request exports
+        let mut journeys: Vec<JourneyResult> = Vec::with_capacity(result.journeys.len());
+        for (j, reasons) in result.journeys.iter().zip(&reasons) {
+            let mut dto = JourneyResult::from_journey(j, &times);
+            dto.add_rank_reasons(reasons);
+            dto.add_disruption_reasons(j, &reference, translation);
+            dto.add_walk_navigation(j, &locations);
+            dto.add_alighting_hints(j, &state.station_metadata);
+            dto.add_platform_hints(j, &state.platforms);
+            dto.add_closure_warnings(j, &hours);
+            dto.add_access_warnings(j, &walkable, &outages);
+            dto.add_station_messages(j, &messages);
+            dto.add_confidence(j, &config, Some(&state.performance));
+            dto.add_price_band(j, &config);
+            dto.add_duration_from(j, now);
+            dto.id = Some(state.journeys.insert(j.clone()).await);
+            journeys.push(dto);
+        }
 
-        Ok(Json(PlanJourneyResponse {
+        // A first portion is stored with its hint, so watching it plans the
+        // rest when it comes into view
+        let first_portion = match &result.continuation {
+            Some(continuation) => {
+                let j = &continuation.journey;
+                let mut dto = JourneyResult::from_journey(j, &times);
+                dto.add_disruption_reasons(j, &reference, translation);
+                dto.add_alighting_hints(j, &state.station_metadata);
+                dto.add_platform_hints(j, &state.platforms);
+                dto.add_confidence(j, &config, Some(&state.performance));
+                dto.add_price_band(j, &config);
+                dto.add_duration_from(j, now);
+                dto.add_continuation_hint(j, &continuation.hint, &times);
+                let id = state.journeys.insert(j.clone()).await;
+                state
+                    .journeys
+                    .set_continuation(&id, Some(continuation.hint.clone()))
+                    .await;
+                dto.id = Some(id);
+                Some(dto)
+            }
+            None => None,
+        };
+
+        let mut response = PlanJourneyResponse {
             journeys,
             routes_explored: result.routes_explored,
-        })
-        .into_response())
-    }
+            departs_in_mins,
+            departure_slipped,
+            near_misses: result
+                .near_misses
+                .iter()
+                .map(|n| NearMissResult::from_near_miss(n, &times))
+                .collect(),
+            first_portion,
+            data_quality,
+        };
+        name_stations(&state.station_names, response.station_labels()).await;
+        json_response(&response, format)
+    };
+
+    Ok(([(header::SET_COOKIE, set_cookie)], response).into_response())
 }
 
-/// Find a service by its Darwin ID.
-///
-/// Searches the board_station first (where the service was originally found),
-/// then falls back to common stations if not found.
-async fn find_service_by_id(
+/// Count a search in the usage analytics, unless they're off or the
+/// request opted out.
+fn record_search(
     state: &AppState,
-    service_id: &str,
-    board_station: &Crs,
-    date: NaiveDate,
-    current_mins: u16,
-) -> Option<Arc<Service>> {
-    // Search the board station first - this is where the service was found
-    if let Ok(services) = state
-        .darwin
-        .get_departures_with_details(board_station, date, current_mins, 0, 120)
+    headers: &HeaderMap,
+    service: &Service,
+    session: &Session,
+    result: &SearchResult,
+    latency: Duration,
+) {
+    let (Some(analytics), Some(origin)) = (&state.analytics, service.calls.get(session.position))
+    else {
+        return;
+    };
+    if opted_out(headers) {
+        return;
+    }
+    let event = SearchEvent {
+        origin: origin.station,
+        destination: session.destination,
+        changes: result.journeys.first().map(Journey::change_count),
+        latency,
+    };
+    analytics.record(state.clock.now().date(), &event);
+}
+
+/// List the trains leaving a station soon, each with the best journey it
+/// starts to the destination (if any).
+async fn reachable_departures(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(req): Query<BoardQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let station = parse_station(&state, &req.station, "station").await?;
+    let destination = parse_station(&state, &req.destination, "destination").await?;
+
+    let window_mins = req.window_mins.unwrap_or(DEFAULT_BOARD_WINDOW_MINS);
+    if !(1..=MAX_BOARD_WINDOW_MINS).contains(&window_mins) {
+        return Err(AppError::BadRequest {
+            message: format!(
+                "window_mins must be between 1 and {MAX_BOARD_WINDOW_MINS}, got {window_mins}"
+            ),
+        });
+    }
+    let overrides = SearchOverrides {
+        max_changes: req.max_changes,
+        ..SearchOverrides::default()
+    };
+    overrides
+        .validate()
+        .map_err(|message| AppError::BadRequest { message })?;
+    let _permit = search_slot(&state, &headers).await?;
+
+    let now = state.clock.now();
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+    let from = RailTime::new(date, now.time());
+    let until = from + chrono::Duration::minutes(window_mins);
+
+    // As for plans, a client disconnecting drops the search with this future
+    let provider = state.providers.provider(date, current_mins);
+    let settings = state.settings.snapshot();
+
+    // Without a session, experiments bucket by the board station
+    let session_id = Session::from_headers(&headers, &state.session_key)
+        .map(|session| session.id)
+        .filter(|id| !id.is_empty());
+    let subject = Subject::for_request(
+        &headers,
+        &state.api_keys,
+        session_id.as_deref().unwrap_or(station.as_str()),
+    );
+    let config = state.experiments.configure(&settings.config, &subject);
+    let config = overrides.apply(&config);
+
+    let walkable = usable_walks(&state, &settings.walkable, &config);
+    let planner = Planner::new(&provider, &walkable, &config)
+        .with_travel_times(&state.travel_times)
+        .with_performance(&state.performance)
+        .with_platforms(&state.platforms);
+    let result = planner
+        .search_board(&BoardRequest::new(station, destination, from, until))
         .await
-    {
-        for s in services.iter() {
-            if s.service.service_ref.darwin_id == service_id {
-                return Some(Arc::new(s.service.clone()));
+        .map_err(AppError::from)?;
+
+    // Journeys are stored so clients can request exports
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let journeys: Vec<Journey> = result
+        .departures
+        .iter()
+        .filter_map(|d| d.journey.clone())
+        .collect();
+    let messages = change_station_messages(&state, &journeys);
+    let reference = state.reference.tables().await;
+    let translation = reason_translation(&state, &headers);
+    let mut departures = Vec::with_capacity(result.departures.len());
+    for departure in &result.departures {
+        let journey = match &departure.journey {
+            Some(j) => {
+                let mut dto = JourneyResult::from_journey(j, &times);
+                dto.add_disruption_reasons(j, &reference, translation);
+                dto.add_alighting_hints(j, &state.station_metadata);
+                dto.add_platform_hints(j, &state.platforms);
+                dto.add_station_messages(j, &messages);
+                dto.add_confidence(j, &config, Some(&state.performance));
+                dto.add_price_band(j, &config);
+                dto.add_duration_from(j, from);
+                dto.id = Some(state.journeys.insert(j.clone()).await);
+                Some(dto)
             }
-        }
+            None => None,
+        };
+        let mut service = ServiceResult::from_service(&departure.service, &times);
+        service.add_disruption_reasons(&departure.service, &reference, translation);
+        departures.push(BoardDepartureResult {
+            service,
+            position: departure.position.0,
+            reaches_destination: departure.reaches_destination(),
+            unavailable: departure.unavailable,
+            rank: departure.rank,
+            journey,
+        });
     }
 
-    // Fallback: try common stations (in case board_station cache expired)
-    let common_stations = ["PAD", "EUS", "KGX", "VIC", "WAT", "LIV", "BHM", "MAN"];
+    let mut response = BoardResponse {
+        departures,
+        routes_explored: result.routes_explored,
+        messages: board_messages(&state, &[station]),
+        data_quality: data_quality_result(&state, &result.data_quality).await,
+    };
+    name_stations(&state.station_names, response.station_labels()).await;
+    Ok(json_response(&response, &format))
+}
 
-    for station in &common_stations {
-        let Ok(crs) = Crs::parse(station) else {
-            continue;
-        };
-        if &crs == board_station {
-            continue; // Already searched
+/// The translation to give delay and cancellation reasons in, from the
+/// request's `Accept-Language`; `None` for Darwin's own en-GB text.
+pub(super) fn reason_translation<'a>(
+    state: &'a AppState,
+    headers: &HeaderMap,
+) -> Option<&'a ReasonTranslation> {
+    let accepted = headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+    state.reference.translations().negotiate(accepted)
+}
+
+/// Report the stations a search had to do without, by name, if any.
+async fn data_quality_result(state: &AppState, quality: &DataQuality) -> Option<DataQualityResult> {
+    let mut names = HashMap::new();
+    for station in quality
+        .unavailable_stations
+        .iter()
+        .chain(&quality.truncated_stations)
+    {
+        if let Some(name) = state.station_names.get(station).await {
+            names.insert(*station, name);
         }
-        let Ok(services) = state
-            .darwin
-            .get_departures_with_details(&crs, date, current_mins, 0, 120)
-            .await
-        else {
-            continue;
-        };
-        for s in services.iter() {
-            if s.service.service_ref.darwin_id == service_id {
-                return Some(Arc::new(s.service.clone()));
+    }
+    DataQualityResult::from_quality(quality, &names)
+}
+
+/// The messages on the boards of `stations`, without repeats.
+fn board_messages(state: &AppState, stations: &[Crs]) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for station in stations {
+        for message in state.darwin.station_messages(station) {
+            if !messages.contains(&message) {
+                messages.push(message);
             }
         }
     }
+    messages
+}
 
-    None
+/// The messages on the boards of the stations `journeys` change at.
+fn change_station_messages(state: &AppState, journeys: &[Journey]) -> HashMap<Crs, Vec<String>> {
+    journeys
+        .iter()
+        .flat_map(change_stations)
+        .map(|station| (station, state.darwin.station_messages(&station)))
+        .filter(|(_, messages)| !messages.is_empty())
+        .collect()
 }
 
-/// Service provider that uses the cached Darwin client.
-struct CachedServiceProvider {
-    darwin: Arc<crate::cache::CachedDarwinClient>,
-    date: NaiveDate,
-    current_mins: u16,
+/// Export a previously planned journey as GeoJSON.
+async fn journey_geojson(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let journey = state
+        .journeys
+        .get(&id)
+        .await
+        .ok_or_else(|| AppError::NotFound {
+            message: format!("Journey {} not found or expired", id),
+        })?;
+
+    let locations = state
+        .station_names
+        .locations(journey_stations(&journey))
+        .await;
+    let geojson = journey_to_geojson(&journey, &locations);
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/geo+json")],
+        Json(geojson),
+    )
+        .into_response())
+}
+
+/// Describe a previously planned journey in plain text.
+///
+/// For screen readers and SMS; one step per line.
+async fn journey_text(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let journey = state
+        .journeys
+        .get(&id)
+        .await
+        .ok_or_else(|| AppError::NotFound {
+            message: format!("Journey {} not found or expired", id),
+        })?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        narrate(&journey, &state.station_metadata),
+    )
+        .into_response())
+}
+
+/// A previously planned journey as a printable PDF itinerary.
+///
+/// For travellers expecting to lose signal on the way. The QR code links
+/// back to the journey's text version, which follows any re-plans; it's
+/// left off unless the server's public URL is configured.
+async fn journey_pdf(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let journey = state
+        .journeys
+        .get(&id)
+        .await
+        .ok_or_else(|| AppError::NotFound {
+            message: format!("Journey {} not found or expired", id),
+        })?;
+
+    // Only the configured address: the request's own Host header is
+    // whatever the client chose to send
+    let link = state
+        .public_url
+        .as_deref()
+        .map(|base| format!("{base}{API_V1}/journeys/{id}/text"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"journey-{id}.pdf\""),
+            ),
+        ],
+        journey_to_pdf(&journey, link.as_deref(), state.clock.now()),
+    )
+        .into_response())
+}
+
+/// How long a long-poll for journey changes waits before answering with
+/// no changes. Kept under common proxy idle timeouts.
+const LONG_POLL_WAIT: Duration = Duration::from_secs(25);
+
+/// How often a watched journey's trains are polled. Boards are cached for
+/// about this long, so polling more often would see the same data.
+pub(super) const JOURNEY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Long-poll for changes to a previously planned journey.
+///
+/// Answers as soon as the journey has a version newer than `since`, or
+/// after [`LONG_POLL_WAIT`] with no changes. New versions come from the
+/// route being re-planned with different real-time data, or from polling
+/// the journey's own trains, at most every [`JOURNEY_REFRESH_INTERVAL`]
+/// while someone is waiting. For clients whose network blocks streaming
+/// responses.
+async fn journey_changes(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<JourneyChangesQuery>,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    if state
+        .journeys
+        .claim_refresh(&id, JOURNEY_REFRESH_INTERVAL)
+        .await
+    {
+        refresh_journey(&state, &id).await;
+    }
+
+    let changes = state
+        .journeys
+        .changes_since(&id, query.since, LONG_POLL_WAIT)
+        .await
+        .ok_or_else(|| AppError::NotFound {
+            message: format!("Journey {} not found or expired", id),
+        })?;
+
+    Ok(json_response(&changes, &format))
 }
 
-impl crate::planner::ServiceProvider for CachedServiceProvider {
-    async fn get_departures(
-        &self,
-        station: &Crs,
-        after: crate::domain::RailTime,
-    ) -> Result<Vec<Arc<Service>>, SearchError> {
-        // Calculate time_offset based on 'after' time so Darwin returns relevant departures.
-        // Without this, we fetch from "now" and may miss trains departing after 'after'.
-        //
-        // Darwin constraints:
-        // - time_offset must be in range [-120, 120]
-        // - time_offset + time_window must not exceed ~120 (Darwin rejects larger ranges)
-        let current_time =
-            chrono::NaiveTime::from_num_seconds_from_midnight_opt(self.current_mins as u32 * 60, 0)
-                .unwrap_or_default();
-        let now = crate::domain::RailTime::new(self.date, current_time);
-        let offset_mins = after.signed_duration_since(now).num_minutes();
-
-        // Clamp offset to Darwin's valid range, and adjust window so total doesn't exceed 120
-        let time_offset = offset_mins.clamp(-120, 120) as i16;
-        let time_window = (120 - time_offset.max(0)) as u16;
-
-        // If the requested time is too far in the future, we can't query Darwin for it
-        if time_window == 0 {
-            return Ok(Vec::new());
+/// Report that the user has boarded one of a journey's trains.
+///
+/// The rest of the journey is planned again from that train, so only the
+/// changes still ahead are considered. It's stored under a new ID that
+/// replaces the old one, the caller's watches of the old journey follow
+/// it, and the session cookie moves onto the boarded train. If nothing can
+/// be planned, the rest of the old plan is kept.
+async fn boarded_leg(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(format): Query<FormatQuery>,
+    Json(req): Json<BoardedLegRequest>,
+) -> Result<Response, AppError> {
+    let journey = state
+        .journeys
+        .get(&id)
+        .await
+        .ok_or_else(|| AppError::NotFound {
+            message: format!("Journey {} not found or expired", id),
+        })?;
+    let (Some(leg), Some(planned)) = (journey.legs().nth(req.leg), journey.from_leg(req.leg))
+    else {
+        return Err(AppError::BadRequest {
+            message: format!(
+                "Journey {} has {} train legs; there is no leg {}",
+                id,
+                journey.leg_count(),
+                req.leg
+            ),
+        });
+    };
+
+    let _permit = search_slot(&state, &headers).await?;
+    let now = state.clock.now();
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+
+    let replanned = replan_from(&state, leg, *journey.destination(), date, current_mins).await;
+    let kept_plan = replanned.is_none();
+    let rest = replanned.unwrap_or(planned);
+    let rest_id = state.journeys.insert(rest.clone()).await;
+    // The rest of a first portion is still only a first portion
+    if kept_plan && let Some(hint) = state.journeys.continuation(&id).await {
+        state.journeys.set_continuation(&rest_id, Some(hint)).await;
+    }
+    state.journeys.replace(&id, rest_id.clone()).await;
+
+    let watches_moved = match owner(&headers) {
+        Some(owner) => state.watches.follow_journey(&owner, &id, &rest_id).await,
+        None => 0,
+    };
+
+    let service = leg.service();
+    let session = Session {
+        id: Session::id_for(&headers, &state.session_key),
+        service_id: service.service_ref.darwin_id.clone(),
+        board_station: service.service_ref.board_crs,
+        position: leg.board_idx().0,
+        destination: *rest.destination(),
+        destination_group: false,
+        identity: ServiceIdentity::of(service),
+        boarding: false,
+        planned_departure: None,
+    };
+
+    let config = state.settings.snapshot().config.clone();
+    let reference = state.reference.tables().await;
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let mut dto = JourneyResult::from_journey(&rest, &times);
+    dto.add_disruption_reasons(&rest, &reference, reason_translation(&state, &headers));
+    dto.add_alighting_hints(&rest, &state.station_metadata);
+    dto.add_platform_hints(&rest, &state.platforms);
+    dto.add_confidence(&rest, &config, Some(&state.performance));
+    dto.add_price_band(&rest, &config);
+    dto.add_duration_from(&rest, RailTime::new(date, now.time()));
+    dto.id = Some(rest_id);
+    name_stations(&state.station_names, dto.station_labels()).await;
+
+    Ok((
+        [(header::SET_COOKIE, session.set_cookie(&state.session_key))],
+        json_response(
+            &BoardedLegResponse {
+                journey: dto,
+                watches_moved,
+            },
+            &format,
+        ),
+    )
+        .into_response())
+}
+
+/// Bring a stored journey up to date by polling only its trains.
+///
+/// If a change can no longer be made, the trip is planned again from the
+/// first train and the stored journey points to the best new plan.
+pub(super) async fn refresh_journey(state: &AppState, id: &str) {
+    let Some(journey) = state.journeys.get(id).await else {
+        return;
+    };
+    let now = state.clock.now();
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+
+    let refresher = JourneyRefresher::new(
+        &state.darwin,
+        date,
+        current_mins,
+        state.settings.snapshot().config.min_connection(),
+    );
+    match refresher.refresh(&journey).await {
+        Refresh::Updated(refreshed) => {
+            alert_watchers(state, id, &diff(&journey, &refreshed));
+            archive_if_arrived(state, id, &refreshed).await;
+            state.journeys.update(id, refreshed).await;
+        }
+        Refresh::Infeasible(refreshed) => {
+            alert_watchers(state, id, &diff(&journey, &refreshed));
+            let replacement = replan(state, &refreshed, date, current_mins).await;
+            state.journeys.update(id, refreshed).await;
+            if let Some(replacement) = replacement {
+                let replacement = state.journeys.insert(replacement).await;
+                state.journeys.replace(id, replacement).await;
+            }
         }
+        Refresh::Lost => {}
+    }
+}
 
-        let services = self
-            .darwin
-            .get_departures_with_details(
-                station,
-                self.date,
-                self.current_mins,
-                time_offset,
-                time_window,
-            )
-            .await
-            .map_err(|e| SearchError::FetchError {
-                station: *station,
-                message: e.to_string(),
-            })?;
+/// Plan the rest of a journey that is only a first portion, once its
+/// continuation hint says departures from where it ends are in view.
+///
+/// The whole journey is stored as a new journey replacing the first
+/// portion, and its ID returned. If the rest can't be planned in one go
+/// either, the new journey is a longer first portion, continued in turn.
+/// Returns `None` (to try again later) if it isn't time yet or nothing
+/// gets further.
+pub(super) async fn continue_journey(state: &AppState, id: &str) -> Option<String> {
+    let hint = state.journeys.continuation(id).await?;
+    let now = state.clock.now();
+    if !hint.is_open(RailTime::new(now.date(), now.time())) {
+        return None;
+    }
+    let first = Continuation {
+        journey: state.journeys.get(id).await?.as_ref().clone(),
+        hint,
+    };
 
-        // Filter to departures after the specified time
-        // (still needed because Darwin might return trains slightly before 'after')
-        let filtered: Vec<Arc<Service>> = services
-            .iter()
-            .filter(|s| {
-                s.candidate
-                    .expected_departure
-                    .or(Some(s.candidate.scheduled_departure))
-                    .is_some_and(|t| t >= after)
-            })
-            .map(|s| Arc::new(s.service.clone()))
-            .collect();
+    // Runs in the background, so it gives way (to try again later) when
+    // no search slot frees up
+    let _permit = state.search_queue.acquire(None).await.ok()?;
+
+    let date = now.date();
+    let current_mins = (now.time().hour() * 60 + now.time().minute()) as u16;
+    let provider = state.providers.provider(date, current_mins);
+    let settings = state.settings.snapshot();
+    let locations = state.station_names.all_locations().await;
+    let walkable = usable_walks(state, &settings.walkable, &settings.config);
+    let planner = Planner::new(&provider, &walkable, &settings.config)
+        .with_travel_times(&state.travel_times)
+        .with_performance(&state.performance)
+        .with_platforms(&state.platforms)
+        .with_locations(&locations);
+    let result = match planner
+        .search_board(&first.hint.board_request(settings.config.time_window()))
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Warning: failed to continue journey: {}", e);
+            return None;
+        }
+    };
+
+    let best = result
+        .departures
+        .iter()
+        .filter(|d| d.rank.is_some())
+        .min_by_key(|d| d.rank)
+        .and_then(|d| d.journey.as_ref());
+    let (whole, next) = match (best, result.continuation) {
+        (Some(rest), _) => (first.join(rest)?, None),
+        (None, Some(further)) => (first.join(&further.journey)?, Some(further.hint)),
+        (None, None) => return None,
+    };
+
+    let whole = state.journeys.insert(whole).await;
+    state.journeys.set_continuation(&whole, next).await;
+    state.journeys.set_continuation(id, None).await;
+    state.journeys.replace(id, whole.clone()).await;
+    Some(whole)
+}
 
-        Ok(filtered)
-    }
-
-    async fn get_arrivals(
-        &self,
-        station: &Crs,
-        after: crate::domain::RailTime,
-    ) -> Result<Vec<Arc<Service>>, SearchError> {
-        // Calculate time_offset based on 'after' time so Darwin returns relevant arrivals.
-        // For arrivals-first search, we want trains arriving at the destination after
-        // the user could possibly reach them.
-        //
-        // Darwin constraints:
-        // - time_offset must be in range [-120, 120]
-        // - time_offset + time_window must not exceed ~120
-        let current_time =
-            chrono::NaiveTime::from_num_seconds_from_midnight_opt(self.current_mins as u32 * 60, 0)
-                .unwrap_or_default();
-        let now = crate::domain::RailTime::new(self.date, current_time);
-        let offset_mins = after.signed_duration_since(now).num_minutes();
-
-        // Clamp offset to Darwin's valid range, and adjust window so total doesn't exceed 120
-        let time_offset = offset_mins.clamp(-120, 120) as i16;
-        let time_window = (120 - time_offset.max(0)) as u16;
-
-        // If the requested time is too far in the future, we can't query Darwin for it
-        if time_window == 0 {
-            return Ok(Vec::new());
+/// The walks a search with `config` may use: all of them, unless it avoids
+/// lifts that are out of service and some are.
+fn usable_walks(
+    state: &AppState,
+    walkable: &Arc<WalkableConnections>,
+    config: &SearchConfig,
+) -> Arc<WalkableConnections> {
+    let outages = state.lift_outages.current();
+    if config.avoid_lift_outages && !outages.is_empty() {
+        Arc::new(walkable.without_lift_walks_at(&outages))
+    } else {
+        walkable.clone()
+    }
+}
+
+/// The best journey to the same destination from a journey's first train,
+/// found by a full search.
+async fn replan(
+    state: &AppState,
+    journey: &Journey,
+    date: NaiveDate,
+    current_mins: u16,
+) -> Option<Journey> {
+    let first = journey.legs().next()?;
+    replan_from(state, first, *journey.destination(), date, current_mins).await
+}
+
+/// The best journey to `destination` staying on a leg's train from where
+/// the leg boards it, found by a full search.
+async fn replan_from(
+    state: &AppState,
+    leg: &Leg,
+    destination: Crs,
+    date: NaiveDate,
+    current_mins: u16,
+) -> Option<Journey> {
+    let provider = state.providers.provider(date, current_mins);
+    let settings = state.settings.snapshot();
+    let walkable = usable_walks(state, &settings.walkable, &settings.config);
+    let planner = Planner::new(&provider, &walkable, &settings.config)
+        .with_travel_times(&state.travel_times)
+        .with_performance(&state.performance)
+        .with_platforms(&state.platforms);
+
+    let mut request = SearchRequest::new(leg.service().clone(), leg.board_idx(), destination);
+    let now = RailTime::new(date, state.clock.now().time());
+    if leg.departure_time() > now {
+        request = request.with_pre_departure();
+    }
+    match planner.search(&request).await {
+        Ok(result) => result.journeys.into_iter().next(),
+        Err(e) => {
+            eprintln!("Warning: failed to re-plan journey: {}", e);
+            None
         }
+    }
+}
 
-        let services = self
-            .darwin
-            .get_arrivals_with_details(
-                station,
-                self.date,
-                self.current_mins,
-                time_offset,
-                time_window,
-            )
-            .await
-            .map_err(|e| SearchError::FetchError {
-                station: *station,
-                message: e.to_string(),
-            })?;
+/// Show the train remembered in the session cookie.
+async fn get_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Result<Response, AppError> {
+    let session =
+        Session::from_headers(&headers, &state.session_key).ok_or_else(|| AppError::NotFound {
+            message: "No train remembered for this session".to_string(),
+        })?;
 
-        // Convert to Arc<Service> - arrivals include previousCallingPoints
-        // which is what we need for the arrivals-first algorithm
-        let result: Vec<Arc<Service>> = services
-            .iter()
-            .map(|s| Arc::new(s.service.clone()))
-            .collect();
+    Ok(json_response(
+        &SessionResponse::from_session(&session),
+        &format,
+    ))
+}
 
-        Ok(result)
+/// Forget the remembered train ("I changed trains").
+///
+/// Browsers are redirected to the search page; API clients get 204.
+async fn reset_session(headers: HeaderMap) -> Response {
+    let clear = [(header::SET_COOKIE, Session::clear_cookie())];
+    if accepts_html(&headers) {
+        (clear, Redirect::to("/")).into_response()
+    } else {
+        (clear, StatusCode::NO_CONTENT).into_response()
     }
 }
 
 /// Application error type.
 #[derive(Debug)]
 pub enum AppError {
-    BadRequest { message: String },
-    NotFound { message: String },
-    Internal { message: String },
+    BadRequest {
+        message: String,
+    },
+    NotFound {
+        message: String,
+    },
+    /// Missing or wrong credentials for an authenticated endpoint
+    Unauthorized {
+        message: String,
+    },
+    Internal {
+        message: String,
+    },
+    /// Bad station code, with suggested alternatives
+    UnknownStation {
+        message: String,
+        suggestions: Vec<StationSearchResult>,
+    },
+    /// Too many searches running; try again after `retry_after`
+    TooManyRequests {
+        message: String,
+        retry_after: Duration,
+    },
+    /// Failure from a lower layer, classified by [`ErrorKind`]
+    Classified {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+impl AppError {
+    /// Build an error from anything with an [`ErrorKind`].
+    fn classified<E: Classify + std::fmt::Display>(e: &E) -> Self {
+        AppError::Classified {
+            kind: e.kind(),
+            message: e.to_string(),
+        }
+    }
 }
 
 impl From<crate::darwin::DarwinError> for AppError {
     fn from(e: crate::darwin::DarwinError) -> Self {
-        AppError::Internal {
+        AppError::classified(&e)
+    }
+}
+
+impl From<crate::stations::StationError> for AppError {
+    fn from(e: crate::stations::StationError) -> Self {
+        AppError::classified(&e)
+    }
+}
+
+impl From<crate::walkable::WalkableError> for AppError {
+    fn from(e: crate::walkable::WalkableError) -> Self {
+        AppError::BadRequest {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<super::watch_store::WatchError> for AppError {
+    fn from(e: super::watch_store::WatchError) -> Self {
+        AppError::BadRequest {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<super::favourite_store::FavouriteError> for AppError {
+    fn from(e: super::favourite_store::FavouriteError) -> Self {
+        AppError::BadRequest {
             message: e.to_string(),
         }
     }
@@ -659,25 +1816,237 @@ impl From<SearchError> for AppError {
     fn from(e: SearchError) -> Self {
         match e {
             SearchError::InvalidRequest(msg) => AppError::BadRequest { message: msg },
-            _ => AppError::Internal {
-                message: e.to_string(),
-            },
+            _ => AppError::classified(&e),
         }
     }
 }
 
+/// Non-standard status for a request the client gave up on.
+const CLIENT_CLOSED_REQUEST: StatusCode = match StatusCode::from_u16(499) {
+    Ok(status) => status,
+    Err(_) => panic!("499 is a valid status code"),
+};
+
+/// HTTP status for a classified failure.
+///
+/// Upstream problems the user can't fix map to 5xx: 503 when worth trying
+/// again shortly, 502 when Darwin sent something unusable, 500 when the
+/// server itself is misconfigured. A search the client abandoned is not an
+/// upstream failure at all and gets nginx's 499, which nobody will read.
+fn status_for_kind(kind: ErrorKind) -> StatusCode {
+    match kind {
+        ErrorKind::Transient | ErrorKind::RateLimited => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::Permanent => StatusCode::BAD_GATEWAY,
+        ErrorKind::Config => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::Cancelled => CLIENT_CLOSED_REQUEST,
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        let (status, message) = match &self {
-            AppError::BadRequest { message } => (StatusCode::BAD_REQUEST, message.clone()),
-            AppError::NotFound { message } => (StatusCode::NOT_FOUND, message.clone()),
-            AppError::Internal { message } => (StatusCode::INTERNAL_SERVER_ERROR, message.clone()),
+        let mut retry_after = None;
+        let (status, message, suggestions) = match self {
+            AppError::BadRequest { message } => (StatusCode::BAD_REQUEST, message, Vec::new()),
+            AppError::NotFound { message } => (StatusCode::NOT_FOUND, message, Vec::new()),
+            AppError::Unauthorized { message } => (StatusCode::UNAUTHORIZED, message, Vec::new()),
+            AppError::Internal { message } => {
+                (StatusCode::INTERNAL_SERVER_ERROR, message, Vec::new())
+            }
+            AppError::UnknownStation {
+                message,
+                suggestions,
+            } => (StatusCode::BAD_REQUEST, message, suggestions),
+            AppError::TooManyRequests {
+                message,
+                retry_after: delay,
+            } => {
+                retry_after = Some(delay.as_secs().max(1));
+                (StatusCode::TOO_MANY_REQUESTS, message, Vec::new())
+            }
+            AppError::Classified { kind, message } => {
+                retry_after = kind.retry_delay().map(|d| d.as_secs().max(1));
+                (status_for_kind(kind), message, Vec::new())
+            }
         };
 
         // Log errors to stderr for debugging
         eprintln!("[{status}] {message}");
 
-        let body = Json(ErrorResponse { error: message });
-        (status, body).into_response()
+        let body = Json(ErrorResponse {
+            error: message,
+            suggestions,
+        });
+        let mut response = (status, body).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::cache::{CacheConfig, CachedDarwinClient};
+    use crate::clock::Clock;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::planner::{DynServiceProvider, SearchConfig, SearchError, ServiceProvider};
+    use crate::stations::{StationClient, StationClientConfig, StationNames};
+    use crate::testing::{ServiceBuilder, crs, time};
+    use crate::walkable::WalkableConnections;
+
+    use super::super::API_KEY_HEADER;
+    use super::super::api_keys::ApiKeys;
+    use super::super::provider::ProviderSource;
+    use super::super::search_queue::SearchLimits;
+
+    /// Answers Paddington's board at once with one train to Swindon, then
+    /// takes a while over every other board, counting the boards asked for
+    /// and those answered.
+    #[derive(Clone, Default)]
+    struct SlowSearches {
+        calls: Arc<AtomicUsize>,
+        answered: Arc<AtomicUsize>,
+    }
+
+    impl SlowSearches {
+        async fn board(&self, station: &Crs) -> Result<Vec<Arc<Service>>, SearchError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if *station == crs("PAD") {
+                return Ok(vec![
+                    ServiceBuilder::new("S1")
+                        .call("PAD")
+                        .dep("10:05")
+                        .call("RDG")
+                        .arr("10:30")
+                        .dep("10:32")
+                        .call("SWI")
+                        .arr("11:00")
+                        .build(),
+                ]);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.answered.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![])
+        }
+    }
+
+    impl ServiceProvider for SlowSearches {
+        async fn get_departures(
+            &self,
+            station: &Crs,
+            _after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            self.board(station).await
+        }
+
+        async fn get_arrivals(
+            &self,
+            station: &Crs,
+            _after: RailTime,
+        ) -> Result<Vec<Arc<Service>>, SearchError> {
+            self.board(station).await
+        }
+    }
+
+    impl ProviderSource for SlowSearches {
+        fn provider(&self, _date: NaiveDate, _current_mins: u16) -> Arc<dyn DynServiceProvider> {
+            Arc::new(self.clone())
+        }
+    }
+
+    fn state_with(providers: &SlowSearches) -> AppState {
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            WalkableConnections::new(),
+            SearchConfig::default(),
+            names,
+        )
+        .with_clock(Clock::frozen(time("10:00").to_datetime()))
+        .with_providers(Arc::new(providers.clone()))
+    }
+
+    async fn board_search(state: AppState) -> Result<Response, AppError> {
+        let query = BoardQuery {
+            station: "PAD".to_string(),
+            destination: "BRI".to_string(),
+            window_mins: None,
+            max_changes: None,
+        };
+        reachable_departures(
+            State(state),
+            HeaderMap::new(),
+            Query(query),
+            Query(FormatQuery::default()),
+        )
+        .await
+    }
+
+    #[test]
+    fn abandoned_searches_are_not_reported_as_upstream_failures() {
+        let response = AppError::from(SearchError::Cancelled).into_response();
+        assert_eq!(response.status().as_u16(), 499);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+
+        let response = AppError::from(SearchError::Timeout).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn only_issued_keys_get_search_slots_of_their_own() {
+        let state = state_with(&SlowSearches::default())
+            .with_api_keys(ApiKeys::parse("issued"))
+            .with_search_limits(SearchLimits {
+                global: 0,
+                per_key: 1,
+                queue_wait: Duration::ZERO,
+            });
+        let key = |key: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(API_KEY_HEADER, HeaderValue::from_str(key).unwrap());
+            headers
+        };
+
+        let _running = search_slot(&state, &key("issued")).await.unwrap();
+        assert!(matches!(
+            search_slot(&state, &key("issued")).await,
+            Err(AppError::TooManyRequests { .. })
+        ));
+
+        // Made-up keys get no limit of their own to fill, like no key at all
+        let _made_up = search_slot(&state, &key("made-up")).await.unwrap();
+        assert!(search_slot(&state, &key("made-up")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn dropping_a_search_stops_its_provider_calls() {
+        // A client disconnecting has axum drop the handler mid-search
+        let providers = SlowSearches::default();
+        let search = board_search(state_with(&providers));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), search)
+                .await
+                .is_err()
+        );
+        let made = providers.calls.load(Ordering::SeqCst);
+        assert!(made > 1, "the search got past the board");
+        assert_eq!(providers.answered.load(Ordering::SeqCst), 0);
+
+        // Nothing carries on in the background
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(providers.calls.load(Ordering::SeqCst), made);
+        assert_eq!(providers.answered.load(Ordering::SeqCst), 0);
     }
 }
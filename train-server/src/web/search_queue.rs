@@ -0,0 +1,255 @@
+//! Limits on how many journey searches run at once.
+//!
+//! Each search can make dozens of Darwin calls, so a burst of them at a
+//! commuter peak uses up the API quota and slows every search down. The
+//! [`SearchQueue`] holds searches beyond a global limit, and beyond a
+//! limit for each API key so one busy client can't crowd out the rest.
+//! A search waits its turn (first come, first served) for a short while;
+//! if no slot frees up in that time it's turned away, and the client is
+//! told to try again later.
+//!
+//! Searches without an issued API key only count towards the global limit.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How many searches may run at once, and how long others wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Searches running at once across all clients (0 = unlimited)
+    pub global: usize,
+
+    /// Searches running at once for one API key (0 = unlimited)
+    pub per_key: usize,
+
+    /// How long a search waits for a slot before being turned away
+    pub queue_wait: Duration,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            global: 32,
+            per_key: 4,
+            queue_wait: Duration::from_secs(2),
+        }
+    }
+}
+
+impl SearchLimits {
+    /// No limits: every search runs straight away.
+    pub fn unlimited() -> Self {
+        Self {
+            global: 0,
+            per_key: 0,
+            queue_wait: Duration::ZERO,
+        }
+    }
+}
+
+/// A search was turned away because no slot freed up in time.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFull {
+    /// The client's API key already has as many searches running as allowed
+    #[error("Too many searches running for this API key, try again shortly")]
+    PerKey,
+
+    /// The server is running as many searches as allowed
+    #[error("Too many searches running, try again shortly")]
+    Global,
+}
+
+/// A slot for one search; the slot is given up when this is dropped.
+#[derive(Debug)]
+pub struct SearchPermit {
+    _key: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+/// Queue bounding the searches running at once.
+#[derive(Debug)]
+pub struct SearchQueue {
+    limits: SearchLimits,
+    global: Option<Arc<Semaphore>>,
+    per_key: Mutex<HashMap<String, Arc<Semaphore>>>,
+    rejected: AtomicU64,
+}
+
+impl Default for SearchQueue {
+    fn default() -> Self {
+        Self::new(SearchLimits::default())
+    }
+}
+
+impl SearchQueue {
+    /// A queue enforcing `limits`.
+    pub fn new(limits: SearchLimits) -> Self {
+        Self {
+            limits,
+            global: (limits.global > 0).then(|| Arc::new(Semaphore::new(limits.global))),
+            per_key: Mutex::new(HashMap::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// The limits this queue enforces.
+    pub fn limits(&self) -> SearchLimits {
+        self.limits
+    }
+
+    /// Wait for a slot to run a search for `api_key`.
+    ///
+    /// The key's own slot is taken first, so a client over its limit
+    /// doesn't hold a global slot while it waits.
+    pub async fn acquire(&self, api_key: Option<&str>) -> Result<SearchPermit, QueueFull> {
+        let key = match api_key.and_then(|k| self.key_semaphore(k)) {
+            Some(semaphore) => Some(self.wait(semaphore, QueueFull::PerKey).await?),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(semaphore) => Some(self.wait(semaphore.clone(), QueueFull::Global).await?),
+            None => None,
+        };
+        Ok(SearchPermit {
+            _key: key,
+            _global: global,
+        })
+    }
+
+    /// How long a turned-away client should wait before trying again, in
+    /// whole seconds.
+    pub fn retry_after(&self) -> Duration {
+        let secs = self.limits.queue_wait.as_secs_f64().ceil().max(1.0);
+        Duration::from_secs(secs as u64)
+    }
+
+    /// Searches running now (0 if there's no global limit).
+    pub fn running(&self) -> usize {
+        self.global
+            .as_ref()
+            .map_or(0, |s| self.limits.global - s.available_permits())
+    }
+
+    /// Searches turned away since startup.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// The semaphore for `key`, creating it if needed (None if there's no
+    /// per-key limit).
+    fn key_semaphore(&self, key: &str) -> Option<Arc<Semaphore>> {
+        if self.limits.per_key == 0 {
+            return None;
+        }
+        let mut per_key = self.per_key.lock().unwrap_or_else(|e| e.into_inner());
+        // Keys with no search running or waiting hold no state worth keeping
+        per_key.retain(|_, s| Arc::strong_count(s) > 1);
+        let semaphore = per_key
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limits.per_key)));
+        Some(semaphore.clone())
+    }
+
+    async fn wait(
+        &self,
+        semaphore: Arc<Semaphore>,
+        full: QueueFull,
+    ) -> Result<OwnedSemaphorePermit, QueueFull> {
+        let acquired =
+            tokio::time::timeout(self.limits.queue_wait, semaphore.acquire_owned()).await;
+        match acquired {
+            Ok(Ok(permit)) => Ok(permit),
+            // The semaphores are never closed, so only the timeout gets here
+            _ => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(full)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(global: usize, per_key: usize) -> SearchLimits {
+        SearchLimits {
+            global,
+            per_key,
+            queue_wait: Duration::from_millis(20),
+        }
+    }
+
+    #[tokio::test]
+    async fn busy_keys_are_turned_away() {
+        let queue = SearchQueue::new(limits(10, 1));
+
+        let first = queue.acquire(Some("alice")).await.unwrap();
+        assert_eq!(
+            queue.acquire(Some("alice")).await.unwrap_err(),
+            QueueFull::PerKey
+        );
+        // Other clients still get through
+        let _bob = queue.acquire(Some("bob")).await.unwrap();
+        assert_eq!(queue.running(), 2);
+
+        drop(first);
+        assert!(queue.acquire(Some("alice")).await.is_ok());
+        assert_eq!(queue.rejected(), 1);
+    }
+
+    #[tokio::test]
+    async fn global_limit_covers_all_clients() {
+        let queue = SearchQueue::new(limits(2, 4));
+
+        let _a = queue.acquire(Some("alice")).await.unwrap();
+        let _b = queue.acquire(None).await.unwrap();
+        assert_eq!(
+            queue.acquire(Some("bob")).await.unwrap_err(),
+            QueueFull::Global
+        );
+        assert_eq!(queue.acquire(None).await.unwrap_err(), QueueFull::Global);
+    }
+
+    #[tokio::test]
+    async fn waiting_searches_run_when_a_slot_frees() {
+        let queue = Arc::new(SearchQueue::new(SearchLimits {
+            queue_wait: Duration::from_secs(5),
+            ..limits(1, 0)
+        }));
+        let first = queue.acquire(None).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire(None).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(first);
+
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn idle_keys_are_forgotten() {
+        let queue = SearchQueue::new(limits(0, 1));
+        drop(queue.acquire(Some("alice")).await.unwrap());
+        let _bob = queue.acquire(Some("bob")).await.unwrap();
+
+        let per_key = queue.per_key.lock().unwrap();
+        assert_eq!(per_key.keys().collect::<Vec<_>>(), ["bob"]);
+    }
+
+    #[tokio::test]
+    async fn unlimited_queue_never_turns_searches_away() {
+        let queue = SearchQueue::new(SearchLimits::unlimited());
+        let permits: Vec<_> =
+            futures::future::join_all((0..100).map(|_| queue.acquire(Some("alice")))).await;
+        assert!(permits.iter().all(Result::is_ok));
+        assert_eq!(queue.running(), 0);
+    }
+}
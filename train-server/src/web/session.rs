@@ -0,0 +1,506 @@
+//! Signed-cookie sessions.
+//!
+//! Remembers the user's identified train (service ref, position and
+//! destination) across requests, so a page reload can re-plan without the
+//! user identifying their train again.
+//!
+//! The cookie value is `base64url(json) "." base64url(hmac_sha256(json))`.
+//! Nothing secret is stored; the signature only stops clients from forging
+//! a session pointing at a service they never identified.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::{HeaderMap, HeaderValue, header};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use moka::future::Cache as MokaCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::domain::{Crs, RailTime, ServiceIdentity};
+use crate::planner::RecentArrivals;
+
+use super::watch_store::random_id;
+
+/// Name of the session cookie.
+pub const SESSION_COOKIE: &str = "tp_session";
+
+/// Session lifetime in seconds (the train will have arrived long before).
+const SESSION_MAX_AGE_SECS: u32 = 6 * 60 * 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key used to sign session cookies.
+#[derive(Clone)]
+pub struct SessionKey([u8; 32]);
+
+impl SessionKey {
+    /// Derive a key from an operator-supplied secret.
+    ///
+    /// Use this so sessions survive server restarts.
+    pub fn from_secret(secret: &str) -> Self {
+        Self(Sha256::digest(secret.as_bytes()).into())
+    }
+
+    /// Generate a random key.
+    ///
+    /// Sessions signed with a generated key are invalidated on restart.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes).expect("OS random number generator unavailable");
+        Self(bytes)
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(payload);
+        mac
+    }
+
+    /// Encode and sign a session as a cookie value.
+    pub fn sign(&self, session: &Session) -> String {
+        let payload =
+            serde_json::to_vec(&SessionWire::from(session)).expect("session serializes to JSON");
+        let tag = self.mac(&payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(tag)
+        )
+    }
+
+    /// Verify and decode a cookie value.
+    ///
+    /// Returns `None` if the value is malformed or the signature doesn't match.
+    pub fn verify(&self, value: &str) -> Option<Session> {
+        let (payload_b64, tag_b64) = value.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload_b64).ok()?;
+        let tag = URL_SAFE_NO_PAD.decode(tag_b64).ok()?;
+
+        // verify_slice is constant-time
+        self.mac(&payload).verify_slice(&tag).ok()?;
+
+        let wire: SessionWire = serde_json::from_slice(&payload).ok()?;
+        wire.try_into().ok()
+    }
+}
+
+impl std::fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
+/// The user's identified train, as remembered between requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// Random ID kept for as long as the user carries on with the session,
+    /// e.g. for assigning experiment variants (empty in cookies set before
+    /// sessions had IDs)
+    pub id: String,
+
+    /// Darwin service ID of the current train
+    pub service_id: String,
+
+    /// Station where the service was found (needed to re-fetch it)
+    pub board_station: Crs,
+
+    /// Current position index in the service
+    pub position: usize,
+
+    /// Destination station
+    pub destination: Crs,
+
+    /// Whether the destination is any station of the group `destination`
+    /// leads (see [`StationGroups`](crate::stations::StationGroups))
+    pub destination_group: bool,
+
+    /// How to find the service again once `service_id` expires
+    pub identity: Option<ServiceIdentity>,
+
+    /// Whether the user is waiting at `position` to board, rather than
+    /// already on the train
+    pub boarding: bool,
+
+    /// While boarding, the departure the last plan assumed
+    pub planned_departure: Option<RailTime>,
+}
+
+impl Session {
+    /// Read and verify the session cookie from request headers.
+    pub fn from_headers(headers: &HeaderMap, key: &SessionKey) -> Option<Self> {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == SESSION_COOKIE)
+            .and_then(|(_, value)| key.verify(value))
+    }
+
+    /// The ID of the request's session, or a new one if it has none.
+    pub fn id_for(headers: &HeaderMap, key: &SessionKey) -> String {
+        Self::from_headers(headers, key)
+            .map(|session| session.id)
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(random_id)
+    }
+
+    /// Build a `Set-Cookie` header value storing this session.
+    pub fn set_cookie(&self, key: &SessionKey) -> HeaderValue {
+        let cookie = format!(
+            "{SESSION_COOKIE}={}; Path=/; Max-Age={SESSION_MAX_AGE_SECS}; HttpOnly; SameSite=Lax",
+            key.sign(self)
+        );
+        HeaderValue::from_str(&cookie).expect("cookie value is base64 and ASCII")
+    }
+
+    /// Build a `Set-Cookie` header value that clears the session.
+    pub fn clear_cookie() -> HeaderValue {
+        HeaderValue::from_static("tp_session=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax")
+    }
+}
+
+/// How long a session's arrivals boards are kept after its last search.
+const SESSION_ARRIVALS_IDLE: Duration = Duration::from_secs(10 * 60);
+
+/// Most sessions whose arrivals boards are kept.
+const MAX_SESSION_ARRIVALS: u64 = 10_000;
+
+/// The train a session is on, as far as it's known.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SessionTrain {
+    Identity(ServiceIdentity),
+    ServiceId(String),
+}
+
+/// Arrivals boards from each session's recent searches, so that searching
+/// again soon after reuses them (see [`RecentArrivals`]).
+///
+/// Sessions live in cookies, so boards are kept for the session's train:
+/// its identity where known, otherwise its service ID.
+#[derive(Clone)]
+pub struct SessionArrivals {
+    sessions: MokaCache<SessionTrain, Arc<RecentArrivals>>,
+}
+
+impl SessionArrivals {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            sessions: MokaCache::builder()
+                .time_to_idle(SESSION_ARRIVALS_IDLE)
+                .max_capacity(MAX_SESSION_ARRIVALS)
+                .build(),
+        }
+    }
+
+    /// The boards kept for a session, empty if it has none yet.
+    pub async fn for_session(&self, session: &Session) -> Arc<RecentArrivals> {
+        let train = match session.identity {
+            Some(identity) => SessionTrain::Identity(identity),
+            None => SessionTrain::ServiceId(session.service_id.clone()),
+        };
+        self.sessions
+            .get_with(train, async { Arc::default() })
+            .await
+    }
+}
+
+impl Default for SessionArrivals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serialized form of [`Session`] (domain types aren't serde-aware).
+#[derive(Serialize, Deserialize)]
+struct SessionWire {
+    /// Absent in cookies set before sessions had IDs
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    id: String,
+    service_id: String,
+    board_station: String,
+    position: usize,
+    destination: String,
+    /// Absent in cookies set before station groups
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    destination_group: bool,
+    /// Absent in cookies set before identities were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identity: Option<IdentityWire>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    boarding: bool,
+    /// Planned departure as RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    planned_departure: Option<String>,
+}
+
+/// Serialized form of [`ServiceIdentity`].
+#[derive(Serialize, Deserialize)]
+struct IdentityWire {
+    origin: String,
+    /// Booked origin departure as RFC 3339
+    departs: String,
+    terminus: String,
+}
+
+/// Format of times in cookies set before times were RFC 3339.
+const LEGACY_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+fn write_time(t: RailTime) -> String {
+    t.to_rfc3339(&t.uk_offset())
+}
+
+fn read_time(s: &str) -> Option<RailTime> {
+    RailTime::from_rfc3339(s).ok().or_else(|| {
+        let t = chrono::NaiveDateTime::parse_from_str(s, LEGACY_TIME_FORMAT).ok()?;
+        Some(RailTime::new(t.date(), t.time()))
+    })
+}
+
+impl From<&Session> for SessionWire {
+    fn from(s: &Session) -> Self {
+        Self {
+            id: s.id.clone(),
+            service_id: s.service_id.clone(),
+            board_station: s.board_station.as_str().to_string(),
+            position: s.position,
+            destination: s.destination.as_str().to_string(),
+            destination_group: s.destination_group,
+            identity: s.identity.as_ref().map(|i| IdentityWire {
+                origin: i.origin.as_str().to_string(),
+                departs: write_time(i.origin_departure),
+                terminus: i.terminus.as_str().to_string(),
+            }),
+            boarding: s.boarding,
+            planned_departure: s.planned_departure.map(write_time),
+        }
+    }
+}
+
+impl TryFrom<SessionWire> for Session {
+    type Error = crate::domain::InvalidCrs;
+
+    fn try_from(w: SessionWire) -> Result<Self, Self::Error> {
+        let identity = match w.identity {
+            Some(i) => {
+                // An unreadable time just loses the fallback, not the session
+                match read_time(&i.departs) {
+                    Some(departs) => Some(ServiceIdentity {
+                        origin: Crs::parse(&i.origin)?,
+                        origin_departure: departs,
+                        terminus: Crs::parse(&i.terminus)?,
+                    }),
+                    None => None,
+                }
+            }
+            None => None,
+        };
+        let planned_departure = w.planned_departure.as_deref().and_then(read_time);
+        Ok(Self {
+            id: w.id,
+            service_id: w.service_id,
+            board_station: Crs::parse(&w.board_station)?,
+            position: w.position,
+            destination: Crs::parse(&w.destination)?,
+            destination_group: w.destination_group,
+            identity,
+            boarding: w.boarding,
+            planned_departure,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crs(s: &str) -> Crs {
+        Crs::parse(s).unwrap()
+    }
+
+    fn session() -> Session {
+        Session {
+            id: "session-1".to_string(),
+            service_id: "ABC123".to_string(),
+            board_station: crs("PAD"),
+            position: 2,
+            destination: crs("BRI"),
+            destination_group: false,
+            identity: None,
+            boarding: false,
+            planned_departure: None,
+        }
+    }
+
+    #[test]
+    fn sign_verify_roundtrip() {
+        let key = SessionKey::from_secret("test secret");
+        let value = key.sign(&session());
+        assert_eq!(key.verify(&value), Some(session()));
+    }
+
+    #[test]
+    fn identity_roundtrip() {
+        let key = SessionKey::from_secret("test secret");
+        let departs = chrono::NaiveDate::from_ymd_opt(2026, 1, 3)
+            .unwrap()
+            .and_hms_opt(14, 15, 0)
+            .unwrap();
+        let with_identity = Session {
+            identity: Some(ServiceIdentity {
+                origin: crs("PAD"),
+                origin_departure: RailTime::new(departs.date(), departs.time()),
+                terminus: crs("BRI"),
+            }),
+            ..session()
+        };
+
+        let value = key.sign(&with_identity);
+        assert_eq!(key.verify(&value), Some(with_identity));
+    }
+
+    #[tokio::test]
+    async fn arrivals_are_kept_per_train() {
+        let store = SessionArrivals::new();
+        let first = store.for_session(&session()).await;
+
+        // Later searches from the same train share the boards
+        let moved_on = Session {
+            position: 3,
+            ..session()
+        };
+        assert!(Arc::ptr_eq(&first, &store.for_session(&moved_on).await));
+
+        let other_train = Session {
+            service_id: "XYZ789".to_string(),
+            ..session()
+        };
+        assert!(!Arc::ptr_eq(&first, &store.for_session(&other_train).await));
+    }
+
+    #[test]
+    fn destination_group_roundtrip() {
+        let key = SessionKey::from_secret("test secret");
+        let to_group = Session {
+            destination: crs("GLC"),
+            destination_group: true,
+            ..session()
+        };
+
+        let value = key.sign(&to_group);
+        assert_eq!(key.verify(&value), Some(to_group));
+    }
+
+    #[test]
+    fn boarding_roundtrip() {
+        let key = SessionKey::from_secret("test secret");
+        let departs = chrono::NaiveDate::from_ymd_opt(2026, 1, 3)
+            .unwrap()
+            .and_hms_opt(14, 15, 0)
+            .unwrap();
+        let boarding = Session {
+            boarding: true,
+            planned_departure: Some(RailTime::new(departs.date(), departs.time())),
+            ..session()
+        };
+
+        let value = key.sign(&boarding);
+        assert_eq!(key.verify(&value), Some(boarding));
+    }
+
+    #[test]
+    fn cookie_without_identity_still_valid() {
+        let key = SessionKey::from_secret("test secret");
+        let payload =
+            br#"{"service_id":"ABC123","board_station":"PAD","position":2,"destination":"BRI"}"#;
+        let tag = key.mac(payload).finalize().into_bytes();
+        let value = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(tag)
+        );
+
+        let without_id = Session {
+            id: String::new(),
+            ..session()
+        };
+        assert_eq!(key.verify(&value), Some(without_id));
+    }
+
+    #[test]
+    fn cookie_with_legacy_times_still_valid() {
+        let key = SessionKey::from_secret("test secret");
+        let payload = br#"{"service_id":"ABC123","board_station":"PAD","position":2,"destination":"BRI","planned_departure":"2026-01-03T14:15"}"#;
+        let tag = key.mac(payload).finalize().into_bytes();
+        let value = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(tag)
+        );
+
+        let planned = key.verify(&value).unwrap().planned_departure.unwrap();
+        assert_eq!(
+            planned.to_rfc3339(&planned.uk_offset()),
+            "2026-01-03T14:15:00+00:00"
+        );
+    }
+
+    #[test]
+    fn wrong_key_rejected() {
+        let value = SessionKey::from_secret("one").sign(&session());
+        assert_eq!(SessionKey::from_secret("two").verify(&value), None);
+    }
+
+    #[test]
+    fn tampered_payload_rejected() {
+        let key = SessionKey::from_secret("test secret");
+        let value = key.sign(&session());
+        let (_, tag) = value.split_once('.').unwrap();
+
+        let mut forged = session();
+        forged.position = 0;
+        let forged_payload = serde_json::to_vec(&SessionWire::from(&forged)).unwrap();
+        let forged_value = format!("{}.{}", URL_SAFE_NO_PAD.encode(forged_payload), tag);
+
+        assert_eq!(key.verify(&forged_value), None);
+    }
+
+    #[test]
+    fn malformed_values_rejected() {
+        let key = SessionKey::generate();
+        assert_eq!(key.verify(""), None);
+        assert_eq!(key.verify("nodot"), None);
+        assert_eq!(key.verify("!!!.???"), None);
+    }
+
+    #[test]
+    fn read_from_cookie_header() {
+        let key = SessionKey::from_secret("test secret");
+        let mut headers = HeaderMap::new();
+        let cookie = format!("other=1; {}={}", SESSION_COOKIE, key.sign(&session()));
+        headers.insert(header::COOKIE, HeaderValue::from_str(&cookie).unwrap());
+
+        assert_eq!(Session::from_headers(&headers, &key), Some(session()));
+    }
+
+    #[test]
+    fn missing_cookie_is_none() {
+        let key = SessionKey::from_secret("test secret");
+        assert_eq!(Session::from_headers(&HeaderMap::new(), &key), None);
+    }
+
+    #[test]
+    fn set_cookie_attributes() {
+        let key = SessionKey::from_secret("test secret");
+        let header = session().set_cookie(&key);
+        let s = header.to_str().unwrap();
+        assert!(s.starts_with("tp_session="));
+        assert!(s.contains("HttpOnly"));
+        assert!(s.contains("SameSite=Lax"));
+    }
+}
@@ -2,10 +2,26 @@
 
 use std::sync::Arc;
 
+use crate::alighting::StationMetadataTable;
 use crate::cache::CachedDarwinClient;
-use crate::planner::SearchConfig;
-use crate::stations::StationNames;
-use crate::walkable::WalkableConnections;
+use crate::clock::Clock;
+use crate::darwin::ReferenceData;
+use crate::notify::Notifier;
+use crate::planner::{PerformanceHistory, SearchConfig, TravelTimes};
+use crate::stations::{PlatformTopology, StationGroups, StationNames};
+use crate::walkable::{LiftOutages, WalkableConnections, WalkableStore};
+
+use super::analytics::UsageAnalytics;
+use super::api_keys::ApiKeys;
+use super::experiments::Experiments;
+use super::favourite_store::FavouriteStore;
+use super::journey_archive::JourneyArchive;
+use super::journey_store::JourneyStore;
+use super::provider::{DarwinProviders, ProviderSource};
+use super::reload::{ConfigSource, LiveSettings};
+use super::search_queue::{SearchLimits, SearchQueue};
+use super::session::{SessionArrivals, SessionKey};
+use super::watch_store::WatchStore;
 
 /// Shared application state.
 ///
@@ -15,29 +31,255 @@ pub struct AppState {
     /// Cached Darwin API client
     pub darwin: Arc<CachedDarwinClient>,
 
-    /// Walkable connections between stations
-    pub walkable: Arc<WalkableConnections>,
+    /// Journey planner configuration and walkable connections, swapped
+    /// whole by reloads and admin edits
+    pub settings: LiveSettings,
+
+    /// Where a reload gets fresh search configuration (None = kept as is)
+    pub config_source: Option<ConfigSource>,
+
+    /// Where admin edits to walkable connections are persisted (None = memory only)
+    pub walkable_store: Option<WalkableStore>,
+
+    /// Bearer token for the admin API (None = admin API disabled)
+    pub admin_token: Option<Arc<str>>,
 
-    /// Journey planner configuration
-    pub config: Arc<SearchConfig>,
+    /// API keys the operator has issued (none unless configured)
+    pub api_keys: Arc<ApiKeys>,
+
+    /// Where clients reach the server, without a trailing slash, for links
+    /// printed on itineraries (None = printed without a link)
+    pub public_url: Option<Arc<str>>,
 
     /// Station CRS → name lookup
     pub station_names: StationNames,
+
+    /// Darwin reason codes and operator names, for explaining disruption
+    pub reference: ReferenceData,
+
+    /// Stations whose lifts are out of service, kept up to date by the
+    /// admin API or a disruption feed
+    pub lift_outages: LiftOutages,
+
+    /// Key for signing session cookies
+    pub session_key: Arc<SessionKey>,
+
+    /// Recently planned journeys, for follow-up requests by ID
+    pub journeys: JourneyStore,
+
+    /// Journeys users are watching
+    pub watches: WatchStore,
+
+    /// Destinations users have saved under a name
+    pub favourites: FavouriteStore,
+
+    /// Watched journeys that have finished, with how they went
+    pub archive: JourneyArchive,
+
+    /// Sends watchers urgent alerts over Telegram, webhooks or SMS (None =
+    /// alerts only seen by polling)
+    pub notifier: Option<Arc<Notifier>>,
+
+    /// Arrivals boards from each session's recent searches, for reuse
+    pub recent_arrivals: SessionArrivals,
+
+    /// Fastest observed travel times, learned across searches for pruning
+    pub travel_times: Arc<TravelTimes>,
+
+    /// How punctual services have been on past days, learned across
+    /// searches for judging changes
+    pub performance: Arc<PerformanceHistory>,
+
+    /// Where to alight at each station for the quickest interchange
+    pub station_metadata: Arc<StationMetadataTable>,
+
+    /// Which platforms share one physical platform, for footbridge margins
+    /// and same-platform hints
+    pub platforms: Arc<PlatformTopology>,
+
+    /// Groups of stations that can be searched as one, e.g. "Glasgow"
+    pub station_groups: Arc<StationGroups>,
+
+    /// Bounds the searches running at once, overall and per API key
+    pub search_queue: Arc<SearchQueue>,
+
+    /// Per-request variants of planner behaviours
+    pub experiments: Arc<Experiments>,
+
+    /// Anonymous counts of searches for the operator (None = not kept)
+    pub analytics: Option<Arc<UsageAnalytics>>,
+
+    /// Source of the current time
+    pub clock: Clock,
+
+    /// Where searches get their trains from
+    pub providers: Arc<dyn ProviderSource>,
 }
 
 impl AppState {
     /// Create a new app state.
+    ///
+    /// Sessions are signed with a randomly generated key; use
+    /// [`AppState::with_session_key`] to keep them valid across restarts.
     pub fn new(
         darwin: CachedDarwinClient,
         walkable: WalkableConnections,
         config: SearchConfig,
         station_names: StationNames,
     ) -> Self {
+        let darwin = Arc::new(darwin);
         Self {
-            darwin: Arc::new(darwin),
-            walkable: Arc::new(walkable),
-            config: Arc::new(config),
+            providers: Arc::new(DarwinProviders::new(darwin.clone())),
+            darwin,
+            settings: LiveSettings::new(config, walkable),
+            config_source: None,
+            walkable_store: None,
+            admin_token: None,
+            api_keys: Arc::new(ApiKeys::new()),
+            public_url: None,
             station_names,
+            reference: ReferenceData::empty(),
+            lift_outages: LiftOutages::new(),
+            session_key: Arc::new(SessionKey::generate()),
+            journeys: JourneyStore::new(),
+            watches: WatchStore::new(),
+            favourites: FavouriteStore::new(),
+            archive: JourneyArchive::new(),
+            notifier: None,
+            recent_arrivals: SessionArrivals::new(),
+            travel_times: Arc::new(TravelTimes::new()),
+            performance: Arc::new(PerformanceHistory::new()),
+            station_metadata: Arc::new(StationMetadataTable::new()),
+            platforms: Arc::new(PlatformTopology::new()),
+            station_groups: Arc::new(StationGroups::new()),
+            search_queue: Arc::new(SearchQueue::default()),
+            experiments: Arc::new(Experiments::new()),
+            analytics: Some(Arc::new(UsageAnalytics::new())),
+            clock: Clock::system(),
         }
     }
+
+    /// Use the given table for alighting hints on changes.
+    pub fn with_station_metadata(mut self, metadata: StationMetadataTable) -> Self {
+        self.station_metadata = Arc::new(metadata);
+        self
+    }
+
+    /// Use the given platform layouts for changes.
+    pub fn with_platform_topology(mut self, platforms: PlatformTopology) -> Self {
+        self.platforms = Arc::new(platforms);
+        self
+    }
+
+    /// Build on the given punctuality history, e.g. one loaded from disk.
+    pub fn with_performance(mut self, performance: Arc<PerformanceHistory>) -> Self {
+        self.performance = performance;
+        self
+    }
+
+    /// Explain delay and cancellation reason codes with the given
+    /// reference data.
+    pub fn with_reference_data(mut self, reference: ReferenceData) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    /// Count searches in the given analytics, e.g. ones loaded from disk,
+    /// or not at all with `None`.
+    pub fn with_analytics(mut self, analytics: Option<Arc<UsageAnalytics>>) -> Self {
+        self.analytics = analytics;
+        self
+    }
+
+    /// Keep finished journeys in the given archive, e.g. one loaded from
+    /// disk.
+    pub fn with_archive(mut self, archive: JourneyArchive) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Send watchers' urgent alerts with the given notifier.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Bound the searches running at once by `limits`.
+    pub fn with_search_limits(mut self, limits: SearchLimits) -> Self {
+        self.search_queue = Arc::new(SearchQueue::new(limits));
+        self
+    }
+
+    /// Run the given experiments on planner behaviours.
+    pub fn with_experiments(mut self, experiments: Experiments) -> Self {
+        self.experiments = Arc::new(experiments);
+        self
+    }
+
+    /// Let searches for a group's name cover all its stations.
+    pub fn with_station_groups(mut self, groups: StationGroups) -> Self {
+        self.station_groups = Arc::new(groups);
+        self
+    }
+
+    /// Search for trains with providers from the given source rather than
+    /// straight from Darwin.
+    pub fn with_providers(mut self, providers: Arc<dyn ProviderSource>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Read the current time from the given clock rather than the system's.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Persist admin edits to walkable connections in the given store.
+    pub fn with_walkable_store(mut self, store: WalkableStore) -> Self {
+        self.walkable_store = Some(store);
+        self
+    }
+
+    /// Reload search configuration from the given source, rather than
+    /// keeping the startup configuration, on [`reload`](super::reload()).
+    pub fn with_config_source(
+        mut self,
+        source: impl Fn() -> SearchConfig + Send + Sync + 'static,
+    ) -> Self {
+        self.config_source = Some(Arc::new(source));
+        self
+    }
+
+    /// Share planned journeys with other instances through Redis.
+    #[cfg(feature = "redis")]
+    pub fn with_shared_cache(mut self, shared: crate::cache::RedisCache) -> Self {
+        self.journeys = self.journeys.with_shared_cache(shared);
+        self
+    }
+
+    /// Enable the admin API, authenticated by the given bearer token.
+    pub fn with_admin_token(mut self, token: impl Into<Arc<str>>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// Accept the given API keys.
+    pub fn with_api_keys(mut self, keys: ApiKeys) -> Self {
+        self.api_keys = Arc::new(keys);
+        self
+    }
+
+    /// Link printed itineraries back to the server at `url` (e.g.
+    /// `https://trains.example.org`).
+    pub fn with_public_url(mut self, url: &str) -> Self {
+        self.public_url = Some(url.trim_end_matches('/').into());
+        self
+    }
+
+    /// Use the given key to sign session cookies.
+    pub fn with_session_key(mut self, key: SessionKey) -> Self {
+        self.session_key = Arc::new(key);
+        self
+    }
 }
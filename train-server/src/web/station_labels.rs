@@ -0,0 +1,94 @@
+//! Station display names for responses.
+//!
+//! Boards name the stations they list, but not always: some calls come
+//! without a name, and walks only know the CRS codes at either end. Before
+//! a response is sent, every station in it without a proper name is looked
+//! up in [`StationNames`] in one go, keeping the code for stations it
+//! doesn't know (which it logs, so stale station data gets noticed).
+
+use crate::domain::Crs;
+use crate::stations::StationNames;
+
+use super::dto::StationLabel;
+
+/// Name every station in `labels` that has no name, or only its CRS code.
+///
+/// Stations [`StationNames`] doesn't know are named by their code.
+pub async fn name_stations<'a>(
+    names: &StationNames,
+    labels: impl IntoIterator<Item = StationLabel<'a>>,
+) {
+    let unnamed: Vec<StationLabel<'a>> = labels
+        .into_iter()
+        .filter(|(crs, name)| name.trim().is_empty() || name.as_str() == *crs)
+        .collect();
+    if unnamed.is_empty() {
+        return;
+    }
+
+    let found = names
+        .names(unnamed.iter().filter_map(|(crs, _)| Crs::parse(crs).ok()))
+        .await;
+    for (crs, name) in unnamed {
+        *name = Crs::parse(crs)
+            .ok()
+            .and_then(|crs| found.get(&crs))
+            .cloned()
+            .unwrap_or_else(|| crs.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::domain::{CallIndex, Journey, Leg, Segment, Walk};
+    use crate::stations::{StationClient, StationClientConfig};
+    use crate::testing::{ServiceBuilder, crs};
+    use crate::web::{JourneyResult, SegmentResult, TimeFormat};
+
+    fn station_names(entries: &[(&str, &str)]) -> StationNames {
+        let client = StationClient::new(StationClientConfig::new("")).unwrap();
+        let names = entries
+            .iter()
+            .map(|(code, name)| (crs(code), name.to_string()))
+            .collect();
+        StationNames::from_known(client, names, HashMap::new())
+    }
+
+    #[tokio::test]
+    async fn walks_and_unnamed_calls_get_display_names() {
+        // The board gave no name for Cambridge; the walk only knows codes
+        let service = ServiceBuilder::new("S1")
+            .call("KGX")
+            .named("London Kings Cross")
+            .dep("10:00")
+            .call("CBG")
+            .named("")
+            .arr("10:50")
+            .build();
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        let walk = Walk::new(crs("CBG"), crs("CMB"), Duration::minutes(5));
+        let journey = Journey::new(vec![Segment::Train(leg), Segment::Walk(walk)]).unwrap();
+        let mut result = JourneyResult::from_journey(&journey, &TimeFormat::default());
+
+        let names = station_names(&[("CBG", "Cambridge"), ("KGX", "King's Cross")]);
+        name_stations(&names, result.station_labels()).await;
+
+        let SegmentResult::Train(leg) = &result.segments[0] else {
+            panic!("expected a leg");
+        };
+        // Names the board gave are kept
+        assert_eq!(leg.origin.name, "London Kings Cross");
+        assert_eq!(leg.destination.name, "Cambridge");
+        let SegmentResult::Walk(walk) = &result.segments[1] else {
+            panic!("expected a walk");
+        };
+        assert_eq!(walk.from.name, "Cambridge");
+        // Unknown stations keep their code
+        assert_eq!(walk.to.name, "CMB");
+    }
+}
@@ -1,8 +1,21 @@
 //! Askama templates for the web frontend.
 
+use std::collections::{BTreeSet, HashMap};
+
 use askama::Template;
 
-use crate::domain::{Journey, Segment, Service};
+use crate::alighting::StationMetadataTable;
+use crate::domain::{Crs, Journey, Segment, Service};
+use crate::planner::{Continuation, NearMiss, RankReason};
+use crate::stations::{OpeningHours, PlatformTopology, StationLocation};
+use crate::walkable::WalkableConnections;
+
+use super::dto::{
+    ContinuationHintResult, NavigationLinks, PlatformChangeInfo, access_warning_messages,
+    closure_warnings, ending_message, routeing_warnings, station_message_warnings,
+};
+use super::journey_archive::{ArchivedCall, ArchivedJourney};
+use super::time_format::TimeFormat;
 
 // ============================================================================
 // Page Templates (extend base.html)
@@ -27,6 +40,13 @@ pub struct ErrorTemplate {
     pub details: Option<String>,
 }
 
+/// The user's recently finished journeys.
+#[derive(Template)]
+#[template(path = "recent_journeys.html")]
+pub struct RecentJourneysTemplate {
+    pub journeys: Vec<RecentJourneyView>,
+}
+
 // ============================================================================
 // Fragment Templates (AJAX responses, no base.html)
 // ============================================================================
@@ -36,6 +56,8 @@ pub struct ErrorTemplate {
 #[template(path = "service_list.html")]
 pub struct ServiceListTemplate {
     pub services: Vec<ServiceView>,
+    /// Station messages on the board
+    pub messages: Vec<String>,
 }
 
 /// Journey results fragment.
@@ -43,6 +65,18 @@ pub struct ServiceListTemplate {
 #[template(path = "journey_results.html")]
 pub struct JourneyResultsTemplate {
     pub journeys: Vec<JourneyView>,
+    /// Minutes until the user's train departs, when planning before boarding
+    pub departs_in_mins: Option<i64>,
+    /// Whether the train's departure has slipped since the last plan
+    pub departure_slipped: bool,
+    /// If no journeys were found, ones that fall just short
+    pub near_misses: Vec<NearMissView>,
+    /// If nothing reaches the destination or comes close, as much of the
+    /// journey as can be planned now
+    pub first_portion: Option<FirstPortionView>,
+    /// Warning that results may be incomplete, naming the stations whose
+    /// data was unavailable
+    pub data_quality: Option<String>,
 }
 
 /// Train identification results fragment.
@@ -50,6 +84,8 @@ pub struct JourneyResultsTemplate {
 #[template(path = "identify_results.html")]
 pub struct IdentifyResultsTemplate {
     pub matches: Vec<TrainMatchView>,
+    /// The user's favourite destinations, for planning to in one tap
+    pub favourites: Vec<FavouriteView>,
     pub next_station: String,
     pub terminus: Option<String>,
 }
@@ -58,6 +94,13 @@ pub struct IdentifyResultsTemplate {
 // View Models (for templates)
 // ============================================================================
 
+/// A favourite destination, offered as a quick plan button.
+#[derive(Debug, Clone)]
+pub struct FavouriteView {
+    pub name: String,
+    pub crs: String,
+}
+
 /// Service view model for templates.
 #[derive(Debug, Clone)]
 pub struct ServiceView {
@@ -70,6 +113,8 @@ pub struct ServiceView {
     pub platform: Option<String>,
     pub is_cancelled: bool,
     pub calls: Vec<CallView>,
+    /// Station the service departs from, shown on a station group's board
+    pub board_station: Option<String>,
 }
 
 impl ServiceView {
@@ -103,7 +148,7 @@ impl ServiceView {
     }
 
     /// Create from a domain Service.
-    pub fn from_service(service: &Service) -> Self {
+    pub fn from_service(service: &Service, times: &TimeFormat) -> Self {
         let calls: Vec<CallView> = service
             .calls
             .iter()
@@ -112,11 +157,11 @@ impl ServiceView {
                 let scheduled = c
                     .booked_departure
                     .or(c.booked_arrival)
-                    .map(|t| t.to_string());
+                    .map(|t| times.display(t));
                 let expected = c
                     .expected_departure()
                     .or(c.expected_arrival())
-                    .map(|t| t.to_string());
+                    .map(|t| times.display(t));
 
                 // Has subsequent stops if not the last call
                 let has_subsequent = i < service.calls.len() - 1;
@@ -144,12 +189,12 @@ impl ServiceView {
 
         let scheduled_departure = board_call
             .and_then(|c| c.booked_departure)
-            .map(|t| t.to_string())
+            .map(|t| times.display(t))
             .unwrap_or_default();
 
         let expected_departure = board_call
             .and_then(|c| c.expected_departure())
-            .map(|t| t.to_string());
+            .map(|t| times.display(t));
 
         let platform = board_call.and_then(|c| c.platform.clone());
 
@@ -165,8 +210,19 @@ impl ServiceView {
             platform,
             is_cancelled,
             calls,
+            board_station: None,
         }
     }
+
+    /// Show which station the service departs from, for a board merged
+    /// from several stations.
+    pub fn with_board_station(mut self, service: &Service) -> Self {
+        self.board_station = service
+            .calls
+            .get(service.board_station_idx.0)
+            .map(|c| c.station_name.clone());
+        self
+    }
 }
 
 /// Calling point view model.
@@ -205,12 +261,63 @@ pub struct JourneyView {
     pub arrival_time: String,
     pub duration_display: String,
     pub changes: usize,
+    /// Minutes between equivalent departures on a turn-up-and-go service
+    pub runs_every: Option<i64>,
+    /// Reasons a guard might reject this route
+    pub routeing_warnings: Vec<String>,
+    /// Stations a walk needs while they are closed
+    pub closure_warnings: Vec<String>,
+    /// Walks that need a lift or have stairs
+    pub access_warnings: Vec<String>,
+    /// Messages posted for the stations the journey changes at
+    pub station_messages: Vec<String>,
+    /// What the journey does better than the others, e.g. "Fastest"
+    pub rank_labels: Vec<&'static str>,
+    /// Other ways to finish on the same trains, e.g. "Or get off at
+    /// Farringdon, arriving 10:06"
+    pub alternative_endings: Vec<String>,
     pub segments: Vec<SegmentView>,
 }
 
+/// View model for a journey that falls just short of the request.
+pub struct NearMissView {
+    pub label: String,
+    pub journey: JourneyView,
+}
+
+impl NearMissView {
+    /// Create from a planner near miss.
+    pub fn from_near_miss(near_miss: &NearMiss, times: &TimeFormat) -> Self {
+        Self {
+            label: near_miss.label(),
+            journey: JourneyView::from_journey(&near_miss.journey, times),
+        }
+    }
+}
+
+/// View model for the first portion of a journey too long to plan in one go.
+pub struct FirstPortionView {
+    /// Where and when to plan the rest, e.g. "Plan the rest from Taunton
+    /// after 12:35"
+    pub label: String,
+    pub journey: JourneyView,
+}
+
+impl FirstPortionView {
+    /// Create from a planner continuation.
+    pub fn from_continuation(continuation: &Continuation, times: &TimeFormat) -> Self {
+        let hint =
+            ContinuationHintResult::from_hint(&continuation.hint, &continuation.journey, times);
+        Self {
+            label: hint.message,
+            journey: JourneyView::from_journey(&continuation.journey, times),
+        }
+    }
+}
+
 impl JourneyView {
     /// Create from a domain Journey.
-    pub fn from_journey(journey: &Journey) -> Self {
+    pub fn from_journey(journey: &Journey, times: &TimeFormat) -> Self {
         // Track whether we've seen the first train leg (the user's current train).
         let mut seen_first_train = false;
         let segments: Vec<SegmentView> = journey
@@ -221,7 +328,7 @@ impl JourneyView {
                 if is_first_train {
                     seen_first_train = true;
                 }
-                SegmentView::from_segment(segment, is_first_train)
+                SegmentView::from_segment(segment, is_first_train, times)
             })
             .collect();
 
@@ -236,13 +343,99 @@ impl JourneyView {
         };
 
         Self {
-            departure_time: journey.departure_time().to_string(),
-            arrival_time: journey.arrival_time().to_string(),
+            departure_time: times.display(journey.departure_time()),
+            arrival_time: times.display(journey.arrival_time()),
             duration_display,
             changes: journey.change_count(),
+            runs_every: journey.headway().map(|h| h.num_minutes()),
+            routeing_warnings: routeing_warnings(journey),
+            closure_warnings: Vec::new(),
+            access_warnings: Vec::new(),
+            station_messages: Vec::new(),
+            rank_labels: Vec::new(),
+            alternative_endings: journey
+                .alternative_endings()
+                .iter()
+                .map(|ending| ending_message(ending, times))
+                .collect(),
             segments,
         }
     }
+
+    /// Add map links to walk segments.
+    ///
+    /// `journey` must be the journey this view was created from.
+    pub fn add_walk_navigation(
+        &mut self,
+        journey: &Journey,
+        locations: &HashMap<Crs, StationLocation>,
+    ) {
+        for (view, segment) in self.segments.iter_mut().zip(journey.segments()) {
+            if let (SegmentView::Walk(walk_view), Segment::Walk(walk)) = (view, segment) {
+                walk_view.navigation = NavigationLinks::for_walk(walk, locations);
+            }
+        }
+    }
+
+    /// Add alighting hints to every leg that ends in a change.
+    ///
+    /// `journey` must be the journey this view was created from.
+    pub fn add_alighting_hints(&mut self, journey: &Journey, metadata: &StationMetadataTable) {
+        let last_train = journey.segments().iter().rposition(Segment::is_train);
+        for (i, (view, segment)) in self.segments.iter_mut().zip(journey.segments()).enumerate() {
+            if Some(i) == last_train {
+                continue;
+            }
+            if let (SegmentView::Train(leg_view), Segment::Train(leg)) = (view, segment) {
+                leg_view.alighting_hint = metadata.for_alighting(leg).and_then(|m| m.hint());
+            }
+        }
+    }
+
+    /// Add same-platform and footbridge hints to every leg that ends in a
+    /// change within a station, where `topology` knows.
+    ///
+    /// `journey` must be the journey this view was created from.
+    pub fn add_platform_hints(&mut self, journey: &Journey, topology: &PlatformTopology) {
+        let legs = self.segments.iter_mut().filter_map(|s| match s {
+            SegmentView::Train(leg) => Some(leg),
+            SegmentView::Walk(_) => None,
+        });
+        for (leg_view, change) in legs.zip(journey.changes()) {
+            leg_view.platform_hint =
+                PlatformChangeInfo::for_change(&change, topology).map(|p| p.hint);
+        }
+    }
+
+    /// Warn about walks through stations that are closed at the time.
+    pub fn add_closure_warnings(&mut self, journey: &Journey, hours: &HashMap<Crs, OpeningHours>) {
+        self.closure_warnings = closure_warnings(journey, hours);
+    }
+
+    /// Warn about walks that need a lift or have stairs, given the lifts
+    /// out of service at `outages`.
+    pub fn add_access_warnings(
+        &mut self,
+        journey: &Journey,
+        walkable: &WalkableConnections,
+        outages: &BTreeSet<Crs>,
+    ) {
+        self.access_warnings = access_warning_messages(journey, walkable, outages);
+    }
+
+    /// Pass on the messages posted for the stations the journey changes at.
+    pub fn add_station_messages(
+        &mut self,
+        journey: &Journey,
+        messages: &HashMap<Crs, Vec<String>>,
+    ) {
+        self.station_messages = station_message_warnings(journey, messages);
+    }
+
+    /// Add labels for what the journey does better than the others.
+    pub fn add_rank_reasons(&mut self, reasons: &[RankReason]) {
+        self.rank_labels = reasons.iter().map(|r| r.label()).collect();
+    }
 }
 
 /// Segment view model (train or walk).
@@ -256,9 +449,11 @@ impl SegmentView {
     /// Create from a domain Segment.
     ///
     /// `is_first_train` indicates this is the first train leg (the train the user is already on).
-    pub fn from_segment(segment: &Segment, is_first_train: bool) -> Self {
+    pub fn from_segment(segment: &Segment, is_first_train: bool, times: &TimeFormat) -> Self {
         match segment {
-            Segment::Train(leg) => SegmentView::Train(LegView::from_leg(leg, is_first_train)),
+            Segment::Train(leg) => {
+                SegmentView::Train(LegView::from_leg(leg, is_first_train, times))
+            }
             Segment::Walk(walk) => SegmentView::Walk(WalkView::from_walk(walk)),
         }
     }
@@ -274,20 +469,27 @@ pub struct LegView {
     pub stops: usize,
     /// Whether this is the train the user is currently on (first leg).
     pub is_current_train: bool,
+    /// Where to alight for the next connection, when known.
+    pub alighting_hint: Option<String>,
+    /// Whether the next train is on the same platform or over the
+    /// footbridge, when known.
+    pub platform_hint: Option<String>,
+    /// Where to sit, if the train divides before this leg ends.
+    pub boarding_hint: Option<String>,
 }
 
 impl LegView {
     /// Create from a domain Leg.
     ///
     /// `is_current_train` indicates this is the first leg (the train the user is already on).
-    pub fn from_leg(leg: &crate::domain::Leg, is_current_train: bool) -> Self {
+    pub fn from_leg(leg: &crate::domain::Leg, is_current_train: bool, times: &TimeFormat) -> Self {
         let origin = StationView {
             crs: leg.board_call().station.as_str().to_string(),
             name: leg.board_call().station_name.clone(),
             time: leg
                 .board_call()
                 .expected_departure()
-                .map(|t| t.to_string())
+                .map(|t| times.display(t))
                 .unwrap_or_default(),
             platform: leg.board_call().platform.clone(),
         };
@@ -298,13 +500,12 @@ impl LegView {
             time: leg
                 .alight_call()
                 .expected_arrival()
-                .map(|t| t.to_string())
+                .map(|t| times.display(t))
                 .unwrap_or_default(),
             platform: leg.alight_call().platform.clone(),
         };
 
-        // Count intermediate stops
-        let stops = leg.intermediate_stop_count();
+        let stops = leg.stops_count();
 
         Self {
             operator: leg.service().operator.clone(),
@@ -313,6 +514,9 @@ impl LegView {
             destination,
             stops,
             is_current_train,
+            alighting_hint: None,
+            platform_hint: None,
+            boarding_hint: leg.boarding_advice().map(|a| a.hint()),
         }
     }
 }
@@ -325,6 +529,8 @@ pub struct WalkView {
     pub to_crs: String,
     pub to_name: String,
     pub duration_mins: i64,
+    /// Walking directions in map apps, if station locations are known
+    pub navigation: Option<NavigationLinks>,
 }
 
 impl WalkView {
@@ -338,6 +544,7 @@ impl WalkView {
             to_crs: walk.to.as_str().to_string(),
             to_name: walk.to.as_str().to_string(),
             duration_mins: walk.duration.num_minutes(),
+            navigation: None,
         }
     }
 }
@@ -372,6 +579,14 @@ pub struct TrainMatchView {
     pub scheduled_terminus_arrival: String,
     /// Expected arrival time at terminus (if different from scheduled)
     pub expected_terminus_arrival: Option<String>,
+    /// Names of the next few calling points after the next station
+    pub next_calls: Vec<String>,
+    /// Final destination as shown on the board
+    pub final_destination: String,
+    /// How well the train fits the user's other observations (percentage)
+    pub score: u8,
+    /// Why the train scored as it did; empty if nothing else was observed
+    pub reasons: Vec<String>,
     /// Index of the board station (next_station) in the calling points list.
     /// This is the user's implicit position when identifying by next_station.
     pub board_station_idx: usize,
@@ -407,6 +622,88 @@ impl TrainMatchView {
     }
 }
 
+/// Finished journey view model, with planned and observed times.
+#[derive(Debug, Clone)]
+pub struct RecentJourneyView {
+    /// Day the journey was made, e.g. "Thu 15 Oct"
+    pub date: String,
+    pub origin: RecentCallView,
+    pub destination: RecentCallView,
+    /// How the arrival compared with the plan, e.g. "7 min late", when known
+    pub arrival_delay: Option<String>,
+    /// Whether it arrived later than planned
+    pub is_late: bool,
+    pub legs: Vec<RecentLegView>,
+}
+
+/// One train of a finished journey.
+#[derive(Debug, Clone)]
+pub struct RecentLegView {
+    pub operator: String,
+    pub headcode: Option<String>,
+    pub origin: RecentCallView,
+    pub destination: RecentCallView,
+    pub is_cancelled: bool,
+}
+
+/// A station on a finished journey, as planned and as observed.
+#[derive(Debug, Clone)]
+pub struct RecentCallView {
+    pub name: String,
+    pub scheduled_time: String,
+    /// Time last observed, if different from scheduled
+    pub actual_time: Option<String>,
+}
+
+impl RecentJourneyView {
+    /// Create from an archived journey.
+    pub fn from_archived(journey: &ArchivedJourney, times: &TimeFormat) -> Self {
+        let delay = journey.arrival_delay().map(|d| d.num_minutes());
+        Self {
+            date: journey.archived.date().format("%a %-d %b").to_string(),
+            origin: RecentCallView::from_archived(journey.origin(), times),
+            destination: RecentCallView::from_archived(journey.destination(), times),
+            arrival_delay: delay.map(|mins| match mins {
+                0 => "On time".to_string(),
+                mins if mins > 0 => format!("{mins} min late"),
+                mins => format!("{} min early", -mins),
+            }),
+            is_late: delay.is_some_and(|mins| mins > 0),
+            legs: journey
+                .legs()
+                .iter()
+                .map(|leg| RecentLegView {
+                    operator: leg.operator.clone(),
+                    headcode: leg.headcode.map(|h| h.to_string()),
+                    origin: RecentCallView::from_archived(&leg.board, times),
+                    destination: RecentCallView::from_archived(&leg.alight, times),
+                    is_cancelled: leg.cancelled,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RecentCallView {
+    fn from_archived(call: &ArchivedCall, times: &TimeFormat) -> Self {
+        let scheduled_time = call.planned.map(|t| times.display(t)).unwrap_or_default();
+        let actual_time = call
+            .actual
+            .map(|t| times.display(t))
+            .filter(|t| *t != scheduled_time);
+        Self {
+            name: call.station_name.clone(),
+            scheduled_time,
+            actual_time,
+        }
+    }
+
+    /// The time to display (observed if it differs, else scheduled).
+    pub fn display_time(&self) -> &str {
+        self.actual_time.as_deref().unwrap_or(&self.scheduled_time)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -423,6 +720,7 @@ mod tests {
             platform: None,
             is_cancelled: false,
             calls: vec![],
+            board_station: None,
         };
 
         assert_eq!(view.display_time(), "10:00");
@@ -441,6 +739,7 @@ mod tests {
             platform: None,
             is_cancelled: false,
             calls: vec![],
+            board_station: None,
         };
 
         assert_eq!(view.display_time(), "10:15");
@@ -459,6 +758,7 @@ mod tests {
             platform: None,
             is_cancelled: false,
             calls: vec![],
+            board_station: None,
         };
 
         assert!(!view.is_delayed());
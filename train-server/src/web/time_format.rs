@@ -0,0 +1,139 @@
+//! How times are written in responses.
+//!
+//! Each time in a JSON response comes twice: as an ISO 8601 datetime for
+//! programs (see [`iso_datetime`]), and as a display string for people.
+//! The display string follows the request's clock preference: `?clock=12`
+//! or `?clock=24`, or failing that the first language in `Accept-Language`.
+//! Locales that usually write times on a 12-hour clock (e.g. `en-US`) get
+//! "2:30pm"; everyone else gets "14:30", as on UK timetables.
+
+use axum::http::{HeaderMap, header};
+use serde::Deserialize;
+
+use crate::domain::RailTime;
+
+/// Locales that usually write times on a 12-hour clock.
+const TWELVE_HOUR_LOCALES: [&str; 6] = ["en-au", "en-ca", "en-in", "en-nz", "en-ph", "en-us"];
+
+/// Which clock to show times on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum HourCycle {
+    /// "14:30"
+    #[default]
+    #[serde(rename = "24")]
+    TwentyFour,
+    /// "2:30pm"
+    #[serde(rename = "12")]
+    Twelve,
+}
+
+impl HourCycle {
+    /// The clock usually used by a language tag such as `en-GB`.
+    fn for_locale(tag: &str) -> Self {
+        let tag = tag.trim().to_ascii_lowercase();
+        if TWELVE_HOUR_LOCALES.contains(&tag.as_str()) {
+            Self::Twelve
+        } else {
+            Self::TwentyFour
+        }
+    }
+}
+
+/// How to write times for one request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeFormat {
+    /// Clock used for display strings
+    pub hour_cycle: HourCycle,
+}
+
+impl TimeFormat {
+    /// Write times on the given clock.
+    pub fn new(hour_cycle: HourCycle) -> Self {
+        Self { hour_cycle }
+    }
+
+    /// The format for a request: an explicit `clock` preference, else the
+    /// first language the client accepts.
+    pub fn for_request(headers: &HeaderMap, clock: Option<HourCycle>) -> Self {
+        let hour_cycle = clock.unwrap_or_else(|| {
+            headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .map(|first| HourCycle::for_locale(first.split(';').next().unwrap_or("")))
+                .unwrap_or_default()
+        });
+        Self::new(hour_cycle)
+    }
+
+    /// A time as shown to people.
+    pub fn display(&self, time: RailTime) -> String {
+        match self.hour_cycle {
+            HourCycle::TwentyFour => time.to_string(),
+            HourCycle::Twelve => time.to_datetime().format("%-I:%M%P").to_string(),
+        }
+    }
+}
+
+/// A time as an ISO 8601 datetime in UK time, with its offset from UTC
+/// (e.g. `2024-03-15T14:30:00+00:00`).
+pub fn iso_datetime(time: RailTime) -> String {
+    time.to_rfc3339(&time.uk_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn at(hour: u32, min: u32) -> RailTime {
+        RailTime::new(
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap(),
+            NaiveTime::from_hms_opt(hour, min, 0).unwrap(),
+        )
+    }
+
+    fn accepting(languages: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_LANGUAGE,
+            HeaderValue::from_str(languages).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn display_follows_hour_cycle() {
+        let twelve = TimeFormat::new(HourCycle::Twelve);
+        assert_eq!(twelve.display(at(14, 30)), "2:30pm");
+        assert_eq!(twelve.display(at(0, 5)), "12:05am");
+        assert_eq!(TimeFormat::default().display(at(9, 5)), "09:05");
+    }
+
+    #[test]
+    fn iso_datetime_includes_date() {
+        assert_eq!(iso_datetime(at(14, 30)), "2024-03-15T14:30:00+00:00");
+    }
+
+    #[test]
+    fn preference_from_request() {
+        let us = accepting("en-US,en;q=0.9");
+        assert_eq!(
+            TimeFormat::for_request(&us, None).hour_cycle,
+            HourCycle::Twelve
+        );
+        assert_eq!(
+            TimeFormat::for_request(&us, Some(HourCycle::TwentyFour)).hour_cycle,
+            HourCycle::TwentyFour
+        );
+        assert_eq!(
+            TimeFormat::for_request(&accepting("en-GB;q=0.8"), None).hour_cycle,
+            HourCycle::TwentyFour
+        );
+        assert_eq!(
+            TimeFormat::for_request(&HeaderMap::new(), None),
+            TimeFormat::default()
+        );
+    }
+}
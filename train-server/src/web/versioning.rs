@@ -0,0 +1,182 @@
+//! API versioning.
+//!
+//! The JSON API lives under [`API_V1`], which always responds with JSON.
+//! The original unversioned paths keep working as compatibility shims for
+//! existing clients, but their JSON responses carry `Deprecation`, `Sunset`
+//! and `Link: rel="successor-version"` headers pointing at the `/api/v1`
+//! equivalent. HTML responses from those paths are the web UI itself and
+//! are not deprecated.
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+
+/// Prefix for version 1 of the JSON API.
+pub const API_V1: &str = "/api/v1";
+
+/// When the unversioned JSON endpoints were deprecated (RFC 9745 date,
+/// 2026-10-15T00:00:00Z).
+const DEPRECATED_SINCE: &str = "@1792022400";
+
+/// When the unversioned JSON endpoints may stop working (RFC 8594).
+const SUNSET: &str = "Thu, 15 Apr 2027 00:00:00 GMT";
+
+/// The `/api/v1` path that supersedes an unversioned path.
+///
+/// Versioned paths mirror the unversioned ones, minus any `/api` prefix:
+/// `/api/stations/search` becomes `/api/v1/stations/search` and
+/// `/journey/plan` becomes `/api/v1/journey/plan`.
+pub fn successor_path(path: &str) -> String {
+    let rest = path.strip_prefix("/api").unwrap_or(path);
+    format!("{API_V1}{rest}")
+}
+
+/// Force JSON responses on versioned routes.
+///
+/// The handlers are shared with the web UI and pick HTML or JSON from the
+/// `Accept` header; versioned clients always get JSON.
+pub async fn force_json(mut request: Request, next: Next) -> Response {
+    request
+        .headers_mut()
+        .insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+    next.run(request).await
+}
+
+/// Add deprecation headers to non-HTML responses from unversioned routes.
+pub async fn deprecate_unversioned(request: Request, next: Next) -> Response {
+    let successor = successor_path(request.uri().path());
+    let mut response = next.run(request).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/html"));
+    if is_html || response.status().is_redirection() {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static(DEPRECATED_SINCE));
+    headers.insert("sunset", HeaderValue::from_static(SUNSET));
+    if let Ok(link) = HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\"")) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, middleware, response::Html, routing::get};
+
+    #[test]
+    fn successor_paths() {
+        assert_eq!(
+            successor_path("/api/stations/search"),
+            "/api/v1/stations/search"
+        );
+        assert_eq!(successor_path("/journey/plan"), "/api/v1/journey/plan");
+        assert_eq!(
+            successor_path("/api/journeys/abc/geojson"),
+            "/api/v1/journeys/abc/geojson"
+        );
+    }
+
+    /// Serve `router` on an ephemeral local port, returning its base URL.
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn only_json_responses_are_deprecated() {
+        let router = Router::new()
+            .route(
+                "/json",
+                get(|| async { Json(serde_json::json!({"ok": true})) }),
+            )
+            .route("/html", get(|| async { Html("<p>ok</p>") }))
+            .layer(middleware::from_fn(deprecate_unversioned));
+        let base = serve(router).await;
+
+        let json = reqwest::get(format!("{base}/json")).await.unwrap();
+        assert_eq!(json.headers()["deprecation"], DEPRECATED_SINCE);
+        assert_eq!(json.headers()["sunset"], SUNSET);
+        assert_eq!(
+            json.headers()["link"],
+            "</api/v1/json>; rel=\"successor-version\""
+        );
+
+        let html = reqwest::get(format!("{base}/html")).await.unwrap();
+        assert!(html.headers().get("deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn versioned_routes_see_json_accept_header() {
+        let router = Router::new()
+            .route(
+                "/accept",
+                get(|headers: axum::http::HeaderMap| async move {
+                    headers[header::ACCEPT].to_str().unwrap().to_string()
+                }),
+            )
+            .layer(middleware::from_fn(force_json));
+        let base = serve(router).await;
+
+        let client = reqwest::Client::new();
+        let body = client
+            .get(format!("{base}/accept"))
+            .header("accept", "text/html")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "application/json");
+    }
+
+    #[tokio::test]
+    async fn app_serves_v1_and_deprecated_paths() {
+        use crate::cache::{CacheConfig, CachedDarwinClient};
+        use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+        use crate::planner::SearchConfig;
+        use crate::stations::{StationClient, StationClientConfig, StationNames};
+        use crate::walkable::london_connections;
+        use crate::web::{AppState, create_router};
+
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        let state = AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        );
+        let base = serve(create_router(state, "static")).await;
+
+        let v1 = reqwest::get(format!("{base}/api/v1/stations/search?q=kings"))
+            .await
+            .unwrap();
+        assert!(v1.status().is_success());
+        assert!(v1.headers().get("deprecation").is_none());
+
+        let legacy = reqwest::get(format!("{base}/api/stations/search?q=kings"))
+            .await
+            .unwrap();
+        assert!(legacy.status().is_success());
+        assert_eq!(
+            legacy.headers()["link"],
+            "</api/v1/stations/search>; rel=\"successor-version\""
+        );
+
+        let health = reqwest::get(format!("{base}/health")).await.unwrap();
+        assert!(health.headers().get("deprecation").is_none());
+    }
+}
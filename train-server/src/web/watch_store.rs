@@ -0,0 +1,373 @@
+//! Journeys users have asked to keep an eye on.
+//!
+//! A watch ties a stored journey (see [`JourneyStore`](super::JourneyStore))
+//! to whoever asked for it: a client identified by its API key, or a browser
+//! identified by a watcher cookie issued alongside its session. Listing a
+//! watch polls the journey's trains when they're due (as long-polling its
+//! changes does), so watches need no background task to stay current; one
+//! only sweeps them for journeys that have arrived, to archive. Watches
+//! expire with the journeys they refer to, and like journey versions are
+//! local to this instance.
+
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use moka::future::Cache as MokaCache;
+use sha2::{Digest, Sha256};
+
+use crate::domain::RailTime;
+use crate::error::{Classify, ErrorKind};
+use crate::notify::Channel;
+
+/// How long watches are kept; the same as the journeys they refer to.
+const WATCH_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Maximum number of watches across all owners.
+const MAX_WATCHES: u64 = 10_000;
+
+/// Most journeys one owner can watch at once.
+pub const MAX_WATCHES_PER_OWNER: usize = 20;
+
+/// Who a watch belongs to.
+///
+/// Held as a digest, so the store never keeps API keys or cookie values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WatchOwner(String);
+
+impl WatchOwner {
+    /// A client identified by its API key.
+    pub fn api_key(key: &str) -> Self {
+        Self::digest("api_key", key)
+    }
+
+    /// A browser identified by its watcher cookie.
+    pub fn session(token: &str) -> Self {
+        Self::digest("session", token)
+    }
+
+    /// An owner as saved by [`as_str`](Self::as_str).
+    pub(super) fn from_digest(digest: String) -> Self {
+        Self(digest)
+    }
+
+    /// The owner's digest, for saving.
+    pub(super) fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn digest(kind: &str, id: &str) -> Self {
+        let digest = Sha256::new()
+            .chain_update(kind)
+            .chain_update([0])
+            .chain_update(id)
+            .finalize();
+        Self(URL_SAFE_NO_PAD.encode(digest))
+    }
+}
+
+/// A journey being watched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    /// ID for deleting the watch
+    pub id: String,
+
+    /// ID of the watched journey in the journey store
+    pub journey_id: String,
+
+    /// Who asked to watch it
+    pub owner: WatchOwner,
+
+    /// When the watch was created
+    pub created: RailTime,
+
+    /// Where to send urgent alerts about the journey, such as platform
+    /// changes (none = only seen when listing watches)
+    pub channels: Vec<Channel>,
+}
+
+/// Errors creating a watch.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WatchError {
+    /// The owner is already watching as many journeys as allowed
+    #[error("At most {0} journeys can be watched at once")]
+    TooMany(usize),
+}
+
+impl Classify for WatchError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            WatchError::TooMany(_) => ErrorKind::Permanent,
+        }
+    }
+}
+
+/// A random identifier, e.g. for a watch or a watcher cookie.
+pub fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator unavailable");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// In-memory store of watches, keyed by watch ID.
+#[derive(Clone)]
+pub struct WatchStore {
+    watches: MokaCache<String, Watch>,
+}
+
+impl WatchStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self {
+            watches: MokaCache::builder()
+                .time_to_live(WATCH_TTL)
+                .max_capacity(MAX_WATCHES)
+                .build(),
+        }
+    }
+
+    /// Watch a journey for an owner.
+    ///
+    /// If the owner is already watching the journey, returns that watch
+    /// rather than adding another.
+    pub async fn create(
+        &self,
+        owner: WatchOwner,
+        journey_id: &str,
+        created: RailTime,
+    ) -> Result<Watch, WatchError> {
+        let existing = self.list(&owner);
+        if let Some(watch) = existing.iter().find(|w| w.journey_id == journey_id) {
+            return Ok(watch.clone());
+        }
+        if existing.len() >= MAX_WATCHES_PER_OWNER {
+            return Err(WatchError::TooMany(MAX_WATCHES_PER_OWNER));
+        }
+
+        let watch = Watch {
+            id: random_id(),
+            journey_id: journey_id.to_string(),
+            owner,
+            created,
+            channels: Vec::new(),
+        };
+        self.watches.insert(watch.id.clone(), watch.clone()).await;
+        Ok(watch)
+    }
+
+    /// An owner's watches, oldest first.
+    pub fn list(&self, owner: &WatchOwner) -> Vec<Watch> {
+        let mut watches: Vec<Watch> = self
+            .watches
+            .iter()
+            .map(|(_, watch)| watch)
+            .filter(|watch| watch.owner == *owner)
+            .collect();
+        watches.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+        watches
+    }
+
+    /// Send a watch's urgent alerts to the given channels, replacing any it
+    /// had.
+    pub async fn set_channels(&self, id: &str, channels: Vec<Channel>) -> Option<Watch> {
+        let mut watch = self.watches.get(id).await?;
+        watch.channels = channels;
+        self.watches.insert(watch.id.clone(), watch.clone()).await;
+        Some(watch)
+    }
+
+    /// Every watch, of every owner.
+    pub fn all(&self) -> Vec<Watch> {
+        self.watches.iter().map(|(_, watch)| watch).collect()
+    }
+
+    /// Every owner's watches of a journey.
+    pub fn watching(&self, journey_id: &str) -> Vec<Watch> {
+        self.watches
+            .iter()
+            .map(|(_, watch)| watch)
+            .filter(|watch| watch.journey_id == journey_id)
+            .collect()
+    }
+
+    /// Point a watch at the journey that replaced its own, e.g. a first
+    /// portion once the rest of it has been planned.
+    pub async fn follow(&self, id: &str, journey_id: &str) -> Option<Watch> {
+        let mut watch = self.watches.get(id).await?;
+        watch.journey_id = journey_id.to_string();
+        self.watches.insert(watch.id.clone(), watch.clone()).await;
+        Some(watch)
+    }
+
+    /// Point an owner's watches of one journey at another, e.g. the rest
+    /// of it after they boarded one of its trains. Returns how many moved.
+    pub async fn follow_journey(&self, owner: &WatchOwner, from: &str, to: &str) -> usize {
+        let mut moved = 0;
+        for watch in self.list(owner) {
+            if watch.journey_id == from && self.follow(&watch.id, to).await.is_some() {
+                moved += 1;
+            }
+        }
+        moved
+    }
+
+    /// Stop watching. Returns `false` if the owner has no such watch.
+    pub async fn remove(&self, owner: &WatchOwner, id: &str) -> bool {
+        match self.watches.get(id).await {
+            Some(watch) if watch.owner == *owner => {
+                self.watches.invalidate(id).await;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for WatchStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn time(s: &str) -> RailTime {
+        RailTime::parse_hhmm(s, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn watches_belong_to_their_owner() {
+        let store = WatchStore::new();
+        let alice = WatchOwner::api_key("alice");
+        let browser = WatchOwner::session("alice");
+        assert_ne!(alice, browser);
+
+        let first = store
+            .create(alice.clone(), "j1", time("10:00"))
+            .await
+            .unwrap();
+        let second = store
+            .create(alice.clone(), "j2", time("10:05"))
+            .await
+            .unwrap();
+        store
+            .create(browser.clone(), "j1", time("10:01"))
+            .await
+            .unwrap();
+
+        let listed: Vec<String> = store.list(&alice).into_iter().map(|w| w.id).collect();
+        assert_eq!(listed, vec![first.id.clone(), second.id]);
+
+        // Only the owner can remove a watch
+        assert!(!store.remove(&browser, &first.id).await);
+        assert!(store.remove(&alice, &first.id).await);
+        assert!(!store.remove(&alice, &first.id).await);
+        assert_eq!(store.list(&alice).len(), 1);
+        assert_eq!(store.list(&browser).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn watching_twice_reuses_the_watch() {
+        let store = WatchStore::new();
+        let owner = WatchOwner::api_key("key");
+
+        let first = store
+            .create(owner.clone(), "j1", time("10:00"))
+            .await
+            .unwrap();
+        let again = store
+            .create(owner.clone(), "j1", time("10:10"))
+            .await
+            .unwrap();
+
+        assert_eq!(first, again);
+        assert_eq!(store.list(&owner).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn watches_follow_replacement_journeys() {
+        let store = WatchStore::new();
+        let owner = WatchOwner::api_key("key");
+        let watch = store
+            .create(owner.clone(), "first", time("10:00"))
+            .await
+            .unwrap();
+
+        let followed = store.follow(&watch.id, "whole").await.unwrap();
+        assert_eq!(followed.id, watch.id);
+        assert_eq!(store.list(&owner)[0].journey_id, "whole");
+        assert!(store.follow("missing", "whole").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn only_the_owners_watches_follow_a_journey() {
+        let store = WatchStore::new();
+        let alice = WatchOwner::api_key("alice");
+        let bob = WatchOwner::api_key("bob");
+        store
+            .create(alice.clone(), "j1", time("10:00"))
+            .await
+            .unwrap();
+        store
+            .create(alice.clone(), "j2", time("10:01"))
+            .await
+            .unwrap();
+        store
+            .create(bob.clone(), "j1", time("10:02"))
+            .await
+            .unwrap();
+
+        assert_eq!(store.follow_journey(&alice, "j1", "rest").await, 1);
+        let journeys: Vec<String> = store
+            .list(&alice)
+            .into_iter()
+            .map(|w| w.journey_id)
+            .collect();
+        assert_eq!(journeys, ["rest", "j2"]);
+        assert_eq!(store.list(&bob)[0].journey_id, "j1");
+    }
+
+    #[tokio::test]
+    async fn watches_of_a_journey_carry_their_channels() {
+        let store = WatchStore::new();
+        let alice = WatchOwner::api_key("alice");
+        let bob = WatchOwner::api_key("bob");
+        let watch = store.create(alice, "j1", time("10:00")).await.unwrap();
+        store.create(bob, "j1", time("10:01")).await.unwrap();
+        store
+            .create(WatchOwner::api_key("carol"), "j2", time("10:02"))
+            .await
+            .unwrap();
+        let channels = vec![Channel::Sms {
+            to: "+447700900123".to_string(),
+        }];
+
+        store.set_channels(&watch.id, channels.clone()).await;
+
+        let watching = store.watching("j1");
+        assert_eq!(watching.len(), 2);
+        let alices = watching.iter().find(|w| w.id == watch.id).unwrap();
+        assert_eq!(alices.channels, channels);
+        assert!(store.set_channels("missing", Vec::new()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn watches_per_owner_are_limited() {
+        let store = WatchStore::new();
+        let owner = WatchOwner::api_key("key");
+        for i in 0..MAX_WATCHES_PER_OWNER {
+            store
+                .create(owner.clone(), &format!("j{i}"), time("10:00"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            store.create(owner, "one more", time("10:00")).await,
+            Err(WatchError::TooMany(MAX_WATCHES_PER_OWNER))
+        );
+    }
+}
@@ -0,0 +1,497 @@
+//! REST API for watching planned journeys.
+//!
+//! Clients watch a journey by the ID it was planned with, then list their
+//! watches to get each journey's latest state and when its trains will next
+//! be polled (see [`super::watch_store`]). Clients sending an API key own
+//! their watches by it; browsers are given a watcher cookie the first time
+//! they watch a journey. Alert channels cost the operator something to
+//! deliver to, so only clients with an issued API key may give them.
+
+use std::collections::BTreeSet;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+
+use crate::darwin::ReasonTranslation;
+use crate::domain::{Journey, RailTime};
+use crate::notify::{Channel, Notification};
+
+use super::compact::{FormatQuery, json_response};
+use super::dto::{CreateWatchRequest, JourneyResult, WatchListResponse, WatchResult};
+use super::experiments::API_KEY_HEADER;
+use super::journey_archive::ArchivedJourney;
+use super::journey_diff::JourneyChange;
+use super::routes::{
+    AppError, JOURNEY_REFRESH_INTERVAL, continue_journey, reason_translation, refresh_journey,
+};
+use super::state::AppState;
+use super::station_labels::name_stations;
+use super::time_format::{TimeFormat, iso_datetime};
+use super::watch_store::{Watch, WatchOwner, random_id};
+
+/// Cookie identifying a browser's watches.
+const WATCHER_COOKIE: &str = "tp_watcher";
+
+/// How long the watcher cookie lasts: as long as the watches it owns.
+const WATCHER_MAX_AGE_SECS: u32 = 6 * 60 * 60;
+
+/// Routes for the watches API, relative to the versioned API prefix.
+pub fn watch_routes() -> Router<AppState> {
+    Router::new()
+        .route("/watches", get(list_watches).post(create_watch))
+        .route("/watches/:id", delete(delete_watch))
+}
+
+/// The API key sent with a request, if any.
+pub(super) fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|k| !k.is_empty())
+}
+
+/// The watcher cookie sent with a request, if any.
+pub(super) fn watcher_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, value)| *name == WATCHER_COOKIE && !value.is_empty())
+        .map(|(_, value)| value)
+}
+
+/// Who a request's watches belong to: its API key if it has one, otherwise
+/// its watcher cookie.
+pub(super) fn owner(headers: &HeaderMap) -> Option<WatchOwner> {
+    api_key(headers)
+        .map(WatchOwner::api_key)
+        .or_else(|| watcher_cookie(headers).map(WatchOwner::session))
+}
+
+/// A watcher cookie for `token`, lasting `max_age` seconds.
+pub(super) fn set_watcher_cookie(token: &str, max_age: u32) -> HeaderValue {
+    let cookie =
+        format!("{WATCHER_COOKIE}={token}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax");
+    HeaderValue::from_str(&cookie).expect("watcher token is base64 and ASCII")
+}
+
+/// The current time, as the watches API reports it.
+fn now(state: &AppState) -> RailTime {
+    let now = state.clock.now();
+    RailTime::new(now.date(), now.time())
+}
+
+/// Keep a record of a journey in the archive of everyone watching it once
+/// it has arrived, as last observed.
+pub(super) async fn archive_if_arrived(state: &AppState, id: &str, journey: &Journey) {
+    let now = now(state);
+    if journey.arrival_time() > now {
+        return;
+    }
+    let Some(archived) = ArchivedJourney::from_journey(id, journey, now) else {
+        return;
+    };
+    for watch in state.watches.watching(id) {
+        state.archive.archive(watch.owner, archived.clone()).await;
+    }
+}
+
+/// Archive every watched journey that has arrived.
+///
+/// Run periodically, so a journey is archived even if nobody polls it
+/// between arriving and its watches expiring.
+pub async fn archive_arrived_watches(state: &AppState) {
+    let ids: BTreeSet<String> = state
+        .watches
+        .all()
+        .into_iter()
+        .map(|watch| watch.journey_id)
+        .collect();
+    for id in ids {
+        if let Some(journey) = state.journeys.get(&id).await {
+            archive_if_arrived(state, &id, &journey).await;
+        }
+    }
+}
+
+/// A watch with its journey's latest state, polling the journey's trains
+/// first if they're due.
+///
+/// A watched first portion (see `continuation_hint`) has the rest of its
+/// journey planned once it can be, and the watch follows the whole journey.
+async fn watch_result(
+    state: &AppState,
+    mut watch: Watch,
+    times: &TimeFormat,
+    translation: Option<&ReasonTranslation>,
+) -> WatchResult {
+    if state
+        .journeys
+        .claim_refresh(&watch.journey_id, JOURNEY_REFRESH_INTERVAL)
+        .await
+    {
+        refresh_journey(state, &watch.journey_id).await;
+        if let Some(whole) = continue_journey(state, &watch.journey_id).await
+            && let Some(followed) = state.watches.follow(&watch.id, &whole).await
+        {
+            watch = followed;
+        }
+    }
+    let id = &watch.journey_id;
+
+    let config = state.settings.snapshot().config.clone();
+    let hint = state.journeys.continuation(id).await;
+    let reference = state.reference.tables().await;
+    let journey = state.journeys.get(id).await;
+    if let Some(journey) = &journey {
+        archive_if_arrived(state, id, journey).await;
+    }
+    let mut journey = journey.map(|journey| {
+        let mut dto = JourneyResult::from_journey(&journey, times);
+        dto.add_disruption_reasons(&journey, &reference, translation);
+        dto.add_confidence(&journey, &config, Some(&state.performance));
+        dto.add_price_band(&journey, &config);
+        if let Some(hint) = &hint {
+            dto.add_continuation_hint(&journey, hint, times);
+        }
+        dto.id = Some(id.clone());
+        dto
+    });
+    if let Some(dto) = &mut journey {
+        name_stations(&state.station_names, dto.station_labels()).await;
+    }
+    let (version, replaced_by) = match state
+        .journeys
+        .changes_since(id, 0, std::time::Duration::ZERO)
+        .await
+    {
+        Some(changes) => (changes.version, changes.replaced_by),
+        None => (0, None),
+    };
+    let next_refresh = state
+        .journeys
+        .next_refresh(id, JOURNEY_REFRESH_INTERVAL)
+        .await
+        .unwrap_or_default();
+    let next_refresh_at = now(state) + chrono::Duration::from_std(next_refresh).unwrap_or_default();
+
+    WatchResult {
+        id: watch.id,
+        journey_id: watch.journey_id,
+        journey,
+        version,
+        replaced_by,
+        next_refresh: iso_datetime(next_refresh_at),
+        next_refresh_in_secs: next_refresh.as_secs(),
+    }
+}
+
+/// Send the urgent alerts among a journey's changes to everyone watching it
+/// who gave channels for them, if a notifier is configured.
+///
+/// Delivery happens in the background, so polling isn't held up by a slow
+/// channel.
+pub(super) fn alert_watchers(state: &AppState, journey_id: &str, changes: &[JourneyChange]) {
+    let Some(notifier) = state.notifier.clone() else {
+        return;
+    };
+    let alerts: Vec<Notification> = changes.iter().filter_map(JourneyChange::alert).collect();
+    if alerts.is_empty() {
+        return;
+    }
+    let channels: Vec<Channel> = state
+        .watches
+        .watching(journey_id)
+        .into_iter()
+        .flat_map(|watch| watch.channels)
+        .collect();
+    if channels.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        for alert in &alerts {
+            notifier.send_all(&channels, alert).await;
+        }
+    });
+}
+
+/// Check that a request may have alerts sent to `channels`: it needs an
+/// issued API key, and each channel must be enabled and allowed.
+async fn check_channels(
+    state: &AppState,
+    headers: &HeaderMap,
+    channels: &[Channel],
+) -> Result<(), AppError> {
+    if channels.is_empty() {
+        return Ok(());
+    }
+    if state.api_keys.authenticate(headers).is_none() {
+        return Err(AppError::Unauthorized {
+            message: "Alert channels need a valid API key".to_string(),
+        });
+    }
+    let Some(notifier) = &state.notifier else {
+        return Err(AppError::BadRequest {
+            message: "Alerts are not enabled on this server".to_string(),
+        });
+    };
+    for channel in channels {
+        notifier
+            .check(channel)
+            .await
+            .map_err(|e| AppError::BadRequest {
+                message: e.to_string(),
+            })?;
+    }
+    Ok(())
+}
+
+/// Watch a previously planned journey.
+///
+/// Returns 201 with the watch. Watching a journey already watched returns
+/// the existing watch, with its alert channels replaced if new ones are
+/// given. Giving channels without an issued API key is refused with 401.
+async fn create_watch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+    Json(req): Json<CreateWatchRequest>,
+) -> Result<Response, AppError> {
+    if let Some(channels) = &req.channels {
+        check_channels(&state, &headers, channels).await?;
+    }
+    if state.journeys.get(&req.journey_id).await.is_none() {
+        return Err(AppError::NotFound {
+            message: format!("Journey {} not found or expired", req.journey_id),
+        });
+    }
+
+    // Browsers without a watcher cookie are given one
+    let (owner, new_cookie) = match owner(&headers) {
+        Some(owner) => (owner, None),
+        None => {
+            let token = random_id();
+            (
+                WatchOwner::session(&token),
+                Some(set_watcher_cookie(&token, WATCHER_MAX_AGE_SECS)),
+            )
+        }
+    };
+
+    let mut watch = state
+        .watches
+        .create(owner, &req.journey_id, now(&state))
+        .await?;
+    if let Some(channels) = req.channels
+        && let Some(updated) = state.watches.set_channels(&watch.id, channels).await
+    {
+        watch = updated;
+    }
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let result = watch_result(&state, watch, &times, reason_translation(&state, &headers)).await;
+
+    let mut response = (StatusCode::CREATED, json_response(&result, &format)).into_response();
+    if let Some(cookie) = new_cookie {
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+    }
+    Ok(response)
+}
+
+/// List the caller's watches, each with its journey's latest state.
+async fn list_watches(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(format): Query<FormatQuery>,
+) -> Response {
+    let watches = match owner(&headers) {
+        Some(owner) => state.watches.list(&owner),
+        None => Vec::new(),
+    };
+
+    let times = TimeFormat::for_request(&headers, format.clock);
+    let translation = reason_translation(&state, &headers);
+    let results = futures::future::join_all(
+        watches
+            .into_iter()
+            .map(|watch| watch_result(&state, watch, &times, translation)),
+    )
+    .await;
+
+    json_response(&WatchListResponse { watches: results }, &format)
+}
+
+/// Stop watching a journey.
+async fn delete_watch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let removed = match owner(&headers) {
+        Some(owner) => state.watches.remove(&owner, &id).await,
+        None => false,
+    };
+    if !removed {
+        return Err(AppError::NotFound {
+            message: format!("Watch {id} not found"),
+        });
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheConfig, CachedDarwinClient};
+    use crate::clock::Clock;
+    use crate::darwin::{DarwinClientImpl, MockDarwinClient};
+    use crate::domain::{CallIndex, Leg, Segment};
+    use crate::notify::{Notifier, NotifierConfig};
+    use crate::planner::SearchConfig;
+    use crate::stations::{StationClient, StationClientConfig, StationNames};
+    use crate::testing::{ServiceBuilder, time};
+    use crate::walkable::london_connections;
+    use crate::web::ApiKeys;
+
+    fn state_at(now: &str) -> AppState {
+        let darwin = DarwinClientImpl::Mock(MockDarwinClient::new("data/mock_boards").unwrap());
+        let names = StationNames::empty(StationClient::new(StationClientConfig::new("")).unwrap());
+        AppState::new(
+            CachedDarwinClient::new(darwin, &CacheConfig::default()),
+            london_connections(),
+            SearchConfig::default(),
+            names,
+        )
+        .with_clock(Clock::frozen(time(now).to_datetime()))
+    }
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn owner_prefers_api_key_over_cookie() {
+        let key = header::HeaderName::from_static(API_KEY_HEADER);
+        let cookie = format!("tp_session=abc; {WATCHER_COOKIE}=token");
+
+        let both = headers(&[(key.clone(), "key"), (header::COOKIE, &cookie)]);
+        assert_eq!(owner(&both), Some(WatchOwner::api_key("key")));
+
+        let browser = headers(&[(header::COOKIE, &cookie)]);
+        assert_eq!(owner(&browser), Some(WatchOwner::session("token")));
+
+        let cleared = headers(&[(header::COOKIE, "tp_watcher=")]);
+        assert_eq!(owner(&cleared), None);
+        assert_eq!(owner(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn watcher_cookie_roundtrips() {
+        let token = random_id();
+        let set = set_watcher_cookie(&token, WATCHER_MAX_AGE_SECS);
+        let sent = set.to_str().unwrap().split(';').next().unwrap();
+        assert_eq!(
+            owner(&headers(&[(header::COOKIE, sent)])),
+            Some(WatchOwner::session(&token))
+        );
+    }
+
+    #[tokio::test]
+    async fn alert_channels_need_an_issued_key_and_an_enabled_channel() {
+        let state = state_at("10:00")
+            .with_api_keys(ApiKeys::parse("issued"))
+            .with_notifier(Notifier::new(NotifierConfig::default().with_webhooks(vec![])).unwrap());
+        let key = header::HeaderName::from_static(API_KEY_HEADER);
+        let webhook = |url: &str| Channel::Webhook {
+            url: url.to_string(),
+        };
+        let sms = Channel::Sms {
+            to: "+447700900123".to_string(),
+        };
+        let check = |headers: HeaderMap, channel: Channel| {
+            let state = state.clone();
+            async move { check_channels(&state, &headers, &[channel]).await }
+        };
+
+        let anonymous = HeaderMap::new();
+        let made_up = headers(&[(key.clone(), "made-up")]);
+        let issued = headers(&[(key.clone(), "issued")]);
+        let public = webhook("https://1.1.1.1/hook");
+
+        for headers in [anonymous, made_up] {
+            assert!(matches!(
+                check(headers, public.clone()).await,
+                Err(AppError::Unauthorized { .. })
+            ));
+        }
+        assert!(check(issued.clone(), public).await.is_ok());
+        assert!(matches!(
+            check(issued.clone(), webhook("https://169.254.169.254/")).await,
+            Err(AppError::BadRequest { .. })
+        ));
+        // SMS is only sent once the operator configures a gateway
+        assert!(matches!(
+            check(issued.clone(), sms.clone()).await,
+            Err(AppError::BadRequest { .. })
+        ));
+
+        // Clearing channels needs no key
+        assert!(check_channels(&state, &HeaderMap::new(), &[]).await.is_ok());
+
+        // Nor can channels be given where the server sends no alerts
+        let silent = state_at("10:00").with_api_keys(ApiKeys::parse("issued"));
+        assert!(matches!(
+            check_channels(&silent, &issued, &[sms]).await,
+            Err(AppError::BadRequest { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn journeys_are_archived_once_arrived() {
+        let service = ServiceBuilder::new("S1")
+            .call("PAD")
+            .dep("10:00")
+            .call("RDG")
+            .arr("10:25")
+            .expected_arr("10:31")
+            .build();
+        let leg = Leg::new(service, CallIndex(0), CallIndex(1)).unwrap();
+        let journey = Journey::new(vec![Segment::Train(leg)]).unwrap();
+        let owner = WatchOwner::session("token");
+        let watched = |state: AppState| {
+            let journey = journey.clone();
+            let owner = owner.clone();
+            async move {
+                let id = state.journeys.insert(journey).await;
+                state
+                    .watches
+                    .create(owner, &id, time("10:00"))
+                    .await
+                    .unwrap();
+                state
+            }
+        };
+
+        // Nobody lists their watches; the sweep archives them all the same
+        let travelling = watched(state_at("10:30")).await;
+        archive_arrived_watches(&travelling).await;
+        assert!(travelling.archive.list(&owner).await.is_empty());
+
+        let arrived = watched(state_at("10:31")).await;
+        archive_arrived_watches(&arrived).await;
+        let archived = arrived.archive.list(&owner).await;
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].destination().actual, Some(time("10:31")));
+        assert_eq!(archived[0].archived, time("10:31"));
+    }
+}